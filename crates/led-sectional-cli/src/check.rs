@@ -0,0 +1,149 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use led_sectional_core::chip::Chip;
+use led_sectional_core::config::{is_special_code, Config, Severity};
+use led_sectional_core::config_layer::ConfigLayer;
+
+/// The firmware's lowest config layer, so results match what the device loads.
+const EMBEDDED_DEFAULT: &str = include_str!("../../../cfg.toml.example");
+
+/// Load `path` the way the firmware does and print the resolved LED mapping
+/// and any diagnostics, checking pins and RMT channels against `chip`. Fails
+/// if the config wouldn't load or has errors.
+pub fn run(path: &str, chip: Chip) -> Result<(), String> {
+    let toml = std::fs::read_to_string(path).map_err(|e| format!("failed to read {path}: {e}"))?;
+    let mut layers = vec![
+        ConfigLayer::parse("embedded", EMBEDDED_DEFAULT)
+            .map_err(|e| format!("embedded default is invalid: {e}"))?,
+        ConfigLayer::parse(path, &toml).map_err(|e| format!("{path}: {e}"))?,
+    ];
+
+    // The device reads credentials from a secrets.toml beside the config
+    let secrets_path = Path::new(path).with_file_name("secrets.toml");
+    if let Ok(secrets) = std::fs::read_to_string(&secrets_path) {
+        let name = secrets_path.display().to_string();
+        let (layer, ignored) =
+            ConfigLayer::parse_secrets(&name, &secrets).map_err(|e| format!("{name}: {e}"))?;
+        for key in ignored {
+            println!("warning: {name}: [{key}] is ignored; only [wifi] is read");
+        }
+        layers.push(layer);
+    }
+
+    let config = Config::from_layers_for(&layers, chip).map_err(|e| format!("{path}: {e}"))?;
+    let names: Vec<&str> = layers.iter().map(|l| l.name.as_str()).collect();
+    println!("Chip: {}\nLayers: {}\n", chip.name(), names.join(" + "));
+    print!("{}", render_report(&config));
+
+    if config.has_errors() {
+        return Err(format!("{path} has errors"));
+    }
+    Ok(())
+}
+
+/// Summary, LED table, and diagnostics for a loaded config.
+fn render_report(config: &Config) -> String {
+    let mut out = String::new();
+    let settings = &config.settings;
+    let _ = writeln!(
+        out,
+        "{} LEDs on GPIO {}, {} METAR stations, ~{} of {} KB RAM",
+        config.num_leds(),
+        settings.data_pin,
+        config.metar_airport_codes().len(),
+        config.estimated_ram_bytes().div_ceil(1024),
+        settings.memory_budget_kb,
+    );
+    if let Some(preset) = &settings.preset {
+        let _ = writeln!(out, "Preset: {preset}");
+    }
+    if let Some(profile) = config.active_profile() {
+        let _ = writeln!(out, "Profile: {profile}");
+    }
+
+    let _ = writeln!(out, "\n LED  Code  Shows");
+    for (i, airport) in config.airports.iter().enumerate() {
+        let shows = if let Some(c) = airport.legend_color {
+            format!("legend color #{:02x}{:02x}{:02x}", c.r, c.g, c.b)
+        } else if is_special_code(&airport.code) {
+            "special code".to_string()
+        } else {
+            let mut shows = "METAR".to_string();
+            if let Some(fallback) = &airport.fallback {
+                let _ = write!(shows, ", fallback {fallback}");
+            }
+            shows
+        };
+        let _ = write!(out, "{i:>4}  {:<4}  {shows}", airport.code);
+        match &airport.name {
+            Some(name) => {
+                let _ = writeln!(out, " ({name})");
+            }
+            None => out.push('\n'),
+        }
+    }
+
+    if !config.diagnostics.is_empty() {
+        let errors = config
+            .diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count();
+        let _ = writeln!(
+            out,
+            "\n{} warnings, {} errors:",
+            config.diagnostics.len() - errors,
+            errors
+        );
+        for diagnostic in &config.diagnostics {
+            let _ = writeln!(out, "  {diagnostic}");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_lists_leds_and_diagnostics() {
+        let config = Config::from_toml(
+            r#"
+[settings]
+brightness = 0
+
+[[legend]]
+code = "HOME"
+color = [255, 0, 255]
+
+[[airports]]
+code = "KSFO"
+name = "San Francisco"
+fallback = "KOAK"
+
+[[airports]]
+code = "VFR"
+
+[[airports]]
+code = "HOME"
+"#,
+        )
+        .unwrap();
+        let report = render_report(&config);
+
+        assert!(report.starts_with("3 LEDs on GPIO 2, 2 METAR stations"));
+        assert!(report.contains("   0  KSFO  METAR, fallback KOAK (San Francisco)\n"));
+        assert!(report.contains("   1  VFR   special code\n"));
+        assert!(report.contains("   2  HOME  legend color #ff00ff\n"));
+        assert!(report.contains("1 warnings, 0 errors:"));
+        assert!(report.contains("  warning: settings.brightness: "));
+    }
+
+    #[test]
+    fn clean_config_has_no_diagnostics_section() {
+        let config = Config::from_toml("[[airports]]\ncode = \"KSFO\"\n").unwrap();
+        assert!(!render_report(&config).contains("warnings"));
+    }
+}
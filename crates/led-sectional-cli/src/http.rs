@@ -0,0 +1,19 @@
+use std::process::Command;
+
+const USER_AGENT: &str = "LED-Sectional-Rust/0.1";
+const TIMEOUT_SECS: &str = "15";
+
+/// Fetch a URL via the system `curl`, returning None if it is unavailable or fails.
+///
+/// Shelling out keeps the CLI free of a TLS stack; lookups are a convenience
+/// and every caller falls back to working offline.
+pub fn get(url: &str) -> Option<String> {
+    let output = Command::new("curl")
+        .args(["-sfL", "--max-time", TIMEOUT_SECS, "-A", USER_AGENT, url])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
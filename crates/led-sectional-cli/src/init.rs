@@ -0,0 +1,294 @@
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+
+use led_sectional_core::config::{is_special_code, Config};
+use led_sectional_core::station::{self, StationInfo};
+
+use crate::http;
+
+/// Answers collected by the setup wizard.
+#[derive(Debug)]
+struct Answers {
+    airports: Vec<String>,
+    num_leds: usize,
+    data_pin: u8,
+    brightness: u8,
+}
+
+/// Run the interactive setup wizard and write the result to `path`.
+pub fn run(path: &str) -> Result<(), String> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut output = io::stdout();
+
+    if std::path::Path::new(path).exists()
+        && !confirm(
+            &mut input,
+            &mut output,
+            &format!("{path} exists. Overwrite?"),
+            false,
+        )?
+    {
+        return Err("aborted".to_string());
+    }
+
+    let answers = ask(&mut input, &mut output)?;
+    let toml = render_config(&answers);
+
+    // Validate with the same parser the firmware uses before writing anything
+    Config::from_toml(&toml).map_err(|e| format!("generated config is invalid: {e}"))?;
+
+    std::fs::write(path, toml).map_err(|e| format!("failed to write {path}: {e}"))?;
+    println!("Wrote {path} ({} LEDs)", answers.num_leds);
+    Ok(())
+}
+
+fn ask(input: &mut impl BufRead, output: &mut impl Write) -> Result<Answers, String> {
+    writeln!(output, "LED Sectional setup\n").map_err(io_err)?;
+
+    let home = prompt(input, output, "Home airport (ICAO)", None)?.to_uppercase();
+    let home_station = lookup_stations(&[home.as_str()]).and_then(|s| s.into_iter().next());
+    match &home_station {
+        Some(s) => writeln!(
+            output,
+            "  Found {}: {}",
+            s.icao_id,
+            s.site.as_deref().unwrap_or("?")
+        ),
+        None => writeln!(output, "  Could not look up {home}; continuing offline"),
+    }
+    .map_err(io_err)?;
+
+    let mode = prompt(
+        input,
+        output,
+        "Airports by (r)adius or explicit (l)ist",
+        Some("l"),
+    )?;
+    let airports = if mode.starts_with('r') {
+        let station = home_station.ok_or("radius selection needs a successful station lookup")?;
+        let radius: f64 = parse(&prompt(input, output, "Radius (nm)", Some("50"))?)?;
+        let found = stations_within(&station, radius)
+            .ok_or("station lookup failed; try an explicit list instead")?;
+        for s in &found {
+            writeln!(
+                output,
+                "  {} {}",
+                s.icao_id,
+                s.site.as_deref().unwrap_or("")
+            )
+            .map_err(io_err)?;
+        }
+        found.into_iter().map(|s| s.icao_id).collect()
+    } else {
+        let default = home.clone();
+        let list = prompt(
+            input,
+            output,
+            "Airport codes (comma separated)",
+            Some(&default),
+        )?;
+        let codes = parse_code_list(&list);
+        confirm_codes(output, &codes)?;
+        codes
+    };
+
+    if airports.is_empty() {
+        return Err("no airports selected".to_string());
+    }
+
+    let default_leds = airports.len().to_string();
+    let num_leds = parse(&prompt(
+        input,
+        output,
+        "Number of LEDs on the strip",
+        Some(&default_leds),
+    )?)?;
+    let data_pin = parse(&prompt(input, output, "LED data GPIO pin", Some("2"))?)?;
+    let brightness = parse(&prompt(input, output, "Brightness (0-255)", Some("20"))?)?;
+
+    Ok(Answers {
+        airports,
+        num_leds,
+        data_pin,
+        brightness,
+    })
+}
+
+/// Print the site name for each code so typos stand out before writing.
+fn confirm_codes(output: &mut impl Write, codes: &[String]) -> Result<(), String> {
+    let real: Vec<&str> = codes
+        .iter()
+        .map(String::as_str)
+        .filter(|c| !is_special_code(c))
+        .collect();
+    let Some(stations) = lookup_stations(&real) else {
+        return Ok(());
+    };
+    for code in real {
+        match stations.iter().find(|s| s.icao_id == code) {
+            Some(s) => writeln!(output, "  {code}: {}", s.site.as_deref().unwrap_or("?")),
+            None => writeln!(output, "  {code}: not found (check for typos)"),
+        }
+        .map_err(io_err)?;
+    }
+    Ok(())
+}
+
+fn lookup_stations(codes: &[&str]) -> Option<Vec<StationInfo>> {
+    if codes.is_empty() {
+        return None;
+    }
+    let body = http::get(&station::build_station_info_url(codes))?;
+    station::parse_station_info(&body).ok()
+}
+
+/// Stations within `radius_nm` of `home`, nearest first.
+fn stations_within(home: &StationInfo, radius_nm: f64) -> Option<Vec<StationInfo>> {
+    let body = http::get(&station::build_station_bbox_url(
+        home.lat, home.lon, radius_nm,
+    ))?;
+    let mut stations: Vec<StationInfo> = station::parse_station_info(&body)
+        .ok()?
+        .into_iter()
+        .filter(|s| home.distance_nm(s) <= radius_nm)
+        .collect();
+    stations.sort_by(|a, b| home.distance_nm(a).total_cmp(&home.distance_nm(b)));
+    Some(stations)
+}
+
+fn parse_code_list(list: &str) -> Vec<String> {
+    list.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|c| !c.is_empty())
+        .map(str::to_uppercase)
+        .collect()
+}
+
+/// Render the answers as a cfg.toml. Extra LEDs are padded with NULL;
+/// airports beyond the LED count are dropped.
+fn render_config(answers: &Answers) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# LED Sectional Configuration (generated by `led-sectional init`)\n"
+    );
+    let _ = writeln!(out, "[settings]");
+    let _ = writeln!(out, "brightness = {}", answers.brightness);
+    let _ = writeln!(out, "data_pin = {}", answers.data_pin);
+
+    let codes = answers
+        .airports
+        .iter()
+        .map(String::as_str)
+        .chain(std::iter::repeat("NULL"))
+        .take(answers.num_leds);
+    for code in codes {
+        let _ = writeln!(out, "\n[[airports]]\ncode = \"{code}\"");
+    }
+    out
+}
+
+fn prompt(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    default: Option<&str>,
+) -> Result<String, String> {
+    match default {
+        Some(d) => write!(output, "{question} [{d}]: "),
+        None => write!(output, "{question}: "),
+    }
+    .map_err(io_err)?;
+    output.flush().map_err(io_err)?;
+
+    let mut line = String::new();
+    if input.read_line(&mut line).map_err(io_err)? == 0 {
+        return Err("unexpected end of input".to_string());
+    }
+    let answer = line.trim();
+    match (answer.is_empty(), default) {
+        (true, Some(d)) => Ok(d.to_string()),
+        (true, None) => Err(format!("{question} is required")),
+        (false, _) => Ok(answer.to_string()),
+    }
+}
+
+fn confirm(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    default: bool,
+) -> Result<bool, String> {
+    let answer = prompt(
+        input,
+        output,
+        question,
+        Some(if default { "y" } else { "n" }),
+    )?;
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
+
+fn parse<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+    s.trim().parse().map_err(|_| format!("invalid number: {s}"))
+}
+
+fn io_err(e: io::Error) -> String {
+    e.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answers(airports: &[&str], num_leds: usize) -> Answers {
+        Answers {
+            airports: airports.iter().map(|s| s.to_string()).collect(),
+            num_leds,
+            data_pin: 5,
+            brightness: 40,
+        }
+    }
+
+    #[test]
+    fn render_config_round_trips() {
+        let toml = render_config(&answers(&["KSFO", "KOAK"], 2));
+        let config = Config::from_toml(&toml).unwrap();
+        assert_eq!(config.settings.brightness, 40);
+        assert_eq!(config.settings.data_pin, 5);
+        assert_eq!(config.metar_airport_codes(), vec!["KSFO", "KOAK"]);
+    }
+
+    #[test]
+    fn render_config_pads_and_truncates() {
+        let config = Config::from_toml(&render_config(&answers(&["KSFO"], 3))).unwrap();
+        assert_eq!(config.num_leds(), 3);
+        assert_eq!(config.airports[2].code, "NULL");
+
+        let config = Config::from_toml(&render_config(&answers(&["KSFO", "KOAK"], 1))).unwrap();
+        assert_eq!(config.num_leds(), 1);
+    }
+
+    #[test]
+    fn parse_code_list_normalizes() {
+        assert_eq!(
+            parse_code_list("ksfo, KOAK  vfr"),
+            vec!["KSFO", "KOAK", "VFR"]
+        );
+        assert!(parse_code_list(" , ").is_empty());
+    }
+
+    #[test]
+    fn prompt_uses_default_on_empty_answer() {
+        let mut input = io::Cursor::new("\nKSFO\n");
+        let mut output = Vec::new();
+        assert_eq!(
+            prompt(&mut input, &mut output, "Pin", Some("2")).unwrap(),
+            "2"
+        );
+        assert_eq!(
+            prompt(&mut input, &mut output, "Home", None).unwrap(),
+            "KSFO"
+        );
+        assert!(prompt(&mut input, &mut output, "Home", None).is_err()); // EOF
+    }
+}
@@ -0,0 +1,67 @@
+mod check;
+mod http;
+mod init;
+mod status;
+
+use std::process::ExitCode;
+
+use led_sectional_core::chip::Chip;
+
+const USAGE: &str = "\
+Usage: led-sectional <command>
+
+Commands:
+  init [path]    Interactively create a cfg.toml (default: ./cfg.toml)
+  check [path] [--chip esp32c3|esp32s3]
+                 Validate a cfg.toml and show its LED mapping (default: ./cfg.toml)
+                 for a board's chip (default: esp32c3); also available as
+                 --check-config <path>
+  status <host>  Show a running map's WiFi, health, and LEDs (e.g. led-sectional.local)
+  help           Show this message";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("init") => init::run(args.get(1).map(String::as_str).unwrap_or("cfg.toml")),
+        Some("check") | Some("--check-config") => {
+            check_args(&args[1..]).and_then(|(path, chip)| check::run(path, chip))
+        }
+        Some("status") => match args.get(1) {
+            Some(host) => status::run(host),
+            None => Err(format!("status needs the map's hostname or address\n\n{USAGE}")),
+        },
+        Some("help") | Some("--help") | Some("-h") | None => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Some(other) => Err(format!("unknown command: {other}\n\n{USAGE}")),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Split `check`'s arguments into the config path and the chip to validate for.
+fn check_args(args: &[String]) -> Result<(&str, Chip), String> {
+    let mut path = None;
+    let mut chip = Chip::default();
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        match arg {
+            "--chip" => {
+                let name = args.next().ok_or("--chip needs esp32c3 or esp32s3")?;
+                chip = Chip::from_name(name)
+                    .ok_or_else(|| format!("unknown chip: {name} (esp32c3 or esp32s3)"))?;
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(format!("unexpected argument: {arg}\n\n{USAGE}")),
+        }
+    }
+    Ok((path.unwrap_or("cfg.toml"), chip))
+}
@@ -0,0 +1,122 @@
+use std::fmt::Write as _;
+
+use led_sectional_core::api::{DeviceHealth, MapResponse, Payload, StatusResponse};
+
+use crate::http;
+
+/// Ask a running map how it is doing and print its WiFi, health, and LEDs.
+/// `host` is a hostname such as `led-sectional.local` or a full URL.
+pub fn run(host: &str) -> Result<(), String> {
+    let base = if host.contains("://") {
+        host.trim_end_matches('/').to_string()
+    } else {
+        format!("http://{host}")
+    };
+    let status: StatusResponse = fetch(&base, "/status")?;
+    let health: DeviceHealth = fetch(&base, "/api/health")?;
+    let map: MapResponse = fetch(&base, "/api/map")?;
+    print!("{}", render_status(&status, &health, &map));
+    Ok(())
+}
+
+fn fetch<T: Payload>(base: &str, path: &str) -> Result<T, String> {
+    let url = format!("{base}{path}");
+    let json = http::get(&url).ok_or_else(|| format!("no answer from {url}"))?;
+    T::from_json(&json).map_err(|e| format!("{url}: {e}"))
+}
+
+/// Summary lines and the LED table.
+fn render_status(status: &StatusResponse, health: &DeviceHealth, map: &MapResponse) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "Firmware {}, last reset: {}",
+        health.build().summary(),
+        health.reset_reason
+    );
+    let signal = match status.rssi {
+        Some(rssi) if status.weak_signal => format!(", {rssi} dBm (weak)"),
+        Some(rssi) => format!(", {rssi} dBm"),
+        None => String::new(),
+    };
+    let _ = writeln!(
+        out,
+        "WiFi: {}{}{signal}",
+        status.wifi,
+        status.ssid.as_deref().map(|s| format!(" to {s}")).unwrap_or_default()
+    );
+    let _ = writeln!(
+        out,
+        "Heap: {} bytes free, {} at the lowest{}",
+        health.free_heap,
+        health.min_free_heap,
+        if health.low_memory { " (low; animations paused)" } else { "" }
+    );
+    match health.secs_since_fetch {
+        Some(secs) => {
+            let _ = writeln!(out, "Last weather fetch: {secs}s ago");
+        }
+        None => out.push_str("Last weather fetch: none yet\n"),
+    }
+    if health.safe_mode {
+        out.push_str("Safe mode: repeated crashes; running the default config\n");
+    }
+    if let Some(crash) = &health.last_crash {
+        let _ = writeln!(out, "Last crash: {}", crash.summary());
+    }
+    if let Some(error) = &map.error {
+        let _ = writeln!(out, "Last fetch failed: {error}");
+    }
+
+    out.push_str("\n LED  Airport  Category  Color\n");
+    for led in &map.leds {
+        let _ = writeln!(
+            out,
+            "{:>4}  {:<7}  {:<8}  {}",
+            led.led,
+            led.code,
+            led.category.as_deref().unwrap_or("-"),
+            led.color
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_what_the_device_reports() {
+        let status = StatusResponse::from_json(
+            r#"{"wifi":"connected","ssid":"Hangar","rssi":-78,"weak_signal":true}"#,
+        )
+        .unwrap();
+        let health = DeviceHealth::from_json(
+            r#"{"version":"0.1.0","git_hash":"1a2b3c4d5e6f",
+                "free_heap":142312,"min_free_heap":98740,"low_memory":true,
+                "rssi":-78,
+                "reset_reason":"brownout","secs_since_fetch":null,"safe_mode":true,
+                "last_crash":{"reset_reason":"task_watchdog","message":null,"location":null,
+                    "version":"0.1.0","uptime_secs":null,"at":null}}"#,
+        )
+        .unwrap();
+        let map = MapResponse::from_json(
+            r##"{"fetched_at":null,"error":"HTTP 503","leds":[
+                {"led":0,"code":"KSFO","name":"KSFO","color":"#00ff00","category":"VFR"},
+                {"led":2,"code":"VFR","name":"VFR","color":"#00ff00","category":null}]}"##,
+        )
+        .unwrap();
+
+        let text = render_status(&status, &health, &map);
+        assert!(text.contains("Firmware 0.1.0 (1a2b3c4d5e6f), last reset: brownout"), "{text}");
+        assert!(text.contains("WiFi: connected to Hangar, -78 dBm (weak)"));
+        assert!(text.contains("98740 at the lowest (low; animations paused)"));
+        assert!(text.contains("Last weather fetch: none yet"));
+        assert!(text.contains("Safe mode: repeated crashes"));
+        assert!(text.contains("Last crash: task_watchdog (firmware 0.1.0)"));
+        assert!(text.contains("Last fetch failed: HTTP 503"));
+        assert!(text.contains("   0  KSFO     VFR       #00ff00"));
+        assert!(text.contains("   2  VFR      -         #00ff00"));
+    }
+}
@@ -0,0 +1,183 @@
+//! The web UI's airport list editor: add, remove, and reorder the map's
+//! airports and legend codes from a browser, without editing `config.toml`.
+
+use crate::config::{is_special_code, looks_like_station_id, Airport, Config, SPECIAL_CODES};
+use crate::error::Result;
+
+/// Read a submitted airport list: codes separated by commas or whitespace,
+/// one LED each in order. Besides station ids and the built-in special
+/// codes, `config`'s own `[[legend]]` codes are accepted.
+pub fn parse_codes(text: &str, config: &Config) -> std::result::Result<Vec<String>, String> {
+    let codes: Vec<String> = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|code| !code.is_empty())
+        .map(str::to_ascii_uppercase)
+        .collect();
+    let known = |code: &str| {
+        looks_like_station_id(code)
+            || is_special_code(code)
+            || config.legend.iter().any(|e| e.code == code)
+    };
+    if let Some(bad) = codes.iter().find(|code| !known(code)) {
+        return Err(format!("\"{bad}\" is not an airport or legend code"));
+    }
+    if codes.is_empty() {
+        return Err("the map needs at least one airport".to_string());
+    }
+    Ok(codes)
+}
+
+/// `config` with `codes` as its airport list. An airport that stays keeps
+/// its name, fallback, and winds station; explicit `led` positions are
+/// dropped since the list order now sets them, and a preset is switched off
+/// so the list is used as given. The result is validated like a loaded file.
+pub fn with_airports(config: &Config, codes: &[String]) -> Result<Config> {
+    // Round-trip so an active profile's overrides aren't written as base
+    // settings
+    let mut edited: Config = toml::from_str(&config.to_toml()?)?;
+    let mut previous = std::mem::take(&mut edited.airports);
+    edited.airports = codes
+        .iter()
+        .map(|code| match previous.iter().position(|a| &a.code == code) {
+            Some(i) => Airport {
+                led: None,
+                ..previous.remove(i)
+            },
+            None => Airport {
+                code: code.clone(),
+                ..Default::default()
+            },
+        })
+        .collect();
+    edited.settings.preset = None;
+    Config::from_toml(&toml::to_string(&edited)?)
+}
+
+/// The editor page, starting from `config`'s airport list. Saving posts the
+/// codes to `/airports`, one per line.
+pub fn editor_page(config: &Config) -> String {
+    let airports: Vec<(&str, &str)> = config
+        .airports
+        .iter()
+        .map(|a| (a.code.as_str(), a.name.as_deref().unwrap_or("")))
+        .collect();
+    // Names go inside a <script>, so keep them from closing it
+    let airports = serde_json::to_string(&airports)
+        .expect("strings always serialize")
+        .replace('<', "\\u003c");
+    let options: String = SPECIAL_CODES
+        .iter()
+        .copied()
+        .chain(config.legend.iter().map(|e| e.code.as_str()))
+        .map(|code| format!("<option value=\"{code}\">"))
+        .collect();
+    HTML_EDITOR
+        .replace("{OPTIONS}", &options)
+        .replace("{AIRPORTS}", &airports)
+}
+
+const HTML_EDITOR: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width,initial-scale=1">
+<title>LED Sectional - Airports</title>
+<style>
+body{font-family:system-ui,sans-serif;background:#1a1a2e;color:#e0e0e0;margin:1rem;max-width:480px}
+h1{font-size:1.4rem;color:#a8d8ea}
+a{color:#a8d8ea}
+li{padding:.25rem 0;border-bottom:1px solid #333}
+li span{display:inline-block;min-width:12rem}
+small{color:#a0a0a0}
+button{background:#0f3460;color:#e0e0e0;border:1px solid #555;border-radius:4px;padding:.2rem .5rem;margin-left:.2rem}
+button:disabled{opacity:.4}
+input{background:#16213e;color:#e0e0e0;border:1px solid #555;border-radius:4px;padding:.3rem;width:8rem}
+#save{background:#e94560;border:none;padding:.5rem 1rem;margin-top:1rem}
+.error{color:#e94560}
+</style>
+</head>
+<body>
+<h1>Airports</h1>
+<p>One LED per entry, in order from the first LED on the strip. Legend codes such as VFR show a fixed color; NULL leaves an LED dark.</p>
+<ol id="list" start="0"></ol>
+<form onsubmit="return add()">
+<input id="code" list="codes" placeholder="KSFO" autocapitalize="characters">
+<datalist id="codes">{OPTIONS}</datalist>
+<button>Add</button>
+</form>
+<button id="save" onclick="save()">Save</button>
+<p id="msg"></p>
+<p><a href="/">Back to the map</a></p>
+<script>
+var list={AIRPORTS};
+function button(text,onclick,disabled){var b=document.createElement('button');b.textContent=text;b.onclick=onclick;b.disabled=disabled;return b}
+function swap(i,j){var t=list[i];list[i]=list[j];list[j]=t;draw()}
+function draw(){var ol=document.getElementById('list');ol.innerHTML='';list.forEach(function(a,i){var li=document.createElement('li');var s=document.createElement('span');s.textContent=a[0]+' ';if(a[1]){var n=document.createElement('small');n.textContent=a[1];s.appendChild(n)}li.appendChild(s);li.appendChild(button('↑',function(){swap(i,i-1)},i==0));li.appendChild(button('↓',function(){swap(i,i+1)},i==list.length-1));li.appendChild(button('✕',function(){list.splice(i,1);draw()},false));ol.appendChild(li)})}
+function add(){var f=document.getElementById('code');var c=f.value.trim().toUpperCase();if(c){list.push([c,'']);f.value='';draw()}return false}
+function save(){var m=document.getElementById('msg');m.className='';m.textContent='Saving…';fetch('/airports',{method:'POST',body:list.map(function(a){return a[0]}).join('\n')}).then(function(r){return r.text().then(function(t){m.textContent=t;m.className=r.ok?'':'error'})}).catch(function(){m.textContent='Could not reach the map';m.className='error'})}
+draw();
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[[airports]]
+code = "KHAF"
+led = 1
+name = "Half Moon Bay"
+fallback = "KSQL"
+
+[[airports]]
+code = "KSQL"
+
+[[legend]]
+code = "HOME"
+color = [255, 128, 0]
+"#;
+
+    #[test]
+    fn parse_accepts_legend_codes() {
+        let config = Config::from_toml(CONFIG).unwrap();
+        assert_eq!(
+            parse_codes("khaf, NULL\nhome vfr", &config).unwrap(),
+            ["KHAF", "NULL", "HOME", "VFR"]
+        );
+        assert!(parse_codes("KSFO SAN-JOSE", &config).unwrap_err().contains("SAN-JOSE"));
+        assert!(parse_codes(" ,\n", &config).is_err());
+    }
+
+    #[test]
+    fn edit_keeps_details_and_replaces_preset() {
+        let config = Config::from_toml(CONFIG).unwrap();
+        let codes: Vec<String> = ["KSFO", "HOME", "KHAF"].map(String::from).into();
+        let edited = with_airports(&config, &codes).unwrap();
+
+        let listed: Vec<&str> = edited.airports.iter().map(|a| a.code.as_str()).collect();
+        assert_eq!(listed, codes);
+        let khaf = &edited.airports[2];
+        assert_eq!(khaf.name.as_deref(), Some("Half Moon Bay"));
+        assert_eq!(khaf.fallback.as_deref(), Some("KSQL"));
+        assert_eq!(khaf.led, None);
+        assert!(edited.airports[1].legend_color.is_some());
+
+        let preset = Config::from_toml("[settings]\npreset = \"front_range\"\n").unwrap();
+        let edited = with_airports(&preset, &codes[..1]).unwrap();
+        assert_eq!(edited.settings.preset, None);
+        assert_eq!(edited.num_leds(), 1);
+    }
+
+    #[test]
+    fn page_embeds_the_list_safely() {
+        let mut config = Config::builder().airport("KSFO").build().unwrap();
+        config.airports[0].name = Some("</script>".to_string());
+        let page = editor_page(&config);
+        assert!(page.contains(r#"var list=[["KSFO","\u003c/script>"]];"#), "{page}");
+        assert!(page.contains("<option value=\"LTNG\">"));
+        assert_eq!(page.matches("</script>").count(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+//! Payloads of the device's JSON API. The firmware serializes these and
+//! host tools read them back, so both sides agree on field names and types.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::build_info::BuildInfo;
+use crate::crash::CrashReport;
+use crate::error::Result;
+use crate::metar::MetarReport;
+
+/// A JSON API payload.
+pub trait Payload: Serialize + DeserializeOwned {
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// `GET /status`: the WiFi connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusResponse {
+    /// Link state, such as `connected` or `reconnecting`.
+    pub wifi: String,
+    pub ssid: Option<String>,
+    /// Signal strength in dBm, while connected.
+    pub rssi: Option<i8>,
+    /// Below `settings.weak_signal_dbm`.
+    pub weak_signal: bool,
+}
+
+/// `GET /api/health`: what a watchdog script needs to decide whether to
+/// power-cycle the device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceHealth {
+    pub version: String,
+    /// Commit the firmware was built from; see [`BuildInfo`].
+    pub git_hash: Option<String>,
+    /// Unix seconds the firmware was built.
+    pub built_at: Option<i64>,
+    pub free_heap: u32,
+    /// Lowest free heap since boot.
+    pub min_free_heap: u32,
+    /// Largest single allocation that could succeed right now; a low value
+    /// with plenty free means the heap is fragmented.
+    #[serde(default)]
+    pub largest_free_block: u32,
+    /// Below `settings.low_heap_kb`, so the map is saving memory.
+    #[serde(default)]
+    pub low_memory: bool,
+    pub rssi: Option<i8>,
+    /// Why the chip last restarted, such as `power_on` or `panic`.
+    pub reset_reason: String,
+    /// Seconds since the last successful weather fetch; None before the
+    /// first.
+    pub secs_since_fetch: Option<u64>,
+    /// Started in safe mode after repeated crashes; see
+    /// [`crate::safe_mode`].
+    #[serde(default)]
+    pub safe_mode: bool,
+    /// The most recent crash, kept until the next one; None if it never
+    /// crashed since the last factory reset.
+    pub last_crash: Option<CrashReport>,
+}
+
+/// One lit LED in [`MapResponse`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AirportStatus {
+    pub led: usize,
+    pub code: String,
+    pub name: String,
+    /// What the LED shows, as `#rrggbb` before brightness scaling.
+    pub color: String,
+    /// Flight category, such as `VFR`; None without a report.
+    pub category: Option<String>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+}
+
+/// `GET /api/map`: the map as of the last fetch.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MapResponse {
+    /// Unix seconds, if the clock was set.
+    pub fetched_at: Option<i64>,
+    /// Why the last fetch failed, while the LEDs show the error color.
+    pub error: Option<String>,
+    pub leds: Vec<AirportStatus>,
+}
+
+/// One lit LED in [`MetarsResponse`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedMetar {
+    pub led: usize,
+    pub code: String,
+    /// The report the LED was painted from, in aviationweather.gov's field
+    /// names; None for legends and airports without one.
+    pub report: Option<MetarReport>,
+}
+
+/// `GET /api/metars`: the report behind each LED.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetarsResponse {
+    pub fetched_at: Option<i64>,
+    pub error: Option<String>,
+    pub metars: Vec<LedMetar>,
+}
+
+impl Payload for StatusResponse {}
+impl Payload for DeviceHealth {}
+
+impl DeviceHealth {
+    /// The running build, as the device reports it.
+    pub fn build(&self) -> BuildInfo {
+        BuildInfo {
+            version: self.version.clone(),
+            git_hash: self.git_hash.clone(),
+            built_at: self.built_at,
+        }
+    }
+}
+impl Payload for MapResponse {}
+impl Payload for MetarsResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_and_health_round_trip() {
+        let status = StatusResponse {
+            wifi: "connected".into(),
+            ssid: Some("Hangar \"B\"".into()),
+            rssi: Some(-62),
+            weak_signal: false,
+        };
+        let json = status.to_json();
+        assert!(json.contains(r#""ssid":"Hangar \"B\"""#), "{json}");
+        assert_eq!(StatusResponse::from_json(&json).unwrap(), status);
+
+        let health = DeviceHealth::from_json(
+            r#"{"version":"0.1.0","free_heap":142312,"min_free_heap":98740,"rssi":null,
+                "reset_reason":"power_on","secs_since_fetch":412}"#,
+        )
+        .unwrap();
+        assert_eq!(health.rssi, None);
+        assert_eq!(health.secs_since_fetch, Some(412));
+        // Older firmware doesn't send last_crash
+        assert_eq!(health.last_crash, None);
+        assert_eq!(DeviceHealth::from_json(&health.to_json()).unwrap(), health);
+    }
+
+    #[test]
+    fn missing_optional_fields_read_as_none() {
+        let map = MapResponse::from_json(
+            r##"{"leds":[{"led":0,"code":"KSFO","name":"KSFO","color":"#00ff00"}]}"##,
+        )
+        .unwrap();
+        assert_eq!(map.fetched_at, None);
+        assert_eq!(map.leds[0].category, None);
+        assert!(MapResponse::from_json(r#"{"leds":[{"led":0}]}"#).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+//! The web UI's static files. The firmware's build script gzips each one
+//! into the image, and the server sends it with `Content-Encoding: gzip`,
+//! so pages cost less flash and load faster as the UI grows. Pages filled in
+//! at request time stay inline strings. The gzip decoding here also serves
+//! the weather client, whose responses arrive gzipped.
+
+use crate::dashboard::PREVIEW_PAGE;
+use crate::error::{Error, Result};
+
+/// A file the web UI serves unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Asset {
+    pub path: &'static str,
+    pub content_type: &'static str,
+    pub body: &'static str,
+}
+
+/// Everything the firmware embeds compressed. Add new pages, stylesheets,
+/// and scripts here rather than serving them as strings.
+pub const ASSETS: &[Asset] = &[Asset {
+    path: "/map",
+    content_type: "text/html; charset=utf-8",
+    body: PREVIEW_PAGE,
+}];
+
+const GZIP_HEADER_LEN: usize = 10;
+const GZIP_TRAILER_LEN: usize = 8;
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+/// Flag bits RFC 1952 reserves; a member setting any is refused.
+const FRESERVED: u8 = 0xe0;
+
+/// Wrap a raw deflate stream of `original` in a gzip (RFC 1952) member,
+/// with no file name or timestamp so builds are reproducible.
+pub fn gzip_frame(deflated: &[u8], original: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(GZIP_HEADER_LEN + deflated.len() + GZIP_TRAILER_LEN);
+    // Magic, deflate, no flags, no mtime, best compression, unknown OS
+    out.extend_from_slice(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 2, 255]);
+    out.extend_from_slice(deflated);
+    out.extend_from_slice(&crc32(original).to_le_bytes());
+    out.extend_from_slice(&(original.len() as u32).to_le_bytes());
+    out
+}
+
+/// The deflate stream inside a gzip member, for inflating it again. None if
+/// the header is malformed.
+pub fn deflated(gzip: &[u8]) -> Option<&[u8]> {
+    split_member(gzip).ok().map(|(deflate, _)| deflate)
+}
+
+/// Decode a gzip (RFC 1952) member: check the header, inflate the deflate
+/// stream with `inflate`, and check the result against the CRC-32 and size
+/// in the trailer, so a truncated or corrupted body isn't taken as whole.
+/// The inflater is passed in so this crate needs no compression library.
+pub fn gunzip<E: std::fmt::Debug>(
+    gzip: &[u8],
+    inflate: impl FnOnce(&[u8]) -> std::result::Result<Vec<u8>, E>,
+) -> Result<Vec<u8>> {
+    let (deflate, trailer) = split_member(gzip)?;
+    let inflated = inflate(deflate).map_err(|e| Error::Gzip(format!("doesn't inflate: {e:?}")))?;
+    if trailer[..4] != crc32(&inflated).to_le_bytes() {
+        return Err(Error::Gzip("doesn't match its CRC-32".to_string()));
+    }
+    if trailer[4..] != (inflated.len() as u32).to_le_bytes() {
+        return Err(Error::Gzip("doesn't match its size".to_string()));
+    }
+    Ok(inflated)
+}
+
+/// Split a gzip member into its deflate stream and trailer, skipping the
+/// optional header fields and checking the header CRC if there is one.
+fn split_member(gzip: &[u8]) -> Result<(&[u8], &[u8])> {
+    let bad = |what: &str| Error::Gzip(what.to_string());
+    if gzip.len() < GZIP_HEADER_LEN + GZIP_TRAILER_LEN || gzip[..3] != [0x1f, 0x8b, 8] {
+        return Err(bad("isn't a gzip member"));
+    }
+    let flags = gzip[3];
+    if flags & FRESERVED != 0 {
+        return Err(bad("sets reserved flags"));
+    }
+    let (head, trailer) = gzip.split_at(gzip.len() - GZIP_TRAILER_LEN);
+    let mut pos = GZIP_HEADER_LEN;
+
+    if flags & FEXTRA != 0 {
+        let len = head
+            .get(pos..pos + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+            .ok_or_else(|| bad("is truncated in the extra field"))?;
+        pos += 2 + len;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = head
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or_else(|| bad("is truncated in a header string"))?;
+            pos += end + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        let crc = head.get(pos..pos + 2).ok_or_else(|| bad("is truncated in the header CRC"))?;
+        if crc != (crc32(&head[..pos]) as u16).to_le_bytes() {
+            return Err(bad("doesn't match its header CRC"));
+        }
+        pos += 2;
+    }
+    let deflate = head.get(pos..).ok_or_else(|| bad("is truncated in the header"))?;
+    Ok((deflate, trailer))
+}
+
+/// Whether an `Accept-Encoding` header allows gzip. Nearly every client
+/// sends it; `gzip;q=0` refuses it.
+pub fn accepts_gzip(header: Option<&str>) -> bool {
+    header.is_some_and(|header| {
+        header.split(',').any(|coding| {
+            let mut parts = coding.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default();
+            let refused = parts.any(|p| {
+                p.strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q <= 0.0)
+            });
+            (name.eq_ignore_ascii_case("gzip") || name == "*") && !refused
+        })
+    })
+}
+
+/// CRC-32 (IEEE), as the gzip trailer uses.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `data` as a single stored (uncompressed) deflate block.
+    fn stored(data: &[u8]) -> Vec<u8> {
+        let len = data.len() as u16;
+        let mut block = vec![1];
+        block.extend_from_slice(&len.to_le_bytes());
+        block.extend_from_slice(&(!len).to_le_bytes());
+        block.extend_from_slice(data);
+        block
+    }
+
+    #[test]
+    fn frame_has_header_and_trailer() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        let body = b"<p>hello</p>";
+        let gz = gzip_frame(&stored(body), body);
+        assert_eq!(&gz[..2], [0x1f, 0x8b]);
+        assert_eq!(gz[gz.len() - 4..], (body.len() as u32).to_le_bytes());
+        assert_eq!(gz[gz.len() - 8..gz.len() - 4], crc32(body).to_le_bytes());
+        assert_eq!(deflated(&gz).unwrap(), stored(body));
+        assert!(deflated(body).is_none());
+    }
+
+    /// Inflate the single stored block [`stored`] writes, like a real
+    /// inflater: an error if the stream ends early.
+    fn inflate_stored(deflate: &[u8]) -> std::result::Result<Vec<u8>, &'static str> {
+        let len = deflate.get(1..3).ok_or("short")?;
+        let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+        deflate.get(5..5 + len).map(<[u8]>::to_vec).ok_or("short")
+    }
+
+    /// A gzip member of `body` with `flags` and `fields` between the fixed
+    /// header and the deflate stream.
+    fn member(flags: u8, fields: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut gz = vec![0x1f, 0x8b, 8, flags, 0, 0, 0, 0, 0, 255];
+        gz.extend_from_slice(fields);
+        gz.extend_from_slice(&stored(body));
+        gz.extend_from_slice(&crc32(body).to_le_bytes());
+        gz.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        gz
+    }
+
+    const BODY: &[u8] = b"KSFO,VFR\nKOAK,MVFR\n";
+
+    #[test]
+    fn gunzip_skips_each_header_field() {
+        let plain = gzip_frame(&stored(BODY), BODY);
+        assert_eq!(gunzip(&plain, inflate_stored).unwrap(), BODY);
+
+        let extra = member(FEXTRA, &[3, 0, b'a', b'b', b'c'], BODY);
+        assert_eq!(gunzip(&extra, inflate_stored).unwrap(), BODY);
+        let name = member(FNAME, b"metars.csv\0", BODY);
+        assert_eq!(gunzip(&name, inflate_stored).unwrap(), BODY);
+        let comment = member(FCOMMENT, b"from the cache\0", BODY);
+        assert_eq!(gunzip(&comment, inflate_stored).unwrap(), BODY);
+        let both = member(FNAME | FCOMMENT, b"a\0b\0", BODY);
+        assert_eq!(deflated(&both).unwrap(), stored(BODY));
+
+        let header = [0x1f, 0x8b, 8, FHCRC, 0, 0, 0, 0, 0, 255];
+        let crc = (crc32(&header) as u16).to_le_bytes();
+        assert_eq!(gunzip(&member(FHCRC, &crc, BODY), inflate_stored).unwrap(), BODY);
+        let wrong = [crc[0] ^ 1, crc[1]];
+        assert!(gunzip(&member(FHCRC, &wrong, BODY), inflate_stored).is_err());
+    }
+
+    #[test]
+    fn gunzip_refuses_malformed_headers() {
+        assert!(gunzip(BODY, inflate_stored).is_err());
+        assert!(gunzip(&member(0x20, &[], BODY), inflate_stored).is_err());
+        // A name that runs into the trailer has no terminator
+        let unterminated = member(FNAME, b"metars.csv", b"");
+        assert!(deflated(&unterminated[..unterminated.len() - 5]).is_none());
+        let long_extra = member(FEXTRA, &[200, 0], BODY);
+        assert!(gunzip(&long_extra, inflate_stored).is_err());
+    }
+
+    #[test]
+    fn gunzip_catches_truncation_and_corruption() {
+        let gz = member(FNAME, b"metars.csv\0", BODY);
+        for len in 0..gz.len() {
+            assert!(gunzip(&gz[..len], inflate_stored).is_err(), "{len} bytes");
+        }
+
+        let mut bad_crc = gz.clone();
+        let at = bad_crc.len() - 8;
+        bad_crc[at] ^= 1;
+        assert!(gunzip(&bad_crc, inflate_stored).is_err());
+        let mut bad_size = gz.clone();
+        let at = bad_size.len() - 4;
+        bad_size[at] += 1;
+        assert!(gunzip(&bad_size, inflate_stored).is_err());
+        let mut bad_body = gz;
+        let at = bad_body.len() - 9;
+        bad_body[at] ^= 0x20;
+        assert!(gunzip(&bad_body, inflate_stored).is_err());
+    }
+
+    #[test]
+    fn gzip_negotiation() {
+        assert!(accepts_gzip(Some("gzip, deflate, br")));
+        assert!(accepts_gzip(Some("br;q=1.0, GZIP;q=0.5")));
+        assert!(accepts_gzip(Some("*")));
+        assert!(!accepts_gzip(Some("gzip;q=0, deflate")));
+        assert!(!accepts_gzip(Some("identity")));
+        assert!(!accepts_gzip(None));
+    }
+
+    #[test]
+    fn asset_paths_are_unique() {
+        for (i, asset) in ASSETS.iter().enumerate() {
+            assert!(asset.path.starts_with('/'), "{}", asset.path);
+            assert!(ASSETS[..i].iter().all(|a| a.path != asset.path), "{}", asset.path);
+            assert!(!asset.body.is_empty());
+        }
+    }
+}
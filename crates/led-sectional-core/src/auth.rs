@@ -0,0 +1,179 @@
+//! The credential guarding HTTP endpoints that change the device.
+//!
+//! Either a bearer token, for scripts, or a username and password, for
+//! browsers. A token also works as the password of HTTP Basic auth with any
+//! username, so a browser prompt can unlock a token-protected device too.
+
+use crate::error::{Error, Result};
+
+/// Sent as `WWW-Authenticate` with a 401, so browsers prompt for a login.
+pub const CHALLENGE: &str = "Basic realm=\"LED Sectional\"";
+const MAX_LEN: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credential {
+    Token(String),
+    Basic { username: String, password: String },
+}
+
+impl Credential {
+    /// Parse `user:password` for Basic auth, or anything without a colon as
+    /// a token. Surrounding whitespace is ignored.
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(Error::Credential("is empty"));
+        }
+        if text.len() > MAX_LEN {
+            return Err(Error::Credential("is too long"));
+        }
+        if text.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(Error::Credential("can't contain spaces"));
+        }
+        match text.split_once(':') {
+            Some(("", _)) => Err(Error::Credential("needs a username before the colon")),
+            Some((_, "")) => Err(Error::Credential("needs a password after the colon")),
+            Some((username, password)) => Ok(Self::Basic {
+                username: username.to_string(),
+                password: password.to_string(),
+            }),
+            None => Ok(Self::Token(text.to_string())),
+        }
+    }
+
+    /// The text form [`parse`](Self::parse) reads, for storing.
+    pub fn to_text(&self) -> String {
+        match self {
+            Self::Token(token) => token.clone(),
+            Self::Basic { username, password } => format!("{username}:{password}"),
+        }
+    }
+
+    /// Whether an `Authorization` header value grants access.
+    pub fn authorizes(&self, header: Option<&str>) -> bool {
+        let Some((scheme, value)) = header.and_then(|h| h.trim().split_once(' ')) else {
+            return false;
+        };
+        let value = value.trim();
+        if scheme.eq_ignore_ascii_case("bearer") {
+            return matches!(self, Self::Token(token) if constant_time_eq(token, value));
+        }
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return false;
+        }
+        let Some(decoded) = decode_base64(value).and_then(|b| String::from_utf8(b).ok()) else {
+            return false;
+        };
+        let Some((user, pass)) = decoded.split_once(':') else {
+            return false;
+        };
+        match self {
+            Self::Token(token) => constant_time_eq(token, pass),
+            Self::Basic { username, password } => {
+                // Both compared, so timing doesn't reveal which was wrong
+                constant_time_eq(username, user) & constant_time_eq(password, pass)
+            }
+        }
+    }
+}
+
+/// Compare without stopping at the first difference, so response times
+/// don't leak how much of a guess was right.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut diff = a.len() ^ b.len();
+    for (i, x) in a.iter().enumerate() {
+        diff |= usize::from(x ^ b.get(i).copied().unwrap_or(!x));
+    }
+    diff == 0
+}
+
+/// Standard base64 with padding, as HTTP Basic auth uses.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some(u32::from(c - b'A')),
+            b'a'..=b'z' => Some(u32::from(c - b'a') + 26),
+            b'0'..=b'9' => Some(u32::from(c - b'0') + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+    let bytes = text.as_bytes();
+    if !bytes.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(bytes.len() / 4 * 3);
+    for (i, chunk) in bytes.chunks(4).enumerate() {
+        let last = i == bytes.len() / 4 - 1;
+        let pad = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if pad > 2 || (pad > 0 && !last) {
+            return None;
+        }
+        let mut n = 0u32;
+        for &c in &chunk[..4 - pad] {
+            n = (n << 6) | value(c)?;
+        }
+        n <<= 6 * pad as u32;
+        out.extend_from_slice(&n.to_be_bytes()[1..4 - pad]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_token_or_user_password() {
+        assert_eq!(Credential::parse(" s3cret\n").unwrap(), Credential::Token("s3cret".into()));
+        assert_eq!(
+            Credential::parse("admin:pa:ss").unwrap(),
+            Credential::Basic { username: "admin".into(), password: "pa:ss".into() }
+        );
+        assert!(Credential::parse("").is_err());
+        assert!(Credential::parse(":pass").is_err());
+        assert!(Credential::parse("admin:").is_err());
+        assert!(Credential::parse("two words").is_err());
+        let basic = Credential::parse("admin:pa:ss").unwrap();
+        assert_eq!(Credential::parse(&basic.to_text()).unwrap(), basic);
+    }
+
+    #[test]
+    fn token_accepts_bearer_or_basic_password() {
+        let token = Credential::Token("s3cret".into());
+        assert!(token.authorizes(Some("Bearer s3cret")));
+        assert!(token.authorizes(Some("bearer  s3cret ")));
+        // "anyone:s3cret"
+        assert!(token.authorizes(Some("Basic YW55b25lOnMzY3JldA==")));
+        assert!(!token.authorizes(Some("Bearer s3cre")));
+        assert!(!token.authorizes(Some("Bearer s3crets")));
+        assert!(!token.authorizes(Some("s3cret")));
+        assert!(!token.authorizes(None));
+    }
+
+    #[test]
+    fn basic_checks_username_and_password() {
+        let basic = Credential::parse("admin:hunter2").unwrap();
+        // "admin:hunter2"
+        assert!(basic.authorizes(Some("Basic YWRtaW46aHVudGVyMg==")));
+        // "admin:hunter3"
+        assert!(!basic.authorizes(Some("Basic YWRtaW46aHVudGVyMw==")));
+        // "root:hunter2"
+        assert!(!basic.authorizes(Some("Basic cm9vdDpodW50ZXIy")));
+        assert!(!basic.authorizes(Some("Bearer hunter2")));
+        assert!(!basic.authorizes(Some("Basic not-base64")));
+    }
+
+    #[test]
+    fn base64_decodes_with_and_without_padding() {
+        assert_eq!(decode_base64("TWFu").unwrap(), b"Man");
+        assert_eq!(decode_base64("TWE=").unwrap(), b"Ma");
+        assert_eq!(decode_base64("TQ==").unwrap(), b"M");
+        assert_eq!(decode_base64("").unwrap(), b"");
+        assert!(decode_base64("TQ=").is_none());
+        assert!(decode_base64("TQ==TWFu").is_none());
+        assert!(decode_base64("T===").is_none());
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+/// Exponential backoff for retrying a failing operation, such as
+/// reconnecting to WiFi: `initial`, doubling per failure up to `max`.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    failures: u32,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max: max.max(initial),
+            failures: 0,
+        }
+    }
+
+    /// Record a failure and return how long to wait before the next attempt.
+    pub fn next_delay(&mut self) -> Duration {
+        let factor = 1u32.checked_shl(self.failures).unwrap_or(u32::MAX);
+        self.failures = self.failures.saturating_add(1);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+
+    /// Number of failures since the last `reset`.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Start over from `initial` after a success.
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_up_to_max_and_resets() {
+        let mut backoff = Backoff::new(Duration::from_secs(5), Duration::from_secs(60));
+        let delays: Vec<u64> = (0..6).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, vec![5, 10, 20, 40, 60, 60]);
+        assert_eq!(backoff.failures(), 6);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn many_failures_do_not_overflow() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(300));
+        for _ in 0..100 {
+            assert!(backoff.next_delay() <= Duration::from_secs(300));
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! What firmware build is running: the crate version plus the git commit and
+//! time the firmware's build script recorded, for the boot log,
+//! `GET /api/health`, the mDNS TXT record and the update checker's requests.
+
+use crate::clock::TimeZone;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub version: String,
+    /// Short commit hash, ending in `-dirty` if there were uncommitted
+    /// changes; None if built outside a git checkout.
+    pub git_hash: Option<String>,
+    /// Unix seconds; None if unknown.
+    pub built_at: Option<i64>,
+}
+
+impl BuildInfo {
+    /// From the strings the build embeds, where empty means unknown.
+    pub fn new(version: &str, git_hash: &str, built_at: &str) -> Self {
+        let git_hash = git_hash.trim();
+        Self {
+            version: version.to_string(),
+            git_hash: (!git_hash.is_empty()).then(|| git_hash.to_string()),
+            built_at: built_at.trim().parse().ok(),
+        }
+    }
+
+    /// Like `0.3.0 (1a2b3c4d5e6f, built 2026-10-14 09:30 UTC)`, leaving out
+    /// what isn't known.
+    pub fn summary(&self) -> String {
+        let mut details = Vec::new();
+        if let Some(hash) = &self.git_hash {
+            details.push(hash.clone());
+        }
+        if let Some(secs) = self.built_at {
+            let t = TimeZone::UTC.to_local(secs);
+            details.push(format!(
+                "built {:04}-{:02}-{:02} {:02}:{:02} UTC",
+                t.year, t.month, t.day, t.hour, t.minute
+            ));
+        }
+        if details.is_empty() {
+            self.version.clone()
+        } else {
+            format!("{} ({})", self.version, details.join(", "))
+        }
+    }
+
+    /// The User-Agent for update checks, so a manifest server can tell
+    /// which builds are asking.
+    pub fn user_agent(&self) -> String {
+        match &self.git_hash {
+            Some(hash) => format!("LED-Sectional-Rust/{} ({})", self.version, hash),
+            None => format!("LED-Sectional-Rust/{}", self.version),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_names_commit_and_time() {
+        let build = BuildInfo::new("0.3.0", "1a2b3c4d5e6f-dirty\n", "1760434200");
+        assert_eq!(build.summary(), "0.3.0 (1a2b3c4d5e6f-dirty, built 2025-10-14 09:30 UTC)");
+        assert_eq!(build.user_agent(), "LED-Sectional-Rust/0.3.0 (1a2b3c4d5e6f-dirty)");
+    }
+
+    #[test]
+    fn unknown_parts_are_left_out() {
+        let build = BuildInfo::new("0.3.0", "", "");
+        assert_eq!(build.git_hash, None);
+        assert_eq!(build.built_at, None);
+        assert_eq!(build.summary(), "0.3.0");
+        assert_eq!(build.user_agent(), "LED-Sectional-Rust/0.3.0");
+        assert_eq!(BuildInfo::new("0.3.0", "abc", "soon").summary(), "0.3.0 (abc)");
+    }
+}
@@ -0,0 +1,103 @@
+//! Hold-to-reset handling for the setup button: a quick press starts WPS
+//! pairing during setup, a medium hold forgets the WiFi networks, a long hold
+//! erases everything.
+
+use std::time::Duration;
+
+/// Released before this counts as a quick press.
+pub const QUICK_PRESS_MAX: Duration = Duration::from_secs(1);
+/// Hold this long, then release, to forget WiFi and start the setup portal.
+pub const FORGET_WIFI_HOLD: Duration = Duration::from_secs(5);
+/// Hold this long to factory reset; it fires without waiting for release.
+pub const FACTORY_RESET_HOLD: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoldAction {
+    QuickPress,
+    ForgetWifi,
+    FactoryReset,
+}
+
+/// Tracks how long the button has been held from periodic samples.
+///
+/// The WiFi wipe happens on release so that holding on towards a factory
+/// reset doesn't trigger it first.
+#[derive(Debug, Clone, Default)]
+pub struct HoldTracker {
+    held: Duration,
+}
+
+impl HoldTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one sample taken `elapsed` after the previous one.
+    pub fn sample(&mut self, pressed: bool, elapsed: Duration) -> Option<HoldAction> {
+        if pressed {
+            self.held += elapsed;
+            return (self.held >= FACTORY_RESET_HOLD).then(|| {
+                self.held = Duration::ZERO;
+                HoldAction::FactoryReset
+            });
+        }
+        match std::mem::take(&mut self.held) {
+            held if held >= FORGET_WIFI_HOLD => Some(HoldAction::ForgetWifi),
+            held if !held.is_zero() && held < QUICK_PRESS_MAX => Some(HoldAction::QuickPress),
+            _ => None,
+        }
+    }
+
+    /// How long the current press has lasted.
+    pub fn held(&self) -> Duration {
+        self.held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(100);
+
+    fn hold(tracker: &mut HoldTracker, duration: Duration) -> Option<HoldAction> {
+        let mut action = None;
+        for _ in 0..duration.as_millis() / TICK.as_millis() {
+            action = action.or(tracker.sample(true, TICK));
+        }
+        action
+    }
+
+    #[test]
+    fn short_press_does_nothing() {
+        let mut tracker = HoldTracker::new();
+        assert_eq!(hold(&mut tracker, Duration::from_secs(2)), None);
+        assert_eq!(tracker.sample(false, TICK), None);
+        assert_eq!(tracker.held(), Duration::ZERO);
+    }
+
+    #[test]
+    fn quick_press_reported_on_release() {
+        let mut tracker = HoldTracker::new();
+        assert_eq!(tracker.sample(false, TICK), None);
+        assert_eq!(hold(&mut tracker, Duration::from_millis(300)), None);
+        assert_eq!(tracker.sample(false, TICK), Some(HoldAction::QuickPress));
+        assert_eq!(tracker.sample(false, TICK), None);
+    }
+
+    #[test]
+    fn medium_hold_forgets_wifi_on_release() {
+        let mut tracker = HoldTracker::new();
+        assert_eq!(hold(&mut tracker, Duration::from_secs(6)), None);
+        assert_eq!(tracker.sample(false, TICK), Some(HoldAction::ForgetWifi));
+        assert_eq!(tracker.sample(false, TICK), None);
+    }
+
+    #[test]
+    fn long_hold_factory_resets_without_release() {
+        let mut tracker = HoldTracker::new();
+        assert_eq!(hold(&mut tracker, FACTORY_RESET_HOLD), Some(HoldAction::FactoryReset));
+        // Still held afterwards: no second action on release
+        assert_eq!(tracker.sample(false, TICK), None);
+    }
+}
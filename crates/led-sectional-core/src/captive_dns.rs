@@ -0,0 +1,125 @@
+//! DNS answers for the provisioning captive portal: every A query resolves
+//! to the access point itself, so phones probing for internet access land on
+//! the setup page.
+
+use std::net::Ipv4Addr;
+
+const HEADER_LEN: usize = 12;
+const TYPE_A: u16 = 1;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// Short, so clients re-resolve soon after joining a real network.
+const TTL_SECS: u32 = 10;
+
+/// Build the reply to a DNS `query`, answering A (and ANY) questions with
+/// `ip` and other types with no records. Returns `None` for packets that
+/// aren't a single-question standard query, which should be dropped.
+pub fn response(query: &[u8], ip: Ipv4Addr) -> Option<Vec<u8>> {
+    if query.len() < HEADER_LEN {
+        return None;
+    }
+    let flags = u16::from_be_bytes([query[2], query[3]]);
+    let is_response = flags & 0x8000 != 0;
+    let opcode = (flags >> 11) & 0xf;
+    let questions = u16::from_be_bytes([query[4], query[5]]);
+    if is_response || opcode != 0 || questions != 1 {
+        return None;
+    }
+
+    let question_end = question_end(query)?;
+    let qtype = u16::from_be_bytes([query[question_end - 4], query[question_end - 3]]);
+    let qclass = u16::from_be_bytes([query[question_end - 2], query[question_end - 1]]);
+    let answer = (qtype == TYPE_A || qtype == TYPE_ANY) && qclass == CLASS_IN;
+
+    let mut out = Vec::with_capacity(question_end + 16);
+    out.extend_from_slice(&query[..2]);
+    // Response, authoritative, recursion-desired copied, no error
+    out.extend_from_slice(&(0x8400 | (flags & 0x0100)).to_be_bytes());
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&u16::from(answer).to_be_bytes());
+    out.extend_from_slice(&[0, 0, 0, 0]);
+    // Only the question is echoed; any EDNS record after it is dropped
+    out.extend_from_slice(&query[HEADER_LEN..question_end]);
+    if answer {
+        // Name: pointer to the question at offset 12
+        out.extend_from_slice(&[0xc0, 0x0c]);
+        out.extend_from_slice(&TYPE_A.to_be_bytes());
+        out.extend_from_slice(&CLASS_IN.to_be_bytes());
+        out.extend_from_slice(&TTL_SECS.to_be_bytes());
+        out.extend_from_slice(&4u16.to_be_bytes());
+        out.extend_from_slice(&ip.octets());
+    }
+    Some(out)
+}
+
+/// Offset just past the first question's type and class.
+fn question_end(query: &[u8]) -> Option<usize> {
+    let mut pos = HEADER_LEN;
+    loop {
+        let len = *query.get(pos)? as usize;
+        pos += 1;
+        if len == 0 {
+            break;
+        }
+        // Compression pointers don't appear in the question of a query
+        if len & 0xc0 != 0 {
+            return None;
+        }
+        pos += len;
+    }
+    let end = pos + 4;
+    (end <= query.len()).then_some(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AP: Ipv4Addr = Ipv4Addr::new(192, 168, 4, 1);
+
+    fn query(name: &str, qtype: u16) -> Vec<u8> {
+        let mut q = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            q.push(label.len() as u8);
+            q.extend_from_slice(label.as_bytes());
+        }
+        q.push(0);
+        q.extend_from_slice(&qtype.to_be_bytes());
+        q.extend_from_slice(&CLASS_IN.to_be_bytes());
+        q
+    }
+
+    #[test]
+    fn a_query_resolves_to_access_point() {
+        let q = query("connectivitycheck.gstatic.com", TYPE_A);
+        let r = response(&q, AP).unwrap();
+        assert_eq!(&r[..2], &[0x12, 0x34]);
+        assert_eq!(&r[2..4], &[0x85, 0x00]);
+        assert_eq!(&r[6..8], &[0, 1]);
+        assert_eq!(&r[HEADER_LEN..q.len()], &q[HEADER_LEN..]);
+        assert_eq!(&r[r.len() - 4..], &[192, 168, 4, 1]);
+    }
+
+    #[test]
+    fn other_types_get_no_answer() {
+        let q = query("captive.apple.com", 28);
+        let r = response(&q, AP).unwrap();
+        assert_eq!(&r[6..8], &[0, 0]);
+        assert_eq!(r.len(), q.len());
+    }
+
+    #[test]
+    fn malformed_packets_are_dropped() {
+        let q = query("example.com", TYPE_A);
+        assert!(response(&q[..8], AP).is_none());
+        assert!(response(&q[..q.len() - 2], AP).is_none());
+
+        let mut reply = q.clone();
+        reply[2] |= 0x80;
+        assert!(response(&reply, AP).is_none());
+
+        let mut two_questions = q;
+        two_questions[5] = 2;
+        assert!(response(&two_questions, AP).is_none());
+    }
+}
@@ -0,0 +1,112 @@
+//! The ESP32 variants the firmware builds for, and what differs between them
+//! that config validation needs: usable pins, the BOOT button, CPU clocks
+//! and RMT channels. The `esp32s3` feature, which the firmware's own
+//! `esp32s3` feature turns on, makes the S3 the [`Chip::TARGET`]; without it
+//! the target is the ESP32-C3.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip {
+    Esp32C3,
+    Esp32S3,
+}
+
+impl Chip {
+    /// The chip this build validates configs for.
+    #[cfg(not(feature = "esp32s3"))]
+    pub const TARGET: Chip = Chip::Esp32C3;
+    #[cfg(feature = "esp32s3")]
+    pub const TARGET: Chip = Chip::Esp32S3;
+
+    /// Parse a name like `esp32s3` or `ESP32-S3`, as the CLI takes it.
+    pub fn from_name(name: &str) -> Option<Chip> {
+        match name.to_ascii_lowercase().replace('-', "").as_str() {
+            "esp32c3" => Some(Self::Esp32C3),
+            "esp32s3" => Some(Self::Esp32S3),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Esp32C3 => "ESP32-C3",
+            Self::Esp32S3 => "ESP32-S3",
+        }
+    }
+
+    /// Whether GPIO `pin` is free for the strip, buttons and peripherals.
+    /// S3 modules use GPIO26-32 for flash, and 33-37 too with octal PSRAM,
+    /// so those are left out.
+    pub fn has_gpio(self, pin: u8) -> bool {
+        match self {
+            Self::Esp32C3 => pin <= 21,
+            Self::Esp32S3 => pin <= 21 || (38..=48).contains(&pin),
+        }
+    }
+
+    /// The highest pin [`Chip::has_gpio`] allows.
+    pub fn max_gpio(self) -> u8 {
+        match self {
+            Self::Esp32C3 => 21,
+            Self::Esp32S3 => 48,
+        }
+    }
+
+    /// The pins [`Chip::has_gpio`] allows, for messages.
+    pub fn gpio_ranges(self) -> &'static str {
+        match self {
+            Self::Esp32C3 => "GPIO0-21",
+            Self::Esp32S3 => "GPIO0-21 and 38-48",
+        }
+    }
+
+    /// The strapping pin dev boards wire their BOOT button to.
+    pub fn boot_button(self) -> u8 {
+        match self {
+            Self::Esp32C3 => 9,
+            Self::Esp32S3 => 0,
+        }
+    }
+
+    /// CPU clocks power management can switch between, slowest first.
+    pub fn cpu_mhz(self) -> &'static [u32] {
+        match self {
+            Self::Esp32C3 => &[40, 80, 160],
+            Self::Esp32S3 => &[40, 80, 160, 240],
+        }
+    }
+
+    /// How many RMT channels can transmit, numbered from 0.
+    pub fn rmt_tx_channels(self) -> u8 {
+        match self {
+            Self::Esp32C3 => 2,
+            Self::Esp32S3 => 4,
+        }
+    }
+}
+
+impl Default for Chip {
+    fn default() -> Self {
+        Self::TARGET
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn s3_skips_the_flash_pins() {
+        assert!(Chip::Esp32C3.has_gpio(21));
+        assert!(!Chip::Esp32C3.has_gpio(22));
+        assert!(Chip::Esp32S3.has_gpio(48));
+        assert!(!Chip::Esp32S3.has_gpio(30));
+        assert!(!Chip::Esp32S3.has_gpio(49));
+    }
+
+    #[test]
+    fn names_parse() {
+        assert_eq!(Chip::from_name("esp32s3"), Some(Chip::Esp32S3));
+        assert_eq!(Chip::from_name("ESP32-C3"), Some(Chip::Esp32C3));
+        assert_eq!(Chip::from_name("esp8266"), None);
+    }
+}
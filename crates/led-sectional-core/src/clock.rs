@@ -0,0 +1,476 @@
+//! Local wall-clock time from a POSIX `TZ` string, for scheduled dimming,
+//! quiet hours, and log timestamps.
+//!
+//! The conversion is done here rather than through libc so schedules can be
+//! tested on the host. Only the `Mm.w.d` DST rule form is supported, which
+//! covers the rules in common use.
+
+/// A broken-down local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTime {
+    pub year: i32,
+    /// 1-12.
+    pub month: u8,
+    /// 1-31.
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// 0 = Sunday.
+    pub weekday: u8,
+    /// Whether daylight saving time is in effect.
+    pub dst: bool,
+}
+
+impl LocalTime {
+    /// Minutes since local midnight, for comparing against schedules.
+    pub fn minute_of_day(&self) -> u16 {
+        self.hour as u16 * 60 + self.minute as u16
+    }
+}
+
+/// A daily span of local time such as `22:00-07:00`, which may run past
+/// midnight; for quiet hours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    /// Minutes since midnight; the start is included, the end isn't.
+    start: u16,
+    end: u16,
+}
+
+impl TimeWindow {
+    /// Parse `HH:MM-HH:MM` in 24-hour time. None if malformed.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (start, end) = text.split_once('-')?;
+        Some(Self {
+            start: parse_hh_mm(start.trim())?,
+            end: parse_hh_mm(end.trim())?,
+        })
+    }
+
+    /// Whether `t` falls inside; a window that starts and ends at the same
+    /// time is empty.
+    pub fn contains(&self, t: &LocalTime) -> bool {
+        let minute = t.minute_of_day();
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl std::fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+fn parse_hh_mm(text: &str) -> Option<u16> {
+    let (hour, minute) = text.split_once(':')?;
+    let digits = |s: &str| !s.is_empty() && s.len() <= 2 && s.bytes().all(|b| b.is_ascii_digit());
+    if !digits(hour) || minute.len() != 2 || !digits(minute) {
+        return None;
+    }
+    let (hour, minute) = (hour.parse::<u16>().ok()?, minute.parse::<u16>().ok()?);
+    (hour < 24 && minute < 60).then_some(hour * 60 + minute)
+}
+
+/// A source of local time. Returns None until the clock has been set (e.g.
+/// before the first SNTP sync).
+pub trait LocalClock {
+    fn now(&self) -> Option<LocalTime>;
+}
+
+/// A clock stuck at a given time, for tests, simulators, and demos.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FixedClock(pub Option<LocalTime>);
+
+impl LocalClock for FixedClock {
+    fn now(&self) -> Option<LocalTime> {
+        self.0
+    }
+}
+
+/// When a DST transition happens: `Mm.w.d/time` in POSIX terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TransitionRule {
+    month: u8,
+    /// 1-5, where 5 means the last such weekday of the month.
+    week: u8,
+    weekday: u8,
+    /// Local time of day of the transition, in seconds.
+    time_secs: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DstRule {
+    offset_secs: i64,
+    start: TransitionRule,
+    end: TransitionRule,
+}
+
+/// A time zone parsed from a POSIX `TZ` string such as `UTC0` or
+/// `PST8PDT,M3.2.0,M11.1.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeZone {
+    /// Standard time offset east of UTC, in seconds.
+    offset_secs: i64,
+    dst: Option<DstRule>,
+}
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        Self::UTC
+    }
+}
+
+impl TimeZone {
+    pub const UTC: TimeZone = TimeZone {
+        offset_secs: 0,
+        dst: None,
+    };
+
+    /// Parse a POSIX `TZ` string. Returns None if it is malformed or uses an
+    /// unsupported rule form.
+    pub fn parse(tz: &str) -> Option<TimeZone> {
+        let mut p = Parser(tz);
+        p.name()?;
+        // POSIX offsets are west of UTC, so "EST5" is UTC-5
+        let offset_secs = -p.offset()?;
+        if p.is_empty() {
+            return Some(TimeZone {
+                offset_secs,
+                dst: None,
+            });
+        }
+
+        p.name()?;
+        let dst_offset_secs = if p.peek() == Some(',') {
+            offset_secs + 3600
+        } else {
+            -p.offset()?
+        };
+        p.expect(',')?;
+        let start = p.rule()?;
+        p.expect(',')?;
+        let end = p.rule()?;
+        if !p.is_empty() {
+            return None;
+        }
+        Some(TimeZone {
+            offset_secs,
+            dst: Some(DstRule {
+                offset_secs: dst_offset_secs,
+                start,
+                end,
+            }),
+        })
+    }
+
+    /// Convert a Unix timestamp to local time.
+    pub fn to_local(&self, unix_secs: i64) -> LocalTime {
+        let (offset, dst) = match &self.dst {
+            Some(rule) if self.in_dst(rule, unix_secs) => (rule.offset_secs, true),
+            _ => (self.offset_secs, false),
+        };
+        let local = unix_secs + offset;
+        let days = local.div_euclid(86_400);
+        let secs = local.rem_euclid(86_400);
+        let (year, month, day) = civil_from_days(days);
+        LocalTime {
+            year,
+            month,
+            day,
+            hour: (secs / 3600) as u8,
+            minute: (secs / 60 % 60) as u8,
+            second: (secs % 60) as u8,
+            weekday: weekday_from_days(days),
+            dst,
+        }
+    }
+
+    fn in_dst(&self, rule: &DstRule, unix_secs: i64) -> bool {
+        let year = civil_from_days((unix_secs + self.offset_secs).div_euclid(86_400)).0;
+        // Start is given in standard time, end in daylight time
+        let start = transition_local_secs(year, &rule.start) - self.offset_secs;
+        let end = transition_local_secs(year, &rule.end) - rule.offset_secs;
+        if start < end {
+            (start..end).contains(&unix_secs)
+        } else {
+            // Southern hemisphere: DST spans the new year
+            unix_secs >= start || unix_secs < end
+        }
+    }
+}
+
+/// Seconds since the epoch, in local time, of a transition in `year`.
+fn transition_local_secs(year: i32, rule: &TransitionRule) -> i64 {
+    let first = days_from_civil(year, rule.month, 1);
+    let first_weekday = weekday_from_days(first);
+    let mut day = 1 + (rule.weekday + 7 - first_weekday) % 7 + (rule.week - 1) * 7;
+    let len = days_in_month(year, rule.month);
+    while day > len {
+        day -= 7;
+    }
+    (first + day as i64 - 1) * 86_400 + rule.time_secs
+}
+
+fn is_leap(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn weekday_from_days(days: i64) -> u8 {
+    // 1970-01-01 was a Thursday
+    (days + 4).rem_euclid(7) as u8
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i32, month: u8, day: u8) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year } as i64;
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i32, u8, u8) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+    (year, month, day)
+}
+
+/// Cursor over a POSIX `TZ` string.
+struct Parser<'a>(&'a str);
+
+impl Parser<'_> {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.0.chars().next()
+    }
+
+    fn expect(&mut self, c: char) -> Option<()> {
+        self.0 = self.0.strip_prefix(c)?;
+        Some(())
+    }
+
+    /// A zone abbreviation: 3+ letters, or anything in angle brackets.
+    fn name(&mut self) -> Option<()> {
+        let len = if let Some(rest) = self.0.strip_prefix('<') {
+            rest.find('>')? + 2
+        } else {
+            self.0
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(self.0.len())
+        };
+        if len < 3 {
+            return None;
+        }
+        self.0 = &self.0[len..];
+        Some(())
+    }
+
+    fn number(&mut self) -> Option<i64> {
+        let len = self
+            .0
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.0.len());
+        let n = self.0[..len].parse().ok()?;
+        self.0 = &self.0[len..];
+        Some(n)
+    }
+
+    /// `[+-]hh[:mm[:ss]]` in seconds.
+    fn offset(&mut self) -> Option<i64> {
+        let sign = match self.peek() {
+            Some('-') => {
+                self.0 = &self.0[1..];
+                -1
+            }
+            Some('+') => {
+                self.0 = &self.0[1..];
+                1
+            }
+            _ => 1,
+        };
+        let mut secs = self.number()? * 3600;
+        if self.expect(':').is_some() {
+            secs += self.number()? * 60;
+            if self.expect(':').is_some() {
+                secs += self.number()?;
+            }
+        }
+        Some(sign * secs)
+    }
+
+    /// `Mm.w.d[/time]`.
+    fn rule(&mut self) -> Option<TransitionRule> {
+        self.expect('M')?;
+        let month = self.number()?;
+        self.expect('.')?;
+        let week = self.number()?;
+        self.expect('.')?;
+        let weekday = self.number()?;
+        if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+            return None;
+        }
+        let time_secs = if self.expect('/').is_some() {
+            self.offset()?
+        } else {
+            2 * 3600
+        };
+        Some(TransitionRule {
+            month: month as u8,
+            week: week as u8,
+            weekday: weekday as u8,
+            time_secs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACIFIC: &str = "PST8PDT,M3.2.0,M11.1.0";
+
+    fn utc(year: i32, month: u8, day: u8, hour: i64, minute: i64) -> i64 {
+        days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60
+    }
+
+    #[test]
+    fn time_windows_may_cross_midnight() {
+        let at = |hour, minute| TimeZone::UTC.to_local(utc(2025, 10, 14, hour, minute));
+        let night = TimeWindow::parse("22:00-07:00").unwrap();
+        assert!(night.contains(&at(23, 30)));
+        assert!(night.contains(&at(6, 59)));
+        assert!(!night.contains(&at(7, 0)));
+        assert!(!night.contains(&at(12, 0)));
+        let lunch = TimeWindow::parse(" 12:00 - 13:30").unwrap();
+        assert!(lunch.contains(&at(13, 29)) && !lunch.contains(&at(22, 0)));
+        assert_eq!(lunch.to_string(), "12:00-13:30");
+        assert!(!TimeWindow::parse("6:00-6:00").unwrap().contains(&at(6, 0)));
+        for bad in ["", "22:00", "24:00-07:00", "22:60-07:00", "10pm-7am", "22:0-07:00"] {
+            assert_eq!(TimeWindow::parse(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn civil_date_round_trip() {
+        for days in [-1, 0, 11_016, 19_723, 20_000, 2_932_896] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+    }
+
+    #[test]
+    fn utc_conversion() {
+        let t = TimeZone::parse("UTC0")
+            .unwrap()
+            .to_local(utc(2024, 7, 4, 15, 30));
+        assert_eq!(
+            (t.year, t.month, t.day, t.hour, t.minute),
+            (2024, 7, 4, 15, 30)
+        );
+        assert_eq!(t.weekday, 4);
+        assert!(!t.dst);
+        assert_eq!(t.minute_of_day(), 15 * 60 + 30);
+    }
+
+    #[test]
+    fn fixed_offset_zones() {
+        let tz = TimeZone::parse("<+0530>-5:30").unwrap();
+        let t = tz.to_local(utc(2024, 1, 1, 20, 0));
+        assert_eq!((t.day, t.hour, t.minute), (2, 1, 30));
+
+        let t = TimeZone::parse("HST10")
+            .unwrap()
+            .to_local(utc(2024, 1, 1, 5, 0));
+        assert_eq!((t.year, t.month, t.day, t.hour), (2023, 12, 31, 19));
+    }
+
+    #[test]
+    fn pacific_dst_transitions() {
+        let tz = TimeZone::parse(PACIFIC).unwrap();
+        // 2024: DST from Mar 10 02:00 PST (10:00 UTC) to Nov 3 02:00 PDT (09:00 UTC)
+        assert!(!tz.to_local(utc(2024, 3, 10, 9, 59)).dst);
+        let start = tz.to_local(utc(2024, 3, 10, 10, 0));
+        assert!(start.dst);
+        assert_eq!(start.hour, 3);
+        assert!(tz.to_local(utc(2024, 11, 3, 8, 59)).dst);
+        let end = tz.to_local(utc(2024, 11, 3, 9, 0));
+        assert!(!end.dst);
+        assert_eq!(end.hour, 1);
+    }
+
+    #[test]
+    fn southern_hemisphere_dst() {
+        let tz = TimeZone::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        let summer = tz.to_local(utc(2024, 1, 15, 0, 0));
+        assert!(summer.dst);
+        assert_eq!(summer.hour, 11);
+        let winter = tz.to_local(utc(2024, 7, 15, 0, 0));
+        assert!(!winter.dst);
+        assert_eq!(winter.hour, 10);
+    }
+
+    #[test]
+    fn last_week_rule() {
+        // EU: last Sunday in March/October
+        let tz = TimeZone::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        assert!(!tz.to_local(utc(2024, 3, 31, 0, 59)).dst);
+        assert!(tz.to_local(utc(2024, 3, 31, 1, 0)).dst);
+    }
+
+    #[test]
+    fn malformed_zones_rejected() {
+        for tz in [
+            "",
+            "PST",
+            "P8",
+            "PST8PDT",
+            "PST8PDT,M3.2.0",
+            "PST8PDT,J60,J300",
+            "UTC0x",
+        ] {
+            assert!(TimeZone::parse(tz).is_none(), "{tz}");
+        }
+    }
+
+    #[test]
+    fn fixed_clock() {
+        let t = TimeZone::UTC.to_local(0);
+        assert_eq!(FixedClock(Some(t)).now(), Some(t));
+        assert_eq!(FixedClock::default().now(), None);
+    }
+}
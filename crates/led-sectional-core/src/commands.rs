@@ -0,0 +1,151 @@
+//! Commands the map takes over MQTT, so automations can drive it without
+//! the HTTP API. Each goes to `<prefix>/<name>/set`, and the resulting
+//! state is published retained to `<prefix>/<name>` (see
+//! [`crate::telemetry`]):
+//!
+//! - `brightness`: `0`-`255` or a percentage such as `40%`
+//! - `display`: `ON` or `OFF`; off blanks the LEDs until switched back on
+//!   or the device restarts
+//! - `mode`: `metar` or `winds_aloft`
+//! - `refresh`: any payload; fetches weather now
+//! - `profile`: any payload; switches to the next `[[profiles]]` entry,
+//!   back to plain `[settings]` after the last
+
+use crate::config::{parse_brightness, Config, DisplayMode};
+use crate::error::{Error, Result};
+
+/// Topic filter matching every command under `prefix`.
+pub fn subscription(prefix: &str) -> String {
+    format!("{prefix}/+/set")
+}
+
+/// One command received from the broker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Brightness(u8),
+    Display(bool),
+    Mode(DisplayMode),
+    Refresh,
+    NextProfile,
+}
+
+impl Command {
+    /// Parse a message on `topic`. Errors name what was wrong, for the log;
+    /// a bad command changes nothing.
+    pub fn parse(prefix: &str, topic: &str, payload: &str) -> std::result::Result<Self, String> {
+        let name = topic
+            .strip_prefix(prefix)
+            .and_then(|t| t.strip_prefix('/'))
+            .and_then(|t| t.strip_suffix("/set"))
+            .ok_or_else(|| format!("{topic} isn't a command topic"))?;
+        let payload = payload.trim();
+        match name {
+            "brightness" => parse_brightness(payload).map(Self::Brightness),
+            "display" => match payload.to_ascii_lowercase().as_str() {
+                "on" | "true" | "1" => Ok(Self::Display(true)),
+                "off" | "false" | "0" => Ok(Self::Display(false)),
+                _ => Err(format!("display must be ON or OFF, not \"{payload}\"")),
+            },
+            "mode" => DisplayMode::from_name(&payload.to_ascii_lowercase())
+                .map(Self::Mode)
+                .ok_or_else(|| format!("mode must be metar or winds_aloft, not \"{payload}\"")),
+            "refresh" => Ok(Self::Refresh),
+            "profile" => Ok(Self::NextProfile),
+            _ => Err(format!("unknown command {name}")),
+        }
+    }
+}
+
+/// `config` with the setting `command` changes, revalidated, for the
+/// commands that are saved like a settings edit: brightness, mode and
+/// profile. None for the others. A profile that sets the same key still
+/// overrides it while active.
+pub fn with_command(config: &Config, command: Command) -> Result<Option<Config>> {
+    // Round-trip so an active profile's overrides aren't written as base
+    // settings
+    let mut edited: Config = toml::from_str(&config.to_toml()?)?;
+    match command {
+        Command::Brightness(level) => edited.settings.brightness = level,
+        Command::Mode(mode) => edited.settings.display_mode = mode,
+        Command::Display(_) | Command::Refresh => return Ok(None),
+        Command::NextProfile => {
+            let mut cycled = config.clone();
+            let next = cycled.cycle_profile()?.map(str::to_string);
+            return with_profile(config, next.as_deref()).map(Some);
+        }
+    }
+    Config::from_toml(&toml::to_string(&edited)?).map(Some)
+}
+
+/// `config` switched to the named profile, or to plain `[settings]` with
+/// None, revalidated so it can be saved like a settings edit.
+pub fn with_profile(config: &Config, name: Option<&str>) -> Result<Config> {
+    if let Some(name) = name {
+        if !config.profiles.iter().any(|p| p.name == name) {
+            return Err(Error::UnknownProfile(name.to_string()));
+        }
+    }
+    let mut edited: Config = toml::from_str(&config.to_toml()?)?;
+    edited.settings.profile = name.map(str::to_string);
+    Config::from_toml(&toml::to_string(&edited)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_command() {
+        let parse = |topic: &str, payload: &str| Command::parse("home/map", topic, payload);
+        assert_eq!(parse("home/map/brightness/set", "40%"), Ok(Command::Brightness(102)));
+        assert_eq!(parse("home/map/brightness/set", "12"), Ok(Command::Brightness(12)));
+        assert_eq!(parse("home/map/display/set", "OFF"), Ok(Command::Display(false)));
+        assert_eq!(parse("home/map/display/set", "on"), Ok(Command::Display(true)));
+        assert_eq!(
+            parse("home/map/mode/set", "winds_aloft"),
+            Ok(Command::Mode(DisplayMode::WindsAloft))
+        );
+        assert_eq!(parse("home/map/refresh/set", "PRESS"), Ok(Command::Refresh));
+
+        assert!(parse("home/map/brightness/set", "300").is_err());
+        assert!(parse("home/map/display/set", "dim").is_err());
+        assert!(parse("home/map/mode/set", "taf").is_err());
+        assert_eq!(parse("home/map/profile/set", "next"), Ok(Command::NextProfile));
+        assert!(parse("home/map/reboot/set", "").is_err());
+        assert!(parse("home/other/brightness/set", "1").is_err());
+        assert_eq!(subscription("home/map"), "home/map/+/set");
+    }
+
+    #[test]
+    fn settings_commands_edit_the_config() {
+        let config = Config::builder().airport("KSFO").build().unwrap();
+        let edited = with_command(&config, Command::Brightness(7)).unwrap().unwrap();
+        assert_eq!(edited.settings.brightness, 7);
+        let edited = with_command(&edited, Command::Mode(DisplayMode::WindsAloft))
+            .unwrap()
+            .unwrap();
+        assert_eq!(edited.settings.display_mode, DisplayMode::WindsAloft);
+        assert_eq!(edited.settings.brightness, 7);
+        assert!(with_command(&config, Command::Refresh).unwrap().is_none());
+    }
+
+    #[test]
+    fn profile_commands_switch_and_keep_base_settings() {
+        let toml =
+            "[settings]\nbrightness = 40\n\n[[profiles]]\nname = \"night\"\nbrightness = 5\n";
+        let config = Config::from_toml(toml).unwrap();
+        let night = with_command(&config, Command::NextProfile).unwrap().unwrap();
+        assert_eq!(night.active_profile(), Some("night"));
+        assert_eq!(night.settings.brightness, 5);
+        assert!(night.to_toml().unwrap().contains("brightness = 40"));
+        let base = with_command(&night, Command::NextProfile).unwrap().unwrap();
+        assert_eq!(base.active_profile(), None);
+        assert_eq!(base.settings.brightness, 40);
+
+        assert_eq!(with_profile(&config, Some("night")).unwrap().settings.brightness, 5);
+        assert!(matches!(
+            with_profile(&config, Some("party")),
+            Err(Error::UnknownProfile(name)) if name == "party"
+        ));
+    }
+}
@@ -1,8 +1,22 @@
-use serde::Deserialize;
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr};
 
-use crate::error::Result;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
+use crate::chip::Chip;
+use crate::clock::{TimeWindow, TimeZone};
+use crate::cors;
+use crate::error::{Error, Result};
+use crate::led::Color;
+use crate::log_filter::{LogFilter, DEFAULT_LOG_LEVEL};
+use crate::networks::{self, Bssid, Credentials, StaticIp, WifiAuth};
+use crate::oled::{self, Controller};
+use crate::power_budget;
+use crate::presets;
+use crate::syslog;
+use crate::user_button::{ButtonAction, Press};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
     #[serde(default)]
     pub settings: Settings,
@@ -10,214 +24,3203 @@ pub struct Config {
     pub wifi: WifiConfig,
     #[serde(default)]
     pub airports: Vec<Airport>,
+    /// User-defined legend codes with fixed colors.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub legend: Vec<LegendEntry>,
+    /// Named setting overlays, switchable at runtime.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<Profile>,
+    /// `settings` as they were before the active profile was applied.
+    #[serde(skip)]
+    base_settings: Settings,
+    /// The chip validation checked pins and channels against.
+    #[serde(skip)]
+    chip: Chip,
+    /// Problems found while validating; see [`Diagnostic`].
+    #[serde(skip)]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// How serious a [`Diagnostic`] is. Neither stops the config from loading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// A value was adjusted or looks unintended.
+    Warning,
+    /// Part of the config can't work as written (e.g. an unknown code).
+    Error,
+}
+
+/// A validation finding, reported instead of silently changing the config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Dotted path of the offending value, e.g. `settings.brightness` or `airports[3].code`.
+    pub field: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn warning(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+
+    fn error(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{level}: {}: {}", self.field, self.message)
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Settings {
-    #[serde(default = "default_brightness")]
+    /// LED brightness, 0-255 or a percentage string such as `"40%"`.
+    #[serde(default = "default_brightness", deserialize_with = "brightness_level")]
     pub brightness: u8,
+    /// Floor for every brightness change, including profiles and runtime controls.
+    #[serde(default, deserialize_with = "brightness_level")]
+    pub min_brightness: u8,
+    /// Ceiling for every brightness change, including profiles and runtime controls.
+    #[serde(default = "default_max_brightness", deserialize_with = "brightness_level")]
+    pub max_brightness: u8,
+    /// Rating of the 5 V supply, in mA. Frames the strip would draw more
+    /// than its share of are dimmed to fit; see [`crate::power_budget`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_current_ma: Option<u32>,
     #[serde(default = "default_request_interval")]
     pub request_interval_secs: u64,
+    /// Random extra delay (0..=N seconds) added to each fetch interval.
+    #[serde(default = "default_request_jitter")]
+    pub request_jitter_secs: u64,
+    /// Fetch at most this often while any airport reports thunderstorms or
+    /// IFR/LIFR. Unset keeps `request_interval_secs` regardless of weather.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_interval_secs: Option<u64>,
     #[serde(default = "default_wind_threshold")]
     pub wind_threshold_kt: u32,
+    /// Judge gusts on their own against this threshold; when unset, the higher
+    /// of wind and gust is compared to `wind_threshold_kt`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gust_threshold_kt: Option<u32>,
+    /// Color for VFR airports over the gust threshold (default orange).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gust_color: Option<[u8; 3]>,
+    /// Blink airports over the gust threshold.
+    #[serde(default)]
+    pub gust_blink: bool,
     #[serde(default = "default_true")]
     pub do_lightning: bool,
     #[serde(default = "default_true")]
     pub do_winds: bool,
+    /// Tint VFR/MVFR airports whose temperature/dewpoint spread suggests fog.
+    #[serde(default)]
+    pub do_fog_risk: bool,
+    /// Animate made-up weather when the setup portal times out or WiFi never
+    /// connects, instead of rebooting; see [`crate::demo`].
+    #[serde(default = "default_true")]
+    pub offline_demo: bool,
+    /// Always show the demo instead of fetched weather, for shows and store
+    /// displays; no network is needed. See [`crate::demo`].
+    #[serde(default)]
+    pub attract_mode: bool,
     #[serde(default = "default_data_pin")]
     pub data_pin: u8,
+    /// GPIO of the (active-low) setup button; defaults to the BOOT button
+    /// of the chip's dev boards, filled in by validation. See
+    /// [`crate::button`] for what holding it does.
+    #[serde(skip)]
+    pub button_pin: u8,
+    /// `button_pin` as written in the config, if it was, so the default can
+    /// follow the chip being validated for.
+    #[serde(default, rename = "button_pin", skip_serializing_if = "Option::is_none")]
+    pub button_pin_setting: Option<u8>,
+    #[serde(default)]
+    pub user_button: UserButtonSettings,
+    /// The airport watched for alerts and shown on the OLED panel, such as
+    /// the field the map hangs at; must be on the map. See
+    /// [`crate::home_alert`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub home_airport: Option<String>,
+    #[serde(default)]
+    pub buzzer: BuzzerSettings,
+    #[serde(default)]
+    pub oled: OledSettings,
+    #[serde(default)]
+    pub display_off: DisplayOffSettings,
+    /// WiFi signal, in dBm, below which the legend LEDs blink dimly to show
+    /// that fetches may time out.
+    #[serde(default = "default_weak_signal_dbm")]
+    pub weak_signal_dbm: i8,
+    /// Upper limit on the number of LEDs (airport entries).
+    #[serde(default = "default_max_leds")]
+    pub max_leds: usize,
+    /// Heap the LED buffers and METAR data may use, in KiB.
+    #[serde(default = "default_memory_budget")]
+    pub memory_budget_kb: usize,
+    /// Free heap, in KiB, below which the map saves memory; see
+    /// [`crate::heap`].
+    #[serde(default = "default_low_heap")]
+    pub low_heap_kb: u32,
+    /// POSIX `TZ` string for local-time schedules, e.g. `PST8PDT,M3.2.0,M11.1.0`.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    /// Which messages are logged, e.g. `info,metar_client=debug,wifi=warn`;
+    /// see [`crate::log_filter`].
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    /// Built-in airport list to use instead of `[[airports]]`; see [`crate::presets`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Name of the active `[[profiles]]` entry, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub display_mode: DisplayMode,
+    #[serde(default)]
+    pub winds_aloft: WindsAloftSettings,
+    #[serde(default)]
+    pub lightning: LightningSettings,
+    #[serde(default)]
+    pub led: LedSettings,
+    #[serde(default)]
+    pub provisioning: ProvisioningSettings,
+    #[serde(default)]
+    pub ota: OtaSettings,
+    #[serde(default)]
+    pub http: HttpSettings,
+    #[serde(default)]
+    pub mqtt: MqttSettings,
+    #[serde(default)]
+    pub syslog: SyslogSettings,
+    #[serde(default)]
+    pub power: PowerSettings,
+    /// Erase stored WiFi credentials and config at boot, then start the
+    /// setup portal. The reset removes the file that set it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub factory_reset: bool,
+}
+
+/// What the LED colors represent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayMode {
+    /// Flight category from METARs (the classic sectional display).
+    #[default]
+    Metar,
+    /// Forecast wind speed at `winds_aloft.altitude_ft`.
+    WindsAloft,
+}
+
+impl DisplayMode {
+    /// The name used in the config, such as `winds_aloft`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Metar => "metar",
+            Self::WindsAloft => "winds_aloft",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Metar, Self::WindsAloft].into_iter().find(|m| m.as_str() == name)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WindsAloftSettings {
+    /// Forecast level in feet MSL; must be one of the FD product levels.
+    #[serde(default = "default_winds_altitude")]
+    pub altitude_ft: u32,
+    /// Wind speed shown as full red; half this is yellow.
+    #[serde(default = "default_winds_max")]
+    pub max_kt: u16,
+    /// Forecast period: 6, 12, or 24 hours.
+    #[serde(default = "default_winds_forecast")]
+    pub forecast_hours: u8,
+}
+
+/// Timing of the thunderstorm flash animation.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LightningSettings {
+    /// How long each flash stays lit.
+    #[serde(default = "default_flash_ms")]
+    pub flash_ms: u64,
+    /// Time between flash opportunities.
+    #[serde(default = "default_flash_interval_ms")]
+    pub interval_ms: u64,
+    /// Chance (0-100%) that a flash opportunity actually flashes.
+    #[serde(default = "default_flash_probability")]
+    pub probability_pct: u8,
+}
+
+/// How the LED strip is driven. The defaults suit a WS2812B wired straight
+/// to `data_pin`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LedSettings {
+    /// RMT peripheral channel (0-7) used to generate the data signal; the
+    /// ESP32-C3 can transmit on 0-1 and the S3 on 0-3. If it can't be used
+    /// the firmware falls back to SPI.
+    #[serde(default)]
+    pub rmt_channel: u8,
+    /// Invert the data signal, for inverting level shifters (e.g. a single
+    /// transistor stage).
+    #[serde(default)]
+    pub invert: bool,
+    /// Idle time that latches a frame. WS2812B needs 280 us or more; older
+    /// WS2812 parts accept 50 us.
+    #[serde(default = "default_reset_us")]
+    pub reset_us: u32,
+}
+
+/// How WiFi credentials are entered when none are saved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvisioningMethod {
+    /// Setup access point with a web form.
+    #[default]
+    Portal,
+    /// Bluetooth LE, using Espressif's provisioning protocol (the "ESP BLE
+    /// Provisioning" phone apps).
+    Ble,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ProvisioningSettings {
+    #[serde(default)]
+    pub method: ProvisioningMethod,
+    /// Proof-of-possession code the phone must enter for BLE provisioning;
+    /// without one, anyone in range can provision the device.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pop: Option<String>,
+    /// WPA2 password for the setup access point; it's open without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ap_password: Option<String>,
+    /// Also listen for credentials from Espressif's ESP-Touch (SmartConfig)
+    /// app while the portal runs; whichever delivers a working network first
+    /// wins.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub smartconfig: bool,
+    /// Let a quick press of the setup button start WPS push-button pairing
+    /// while the portal runs; press the router's WPS button too.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub wps: bool,
+}
+
+/// Where the device looks for firmware updates on its own.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct OtaSettings {
+    /// HTTPS URL of an update manifest, checked when WiFi first connects and
+    /// on `POST /api/update/check`; see [`crate::ota::Manifest`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest_url: Option<String>,
+}
+
+/// Log shipping to a collector on the LAN; see [`crate::syslog`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SyslogSettings {
+    /// The collector's IP address, optionally with a port (default 514).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+}
+
+impl SyslogSettings {
+    /// Where to send log lines; None if no `server` is set.
+    pub fn server_addr(&self) -> Option<SocketAddr> {
+        self.server.as_deref().and_then(syslog::parse_server)
+    }
+}
+
+/// The optional everyday button; see [`crate::user_button`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct UserButtonSettings {
+    /// GPIO of the (active-low) button; none by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin: Option<u8>,
+    #[serde(default = "default_short_press")]
+    pub short_press: ButtonAction,
+    /// Leaving this `none` makes short presses act at once rather than
+    /// after the gap a double press could start in.
+    #[serde(default = "default_double_press")]
+    pub double_press: ButtonAction,
+    #[serde(default = "default_long_press")]
+    pub long_press: ButtonAction,
+}
+
+impl UserButtonSettings {
+    pub fn action(&self, press: Press) -> ButtonAction {
+        match press {
+            Press::Short => self.short_press,
+            Press::Double => self.double_press,
+            Press::Long => self.long_press,
+        }
+    }
+}
+
+impl Default for UserButtonSettings {
+    fn default() -> Self {
+        Self {
+            pin: None,
+            short_press: default_short_press(),
+            double_press: default_double_press(),
+            long_press: default_long_press(),
+        }
+    }
+}
+
+/// A piezo buzzer that chirps on home airport alerts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BuzzerSettings {
+    /// GPIO driving the buzzer; none by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin: Option<u8>,
+    /// Local times to stay silent, like `22:00-07:00`; see `timezone`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<String>,
+}
+
+impl BuzzerSettings {
+    /// `quiet_hours` parsed; None if unset or malformed.
+    pub fn quiet_window(&self) -> Option<TimeWindow> {
+        self.quiet_hours.as_deref().and_then(TimeWindow::parse)
+    }
+}
+
+/// A 128x64 I2C OLED panel for status; see [`crate::status_screen`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct OledSettings {
+    /// I2C data and clock GPIOs; the panel is off unless both are set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sda: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scl: Option<u8>,
+    #[serde(default)]
+    pub controller: Controller,
+    /// 7-bit I2C address.
+    #[serde(default = "default_oled_address")]
+    pub address: u8,
+}
+
+impl OledSettings {
+    /// `(sda, scl)`, if the panel is on.
+    pub fn pins(&self) -> Option<(u8, u8)> {
+        self.sda.zip(self.scl)
+    }
+}
+
+impl Default for OledSettings {
+    fn default() -> Self {
+        Self {
+            sda: None,
+            scl: None,
+            controller: Controller::default(),
+            address: default_oled_address(),
+        }
+    }
+}
+
+/// Hours the LEDs stay dark; see [`crate::display_schedule`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DisplayOffSettings {
+    /// Local times like `23:00-06:00`; see `timezone`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub windows: Vec<String>,
+    /// How long motion or a button press lights the map inside a window.
+    #[serde(default = "default_wake_secs")]
+    pub wake_secs: u64,
+    /// GPIO of an (active-high) PIR motion sensor; none by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion_pin: Option<u8>,
+}
+
+impl DisplayOffSettings {
+    /// `windows` parsed, skipping malformed ones.
+    pub fn time_windows(&self) -> Vec<TimeWindow> {
+        self.windows.iter().filter_map(|w| TimeWindow::parse(w)).collect()
+    }
+}
+
+impl Default for DisplayOffSettings {
+    fn default() -> Self {
+        Self {
+            windows: Vec::new(),
+            wake_secs: default_wake_secs(),
+            motion_pin: None,
+        }
+    }
+}
+
+/// How the device's web server answers other sites.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct HttpSettings {
+    /// Origins such as `https://app.example.com` whose pages may call the
+    /// API from the browser, or `"*"` for any; see [`crate::cors`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cors_origins: Vec<String>,
+}
+
+/// Publishing telemetry to an MQTT broker; off without a `broker`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MqttSettings {
+    /// Broker URL: `mqtt://host[:port]`, or `mqtts://` for TLS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub broker: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Topics start with this; defaults to `led-sectional/<hostname>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic_prefix: Option<String>,
+    /// How often everything is published again, changed or not.
+    #[serde(default = "default_mqtt_heartbeat")]
+    pub heartbeat_secs: u64,
+}
+
+impl Default for MqttSettings {
+    fn default() -> Self {
+        Self {
+            broker: None,
+            username: None,
+            password: None,
+            topic_prefix: None,
+            heartbeat_secs: default_mqtt_heartbeat(),
+        }
+    }
+}
+
+/// Power saving, for battery-powered maps and to run cooler.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PowerSettings {
+    /// Deep-sleep between fetches, waking on a timer; see
+    /// [`crate::deep_sleep`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deep_sleep: bool,
+    /// CPU clock while busy: 80 or 160 MHz, or 240 on the ESP32-S3.
+    #[serde(default = "default_max_cpu_mhz")]
+    pub max_cpu_mhz: u32,
+    /// CPU clock the chip drops to while idle: 40, 80, 160 (or 240) MHz, at
+    /// most `max_cpu_mhz`. The same as `max_cpu_mhz` keeps the clock fixed.
+    #[serde(default = "default_min_cpu_mhz")]
+    pub min_cpu_mhz: u32,
+    /// Light-sleep while idle between frames and fetches. The USB serial
+    /// console drops out while the chip sleeps.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub light_sleep: bool,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        Self {
+            deep_sleep: false,
+            max_cpu_mhz: default_max_cpu_mhz(),
+            min_cpu_mhz: default_min_cpu_mhz(),
+            light_sleep: false,
+        }
+    }
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct WifiConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub ssid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<WifiAuth>,
+    /// WPA2-Enterprise login name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// WPA2-Enterprise outer identity, if the network wants one other than
+    /// `username`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    /// Join only this access point, e.g. the nearest node of a mesh.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bssid: Option<Bssid>,
+    /// The network doesn't broadcast its SSID.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
+    /// Fixed address instead of DHCP; needs `gateway` as well.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<Ipv4Addr>,
+    /// Defaults to 255.255.255.0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub netmask: Option<Ipv4Addr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<Ipv4Addr>,
+    /// Defaults to the gateway.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Ipv4Addr>,
+    /// Name registered over mDNS as `<hostname>.local`; defaults to
+    /// [`networks::DEFAULT_HOSTNAME`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+}
+
+impl WifiConfig {
+    pub fn hostname(&self) -> &str {
+        self.hostname.as_deref().unwrap_or(networks::DEFAULT_HOSTNAME)
+    }
+    /// The fixed address to use, or `None` for DHCP. Needs both `ip` and
+    /// `gateway`; an unusable netmask falls back to /24.
+    pub fn static_ip(&self) -> Option<StaticIp> {
+        let ip = self.ip?;
+        let gateway = self.gateway?;
+        Some(StaticIp {
+            ip,
+            prefix_len: self.netmask.and_then(networks::prefix_len).unwrap_or(24),
+            gateway,
+            dns: self.dns.unwrap_or(gateway),
+        })
+    }
+
+    fn check_static_ip(&self, diags: &mut Vec<Diagnostic>) {
+        if self.ip.is_none() {
+            if self.gateway.is_some() || self.netmask.is_some() || self.dns.is_some() {
+                diags.push(Diagnostic::warning(
+                    "wifi.ip",
+                    "netmask, gateway, and dns are ignored without a static ip",
+                ));
+            }
+            return;
+        }
+        if self.gateway.is_none() {
+            diags.push(Diagnostic::warning(
+                "wifi.gateway",
+                "a static ip needs a gateway; using DHCP",
+            ));
+            return;
+        }
+        if let Some(mask) = self.netmask.filter(|m| networks::prefix_len(*m).is_none()) {
+            diags.push(Diagnostic::warning(
+                "wifi.netmask",
+                format!("{mask} is not a valid netmask, using 255.255.255.0"),
+            ));
+        }
+        if let Some(fixed) = self.static_ip().filter(|f| !f.contains(f.gateway)) {
+            diags.push(Diagnostic::warning(
+                "wifi.gateway",
+                format!("{} is outside {}/{}", fixed.gateway, fixed.ip, fixed.prefix_len),
+            ));
+        }
+    }
+
+    /// The configured network, if an SSID is set.
+    pub fn credentials(&self) -> Option<Credentials> {
+        let ssid = self.ssid.as_ref().filter(|s| !s.is_empty())?;
+        Some(Credentials {
+            ssid: ssid.clone(),
+            password: self.password.clone().unwrap_or_default(),
+            auth: self.auth.unwrap_or_default(),
+            username: self.username.clone(),
+            identity: self.identity.clone(),
+            bssid: self.bssid,
+            hidden: self.hidden,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Airport {
+    pub code: String,
+    /// Physical LED position; entries without one fill the free positions in order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub led: Option<usize>,
+    /// Friendly name for the web UI and simulator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Nearby station whose METAR is used when this one has no report.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+    /// Winds-aloft (FD) station to use in winds-aloft mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub winds_station: Option<String>,
+    /// Latitude in degrees, for plotting the map preview; needs `lon` too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lat: Option<f64>,
+    /// Longitude in degrees, east positive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lon: Option<f64>,
+    /// Fixed color from a matching `[[legend]]` entry, resolved at load.
+    #[serde(skip)]
+    pub legend_color: Option<Color>,
+}
+
+/// A custom code that always shows a fixed color, like the built-in VFR/IFR
+/// legend codes. Takes precedence over a built-in code with the same name.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LegendEntry {
+    pub code: String,
+    /// RGB, e.g. `[255, 128, 0]`.
+    pub color: [u8; 3],
+}
+
+/// A named set of overrides for display settings, e.g. a dim `night` profile.
+/// Unset fields keep the value from `[settings]`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "opt_brightness_level"
+    )]
+    pub brightness: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_threshold_kt: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub do_lightning: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub do_winds: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub do_fog_risk: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_mode: Option<DisplayMode>,
+}
+
+impl Profile {
+    fn apply(&self, settings: &mut Settings) {
+        if let Some(v) = self.brightness {
+            settings.brightness = v;
+        }
+        if let Some(v) = self.wind_threshold_kt {
+            settings.wind_threshold_kt = v;
+        }
+        if let Some(v) = self.do_lightning {
+            settings.do_lightning = v;
+        }
+        if let Some(v) = self.do_winds {
+            settings.do_winds = v;
+        }
+        if let Some(v) = self.do_fog_risk {
+            settings.do_fog_risk = v;
+        }
+        if let Some(v) = self.display_mode {
+            settings.display_mode = v;
+        }
+    }
+
+    /// Undo `apply`: put back the `base` value of every field this profile sets.
+    fn revert(&self, settings: &mut Settings, base: &Settings) {
+        if self.brightness.is_some() {
+            settings.brightness = base.brightness;
+        }
+        if self.wind_threshold_kt.is_some() {
+            settings.wind_threshold_kt = base.wind_threshold_kt;
+        }
+        if self.do_lightning.is_some() {
+            settings.do_lightning = base.do_lightning;
+        }
+        if self.do_winds.is_some() {
+            settings.do_winds = base.do_winds;
+        }
+        if self.do_fog_risk.is_some() {
+            settings.do_fog_risk = base.do_fog_risk;
+        }
+        if self.display_mode.is_some() {
+            settings.display_mode = base.display_mode;
+        }
+    }
+}
+
+impl Airport {
+    /// The configured name, or the code when none is set.
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.code)
+    }
+
+    /// Latitude and longitude, when both are set.
+    pub fn position(&self) -> Option<(f64, f64)> {
+        Some((self.lat?, self.lon?))
+    }
+}
+
+fn default_brightness() -> u8 {
+    20
+}
+fn default_max_brightness() -> u8 {
+    255
+}
+fn default_request_interval() -> u64 {
+    900
+}
+fn default_request_jitter() -> u64 {
+    30
+}
+fn default_wind_threshold() -> u32 {
+    25
+}
+fn default_true() -> bool {
+    true
+}
+fn default_data_pin() -> u8 {
+    2
+}
+/// Why a button or buzzer can't use GPIO `pin`, if it can't.
+fn pin_problem(chip: Chip, settings: &Settings, pin: u8) -> Option<String> {
+    if !chip.has_gpio(pin) {
+        Some(format!("GPIO{pin} isn't usable on the {} ({})", chip.name(), chip.gpio_ranges()))
+    } else if pin == settings.data_pin {
+        Some(format!("GPIO{pin} is also data_pin"))
+    } else if pin == settings.button_pin {
+        Some(format!("GPIO{pin} is also button_pin"))
+    } else {
+        None
+    }
+}
+
+/// Why the OLED panel can't be used as set, if it can't.
+fn oled_problem(chip: Chip, settings: &Settings) -> Option<String> {
+    let oled = &settings.oled;
+    let (sda, scl) = match (oled.sda, oled.scl) {
+        (None, None) => return None,
+        (Some(sda), Some(scl)) => (sda, scl),
+        _ => return Some("set both sda and scl".into()),
+    };
+    if sda == scl {
+        return Some(format!("GPIO{sda} is both sda and scl"));
+    }
+    if !(0x08..=0x77).contains(&oled.address) {
+        return Some(format!("address {:#04x} isn't a 7-bit I2C address", oled.address));
+    }
+    [sda, scl].into_iter().find_map(|pin| {
+        pin_problem(chip, settings, pin)
+            .or_else(|| {
+                (settings.user_button.pin == Some(pin))
+                    .then(|| format!("GPIO{pin} is also user_button.pin"))
+            })
+            .or_else(|| {
+                (settings.buzzer.pin == Some(pin)).then(|| format!("GPIO{pin} is also buzzer.pin"))
+            })
+    })
+}
+
+fn default_wake_secs() -> u64 {
+    120
+}
+
+fn default_oled_address() -> u8 {
+    oled::DEFAULT_ADDRESS
+}
+
+fn default_short_press() -> ButtonAction {
+    ButtonAction::CycleBrightness
+}
+
+fn default_double_press() -> ButtonAction {
+    ButtonAction::Refresh
+}
+
+fn default_long_press() -> ButtonAction {
+    ButtonAction::ToggleDisplay
+}
+
+fn default_weak_signal_dbm() -> i8 {
+    networks::DEFAULT_WEAK_SIGNAL_DBM
+}
+fn default_timezone() -> String {
+    "UTC0".to_string()
+}
+fn default_log_level() -> String {
+    DEFAULT_LOG_LEVEL.to_string()
+}
+fn default_mqtt_heartbeat() -> u64 {
+    300
+}
+fn default_max_leds() -> usize {
+    250
+}
+fn default_memory_budget() -> usize {
+    128
+}
+fn default_low_heap() -> u32 {
+    32
+}
+fn default_max_cpu_mhz() -> u32 {
+    160
+}
+fn default_min_cpu_mhz() -> u32 {
+    40
+}
+fn default_reset_us() -> u32 {
+    300
+}
+fn default_flash_ms() -> u64 {
+    25
+}
+fn default_flash_interval_ms() -> u64 {
+    5000
+}
+fn default_flash_probability() -> u8 {
+    100
+}
+fn default_winds_altitude() -> u32 {
+    6000
+}
+fn default_winds_max() -> u16 {
+    50
+}
+fn default_winds_forecast() -> u8 {
+    6
+}
+
+/// Estimated heap per LED: the color buffer, its brightness-scaled copy, and
+/// the RMT symbols for 24 bits.
+const RAM_BYTES_PER_LED: usize = 3 + 3 + 24 * 4;
+/// Estimated heap per requested station: its share of the JSON response plus
+/// the parsed report.
+const RAM_BYTES_PER_STATION: usize = 800;
+
+/// Altitudes published in the low-level FD winds-aloft product.
+const WINDS_ALOFT_LEVELS: &[u32] = &[
+    3000, 6000, 9000, 12000, 18000, 24000, 30000, 34000, 39000,
+];
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            brightness: default_brightness(),
+            min_brightness: 0,
+            max_brightness: default_max_brightness(),
+            max_current_ma: None,
+            request_interval_secs: default_request_interval(),
+            request_jitter_secs: default_request_jitter(),
+            active_interval_secs: None,
+            wind_threshold_kt: default_wind_threshold(),
+            gust_threshold_kt: None,
+            gust_color: None,
+            gust_blink: false,
+            do_lightning: default_true(),
+            do_winds: default_true(),
+            do_fog_risk: false,
+            offline_demo: default_true(),
+            attract_mode: false,
+            data_pin: default_data_pin(),
+            button_pin: Chip::TARGET.boot_button(),
+            button_pin_setting: None,
+            user_button: UserButtonSettings::default(),
+            home_airport: None,
+            buzzer: BuzzerSettings::default(),
+            oled: OledSettings::default(),
+            display_off: DisplayOffSettings::default(),
+            weak_signal_dbm: default_weak_signal_dbm(),
+            max_leds: default_max_leds(),
+            memory_budget_kb: default_memory_budget(),
+            low_heap_kb: default_low_heap(),
+            timezone: default_timezone(),
+            log_level: default_log_level(),
+            preset: None,
+            profile: None,
+            display_mode: DisplayMode::default(),
+            winds_aloft: WindsAloftSettings::default(),
+            lightning: LightningSettings::default(),
+            led: LedSettings::default(),
+            provisioning: ProvisioningSettings::default(),
+            ota: OtaSettings::default(),
+            http: HttpSettings::default(),
+            mqtt: MqttSettings::default(),
+            syslog: SyslogSettings::default(),
+            power: PowerSettings::default(),
+            factory_reset: false,
+        }
+    }
+}
+
+impl Default for LedSettings {
+    fn default() -> Self {
+        Self {
+            rmt_channel: 0,
+            invert: false,
+            reset_us: default_reset_us(),
+        }
+    }
+}
+
+impl Default for LightningSettings {
+    fn default() -> Self {
+        Self {
+            flash_ms: default_flash_ms(),
+            interval_ms: default_flash_interval_ms(),
+            probability_pct: default_flash_probability(),
+        }
+    }
+}
+
+impl Settings {
+    /// Clamp a requested brightness to `min_brightness..=max_brightness`.
+    pub fn limit_brightness(&self, brightness: u8) -> u8 {
+        brightness.clamp(self.min_brightness, self.max_brightness.max(self.min_brightness))
+    }
+
+    /// Color for gusty VFR airports, from `gust_color` or the default.
+    pub fn gust_color(&self) -> Color {
+        self.gust_color
+            .map(|[r, g, b]| Color::new(r, g, b))
+            .unwrap_or(crate::led::COLOR_GUST)
+    }
+
+    /// The configured time zone; UTC if `timezone` doesn't parse.
+    pub fn time_zone(&self) -> TimeZone {
+        TimeZone::parse(&self.timezone).unwrap_or_default()
+    }
+
+    /// The configured log filter; `info` for everything if `log_level`
+    /// doesn't parse.
+    pub fn log_filter(&self) -> LogFilter {
+        LogFilter::parse(&self.log_level).unwrap_or_default()
+    }
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct Airport {
-    pub code: String,
-}
+impl Default for WindsAloftSettings {
+    fn default() -> Self {
+        Self {
+            altitude_ft: default_winds_altitude(),
+            max_kt: default_winds_max(),
+            forecast_hours: default_winds_forecast(),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_toml(s: &str) -> Result<Self> {
+        Self::from_toml_for(s, Chip::TARGET)
+    }
+
+    /// Like [`from_toml`](Self::from_toml), validating pins and channels for
+    /// `chip` rather than the one this build targets.
+    pub fn from_toml_for(s: &str, chip: Chip) -> Result<Self> {
+        let mut config: Config = toml::from_str(s)?;
+        config.chip = chip;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Serialize back to TOML, e.g. for persisting runtime changes. Values
+    /// overridden by the active profile are written as they were before it was
+    /// applied; the profile itself is recorded by name.
+    pub fn to_toml(&self) -> Result<String> {
+        let mut out = self.clone();
+        if let Some(profile) = self.find_profile(self.active_profile()) {
+            profile.revert(&mut out.settings, &self.base_settings);
+        }
+        Ok(toml::to_string(&out)?)
+    }
+
+    fn find_profile(&self, name: Option<&str>) -> Option<&Profile> {
+        name.and_then(|name| self.profiles.iter().find(|p| p.name == name))
+    }
+
+    /// Name of the active profile, or None when running on `[settings]` alone.
+    pub fn active_profile(&self) -> Option<&str> {
+        self.settings.profile.as_deref()
+    }
+
+    /// Switch to the named profile, or back to plain `[settings]` with None.
+    pub fn set_profile(&mut self, name: Option<&str>) -> Result<()> {
+        let next = match name {
+            Some(name) => Some(
+                self.find_profile(Some(name))
+                    .cloned()
+                    .ok_or_else(|| Error::UnknownProfile(name.to_string()))?,
+            ),
+            None => None,
+        };
+        if let Some(current) = self.find_profile(self.active_profile()).cloned() {
+            current.revert(&mut self.settings, &self.base_settings);
+        }
+        self.base_settings = self.settings.clone();
+        if let Some(profile) = &next {
+            profile.apply(&mut self.settings);
+        }
+        self.settings.profile = next.map(|p| p.name);
+        Ok(())
+    }
+
+    /// Advance to the next profile in file order, wrapping back to plain
+    /// `[settings]` after the last. Returns the new active profile name.
+    pub fn cycle_profile(&mut self) -> Result<Option<&str>> {
+        let next = match self.active_profile() {
+            None => self.profiles.first(),
+            Some(current) => self
+                .profiles
+                .iter()
+                .skip_while(|p| p.name != current)
+                .nth(1),
+        }
+        .map(|p| p.name.clone());
+        self.set_profile(next.as_deref())?;
+        Ok(self.active_profile())
+    }
+
+    pub fn num_leds(&self) -> usize {
+        self.airports.len()
+    }
+
+    /// LEDs showing legend colors, which double as status indicators.
+    pub fn indicator_led_indices(&self) -> Vec<usize> {
+        self.airports
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| a.legend_color.is_some() || is_special_code(&a.code))
+            .filter(|(_, a)| a.code != "NULL")
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Rough heap needed for this config's LED buffers and METAR data.
+    pub fn estimated_ram_bytes(&self) -> usize {
+        self.num_leds() * RAM_BYTES_PER_LED
+            + self.metar_airport_codes().len() * RAM_BYTES_PER_STATION
+    }
+
+    /// Returns only real ICAO airport codes, filtering out special and legend codes.
+    /// Fallback stations are included after the primaries, without duplicates.
+    pub fn metar_airport_codes(&self) -> Vec<&str> {
+        let mut codes: Vec<&str> = self
+            .airports
+            .iter()
+            .filter_map(|a| {
+                if is_special_code(&a.code) || a.legend_color.is_some() {
+                    None
+                } else {
+                    Some(a.code.as_str())
+                }
+            })
+            .collect();
+        for fallback in self.airports.iter().filter_map(|a| a.fallback.as_deref()) {
+            if !codes.contains(&fallback) {
+                codes.push(fallback);
+            }
+        }
+        codes
+    }
+
+    /// Whether validation found anything at [`Severity::Error`].
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+
+    fn validate(&mut self) -> Result<()> {
+        let mut diags = Vec::new();
+        let settings = &mut self.settings;
+
+        clamp_setting(
+            &mut diags,
+            "settings.request_interval_secs",
+            &mut settings.request_interval_secs,
+            60,
+            3600,
+        );
+        clamp_setting(
+            &mut diags,
+            "settings.request_jitter_secs",
+            &mut settings.request_jitter_secs,
+            0,
+            300,
+        );
+        if let Some(active) = &mut settings.active_interval_secs {
+            clamp_setting(
+                &mut diags,
+                "settings.active_interval_secs",
+                active,
+                60,
+                settings.request_interval_secs,
+            );
+        }
+        clamp_setting(
+            &mut diags,
+            "settings.wind_threshold_kt",
+            &mut settings.wind_threshold_kt,
+            0,
+            100,
+        );
+        if let Some(gust) = &mut settings.gust_threshold_kt {
+            clamp_setting(&mut diags, "settings.gust_threshold_kt", gust, 0, 150);
+        }
+        if TimeZone::parse(&settings.timezone).is_none() {
+            diags.push(Diagnostic::warning(
+                "settings.timezone",
+                format!(
+                    "\"{}\" is not a supported POSIX TZ string; using UTC",
+                    settings.timezone
+                ),
+            ));
+        }
+        if let Err(e) = LogFilter::parse(&settings.log_level) {
+            diags.push(Diagnostic::warning(
+                "settings.log_level",
+                format!("{e}; logging at info"),
+            ));
+        }
+        if settings.min_brightness > settings.max_brightness {
+            diags.push(Diagnostic::warning(
+                "settings.min_brightness",
+                format!(
+                    "{} is above max_brightness {}; using {}",
+                    settings.min_brightness, settings.max_brightness, settings.max_brightness
+                ),
+            ));
+            settings.min_brightness = settings.max_brightness;
+        }
+        clamp_setting(
+            &mut diags,
+            "settings.brightness",
+            &mut settings.brightness,
+            settings.min_brightness,
+            settings.max_brightness,
+        );
+        if settings.brightness == 0 {
+            diags.push(Diagnostic::warning(
+                "settings.brightness",
+                "brightness is 0, so all LEDs will be off",
+            ));
+        }
+        if let Some(ma) = settings.max_current_ma.filter(|&ma| ma <= power_budget::BOARD_MA) {
+            diags.push(Diagnostic::warning(
+                "settings.max_current_ma",
+                format!(
+                    "{ma} mA leaves nothing past the {} mA kept for the board; no limit",
+                    power_budget::BOARD_MA
+                ),
+            ));
+            settings.max_current_ma = None;
+        }
+
+        let winds = &mut settings.winds_aloft;
+        if !WINDS_ALOFT_LEVELS.contains(&winds.altitude_ft) {
+            // Snap to the nearest published level
+            let snapped = *WINDS_ALOFT_LEVELS
+                .iter()
+                .min_by_key(|l| l.abs_diff(winds.altitude_ft))
+                .unwrap_or(&WINDS_ALOFT_LEVELS[0]);
+            diags.push(Diagnostic::warning(
+                "settings.winds_aloft.altitude_ft",
+                format!(
+                    "{} is not a forecast level; using {}",
+                    winds.altitude_ft, snapped
+                ),
+            ));
+            winds.altitude_ft = snapped;
+        }
+        if ![6, 12, 24].contains(&winds.forecast_hours) {
+            diags.push(Diagnostic::warning(
+                "settings.winds_aloft.forecast_hours",
+                format!(
+                    "{} is not 6, 12, or 24; using {}",
+                    winds.forecast_hours,
+                    default_winds_forecast()
+                ),
+            ));
+            winds.forecast_hours = default_winds_forecast();
+        }
+        clamp_setting(
+            &mut diags,
+            "settings.winds_aloft.max_kt",
+            &mut winds.max_kt,
+            1,
+            u16::MAX,
+        );
+
+        let lightning = &mut settings.lightning;
+        clamp_setting(
+            &mut diags,
+            "settings.lightning.flash_ms",
+            &mut lightning.flash_ms,
+            5,
+            1000,
+        );
+        clamp_setting(
+            &mut diags,
+            "settings.lightning.interval_ms",
+            &mut lightning.interval_ms,
+            100,
+            60_000,
+        );
+        clamp_setting(
+            &mut diags,
+            "settings.lightning.probability_pct",
+            &mut lightning.probability_pct,
+            0,
+            100,
+        );
+
+        let chip = self.chip;
+        settings.button_pin = settings.button_pin_setting.unwrap_or(chip.boot_button());
+        let button_pin = &mut settings.button_pin;
+        clamp_setting(&mut diags, "settings.button_pin", button_pin, 0, chip.max_gpio());
+        if !chip.has_gpio(settings.button_pin) {
+            let fallback = chip.boot_button();
+            let (name, ranges) = (chip.name(), chip.gpio_ranges());
+            diags.push(Diagnostic::warning(
+                "settings.button_pin",
+                format!("isn't usable on the {name} ({ranges}); using {fallback}"),
+            ));
+            settings.button_pin = fallback;
+        }
+        if settings.button_pin == settings.data_pin {
+            diags.push(Diagnostic::warning(
+                "settings.button_pin",
+                format!("GPIO{} is also data_pin; the button is disabled", settings.button_pin),
+            ));
+        }
+        if let Some(problem) =
+            settings.user_button.pin.and_then(|pin| pin_problem(chip, settings, pin))
+        {
+            settings.user_button.pin = None;
+            diags.push(Diagnostic::warning(
+                "settings.user_button.pin",
+                format!("{problem}; the button is disabled"),
+            ));
+        }
+        let buzzer_pin = settings.buzzer.pin;
+        let buzzer_problem = buzzer_pin.and_then(|pin| {
+            pin_problem(chip, settings, pin).or_else(|| {
+                (settings.user_button.pin == Some(pin))
+                    .then(|| format!("GPIO{pin} is also user_button.pin"))
+            })
+        });
+        if let Some(problem) = buzzer_problem {
+            settings.buzzer.pin = None;
+            diags.push(Diagnostic::warning(
+                "settings.buzzer.pin",
+                format!("{problem}; the buzzer is off"),
+            ));
+        }
+        if let Some(quiet) = &settings.buzzer.quiet_hours {
+            if settings.buzzer.quiet_window().is_none() {
+                diags.push(Diagnostic::warning(
+                    "settings.buzzer.quiet_hours",
+                    format!("\"{quiet}\" must look like 22:00-07:00; the buzzer is never quiet"),
+                ));
+                settings.buzzer.quiet_hours = None;
+            }
+        }
+        if let Some(problem) = oled_problem(chip, settings) {
+            settings.oled.sda = None;
+            settings.oled.scl = None;
+            let message = format!("{problem}; the panel is off");
+            diags.push(Diagnostic::warning("settings.oled", message));
+        }
+        let display_off = &mut settings.display_off;
+        display_off.windows.retain(|window| {
+            let parsed = TimeWindow::parse(window).is_some();
+            if !parsed {
+                diags.push(Diagnostic::warning(
+                    "settings.display_off.windows",
+                    format!("\"{window}\" must look like 23:00-06:00; it's ignored"),
+                ));
+            }
+            parsed
+        });
+        clamp_setting(
+            &mut diags,
+            "settings.display_off.wake_secs",
+            &mut display_off.wake_secs,
+            10,
+            3600,
+        );
+        let motion_problem = settings.display_off.motion_pin.and_then(|pin| {
+            let taken = [
+                (settings.user_button.pin, "user_button.pin"),
+                (settings.buzzer.pin, "buzzer.pin"),
+                (settings.oled.sda, "oled.sda"),
+                (settings.oled.scl, "oled.scl"),
+            ];
+            pin_problem(chip, settings, pin).or_else(|| {
+                taken
+                    .into_iter()
+                    .find(|(other, _)| *other == Some(pin))
+                    .map(|(_, name)| format!("GPIO{pin} is also {name}"))
+            })
+        });
+        if let Some(problem) = motion_problem {
+            settings.display_off.motion_pin = None;
+            diags.push(Diagnostic::warning(
+                "settings.display_off.motion_pin",
+                format!("{problem}; the motion sensor is off"),
+            ));
+        }
+        clamp_setting(
+            &mut diags,
+            "settings.weak_signal_dbm",
+            &mut settings.weak_signal_dbm,
+            -100,
+            -30,
+        );
+        clamp_setting(&mut diags, "settings.low_heap_kb", &mut settings.low_heap_kb, 8, 256);
+
+        let led = &mut settings.led;
+        let channels = chip.rmt_tx_channels();
+        if led.rmt_channel >= channels {
+            diags.push(Diagnostic::warning(
+                "settings.led.rmt_channel",
+                format!(
+                    "the {} transmits on RMT channels 0-{}; using 0",
+                    chip.name(),
+                    channels - 1
+                ),
+            ));
+            led.rmt_channel = 0;
+        }
+        // The RMT encodes one level for at most 32767 ticks of 12.5 ns
+        clamp_setting(&mut diags, "settings.led.reset_us", &mut led.reset_us, 50, 400);
+
+        let provisioning = &mut settings.provisioning;
+        if provisioning.pop.as_ref().is_some_and(|pop| pop.is_empty()) {
+            provisioning.pop = None;
+        }
+        if provisioning.method == ProvisioningMethod::Ble && provisioning.pop.is_none() {
+            diags.push(Diagnostic::warning(
+                "settings.provisioning.pop",
+                "BLE provisioning without a pop code lets anyone nearby set the WiFi network",
+            ));
+        }
+        if provisioning.smartconfig && provisioning.method != ProvisioningMethod::Portal {
+            diags.push(Diagnostic::warning(
+                "settings.provisioning.smartconfig",
+                "SmartConfig only runs alongside the portal; it's off with this method",
+            ));
+        }
+        if provisioning.wps && provisioning.method != ProvisioningMethod::Portal {
+            diags.push(Diagnostic::warning(
+                "settings.provisioning.wps",
+                "WPS only runs alongside the portal; it's off with this method",
+            ));
+        }
+        if let Some(password) = provisioning.ap_password.take() {
+            if networks::is_valid_ap_password(&password) {
+                provisioning.ap_password = Some(password);
+            } else if !password.is_empty() {
+                diags.push(Diagnostic::warning(
+                    "settings.provisioning.ap_password",
+                    "must be 8-63 printable ASCII characters; the setup AP stays open",
+                ));
+            }
+        }
+
+        let power = &mut settings.power;
+        // 40 MHz only works as the idle clock
+        let clocks = chip.cpu_mhz();
+        if !clocks[1..].contains(&power.max_cpu_mhz) {
+            diags.push(Diagnostic::warning(
+                "settings.power.max_cpu_mhz",
+                format!("must be {}; using {}", or_list(&clocks[1..]), default_max_cpu_mhz()),
+            ));
+            power.max_cpu_mhz = default_max_cpu_mhz();
+        }
+        if !clocks.contains(&power.min_cpu_mhz) || power.min_cpu_mhz > power.max_cpu_mhz {
+            let fallback = default_min_cpu_mhz();
+            diags.push(Diagnostic::warning(
+                "settings.power.min_cpu_mhz",
+                format!("must be {} and at most max_cpu_mhz; using {fallback}", or_list(clocks)),
+            ));
+            power.min_cpu_mhz = fallback;
+        }
+
+        let ota = &mut settings.ota;
+        if ota.manifest_url.as_ref().is_some_and(|url| !url.starts_with("https://")) {
+            ota.manifest_url = None;
+            diags.push(Diagnostic::warning(
+                "settings.ota.manifest_url",
+                "must be an https:// URL; update checks are off",
+            ));
+        }
+
+        let origins = &mut settings.http.cors_origins;
+        for bad in origins.iter().filter(|o| !cors::is_valid_origin(o)) {
+            diags.push(Diagnostic::warning(
+                "settings.http.cors_origins",
+                format!("\"{bad}\" isn't an origin like https://app.example.com; ignored"),
+            ));
+        }
+        origins.retain(|o| cors::is_valid_origin(o));
+
+        let mqtt = &mut settings.mqtt;
+        let url_ok = |url: &str| {
+            ["mqtt://", "mqtts://"]
+                .iter()
+                .any(|scheme| url.strip_prefix(scheme).is_some_and(|host| !host.is_empty()))
+        };
+        if mqtt.broker.as_deref().is_some_and(|url| !url_ok(url)) {
+            mqtt.broker = None;
+            diags.push(Diagnostic::warning(
+                "settings.mqtt.broker",
+                "must be an mqtt:// or mqtts:// URL; MQTT is off",
+            ));
+        }
+        if let Some(prefix) = mqtt.topic_prefix.take() {
+            let prefix = prefix.trim_matches('/');
+            if prefix.is_empty() || prefix.contains(['+', '#']) {
+                diags.push(Diagnostic::warning(
+                    "settings.mqtt.topic_prefix",
+                    "can't be empty or contain + or #; using the default",
+                ));
+            } else {
+                mqtt.topic_prefix = Some(prefix.to_string());
+            }
+        }
+        clamp_setting(
+            &mut diags,
+            "settings.mqtt.heartbeat_secs",
+            &mut mqtt.heartbeat_secs,
+            30,
+            86_400,
+        );
+
+        let syslog = &mut settings.syslog;
+        if syslog.server.is_some() && syslog.server_addr().is_none() {
+            syslog.server = None;
+            diags.push(Diagnostic::warning(
+                "settings.syslog.server",
+                "must be an IP address, optionally with a :port; log shipping is off",
+            ));
+        }
+
+        self.apply_preset(&mut diags);
+        self.resolve_legend(&mut diags);
+        self.check_airports(&mut diags);
+        self.check_positions(&mut diags);
+        if let Some(problem) = self.wifi.credentials().and_then(|c| c.problem()) {
+            diags.push(Diagnostic::warning("wifi.auth", problem));
+        }
+        if self.wifi.bssid.is_some() && self.wifi.credentials().is_none() {
+            diags.push(Diagnostic::warning("wifi.bssid", "ignored without an ssid"));
+        }
+        self.wifi.check_static_ip(&mut diags);
+        if let Some(name) = &self.wifi.hostname {
+            if !networks::is_valid_hostname(name) {
+                diags.push(Diagnostic::warning(
+                    "wifi.hostname",
+                    format!(
+                        "\"{name}\" must be 1-63 letters, digits, or hyphens; using {}",
+                        networks::DEFAULT_HOSTNAME
+                    ),
+                ));
+                self.wifi.hostname = None;
+            }
+        }
+        self.check_home_airport(&mut diags);
+        self.check_current_limit(&mut diags);
+        self.check_profiles(&mut diags);
+        self.diagnostics = diags;
+        self.check_budget()?;
+        self.order_airports_by_led()
+    }
+
+    /// Reject configs that would exhaust the heap rather than fail at runtime.
+    fn check_budget(&self) -> Result<()> {
+        let num_leds = self.num_leds();
+        if num_leds > self.settings.max_leds {
+            return Err(Error::TooManyLeds {
+                num_leds,
+                max: self.settings.max_leds,
+            });
+        }
+        let estimated = self.estimated_ram_bytes();
+        let budget = self.settings.memory_budget_kb * 1024;
+        if estimated > budget {
+            return Err(Error::MemoryBudgetExceeded { estimated, budget });
+        }
+        Ok(())
+    }
+
+    /// Validate profile overrides, then apply the profile named in `[settings]`.
+    fn check_profiles(&mut self, diags: &mut Vec<Diagnostic>) {
+        for i in 0..self.profiles.len() {
+            let name = &self.profiles[i].name;
+            if self.profiles[..i].iter().any(|p| &p.name == name) {
+                diags.push(Diagnostic::warning(
+                    format!("profiles[{i}].name"),
+                    format!("profile {name} is defined more than once; the first is used"),
+                ));
+            }
+            if let Some(brightness) = &mut self.profiles[i].brightness {
+                clamp_setting(
+                    diags,
+                    &format!("profiles[{i}].brightness"),
+                    brightness,
+                    self.settings.min_brightness,
+                    self.settings.max_brightness,
+                );
+            }
+            if let Some(threshold) = &mut self.profiles[i].wind_threshold_kt {
+                clamp_setting(
+                    diags,
+                    &format!("profiles[{i}].wind_threshold_kt"),
+                    threshold,
+                    0,
+                    100,
+                );
+            }
+        }
+
+        let active = self.settings.profile.take();
+        if let Err(e) = self.set_profile(active.as_deref()) {
+            diags.push(Diagnostic::error("settings.profile", e.to_string()));
+        }
+    }
+
+    /// Attach `[[legend]]` colors to the airport entries that use them.
+    fn resolve_legend(&mut self, diags: &mut Vec<Diagnostic>) {
+        for (i, entry) in self.legend.iter().enumerate() {
+            let field = format!("legend[{i}].code");
+            if entry.code.is_empty() {
+                diags.push(Diagnostic::error(field, "legend code is empty"));
+            } else if self.legend[..i].iter().any(|e| e.code == entry.code) {
+                diags.push(Diagnostic::warning(
+                    field,
+                    format!("{} is defined more than once; the first is used", entry.code),
+                ));
+            } else if is_special_code(&entry.code) {
+                diags.push(Diagnostic::warning(
+                    field,
+                    format!("{} overrides the built-in special code", entry.code),
+                ));
+            }
+        }
+        for airport in &mut self.airports {
+            airport.legend_color = self
+                .legend
+                .iter()
+                .find(|e| e.code == airport.code)
+                .map(|e| Color::new(e.color[0], e.color[1], e.color[2]));
+        }
+    }
+
+    /// Replace the airport list with the selected preset, if any. A preset wins
+    /// over `[[airports]]` so it also works on top of the embedded default.
+    fn apply_preset(&mut self, diags: &mut Vec<Diagnostic>) {
+        let Some(id) = &self.settings.preset else {
+            return;
+        };
+        match presets::find_preset(id) {
+            Some(preset) => {
+                self.airports = preset
+                    .airports
+                    .iter()
+                    .map(|code| Airport {
+                        code: code.to_string(),
+                        ..Default::default()
+                    })
+                    .collect();
+            }
+            None => {
+                let known: Vec<&str> = presets::PRESETS.iter().map(|p| p.id).collect();
+                diags.push(Diagnostic::error(
+                    "settings.preset",
+                    format!("unknown preset \"{id}\" (known: {})", known.join(", ")),
+                ));
+            }
+        }
+    }
+
+    /// Warn if the strip's dark draw alone uses up `max_current_ma`.
+    fn check_current_limit(&self, diags: &mut Vec<Diagnostic>) {
+        let Some(max_ma) = self.settings.max_current_ma else {
+            return;
+        };
+        let dark_ma = self.num_leds() as u32 * power_budget::IDLE_MA;
+        if dark_ma >= power_budget::strip_budget_ma(max_ma) {
+            diags.push(Diagnostic::warning(
+                "settings.max_current_ma",
+                format!(
+                    "{} LEDs draw about {dark_ma} mA even when dark; the strip stays dark",
+                    self.num_leds()
+                ),
+            ));
+        }
+    }
+
+    fn check_home_airport(&mut self, diags: &mut Vec<Diagnostic>) {
+        if let Some(home) = &self.settings.home_airport {
+            let on_map = self.airports.iter().any(|a| a.code.eq_ignore_ascii_case(home));
+            if !on_map {
+                diags.push(Diagnostic::warning(
+                    "settings.home_airport",
+                    format!("{home} isn't on the map; it has no alerts"),
+                ));
+            }
+        } else if self.settings.buzzer.pin.is_some() {
+            diags.push(Diagnostic::warning(
+                "settings.buzzer.pin",
+                "set home_airport too; the buzzer has nothing to alert on",
+            ));
+        }
+    }
+
+    fn check_airports(&mut self, diags: &mut Vec<Diagnostic>) {
+        if self.airports.is_empty() {
+            diags.push(Diagnostic::warning("airports", "no airports configured"));
+        }
+        for (i, airport) in self.airports.iter().enumerate() {
+            let field = format!("airports[{i}].code");
+            if is_special_code(&airport.code) || airport.legend_color.is_some() {
+                continue;
+            }
+            if !looks_like_station_id(&airport.code) {
+                diags.push(Diagnostic::error(
+                    field,
+                    format!(
+                        "\"{}\" is neither a special code nor an ICAO identifier",
+                        airport.code
+                    ),
+                ));
+            } else if let Some(first) = self.airports[..i]
+                .iter()
+                .position(|a| a.code == airport.code)
+            {
+                diags.push(Diagnostic::warning(
+                    field,
+                    format!("{} is also assigned to airports[{first}]", airport.code),
+                ));
+            }
+        }
+        self.check_fallbacks(diags);
+    }
+
+    /// Drop fallbacks that can't be fetched as a METAR, so a typo isn't
+    /// requested from the weather service on every update.
+    fn check_fallbacks(&mut self, diags: &mut Vec<Diagnostic>) {
+        for (i, airport) in self.airports.iter_mut().enumerate() {
+            let Some(fallback) = &airport.fallback else {
+                continue;
+            };
+            let problem = if *fallback == airport.code {
+                "is the airport itself"
+            } else if is_special_code(fallback) || self.legend.iter().any(|e| e.code == *fallback) {
+                "is a special or legend code, not a station"
+            } else if !looks_like_station_id(fallback) {
+                "isn't an ICAO identifier"
+            } else {
+                continue;
+            };
+            diags.push(Diagnostic::warning(
+                format!("airports[{i}].fallback"),
+                format!("\"{fallback}\" {problem}; no fallback is used"),
+            ));
+            airport.fallback = None;
+        }
+    }
+
+    /// Drop coordinates that are half set or off the globe, so the map
+    /// preview doesn't plot them.
+    fn check_positions(&mut self, diags: &mut Vec<Diagnostic>) {
+        for (i, airport) in self.airports.iter_mut().enumerate() {
+            let valid = match (airport.lat, airport.lon) {
+                (None, None) => continue,
+                (Some(lat), Some(lon)) => {
+                    (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)
+                }
+                _ => false,
+            };
+            if !valid {
+                airport.lat = None;
+                airport.lon = None;
+                diags.push(Diagnostic::warning(
+                    format!("airports[{i}].lat"),
+                    "needs both lat (-90 to 90) and lon (-180 to 180); not plotted",
+                ));
+            }
+        }
+    }
+
+    /// Reorder `airports` so each entry's position is its LED index. Explicit
+    /// `led` values must be unique and within the strip (one LED per entry).
+    fn order_airports_by_led(&mut self) -> Result<()> {
+        let num_leds = self.airports.len();
+        let mut slots: Vec<Option<Airport>> = vec![None; num_leds];
+        let mut unplaced = Vec::new();
+
+        for airport in self.airports.drain(..) {
+            let Some(index) = airport.led else {
+                unplaced.push(airport);
+                continue;
+            };
+            let slot = slots
+                .get_mut(index)
+                .ok_or(Error::LedIndexOutOfBounds { index, num_leds })?;
+            if let Some(existing) = slot {
+                return Err(Error::DuplicateLedIndex {
+                    index,
+                    first: existing.code.clone(),
+                    second: airport.code,
+                });
+            }
+            *slot = Some(airport);
+        }
+
+        let mut unplaced = unplaced.into_iter();
+        self.airports = slots
+            .into_iter()
+            .enumerate()
+            .map(|(index, slot)| {
+                slot.or_else(|| unplaced.next()).ok_or(Error::UnfilledLed { index })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(())
+    }
+}
+
+/// Assembles a [`Config`] in code, with the same validation as
+/// [`Config::from_toml`]. Unset values take their TOML defaults.
+#[derive(Debug, Default)]
+pub struct ConfigBuilder {
+    settings: Settings,
+    wifi: WifiConfig,
+    airports: Vec<Airport>,
+    legend: Vec<LegendEntry>,
+    profiles: Vec<Profile>,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace all settings at once.
+    pub fn settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub fn brightness(mut self, brightness: u8) -> Self {
+        self.settings.brightness = brightness;
+        self
+    }
+
+    pub fn brightness_limits(mut self, min: u8, max: u8) -> Self {
+        self.settings.min_brightness = min;
+        self.settings.max_brightness = max;
+        self
+    }
+
+    pub fn max_current_ma(mut self, ma: u32) -> Self {
+        self.settings.max_current_ma = Some(ma);
+        self
+    }
+
+    pub fn request_interval_secs(mut self, secs: u64) -> Self {
+        self.settings.request_interval_secs = secs;
+        self
+    }
+
+    pub fn request_jitter_secs(mut self, secs: u64) -> Self {
+        self.settings.request_jitter_secs = secs;
+        self
+    }
+
+    pub fn active_interval_secs(mut self, secs: u64) -> Self {
+        self.settings.active_interval_secs = Some(secs);
+        self
+    }
+
+    pub fn wind_threshold_kt(mut self, kt: u32) -> Self {
+        self.settings.wind_threshold_kt = kt;
+        self
+    }
+
+    pub fn gust_threshold_kt(mut self, kt: u32) -> Self {
+        self.settings.gust_threshold_kt = Some(kt);
+        self
+    }
+
+    pub fn do_lightning(mut self, enabled: bool) -> Self {
+        self.settings.do_lightning = enabled;
+        self
+    }
+
+    pub fn do_winds(mut self, enabled: bool) -> Self {
+        self.settings.do_winds = enabled;
+        self
+    }
+
+    pub fn do_fog_risk(mut self, enabled: bool) -> Self {
+        self.settings.do_fog_risk = enabled;
+        self
+    }
+
+    pub fn offline_demo(mut self, enabled: bool) -> Self {
+        self.settings.offline_demo = enabled;
+        self
+    }
+
+    pub fn attract_mode(mut self, enabled: bool) -> Self {
+        self.settings.attract_mode = enabled;
+        self
+    }
+
+    pub fn data_pin(mut self, pin: u8) -> Self {
+        self.settings.data_pin = pin;
+        self
+    }
+
+    pub fn button_pin(mut self, pin: u8) -> Self {
+        self.settings.button_pin_setting = Some(pin);
+        self
+    }
+
+    pub fn weak_signal_dbm(mut self, dbm: i8) -> Self {
+        self.settings.weak_signal_dbm = dbm;
+        self
+    }
+
+    pub fn display_mode(mut self, mode: DisplayMode) -> Self {
+        self.settings.display_mode = mode;
+        self
+    }
+
+    pub fn winds_aloft(mut self, winds_aloft: WindsAloftSettings) -> Self {
+        self.settings.winds_aloft = winds_aloft;
+        self
+    }
+
+    pub fn lightning(mut self, lightning: LightningSettings) -> Self {
+        self.settings.lightning = lightning;
+        self
+    }
+
+    pub fn led(mut self, led: LedSettings) -> Self {
+        self.settings.led = led;
+        self
+    }
+
+    pub fn provisioning(mut self, provisioning: ProvisioningSettings) -> Self {
+        self.settings.provisioning = provisioning;
+        self
+    }
+
+    pub fn ota(mut self, ota: OtaSettings) -> Self {
+        self.settings.ota = ota;
+        self
+    }
+
+    pub fn mqtt(mut self, mqtt: MqttSettings) -> Self {
+        self.settings.mqtt = mqtt;
+        self
+    }
+
+    pub fn home_airport(mut self, code: &str) -> Self {
+        self.settings.home_airport = Some(code.to_string());
+        self
+    }
+
+    pub fn buzzer(mut self, buzzer: BuzzerSettings) -> Self {
+        self.settings.buzzer = buzzer;
+        self
+    }
+
+    pub fn oled(mut self, oled: OledSettings) -> Self {
+        self.settings.oled = oled;
+        self
+    }
+
+    pub fn display_off(mut self, display_off: DisplayOffSettings) -> Self {
+        self.settings.display_off = display_off;
+        self
+    }
+
+    pub fn user_button(mut self, user_button: UserButtonSettings) -> Self {
+        self.settings.user_button = user_button;
+        self
+    }
+
+    pub fn syslog(mut self, syslog: SyslogSettings) -> Self {
+        self.settings.syslog = syslog;
+        self
+    }
+
+    pub fn http(mut self, http: HttpSettings) -> Self {
+        self.settings.http = http;
+        self
+    }
+
+    pub fn power(mut self, power: PowerSettings) -> Self {
+        self.settings.power = power;
+        self
+    }
+
+    pub fn timezone(mut self, tz: impl Into<String>) -> Self {
+        self.settings.timezone = tz.into();
+        self
+    }
+
+    pub fn log_level(mut self, filter: impl Into<String>) -> Self {
+        self.settings.log_level = filter.into();
+        self
+    }
+
+    pub fn wifi(mut self, ssid: impl Into<String>, password: impl Into<String>) -> Self {
+        self.wifi = WifiConfig {
+            ssid: Some(ssid.into()),
+            password: Some(password.into()),
+            ..Default::default()
+        };
+        self
+    }
+
+    /// Set the auth method for the network given to [`ConfigBuilder::wifi`].
+    pub fn wifi_auth(mut self, auth: WifiAuth, username: Option<&str>) -> Self {
+        self.wifi.auth = Some(auth);
+        self.wifi.username = username.map(str::to_string);
+        self
+    }
+
+    /// Pin the network given to [`ConfigBuilder::wifi`] to one access point.
+    pub fn wifi_bssid(mut self, bssid: Bssid) -> Self {
+        self.wifi.bssid = Some(bssid);
+        self
+    }
+
+    /// Use a fixed address instead of DHCP.
+    pub fn wifi_static_ip(mut self, ip: Ipv4Addr, netmask: Ipv4Addr, gateway: Ipv4Addr) -> Self {
+        self.wifi.ip = Some(ip);
+        self.wifi.netmask = Some(netmask);
+        self.wifi.gateway = Some(gateway);
+        self
+    }
+
+    /// Append an airport (or special code) on the next LED.
+    pub fn airport(self, code: impl Into<String>) -> Self {
+        self.airport_entry(Airport {
+            code: code.into(),
+            ..Default::default()
+        })
+    }
+
+    /// Append a fully specified airport entry.
+    pub fn airport_entry(mut self, airport: Airport) -> Self {
+        self.airports.push(airport);
+        self
+    }
+
+    pub fn legend(mut self, code: impl Into<String>, color: Color) -> Self {
+        self.legend.push(LegendEntry {
+            code: code.into(),
+            color: [color.r, color.g, color.b],
+        });
+        self
+    }
+
+    /// Define a profile; it is not made active.
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profiles.push(profile);
+        self
+    }
+
+    /// Make the named profile active in the built config.
+    pub fn active_profile(mut self, name: impl Into<String>) -> Self {
+        self.settings.profile = Some(name.into());
+        self
+    }
+
+    /// Validate and build, exactly as `from_toml` would for the same values.
+    pub fn build(self) -> Result<Config> {
+        let mut config = Config {
+            settings: self.settings,
+            wifi: self.wifi,
+            airports: self.airports,
+            legend: self.legend,
+            profiles: self.profiles,
+            base_settings: Settings::default(),
+            chip: Chip::TARGET,
+            diagnostics: Vec::new(),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl Config {
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::new()
+    }
+}
+
+/// Clamp `value` into `min..=max`, recording a warning if it had to change.
+fn clamp_setting<T>(diags: &mut Vec<Diagnostic>, field: &str, value: &mut T, min: T, max: T)
+where
+    T: Copy + Ord + fmt::Display,
+{
+    let clamped = (*value).clamp(min, max);
+    if clamped != *value {
+        diags.push(Diagnostic::warning(
+            field,
+            format!("{} is outside {}..={}; using {}", value, min, max, clamped),
+        ));
+        *value = clamped;
+    }
+}
+
+/// Like `40, 80 or 160`.
+fn or_list(values: &[u32]) -> String {
+    match values {
+        [] => String::new(),
+        [only] => only.to_string(),
+        [rest @ .., last] => {
+            let rest: Vec<String> = rest.iter().map(u32::to_string).collect();
+            format!("{} or {last}", rest.join(", "))
+        }
+    }
+}
+
+/// A brightness as written in TOML: a raw level or a percentage string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BrightnessValue {
+    Level(i64),
+    Percent(String),
+}
+
+impl BrightnessValue {
+    fn to_level(&self) -> std::result::Result<u8, String> {
+        match self {
+            Self::Level(n) => {
+                u8::try_from(*n).map_err(|_| format!("brightness {n} is outside 0..=255"))
+            }
+            Self::Percent(s) => {
+                let pct = s
+                    .trim()
+                    .strip_suffix('%')
+                    .and_then(|p| p.trim().parse::<f32>().ok())
+                    .filter(|p| (0.0..=100.0).contains(p))
+                    .ok_or_else(|| format!("brightness \"{s}\" is not a percentage like \"40%\""))?;
+                Ok((pct * 255.0 / 100.0).round() as u8)
+            }
+        }
+    }
+}
+
+/// Read a brightness as written outside TOML, such as an MQTT payload:
+/// `0`-`255` or a percentage like `40%`.
+pub fn parse_brightness(text: &str) -> std::result::Result<u8, String> {
+    let text = text.trim();
+    match text.parse::<i64>() {
+        Ok(n) => BrightnessValue::Level(n).to_level(),
+        Err(_) => BrightnessValue::Percent(text.to_string()).to_level(),
+    }
+}
+
+fn brightness_level<'de, D>(deserializer: D) -> std::result::Result<u8, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    BrightnessValue::deserialize(deserializer)?
+        .to_level()
+        .map_err(serde::de::Error::custom)
+}
+
+fn opt_brightness_level<'de, D>(deserializer: D) -> std::result::Result<Option<u8>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    brightness_level(deserializer).map(Some)
+}
+
+/// Station identifiers are 3-4 uppercase letters or digits (e.g. KSFO, 0Q9).
+pub(crate) fn looks_like_station_id(code: &str) -> bool {
+    (3..=4).contains(&code.len())
+        && code
+            .bytes()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+}
+
+/// Special codes that are not real ICAO airport identifiers.
+pub(crate) const SPECIAL_CODES: &[&str] = &[
+    "NULL", "VFR", "MVFR", "IFR", "LIFR", "WVFR", "LTNG", "WBNK", "SUMM",
+];
+
+pub fn is_special_code(code: &str) -> bool {
+    SPECIAL_CODES.contains(&code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FULL_CONFIG: &str = r#"
+[settings]
+brightness = 50
+request_interval_secs = 300
+wind_threshold_kt = 30
+do_lightning = false
+do_winds = false
+do_fog_risk = true
+data_pin = 5
+
+[wifi]
+ssid = "TestNetwork"
+password = "TestPass123"
+
+[[airports]]
+code = "LIFR"
+
+[[airports]]
+code = "IFR"
+
+[[airports]]
+code = "MVFR"
+
+[[airports]]
+code = "VFR"
+
+[[airports]]
+code = "WVFR"
+
+[[airports]]
+code = "KSFO"
+
+[[airports]]
+code = "KLAX"
+
+[[airports]]
+code = "NULL"
+
+[[airports]]
+code = "LTNG"
+"#;
+
+    #[test]
+    fn parse_full_config() {
+        let config = Config::from_toml(FULL_CONFIG).unwrap();
+        assert_eq!(config.settings.brightness, 50);
+        assert_eq!(config.settings.request_interval_secs, 300);
+        assert_eq!(config.settings.wind_threshold_kt, 30);
+        assert!(!config.settings.do_lightning);
+        assert!(!config.settings.do_winds);
+        assert!(config.settings.do_fog_risk);
+        assert_eq!(config.settings.data_pin, 5);
+        assert_eq!(config.wifi.ssid.as_deref(), Some("TestNetwork"));
+        assert_eq!(config.wifi.password.as_deref(), Some("TestPass123"));
+        assert_eq!(config.airports.len(), 9);
+    }
+
+    #[test]
+    fn enterprise_wifi_credentials() {
+        let toml = "[wifi]\nssid = \"Campus\"\npassword = \"pw\"\nauth = \"wpa2_enterprise\"\n";
+        let config = Config::from_toml(toml).unwrap();
+        let creds = config.wifi.credentials().unwrap();
+        assert_eq!(creds.auth_method(), WifiAuth::Wpa2Enterprise);
+        assert!(config.diagnostics.iter().any(|d| d.field == "wifi.auth"));
+
+        let config = Config::from_toml(&format!("{toml}username = \"student\"\n")).unwrap();
+        assert_eq!(config.wifi.credentials().unwrap().username.as_deref(), Some("student"));
+        assert!(!config.diagnostics.iter().any(|d| d.field == "wifi.auth"));
+        assert!(Config::from_toml("").unwrap().wifi.credentials().is_none());
+    }
+
+    #[test]
+    fn bssid_and_hidden_network_options() {
+        let toml = "[wifi]\nssid = \"Mesh\"\nbssid = \"a4:2b:b0:12:3f:0e\"\n";
+        let config = Config::from_toml(toml).unwrap();
+        let creds = config.wifi.credentials().unwrap();
+        assert_eq!(creds.bssid.unwrap().to_string(), "a4:2b:b0:12:3f:0e");
+        assert!(!creds.hidden);
+        let hidden = Config::from_toml(&format!("{toml}hidden = true\n")).unwrap();
+        assert!(hidden.wifi.credentials().unwrap().hidden);
+        assert!(!config.diagnostics.iter().any(|d| d.field == "wifi.bssid"));
+
+        let config = Config::from_toml("[wifi]\nbssid = \"a4:2b:b0:12:3f:0e\"\n").unwrap();
+        assert!(config.diagnostics.iter().any(|d| d.field == "wifi.bssid"));
+        assert!(Config::from_toml("[wifi]\nssid = \"Mesh\"\nbssid = \"nearest\"\n").is_err());
+    }
+
+    #[test]
+    fn static_ip_settings() {
+        let config = Config::from_toml(
+            "[wifi]\nip = \"192.168.1.50\"\ngateway = \"192.168.1.1\"\n",
+        )
+        .unwrap();
+        let fixed = config.wifi.static_ip().unwrap();
+        assert_eq!(fixed.ip, Ipv4Addr::new(192, 168, 1, 50));
+        assert_eq!(fixed.prefix_len, 24);
+        assert_eq!(fixed.dns, fixed.gateway);
+        assert!(!config.diagnostics.iter().any(|d| d.field.starts_with("wifi.")));
+
+        let config = Config::from_toml(
+            "[wifi]\nip = \"10.0.0.5\"\nnetmask = \"255.0.255.0\"\ngateway = \"192.168.1.1\"\n",
+        )
+        .unwrap();
+        let fields: Vec<&str> = config.diagnostics.iter().map(|d| d.field.as_str()).collect();
+        assert!(fields.contains(&"wifi.netmask"));
+        assert!(fields.contains(&"wifi.gateway"));
+
+        let config = Config::from_toml("[wifi]\nip = \"10.0.0.5\"\n").unwrap();
+        assert!(config.wifi.static_ip().is_none());
+        assert!(config.diagnostics.iter().any(|d| d.field == "wifi.gateway"));
+        assert!(Config::from_toml("[wifi]\nip = \"10.0.0\"\n").is_err());
+    }
+
+    #[test]
+    fn hostname_setting() {
+        assert_eq!(Config::from_toml("").unwrap().wifi.hostname(), "led-sectional");
+        let config = Config::from_toml("[wifi]\nhostname = \"hangar-map\"\n").unwrap();
+        assert_eq!(config.wifi.hostname(), "hangar-map");
+
+        let config = Config::from_toml("[wifi]\nhostname = \"my map\"\n").unwrap();
+        assert_eq!(config.wifi.hostname(), "led-sectional");
+        assert!(config.diagnostics.iter().any(|d| d.field == "wifi.hostname"));
+    }
+
+    #[test]
+    fn parse_empty_config_uses_defaults() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config.settings.brightness, 20);
+        assert_eq!(config.settings.request_interval_secs, 900);
+        assert_eq!(config.settings.request_jitter_secs, 30);
+        assert_eq!(config.settings.wind_threshold_kt, 25);
+        assert!(config.settings.do_lightning);
+        assert!(config.settings.do_winds);
+        assert!(!config.settings.do_fog_risk);
+        assert!(config.settings.offline_demo);
+        assert!(!config.settings.attract_mode);
+        assert_eq!(config.settings.data_pin, 2);
+        assert!(config.wifi.ssid.is_none());
+        assert!(config.wifi.password.is_none());
+        assert!(config.airports.is_empty());
+    }
+
+    #[test]
+    fn parse_partial_config() {
+        let toml = r#"
+[settings]
+brightness = 100
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.settings.brightness, 100);
+        // Other fields should be defaults
+        assert_eq!(config.settings.request_interval_secs, 900);
+        assert!(config.settings.do_lightning);
+    }
+
+    #[test]
+    fn to_toml_round_trips() {
+        let mut config = Config::from_toml(FULL_CONFIG).unwrap();
+        config.airports[5].fallback = Some("KOAK".to_string());
+        config.settings.display_mode = DisplayMode::WindsAloft;
+
+        let reparsed = Config::from_toml(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(reparsed.settings.brightness, 50);
+        assert!(reparsed.settings.do_fog_risk);
+        assert_eq!(reparsed.settings.display_mode, DisplayMode::WindsAloft);
+        assert_eq!(reparsed.wifi.ssid.as_deref(), Some("TestNetwork"));
+        assert_eq!(reparsed.airports.len(), 9);
+        assert_eq!(reparsed.airports[5].fallback.as_deref(), Some("KOAK"));
+        assert!(reparsed.airports[6].fallback.is_none());
+    }
+
+    #[test]
+    fn num_leds() {
+        let config = Config::from_toml(FULL_CONFIG).unwrap();
+        assert_eq!(config.num_leds(), 9);
+    }
+
+    #[test]
+    fn metar_airport_codes_filters_special() {
+        let config = Config::from_toml(FULL_CONFIG).unwrap();
+        let codes = config.metar_airport_codes();
+        assert_eq!(codes, vec!["KSFO", "KLAX"]);
+    }
+
+    #[test]
+    fn metar_airport_codes_empty() {
+        let toml = r#"
+[[airports]]
+code = "NULL"
+
+[[airports]]
+code = "VFR"
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert!(config.metar_airport_codes().is_empty());
+    }
+
+    #[test]
+    fn metar_airport_codes_includes_fallbacks() {
+        let toml = r#"
+[[airports]]
+code = "KSFO"
+
+[[airports]]
+code = "KHAF"
+fallback = "KSQL"
+
+[[airports]]
+code = "KOAK"
+fallback = "KSFO"
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.airports[1].fallback.as_deref(), Some("KSQL"));
+        assert!(config.airports[0].fallback.is_none());
+        assert_eq!(config.metar_airport_codes(), vec!["KSFO", "KHAF", "KOAK", "KSQL"]);
+    }
+
+    #[test]
+    fn unusable_fallbacks_are_dropped() {
+        let toml = r#"
+[[airports]]
+code = "KSFO"
+fallback = "koak"
+
+[[airports]]
+code = "KHAF"
+fallback = "LIFR"
+
+[[airports]]
+code = "KOAK"
+fallback = "KOAK"
+
+[[airports]]
+code = "KSQL"
+fallback = "KPAO"
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        let fields: Vec<&str> = config.diagnostics.iter().map(|d| d.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec!["airports[0].fallback", "airports[1].fallback", "airports[2].fallback"]
+        );
+        assert!(config.diagnostics[2].message.contains("the airport itself"));
+        assert_eq!(config.airports[3].fallback.as_deref(), Some("KPAO"));
+        assert_eq!(config.metar_airport_codes(), vec!["KSFO", "KHAF", "KOAK", "KSQL", "KPAO"]);
+    }
+
+    #[test]
+    fn explicit_led_indices_reorder_airports() {
+        let toml = r#"
+[[airports]]
+code = "KSFO"
+led = 2
+name = "San Francisco"
+
+[[airports]]
+code = "KOAK"
+
+[[airports]]
+code = "KSJC"
+led = 0
+
+[[airports]]
+code = "KHWD"
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        let codes: Vec<&str> = config.airports.iter().map(|a| a.code.as_str()).collect();
+        assert_eq!(codes, vec!["KSJC", "KOAK", "KSFO", "KHWD"]);
+        assert_eq!(config.airports[2].display_name(), "San Francisco");
+        assert_eq!(config.airports[1].display_name(), "KOAK");
+    }
+
+    #[test]
+    fn duplicate_led_index_rejected() {
+        let toml = r#"
+[[airports]]
+code = "KSFO"
+led = 1
+
+[[airports]]
+code = "KOAK"
+led = 1
+"#;
+        let err = Config::from_toml(toml).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DuplicateLedIndex { index: 1, ref first, ref second }
+                if first == "KSFO" && second == "KOAK"
+        ));
+    }
+
+    #[test]
+    fn out_of_range_led_index_rejected() {
+        let toml = r#"
+[[airports]]
+code = "KSFO"
+led = 0
+
+[[airports]]
+code = "KOAK"
+led = 2
+"#;
+        let err = Config::from_toml(toml).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::LedIndexOutOfBounds { index: 2, num_leds: 2 }
+        ));
+    }
+
+    #[test]
+    fn valid_config_has_no_diagnostics() {
+        let config = Config::from_toml(FULL_CONFIG).unwrap();
+        assert!(config.diagnostics.is_empty());
+        assert!(!config.has_errors());
+    }
+
+    #[test]
+    fn clamped_values_are_reported() {
+        let toml = r#"
+[settings]
+request_interval_secs = 10
+brightness = 0
+
+[settings.winds_aloft]
+altitude_ft = 7000
+
+[[airports]]
+code = "KSFO"
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        let fields: Vec<&str> = config.diagnostics.iter().map(|d| d.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "settings.request_interval_secs",
+                "settings.brightness",
+                "settings.winds_aloft.altitude_ft",
+            ]
+        );
+        assert!(config.diagnostics.iter().all(|d| d.severity == Severity::Warning));
+        assert_eq!(
+            config.diagnostics[0].to_string(),
+            "warning: settings.request_interval_secs: 10 is outside 60..=3600; using 60"
+        );
+    }
+
+    #[test]
+    fn parse_led_settings() {
+        let toml = r#"
+[settings.led]
+rmt_channel = 3
+invert = true
+reset_us = 1000
+"#;
+        let config = Config::from_toml_for(toml, Chip::Esp32S3).unwrap();
+        let led = &config.settings.led;
+        assert_eq!(led.rmt_channel, 3);
+        assert!(led.invert);
+        assert_eq!(led.reset_us, 400);
+        assert_eq!(config.diagnostics[0].field, "settings.led.reset_us");
+
+        // The C3 only transmits on channels 0 and 1
+        let config = Config::from_toml_for(toml, Chip::Esp32C3).unwrap();
+        assert_eq!(config.settings.led.rmt_channel, 0);
+        assert_eq!(
+            config.diagnostics[0].to_string(),
+            "warning: settings.led.rmt_channel: the ESP32-C3 transmits on RMT channels 0-1; \
+             using 0"
+        );
+
+        let defaults = Config::from_toml("").unwrap().settings.led;
+        assert_eq!((defaults.rmt_channel, defaults.invert, defaults.reset_us), (0, false, 300));
+    }
+
+    #[test]
+    fn button_pin_setting() {
+        assert_eq!(Config::from_toml("").unwrap().settings.button_pin, 9);
+
+        let config = Config::from_toml("[settings]\nbutton_pin = 40\n").unwrap();
+        assert_eq!(config.settings.button_pin, 21);
+
+        let config = Config::from_toml("[settings]\nbutton_pin = 2\n").unwrap();
+        assert!(config
+            .diagnostics
+            .iter()
+            .any(|d| d.field == "settings.button_pin" && d.message.contains("data_pin")));
+    }
+
+    #[test]
+    fn validates_for_the_chip_asked_for() {
+        let toml = "[settings]\ndata_pin = 40\n\n[settings.user_button]\npin = 45\n";
+        let s3 = Config::from_toml_for(toml, Chip::Esp32S3).unwrap();
+        assert_eq!(s3.settings.button_pin, 0);
+        assert_eq!(s3.settings.user_button.pin, Some(45));
+        assert!(
+            s3.diagnostics.iter().all(|d| !d.field.starts_with("settings")),
+            "{:?}",
+            s3.diagnostics
+        );
+        // The default isn't written out, so it follows the chip on reload
+        assert!(!s3.to_toml().unwrap().contains("button_pin"));
+
+        let c3 = Config::from_toml_for(toml, Chip::Esp32C3).unwrap();
+        assert_eq!(c3.settings.button_pin, 9);
+        assert_eq!(c3.settings.user_button.pin, None);
+    }
+
+    #[test]
+    fn weak_signal_setting_and_indicator_leds() {
+        let config = Config::from_toml(
+            r#"
+[settings]
+weak_signal_dbm = -20
+
+[[legend]]
+code = "HOME"
+color = [255, 0, 255]
+
+[[airports]]
+code = "KSFO"
+
+[[airports]]
+code = "VFR"
+
+[[airports]]
+code = "NULL"
+
+[[airports]]
+code = "HOME"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.settings.weak_signal_dbm, -30);
+        assert_eq!(config.indicator_led_indices(), [1, 3]);
+        assert_eq!(Config::from_toml("").unwrap().settings.weak_signal_dbm, -75);
+    }
+
+    #[test]
+    fn provisioning_settings() {
+        let defaults = Config::from_toml("").unwrap().settings.provisioning;
+        assert_eq!(defaults.method, ProvisioningMethod::Portal);
+        assert!(defaults.pop.is_none());
+
+        let config = Config::from_toml("[settings.provisioning]\nmethod = \"ble\"\npop = \"\"\n");
+        let config = config.unwrap();
+        assert_eq!(config.settings.provisioning.method, ProvisioningMethod::Ble);
+        assert!(config.settings.provisioning.pop.is_none());
+        assert!(config
+            .diagnostics
+            .iter()
+            .any(|d| d.field == "settings.provisioning.pop"));
+
+        let toml = "[settings.provisioning]\nmethod = \"ble\"\npop = \"hangar42\"\n";
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.settings.provisioning.pop.as_deref(), Some("hangar42"));
+        assert!(Config::from_toml("[settings.provisioning]\nmethod = \"nfc\"\n").is_err());
+
+        let toml = "[settings.provisioning]\nmethod = \"ble\"\npop = \"x\"\nsmartconfig = true\nwps = true\n";
+        let config = Config::from_toml(toml).unwrap();
+        assert!(config
+            .diagnostics
+            .iter()
+            .any(|d| d.field == "settings.provisioning.smartconfig"));
+        assert!(config.settings.provisioning.wps);
+        assert!(config
+            .diagnostics
+            .iter()
+            .any(|d| d.field == "settings.provisioning.wps"));
+
+        let toml = "[settings.provisioning]\nap_password = \"hangar42\"\n";
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.settings.provisioning.ap_password.as_deref(), Some("hangar42"));
+        let config = Config::from_toml("[settings.provisioning]\nap_password = \"abc\"\n").unwrap();
+        assert!(config.settings.provisioning.ap_password.is_none());
+        assert!(config
+            .diagnostics
+            .iter()
+            .any(|d| d.field == "settings.provisioning.ap_password"));
+    }
+
+    #[test]
+    fn log_level_falls_back_to_info() {
+        let config = Config::builder().log_level("warn,wifi=debug").build().unwrap();
+        let warned = |config: &Config| {
+            config.diagnostics.iter().any(|d| d.field == "settings.log_level")
+        };
+        assert_eq!(config.settings.log_filter().to_string(), "warn,wifi=debug");
+        assert!(!warned(&config));
+
+        let config = Config::from_toml("[settings]\nlog_level = \"wifi=loud\"\n").unwrap();
+        assert_eq!(config.settings.log_filter(), LogFilter::default());
+        assert!(warned(&config));
+        assert!(!config.has_errors());
+    }
+
+    #[test]
+    fn cpu_frequencies_are_checked() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config.settings.power, PowerSettings::default());
+        assert_eq!(config.settings.power.min_cpu_mhz, 40);
+
+        let toml = "[settings.power]\nmax_cpu_mhz = 80\nmin_cpu_mhz = 160\nlight_sleep = true\n";
+        let config = Config::from_toml(toml).unwrap();
+        let power = &config.settings.power;
+        assert_eq!((power.max_cpu_mhz, power.min_cpu_mhz), (80, 40));
+        assert!(power.light_sleep);
+        let warned = |field: &str| config.diagnostics.iter().any(|d| d.field == field);
+        assert!(warned("settings.power.min_cpu_mhz"));
+        assert!(!warned("settings.power.max_cpu_mhz"));
+
+        let config = Config::from_toml("[settings.power]\nmax_cpu_mhz = 240\n").unwrap();
+        assert_eq!(config.settings.power.max_cpu_mhz, 160);
+        assert!(!config.has_errors());
+    }
+
+    #[test]
+    fn ota_manifest_must_be_https() {
+        let url = "https://example.com/led-sectional/manifest.json";
+        let config = Config::from_toml(&format!("[settings.ota]\nmanifest_url = \"{url}\"\n"))
+            .unwrap();
+        assert_eq!(config.settings.ota.manifest_url.as_deref(), Some(url));
+
+        let toml = "[settings.ota]\nmanifest_url = \"http://example.com/manifest.json\"\n";
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.settings.ota.manifest_url, None);
+        assert!(config
+            .diagnostics
+            .iter()
+            .any(|d| d.field == "settings.ota.manifest_url"));
+    }
+
+    #[test]
+    fn cors_origins_drop_invalid_entries() {
+        let toml = r#"
+[settings.http]
+cors_origins = ["https://app.example.com", "app.example.com", "http://10.0.0.5:8080/"]
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.settings.http.cors_origins, ["https://app.example.com"]);
+        let warnings = config
+            .diagnostics
+            .iter()
+            .filter(|d| d.field == "settings.http.cors_origins")
+            .count();
+        assert_eq!(warnings, 2);
+        assert!(!config.has_errors());
+    }
+
+    #[test]
+    fn user_button_maps_presses() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config.settings.user_button.pin, None);
+        assert_eq!(config.settings.user_button.action(Press::Short), ButtonAction::CycleBrightness);
+
+        let toml = "[settings.user_button]\npin = 4\ndouble_press = \"none\"\n\
+                    long_press = \"next_mode\"\n";
+        let buttons = Config::from_toml(toml).unwrap().settings.user_button;
+        assert_eq!(buttons.pin, Some(4));
+        assert_eq!(buttons.action(Press::Double), ButtonAction::None);
+        assert_eq!(buttons.action(Press::Long), ButtonAction::NextMode);
 
-fn default_brightness() -> u8 {
-    20
-}
-fn default_request_interval() -> u64 {
-    900
-}
-fn default_wind_threshold() -> u32 {
-    25
-}
-fn default_true() -> bool {
-    true
-}
-fn default_data_pin() -> u8 {
-    2
-}
+        let config = Config::from_toml("[settings.user_button]\npin = 9\n").unwrap();
+        assert_eq!(config.settings.user_button.pin, None);
+        assert!(config
+            .diagnostics
+            .iter()
+            .any(|d| d.field == "settings.user_button.pin" && d.message.contains("button_pin")));
+        assert!(Config::from_toml("[settings.user_button]\nlong_press = \"reboot\"\n").is_err());
+    }
 
-impl Default for Settings {
-    fn default() -> Self {
-        Self {
-            brightness: default_brightness(),
-            request_interval_secs: default_request_interval(),
-            wind_threshold_kt: default_wind_threshold(),
-            do_lightning: default_true(),
-            do_winds: default_true(),
-            data_pin: default_data_pin(),
+    #[test]
+    fn buzzer_needs_a_home_airport_on_the_map() {
+        let buzzer = BuzzerSettings {
+            pin: Some(5),
+            quiet_hours: Some("22:00-07:00".into()),
+        };
+        let config = Config::builder()
+            .airport("KSFO")
+            .home_airport("ksfo")
+            .buzzer(buzzer.clone())
+            .build()
+            .unwrap();
+        assert_eq!(config.settings.buzzer, buzzer);
+        assert!(config.settings.buzzer.quiet_window().is_some());
+        assert!(!config.diagnostics.iter().any(|d| d.field.starts_with("settings.buzzer")));
+
+        let toml = "[[airports]]\ncode = \"KSFO\"\n[settings]\nhome_airport = \"KOAK\"\n\
+                    [settings.buzzer]\npin = 9\nquiet_hours = \"late\"\n";
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.settings.buzzer, BuzzerSettings::default());
+        for field in [
+            "settings.home_airport",
+            "settings.buzzer.pin",
+            "settings.buzzer.quiet_hours",
+        ] {
+            assert!(config.diagnostics.iter().any(|d| d.field == field), "{field}");
         }
     }
-}
 
-impl Config {
-    pub fn from_toml(s: &str) -> Result<Self> {
-        let mut config: Config = toml::from_str(s)?;
-        config.validate();
-        Ok(config)
+    #[test]
+    fn oled_needs_two_free_pins() {
+        let oled = OledSettings {
+            sda: Some(5),
+            scl: Some(6),
+            controller: Controller::Sh1106,
+            ..Default::default()
+        };
+        let config = Config::builder().airport("KSFO").oled(oled.clone()).build().unwrap();
+        assert_eq!(config.settings.oled.pins(), Some((5, 6)));
+        assert_eq!(config.settings.oled.address, 0x3C);
+        assert!(!config.diagnostics.iter().any(|d| d.field == "settings.oled"));
+
+        for toml in [
+            "[settings.oled]\nsda = 5\n",
+            "[settings.oled]\nsda = 5\nscl = 5\n",
+            "[settings.buzzer]\npin = 6\n[settings.oled]\nsda = 5\nscl = 6\n",
+            "[settings.oled]\nsda = 5\nscl = 6\naddress = 200\n",
+        ] {
+            let config = Config::from_toml(toml).unwrap();
+            assert_eq!(config.settings.oled.pins(), None, "{toml}");
+            assert!(config.diagnostics.iter().any(|d| d.field == "settings.oled"), "{toml}");
+        }
     }
 
-    pub fn num_leds(&self) -> usize {
-        self.airports.len()
+    #[test]
+    fn display_off_windows_are_checked() {
+        let display_off = DisplayOffSettings {
+            windows: vec!["23:00-06:00".into()],
+            wake_secs: 60,
+            motion_pin: Some(4),
+        };
+        let config = Config::builder()
+            .airport("KSFO")
+            .display_off(display_off.clone())
+            .build()
+            .unwrap();
+        assert_eq!(config.settings.display_off, display_off);
+        assert_eq!(config.settings.display_off.time_windows().len(), 1);
+        assert!(!config.diagnostics.iter().any(|d| d.field.starts_with("settings.display_off")));
+
+        let toml = "[settings.user_button]\npin = 4\n[settings.display_off]\n\
+                    windows = [\"late\", \"12:00-13:00\"]\nwake_secs = 1\nmotion_pin = 4\n";
+        let config = Config::from_toml(toml).unwrap();
+        let display_off = &config.settings.display_off;
+        assert_eq!(display_off.windows, ["12:00-13:00"]);
+        assert_eq!(display_off.wake_secs, 10);
+        assert_eq!(display_off.motion_pin, None);
+        for field in ["windows", "wake_secs", "motion_pin"] {
+            let field = format!("settings.display_off.{field}");
+            assert!(config.diagnostics.iter().any(|d| d.field == field), "{field}");
+        }
     }
 
-    /// Returns only real ICAO airport codes, filtering out special codes.
-    pub fn metar_airport_codes(&self) -> Vec<&str> {
-        self.airports
+    #[test]
+    fn syslog_server_must_be_an_address() {
+        let config = Config::from_toml("[settings.syslog]\nserver = \"10.0.0.2\"\n").unwrap();
+        let addr = config.settings.syslog.server_addr();
+        assert_eq!(addr, Some("10.0.0.2:514".parse().unwrap()));
+
+        let config = Config::from_toml("[settings.syslog]\nserver = \"logs.local\"\n").unwrap();
+        assert_eq!(config.settings.syslog.server, None);
+        assert!(config
+            .diagnostics
             .iter()
-            .filter_map(|a| {
-                if is_special_code(&a.code) {
-                    None
-                } else {
-                    Some(a.code.as_str())
-                }
-            })
-            .collect()
+            .any(|d| d.field == "settings.syslog.server"));
+        assert!(Config::from_toml("").unwrap().settings.syslog.server_addr().is_none());
     }
 
-    fn validate(&mut self) {
-        self.settings.request_interval_secs =
-            self.settings.request_interval_secs.clamp(60, 3600);
-        self.settings.wind_threshold_kt =
-            self.settings.wind_threshold_kt.clamp(0, 100);
+    #[test]
+    fn mqtt_settings_are_checked() {
+        let toml = r#"
+[settings.mqtt]
+broker = "mqtt://192.168.1.10:1883"
+topic_prefix = "/home/sectional/"
+heartbeat_secs = 5
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        let mqtt = &config.settings.mqtt;
+        assert_eq!(mqtt.broker.as_deref(), Some("mqtt://192.168.1.10:1883"));
+        assert_eq!(mqtt.topic_prefix.as_deref(), Some("home/sectional"));
+        assert_eq!(mqtt.heartbeat_secs, 30);
+
+        let toml = "[settings.mqtt]\nbroker = \"tcp://broker\"\ntopic_prefix = \"a/#\"\n";
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.settings.mqtt.broker, None);
+        assert_eq!(config.settings.mqtt.topic_prefix, None);
+        assert_eq!(config.settings.mqtt.heartbeat_secs, 300);
+        let fields: Vec<&str> = config.diagnostics.iter().map(|d| d.field.as_str()).collect();
+        assert!(fields.contains(&"settings.mqtt.broker"));
+        assert!(fields.contains(&"settings.mqtt.topic_prefix"));
     }
-}
 
-/// Special codes that are not real ICAO airport identifiers.
-const SPECIAL_CODES: &[&str] = &["NULL", "VFR", "MVFR", "IFR", "LIFR", "WVFR", "LTNG", "WBNK"];
+    #[test]
+    fn airport_positions_need_both_coordinates_in_range() {
+        let toml = r#"
+[[airports]]
+code = "KSFO"
+lat = 37.619
+lon = -122.375
 
-pub fn is_special_code(code: &str) -> bool {
-    SPECIAL_CODES.contains(&code)
-}
+[[airports]]
+code = "KOAK"
+lat = 37.721
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+[[airports]]
+code = "KSJC"
+lat = 37.363
+lon = -221.929
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.airports[0].position(), Some((37.619, -122.375)));
+        assert_eq!(config.airports[1].lat, None);
+        assert_eq!(config.airports[2].position(), None);
+        for field in ["airports[1].lat", "airports[2].lat"] {
+            assert!(config.diagnostics.iter().any(|d| d.field == field), "{field}");
+        }
+        assert!(!config.diagnostics.iter().any(|d| d.field == "airports[0].lat"));
+    }
 
-    const FULL_CONFIG: &str = r#"
+    #[test]
+    fn parse_brightness_percentages() {
+        let toml = r#"
 [settings]
-brightness = 50
-request_interval_secs = 300
-wind_threshold_kt = 30
-do_lightning = false
-do_winds = false
-data_pin = 5
+brightness = "40%"
+min_brightness = "5%"
+max_brightness = 200
 
-[wifi]
-ssid = "TestNetwork"
-password = "TestPass123"
+[[profiles]]
+name = "night"
+brightness = "1%"
+"#;
+        let mut config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.settings.brightness, 102);
+        assert_eq!(config.settings.min_brightness, 13);
+        assert_eq!(config.settings.max_brightness, 200);
+        // The profile's 1% (3) is raised to the floor
+        assert_eq!(config.profiles[0].brightness, Some(13));
+        config.set_profile(Some("night")).unwrap();
+        assert_eq!(config.settings.brightness, 13);
+
+        assert!(Config::from_toml("[settings]\nbrightness = \"150%\"\n").is_err());
+        assert!(Config::from_toml("[settings]\nbrightness = \"bright\"\n").is_err());
+        assert!(Config::from_toml("[settings]\nbrightness = 300\n").is_err());
+
+        assert_eq!(parse_brightness(" 200 "), Ok(200));
+        assert_eq!(parse_brightness("40%"), Ok(102));
+        assert!(parse_brightness("-1").is_err());
+        assert!(parse_brightness("dim").is_err());
+    }
+
+    #[test]
+    fn brightness_clamped_to_limits() {
+        let toml = r#"
+[settings]
+brightness = 250
+min_brightness = 50
+max_brightness = 180
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.settings.brightness, 180);
+        assert_eq!(config.settings.limit_brightness(0), 50);
+        assert_eq!(config.diagnostics[0].field, "settings.brightness");
+
+        let inverted = Config::from_toml("[settings]\nmin_brightness = 90\nmax_brightness = 30\n")
+            .unwrap();
+        assert_eq!(inverted.settings.min_brightness, 30);
+        assert_eq!(inverted.settings.brightness, 30);
+    }
 
+    #[test]
+    fn airport_problems_are_reported() {
+        let toml = r#"
 [[airports]]
-code = "LIFR"
+code = "KSFO"
 
 [[airports]]
-code = "IFR"
+code = "SUMMARY"
 
 [[airports]]
-code = "MVFR"
+code = "KSFO"
 
 [[airports]]
+code = "ksjc"
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert!(config.has_errors());
+        let found: Vec<(Severity, &str)> = config
+            .diagnostics
+            .iter()
+            .map(|d| (d.severity, d.field.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Severity::Error, "airports[1].code"),
+                (Severity::Warning, "airports[2].code"),
+                (Severity::Error, "airports[3].code"),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_airport_list_is_reported() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config.diagnostics.len(), 1);
+        assert_eq!(config.diagnostics[0].field, "airports");
+    }
+
+    fn airports_toml(count: usize) -> String {
+        (0..count)
+            .map(|i| format!("[[airports]]\ncode = \"K{:03}\"\n", i))
+            .collect()
+    }
+
+    #[test]
+    fn too_many_leds_rejected() {
+        let toml = format!("[settings]\nmax_leds = 3\n{}", airports_toml(4));
+        let err = Config::from_toml(&toml).unwrap_err();
+        assert!(matches!(err, Error::TooManyLeds { num_leds: 4, max: 3 }));
+        assert!(Config::from_toml(&airports_toml(4)).is_ok());
+    }
+
+    #[test]
+    fn memory_budget_enforced() {
+        let config = Config::from_toml(&airports_toml(10)).unwrap();
+        assert_eq!(
+            config.estimated_ram_bytes(),
+            10 * (RAM_BYTES_PER_LED + RAM_BYTES_PER_STATION)
+        );
+
+        let toml = format!("[settings]\nmemory_budget_kb = 4\n{}", airports_toml(10));
+        let err = Config::from_toml(&toml).unwrap_err();
+        assert!(matches!(err, Error::MemoryBudgetExceeded { budget: 4096, .. }));
+    }
+
+    #[test]
+    fn current_limit_must_leave_room_for_the_strip() {
+        let config = Config::builder().airport("KSFO").max_current_ma(2_000).build().unwrap();
+        assert_eq!(config.settings.max_current_ma, Some(2_000));
+        assert!(config.diagnostics.is_empty());
+
+        let config = Config::from_toml("[settings]\nmax_current_ma = 250\n").unwrap();
+        assert_eq!(config.settings.max_current_ma, None);
+        assert_eq!(config.diagnostics[0].field, "settings.max_current_ma");
+
+        let toml = format!("[settings]\nmax_current_ma = 350\n{}", airports_toml(60));
+        let config = Config::from_toml(&toml).unwrap();
+        assert_eq!(config.settings.max_current_ma, Some(350));
+        assert!(config.diagnostics.iter().any(|d| d.message.contains("stays dark")));
+    }
+
+    #[test]
+    fn default_budget_rejects_huge_config() {
+        assert!(Config::from_toml(&airports_toml(500)).is_err());
+    }
+
+    #[test]
+    fn legend_codes_resolve_to_colors() {
+        let toml = r#"
+[[legend]]
+code = "SOCK"
+color = [255, 128, 0]
+
+[[legend]]
 code = "VFR"
+color = [0, 64, 0]
 
 [[airports]]
-code = "WVFR"
+code = "SOCK"
 
 [[airports]]
-code = "KSFO"
+code = "VFR"
 
 [[airports]]
-code = "KLAX"
+code = "KSFO"
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.airports[0].legend_color, Some(Color::new(255, 128, 0)));
+        assert_eq!(config.airports[1].legend_color, Some(Color::new(0, 64, 0)));
+        assert!(config.airports[2].legend_color.is_none());
+        assert_eq!(config.metar_airport_codes(), vec!["KSFO"]);
+        // Only the override of a built-in code is worth mentioning
+        assert_eq!(config.diagnostics.len(), 1);
+        assert_eq!(config.diagnostics[0].field, "legend[1].code");
+    }
 
-[[airports]]
-code = "NULL"
+    const PROFILES: &str = r#"
+[settings]
+brightness = 40
+profile = "night"
 
-[[airports]]
-code = "LTNG"
+[[profiles]]
+name = "night"
+brightness = 5
+do_lightning = false
+
+[[profiles]]
+name = "demo"
+display_mode = "winds_aloft"
 "#;
 
     #[test]
-    fn parse_full_config() {
-        let config = Config::from_toml(FULL_CONFIG).unwrap();
-        assert_eq!(config.settings.brightness, 50);
-        assert_eq!(config.settings.request_interval_secs, 300);
-        assert_eq!(config.settings.wind_threshold_kt, 30);
+    fn active_profile_applied_at_load() {
+        let config = Config::from_toml(PROFILES).unwrap();
+        assert_eq!(config.active_profile(), Some("night"));
+        assert_eq!(config.settings.brightness, 5);
         assert!(!config.settings.do_lightning);
-        assert!(!config.settings.do_winds);
-        assert_eq!(config.settings.data_pin, 5);
-        assert_eq!(config.wifi.ssid.as_deref(), Some("TestNetwork"));
-        assert_eq!(config.wifi.password.as_deref(), Some("TestPass123"));
-        assert_eq!(config.airports.len(), 9);
+        assert_eq!(config.settings.display_mode, DisplayMode::Metar);
     }
 
     #[test]
-    fn parse_empty_config_uses_defaults() {
-        let config = Config::from_toml("").unwrap();
-        assert_eq!(config.settings.brightness, 20);
-        assert_eq!(config.settings.request_interval_secs, 900);
-        assert_eq!(config.settings.wind_threshold_kt, 25);
+    fn set_profile_restores_base_settings() {
+        let mut config = Config::from_toml(PROFILES).unwrap();
+        config.set_profile(Some("demo")).unwrap();
+        assert_eq!(config.settings.brightness, 40);
         assert!(config.settings.do_lightning);
-        assert!(config.settings.do_winds);
-        assert_eq!(config.settings.data_pin, 2);
-        assert!(config.wifi.ssid.is_none());
-        assert!(config.wifi.password.is_none());
-        assert!(config.airports.is_empty());
+        assert_eq!(config.settings.display_mode, DisplayMode::WindsAloft);
+
+        config.set_profile(None).unwrap();
+        assert_eq!(config.active_profile(), None);
+        assert_eq!(config.settings.display_mode, DisplayMode::Metar);
+
+        assert!(matches!(
+            config.set_profile(Some("party")),
+            Err(Error::UnknownProfile(ref name)) if name == "party"
+        ));
+        assert_eq!(config.active_profile(), None);
     }
 
     #[test]
-    fn parse_partial_config() {
-        let toml = r#"
-[settings]
-brightness = 100
-"#;
-        let config = Config::from_toml(toml).unwrap();
-        assert_eq!(config.settings.brightness, 100);
-        // Other fields should be defaults
-        assert_eq!(config.settings.request_interval_secs, 900);
-        assert!(config.settings.do_lightning);
+    fn cycle_profile_wraps_to_base() {
+        let mut config = Config::from_toml(PROFILES).unwrap();
+        assert_eq!(config.cycle_profile().unwrap(), Some("demo"));
+        assert_eq!(config.cycle_profile().unwrap(), None);
+        assert_eq!(config.cycle_profile().unwrap(), Some("night"));
     }
 
     #[test]
-    fn num_leds() {
-        let config = Config::from_toml(FULL_CONFIG).unwrap();
-        assert_eq!(config.num_leds(), 9);
+    fn unknown_active_profile_is_reported() {
+        let config = Config::from_toml("[settings]\nprofile = \"nope\"\n").unwrap();
+        assert_eq!(config.active_profile(), None);
+        assert!(config.has_errors());
     }
 
     #[test]
-    fn metar_airport_codes_filters_special() {
-        let config = Config::from_toml(FULL_CONFIG).unwrap();
-        let codes = config.metar_airport_codes();
-        assert_eq!(codes, vec!["KSFO", "KLAX"]);
+    fn to_toml_keeps_base_settings_and_active_profile() {
+        let mut config = Config::from_toml(PROFILES).unwrap();
+        config.set_profile(Some("demo")).unwrap();
+        let reparsed = Config::from_toml(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(reparsed.active_profile(), Some("demo"));
+        assert_eq!(reparsed.settings.brightness, 40);
+        assert_eq!(reparsed.profiles.len(), 2);
     }
 
     #[test]
-    fn metar_airport_codes_empty() {
+    fn direct_edits_survive_profile_switch() {
+        let mut config = Config::from_toml(PROFILES).unwrap();
+        config.settings.do_winds = false;
+        config.set_profile(None).unwrap();
+        assert!(!config.settings.do_winds);
+        assert_eq!(config.settings.brightness, 40);
+    }
+
+    #[test]
+    fn timezone_setting() {
+        let config = Config::from_toml("[settings]\ntimezone = \"EST5EDT,M3.2.0,M11.1.0\"\n");
+        let tz = config.unwrap().settings.time_zone();
+        assert_eq!(tz.to_local(0).hour, 19);
+
+        let config = Config::from_toml("[settings]\ntimezone = \"America/New_York\"\n").unwrap();
+        assert_eq!(config.settings.time_zone(), TimeZone::UTC);
+        assert!(config
+            .diagnostics
+            .iter()
+            .any(|d| d.field == "settings.timezone"));
+    }
+
+    #[test]
+    fn builder_matches_toml() {
+        let built = Config::builder()
+            .brightness(50)
+            .request_interval_secs(300)
+            .wind_threshold_kt(30)
+            .do_lightning(false)
+            .do_winds(false)
+            .do_fog_risk(true)
+            .data_pin(5)
+            .wifi("TestNetwork", "TestPass123")
+            .airport("KSFO")
+            .airport_entry(Airport {
+                code: "KOAK".to_string(),
+                fallback: Some("KSFO".to_string()),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        let parsed = Config::from_toml(&built.to_toml().unwrap()).unwrap();
+        assert_eq!(built.to_toml().unwrap(), parsed.to_toml().unwrap());
+        assert_eq!(built.settings.brightness, 50);
+        assert_eq!(built.metar_airport_codes(), vec!["KSFO", "KOAK"]);
+    }
+
+    #[test]
+    fn builder_validates() {
+        let config = Config::builder()
+            .request_interval_secs(5)
+            .legend("SOCK", Color::new(1, 2, 3))
+            .airport("SOCK")
+            .build()
+            .unwrap();
+        assert_eq!(config.settings.request_interval_secs, 60);
+        assert_eq!(config.airports[0].legend_color, Some(Color::new(1, 2, 3)));
+        assert_eq!(config.diagnostics.len(), 1);
+
+        let err = Config::builder()
+            .airport_entry(Airport {
+                code: "KSFO".to_string(),
+                led: Some(3),
+                ..Default::default()
+            })
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::LedIndexOutOfBounds { index: 3, .. }));
+    }
+
+    #[test]
+    fn builder_applies_active_profile() {
+        let config = Config::builder()
+            .brightness(40)
+            .profile(Profile {
+                name: "night".to_string(),
+                brightness: Some(5),
+                ..Default::default()
+            })
+            .active_profile("night")
+            .build()
+            .unwrap();
+        assert_eq!(config.settings.brightness, 5);
+        assert_eq!(config.active_profile(), Some("night"));
+    }
+
+    #[test]
+    fn preset_replaces_airport_list() {
         let toml = r#"
-[[airports]]
-code = "NULL"
+[settings]
+preset = "front_range"
 
 [[airports]]
-code = "VFR"
+code = "KSFO"
 "#;
         let config = Config::from_toml(toml).unwrap();
-        assert!(config.metar_airport_codes().is_empty());
+        assert_eq!(config.airports[0].code, "KFNL");
+        assert_eq!(config.num_leds(), presets::find_preset("front_range").unwrap().airports.len());
+        assert!(config.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn unknown_preset_is_reported() {
+        let config = Config::from_toml("[settings]\npreset = \"atlantis\"\n").unwrap();
+        assert!(config.has_errors());
+        assert_eq!(config.diagnostics[0].field, "settings.preset");
+    }
+
+    #[test]
+    fn every_preset_fits_default_budget() {
+        for preset in presets::PRESETS {
+            let toml = format!("[settings]\npreset = \"{}\"\n", preset.id);
+            assert!(Config::from_toml(&toml).is_ok(), "{}", preset.id);
+        }
     }
 
     #[test]
@@ -240,6 +3243,16 @@ request_interval_secs = 99999
         assert_eq!(config.settings.request_interval_secs, 3600);
     }
 
+    #[test]
+    fn validation_clamps_jitter() {
+        let toml = r#"
+[settings]
+request_jitter_secs = 1000
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.settings.request_jitter_secs, 300);
+    }
+
     #[test]
     fn validation_clamps_wind_threshold() {
         let toml = r#"
@@ -250,6 +3263,44 @@ wind_threshold_kt = 200
         assert_eq!(config.settings.wind_threshold_kt, 100);
     }
 
+    #[test]
+    fn parse_winds_aloft_mode() {
+        let toml = r#"
+[settings]
+display_mode = "winds_aloft"
+
+[settings.winds_aloft]
+altitude_ft = 9000
+max_kt = 40
+forecast_hours = 12
+
+[[airports]]
+code = "PAMR"
+winds_station = "ANC"
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.settings.display_mode, DisplayMode::WindsAloft);
+        assert_eq!(config.settings.winds_aloft.altitude_ft, 9000);
+        assert_eq!(config.settings.winds_aloft.max_kt, 40);
+        assert_eq!(config.settings.winds_aloft.forecast_hours, 12);
+        assert_eq!(config.airports[0].winds_station.as_deref(), Some("ANC"));
+    }
+
+    #[test]
+    fn validation_snaps_winds_aloft_settings() {
+        let toml = r#"
+[settings.winds_aloft]
+altitude_ft = 10000
+forecast_hours = 3
+max_kt = 0
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.settings.display_mode, DisplayMode::Metar);
+        assert_eq!(config.settings.winds_aloft.altitude_ft, 9000);
+        assert_eq!(config.settings.winds_aloft.forecast_hours, 6);
+        assert_eq!(config.settings.winds_aloft.max_kt, 1);
+    }
+
     #[test]
     fn is_special_code_checks() {
         assert!(is_special_code("NULL"));
@@ -260,6 +3311,7 @@ wind_threshold_kt = 200
         assert!(is_special_code("WVFR"));
         assert!(is_special_code("LTNG"));
         assert!(is_special_code("WBNK"));
+        assert!(is_special_code("SUMM"));
         assert!(!is_special_code("KSFO"));
         assert!(!is_special_code("KLAX"));
         assert!(!is_special_code(""));
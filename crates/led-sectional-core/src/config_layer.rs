@@ -0,0 +1,303 @@
+//! Layered configuration: several partial TOML documents merged in order.
+//!
+//! The firmware stacks three layers, lowest precedence first:
+//!
+//! 1. the embedded default (`cfg.toml.example`),
+//! 2. the user file on flash (`/config.toml`),
+//! 3. WiFi credentials from `/secrets.toml`,
+//! 4. runtime overrides stored in NVS.
+//!
+//! Tables merge key by key, so a layer only needs the values it changes.
+//! Anything else, including arrays such as `[[airports]]`, is replaced whole
+//! by the highest layer that sets it.
+
+use toml::{Table, Value};
+
+use crate::chip::Chip;
+use crate::config::Config;
+use crate::error::Result;
+
+/// The only table a secrets layer may set.
+const SECRETS_TABLE: &str = "wifi";
+
+/// One partial config document and where it came from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigLayer {
+    pub name: String,
+    table: Table,
+}
+
+impl ConfigLayer {
+    /// Parse a layer. Only TOML syntax is checked here; values are validated
+    /// once all layers are merged.
+    pub fn parse(name: impl Into<String>, toml: &str) -> Result<Self> {
+        Ok(Self {
+            name: name.into(),
+            table: toml.parse()?,
+        })
+    }
+
+    /// Parse a secrets layer such as `secrets.toml`. Only the `[wifi]` table is
+    /// kept, so the main config can be shared without scrubbing credentials
+    /// and a secrets file can't change anything else. Returns the layer and
+    /// the names of any ignored top-level keys.
+    pub fn parse_secrets(name: impl Into<String>, toml: &str) -> Result<(Self, Vec<String>)> {
+        let mut table: Table = toml.parse()?;
+        let ignored = table
+            .keys()
+            .filter(|k| k.as_str() != SECRETS_TABLE)
+            .cloned()
+            .collect();
+        table.retain(|k, _| k == SECRETS_TABLE);
+        Ok((
+            Self {
+                name: name.into(),
+                table,
+            },
+            ignored,
+        ))
+    }
+
+    /// A layer holding every value of a loaded config.
+    pub fn from_config(name: impl Into<String>, config: &Config) -> Result<Self> {
+        Self::parse(name, &config.to_toml()?)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    pub fn to_toml(&self) -> String {
+        self.table.to_string()
+    }
+
+    /// The values a layer on top of `base` must hold to turn it into `self`.
+    pub fn diff(&self, base: &ConfigLayer) -> ConfigLayer {
+        ConfigLayer {
+            name: self.name.clone(),
+            table: diff_tables(&self.table, &base.table),
+        }
+    }
+
+    /// The smallest layer that, stacked on `lower`, reproduces `config`. Used
+    /// to store runtime changes as overrides rather than a full copy.
+    pub fn overrides(
+        name: impl Into<String>,
+        config: &Config,
+        lower: &[ConfigLayer],
+    ) -> Result<ConfigLayer> {
+        // Compare fully expanded configs so defaults don't count as changes
+        let base = Self::from_config("base", &Config::from_layers(lower)?)?;
+        Ok(Self::from_config(name, config)?.diff(&base))
+    }
+
+    /// Merge layers in order, later ones taking precedence.
+    pub fn merge(name: impl Into<String>, layers: &[ConfigLayer]) -> ConfigLayer {
+        let mut table = Table::new();
+        for layer in layers {
+            merge_into(&mut table, &layer.table);
+        }
+        ConfigLayer {
+            name: name.into(),
+            table,
+        }
+    }
+}
+
+/// CRC-32 (IEEE) of a stored layer's TOML, kept alongside it so corruption
+/// in storage can be told apart from a layer that merely fails validation.
+pub fn checksum(toml: &str) -> u32 {
+    let mut crc = !0u32;
+    for &byte in toml.as_bytes() {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+impl Config {
+    /// Merge `layers` (lowest precedence first), then parse and validate the
+    /// result as a single config.
+    pub fn from_layers(layers: &[ConfigLayer]) -> Result<Config> {
+        Self::from_layers_for(layers, Chip::TARGET)
+    }
+
+    /// Like [`from_layers`](Self::from_layers), validating for `chip`.
+    pub fn from_layers_for(layers: &[ConfigLayer], chip: Chip) -> Result<Config> {
+        let merged = ConfigLayer::merge("merged", layers);
+        Config::from_toml_for(&merged.to_toml(), chip)
+    }
+}
+
+fn merge_into(base: &mut Table, overlay: &Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(Value::Table(base_table)), Value::Table(overlay_table)) => {
+                merge_into(base_table, overlay_table);
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+fn diff_tables(current: &Table, base: &Table) -> Table {
+    let mut diff = Table::new();
+    for (key, value) in current {
+        match (base.get(key), value) {
+            (Some(Value::Table(base_table)), Value::Table(table)) => {
+                let nested = diff_tables(table, base_table);
+                if !nested.is_empty() {
+                    diff.insert(key.clone(), Value::Table(nested));
+                }
+            }
+            (Some(base_value), _) if base_value == value => {}
+            _ => {
+                diff.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMBEDDED: &str = r#"
+[settings]
+brightness = 20
+do_winds = true
+
+[settings.winds_aloft]
+altitude_ft = 6000
+
+[[airports]]
+code = "KSFO"
+"#;
+
+    const FLASH: &str = r#"
+[settings]
+do_winds = false
+
+[settings.winds_aloft]
+max_kt = 40
+
+[[airports]]
+code = "KOAK"
+
+[[airports]]
+code = "KSJC"
+"#;
+
+    const NVS: &str = r#"
+[settings]
+brightness = 5
+"#;
+
+    fn layers() -> Vec<ConfigLayer> {
+        vec![
+            ConfigLayer::parse("embedded", EMBEDDED).unwrap(),
+            ConfigLayer::parse("flash", FLASH).unwrap(),
+            ConfigLayer::parse("nvs", NVS).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn later_layers_take_precedence() {
+        let config = Config::from_layers(&layers()).unwrap();
+        assert_eq!(config.settings.brightness, 5);
+        assert!(!config.settings.do_winds);
+        // Nested tables merge key by key
+        assert_eq!(config.settings.winds_aloft.altitude_ft, 6000);
+        assert_eq!(config.settings.winds_aloft.max_kt, 40);
+    }
+
+    #[test]
+    fn arrays_are_replaced_whole() {
+        let config = Config::from_layers(&layers()).unwrap();
+        let codes: Vec<&str> = config.airports.iter().map(|a| a.code.as_str()).collect();
+        assert_eq!(codes, vec!["KOAK", "KSJC"]);
+    }
+
+    #[test]
+    fn missing_layers_fall_through() {
+        let config = Config::from_layers(&layers()[..1]).unwrap();
+        assert_eq!(config.settings.brightness, 20);
+        assert_eq!(config.airports.len(), 1);
+    }
+
+    #[test]
+    fn syntax_errors_rejected_per_layer() {
+        assert!(ConfigLayer::parse("flash", "[settings\nbrightness = 1").is_err());
+    }
+
+    #[test]
+    fn diff_keeps_only_changed_values() {
+        let base = ConfigLayer::parse("base", "[settings]\nbrightness = 20\ndo_winds = true\n");
+        let current =
+            ConfigLayer::parse("current", "[settings]\nbrightness = 80\ndo_winds = true\n");
+        let diff = current.unwrap().diff(&base.unwrap());
+        assert_eq!(diff.to_toml().trim(), "[settings]\nbrightness = 80");
+    }
+
+    #[test]
+    fn overrides_round_trip_runtime_changes() {
+        let lower = &layers()[..2];
+        let mut config = Config::from_layers(lower).unwrap();
+        config.settings.brightness = 80;
+
+        let overrides = ConfigLayer::overrides("nvs", &config, lower).unwrap();
+        assert_eq!(overrides.to_toml().trim(), "[settings]\nbrightness = 80");
+
+        let mut stacked = lower.to_vec();
+        stacked.push(overrides);
+        assert_eq!(
+            Config::from_layers(&stacked).unwrap().settings.brightness,
+            80
+        );
+    }
+
+    #[test]
+    fn overrides_of_unchanged_config_are_empty() {
+        let lower = &layers()[..2];
+        let config = Config::from_layers(lower).unwrap();
+        assert!(ConfigLayer::overrides("nvs", &config, lower)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn secrets_layer_only_sets_wifi() {
+        let secrets = r#"
+[wifi]
+ssid = "hangar"
+password = "hunter2"
+
+[settings]
+brightness = 255
+"#;
+        let (layer, ignored) = ConfigLayer::parse_secrets("secrets", secrets).unwrap();
+        assert_eq!(ignored, vec!["settings".to_string()]);
+
+        let mut stacked = layers()[..2].to_vec();
+        stacked.push(layer);
+        let config = Config::from_layers(&stacked).unwrap();
+        assert_eq!(config.wifi.ssid.as_deref(), Some("hangar"));
+        assert_eq!(config.wifi.password.as_deref(), Some("hunter2"));
+        assert_eq!(config.settings.brightness, 20);
+    }
+
+    #[test]
+    fn checksum_matches_crc32() {
+        assert_eq!(checksum(""), 0);
+        assert_eq!(checksum("123456789"), 0xCBF4_3926);
+        assert_ne!(
+            checksum("[settings]\nbrightness = 5\n"),
+            checksum("[settings]\nbrightness = 6\n")
+        );
+    }
+}
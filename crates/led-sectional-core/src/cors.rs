@@ -0,0 +1,149 @@
+//! Cross-origin access to the device API, so a companion web app hosted
+//! elsewhere can call the map on the LAN straight from the browser. Only
+//! origins listed in `settings.http.cors_origins` are let in.
+
+/// Methods a cross-origin page may use.
+pub const ALLOW_METHODS: &str = "GET, POST, DELETE, OPTIONS";
+/// Request headers a cross-origin page may send: the credential and JSON.
+pub const ALLOW_HEADERS: &str = "Authorization, Content-Type";
+/// How long a browser may cache a preflight answer, in seconds.
+pub const MAX_AGE_SECS: &str = "600";
+
+/// Which origins get CORS headers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CorsPolicy {
+    origins: Vec<String>,
+    any: bool,
+}
+
+impl CorsPolicy {
+    /// No cross-origin access; `const` so the firmware can keep one in a
+    /// static until the config loads.
+    pub const fn disabled() -> Self {
+        Self {
+            origins: Vec::new(),
+            any: false,
+        }
+    }
+
+    /// Allow `origins`, as validated by the config; `"*"` allows any.
+    pub fn new(origins: &[String]) -> Self {
+        Self {
+            any: origins.iter().any(|o| o == "*"),
+            origins: origins.iter().filter(|o| *o != "*").cloned().collect(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.any || !self.origins.is_empty()
+    }
+
+    /// Headers for a response to a request with `origin` as its `Origin`
+    /// header. Empty for same-origin requests and origins not allowed.
+    pub fn headers(&self, origin: Option<&str>) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        if self.any {
+            if origin.is_some() {
+                headers.push(("Access-Control-Allow-Origin", "*".to_string()));
+            }
+        } else if !self.origins.is_empty() {
+            // Answers differ by origin, so caches must keep them apart
+            headers.push(("Vary", "Origin".to_string()));
+            match origin.filter(|o| self.origins.iter().any(|a| a.eq_ignore_ascii_case(o))) {
+                Some(origin) => headers.push(("Access-Control-Allow-Origin", origin.to_string())),
+                None => return headers,
+            }
+        }
+        if headers.iter().any(|(name, _)| *name == "Access-Control-Allow-Origin") {
+            headers.push(("Access-Control-Expose-Headers", "Retry-After".to_string()));
+        }
+        headers
+    }
+
+    /// Headers for an `OPTIONS` preflight from `origin`. Chrome also asks
+    /// before a public site may reach a private address, which the map
+    /// always has.
+    pub fn preflight_headers(&self, origin: Option<&str>) -> Vec<(&'static str, String)> {
+        let mut headers = self.headers(origin);
+        if headers.iter().any(|(name, _)| *name == "Access-Control-Allow-Origin") {
+            headers.extend([
+                ("Access-Control-Allow-Methods", ALLOW_METHODS.to_string()),
+                ("Access-Control-Allow-Headers", ALLOW_HEADERS.to_string()),
+                ("Access-Control-Allow-Private-Network", "true".to_string()),
+                ("Access-Control-Max-Age", MAX_AGE_SECS.to_string()),
+            ]);
+        }
+        headers
+    }
+}
+
+/// Whether `origin` can be listed in `cors_origins`: `*`, or a scheme and
+/// host with an optional port, exactly as browsers send it in `Origin`.
+pub fn is_valid_origin(origin: &str) -> bool {
+    if origin == "*" {
+        return true;
+    }
+    let Some((scheme, rest)) = origin.split_once("://") else {
+        return false;
+    };
+    let (host, port) = match rest.rsplit_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (rest, None),
+    };
+    matches!(scheme, "http" | "https")
+        && !host.is_empty()
+        && host.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.')
+        && port.is_none_or(|p| p.parse::<u16>().is_ok_and(|p| p > 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(headers: &[(&'static str, String)]) -> Vec<&'static str> {
+        headers.iter().map(|(name, _)| *name).collect()
+    }
+
+    #[test]
+    fn listed_origins_are_echoed() {
+        let policy = CorsPolicy::new(&["https://app.example.com".to_string()]);
+        assert!(policy.is_enabled());
+        let headers = policy.headers(Some("https://app.example.com"));
+        assert!(headers.contains(&("Access-Control-Allow-Origin", "https://app.example.com".into())));
+        assert!(headers.contains(&("Vary", "Origin".into())));
+        assert_eq!(names(&policy.headers(Some("https://evil.example"))), ["Vary"]);
+        assert_eq!(names(&policy.headers(None)), ["Vary"]);
+    }
+
+    #[test]
+    fn wildcard_and_disabled() {
+        let any = CorsPolicy::new(&["*".to_string()]);
+        let headers = any.headers(Some("http://anything"));
+        assert_eq!(headers[0], ("Access-Control-Allow-Origin", "*".into()));
+        assert!(any.headers(None).is_empty());
+        let off = CorsPolicy::disabled();
+        assert!(!off.is_enabled());
+        assert!(off.headers(Some("http://anything")).is_empty());
+        assert!(off.preflight_headers(Some("http://anything")).is_empty());
+    }
+
+    #[test]
+    fn preflight_lists_methods_and_headers() {
+        let policy = CorsPolicy::new(&["http://localhost:5173".to_string()]);
+        let headers = policy.preflight_headers(Some("http://localhost:5173"));
+        assert!(headers.contains(&("Access-Control-Allow-Headers", ALLOW_HEADERS.into())));
+        assert!(headers.contains(&("Access-Control-Allow-Private-Network", "true".into())));
+        assert!(!names(&policy.preflight_headers(Some("http://localhost:8080")))
+            .contains(&"Access-Control-Allow-Methods"));
+    }
+
+    #[test]
+    fn origins_are_scheme_host_and_port() {
+        for ok in ["*", "https://app.example.com", "http://10.0.0.5:8080", "http://localhost"] {
+            assert!(is_valid_origin(ok), "{ok}");
+        }
+        for bad in ["app.example.com", "https://", "https://a.com/", "ftp://a.com", "http://a:0"] {
+            assert!(!is_valid_origin(bad), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Reports of crashes, kept across the reset that follows so a unit that
+//! reboots in the field can say why. The firmware's panic hook records the
+//! message and location; at the next boot that is combined with the reset
+//! reason, which also catches crashes outside Rust such as the watchdog.
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::Payload;
+
+/// Longest panic message kept, in bytes.
+pub const MAX_MESSAGE_LEN: usize = 200;
+
+/// Whether a reset for `reset_reason` (as in [`crate::api::DeviceHealth`])
+/// means the firmware crashed rather than restarting on purpose or losing
+/// power.
+pub fn is_crash(reset_reason: &str) -> bool {
+    matches!(
+        reset_reason,
+        "panic" | "task_watchdog" | "interrupt_watchdog" | "watchdog"
+    )
+}
+
+/// The last crash, as `GET /api/health` reports it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// Why the chip reset, such as `panic` or `task_watchdog`.
+    pub reset_reason: String,
+    /// The panic message; None for crashes outside Rust.
+    pub message: Option<String>,
+    /// `file:line:column` of the panic.
+    pub location: Option<String>,
+    /// The firmware that crashed.
+    pub version: String,
+    /// Seconds after boot it happened, when known.
+    pub uptime_secs: Option<u64>,
+    /// Unix seconds, if the clock was set.
+    pub at: Option<i64>,
+}
+
+impl Payload for CrashReport {}
+
+impl CrashReport {
+    /// A panic as the hook sees it, before the reset. `message` is cut to
+    /// [`MAX_MESSAGE_LEN`] so the report fits in NVS.
+    pub fn panic(message: &str, location: Option<String>, version: &str) -> Self {
+        let mut end = message.len().min(MAX_MESSAGE_LEN);
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        Self {
+            reset_reason: "panic".to_string(),
+            message: Some(message[..end].to_string()),
+            location,
+            version: version.to_string(),
+            uptime_secs: None,
+            at: None,
+        }
+    }
+
+    /// What to report at boot, given the panic recorded before the reset
+    /// (if any) and why the chip reset. None if it didn't crash.
+    pub fn after_reset(
+        recorded: Option<CrashReport>,
+        reset_reason: &str,
+        version: &str,
+    ) -> Option<Self> {
+        if !is_crash(reset_reason) {
+            return None;
+        }
+        let mut report = recorded.unwrap_or_else(|| Self {
+            reset_reason: String::new(),
+            message: None,
+            location: None,
+            version: version.to_string(),
+            uptime_secs: None,
+            at: None,
+        });
+        report.reset_reason = reset_reason.to_string();
+        Some(report)
+    }
+
+    /// One line for the log or a status report.
+    pub fn summary(&self) -> String {
+        let mut text = self.reset_reason.clone();
+        if let Some(message) = &self.message {
+            text.push_str(&format!(": {message}"));
+        }
+        if let Some(location) = &self.location {
+            text.push_str(&format!(" at {location}"));
+        }
+        text.push_str(&format!(" (firmware {}", self.version));
+        if let Some(secs) = self.uptime_secs {
+            text.push_str(&format!(", {secs}s after boot"));
+        }
+        text.push(')');
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panics_are_reported_after_the_reset() {
+        let mut recorded = CrashReport::panic(
+            "index out of bounds",
+            Some("src/main.rs:10:5".into()),
+            "0.2.0",
+        );
+        recorded.uptime_secs = Some(312);
+        let json = recorded.to_json();
+        let recorded = CrashReport::from_json(&json).unwrap();
+
+        let report = CrashReport::after_reset(Some(recorded), "panic", "0.2.0").unwrap();
+        assert_eq!(
+            report.summary(),
+            "panic: index out of bounds at src/main.rs:10:5 (firmware 0.2.0, 312s after boot)"
+        );
+    }
+
+    #[test]
+    fn crashes_outside_rust_have_only_the_reason() {
+        let report = CrashReport::after_reset(None, "task_watchdog", "0.2.0").unwrap();
+        assert_eq!(report.message, None);
+        assert_eq!(report.summary(), "task_watchdog (firmware 0.2.0)");
+        assert!(CrashReport::after_reset(None, "power_on", "0.2.0").is_none());
+        assert!(!is_crash("brownout"));
+    }
+
+    #[test]
+    fn long_messages_are_cut_on_a_char_boundary() {
+        let message = "é".repeat(MAX_MESSAGE_LEN);
+        let report = CrashReport::panic(&message, None, "0.2.0");
+        let kept = report.message.unwrap();
+        assert!(kept.len() <= MAX_MESSAGE_LEN);
+        assert!(kept.chars().all(|c| c == 'é'));
+    }
+}
@@ -0,0 +1,455 @@
+//! A text mirror of the map for the web UI, so it can be checked from
+//! anywhere on the network: each LED's airport, color, flight category, wind,
+//! and observation time. The same snapshot feeds the JSON behind the map
+//! preview page, which draws the strip or, with coordinates, the map, and
+//! the reports themselves, raw text included, for `/api/metars`.
+
+use std::collections::HashMap;
+
+use crate::api::{AirportStatus, LedMetar, MapResponse, MetarsResponse, Payload};
+use crate::config::{is_special_code, Config};
+use crate::led::{select_metar, Color, LedState};
+use crate::metar::{FlightCategory, MetarReport};
+
+/// One LED of the map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapRow {
+    pub led: usize,
+    pub code: String,
+    pub name: String,
+    /// What the LED shows, before brightness scaling.
+    pub color: Color,
+    pub category: Option<FlightCategory>,
+    /// Whether the LED shows weather, rather than a legend's fixed color.
+    pub weather: bool,
+    /// The station the report came from, when it was the fallback.
+    pub fallback: Option<String>,
+    pub wind: Option<String>,
+    /// Observation time as Unix seconds.
+    pub observed: Option<i64>,
+    /// Latitude and longitude from the config, for plotting.
+    pub position: Option<(f64, f64)>,
+    /// The report behind the color, as fetched.
+    pub report: Option<MetarReport>,
+}
+
+/// The map as of the last fetch.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapSnapshot {
+    pub rows: Vec<MapRow>,
+    /// When the data was fetched, as Unix seconds, if the clock was set.
+    pub fetched_at: Option<i64>,
+    /// Why the last fetch failed, while the LEDs show the error color.
+    pub error: Option<String>,
+}
+
+impl MapSnapshot {
+    /// Describe every lit LED: `reports` from the poller, colors from
+    /// `led_state`. Positions holding `NULL` are left out.
+    pub fn capture(
+        config: &Config,
+        reports: &HashMap<String, MetarReport>,
+        led_state: &LedState,
+    ) -> Self {
+        let rows = config
+            .airports
+            .iter()
+            .enumerate()
+            .filter(|(_, airport)| airport.code != "NULL")
+            .map(|(led, airport)| {
+                let real = airport.legend_color.is_none() && !is_special_code(&airport.code);
+                let report = real.then(|| select_metar(airport, reports)).flatten();
+                MapRow {
+                    led,
+                    code: airport.code.clone(),
+                    name: airport.display_name().to_string(),
+                    color: led_state.get(led).unwrap_or(Color::new(0, 0, 0)),
+                    category: report.and_then(MetarReport::flight_category),
+                    weather: real,
+                    fallback: report
+                        .filter(|r| r.icao_id != airport.code)
+                        .map(|r| r.icao_id.clone()),
+                    wind: report.and_then(|r| wind_text(r.wspd, r.wgst)),
+                    observed: report.and_then(|r| r.obs_time),
+                    position: airport.position(),
+                    report: report.cloned(),
+                }
+            })
+            .collect();
+        Self {
+            rows,
+            ..Default::default()
+        }
+    }
+
+    /// The dashboard page. `now` (Unix seconds, if the clock is set) adds
+    /// how long ago each report was observed.
+    pub fn to_html(&self, now: Option<i64>) -> String {
+        let mut html = String::from(HTML_HEAD);
+        match self.fetched_at {
+            Some(at) => html.push_str(&format!("<p>Updated {}</p>\n", time_text(at, now))),
+            None if self.rows.iter().all(|r| r.category.is_none()) => {
+                html.push_str("<p>Waiting for the first weather fetch.</p>\n")
+            }
+            None => {}
+        }
+        if let Some(error) = &self.error {
+            html.push_str(&format!(
+                "<p class=\"error\">Last fetch failed: {}</p>\n",
+                escape(error)
+            ));
+        }
+        html.push_str(
+            "<table>\n<tr><th>LED</th><th></th><th>Airport</th><th>Category</th>\
+             <th>Wind</th><th>Observed</th></tr>\n",
+        );
+        for row in &self.rows {
+            let name = if row.name == row.code {
+                escape(&row.code)
+            } else {
+                format!("{} <small>{}</small>", escape(&row.code), escape(&row.name))
+            };
+            let via = match &row.fallback {
+                Some(station) => format!(" <small>via {}</small>", escape(station)),
+                None => String::new(),
+            };
+            // Hovering the category shows the observation it came from
+            let raw = match row.report.as_ref().and_then(|r| r.raw_ob.as_deref()) {
+                Some(raw) => format!(" title=\"{}\"", escape(raw)),
+                None => String::new(),
+            };
+            html.push_str(&format!(
+                "<tr><td>{}</td><td><span class=\"led\" style=\"background:{}\"></span></td>\
+                 <td>{name}</td><td{raw}>{}{via}</td><td>{}</td><td>{}</td></tr>\n",
+                row.led,
+                hex(row.color),
+                row.category.map_or("–", |c| c.as_str()),
+                row.wind.as_deref().unwrap_or("–"),
+                row.observed.map_or("–".to_string(), |t| time_text(t, now)),
+            ));
+        }
+        html.push_str(
+            "</table>\n<p><a href=\"/map\">Map preview</a> · \
+             <a href=\"/airports\">Edit airports</a> · <a href=\"/settings\">Settings</a></p>\n",
+        );
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// The snapshot as JSON for the map preview page.
+    pub fn to_json(&self) -> String {
+        MapResponse {
+            fetched_at: self.fetched_at,
+            error: self.error.clone(),
+            leds: self
+                .rows
+                .iter()
+                .map(|row| AirportStatus {
+                    led: row.led,
+                    code: row.code.clone(),
+                    name: row.name.clone(),
+                    color: hex(row.color),
+                    category: row.category.map(|c| c.as_str().to_string()),
+                    lat: row.position.map(|(lat, _)| lat),
+                    lon: row.position.map(|(_, lon)| lon),
+                })
+                .collect(),
+        }
+        .to_json()
+    }
+
+    /// The report behind each LED as JSON for `/api/metars`, in the field
+    /// names aviationweather.gov uses, `rawOb` holding the observation
+    /// text. LEDs with no report, such as legends, have `report: null`.
+    pub fn metars_json(&self) -> String {
+        MetarsResponse {
+            fetched_at: self.fetched_at,
+            error: self.error.clone(),
+            metars: self
+                .rows
+                .iter()
+                .map(|row| LedMetar {
+                    led: row.led,
+                    code: row.code.clone(),
+                    report: row.report.clone(),
+                })
+                .collect(),
+        }
+        .to_json()
+    }
+}
+
+/// The map preview: polls `/api/map` and draws each LED in its live color,
+/// on a plotted map when every lit LED has coordinates, else as the strip.
+pub const PREVIEW_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width,initial-scale=1">
+<title>LED Sectional map</title>
+<style>
+body{font-family:system-ui,sans-serif;background:#1a1a2e;color:#e0e0e0;margin:1rem}
+h1{font-size:1.4rem;color:#a8d8ea}
+a{color:#a8d8ea}
+svg{display:block;width:100%;max-width:720px;background:#10101c;border-radius:8px}
+text{fill:#e0e0e0;font-size:11px}
+small{color:#a0a0a0}
+.error{color:#e94560}
+</style>
+</head>
+<body>
+<h1>LED Sectional</h1>
+<p id="info"><small>Loading…</small></p>
+<svg id="map" viewBox="0 0 720 120"></svg>
+<p><a href="/">Details</a></p>
+<script>
+const NS='http://www.w3.org/2000/svg';
+function el(name,attrs,text){
+  const e=document.createElementNS(NS,name);
+  for(const k in attrs)e.setAttribute(k,attrs[k]);
+  if(text)e.textContent=text;
+  return e;
+}
+function layout(leds){
+  if(leds.length&&leds.every(l=>l.lat!=null)){
+    const lats=leds.map(l=>l.lat),lons=leds.map(l=>l.lon);
+    const n=Math.max(...lats),s=Math.min(...lats),e=Math.max(...lons),w=Math.min(...lons);
+    const k=Math.cos((n+s)/2*Math.PI/180);
+    const sx=Math.max((e-w)*k,1e-3),sy=Math.max(n-s,1e-3);
+    const scale=Math.min(600/sx,400/sy);
+    const width=sx*scale+120,height=sy*scale+60;
+    return{width,height,r:9,label:'right',points:leds.map(l=>
+      [60+(l.lon-w)*k*scale,30+(n-l.lat)*scale])};
+  }
+  const cols=Math.max(1,Math.min(leds.length,16));
+  const rows=Math.ceil(leds.length/cols);
+  return{width:720,height:rows*60+10,r:14,label:'below',points:leds.map((l,i)=>
+    [720/cols*(i%cols+.5),30+60*Math.floor(i/cols)])};
+}
+function draw(m){
+  const svg=document.getElementById('map');
+  const {width,height,r,label,points}=layout(m.leds);
+  svg.setAttribute('viewBox','0 0 '+width+' '+height);
+  svg.replaceChildren();
+  m.leds.forEach((l,i)=>{
+    const [x,y]=points[i];
+    const dot=el('circle',{cx:x,cy:y,r:r,fill:l.color,stroke:'#555'});
+    dot.appendChild(el('title',{},l.name+(l.category?' – '+l.category:'')+' (LED '+l.led+')'));
+    svg.appendChild(dot);
+    svg.appendChild(label=='right'
+      ?el('text',{x:x+r+3,y:y+4},l.code)
+      :el('text',{x:x,y:y+r+13,'text-anchor':'middle'},l.code));
+  });
+  let info='';
+  if(m.fetched_at){
+    const t=new Date(m.fetched_at*1000);
+    info='Updated '+t.toISOString().slice(11,16)+'Z';
+  }else if(!m.leds.some(l=>l.category)){
+    info='Waiting for the first weather fetch.';
+  }
+  const p=document.getElementById('info');
+  p.textContent=info;
+  if(m.error){
+    p.appendChild(el('br'));
+    const e=document.createElement('span');
+    e.className='error';
+    e.textContent='Last fetch failed: '+m.error;
+    p.appendChild(e);
+  }
+}
+function load(){
+  fetch('/api/map').then(r=>r.json()).then(draw)
+    .catch(()=>{document.getElementById('info').textContent='Map unavailable; retrying.';});
+}
+load();
+setInterval(load,30000);
+</script>
+</body>
+</html>
+"#;
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width,initial-scale=1">
+<meta http-equiv="refresh" content="60">
+<title>LED Sectional</title>
+<style>
+body{font-family:system-ui,sans-serif;background:#1a1a2e;color:#e0e0e0;margin:1rem}
+h1{font-size:1.4rem;color:#a8d8ea}
+a{color:#a8d8ea}
+table{border-collapse:collapse;width:100%;max-width:720px}
+th,td{text-align:left;padding:.35rem .5rem;border-bottom:1px solid #333}
+small{color:#a0a0a0}
+.led{display:inline-block;width:1rem;height:1rem;border-radius:50%;border:1px solid #555}
+.error{color:#e94560}
+</style>
+</head>
+<body>
+<h1>LED Sectional</h1>
+"#;
+
+/// Wind as shown on the dashboard, e.g. "12 kt, gusting 28".
+pub fn wind_text(speed: Option<u32>, gust: Option<u32>) -> Option<String> {
+    Some(match (speed?, gust) {
+        (0, None) => "calm".to_string(),
+        (speed, Some(gust)) => format!("{speed} kt, gusting {gust}"),
+        (speed, None) => format!("{speed} kt"),
+    })
+}
+
+/// `at` as UTC hours and minutes, plus its age when `now` is known.
+fn time_text(at: i64, now: Option<i64>) -> String {
+    let minute_of_day = at.rem_euclid(86_400) / 60;
+    let clock = format!("{:02}:{:02}Z", minute_of_day / 60, minute_of_day % 60);
+    match now.map(|now| (now - at) / 60) {
+        Some(minutes) if minutes >= 120 => format!("{clock} ({} h ago)", minutes / 60),
+        Some(minutes) if minutes >= 0 => format!("{clock} ({minutes} min ago)"),
+        _ => clock,
+    }
+}
+
+fn hex(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+/// Escape text for HTML; names and error messages can contain anything.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::{update_leds_from_metars, COLOR_IFR, COLOR_VFR};
+
+    const CONFIG: &str = r#"
+[[airports]]
+code = "KHAF"
+name = "Half Moon Bay"
+fallback = "KSQL"
+
+[[airports]]
+code = "NULL"
+
+[[airports]]
+code = "VFR"
+
+[[airports]]
+code = "KSFO"
+lat = 37.619
+lon = -122.375
+"#;
+
+    fn reports() -> HashMap<String, MetarReport> {
+        let report = |icao: &str, cat: &str, wspd, wgst| MetarReport {
+            icao_id: icao.to_string(),
+            flt_cat: Some(cat.to_string()),
+            wspd,
+            wgst,
+            obs_time: Some(1_700_000_000),
+            raw_ob: Some(format!("{icao} 142213Z 27012G28KT 2SM BR OVC005 RMK <AO2>")),
+            ..Default::default()
+        };
+        [report("KSQL", "IFR", Some(12), Some(28)), report("KSFO", "VFR", Some(0), None)]
+            .into_iter()
+            .map(|r| (r.icao_id.clone(), r))
+            .collect()
+    }
+
+    #[test]
+    fn capture_describes_each_lit_led() {
+        let config = Config::from_toml(CONFIG).unwrap();
+        let reports = reports();
+        let mut leds = LedState::new(config.num_leds(), 255);
+        update_leds_from_metars(&mut leds, &config.airports, &reports, &config.settings);
+        let snapshot = MapSnapshot::capture(&config, &reports, &leds);
+
+        let leds: Vec<usize> = snapshot.rows.iter().map(|r| r.led).collect();
+        assert_eq!(leds, [0, 2, 3]);
+        let khaf = &snapshot.rows[0];
+        assert_eq!(khaf.name, "Half Moon Bay");
+        assert_eq!(khaf.color, COLOR_IFR);
+        assert_eq!(khaf.category, Some(FlightCategory::Ifr));
+        assert_eq!(khaf.fallback.as_deref(), Some("KSQL"));
+        assert_eq!(khaf.wind.as_deref(), Some("12 kt, gusting 28"));
+        // Legend LEDs have a color but no weather
+        assert_eq!(snapshot.rows[1].color, COLOR_VFR);
+        assert!(khaf.weather && !snapshot.rows[1].weather);
+        assert_eq!(snapshot.rows[1].category, None);
+        assert_eq!(snapshot.rows[2].wind.as_deref(), Some("calm"));
+        assert_eq!(snapshot.rows[2].fallback, None);
+    }
+
+    #[test]
+    fn html_lists_rows_with_ages() {
+        let config = Config::from_toml(CONFIG).unwrap();
+        let leds = LedState::new(config.num_leds(), 255);
+        let mut snapshot = MapSnapshot::capture(&config, &reports(), &leds);
+        snapshot.fetched_at = Some(1_700_000_300);
+        snapshot.error = Some("HTTP <503>".into());
+
+        let html = snapshot.to_html(Some(1_700_000_000 + 12 * 60));
+        assert!(html.contains("Updated 22:18Z (7 min ago)"), "{html}");
+        assert!(html.contains("KHAF <small>Half Moon Bay</small>"));
+        assert!(html.contains("IFR <small>via KSQL</small>"));
+        assert!(html.contains("22:13Z (12 min ago)"));
+        assert!(html.contains("HTTP &lt;503&gt;"));
+        let raw = r#"<td title="KSQL 142213Z 27012G28KT 2SM BR OVC005 RMK &lt;AO2&gt;">IFR"#;
+        assert!(html.contains(raw), "{html}");
+        assert!(!snapshot.to_html(None).contains("ago"));
+    }
+
+    #[test]
+    fn json_has_colors_and_positions() {
+        let config = Config::from_toml(CONFIG).unwrap();
+        let reports = reports();
+        let mut leds = LedState::new(config.num_leds(), 255);
+        update_leds_from_metars(&mut leds, &config.airports, &reports, &config.settings);
+        let mut snapshot = MapSnapshot::capture(&config, &reports, &leds);
+        snapshot.fetched_at = Some(1_700_000_300);
+
+        let json: serde_json::Value = serde_json::from_str(&snapshot.to_json()).unwrap();
+        assert_eq!(json["fetched_at"], 1_700_000_300);
+        assert!(json["error"].is_null());
+        let leds = json["leds"].as_array().unwrap();
+        assert_eq!(leds.len(), 3);
+        assert_eq!(leds[0]["code"], "KHAF");
+        assert_eq!(leds[0]["color"], hex(COLOR_IFR));
+        assert_eq!(leds[0]["category"], "IFR");
+        assert!(leds[0]["lat"].is_null());
+        assert_eq!(leds[2]["led"], 3);
+        assert_eq!(leds[2]["lat"], 37.619);
+        assert_eq!(leds[2]["lon"], -122.375);
+    }
+
+    #[test]
+    fn metars_json_passes_reports_through() {
+        let config = Config::from_toml(CONFIG).unwrap();
+        let leds = LedState::new(config.num_leds(), 255);
+        let snapshot = MapSnapshot::capture(&config, &reports(), &leds);
+
+        let json: serde_json::Value = serde_json::from_str(&snapshot.metars_json()).unwrap();
+        let metars = json["metars"].as_array().unwrap();
+        assert_eq!(metars.len(), 3);
+        assert_eq!(metars[0]["code"], "KHAF");
+        assert_eq!(metars[0]["report"]["icaoId"], "KSQL");
+        assert!(metars[0]["report"]["rawOb"].as_str().unwrap().starts_with("KSQL 142213Z"));
+        assert!(metars[1]["report"].is_null());
+        // What is served parses back as a report
+        let report: MetarReport = serde_json::from_value(metars[2]["report"].clone()).unwrap();
+        assert_eq!(Some(&report), reports().get("KSFO"));
+    }
+
+    #[test]
+    fn times_and_winds() {
+        assert_eq!(time_text(3 * 3600 + 5 * 60, Some(6 * 3600)), "03:05Z (2 h ago)");
+        assert_eq!(time_text(60, Some(0)), "00:01Z");
+        assert_eq!(wind_text(Some(8), None).as_deref(), Some("8 kt"));
+        assert_eq!(wind_text(None, Some(20)), None);
+    }
+}
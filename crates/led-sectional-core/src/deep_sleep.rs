@@ -0,0 +1,201 @@
+//! Deep sleep between fetches for battery-powered maps, with
+//! `settings.power.deep_sleep`. WS2812s hold their last colors while the
+//! chip sleeps, so the LED state is saved in RTC memory before sleeping and
+//! put back on the timer wake; the LEDs are only written again once the
+//! next fetch has repainted them.
+//!
+//! After power-on or a reset the map stays awake for [`AWAKE_AFTER_BOOT`],
+//! so the web UI can be used to change settings, then sleeps from fetch to
+//! fetch.
+
+use std::time::{Duration, Instant};
+
+use crate::asset::crc32;
+use crate::led::{Color, LedState};
+
+/// Most LEDs the saved image has room for; a longer strip is repainted
+/// from scratch on every wake.
+pub const MAX_SAVED_LEDS: usize = 512;
+const MAGIC: &[u8; 4] = b"LSW1";
+/// Magic, LED count, brightness, display on/off.
+const HEADER_LEN: usize = 4 + 2 + 1 + 1;
+/// Bytes to reserve in RTC memory for [`save`].
+pub const IMAGE_LEN: usize = HEADER_LEN + 3 * MAX_SAVED_LEDS + 4;
+
+/// How long the map stays up after power-on or a reset before sleeping.
+pub const AWAKE_AFTER_BOOT: Duration = Duration::from_secs(300);
+/// Time after a fetch for the LEDs and MQTT to be sent before sleeping.
+pub const SETTLE: Duration = Duration::from_secs(5);
+/// How long a timer wake waits for WiFi before sleeping until the next try.
+pub const CONNECT_BUDGET: Duration = Duration::from_secs(30);
+/// Shortest sleep worth reconnecting after; nearer fetches are waited out
+/// awake.
+pub const MIN_SLEEP: Duration = Duration::from_secs(20);
+
+/// Write `led_state` into `image`. False if it has more LEDs than fit.
+pub fn save(led_state: &LedState, image: &mut [u8; IMAGE_LEN]) -> bool {
+    let count = led_state.num_leds();
+    if count > MAX_SAVED_LEDS {
+        image[..4].fill(0);
+        return false;
+    }
+    image[..4].copy_from_slice(MAGIC);
+    image[4..6].copy_from_slice(&(count as u16).to_le_bytes());
+    image[6] = led_state.brightness();
+    image[7] = u8::from(led_state.display_on());
+    for i in 0..count {
+        let c = led_state.get(i).unwrap_or(Color::new(0, 0, 0));
+        image[HEADER_LEN + 3 * i..][..3].copy_from_slice(&[c.r, c.g, c.b]);
+    }
+    let end = HEADER_LEN + 3 * count;
+    let crc = crc32(&image[..end]);
+    image[end..end + 4].copy_from_slice(&crc.to_le_bytes());
+    true
+}
+
+/// Put the state saved in `image` back into `led_state`. False, leaving it
+/// alone, if `image` is blank or damaged or was saved for a different
+/// number of LEDs.
+pub fn restore(image: &[u8], led_state: &mut LedState) -> bool {
+    if image.len() < HEADER_LEN || &image[..4] != MAGIC {
+        return false;
+    }
+    let count = usize::from(u16::from_le_bytes([image[4], image[5]]));
+    let end = HEADER_LEN + 3 * count;
+    if count != led_state.num_leds() || image.len() < end + 4 {
+        return false;
+    }
+    let crc = u32::from_le_bytes([image[end], image[end + 1], image[end + 2], image[end + 3]]);
+    if crc != crc32(&image[..end]) {
+        return false;
+    }
+    led_state.set_brightness(image[6]);
+    led_state.set_display_on(image[7] != 0);
+    for (i, rgb) in image[HEADER_LEN..end].chunks_exact(3).enumerate() {
+        let _ = led_state.set(i, Color::new(rgb[0], rgb[1], rgb[2]));
+    }
+    true
+}
+
+/// When the main loop may put the map to sleep.
+#[derive(Debug, Clone)]
+pub struct SleepSchedule {
+    woke: Instant,
+    timer_wake: bool,
+    awake_until: Instant,
+    settled_at: Option<Instant>,
+}
+
+impl SleepSchedule {
+    /// Start the schedule at boot; `timer_wake` if this boot is the end of
+    /// a deep sleep rather than power-on or a reset.
+    pub fn new(now: Instant, timer_wake: bool) -> Self {
+        Self {
+            woke: now,
+            timer_wake,
+            awake_until: if timer_wake { now } else { now + AWAKE_AFTER_BOOT },
+            settled_at: None,
+        }
+    }
+
+    /// Whether this boot is the end of a deep sleep.
+    pub fn is_timer_wake(&self) -> bool {
+        self.timer_wake
+    }
+
+    /// A fetch finished at `now`, successfully or not.
+    pub fn fetched(&mut self, now: Instant) {
+        self.settled_at = Some(now + SETTLE);
+    }
+
+    /// How long to sleep, with the next fetch due at `next_fetch`; None to
+    /// stay awake.
+    pub fn sleep_for(&self, now: Instant, next_fetch: Option<Instant>) -> Option<Duration> {
+        if now < self.awake_until || self.settled_at.is_none_or(|t| now < t) {
+            return None;
+        }
+        Some(next_fetch?.saturating_duration_since(now)).filter(|d| *d >= MIN_SLEEP)
+    }
+
+    /// How long to sleep when WiFi still isn't up: on a timer wake that
+    /// has waited [`CONNECT_BUDGET`], until a fetch would next be due.
+    pub fn sleep_offline(&self, now: Instant, interval: Duration) -> Option<Duration> {
+        let gave_up = self.timer_wake && now >= self.woke + CONNECT_BUDGET;
+        gave_up.then_some(interval.max(MIN_SLEEP))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn led_state_survives_the_image() {
+        let mut state = LedState::new(3, 200);
+        state.set(0, Color::new(0, 255, 0)).unwrap();
+        state.set(2, Color::new(255, 0, 255)).unwrap();
+        state.set_display_on(false);
+        let mut image = [0; IMAGE_LEN];
+        assert!(save(&state, &mut image));
+
+        let mut woke = LedState::new(3, 40);
+        assert!(restore(&image, &mut woke));
+        assert_eq!(woke.brightness(), 200);
+        assert!(!woke.display_on());
+        assert_eq!(woke.get(2).unwrap(), Color::new(255, 0, 255));
+
+        // A different strip, a blank image, or a flipped bit is repainted
+        assert!(!restore(&image, &mut LedState::new(4, 40)));
+        assert!(!restore(&[0; IMAGE_LEN], &mut woke));
+        image[HEADER_LEN] ^= 1;
+        let mut fresh = LedState::new(3, 40);
+        assert!(!restore(&image, &mut fresh));
+        assert_eq!(fresh.brightness(), 40);
+
+        assert!(!save(&LedState::new(MAX_SAVED_LEDS + 1, 40), &mut image));
+        assert!(!restore(&image, &mut LedState::new(MAX_SAVED_LEDS + 1, 40)));
+    }
+
+    #[test]
+    fn sleeps_after_the_fetch_settles() {
+        let now = Instant::now();
+        let mut schedule = SleepSchedule::new(now, true);
+        let next = now + Duration::from_secs(900);
+        // Nothing fetched yet this wake
+        assert_eq!(schedule.sleep_for(now, Some(next)), None);
+        schedule.fetched(now);
+        assert_eq!(schedule.sleep_for(now + Duration::from_secs(1), Some(next)), None);
+        let settled = now + SETTLE;
+        assert_eq!(schedule.sleep_for(settled, Some(next)), Some(Duration::from_secs(895)));
+        // Too close to the next fetch to be worth it
+        assert_eq!(schedule.sleep_for(settled, Some(settled + Duration::from_secs(5))), None);
+    }
+
+    #[test]
+    fn power_on_stays_awake_first() {
+        let now = Instant::now();
+        let mut schedule = SleepSchedule::new(now, false);
+        schedule.fetched(now);
+        let next = now + Duration::from_secs(900);
+        assert_eq!(schedule.sleep_for(now + SETTLE, Some(next)), None);
+        let later = now + AWAKE_AFTER_BOOT;
+        assert_eq!(schedule.sleep_for(later, Some(next)), Some(Duration::from_secs(600)));
+        // Only timer wakes give up on WiFi
+        assert_eq!(schedule.sleep_offline(later, Duration::from_secs(900)), None);
+        let woke = SleepSchedule::new(now, true);
+        assert_eq!(woke.sleep_offline(now, Duration::from_secs(900)), None);
+        assert_eq!(
+            woke.sleep_offline(now + CONNECT_BUDGET, Duration::from_secs(900)),
+            Some(Duration::from_secs(900))
+        );
+    }
+
+    #[test]
+    fn off_by_default() {
+        let config = Config::builder().airport("KSFO").build().unwrap();
+        assert!(!config.settings.power.deep_sleep);
+        let config = Config::from_toml("[settings.power]\ndeep_sleep = true\n").unwrap();
+        assert!(config.settings.power.deep_sleep);
+    }
+}
@@ -0,0 +1,156 @@
+//! Demo mode: made-up weather drifting across the map, for a map with no
+//! network (on a shelf, at a show) that should still look alive. The
+//! firmware shows it when WiFi isn't set up or doesn't connect, and all the
+//! time with `attract_mode` on.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::config::{is_special_code, Config};
+use crate::led::{update_leds_from_metars, LedState};
+use crate::metar::{FlightCategory, MetarReport};
+
+/// How long each frame of the demo is shown.
+pub const DEMO_STEP: Duration = Duration::from_secs(2);
+
+/// Moves a band of bad weather along the airport list, one airport per
+/// [`DEMO_STEP`]: LIFR with a thunderstorm at its center, IFR and MVFR around
+/// it, and VFR with some wind everywhere else.
+#[derive(Debug, Clone, Default)]
+pub struct DemoAnimator {
+    frame: usize,
+    next_step: Option<Instant>,
+}
+
+impl DemoAnimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Paint the next frame if it's due. Returns true if LEDs changed and
+    /// should be written out.
+    pub fn tick(&mut self, now: Instant, config: &Config, led_state: &mut LedState) -> bool {
+        if self.next_step.is_some_and(|t| now < t) {
+            return false;
+        }
+        self.next_step = Some(now + DEMO_STEP);
+
+        let metars: HashMap<String, MetarReport> = demo_reports(config, self.frame)
+            .into_iter()
+            .map(|report| (report.icao_id.clone(), report))
+            .collect();
+        let summary =
+            update_leds_from_metars(led_state, &config.airports, &metars, &config.settings);
+        led_state.set_lightning_indices(summary.lightning_indices);
+        led_state.set_distant_lightning_indices(summary.distant_lightning_indices);
+        let blink = if config.settings.gust_blink {
+            summary.gust_indices
+        } else {
+            Vec::new()
+        };
+        led_state.set_blink_indices(blink);
+
+        self.frame = self.frame.wrapping_add(1);
+        true
+    }
+
+    /// When `tick` next has something to do, for sleeping between frames.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.next_step
+    }
+}
+
+/// Fake reports for frame `frame`, one per real airport on the map.
+pub fn demo_reports(config: &Config, frame: usize) -> Vec<MetarReport> {
+    let codes: Vec<&str> = config
+        .airports
+        .iter()
+        .filter(|a| a.legend_color.is_none() && !is_special_code(&a.code))
+        .map(|a| a.code.as_str())
+        .collect();
+    let len = codes.len().max(1);
+    let front = frame % len;
+    codes
+        .iter()
+        .enumerate()
+        .map(|(i, code)| {
+            // Distance behind the front, wrapping around the list
+            let distance = (i + len - front) % len;
+            let category = match distance {
+                0 => FlightCategory::Lifr,
+                1 => FlightCategory::Ifr,
+                2 | 3 => FlightCategory::Mvfr,
+                _ => FlightCategory::Vfr,
+            };
+            let (wspd, wgst) = match (i + frame / 4) % 7 {
+                0 => (Some(30), None),
+                3 => (Some(12), Some(28)),
+                _ => (Some(5), None),
+            };
+            MetarReport {
+                icao_id: code.to_string(),
+                flt_cat: Some(category.as_str().to_string()),
+                wspd,
+                wgst,
+                wx_string: match distance {
+                    0 => Some("TSRA".to_string()),
+                    1 => Some("VCTS".to_string()),
+                    _ => None,
+                },
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::{COLOR_IFR, COLOR_LIFR, COLOR_VFR};
+
+    fn config() -> Config {
+        Config::builder()
+            .airport("KSFO")
+            .airport("KOAK")
+            .airport("VFR")
+            .airport("KSJC")
+            .airport("KHAF")
+            .airport("KPAO")
+            .airport("KSQL")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn front_moves_one_airport_per_step() {
+        let config = config();
+        let mut demo = DemoAnimator::new();
+        let mut leds = LedState::new(config.num_leds(), 255);
+        let start = Instant::now();
+
+        assert!(demo.tick(start, &config, &mut leds));
+        assert_eq!(leds.get(0).unwrap(), COLOR_LIFR);
+        assert_eq!(leds.get(1).unwrap(), COLOR_IFR);
+        // The legend LED keeps its color
+        assert_eq!(leds.get(2).unwrap(), COLOR_VFR);
+
+        assert!(!demo.tick(start + DEMO_STEP / 2, &config, &mut leds));
+        assert!(demo.tick(start + DEMO_STEP, &config, &mut leds));
+        assert_eq!(leds.get(1).unwrap(), COLOR_LIFR);
+        assert_eq!(leds.get(3).unwrap(), COLOR_IFR);
+        assert_eq!(demo.next_deadline(), Some(start + DEMO_STEP * 2));
+    }
+
+    #[test]
+    fn reports_cover_real_airports_with_every_category() {
+        let config = config();
+        let reports = demo_reports(&config, 9);
+        assert_eq!(reports.len(), 6);
+        assert!(reports.iter().all(|r| r.icao_id != "VFR"));
+        for category in ["VFR", "MVFR", "IFR", "LIFR"] {
+            assert!(reports.iter().any(|r| r.flt_cat.as_deref() == Some(category)));
+        }
+        assert!(reports.iter().any(|r| r.wx_string.as_deref() == Some("TSRA")));
+        assert!(demo_reports(&Config::builder().build().unwrap(), 0).is_empty());
+    }
+}
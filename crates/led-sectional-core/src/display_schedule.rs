@@ -0,0 +1,101 @@
+//! Quiet hours for the LEDs, `[settings.display_off]`: inside a window the
+//! strip is blanked while the map keeps updating underneath, so it comes
+//! back at once, with current weather, when the window ends. Motion or a
+//! button press lights it early for `wake_secs`.
+
+use std::time::{Duration, Instant};
+
+use crate::clock::{LocalTime, TimeWindow};
+use crate::config::DisplayOffSettings;
+
+#[derive(Debug, Clone, Default)]
+pub struct DisplaySchedule {
+    windows: Vec<TimeWindow>,
+    wake_for: Duration,
+    /// Lit early until then, inside a window.
+    awake_until: Option<Instant>,
+}
+
+impl DisplaySchedule {
+    pub fn new(settings: &DisplayOffSettings) -> Self {
+        let mut schedule = Self::default();
+        schedule.configure(settings);
+        schedule
+    }
+
+    /// Follow changed settings, keeping a wake in progress.
+    pub fn configure(&mut self, settings: &DisplayOffSettings) {
+        self.windows = settings.time_windows();
+        self.wake_for = Duration::from_secs(settings.wake_secs);
+    }
+
+    /// Light the map for `wake_secs` from `now`, or keep it lit that much
+    /// longer; a wake outside the windows is forgotten.
+    pub fn wake(&mut self, now: Instant) {
+        self.awake_until = Some(now + self.wake_for);
+    }
+
+    /// Whether the LEDs should be dark at local time `time`: inside a
+    /// window and not woken. Never while the clock isn't set.
+    pub fn is_dark(&mut self, time: Option<&LocalTime>, now: Instant) -> bool {
+        let in_window = time.is_some_and(|t| self.windows.iter().any(|w| w.contains(t)));
+        if !in_window {
+            // A wake before a window starts doesn't carry into it
+            self.awake_until = None;
+            return false;
+        }
+        match self.awake_until {
+            Some(until) if now < until => false,
+            _ => {
+                self.awake_until = None;
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TimeZone;
+
+    fn at(hour: i64, minute: i64) -> LocalTime {
+        TimeZone::UTC.to_local(hour * 3600 + minute * 60)
+    }
+
+    fn schedule() -> DisplaySchedule {
+        DisplaySchedule::new(&DisplayOffSettings {
+            windows: vec!["23:00-06:00".into(), "12:00-13:00".into()],
+            wake_secs: 60,
+            motion_pin: None,
+        })
+    }
+
+    #[test]
+    fn dark_inside_windows_only() {
+        let mut schedule = schedule();
+        let now = Instant::now();
+        assert!(!schedule.is_dark(Some(&at(22, 59)), now));
+        assert!(schedule.is_dark(Some(&at(23, 0)), now));
+        assert!(schedule.is_dark(Some(&at(5, 59)), now));
+        assert!(!schedule.is_dark(Some(&at(6, 0)), now));
+        assert!(schedule.is_dark(Some(&at(12, 30)), now));
+        assert!(!schedule.is_dark(None, now));
+    }
+
+    #[test]
+    fn waking_lights_the_map_for_a_while() {
+        let mut schedule = schedule();
+        let start = Instant::now();
+        let night = at(2, 0);
+        assert!(schedule.is_dark(Some(&night), start));
+        schedule.wake(start);
+        assert!(!schedule.is_dark(Some(&night), start + Duration::from_secs(59)));
+        assert!(schedule.is_dark(Some(&night), start + Duration::from_secs(60)));
+
+        // A wake just before the window doesn't keep it lit
+        schedule.wake(start);
+        assert!(!schedule.is_dark(Some(&at(22, 59)), start));
+        assert!(schedule.is_dark(Some(&at(23, 0)), start + Duration::from_secs(1)));
+    }
+}
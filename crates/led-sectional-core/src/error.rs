@@ -5,11 +5,51 @@ pub enum Error {
     #[error("config parse error: {0}")]
     ConfigParse(#[from] toml::de::Error),
 
+    #[error("config serialize error: {0}")]
+    ConfigSerialize(#[from] toml::ser::Error),
+
     #[error("JSON parse error: {0}")]
     JsonParse(#[from] serde_json::Error),
 
     #[error("LED index {index} out of bounds (num_leds: {num_leds})")]
     LedIndexOutOfBounds { index: usize, num_leds: usize },
+
+    #[error("config has {num_leds} LEDs but settings.max_leds is {max}")]
+    TooManyLeds { num_leds: usize, max: usize },
+
+    #[error(
+        "config needs about {estimated} bytes of RAM, over the {budget} byte budget \
+         (settings.memory_budget_kb)"
+    )]
+    MemoryBudgetExceeded { estimated: usize, budget: usize },
+
+    #[error("unknown profile: {0}")]
+    UnknownProfile(String),
+
+    #[error("LED index {index} assigned to both {first} and {second}")]
+    DuplicateLedIndex {
+        index: usize,
+        first: String,
+        second: String,
+    },
+
+    #[error("no airport left for LED index {index}")]
+    UnfilledLed { index: usize },
+
+    #[error("sealed data {0}")]
+    Unseal(&'static str),
+
+    #[error("firmware image {0}")]
+    FirmwareImage(String),
+
+    #[error("update manifest {0}")]
+    Manifest(String),
+
+    #[error("credential {0}")]
+    Credential(&'static str),
+
+    #[error("gzip stream {0}")]
+    Gzip(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,126 @@
+//! State changes the main loop announces. The pieces that react to them,
+//! such as the web server's snapshots, MQTT telemetry, the log and the LED
+//! strip, implement [`Subscriber`], so the loop publishes a change once
+//! instead of calling each of them where it happens.
+
+use std::collections::VecDeque;
+
+use crate::dashboard::MapSnapshot;
+use crate::wifi_link::LinkState;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A fetch finished, with the map as it now shows. `error` is set if it
+    /// failed.
+    MetarUpdated(MapSnapshot),
+    /// The WiFi link moved to another state.
+    WifiStateChanged(LinkState),
+    /// The LEDs run at a new brightness.
+    BrightnessChanged(u8),
+}
+
+pub trait Subscriber {
+    fn on_event(&mut self, event: &Event);
+}
+
+/// Absent subscribers, such as telemetry with no broker set, miss events.
+impl<T: Subscriber> Subscriber for Option<T> {
+    fn on_event(&mut self, event: &Event) {
+        if let Some(subscriber) = self {
+            subscriber.on_event(event);
+        }
+    }
+}
+
+/// Events published since the last dispatch.
+#[derive(Debug, Default)]
+pub struct EventBus {
+    pending: VecDeque<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn publish(&mut self, event: Event) {
+        self.pending.push_back(event);
+    }
+
+    /// Hand each pending event, oldest first, to every subscriber in
+    /// order. Returns how many there were.
+    pub fn dispatch(&mut self, subscribers: &mut [&mut dyn Subscriber]) -> usize {
+        let count = self.pending.len();
+        for event in self.pending.drain(..) {
+            for subscriber in subscribers.iter_mut() {
+                subscriber.on_event(&event);
+            }
+        }
+        count
+    }
+}
+
+/// Whether the strip needs writing, after a change that alters what it
+/// shows.
+#[derive(Debug, Default)]
+pub struct Repaint {
+    needed: bool,
+}
+
+impl Repaint {
+    /// True once after each such change.
+    pub fn take(&mut self) -> bool {
+        std::mem::take(&mut self.needed)
+    }
+}
+
+impl Subscriber for Repaint {
+    fn on_event(&mut self, event: &Event) {
+        match event {
+            Event::MetarUpdated(_) | Event::BrightnessChanged(_) => self.needed = true,
+            Event::WifiStateChanged(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Vec<Event>);
+
+    impl Subscriber for Recorder {
+        fn on_event(&mut self, event: &Event) {
+            self.0.push(event.clone());
+        }
+    }
+
+    #[test]
+    fn every_subscriber_sees_events_in_order() {
+        let mut bus = EventBus::new();
+        bus.publish(Event::BrightnessChanged(40));
+        bus.publish(Event::MetarUpdated(MapSnapshot::default()));
+
+        let (mut first, mut second) = (Recorder::default(), Some(Recorder::default()));
+        let mut absent: Option<Recorder> = None;
+        assert_eq!(bus.dispatch(&mut [&mut first, &mut second, &mut absent]), 2);
+        assert_eq!(first.0[0], Event::BrightnessChanged(40));
+        assert_eq!(second.unwrap().0, first.0);
+        assert_eq!(bus.dispatch(&mut [&mut first]), 0);
+    }
+
+    #[test]
+    fn repaint_after_changes_to_the_strip() {
+        let mut repaint = Repaint::default();
+        let online = LinkState::Online {
+            ssid: "Hangar".into(),
+            ip: std::net::Ipv4Addr::new(192, 168, 1, 20),
+        };
+        repaint.on_event(&Event::WifiStateChanged(online));
+        assert!(!repaint.take());
+        repaint.on_event(&Event::BrightnessChanged(40));
+        assert!(repaint.take());
+        assert!(!repaint.take());
+    }
+}
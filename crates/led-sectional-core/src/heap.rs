@@ -0,0 +1,89 @@
+//! Low-memory detection. Below `settings.low_heap_kb` free, or without a
+//! block big enough for a TLS record, the firmware sheds what it can do
+//! without: lightning and gust animations stop, the log keeps
+//! [`LOW_MEMORY_LOG_LINES`], and the weather connection isn't kept open
+//! between fetches. That buys time before an allocation fails and resets
+//! the map.
+
+/// Extra free heap, past the threshold, needed to leave low-memory mode, so
+/// a heap hovering near it doesn't flap.
+const RECOVERY_MARGIN: u32 = 8 * 1024;
+
+/// Smallest largest-free-block that still fits a TLS record buffer.
+pub const MIN_BLOCK: u32 = 16 * 1024;
+
+/// Lines the log keeps while memory is low.
+pub const LOW_MEMORY_LOG_LINES: usize = 20;
+
+/// Decides from heap readings whether memory is low.
+#[derive(Debug, Clone)]
+pub struct HeapMonitor {
+    threshold: u32,
+    low: bool,
+}
+
+impl HeapMonitor {
+    /// Watch for less than `threshold_kb` KiB free.
+    pub fn new(threshold_kb: u32) -> Self {
+        Self {
+            threshold: threshold_kb * 1024,
+            low: false,
+        }
+    }
+
+    pub fn set_threshold(&mut self, threshold_kb: u32) {
+        self.threshold = threshold_kb * 1024;
+    }
+
+    /// Record the free heap and largest free block, in bytes. Returns the
+    /// new state when memory becomes low or recovers.
+    pub fn sample(&mut self, free: u32, largest_block: u32) -> Option<bool> {
+        let low = if self.low {
+            free < self.threshold + RECOVERY_MARGIN || largest_block < MIN_BLOCK + RECOVERY_MARGIN
+        } else {
+            free < self.threshold || largest_block < MIN_BLOCK
+        };
+        if low == self.low {
+            return None;
+        }
+        self.low = low;
+        Some(low)
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.low
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn low_with_hysteresis() {
+        let mut heap = HeapMonitor::new(32);
+        assert_eq!(heap.sample(100_000, 60_000), None);
+        assert_eq!(heap.sample(30_000, 20_000), Some(true));
+        assert!(heap.is_low());
+        // Back over the threshold, but not by the margin
+        assert_eq!(heap.sample(34_000, 20_000), None);
+        assert_eq!(heap.sample(45_000, 30_000), Some(false));
+    }
+
+    #[test]
+    fn fragmentation_counts_as_low() {
+        let mut heap = HeapMonitor::new(32);
+        assert_eq!(heap.sample(90_000, 12_000), Some(true));
+        assert_eq!(heap.sample(90_000, 20_000), None);
+        assert_eq!(heap.sample(90_000, 30_000), Some(false));
+    }
+
+    #[test]
+    fn threshold_setting_is_clamped() {
+        assert_eq!(Config::from_toml("").unwrap().settings.low_heap_kb, 32);
+        let config = Config::from_toml("[settings]\nlow_heap_kb = 1\n").unwrap();
+        assert_eq!(config.settings.low_heap_kb, 8);
+        assert!(config.diagnostics.iter().any(|d| d.field == "settings.low_heap_kb"));
+    }
+}
@@ -0,0 +1,169 @@
+//! Alerts for the home airport, `settings.home_airport`: the firmware chirps
+//! a buzzer when it goes LIFR or starts reporting a thunderstorm. Only
+//! changes alert; the first report after boot sets the starting point.
+
+use std::time::Duration;
+
+use crate::dashboard::{MapRow, MapSnapshot};
+use crate::events::{Event, Subscriber};
+use crate::metar::FlightCategory;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alert {
+    Lifr,
+    Thunderstorm,
+}
+
+impl Alert {
+    /// Tone and silence lengths, alternating, starting with a tone: two
+    /// chirps for LIFR, three quicker ones for a thunderstorm.
+    pub fn pattern(self) -> &'static [Duration] {
+        const fn ms(ms: u64) -> Duration {
+            Duration::from_millis(ms)
+        }
+        const LIFR: [Duration; 3] = [ms(150), ms(100), ms(150)];
+        const THUNDERSTORM: [Duration; 5] = [ms(80), ms(60), ms(80), ms(60), ms(80)];
+        match self {
+            Self::Lifr => &LIFR,
+            Self::Thunderstorm => &THUNDERSTORM,
+        }
+    }
+}
+
+/// What was last seen at the home airport.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Conditions {
+    lifr: bool,
+    thunderstorm: bool,
+}
+
+/// Watches fetches for the home airport getting worse.
+#[derive(Debug, Default)]
+pub struct HomeAlerts {
+    airport: Option<String>,
+    last: Option<Conditions>,
+    pending: Vec<Alert>,
+}
+
+impl HomeAlerts {
+    pub fn new(airport: Option<&str>) -> Self {
+        let mut alerts = Self::default();
+        alerts.set_airport(airport);
+        alerts
+    }
+
+    /// Watch another airport, starting afresh; None stops alerts.
+    pub fn set_airport(&mut self, airport: Option<&str>) {
+        let airport = airport.map(str::to_ascii_uppercase);
+        if airport != self.airport {
+            self.airport = airport;
+            self.last = None;
+        }
+    }
+
+    /// Alerts raised since the last call, oldest first.
+    pub fn take_alerts(&mut self) -> Vec<Alert> {
+        std::mem::take(&mut self.pending)
+    }
+
+    fn update(&mut self, snapshot: &MapSnapshot) {
+        let Some(airport) = &self.airport else {
+            return;
+        };
+        if snapshot.error.is_some() {
+            return;
+        }
+        let home = |row: &&MapRow| row.weather && row.code.eq_ignore_ascii_case(airport);
+        let Some(row) = snapshot.rows.iter().find(home) else {
+            return;
+        };
+        // No report says nothing either way
+        let Some(report) = &row.report else {
+            return;
+        };
+        let now = Conditions {
+            lifr: row.category == Some(FlightCategory::Lifr),
+            thunderstorm: report.has_thunderstorm(),
+        };
+        if let Some(last) = self.last.replace(now) {
+            if now.lifr && !last.lifr {
+                self.pending.push(Alert::Lifr);
+            }
+            if now.thunderstorm && !last.thunderstorm {
+                self.pending.push(Alert::Thunderstorm);
+            }
+        }
+    }
+}
+
+impl Subscriber for HomeAlerts {
+    fn on_event(&mut self, event: &Event) {
+        if let Event::MetarUpdated(snapshot) = event {
+            self.update(snapshot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::Color;
+    use crate::metar::MetarReport;
+
+    fn snapshot(category: FlightCategory, wx: Option<&str>) -> Event {
+        let report = MetarReport {
+            icao_id: "KSFO".into(),
+            wx_string: wx.map(String::from),
+            ..Default::default()
+        };
+        let row = MapRow {
+            led: 0,
+            code: "KSFO".into(),
+            name: "KSFO".into(),
+            color: Color::new(255, 0, 255),
+            category: Some(category),
+            weather: true,
+            fallback: None,
+            wind: None,
+            observed: None,
+            position: None,
+            report: Some(report),
+        };
+        Event::MetarUpdated(MapSnapshot {
+            rows: vec![row],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn alerts_when_the_home_airport_worsens() {
+        let mut alerts = HomeAlerts::new(Some("ksfo"));
+        // Already LIFR at boot: no alert
+        alerts.on_event(&snapshot(FlightCategory::Lifr, None));
+        assert!(alerts.take_alerts().is_empty());
+        alerts.on_event(&snapshot(FlightCategory::Ifr, None));
+        alerts.on_event(&snapshot(FlightCategory::Lifr, Some("TSRA BR")));
+        assert_eq!(alerts.take_alerts(), [Alert::Lifr, Alert::Thunderstorm]);
+        // Still the same: nothing new
+        alerts.on_event(&snapshot(FlightCategory::Lifr, Some("TS")));
+        assert!(alerts.take_alerts().is_empty());
+        assert_eq!(Alert::Thunderstorm.pattern().len() % 2, 1);
+    }
+
+    #[test]
+    fn other_airports_and_failed_fetches_are_ignored() {
+        let mut alerts = HomeAlerts::new(Some("KOAK"));
+        alerts.on_event(&snapshot(FlightCategory::Vfr, None));
+        alerts.on_event(&snapshot(FlightCategory::Lifr, None));
+        assert!(alerts.take_alerts().is_empty());
+
+        alerts.set_airport(Some("KSFO"));
+        alerts.on_event(&snapshot(FlightCategory::Vfr, None));
+        alerts.on_event(&Event::MetarUpdated(MapSnapshot {
+            error: Some("HTTP 503".into()),
+            ..Default::default()
+        }));
+        alerts.on_event(&snapshot(FlightCategory::Lifr, None));
+        assert_eq!(alerts.take_alerts(), [Alert::Lifr]);
+    }
+}
@@ -1,4 +1,6 @@
 use crate::error::{Error, Result};
+use crate::metar::{FlightCategory, Lightning};
+use crate::power_budget;
 
 /// RGB color representation, compatible with smart-leds RGB8.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -12,6 +14,19 @@ impl Color {
     pub const fn new(r: u8, g: u8, b: u8) -> Self {
         Self { r, g, b }
     }
+
+    /// Blend toward `other` by `amount` (0 = self, 255 = other).
+    pub fn blend(self, other: Color, amount: u8) -> Color {
+        let mix = |a: u8, b: u8| {
+            let (a, b, t) = (a as u16, b as u16, amount as u16);
+            ((a * (255 - t) + b * t) / 255) as u8
+        };
+        Color {
+            r: mix(self.r, other.r),
+            g: mix(self.g, other.g),
+            b: mix(self.b, other.b),
+        }
+    }
 }
 
 // Flight category colors (matching the original C project)
@@ -20,20 +35,39 @@ pub const COLOR_MVFR: Color = Color::new(0, 0, 255);
 pub const COLOR_IFR: Color = Color::new(255, 0, 0);
 pub const COLOR_LIFR: Color = Color::new(255, 0, 255);
 pub const COLOR_WIND: Color = Color::new(255, 255, 0);
+pub const COLOR_GUST: Color = Color::new(255, 96, 0);
 pub const COLOR_UNKNOWN: Color = Color::new(0, 0, 0);
 pub const COLOR_LIGHTNING: Color = Color::new(255, 255, 255);
+pub const COLOR_LIGHTNING_DISTANT: Color = Color::new(96, 96, 96);
+pub const COLOR_FOG: Color = Color::new(160, 160, 160);
+
+/// How strongly fog-risk airports are tinted toward `COLOR_FOG`.
+const FOG_TINT_AMOUNT: u8 = 96;
+/// How far dimmed indicator LEDs fade toward off.
+const INDICATOR_DIM_AMOUNT: u8 = 192;
 
 // Status colors
 pub const COLOR_CONNECTING: Color = Color::new(255, 165, 0);
 pub const COLOR_CONNECTED: Color = Color::new(128, 0, 128);
 pub const COLOR_FETCH_ERROR: Color = Color::new(0, 255, 255);
+pub const COLOR_RECONNECTING: Color = Color::new(64, 0, 255);
+pub const COLOR_OBTAINING_IP: Color = Color::new(255, 255, 255);
 
 /// Manages the LED color buffer and brightness.
 pub struct LedState {
     leds: Vec<Color>,
     brightness: u8,
+    brightness_limits: (u8, u8),
     lightning_indices: Vec<usize>,
+    distant_lightning_indices: Vec<usize>,
     lightning_saved: Vec<(usize, Color)>,
+    blink_indices: Vec<usize>,
+    blink_saved: Vec<(usize, Color)>,
+    indicator_indices: Vec<usize>,
+    indicator_dim: bool,
+    display_on: bool,
+    scheduled_off: bool,
+    max_current_ma: Option<u32>,
 }
 
 impl LedState {
@@ -41,8 +75,17 @@ impl LedState {
         Self {
             leds: vec![COLOR_UNKNOWN; num_leds],
             brightness,
+            brightness_limits: (0, 255),
             lightning_indices: Vec::new(),
+            distant_lightning_indices: Vec::new(),
             lightning_saved: Vec::new(),
+            blink_indices: Vec::new(),
+            blink_saved: Vec::new(),
+            indicator_indices: Vec::new(),
+            indicator_dim: false,
+            display_on: true,
+            scheduled_off: false,
+            max_current_ma: None,
         }
     }
 
@@ -72,25 +115,92 @@ impl LedState {
         self.leds.fill(color);
     }
 
+    /// Set the brightness, clamped to the limits from `set_brightness_limits`.
     pub fn set_brightness(&mut self, brightness: u8) {
-        self.brightness = brightness;
+        let (min, max) = self.brightness_limits;
+        self.brightness = brightness.clamp(min, max);
+    }
+
+    /// Bound every later `set_brightness`, and re-clamp the current level.
+    pub fn set_brightness_limits(&mut self, min: u8, max: u8) {
+        self.brightness_limits = (min, max.max(min));
+        self.set_brightness(self.brightness);
     }
 
     pub fn brightness(&self) -> u8 {
         self.brightness
     }
 
-    /// Returns the LED buffer with brightness scaling applied.
+    /// Blank the LEDs without losing their colors, as when switched off
+    /// remotely. Returns whether this changed anything.
+    pub fn set_display_on(&mut self, on: bool) -> bool {
+        std::mem::replace(&mut self.display_on, on) != on
+    }
+
+    pub fn display_on(&self) -> bool {
+        self.display_on
+    }
+
+    /// Blank the LEDs for display-off hours, apart from `set_display_on` so
+    /// the end of the window doesn't turn on a display switched off by
+    /// hand; see [`crate::display_schedule`]. Returns whether this changed
+    /// anything.
+    pub fn set_scheduled_off(&mut self, off: bool) -> bool {
+        std::mem::replace(&mut self.scheduled_off, off) != off
+    }
+
+    pub fn scheduled_off(&self) -> bool {
+        self.scheduled_off
+    }
+
+    /// Dim frames that would draw more than a supply rated `max_ma` can
+    /// give; see [`crate::power_budget`]. None lifts the limit.
+    pub fn set_current_limit(&mut self, max_ma: Option<u32>) {
+        self.max_current_ma = max_ma;
+    }
+
+    /// What the current frame would draw, in mA, if it's being dimmed to
+    /// fit the current limit.
+    pub fn over_current_limit(&self) -> Option<u32> {
+        let max_ma = self.max_current_ma?;
+        let estimate = power_budget::estimate_ma(&self.unlimited_buffer());
+        (estimate > power_budget::strip_budget_ma(max_ma)).then_some(estimate)
+    }
+
+    /// Returns the LED buffer with brightness scaling applied, the
+    /// indicator LEDs dimmed if `set_indicator_dim` is on, and the whole
+    /// frame dimmed further if it would overrun `set_current_limit`. All
+    /// black while the display is off or scheduled off.
     pub fn brightness_scaled_buffer(&self) -> Vec<Color> {
+        let mut buf = self.unlimited_buffer();
+        if let Some(max_ma) = self.max_current_ma {
+            power_budget::fit(&mut buf, max_ma);
+        }
+        buf
+    }
+
+    fn unlimited_buffer(&self) -> Vec<Color> {
+        if !self.display_on || self.scheduled_off {
+            return vec![Color::new(0, 0, 0); self.leds.len()];
+        }
         let scale = self.brightness as u16;
-        self.leds
+        let mut buf: Vec<Color> = self
+            .leds
             .iter()
             .map(|c| Color {
                 r: ((c.r as u16 * scale) / 255) as u8,
                 g: ((c.g as u16 * scale) / 255) as u8,
                 b: ((c.b as u16 * scale) / 255) as u8,
             })
-            .collect()
+            .collect();
+        if self.indicator_dim {
+            for &i in &self.indicator_indices {
+                if let Some(c) = buf.get_mut(i) {
+                    *c = c.blend(COLOR_UNKNOWN, INDICATOR_DIM_AMOUNT);
+                }
+            }
+        }
+        buf
     }
 
     // -- Lightning management --
@@ -104,17 +214,29 @@ impl LedState {
         self.lightning_indices = indices;
     }
 
-    /// Flash lightning LEDs to white. Returns true if any LEDs were flashed.
+    /// Set which LED indices should flash dimly for distant lightning.
+    pub fn set_distant_lightning_indices(&mut self, indices: Vec<usize>) {
+        self.distant_lightning_indices = indices;
+    }
+
+    /// Flash lightning LEDs: white for lightning at the field, dim white for
+    /// distant lightning. Returns true if any LEDs were flashed.
     pub fn apply_lightning_flash(&mut self) -> bool {
-        if self.lightning_indices.is_empty() {
+        if self.lightning_indices.is_empty() && self.distant_lightning_indices.is_empty() {
             return false;
         }
         // Save current colors before flashing
         self.lightning_saved = self
             .lightning_indices
             .iter()
+            .chain(&self.distant_lightning_indices)
             .filter_map(|&i| self.leds.get(i).map(|&c| (i, c)))
             .collect();
+        for &idx in &self.distant_lightning_indices {
+            if idx < self.leds.len() {
+                self.leds[idx] = COLOR_LIGHTNING_DISTANT;
+            }
+        }
         for &idx in &self.lightning_indices {
             if idx < self.leds.len() {
                 self.leds[idx] = COLOR_LIGHTNING;
@@ -131,6 +253,67 @@ impl LedState {
             }
         }
     }
+
+    // -- Status indicator --
+
+    /// Set which LEDs show status indicators, e.g. the legend LEDs.
+    pub fn set_indicator_indices(&mut self, indices: Vec<usize>) {
+        self.indicator_indices = indices;
+    }
+
+    /// Dim the indicator LEDs in the output without changing their colors;
+    /// toggle it to blink them. Returns true if the output changed.
+    pub fn set_indicator_dim(&mut self, dim: bool) -> bool {
+        let changed = dim != self.indicator_dim && !self.indicator_indices.is_empty();
+        self.indicator_dim = dim;
+        changed
+    }
+
+    pub fn indicator_dim(&self) -> bool {
+        self.indicator_dim
+    }
+
+    // -- Blinking --
+
+    /// Set which LED indices blink (e.g. gusty airports). Call after the LED
+    /// colors have been updated so the current colors are what blinks.
+    pub fn set_blink_indices(&mut self, indices: Vec<usize>) {
+        self.blink_indices = indices;
+        self.blink_saved.clear();
+    }
+
+    /// Stop a blink part-way through, putting back the colors it has off.
+    /// Returns true if any LEDs changed.
+    pub fn end_blink(&mut self) -> bool {
+        let changed = !self.blink_saved.is_empty();
+        for (idx, color) in self.blink_saved.drain(..) {
+            self.leds[idx] = color;
+        }
+        changed
+    }
+
+    /// Toggle blinking LEDs between off and their color. Returns true if any
+    /// LEDs changed.
+    pub fn toggle_blink(&mut self) -> bool {
+        if self.blink_indices.is_empty() {
+            return false;
+        }
+        if self.blink_saved.is_empty() {
+            self.blink_saved = self
+                .blink_indices
+                .iter()
+                .filter_map(|&i| self.leds.get(i).map(|&c| (i, c)))
+                .collect();
+            for &(idx, _) in &self.blink_saved {
+                self.leds[idx] = COLOR_UNKNOWN;
+            }
+        } else {
+            for (idx, color) in self.blink_saved.drain(..) {
+                self.leds[idx] = color;
+            }
+        }
+        true
+    }
 }
 
 /// Determine LED color for a flight category.
@@ -154,6 +337,45 @@ pub fn flight_category_color(
     }
 }
 
+/// How the wind at an airport should affect its color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindCondition {
+    Calm,
+    /// Over the wind threshold (sustained, or either when gusts aren't judged separately).
+    Windy,
+    /// Sustained wind is fine but gusts exceed `gust_threshold_kt`.
+    Gusty,
+}
+
+/// Classify wind and gusts against the thresholds in `settings`.
+pub fn wind_condition(
+    wind_speed: Option<u32>,
+    wind_gust: Option<u32>,
+    settings: &crate::config::Settings,
+) -> WindCondition {
+    if !settings.do_winds {
+        return WindCondition::Calm;
+    }
+    let speed = wind_speed.unwrap_or(0);
+    let gust = wind_gust.unwrap_or(0);
+    match settings.gust_threshold_kt {
+        None if speed.max(gust) > settings.wind_threshold_kt => WindCondition::Windy,
+        Some(_) if speed > settings.wind_threshold_kt => WindCondition::Windy,
+        Some(limit) if gust > limit => WindCondition::Gusty,
+        _ => WindCondition::Calm,
+    }
+}
+
+/// Return the base color for a flight category, without wind override.
+pub fn category_color(category: FlightCategory) -> Color {
+    match category {
+        FlightCategory::Vfr => COLOR_VFR,
+        FlightCategory::Mvfr => COLOR_MVFR,
+        FlightCategory::Ifr => COLOR_IFR,
+        FlightCategory::Lifr => COLOR_LIFR,
+    }
+}
+
 /// Return the static legend color for a special airport code, or None for real airports.
 pub fn special_code_color(code: &str) -> Option<Color> {
     match code {
@@ -164,55 +386,150 @@ pub fn special_code_color(code: &str) -> Option<Color> {
         "WVFR" => Some(COLOR_WIND),
         "LTNG" => Some(COLOR_VFR), // Lightning demo shows green, flashes white
         "NULL" => Some(COLOR_UNKNOWN),
+        "SUMM" => Some(COLOR_UNKNOWN), // Recolored after the update pass
         _ => None,
     }
 }
 
-/// Update LED state from config and METAR reports. Returns lightning LED indices.
+/// Pick the report for an airport, using its fallback station when the
+/// primary has no report or no usable flight category.
+pub(crate) fn select_metar<'a>(
+    airport: &crate::config::Airport,
+    metars: &'a std::collections::HashMap<String, crate::metar::MetarReport>,
+) -> Option<&'a crate::metar::MetarReport> {
+    let primary = metars.get(&airport.code);
+    if primary.is_some_and(|m| m.flight_category().is_some()) {
+        return primary;
+    }
+    airport
+        .fallback
+        .as_ref()
+        .and_then(|f| metars.get(f))
+        .or(primary)
+}
+
+/// Result of an LED update pass over the airport list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpdateSummary {
+    /// LED indices that should flash for lightning at the field.
+    pub lightning_indices: Vec<usize>,
+    /// LED indices that should flash dimly for distant lightning.
+    pub distant_lightning_indices: Vec<usize>,
+    /// Flight category of each real airport LED that had a usable report.
+    pub categories: Vec<(usize, FlightCategory)>,
+    /// LED indices of real airports with no report or an unknown category.
+    pub missing: Vec<usize>,
+    /// LED indices of airports flagged by the fog-risk heuristic.
+    pub fog_risk_indices: Vec<usize>,
+    /// LED indices of VFR airports shown in the gust color.
+    pub gust_indices: Vec<usize>,
+}
+
+impl UpdateSummary {
+    /// Worst flight category reported anywhere on the map.
+    pub fn worst_category(&self) -> Option<FlightCategory> {
+        self.categories.iter().map(|&(_, cat)| cat).max()
+    }
+
+    /// Number of airports reporting the given category.
+    pub fn count(&self, category: FlightCategory) -> usize {
+        self.categories
+            .iter()
+            .filter(|&&(_, cat)| cat == category)
+            .count()
+    }
+
+    pub fn has_lightning(&self) -> bool {
+        !self.lightning_indices.is_empty() || !self.distant_lightning_indices.is_empty()
+    }
+
+    /// Color for summary pixels: the worst category's color, or unknown if no data.
+    pub fn summary_color(&self) -> Color {
+        self.worst_category()
+            .map(category_color)
+            .unwrap_or(COLOR_UNKNOWN)
+    }
+}
+
+/// Update LED state from config and METAR reports.
+///
+/// `[[legend]]` codes are checked first, then the built-in special codes.
+/// Airports with the `SUMM` special code are colored by the worst category
+/// anywhere on the map once all other airports have been processed.
 pub fn update_leds_from_metars(
     led_state: &mut LedState,
     airports: &[crate::config::Airport],
     metars: &std::collections::HashMap<String, crate::metar::MetarReport>,
-    wind_threshold: u32,
-    do_winds: bool,
-) -> Vec<usize> {
-    let mut lightning_indices = Vec::new();
+    settings: &crate::config::Settings,
+) -> UpdateSummary {
+    let mut summary = UpdateSummary::default();
+    let mut summary_leds = Vec::new();
 
     for (i, airport) in airports.iter().enumerate() {
         if i >= led_state.num_leds() {
             break;
         }
 
-        if let Some(color) = special_code_color(&airport.code) {
+        if let Some(color) = airport.legend_color {
+            let _ = led_state.set(i, color);
+        } else if let Some(color) = special_code_color(&airport.code) {
             let _ = led_state.set(i, color);
             // LTNG special code always flashes
             if airport.code == "LTNG" {
-                lightning_indices.push(i);
+                summary.lightning_indices.push(i);
+            } else if airport.code == "SUMM" {
+                summary_leds.push(i);
+            }
+        } else if let Some(metar) = select_metar(airport, metars) {
+            let category = metar.flight_category();
+            let wind = wind_condition(metar.wspd, metar.wgst, settings);
+            let mut color = match (category, wind) {
+                (Some(FlightCategory::Vfr), WindCondition::Windy) => COLOR_WIND,
+                (Some(FlightCategory::Vfr), WindCondition::Gusty) => settings.gust_color(),
+                (Some(cat), _) => category_color(cat),
+                (None, _) => COLOR_UNKNOWN,
+            };
+            if category == Some(FlightCategory::Vfr) && wind == WindCondition::Gusty {
+                summary.gust_indices.push(i);
+            }
+            // Fog risk is only a useful warning while conditions are still good
+            if settings.do_fog_risk
+                && metar.fog_risk()
+                && category.is_some_and(|c| c <= FlightCategory::Mvfr)
+            {
+                color = color.blend(COLOR_FOG, FOG_TINT_AMOUNT);
+                summary.fog_risk_indices.push(i);
             }
-        } else if let Some(metar) = metars.get(&airport.code) {
-            let color = flight_category_color(
-                metar.flt_cat.as_deref(),
-                metar.wspd,
-                metar.wgst,
-                wind_threshold,
-                do_winds,
-            );
             let _ = led_state.set(i, color);
 
-            if metar.has_thunderstorm() {
-                lightning_indices.push(i);
+            match category {
+                Some(cat) => summary.categories.push((i, cat)),
+                None => summary.missing.push(i),
+            }
+
+            match metar.lightning() {
+                Some(Lightning::AtStation) => summary.lightning_indices.push(i),
+                Some(Lightning::Distant) => summary.distant_lightning_indices.push(i),
+                None => {}
             }
         } else {
             let _ = led_state.set(i, COLOR_UNKNOWN);
+            summary.missing.push(i);
         }
     }
 
-    lightning_indices
+    let summary_color = summary.summary_color();
+    for i in summary_leds {
+        let _ = led_state.set(i, summary_color);
+    }
+
+    summary
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Settings;
 
     #[test]
     fn color_constants_match_original() {
@@ -289,6 +606,51 @@ mod tests {
         assert_eq!(scaled[0], Color::new(0, 0, 0));
     }
 
+    #[test]
+    fn display_off_blanks_but_keeps_colors() {
+        let mut state = LedState::new(2, 255);
+        state.set(0, COLOR_IFR).unwrap();
+        assert!(state.set_display_on(false));
+        assert!(!state.set_display_on(false));
+        assert_eq!(state.brightness_scaled_buffer(), [Color::new(0, 0, 0); 2]);
+        assert_eq!(state.get(0).unwrap(), COLOR_IFR);
+        assert!(state.set_display_on(true));
+        assert_eq!(state.brightness_scaled_buffer()[0], COLOR_IFR);
+
+        // Either one keeps it dark
+        assert!(state.set_scheduled_off(true));
+        assert_eq!(state.brightness_scaled_buffer()[0], Color::new(0, 0, 0));
+        assert!(state.display_on());
+        assert!(state.set_scheduled_off(false));
+        assert_eq!(state.brightness_scaled_buffer()[0], COLOR_IFR);
+    }
+
+    #[test]
+    fn current_limit_dims_the_frame_only() {
+        let mut state = LedState::new(100, 255);
+        state.set_all(COLOR_LIGHTNING);
+        assert_eq!(state.over_current_limit(), None);
+        state.set_current_limit(Some(2_000));
+        assert_eq!(state.over_current_limit(), Some(6_100));
+        let frame = state.brightness_scaled_buffer();
+        assert!(frame[0].r < 80);
+        assert_eq!(state.brightness(), 255);
+        assert_eq!(state.get(0).unwrap(), COLOR_LIGHTNING);
+
+        state.set_brightness(20);
+        assert_eq!(state.over_current_limit(), None);
+        assert_eq!(state.brightness_scaled_buffer()[0], Color::new(20, 20, 20));
+    }
+
+    #[test]
+    fn color_blend() {
+        let black = Color::new(0, 0, 0);
+        let white = Color::new(255, 255, 255);
+        assert_eq!(black.blend(white, 0), black);
+        assert_eq!(black.blend(white, 255), white);
+        assert_eq!(black.blend(white, 128), Color::new(128, 128, 128));
+    }
+
     #[test]
     fn flight_category_colors() {
         assert_eq!(flight_category_color(Some("VFR"), None, None, 25, true), COLOR_VFR);
@@ -332,6 +694,7 @@ mod tests {
         assert_eq!(special_code_color("WVFR"), Some(COLOR_WIND));
         assert_eq!(special_code_color("LTNG"), Some(COLOR_VFR));
         assert_eq!(special_code_color("NULL"), Some(COLOR_UNKNOWN));
+        assert_eq!(special_code_color("SUMM"), Some(COLOR_UNKNOWN));
         assert_eq!(special_code_color("KSFO"), None);
     }
 
@@ -355,6 +718,23 @@ mod tests {
         assert_eq!(state.get(2).unwrap(), COLOR_MVFR);
     }
 
+    #[test]
+    fn lightning_distant_flashes_dim() {
+        let mut state = LedState::new(3, 255);
+        state.set_all(COLOR_VFR);
+        state.set_lightning_indices(vec![0]);
+        state.set_distant_lightning_indices(vec![1]);
+
+        assert!(state.apply_lightning_flash());
+        assert_eq!(state.get(0).unwrap(), COLOR_LIGHTNING);
+        assert_eq!(state.get(1).unwrap(), COLOR_LIGHTNING_DISTANT);
+        assert_eq!(state.get(2).unwrap(), COLOR_VFR);
+
+        state.restore_lightning();
+        assert_eq!(state.get(0).unwrap(), COLOR_VFR);
+        assert_eq!(state.get(1).unwrap(), COLOR_VFR);
+    }
+
     #[test]
     fn lightning_no_indices() {
         let mut state = LedState::new(3, 255);
@@ -367,6 +747,7 @@ mod tests {
     fn make_airport(code: &str) -> crate::config::Airport {
         crate::config::Airport {
             code: code.to_string(),
+            ..Default::default()
         }
     }
 
@@ -377,6 +758,7 @@ mod tests {
             wspd: Some(wspd),
             wgst: None,
             wx_string: wx.map(|s| s.to_string()),
+            ..Default::default()
         }
     }
 
@@ -390,12 +772,136 @@ mod tests {
         let mut state = LedState::new(3, 255);
         let metars = std::collections::HashMap::new();
 
-        let lightning = update_leds_from_metars(&mut state, &airports, &metars, 25, true);
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &Settings::default());
 
         assert_eq!(state.get(0).unwrap(), COLOR_VFR);
         assert_eq!(state.get(1).unwrap(), COLOR_IFR);
         assert_eq!(state.get(2).unwrap(), COLOR_UNKNOWN);
-        assert!(lightning.is_empty());
+        assert!(summary.lightning_indices.is_empty());
+    }
+
+    #[test]
+    fn update_leds_legend_codes_take_precedence() {
+        let sock = Color::new(255, 128, 0);
+        let airports = vec![
+            crate::config::Airport {
+                legend_color: Some(sock),
+                ..make_airport("SOCK")
+            },
+            crate::config::Airport {
+                legend_color: Some(sock),
+                ..make_airport("LTNG")
+            },
+        ];
+        let mut state = LedState::new(2, 255);
+        let metars = std::collections::HashMap::new();
+
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &Settings::default());
+
+        assert_eq!(state.get(0).unwrap(), sock);
+        // An overridden built-in code loses its special behavior too
+        assert_eq!(state.get(1).unwrap(), sock);
+        assert!(summary.lightning_indices.is_empty());
+        assert!(summary.missing.is_empty());
+    }
+
+    #[test]
+    fn wind_condition_thresholds() {
+        let legacy = Settings::default();
+        assert_eq!(wind_condition(Some(15), Some(30), &legacy), WindCondition::Windy);
+        assert_eq!(wind_condition(Some(15), None, &legacy), WindCondition::Calm);
+
+        let split = Settings {
+            gust_threshold_kt: Some(35),
+            ..Settings::default()
+        };
+        assert_eq!(wind_condition(Some(15), Some(30), &split), WindCondition::Calm);
+        assert_eq!(wind_condition(Some(15), Some(40), &split), WindCondition::Gusty);
+        assert_eq!(wind_condition(Some(30), Some(40), &split), WindCondition::Windy);
+
+        let off = Settings {
+            do_winds: false,
+            ..split
+        };
+        assert_eq!(wind_condition(Some(30), Some(40), &off), WindCondition::Calm);
+    }
+
+    #[test]
+    fn update_leds_gusty_vfr() {
+        let airports = vec![make_airport("KSFO"), make_airport("KOAK"), make_airport("KSJC")];
+        let mut state = LedState::new(3, 255);
+        let mut metars = std::collections::HashMap::new();
+        let mut gusty = make_metar("KSFO", "VFR", 15, None);
+        gusty.wgst = Some(30);
+        metars.insert("KSFO".to_string(), gusty);
+        metars.insert("KOAK".to_string(), make_metar("KOAK", "VFR", 30, None));
+        let mut gusty_ifr = make_metar("KSJC", "IFR", 10, None);
+        gusty_ifr.wgst = Some(40);
+        metars.insert("KSJC".to_string(), gusty_ifr);
+
+        let settings = Settings {
+            gust_threshold_kt: Some(25),
+            gust_color: Some([200, 0, 200]),
+            ..Settings::default()
+        };
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &settings);
+
+        assert_eq!(state.get(0).unwrap(), Color::new(200, 0, 200));
+        assert_eq!(state.get(1).unwrap(), COLOR_WIND);
+        assert_eq!(state.get(2).unwrap(), COLOR_IFR);
+        assert_eq!(summary.gust_indices, vec![0]);
+    }
+
+    #[test]
+    fn brightness_respects_limits() {
+        let mut state = LedState::new(1, 0);
+        state.set_brightness_limits(10, 200);
+        assert_eq!(state.brightness(), 10);
+        state.set_brightness(255);
+        assert_eq!(state.brightness(), 200);
+        state.set_brightness(50);
+        assert_eq!(state.brightness(), 50);
+    }
+
+    #[test]
+    fn blink_toggles_and_restores() {
+        let mut state = LedState::new(2, 255);
+        state.set(0, COLOR_GUST).unwrap();
+        state.set(1, COLOR_VFR).unwrap();
+        assert!(!state.toggle_blink());
+
+        state.set_blink_indices(vec![0]);
+        assert!(state.toggle_blink());
+        assert_eq!(state.get(0).unwrap(), COLOR_UNKNOWN);
+        assert_eq!(state.get(1).unwrap(), COLOR_VFR);
+        assert!(state.toggle_blink());
+        assert_eq!(state.get(0).unwrap(), COLOR_GUST);
+
+        // Ending mid-blink puts the color back
+        assert!(!state.end_blink());
+        state.toggle_blink();
+        assert!(state.end_blink());
+        assert_eq!(state.get(0).unwrap(), COLOR_GUST);
+    }
+
+    #[test]
+    fn indicator_dims_output_only() {
+        let mut state = LedState::new(2, 255);
+        state.set(0, COLOR_VFR).unwrap();
+        state.set(1, COLOR_IFR).unwrap();
+        assert!(!state.set_indicator_dim(true));
+
+        state.set_indicator_dim(false);
+        state.set_indicator_indices(vec![0]);
+        assert!(state.set_indicator_dim(true));
+        assert!(!state.set_indicator_dim(true));
+        let buf = state.brightness_scaled_buffer();
+        assert_eq!(buf[0], Color::new(0, 63, 0));
+        assert_eq!(buf[1], COLOR_IFR);
+        assert_eq!(state.get(0).unwrap(), COLOR_VFR);
+
+        assert!(state.set_indicator_dim(false));
+        assert_eq!(state.brightness_scaled_buffer()[0], COLOR_VFR);
     }
 
     #[test]
@@ -407,11 +913,11 @@ mod tests {
         metars.insert("KSFO".to_string(), make_metar("KSFO", "VFR", 10, None));
         metars.insert("KLAX".to_string(), make_metar("KLAX", "IFR", 5, None));
 
-        let lightning = update_leds_from_metars(&mut state, &airports, &metars, 25, true);
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &Settings::default());
 
         assert_eq!(state.get(0).unwrap(), COLOR_VFR);
         assert_eq!(state.get(1).unwrap(), COLOR_IFR);
-        assert!(lightning.is_empty());
+        assert!(summary.lightning_indices.is_empty());
     }
 
     #[test]
@@ -422,11 +928,11 @@ mod tests {
         let mut metars = std::collections::HashMap::new();
         metars.insert("KSFO".to_string(), make_metar("KSFO", "MVFR", 5, None));
 
-        let lightning = update_leds_from_metars(&mut state, &airports, &metars, 25, true);
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &Settings::default());
 
         assert_eq!(state.get(0).unwrap(), COLOR_MVFR);
         assert_eq!(state.get(1).unwrap(), COLOR_UNKNOWN); // missing METAR
-        assert!(lightning.is_empty());
+        assert!(summary.lightning_indices.is_empty());
     }
 
     #[test]
@@ -437,10 +943,10 @@ mod tests {
         let mut metars = std::collections::HashMap::new();
         metars.insert("KSFO".to_string(), make_metar("KSFO", "VFR", 30, None));
 
-        let lightning = update_leds_from_metars(&mut state, &airports, &metars, 25, true);
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &Settings::default());
 
         assert_eq!(state.get(0).unwrap(), COLOR_WIND); // high wind -> yellow
-        assert!(lightning.is_empty());
+        assert!(summary.lightning_indices.is_empty());
     }
 
     #[test]
@@ -452,9 +958,9 @@ mod tests {
         metars.insert("KSFO".to_string(), make_metar("KSFO", "VFR", 10, Some("TS")));
         metars.insert("KLAX".to_string(), make_metar("KLAX", "VFR", 5, None));
 
-        let lightning = update_leds_from_metars(&mut state, &airports, &metars, 25, true);
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &Settings::default());
 
-        assert_eq!(lightning, vec![0]); // KSFO has thunderstorm
+        assert_eq!(summary.lightning_indices, vec![0]); // KSFO has thunderstorm
     }
 
     #[test]
@@ -463,10 +969,10 @@ mod tests {
         let mut state = LedState::new(2, 255);
         let metars = std::collections::HashMap::new();
 
-        let lightning = update_leds_from_metars(&mut state, &airports, &metars, 25, true);
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &Settings::default());
 
         assert_eq!(state.get(0).unwrap(), COLOR_VFR); // LTNG shows green
-        assert_eq!(lightning, vec![0]); // LTNG is in lightning list
+        assert_eq!(summary.lightning_indices, vec![0]); // LTNG is in lightning list
     }
 
     #[test]
@@ -484,7 +990,7 @@ mod tests {
         metars.insert("KSFO".to_string(), make_metar("KSFO", "VFR", 10, None));
         metars.insert("KLAX".to_string(), make_metar("KLAX", "LIFR", 5, Some("TS BR")));
 
-        let lightning = update_leds_from_metars(&mut state, &airports, &metars, 25, true);
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &Settings::default());
 
         assert_eq!(state.get(0).unwrap(), COLOR_LIFR);    // legend
         assert_eq!(state.get(1).unwrap(), COLOR_VFR);     // KSFO VFR
@@ -493,6 +999,114 @@ mod tests {
         assert_eq!(state.get(4).unwrap(), COLOR_LIFR);    // KLAX LIFR
 
         // LTNG at index 3 and KLAX thunderstorm at index 4
-        assert_eq!(lightning, vec![3, 4]);
+        assert_eq!(summary.lightning_indices, vec![3, 4]);
+    }
+
+    #[test]
+    fn update_summary_worst_category() {
+        let airports = vec![
+            make_airport("KSFO"),
+            make_airport("KLAX"),
+            make_airport("KJFK"),
+            make_airport("KXYZ"),
+        ];
+        let mut state = LedState::new(4, 255);
+
+        let mut metars = std::collections::HashMap::new();
+        metars.insert("KSFO".to_string(), make_metar("KSFO", "VFR", 10, None));
+        metars.insert("KLAX".to_string(), make_metar("KLAX", "IFR", 5, None));
+        metars.insert("KJFK".to_string(), make_metar("KJFK", "MVFR", 5, None));
+
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &Settings::default());
+
+        assert_eq!(summary.worst_category(), Some(FlightCategory::Ifr));
+        assert_eq!(summary.count(FlightCategory::Vfr), 1);
+        assert_eq!(summary.count(FlightCategory::Lifr), 0);
+        assert_eq!(summary.missing, vec![3]); // KXYZ has no METAR
+        assert_eq!(summary.summary_color(), COLOR_IFR);
+    }
+
+    #[test]
+    fn update_summary_empty() {
+        let summary = UpdateSummary::default();
+        assert_eq!(summary.worst_category(), None);
+        assert_eq!(summary.summary_color(), COLOR_UNKNOWN);
+        assert!(!summary.has_lightning());
+    }
+
+    #[test]
+    fn update_leds_summary_pixel() {
+        let airports = vec![
+            make_airport("SUMM"),
+            make_airport("KSFO"),
+            make_airport("KLAX"),
+            make_airport("VFR"), // legends don't count toward the summary
+        ];
+        let mut state = LedState::new(4, 255);
+
+        let mut metars = std::collections::HashMap::new();
+        metars.insert("KSFO".to_string(), make_metar("KSFO", "VFR", 10, None));
+        metars.insert("KLAX".to_string(), make_metar("KLAX", "MVFR", 5, None));
+
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &Settings::default());
+
+        assert_eq!(state.get(0).unwrap(), COLOR_MVFR);
+        assert_eq!(summary.categories.len(), 2);
+    }
+
+    #[test]
+    fn update_leds_uses_fallback_station() {
+        let airports = vec![
+            crate::config::Airport {
+                fallback: Some("KSQL".to_string()),
+                ..make_airport("KHAF")
+            },
+            crate::config::Airport {
+                fallback: Some("KSQL".to_string()),
+                ..make_airport("KSFO")
+            },
+        ];
+        let mut state = LedState::new(2, 255);
+
+        let mut metars = std::collections::HashMap::new();
+        metars.insert("KSQL".to_string(), make_metar("KSQL", "IFR", 5, None));
+        metars.insert("KSFO".to_string(), make_metar("KSFO", "VFR", 5, None));
+
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &Settings::default());
+
+        assert_eq!(state.get(0).unwrap(), COLOR_IFR); // KHAF missing -> KSQL
+        assert_eq!(state.get(1).unwrap(), COLOR_VFR); // KSFO has its own report
+        assert!(summary.missing.is_empty());
+    }
+
+    #[test]
+    fn update_leds_fog_risk_tint() {
+        let airports = vec![make_airport("KSFO"), make_airport("KLAX"), make_airport("KJFK")];
+        let mut state = LedState::new(3, 255);
+
+        let foggy = |icao: &str, cat: &str| crate::metar::MetarReport {
+            temp: Some(10.0),
+            dewp: Some(9.0),
+            ..make_metar(icao, cat, 2, None)
+        };
+        let mut metars = std::collections::HashMap::new();
+        metars.insert("KSFO".to_string(), foggy("KSFO", "VFR"));
+        metars.insert("KLAX".to_string(), foggy("KLAX", "IFR"));
+        metars.insert("KJFK".to_string(), make_metar("KJFK", "VFR", 2, None));
+
+        let settings = Settings {
+            do_fog_risk: true,
+            ..Settings::default()
+        };
+        let summary = update_leds_from_metars(&mut state, &airports, &metars, &settings);
+
+        assert_eq!(state.get(0).unwrap(), COLOR_VFR.blend(COLOR_FOG, FOG_TINT_AMOUNT));
+        assert_eq!(state.get(1).unwrap(), COLOR_IFR); // already IFR, no tint
+        assert_eq!(state.get(2).unwrap(), COLOR_VFR); // no temp/dewpoint
+        assert_eq!(summary.fog_risk_indices, vec![0]);
+
+        // Disabled by default
+        update_leds_from_metars(&mut state, &airports, &metars, &Settings::default());
+        assert_eq!(state.get(0).unwrap(), COLOR_VFR);
     }
 }
@@ -1,4 +1,50 @@
+pub mod airport_editor;
+pub mod api;
+pub mod asset;
+pub mod auth;
+pub mod backoff;
+pub mod build_info;
+pub mod button;
+pub mod captive_dns;
+pub mod chip;
+pub mod clock;
+pub mod commands;
 pub mod config;
+pub mod config_layer;
+pub mod cors;
+pub mod crash;
+pub mod dashboard;
+pub mod deep_sleep;
+pub mod demo;
+pub mod display_schedule;
 pub mod error;
+pub mod events;
+pub mod heap;
+pub mod home_alert;
 pub mod led;
+pub mod lightning;
+pub mod link_anim;
+pub mod log_event;
+pub mod log_filter;
+pub mod log_ring;
 pub mod metar;
+pub mod networks;
+pub mod oled;
+pub mod ota;
+pub mod poller;
+pub mod power_budget;
+pub mod presets;
+pub mod safe_mode;
+pub mod seal;
+pub mod settings_editor;
+pub mod setup;
+pub mod source;
+pub mod station;
+pub mod status_screen;
+pub mod syslog;
+pub mod telemetry;
+pub mod test_pattern;
+pub mod user_button;
+pub mod wifi_link;
+pub mod winds_aloft;
+pub mod ws2812;
@@ -0,0 +1,170 @@
+use std::time::{Duration, Instant};
+
+use crate::config::Settings;
+use crate::led::LedState;
+use crate::poller::XorShift;
+
+/// Drives the thunderstorm flash from `[settings.lightning]`: every
+/// `interval_ms` a flash fires with `probability_pct` chance and stays lit for
+/// `flash_ms`.
+pub struct LightningAnimator {
+    rng: XorShift,
+    next_flash: Option<Instant>,
+    flashing_until: Option<Instant>,
+    paused: bool,
+}
+
+impl LightningAnimator {
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    /// Create an animator whose random flashes are derived from `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng: XorShift::new(seed),
+            next_flash: None,
+            flashing_until: None,
+            paused: false,
+        }
+    }
+
+    /// Advance the animation to `now`. Returns true if LEDs changed and
+    /// should be written out.
+    pub fn tick(&mut self, now: Instant, settings: &Settings, led_state: &mut LedState) -> bool {
+        let lightning = &settings.lightning;
+        if let Some(until) = self.flashing_until {
+            // Always finish a flash, even if lightning was just turned off
+            if now < until {
+                return false;
+            }
+            led_state.restore_lightning();
+            self.flashing_until = None;
+            self.next_flash = Some(now + Duration::from_millis(lightning.interval_ms));
+            return true;
+        }
+        if !settings.do_lightning || self.paused || self.next_flash.is_some_and(|t| now < t) {
+            return false;
+        }
+
+        self.next_flash = Some(now + Duration::from_millis(lightning.interval_ms));
+        let roll = (self.rng.next() % 100) as u8;
+        if roll >= lightning.probability_pct || !led_state.apply_lightning_flash() {
+            return false;
+        }
+        self.flashing_until = Some(now + Duration::from_millis(lightning.flash_ms));
+        self.next_flash = None;
+        true
+    }
+
+    /// Start no new flashes while `paused`, as when memory is low; a lit
+    /// one still ends on time.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// When `tick` next has something to do, for sleeping between frames.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.flashing_until.or(self.next_flash)
+    }
+
+    /// Whether a flash is currently lit.
+    pub fn is_flashing(&self) -> bool {
+        self.flashing_until.is_some()
+    }
+}
+
+impl Default for LightningAnimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LightningSettings;
+    use crate::led::{COLOR_LIGHTNING, COLOR_VFR};
+
+    fn storm_state() -> LedState {
+        let mut state = LedState::new(2, 255);
+        state.set_all(COLOR_VFR);
+        state.set_lightning_indices(vec![1]);
+        state
+    }
+
+    #[test]
+    fn flashes_for_configured_duration() {
+        let settings = Settings {
+            lightning: LightningSettings {
+                flash_ms: 50,
+                interval_ms: 1000,
+                probability_pct: 100,
+            },
+            ..Settings::default()
+        };
+        let mut state = storm_state();
+        let mut animator = LightningAnimator::new();
+        let t0 = Instant::now();
+
+        assert!(animator.tick(t0, &settings, &mut state));
+        assert_eq!(state.get(1).unwrap(), COLOR_LIGHTNING);
+        assert_eq!(animator.next_deadline(), Some(t0 + Duration::from_millis(50)));
+
+        assert!(!animator.tick(t0 + Duration::from_millis(20), &settings, &mut state));
+        assert!(animator.tick(t0 + Duration::from_millis(50), &settings, &mut state));
+        assert_eq!(state.get(1).unwrap(), COLOR_VFR);
+        assert_eq!(animator.next_deadline(), Some(t0 + Duration::from_millis(1050)));
+
+        assert!(!animator.tick(t0 + Duration::from_millis(500), &settings, &mut state));
+        assert!(animator.tick(t0 + Duration::from_millis(1050), &settings, &mut state));
+    }
+
+    #[test]
+    fn zero_probability_never_flashes() {
+        let settings = Settings {
+            lightning: LightningSettings {
+                probability_pct: 0,
+                ..LightningSettings::default()
+            },
+            ..Settings::default()
+        };
+        let mut state = storm_state();
+        let mut animator = LightningAnimator::with_seed(7);
+        let t0 = Instant::now();
+        for i in 0..20 {
+            assert!(!animator.tick(t0 + Duration::from_secs(10 * i), &settings, &mut state));
+        }
+        assert_eq!(state.get(1).unwrap(), COLOR_VFR);
+    }
+
+    #[test]
+    fn disabling_lightning_still_ends_flash() {
+        let mut settings = Settings::default();
+        let mut state = storm_state();
+        let mut animator = LightningAnimator::new();
+        let t0 = Instant::now();
+        assert!(animator.tick(t0, &settings, &mut state));
+
+        settings.do_lightning = false;
+        assert!(animator.tick(t0 + Duration::from_secs(1), &settings, &mut state));
+        assert_eq!(state.get(1).unwrap(), COLOR_VFR);
+        assert!(!animator.tick(t0 + Duration::from_secs(60), &settings, &mut state));
+    }
+
+    #[test]
+    fn pausing_stops_new_flashes() {
+        let settings = Settings::default();
+        let mut state = storm_state();
+        let mut animator = LightningAnimator::new();
+        let t0 = Instant::now();
+        assert!(animator.tick(t0, &settings, &mut state));
+
+        animator.set_paused(true);
+        assert!(animator.tick(t0 + Duration::from_secs(1), &settings, &mut state));
+        assert_eq!(state.get(1).unwrap(), COLOR_VFR);
+        assert!(!animator.tick(t0 + Duration::from_secs(60), &settings, &mut state));
+        animator.set_paused(false);
+        assert!(animator.tick(t0 + Duration::from_secs(61), &settings, &mut state));
+    }
+}
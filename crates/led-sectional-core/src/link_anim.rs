@@ -0,0 +1,203 @@
+//! The WiFi state on the LEDs while the map isn't online, so the phase of a
+//! connection can be told at a glance: a dot sweeping along the strip while
+//! associating (orange, or violet after a drop) and while waiting for DHCP
+//! (white), and the whole strip blinking in the fetch-error color between
+//! rounds of failed attempts.
+
+use std::time::{Duration, Instant};
+
+use crate::led::{
+    Color, LedState, COLOR_CONNECTING, COLOR_FETCH_ERROR, COLOR_OBTAINING_IP, COLOR_RECONNECTING,
+};
+use crate::wifi_link::LinkState;
+
+/// Time per sweep step before any failed round; each failed round adds as
+/// much again, up to [`MAX_SLOWDOWN`] times, so a slow sweep means many
+/// retries.
+pub const SWEEP_STEP: Duration = Duration::from_millis(80);
+const MAX_SLOWDOWN: u32 = 5;
+/// On and off time of the failed blink.
+pub const FAILED_BLINK: Duration = Duration::from_millis(500);
+
+const OFF: Color = Color::new(0, 0, 0);
+/// How far each LED behind the dot fades toward off.
+const TAIL_FADE: [u8; 2] = [160, 224];
+
+/// Where a connection that isn't online yet has got to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkPhase {
+    /// Joining an access point; `reconnecting` after the link dropped.
+    Associating { reconnecting: bool },
+    /// Associated, waiting for DHCP.
+    ObtainingIp,
+    /// Every saved network failed; waiting out the backoff.
+    Failed,
+}
+
+impl LinkPhase {
+    /// The phase of `state`, or None once online. `was_online` tells a
+    /// reconnect from the first connection.
+    pub fn of(state: &LinkState, was_online: bool) -> Option<Self> {
+        match state {
+            LinkState::Online { .. } => None,
+            LinkState::Connecting {
+                associated: true, ..
+            } => Some(Self::ObtainingIp),
+            LinkState::Connecting { .. } => Some(Self::Associating {
+                reconnecting: was_online,
+            }),
+            LinkState::Waiting { .. } => Some(Self::Failed),
+        }
+    }
+
+    /// Time between frames after `failed_rounds` failed rounds.
+    pub fn step(self, failed_rounds: u32) -> Duration {
+        match self {
+            Self::Failed => FAILED_BLINK,
+            _ => SWEEP_STEP * (1 + failed_rounds.min(MAX_SLOWDOWN - 1)),
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Self::Associating {
+                reconnecting: false,
+            } => COLOR_CONNECTING,
+            Self::Associating { reconnecting: true } => COLOR_RECONNECTING,
+            Self::ObtainingIp => COLOR_OBTAINING_IP,
+            Self::Failed => COLOR_FETCH_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LinkAnimator {
+    /// Sweep position, or blink count while failed.
+    frame: usize,
+    phase: Option<LinkPhase>,
+    next_step: Option<Instant>,
+}
+
+impl LinkAnimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Paint the next frame for `phase` if it's due, or straight away if the
+    /// phase changed. Returns true if LEDs changed and should be written out.
+    pub fn tick(
+        &mut self,
+        now: Instant,
+        phase: LinkPhase,
+        failed_rounds: u32,
+        led_state: &mut LedState,
+    ) -> bool {
+        if self.phase == Some(phase) && self.next_step.is_some_and(|t| now < t) {
+            return false;
+        }
+        if self.phase != Some(phase) {
+            // Sweeps carry on from where the dot was
+            if phase == LinkPhase::Failed || self.phase == Some(LinkPhase::Failed) {
+                self.frame = 0;
+            }
+            self.phase = Some(phase);
+        }
+        self.next_step = Some(now + phase.step(failed_rounds));
+
+        let color = phase.color();
+        let len = led_state.num_leds();
+        if phase == LinkPhase::Failed {
+            led_state.set_all(if self.frame.is_multiple_of(2) { color } else { OFF });
+        } else if len > 0 {
+            let dot = self.frame % len;
+            led_state.set_all(OFF);
+            for (behind, fade) in TAIL_FADE.iter().enumerate() {
+                if behind + 1 < len {
+                    let index = (dot + len - behind - 1) % len;
+                    let _ = led_state.set(index, color.blend(OFF, *fade));
+                }
+            }
+            let _ = led_state.set(dot, color);
+        }
+        self.frame = self.frame.wrapping_add(1);
+        true
+    }
+
+    /// When `tick` next has something to do, for sleeping between frames.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.next_step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connecting(associated: bool) -> LinkState {
+        LinkState::Connecting {
+            ssid: "Home".into(),
+            deadline: Instant::now(),
+            associated,
+        }
+    }
+
+    #[test]
+    fn phases_follow_link_state() {
+        assert_eq!(
+            LinkPhase::of(&connecting(false), false),
+            Some(LinkPhase::Associating {
+                reconnecting: false
+            })
+        );
+        assert_eq!(
+            LinkPhase::of(&connecting(false), true),
+            Some(LinkPhase::Associating { reconnecting: true })
+        );
+        assert_eq!(LinkPhase::of(&connecting(true), false), Some(LinkPhase::ObtainingIp));
+        let waiting = LinkState::Waiting {
+            until: Instant::now(),
+        };
+        assert_eq!(LinkPhase::of(&waiting, false), Some(LinkPhase::Failed));
+    }
+
+    #[test]
+    fn dot_sweeps_slower_with_each_failed_round() {
+        let phase = LinkPhase::ObtainingIp;
+        assert_eq!(phase.step(0), SWEEP_STEP);
+        assert_eq!(phase.step(2), SWEEP_STEP * 3);
+        assert_eq!(phase.step(50), SWEEP_STEP * MAX_SLOWDOWN);
+
+        let mut anim = LinkAnimator::new();
+        let mut leds = LedState::new(5, 255);
+        let start = Instant::now();
+        assert!(anim.tick(start, phase, 1, &mut leds));
+        assert_eq!(leds.get(0).unwrap(), COLOR_OBTAINING_IP);
+        // The tail wraps around the end of the strip
+        assert_eq!(leds.get(4).unwrap(), COLOR_OBTAINING_IP.blend(OFF, 160));
+        assert_eq!(leds.get(2).unwrap(), OFF);
+
+        assert!(!anim.tick(start + SWEEP_STEP, phase, 1, &mut leds));
+        assert!(anim.tick(start + SWEEP_STEP * 2, phase, 1, &mut leds));
+        assert_eq!(leds.get(1).unwrap(), COLOR_OBTAINING_IP);
+        assert_eq!(leds.get(0).unwrap(), COLOR_OBTAINING_IP.blend(OFF, 160));
+    }
+
+    #[test]
+    fn failed_blinks_the_whole_strip_and_phase_changes_repaint() {
+        let mut anim = LinkAnimator::new();
+        let mut leds = LedState::new(3, 255);
+        let start = Instant::now();
+        let associating = LinkPhase::Associating {
+            reconnecting: false,
+        };
+        anim.tick(start, associating, 0, &mut leds);
+        assert_eq!(leds.get(0).unwrap(), COLOR_CONNECTING);
+
+        // A new phase shows at once, not at the next step
+        assert!(anim.tick(start, LinkPhase::Failed, 1, &mut leds));
+        assert!((0..3).all(|i| leds.get(i).unwrap() == COLOR_FETCH_ERROR));
+        assert!(anim.tick(start + FAILED_BLINK, LinkPhase::Failed, 1, &mut leds));
+        assert!((0..3).all(|i| leds.get(i).unwrap() == OFF));
+        assert_eq!(anim.next_deadline(), Some(start + FAILED_BLINK * 2));
+    }
+}
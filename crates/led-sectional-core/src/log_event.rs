@@ -0,0 +1,203 @@
+//! Structured log events: a numeric code and key/value fields, written as
+//! one JSON line each so monitoring tools can parse them. The firmware logs
+//! them with the target `event` when built with the `structured-logs`
+//! feature, next to its usual messages.
+//!
+//! Codes are grouped by hundreds: 1xx fetches, 2xx WiFi, 3xx firmware
+//! updates. A code keeps its meaning once released; new ones are added.
+
+use log::Level;
+use serde_json::Value;
+
+use crate::events::Event;
+use crate::wifi_link::LinkState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventCode {
+    FetchOk,
+    FetchFailed,
+    WifiWaiting,
+    WifiConnecting,
+    WifiAssociated,
+    WifiOnline,
+    OtaChecking,
+    OtaUpToDate,
+    OtaStarted,
+    OtaProgress,
+    OtaWritten,
+    OtaFailed,
+    OtaRolledBack,
+    OtaConfirmed,
+}
+
+impl EventCode {
+    pub fn code(self) -> u16 {
+        match self {
+            Self::FetchOk => 100,
+            Self::FetchFailed => 101,
+            Self::WifiWaiting => 200,
+            Self::WifiConnecting => 201,
+            Self::WifiAssociated => 202,
+            Self::WifiOnline => 203,
+            Self::OtaChecking => 300,
+            Self::OtaUpToDate => 301,
+            Self::OtaStarted => 302,
+            Self::OtaProgress => 303,
+            Self::OtaWritten => 304,
+            Self::OtaFailed => 305,
+            Self::OtaRolledBack => 306,
+            Self::OtaConfirmed => 307,
+        }
+    }
+
+    /// snake_case name, written next to the code for people reading along.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::FetchOk => "fetch_ok",
+            Self::FetchFailed => "fetch_failed",
+            Self::WifiWaiting => "wifi_waiting",
+            Self::WifiConnecting => "wifi_connecting",
+            Self::WifiAssociated => "wifi_associated",
+            Self::WifiOnline => "wifi_online",
+            Self::OtaChecking => "ota_checking",
+            Self::OtaUpToDate => "ota_up_to_date",
+            Self::OtaStarted => "ota_started",
+            Self::OtaProgress => "ota_progress",
+            Self::OtaWritten => "ota_written",
+            Self::OtaFailed => "ota_failed",
+            Self::OtaRolledBack => "ota_rolled_back",
+            Self::OtaConfirmed => "ota_confirmed",
+        }
+    }
+
+    /// The level the event is logged at.
+    pub fn level(self) -> Level {
+        match self {
+            Self::FetchFailed | Self::OtaFailed => Level::Error,
+            Self::OtaRolledBack => Level::Warn,
+            Self::OtaProgress => Level::Debug,
+            _ => Level::Info,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEvent {
+    pub code: EventCode,
+    /// In the order added.
+    fields: Vec<(&'static str, Value)>,
+}
+
+impl LogEvent {
+    pub fn new(code: EventCode) -> Self {
+        Self {
+            code,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Add `key`; None values are left out.
+    pub fn field(mut self, key: &'static str, value: impl Into<Value>) -> Self {
+        let value = value.into();
+        if !value.is_null() {
+            self.fields.push((key, value));
+        }
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.fields.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// What the main loop's events say about fetches and WiFi, if anything.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::MetarUpdated(snapshot) => Some(match &snapshot.error {
+                Some(error) => Self::new(EventCode::FetchFailed).field("error", error.as_str()),
+                None => {
+                    let reporting = snapshot.rows.iter().filter(|row| row.category.is_some());
+                    let worst = snapshot.rows.iter().filter_map(|row| row.category).max();
+                    Self::new(EventCode::FetchOk)
+                        .field("airports", reporting.count())
+                        .field("worst", worst.map(|c| c.as_str()))
+                        .field("fetched_at", snapshot.fetched_at)
+                }
+            }),
+            Event::WifiStateChanged(state) => Some(match state {
+                LinkState::Waiting { .. } => Self::new(EventCode::WifiWaiting),
+                LinkState::Connecting {
+                    ssid, associated, ..
+                } => {
+                    let code = if *associated {
+                        EventCode::WifiAssociated
+                    } else {
+                        EventCode::WifiConnecting
+                    };
+                    Self::new(code).field("ssid", ssid.as_str())
+                }
+                LinkState::Online { ssid, ip } => Self::new(EventCode::WifiOnline)
+                    .field("ssid", ssid.as_str())
+                    .field("ip", ip.to_string()),
+            }),
+            Event::BrightnessChanged(_) => None,
+        }
+    }
+
+    /// One JSON object: `code`, `event`, `ts` (Unix seconds, once the clock
+    /// is set), then the fields.
+    pub fn to_json(&self, unix_secs: Option<i64>) -> String {
+        let mut json = format!(r#"{{"code":{},"event":"{}""#, self.code.code(), self.code.name());
+        if let Some(secs) = unix_secs {
+            json.push_str(&format!(r#","ts":{secs}"#));
+        }
+        for (key, value) in &self.fields {
+            json.push_str(&format!(r#","{key}":{value}"#));
+        }
+        json.push('}');
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dashboard::MapSnapshot;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn events_are_json_lines() {
+        let event = LogEvent::new(EventCode::OtaProgress)
+            .field("version", "0.3.0")
+            .field("bytes", 65536)
+            .field("total", None::<u64>);
+        assert_eq!(
+            event.to_json(Some(1_760_400_000)),
+            r#"{"code":303,"event":"ota_progress","ts":1760400000,"version":"0.3.0","bytes":65536}"#
+        );
+        let parsed: Value = serde_json::from_str(&event.to_json(None)).unwrap();
+        assert_eq!(parsed["event"], "ota_progress");
+        assert_eq!(EventCode::OtaProgress.level(), Level::Debug);
+    }
+
+    #[test]
+    fn fetches_and_wifi_changes_become_events() {
+        let snapshot = MapSnapshot {
+            error: Some("HTTP 503".into()),
+            ..Default::default()
+        };
+        let event = LogEvent::from_event(&Event::MetarUpdated(snapshot)).unwrap();
+        assert_eq!(event.code, EventCode::FetchFailed);
+        assert_eq!(event.get("error"), Some(&Value::from("HTTP 503")));
+
+        let online = LinkState::Online {
+            ssid: "Hangar \"2\"".into(),
+            ip: Ipv4Addr::new(192, 168, 1, 20),
+        };
+        let event = LogEvent::from_event(&Event::WifiStateChanged(online)).unwrap();
+        assert_eq!(
+            event.to_json(None),
+            r#"{"code":203,"event":"wifi_online","ssid":"Hangar \"2\"","ip":"192.168.1.20"}"#
+        );
+        assert!(LogEvent::from_event(&Event::BrightnessChanged(40)).is_none());
+    }
+}
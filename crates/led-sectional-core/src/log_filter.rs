@@ -0,0 +1,143 @@
+//! Which log messages are kept, set by `settings.log_level` or at runtime
+//! over the API. A filter is written like `info,metar_client=debug,wifi=warn`:
+//! a default level, then levels for single modules.
+
+use std::fmt;
+
+use log::{Level, LevelFilter};
+
+/// `settings.log_level` when it isn't set.
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    /// Module names and their levels, in the order given.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    /// Everything at `default`, with no module overrides. `const` so the
+    /// firmware can keep one in a static.
+    pub const fn new(default: LevelFilter) -> Self {
+        Self {
+            default,
+            modules: Vec::new(),
+        }
+    }
+
+    /// Parse a filter such as `info,metar_client=debug`. Levels are `off`,
+    /// `error`, `warn`, `info`, `debug` or `trace`, in any case; the default
+    /// may be left out, meaning `info`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut filter = Self::new(LevelFilter::Info);
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.split_once('=') {
+                Some((module, level)) => {
+                    let module = module.trim();
+                    if module.is_empty() || module.contains(char::is_whitespace) {
+                        return Err(format!("\"{part}\" needs a module name before ="));
+                    }
+                    filter.modules.push((module.to_string(), parse_level(level.trim())?));
+                }
+                None => filter.default = parse_level(part)?,
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Whether a message at `level` from `target` (a module path such as
+    /// `led_sectional_firmware::wifi`) is kept.
+    pub fn enabled(&self, level: Level, target: &str) -> bool {
+        level <= self.level_for(target)
+    }
+
+    /// The level for `target`: that of the last module listed that names
+    /// the whole path, a leading part of it, or one of its segments, else
+    /// the default.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .rev()
+            .find(|(module, _)| names_module(target, module))
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// The most verbose level anything gets, for `log::set_max_level`.
+    pub fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|(_, level)| *level).fold(self.default, Ord::max)
+    }
+
+    pub fn default_level(&self) -> LevelFilter {
+        self.default
+    }
+
+    /// The module overrides, in the order given.
+    pub fn modules(&self) -> impl Iterator<Item = (&str, LevelFilter)> {
+        self.modules.iter().map(|(module, level)| (module.as_str(), *level))
+    }
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self::new(LevelFilter::Info)
+    }
+}
+
+/// The filter in the form [`LogFilter::parse`] reads.
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default.as_str().to_ascii_lowercase())?;
+        for (module, level) in &self.modules {
+            write!(f, ",{}={}", module, level.as_str().to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_level(text: &str) -> Result<LevelFilter, String> {
+    text.parse()
+        .map_err(|_| format!("\"{text}\" isn't a log level (off, error, warn, info, debug, trace)"))
+}
+
+fn names_module(target: &str, module: &str) -> bool {
+    target.split("::").any(|segment| segment == module)
+        || target
+            .strip_prefix(module)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modules_override_the_default() {
+        let filter = LogFilter::parse("warn, metar_client=debug,wifi=error").unwrap();
+        assert!(filter.enabled(Level::Debug, "led_sectional_firmware::metar_client"));
+        assert!(!filter.enabled(Level::Warn, "led_sectional_firmware::wifi"));
+        assert!(!filter.enabled(Level::Warn, "esp_idf_svc::wifi"));
+        assert!(filter.enabled(Level::Warn, "led_sectional_firmware::web"));
+        assert!(!filter.enabled(Level::Info, "led_sectional_firmware::web"));
+        assert_eq!(filter.max_level(), LevelFilter::Debug);
+        assert_eq!(filter.to_string(), "warn,metar_client=debug,wifi=error");
+    }
+
+    #[test]
+    fn paths_and_the_last_rule_win() {
+        let filter = LogFilter::parse("esp_idf_svc=off,esp_idf_svc::http=info").unwrap();
+        assert_eq!(filter.level_for("esp_idf_svc::wifi"), LevelFilter::Off);
+        assert_eq!(filter.level_for("esp_idf_svc::http::server"), LevelFilter::Info);
+        // Part of a segment doesn't count
+        assert_eq!(filter.level_for("esp_idf_svc_extra"), LevelFilter::Info);
+        assert_eq!(LogFilter::parse("").unwrap(), LogFilter::default());
+    }
+
+    #[test]
+    fn bad_levels_are_refused() {
+        assert!(LogFilter::parse("loud").is_err());
+        assert!(LogFilter::parse("wifi=loud").is_err());
+        assert!(LogFilter::parse("=debug").is_err());
+        assert_eq!(LogFilter::parse("DEBUG").unwrap().default_level(), LevelFilter::Debug);
+    }
+}
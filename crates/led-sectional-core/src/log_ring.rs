@@ -0,0 +1,129 @@
+//! The most recent log lines, kept in memory so they can be read over HTTP
+//! without a serial console.
+
+use std::collections::VecDeque;
+
+/// Longest line kept; the rest is cut off so one runaway message can't
+/// take the whole buffer.
+pub const MAX_LINE_LEN: usize = 200;
+
+/// A fixed number of the latest lines; pushing past it drops the oldest.
+#[derive(Debug)]
+pub struct LogRing {
+    lines: VecDeque<String>,
+    capacity: usize,
+    /// Lines dropped to make room, so readers can tell the log was cut.
+    dropped: u64,
+}
+
+impl LogRing {
+    /// `const` so the firmware can keep one in a static.
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    pub fn push(&mut self, mut line: String) {
+        if self.capacity == 0 {
+            self.dropped += 1;
+            return;
+        }
+        if line.len() > MAX_LINE_LEN {
+            let mut end = MAX_LINE_LEN;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            line.truncate(end);
+            line.push('…');
+        }
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+            self.dropped += 1;
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Keep at most `capacity` lines from now on, dropping the oldest if
+    /// there are more.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.lines.len() > capacity {
+            self.lines.pop_front();
+            self.dropped += 1;
+        }
+        self.lines.shrink_to(capacity);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// The kept lines, oldest first, one per line, after a note of how many
+    /// earlier ones were dropped.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        if self.dropped > 0 {
+            text.push_str(&format!("({} earlier lines dropped)\n", self.dropped));
+        }
+        for line in &self.lines {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_lines() {
+        let mut ring = LogRing::new(3);
+        assert!(ring.is_empty());
+        assert_eq!(ring.to_text(), "");
+        for i in 0..5 {
+            ring.push(format!("line {i}"));
+        }
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.to_text(), "(2 earlier lines dropped)\nline 2\nline 3\nline 4\n");
+    }
+
+    #[test]
+    fn long_lines_are_cut_on_a_char_boundary() {
+        let mut ring = LogRing::new(2);
+        ring.push("é".repeat(MAX_LINE_LEN));
+        let text = ring.to_text();
+        let line = text.trim_end();
+        assert!(line.ends_with('…'));
+        assert!(line.len() <= MAX_LINE_LEN + '…'.len_utf8());
+    }
+
+    #[test]
+    fn shrinking_drops_the_oldest() {
+        let mut ring = LogRing::new(4);
+        for i in 0..4 {
+            ring.push(format!("line {i}"));
+        }
+        ring.set_capacity(2);
+        assert_eq!(ring.to_text(), "(2 earlier lines dropped)\nline 2\nline 3\n");
+        ring.set_capacity(4);
+        ring.push("line 4".into());
+        assert_eq!(ring.len(), 3);
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut ring = LogRing::new(0);
+        ring.push("lost".into());
+        assert!(ring.is_empty());
+        assert_eq!(ring.to_text(), "(1 earlier lines dropped)\n");
+    }
+}
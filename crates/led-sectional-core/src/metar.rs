@@ -1,25 +1,260 @@
-use serde::Deserialize;
+use log::warn;
+use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::error::Result;
 
 const METAR_BASE_URL: &str = "https://aviationweather.gov/api/data/metar?format=json&ids=";
 
-#[derive(Debug, Clone, Deserialize)]
+// Plausibility limits; values outside them are treated as missing.
+const MAX_WIND_KT: u32 = 200;
+const MAX_GUST_KT: u32 = 250;
+const MAX_VISIB_SM: f32 = 100.0;
+const MAX_CLOUD_BASE_FT: u32 = 60_000;
+const TEMP_RANGE_C: std::ops::RangeInclusive<f32> = -90.0..=60.0;
+
+/// One station's report, as aviationweather.gov sends it. Serializes back
+/// to the same field names for `/api/metars`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MetarReport {
     pub icao_id: String,
     pub flt_cat: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_lenient_u32")]
     pub wspd: Option<u32>,
+    #[serde(default, deserialize_with = "deserialize_lenient_u32")]
     pub wgst: Option<u32>,
     pub wx_string: Option<String>,
+    /// Raw METAR text, including the remarks section.
+    pub raw_ob: Option<String>,
+    /// Prevailing visibility in statute miles ("10+" is reported as 10).
+    #[serde(default, deserialize_with = "deserialize_visib")]
+    pub visib: Option<f32>,
+    #[serde(default, deserialize_with = "deserialize_null_default")]
+    pub clouds: Vec<CloudLayer>,
+    /// Vertical visibility into an indefinite ceiling, in feet AGL.
+    #[serde(default, deserialize_with = "deserialize_lenient_u32")]
+    pub vert_vis: Option<u32>,
+    /// Temperature in degrees Celsius.
+    pub temp: Option<f32>,
+    /// Dewpoint in degrees Celsius.
+    pub dewp: Option<f32>,
+    /// Observation time as Unix seconds.
+    pub obs_time: Option<i64>,
+    /// Report type: "METAR" for routine reports, "SPECI" for specials.
+    pub metar_type: Option<String>,
+}
+
+/// A single reported cloud layer.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CloudLayer {
+    /// Coverage code: SKC, CLR, FEW, SCT, BKN, OVC, or OVX.
+    pub cover: String,
+    /// Layer base in feet AGL.
+    #[serde(default, deserialize_with = "deserialize_lenient_u32")]
+    pub base: Option<u32>,
+}
+
+/// Sky coverage of a cloud layer, from least to most coverage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CloudCover {
+    Clear,
+    Few,
+    Scattered,
+    Broken,
+    Overcast,
+    Obscured,
+}
+
+impl CloudCover {
+    /// Parse a coverage code. Returns None for unknown codes.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "SKC" | "CLR" | "CAVOK" | "NSC" | "NCD" => Some(Self::Clear),
+            "FEW" => Some(Self::Few),
+            "SCT" => Some(Self::Scattered),
+            "BKN" => Some(Self::Broken),
+            "OVC" => Some(Self::Overcast),
+            "OVX" => Some(Self::Obscured),
+            _ => None,
+        }
+    }
+}
+
+impl CloudLayer {
+    pub fn coverage(&self) -> Option<CloudCover> {
+        CloudCover::parse(&self.cover)
+    }
+
+    /// Broken, overcast, and obscured layers constitute a ceiling.
+    pub fn is_ceiling(&self) -> bool {
+        self.coverage().is_some_and(|c| c >= CloudCover::Broken)
+    }
+}
+
+/// Temperature/dewpoint spread at or below which fog may form.
+const FOG_MAX_SPREAD_C: f32 = 2.0;
+/// Wind speed at or below which air is calm enough for radiation fog.
+const FOG_MAX_WIND_KT: u32 = 5;
+
+/// Flight category, ordered from best (VFR) to worst (LIFR).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FlightCategory {
+    Vfr,
+    Mvfr,
+    Ifr,
+    Lifr,
+}
+
+impl FlightCategory {
+    /// Parse an API `fltCat` string. Returns None for unknown values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "VFR" => Some(Self::Vfr),
+            "MVFR" => Some(Self::Mvfr),
+            "IFR" => Some(Self::Ifr),
+            "LIFR" => Some(Self::Lifr),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Vfr => "VFR",
+            Self::Mvfr => "MVFR",
+            Self::Ifr => "IFR",
+            Self::Lifr => "LIFR",
+        }
+    }
+}
+
+/// Where lightning was observed relative to the station.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Lightning {
+    /// Reported in the vicinity (VC) or distant (DSNT) from the station.
+    Distant,
+    /// Thunderstorm or lightning at the field.
+    AtStation,
 }
 
 impl MetarReport {
-    /// Check if the weather string indicates thunderstorms.
-    pub fn has_thunderstorm(&self) -> bool {
-        self.wx_string
+    /// Return the flight category, deriving it from visibility and clouds
+    /// when the report has no usable `fltCat` (common outside the US).
+    pub fn flight_category(&self) -> Option<FlightCategory> {
+        self.flt_cat
             .as_deref()
-            .is_some_and(|wx| wx.contains("TS"))
+            .and_then(FlightCategory::parse)
+            .or_else(|| {
+                if self.visib.is_none() && self.clouds.is_empty() && self.vert_vis.is_none() {
+                    return None;
+                }
+                Some(categorize(self.visib, self.ceiling()))
+            })
+    }
+
+    /// Ceiling in feet AGL: the lowest broken/overcast layer or the vertical
+    /// visibility, whichever is lower. None means no ceiling was reported.
+    pub fn ceiling(&self) -> Option<u32> {
+        ceiling_ft(&self.clouds).into_iter().chain(self.vert_vis).min()
+    }
+
+    /// Check if the weather string reports a thunderstorm at the field.
+    pub fn has_thunderstorm(&self) -> bool {
+        self.wx_string.as_deref().is_some_and(|wx| {
+            wx.split_whitespace()
+                .any(|t| wx_token_has_ts(t) == Some(false))
+        })
+    }
+
+    /// Classify lightning from present weather and the `RMK` section of the raw
+    /// observation. Returns the closest lightning reported, if any.
+    pub fn lightning(&self) -> Option<Lightning> {
+        let from_wx = self.wx_string.as_deref().and_then(|wx| {
+            wx.split_whitespace()
+                .filter_map(wx_token_has_ts)
+                .map(|vicinity| {
+                    if vicinity {
+                        Lightning::Distant
+                    } else {
+                        Lightning::AtStation
+                    }
+                })
+                .max()
+        });
+        let from_remarks = self.raw_ob.as_deref().and_then(remarks_lightning);
+        from_wx.max(from_remarks)
+    }
+
+    /// Temperature/dewpoint spread in degrees Celsius, if both are reported.
+    pub fn temp_dewpoint_spread(&self) -> Option<f32> {
+        Some(self.temp? - self.dewp?)
+    }
+
+    /// Fog-risk heuristic: a small temperature/dewpoint spread with calm wind.
+    pub fn fog_risk(&self) -> bool {
+        self.temp_dewpoint_spread()
+            .is_some_and(|spread| spread <= FOG_MAX_SPREAD_C)
+            && self.max_wind() <= FOG_MAX_WIND_KT
+    }
+
+    /// Clear physically implausible values so they are treated as missing.
+    /// Returns the number of values rejected.
+    pub fn sanitize(&mut self) -> usize {
+        let icao = self.icao_id.as_str();
+        let mut rejected = 0;
+        let mut reject = |field: &str, value: &dyn std::fmt::Debug| {
+            warn!("{icao}: ignoring implausible {field} {value:?}");
+            rejected += 1;
+        };
+
+        if let Some(v) = self.wspd.filter(|&v| v > MAX_WIND_KT) {
+            reject("wind speed", &v);
+            self.wspd = None;
+        }
+        if let Some(v) = self.wgst.filter(|&v| v > MAX_GUST_KT || self.wspd.is_some_and(|s| v < s)) {
+            reject("gust", &v);
+            self.wgst = None;
+        }
+        if let Some(v) = self.visib.filter(|v| !(0.0..=MAX_VISIB_SM).contains(v)) {
+            reject("visibility", &v);
+            self.visib = None;
+        }
+        if let Some(v) = self.vert_vis.filter(|&v| v > MAX_CLOUD_BASE_FT) {
+            reject("vertical visibility", &v);
+            self.vert_vis = None;
+        }
+        for layer in &mut self.clouds {
+            if let Some(v) = layer.base.filter(|&v| v > MAX_CLOUD_BASE_FT) {
+                reject("cloud base", &v);
+                layer.base = None;
+            }
+        }
+        if let Some(v) = self.temp.filter(|v| !TEMP_RANGE_C.contains(v)) {
+            reject("temperature", &v);
+            self.temp = None;
+        }
+        // Dewpoint can't meaningfully exceed the temperature
+        if let Some(v) = self
+            .dewp
+            .filter(|v| !TEMP_RANGE_C.contains(v) || self.temp.is_some_and(|t| *v > t + 1.0))
+        {
+            reject("dewpoint", &v);
+            self.dewp = None;
+        }
+        rejected
+    }
+
+    pub fn is_speci(&self) -> bool {
+        self.metar_type.as_deref() == Some("SPECI")
+    }
+
+    /// Whether this report should replace `other` for the same station:
+    /// newer observations win, and a SPECI wins a tie with a routine METAR.
+    pub fn supersedes(&self, other: &MetarReport) -> bool {
+        match self.obs_time.cmp(&other.obs_time) {
+            std::cmp::Ordering::Greater => true,
+            std::cmp::Ordering::Less => false,
+            std::cmp::Ordering::Equal => self.is_speci() && !other.is_speci(),
+        }
     }
 
     /// Return the maximum of wind speed and wind gust.
@@ -28,9 +263,154 @@ impl MetarReport {
     }
 }
 
+/// If a present-weather token contains the TS descriptor, return whether it is
+/// a vicinity (VC) report. Tokens are parsed as 2-letter groups so codes that
+/// merely contain the letters "TS" across a group boundary don't match.
+fn wx_token_has_ts(token: &str) -> Option<bool> {
+    let body = token.trim_start_matches(['+', '-']);
+    let (vicinity, body) = match body.strip_prefix("VC") {
+        Some(rest) => (true, rest),
+        None => (false, body),
+    };
+    if body.len() % 2 != 0 || !body.is_ascii() {
+        return None;
+    }
+    let has_ts = (0..body.len()).step_by(2).any(|i| &body[i..i + 2] == "TS");
+    has_ts.then_some(vicinity)
+}
+
+/// Find lightning groups (`LTG`, `LTGICCG`, ...) in the remarks of a raw METAR.
+/// A group followed by `DSNT` or `VC` before the next lightning group is distant.
+fn remarks_lightning(raw: &str) -> Option<Lightning> {
+    let mut tokens = raw
+        .split_whitespace()
+        .skip_while(|t| *t != "RMK")
+        .skip(1)
+        .peekable();
+    let mut closest = None;
+
+    while let Some(token) = tokens.next() {
+        if !token.starts_with("LTG") {
+            continue;
+        }
+        let mut kind = Lightning::AtStation;
+        while let Some(next) = tokens.peek() {
+            if next.starts_with("LTG") {
+                break;
+            }
+            if matches!(*next, "DSNT" | "VC") {
+                kind = Lightning::Distant;
+            }
+            // Direction and frequency qualifiers (ALQDS, NE-SE, OCNL, ...) follow
+            if next.chars().all(|c| c.is_ascii_uppercase() || c == '-') && next.len() <= 5 {
+                tokens.next();
+            } else {
+                break;
+            }
+        }
+        closest = closest.max(Some(kind));
+    }
+    closest
+}
+
+/// Derive a flight category from visibility (statute miles) and cloud layers
+/// using the standard FAA thresholds. Returns None when neither is known.
+pub fn derive_flight_category(
+    visib: Option<f32>,
+    clouds: &[CloudLayer],
+) -> Option<FlightCategory> {
+    if visib.is_none() && clouds.is_empty() {
+        return None;
+    }
+    Some(categorize(visib, ceiling_ft(clouds)))
+}
+
+/// Lowest base of a broken, overcast, or obscured layer, in feet AGL.
+pub fn ceiling_ft(clouds: &[CloudLayer]) -> Option<u32> {
+    clouds
+        .iter()
+        .filter(|c| c.is_ceiling())
+        .filter_map(|c| c.base)
+        .min()
+}
+
+/// Apply the FAA thresholds. Unknown values are treated as unrestricted.
+fn categorize(visib: Option<f32>, ceiling: Option<u32>) -> FlightCategory {
+    let vis = visib.unwrap_or(f32::INFINITY);
+    let ceil = ceiling.unwrap_or(u32::MAX);
+
+    if ceil < 500 || vis < 1.0 {
+        FlightCategory::Lifr
+    } else if ceil < 1000 || vis < 3.0 {
+        FlightCategory::Ifr
+    } else if ceil <= 3000 || vis <= 5.0 {
+        FlightCategory::Mvfr
+    } else {
+        FlightCategory::Vfr
+    }
+}
+
+/// Accept visibility as a number or a string such as "10+" or "6".
+fn deserialize_visib<'de, D>(deserializer: D) -> std::result::Result<Option<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Visib {
+        Number(f32),
+        Text(String),
+    }
+
+    Ok(match Option::<Visib>::deserialize(deserializer)? {
+        Some(Visib::Number(n)) => Some(n),
+        Some(Visib::Text(s)) => s.trim().trim_end_matches('+').parse().ok(),
+        None => None,
+    })
+}
+
+/// Accept a non-negative integer given as a number or numeric string.
+/// Negative or non-numeric values become None instead of
+/// failing the whole response.
+fn deserialize_lenient_u32<'de, D>(deserializer: D) -> std::result::Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Number {
+        Int(i64),
+        Float(f64),
+        Text(String),
+    }
+
+    let value = match Option::<Number>::deserialize(deserializer)? {
+        Some(Number::Int(n)) => Some(n as f64),
+        Some(Number::Float(n)) => Some(n),
+        Some(Number::Text(s)) => s.trim().parse().ok(),
+        None => None,
+    };
+    Ok(value
+        .filter(|n| n.is_finite() && *n >= 0.0 && *n <= u32::MAX as f64)
+        .map(|n| n.round() as u32))
+}
+
+/// Treat an explicit JSON `null` the same as a missing field.
+fn deserialize_null_default<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
 /// Parse a JSON string containing an array of METAR reports.
+/// Implausible values are cleared (see [`MetarReport::sanitize`]).
 pub fn parse_metars(json: &str) -> Result<Vec<MetarReport>> {
-    let reports: Vec<MetarReport> = serde_json::from_str(json)?;
+    let mut reports: Vec<MetarReport> = serde_json::from_str(json)?;
+    for report in &mut reports {
+        report.sanitize();
+    }
     Ok(reports)
 }
 
@@ -41,12 +421,23 @@ pub fn build_metar_url(codes: &[&str]) -> String {
     url
 }
 
-/// Build a HashMap from ICAO ID to MetarReport for quick lookup.
+/// Build a HashMap from ICAO ID to MetarReport for quick lookup, keeping
+/// only the most recent report when a station appears more than once.
 pub fn metars_by_icao(reports: Vec<MetarReport>) -> std::collections::HashMap<String, MetarReport> {
-    reports
-        .into_iter()
-        .map(|r| (r.icao_id.clone(), r))
-        .collect()
+    let mut map = std::collections::HashMap::with_capacity(reports.len());
+    for report in reports {
+        match map.entry(report.icao_id.clone()) {
+            std::collections::hash_map::Entry::Occupied(mut e) => {
+                if report.supersedes(e.get()) {
+                    e.insert(report);
+                }
+            }
+            std::collections::hash_map::Entry::Vacant(e) => {
+                e.insert(report);
+            }
+        }
+    }
+    map
 }
 
 #[cfg(test)]
@@ -123,14 +514,78 @@ mod tests {
     fn has_thunderstorm_with_none() {
         let report = MetarReport {
             icao_id: "TEST".to_string(),
-            flt_cat: None,
-            wspd: None,
-            wgst: None,
-            wx_string: None,
+            ..Default::default()
         };
         assert!(!report.has_thunderstorm());
     }
 
+    fn report_with(wx: Option<&str>, raw: Option<&str>) -> MetarReport {
+        MetarReport {
+            icao_id: "TEST".to_string(),
+            wx_string: wx.map(str::to_string),
+            raw_ob: raw.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn has_thunderstorm_parses_tokens() {
+        assert!(report_with(Some("+TSRA"), None).has_thunderstorm());
+        assert!(report_with(Some("-RA TS"), None).has_thunderstorm());
+        assert!(!report_with(Some("VCTS"), None).has_thunderstorm()); // vicinity only
+        assert!(!report_with(Some("BCFG"), None).has_thunderstorm());
+    }
+
+    #[test]
+    fn lightning_from_wx_string() {
+        assert_eq!(report_with(Some("TSRA"), None).lightning(), Some(Lightning::AtStation));
+        assert_eq!(report_with(Some("VCTS"), None).lightning(), Some(Lightning::Distant));
+        assert_eq!(report_with(Some("-RA BR"), None).lightning(), None);
+        assert_eq!(report_with(None, None).lightning(), None);
+    }
+
+    #[test]
+    fn lightning_from_remarks() {
+        let distant = "KDEN 121853Z 27015KT 10SM FEW080 25/05 A3001 RMK AO2 LTG DSNT NE-SE SLP123";
+        assert_eq!(report_with(None, Some(distant)).lightning(), Some(Lightning::Distant));
+
+        let at_field = "KMCO 121853Z 09010KT 3SM TSRA BKN020CB RMK AO2 OCNL LTGICCG OHD TSB45";
+        assert_eq!(report_with(None, Some(at_field)).lightning(), Some(Lightning::AtStation));
+
+        let vicinity = "KTPA 121853Z 00000KT 10SM SCT040 RMK AO2 LTGCG VC W";
+        assert_eq!(report_with(None, Some(vicinity)).lightning(), Some(Lightning::Distant));
+    }
+
+    #[test]
+    fn lightning_ignores_body_before_remarks() {
+        // "LTG" in the station name or body without RMK must not match
+        let raw = "KXYZ 121853Z 27015KT 10SM CLR 25/05 A3001";
+        assert_eq!(report_with(None, Some(raw)).lightning(), None);
+    }
+
+    #[test]
+    fn lightning_prefers_closest_report() {
+        let raw = "KDEN 121853Z 27015KT 10SM FEW080 RMK LTG DSNT W";
+        assert_eq!(report_with(Some("TS"), Some(raw)).lightning(), Some(Lightning::AtStation));
+    }
+
+    #[test]
+    fn fog_risk_heuristic() {
+        let json = r#"[
+            {"icaoId": "KSFO", "temp": 12.2, "dewp": 11.1, "wspd": 3},
+            {"icaoId": "KLAX", "temp": 12.2, "dewp": 11.1, "wspd": 15},
+            {"icaoId": "KJFK", "temp": 20.0, "dewp": 5.0, "wspd": 0},
+            {"icaoId": "KORD", "temp": 10.0, "dewp": null}
+        ]"#;
+        let reports = parse_metars(json).unwrap();
+        assert!(reports[0].fog_risk());
+        assert!(!reports[1].fog_risk()); // too windy
+        assert!(!reports[2].fog_risk()); // spread too large
+        assert!(!reports[3].fog_risk()); // no dewpoint
+        assert_eq!(reports[2].temp_dewpoint_spread(), Some(15.0));
+        assert_eq!(reports[3].temp_dewpoint_spread(), None);
+    }
+
     #[test]
     fn max_wind_with_both() {
         let reports = parse_metars(SAMPLE_JSON).unwrap();
@@ -143,14 +598,122 @@ mod tests {
     fn max_wind_with_none() {
         let report = MetarReport {
             icao_id: "TEST".to_string(),
-            flt_cat: None,
-            wspd: None,
-            wgst: None,
-            wx_string: None,
+            ..Default::default()
         };
         assert_eq!(report.max_wind(), 0);
     }
 
+    #[test]
+    fn flight_category_parse_and_order() {
+        assert_eq!(FlightCategory::parse("VFR"), Some(FlightCategory::Vfr));
+        assert_eq!(FlightCategory::parse("LIFR"), Some(FlightCategory::Lifr));
+        assert_eq!(FlightCategory::parse("GARBAGE"), None);
+        assert_eq!(FlightCategory::Mvfr.as_str(), "MVFR");
+        assert!(FlightCategory::Vfr < FlightCategory::Mvfr);
+        assert!(FlightCategory::Mvfr < FlightCategory::Ifr);
+        assert!(FlightCategory::Ifr < FlightCategory::Lifr);
+    }
+
+    #[test]
+    fn report_flight_category() {
+        let reports = parse_metars(SAMPLE_JSON).unwrap();
+        assert_eq!(reports[0].flight_category(), Some(FlightCategory::Vfr));
+        assert_eq!(reports[2].flight_category(), Some(FlightCategory::Ifr));
+    }
+
+    fn layer(cover: &str, base: u32) -> CloudLayer {
+        CloudLayer {
+            cover: cover.to_string(),
+            base: Some(base),
+        }
+    }
+
+    #[test]
+    fn parse_visib_and_clouds() {
+        let json = r#"[
+            {"icaoId": "EGLL", "fltCat": null, "visib": "10+", "clouds": [{"cover": "BKN", "base": 2500}]},
+            {"icaoId": "LFPG", "visib": 1.5, "clouds": null},
+            {"icaoId": "EDDF"}
+        ]"#;
+        let reports = parse_metars(json).unwrap();
+        assert_eq!(reports[0].visib, Some(10.0));
+        assert_eq!(reports[0].clouds, vec![layer("BKN", 2500)]);
+        assert_eq!(reports[1].visib, Some(1.5));
+        assert!(reports[1].clouds.is_empty());
+        assert_eq!(reports[2].visib, None);
+    }
+
+    #[test]
+    fn derive_flight_category_thresholds() {
+        assert_eq!(derive_flight_category(Some(10.0), &[]), Some(FlightCategory::Vfr));
+        assert_eq!(derive_flight_category(Some(5.0), &[]), Some(FlightCategory::Mvfr));
+        assert_eq!(derive_flight_category(Some(2.0), &[]), Some(FlightCategory::Ifr));
+        assert_eq!(derive_flight_category(Some(0.5), &[]), Some(FlightCategory::Lifr));
+        assert_eq!(
+            derive_flight_category(Some(10.0), &[layer("OVC", 3000)]),
+            Some(FlightCategory::Mvfr)
+        );
+        assert_eq!(
+            derive_flight_category(Some(10.0), &[layer("BKN", 800)]),
+            Some(FlightCategory::Ifr)
+        );
+        assert_eq!(
+            derive_flight_category(Some(10.0), &[layer("OVX", 200)]),
+            Some(FlightCategory::Lifr)
+        );
+        assert_eq!(derive_flight_category(None, &[]), None);
+    }
+
+    #[test]
+    fn derive_flight_category_ignores_non_ceiling_layers() {
+        let clouds = [layer("FEW", 300), layer("SCT", 800), layer("BKN", 5000)];
+        assert_eq!(derive_flight_category(Some(10.0), &clouds), Some(FlightCategory::Vfr));
+    }
+
+    #[test]
+    fn cloud_cover_parse() {
+        assert_eq!(CloudCover::parse("CLR"), Some(CloudCover::Clear));
+        assert_eq!(CloudCover::parse("CAVOK"), Some(CloudCover::Clear));
+        assert_eq!(CloudCover::parse("SCT"), Some(CloudCover::Scattered));
+        assert_eq!(CloudCover::parse("OVX"), Some(CloudCover::Obscured));
+        assert_eq!(CloudCover::parse("???"), None);
+        assert!(layer("BKN", 1000).is_ceiling());
+        assert!(!layer("SCT", 1000).is_ceiling());
+    }
+
+    #[test]
+    fn report_ceiling() {
+        let json = r#"[
+            {"icaoId": "KSFO", "clouds": [{"cover": "FEW", "base": 800}, {"cover": "OVC", "base": 1500}, {"cover": "BKN", "base": 1200}]},
+            {"icaoId": "KLAX", "clouds": [{"cover": "OVX", "base": null}], "vertVis": 300},
+            {"icaoId": "KJFK", "clouds": [{"cover": "CLR", "base": null}]}
+        ]"#;
+        let reports = parse_metars(json).unwrap();
+        assert_eq!(reports[0].ceiling(), Some(1200));
+        assert_eq!(reports[1].ceiling(), Some(300));
+        assert_eq!(reports[1].vert_vis, Some(300));
+        assert_eq!(reports[1].flight_category(), Some(FlightCategory::Lifr));
+        assert_eq!(reports[2].ceiling(), None);
+        assert_eq!(reports[2].flight_category(), Some(FlightCategory::Vfr));
+    }
+
+    #[test]
+    fn flight_category_derived_when_fltcat_missing() {
+        let report = MetarReport {
+            icao_id: "EGLL".to_string(),
+            visib: Some(2.0),
+            ..Default::default()
+        };
+        assert_eq!(report.flight_category(), Some(FlightCategory::Ifr));
+
+        // An explicit fltCat always wins over the derived value
+        let report = MetarReport {
+            flt_cat: Some("VFR".to_string()),
+            ..report
+        };
+        assert_eq!(report.flight_category(), Some(FlightCategory::Vfr));
+    }
+
     #[test]
     fn build_metar_url_single() {
         let url = build_metar_url(&["KSFO"]);
@@ -188,4 +751,92 @@ mod tests {
         assert!(map.contains_key("KJFK"));
         assert_eq!(map["KSFO"].flt_cat.as_deref(), Some("VFR"));
     }
+
+    #[test]
+    fn metars_by_icao_keeps_newest() {
+        let json = r#"[
+            {"icaoId": "KSFO", "fltCat": "VFR", "obsTime": 1700000000, "metarType": "METAR"},
+            {"icaoId": "KSFO", "fltCat": "IFR", "obsTime": 1700001200, "metarType": "SPECI"},
+            {"icaoId": "KLAX", "fltCat": "MVFR", "obsTime": 1700003000, "metarType": "METAR"},
+            {"icaoId": "KLAX", "fltCat": "LIFR", "obsTime": 1700001000, "metarType": "SPECI"}
+        ]"#;
+        let map = metars_by_icao(parse_metars(json).unwrap());
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["KSFO"].flt_cat.as_deref(), Some("IFR")); // newer SPECI
+        assert_eq!(map["KLAX"].flt_cat.as_deref(), Some("MVFR")); // older SPECI loses
+    }
+
+    #[test]
+    fn supersedes_prefers_speci_on_tie() {
+        let metar = MetarReport {
+            obs_time: Some(100),
+            metar_type: Some("METAR".to_string()),
+            ..Default::default()
+        };
+        let speci = MetarReport {
+            metar_type: Some("SPECI".to_string()),
+            ..metar.clone()
+        };
+        assert!(speci.supersedes(&metar));
+        assert!(!metar.supersedes(&speci));
+
+        // Reports without a time are treated as oldest
+        let untimed = MetarReport::default();
+        assert!(metar.supersedes(&untimed));
+        assert!(!untimed.supersedes(&metar));
+    }
+
+    #[test]
+    fn sanitize_rejects_implausible_values() {
+        let json = r#"[{
+            "icaoId": "KBAD", "fltCat": "VFR", "wspd": 999, "wgst": 300,
+            "visib": -1, "temp": 150.0, "dewp": 10.0, "vertVis": 99999,
+            "clouds": [{"cover": "BKN", "base": 250000}, {"cover": "OVC", "base": 4000}]
+        }]"#;
+        let report = &parse_metars(json).unwrap()[0];
+        assert_eq!(report.wspd, None);
+        assert_eq!(report.wgst, None);
+        assert_eq!(report.visib, None);
+        assert_eq!(report.temp, None);
+        assert_eq!(report.dewp, Some(10.0)); // plausible once temp is gone
+        assert_eq!(report.vert_vis, None);
+        assert_eq!(report.clouds[0].base, None);
+        assert_eq!(report.clouds[1].base, Some(4000));
+    }
+
+    #[test]
+    fn sanitize_keeps_plausible_values() {
+        let mut report = parse_metars(SAMPLE_JSON).unwrap().remove(1);
+        assert_eq!(report.sanitize(), 0);
+        assert_eq!(report.wspd, Some(8));
+        assert_eq!(report.wgst, Some(20));
+    }
+
+    #[test]
+    fn sanitize_gust_below_wind_and_high_dewpoint() {
+        let mut report = MetarReport {
+            wspd: Some(20),
+            wgst: Some(10),
+            temp: Some(10.0),
+            dewp: Some(15.0),
+            ..Default::default()
+        };
+        assert_eq!(report.sanitize(), 2);
+        assert_eq!(report.wspd, Some(20));
+        assert_eq!(report.wgst, None);
+        assert_eq!(report.dewp, None);
+    }
+
+    #[test]
+    fn lenient_numbers_do_not_fail_parse() {
+        let json = r#"[
+            {"icaoId": "KNEG", "wspd": -5, "wgst": "15"},
+            {"icaoId": "KTXT", "wspd": "calm", "wgst": 12.0}
+        ]"#;
+        let reports = parse_metars(json).unwrap();
+        assert_eq!(reports[0].wspd, None);
+        assert_eq!(reports[0].wgst, Some(15));
+        assert_eq!(reports[1].wspd, None);
+        assert_eq!(reports[1].wgst, Some(12));
+    }
 }
@@ -0,0 +1,629 @@
+//! Saved WiFi networks, tried in order until one connects.
+
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+/// Most networks kept; adding another drops the oldest.
+pub const MAX_NETWORKS: usize = 5;
+
+/// Hostname advertised over mDNS (as `led-sectional.local`) and DHCP.
+pub const DEFAULT_HOSTNAME: &str = "led-sectional";
+
+/// Start of the setup access point's name; see [`setup_ap_ssid`].
+pub const SETUP_AP_PREFIX: &str = "LED-Sectional";
+
+/// The setup access point's name, e.g. `LED-Sectional-3F2A`, from the last
+/// two bytes of the station MAC address so nearby devices don't share one.
+pub fn setup_ap_ssid(mac: &[u8; 6]) -> String {
+    format!("{SETUP_AP_PREFIX}-{:02X}{:02X}", mac[4], mac[5])
+}
+
+/// Whether `password` can secure the setup access point: 8-63 printable
+/// ASCII characters, as WPA2-Personal requires.
+pub fn is_valid_ap_password(password: &str) -> bool {
+    (8..=63).contains(&password.len()) && password.bytes().all(|b| (b' '..=b'~').contains(&b))
+}
+
+/// Whether `name` is a usable single-label hostname: 1-63 ASCII letters,
+/// digits, or hyphens, not starting or ending with a hyphen.
+pub fn is_valid_hostname(name: &str) -> bool {
+    (1..=63).contains(&name.len())
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+}
+
+/// How to authenticate to a network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WifiAuth {
+    /// Open if there is no password, otherwise WPA2-Personal.
+    #[default]
+    Auto,
+    Open,
+    Wpa2Personal,
+    /// WPA3-SAE.
+    Wpa3Personal,
+    /// WPA2-Enterprise (802.1X) with PEAP/TTLS username and password.
+    Wpa2Enterprise,
+}
+
+impl WifiAuth {
+    /// Resolve `Auto` from whether a password was given.
+    pub fn resolve(self, password: &str) -> WifiAuth {
+        match self {
+            Self::Auto if password.is_empty() => Self::Open,
+            Self::Auto => Self::Wpa2Personal,
+            other => other,
+        }
+    }
+
+    pub fn is_enterprise(self) -> bool {
+        self == Self::Wpa2Enterprise
+    }
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// An access point's MAC address, written `aa:bb:cc:dd:ee:ff`. Pins a
+/// network to one node of a mesh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Bssid(pub [u8; 6]);
+
+impl FromStr for Bssid {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        let invalid = || format!("{s:?} is not a BSSID like aa:bb:cc:dd:ee:ff");
+        let mut bytes = [0u8; 6];
+        let mut parts = s.trim().split([':', '-']);
+        for byte in &mut bytes {
+            let part = parts.next().filter(|p| p.len() == 2).ok_or_else(invalid)?;
+            *byte = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+        }
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl TryFrom<String> for Bssid {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl From<Bssid> for String {
+    fn from(bssid: Bssid) -> String {
+        bssid.to_string()
+    }
+}
+
+impl fmt::Display for Bssid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Credentials {
+    pub ssid: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub auth: WifiAuth,
+    /// Enterprise login name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Enterprise outer (anonymous) identity; defaults to `username`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    /// Only join this access point, rather than the strongest one with the
+    /// SSID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bssid: Option<Bssid>,
+    /// The network doesn't broadcast its SSID, so it won't show up in scans.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
+}
+
+impl Credentials {
+    /// Personal-network credentials, authenticated by [`WifiAuth::Auto`].
+    pub fn new(ssid: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            ssid: ssid.into(),
+            password: password.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Credentials from fixed-size, NUL-padded driver buffers, as delivered
+    /// by SmartConfig. None if the SSID is empty or either isn't UTF-8.
+    pub fn from_raw(ssid: &[u8], password: &[u8]) -> Option<Self> {
+        let field = |bytes: &[u8]| {
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            std::str::from_utf8(&bytes[..end]).ok().map(str::to_string)
+        };
+        let ssid = field(ssid).filter(|s| !s.is_empty())?;
+        Some(Self::new(ssid, field(password)?))
+    }
+
+    /// The auth method to use, with `Auto` resolved.
+    pub fn auth_method(&self) -> WifiAuth {
+        self.auth.resolve(&self.password)
+    }
+
+    /// Why these credentials can't work, if they can't.
+    pub fn problem(&self) -> Option<&'static str> {
+        match self.auth_method() {
+            WifiAuth::Wpa2Enterprise if self.username.as_deref().unwrap_or("").is_empty() => {
+                Some("WPA2-Enterprise needs a username")
+            }
+            WifiAuth::Wpa2Personal | WifiAuth::Wpa3Personal | WifiAuth::Wpa2Enterprise
+                if self.password.is_empty() =>
+            {
+                Some("this auth method needs a password")
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Default `settings.weak_signal_dbm`; below this, fetches start to time out.
+pub const DEFAULT_WEAK_SIGNAL_DBM: i8 = -75;
+/// How far above the threshold the signal must climb to count as recovered,
+/// so a signal hovering near it doesn't flap.
+const WEAK_SIGNAL_HYSTERESIS_DB: i16 = 5;
+
+/// Smooths periodic RSSI readings and decides whether the signal is weak.
+#[derive(Debug, Clone)]
+pub struct SignalMonitor {
+    threshold: i8,
+    average: Option<i16>,
+    weak: bool,
+}
+
+impl SignalMonitor {
+    pub fn new(threshold: i8) -> Self {
+        Self {
+            threshold,
+            average: None,
+            weak: false,
+        }
+    }
+
+    pub fn set_threshold(&mut self, threshold: i8) {
+        self.threshold = threshold;
+    }
+
+    /// Record a reading. Returns the new state when the signal becomes weak
+    /// or recovers.
+    pub fn sample(&mut self, rssi: i8) -> Option<bool> {
+        // Each reading moves the average a quarter of the way
+        let average = match self.average {
+            Some(avg) => avg + (rssi as i16 - avg) / 4,
+            None => rssi as i16,
+        };
+        self.average = Some(average);
+
+        let threshold = self.threshold as i16;
+        let weak = if self.weak {
+            average < threshold + WEAK_SIGNAL_HYSTERESIS_DB
+        } else {
+            average < threshold
+        };
+        if weak == self.weak {
+            return None;
+        }
+        self.weak = weak;
+        Some(weak)
+    }
+
+    /// The smoothed signal in dBm, once there's been a reading.
+    pub fn rssi(&self) -> Option<i8> {
+        self.average.map(|avg| avg as i8)
+    }
+
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    /// Forget past readings, e.g. after joining a different network.
+    pub fn reset(&mut self) {
+        self.average = None;
+        self.weak = false;
+    }
+}
+
+/// Most scan results listed in the provisioning form.
+pub const MAX_SCAN_RESULTS: usize = 20;
+
+/// An access point seen in a WiFi scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScannedNetwork {
+    pub ssid: String,
+    /// Signal strength in dBm.
+    pub rssi: i8,
+    pub auth: WifiAuth,
+}
+
+impl ScannedNetwork {
+    /// Signal strength as 0-4 bars, as phones show it.
+    pub fn bars(&self) -> u8 {
+        match self.rssi {
+            -55.. => 4,
+            -65..=-56 => 3,
+            -75..=-66 => 2,
+            -85..=-76 => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Tidy raw scan results for display: hidden networks dropped, one entry
+/// per SSID (the strongest of its access points), strongest first, and at
+/// most [`MAX_SCAN_RESULTS`].
+pub fn sort_scan_results(results: Vec<ScannedNetwork>) -> Vec<ScannedNetwork> {
+    let mut best: Vec<ScannedNetwork> = Vec::new();
+    for network in results.into_iter().filter(|n| !n.ssid.is_empty()) {
+        match best.iter_mut().find(|b| b.ssid == network.ssid) {
+            Some(existing) if existing.rssi < network.rssi => *existing = network,
+            Some(_) => {}
+            None => best.push(network),
+        }
+    }
+    best.sort_by(|a, b| b.rssi.cmp(&a.rssi).then_with(|| a.ssid.cmp(&b.ssid)));
+    best.truncate(MAX_SCAN_RESULTS);
+    best
+}
+
+/// A fixed IPv4 address used instead of DHCP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticIp {
+    pub ip: Ipv4Addr,
+    pub prefix_len: u8,
+    pub gateway: Ipv4Addr,
+    pub dns: Ipv4Addr,
+}
+
+impl StaticIp {
+    /// Whether `addr` is on the same subnet as `ip`.
+    pub fn contains(&self, addr: Ipv4Addr) -> bool {
+        let mask = u32::MAX.checked_shl(32 - self.prefix_len as u32).unwrap_or(0);
+        u32::from(self.ip) & mask == u32::from(addr) & mask
+    }
+}
+
+/// The prefix length of a dotted netmask, or `None` if its bits aren't
+/// contiguous (e.g. 255.0.255.0).
+pub fn prefix_len(netmask: Ipv4Addr) -> Option<u8> {
+    let bits = u32::from(netmask);
+    (bits.leading_ones() + bits.trailing_zeros() == 32).then(|| bits.leading_ones() as u8)
+}
+
+/// An ordered list of credentials plus the one that last connected, which
+/// is tried first on the next attempt.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct NetworkList {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_connected: Option<String>,
+    #[serde(default)]
+    pub networks: Vec<Credentials>,
+}
+
+impl NetworkList {
+    pub fn from_toml(s: &str) -> Result<Self> {
+        Ok(toml::from_str(s)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.networks.len()
+    }
+
+    /// Add a network, or replace the saved details of one with the same SSID.
+    /// Returns false if nothing changed.
+    pub fn add(&mut self, credentials: Credentials) -> bool {
+        if let Some(existing) = self.networks.iter_mut().find(|n| n.ssid == credentials.ssid) {
+            if *existing == credentials {
+                return false;
+            }
+            *existing = credentials;
+            return true;
+        }
+        if self.networks.len() >= MAX_NETWORKS {
+            self.networks.remove(0);
+        }
+        self.networks.push(credentials);
+        true
+    }
+
+    /// Pin the saved network `ssid` to one access point, or unpin it with
+    /// None. Returns false if it isn't saved.
+    pub fn set_bssid(&mut self, ssid: &str, bssid: Option<Bssid>) -> bool {
+        match self.networks.iter_mut().find(|n| n.ssid == ssid) {
+            Some(network) => {
+                network.bssid = bssid;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget a network. Returns false if it wasn't saved.
+    pub fn remove(&mut self, ssid: &str) -> bool {
+        let before = self.networks.len();
+        self.networks.retain(|n| n.ssid != ssid);
+        if self.last_connected.as_deref() == Some(ssid) {
+            self.last_connected = None;
+        }
+        self.networks.len() != before
+    }
+
+    /// Note a successful connection. Returns false if it was already the last.
+    pub fn mark_connected(&mut self, ssid: &str) -> bool {
+        if self.last_connected.as_deref() == Some(ssid) {
+            return false;
+        }
+        self.last_connected = Some(ssid.to_string());
+        true
+    }
+
+    /// Networks in the order to try them: the last one that connected, then
+    /// the rest as saved.
+    pub fn connection_order(&self) -> Vec<&Credentials> {
+        let last = self.last_connected.as_deref();
+        let first = self.networks.iter().filter(|n| Some(n.ssid.as_str()) == last);
+        let rest = self.networks.iter().filter(|n| Some(n.ssid.as_str()) != last);
+        first.chain(rest).collect()
+    }
+
+    pub fn ssids(&self) -> impl Iterator<Item = &str> {
+        self.networks.iter().map(|n| n.ssid.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(ssids: &[&str]) -> NetworkList {
+        let mut list = NetworkList::default();
+        for ssid in ssids {
+            list.add(Credentials::new(*ssid, "secret"));
+        }
+        list
+    }
+
+    fn order(list: &NetworkList) -> Vec<&str> {
+        list.connection_order()
+            .iter()
+            .map(|n| n.ssid.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn last_connected_is_tried_first() {
+        let mut list = list(&["home", "shop", "phone"]);
+        assert_eq!(order(&list), vec!["home", "shop", "phone"]);
+
+        assert!(list.mark_connected("shop"));
+        assert!(!list.mark_connected("shop"));
+        assert_eq!(order(&list), vec!["shop", "home", "phone"]);
+    }
+
+    #[test]
+    fn add_updates_existing_and_caps_length() {
+        let mut list = list(&["home"]);
+        assert!(!list.add(Credentials::new("home", "secret")));
+        assert!(list.add(Credentials::new("home", "new")));
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.networks[0].password, "new");
+
+        for i in 0..MAX_NETWORKS {
+            list.add(Credentials::new(format!("net{i}"), ""));
+        }
+        assert_eq!(list.len(), MAX_NETWORKS);
+        assert!(!list.ssids().any(|s| s == "home"));
+    }
+
+    #[test]
+    fn remove_clears_last_connected() {
+        let mut list = list(&["home", "shop"]);
+        list.mark_connected("home");
+        assert!(list.remove("home"));
+        assert!(!list.remove("home"));
+        assert_eq!(list.last_connected, None);
+        assert_eq!(order(&list), vec!["shop"]);
+    }
+
+    #[test]
+    fn auth_auto_resolves_from_password() {
+        assert_eq!(Credentials::new("cafe", "").auth_method(), WifiAuth::Open);
+        assert_eq!(Credentials::new("home", "pw").auth_method(), WifiAuth::Wpa2Personal);
+        let sae = Credentials {
+            auth: WifiAuth::Wpa3Personal,
+            ..Credentials::new("home", "pw")
+        };
+        assert_eq!(sae.auth_method(), WifiAuth::Wpa3Personal);
+        assert_eq!(sae.problem(), None);
+    }
+
+    #[test]
+    fn enterprise_needs_username_and_password() {
+        let mut creds = Credentials {
+            auth: WifiAuth::Wpa2Enterprise,
+            ..Credentials::new("school", "pw")
+        };
+        assert_eq!(creds.problem(), Some("WPA2-Enterprise needs a username"));
+        creds.username = Some("student".to_string());
+        assert_eq!(creds.problem(), None);
+        creds.password.clear();
+        assert_eq!(creds.problem(), Some("this auth method needs a password"));
+    }
+
+    #[test]
+    fn bssid_parses_and_formats() {
+        let bssid: Bssid = "A4:2B:B0:12:3F:0E".parse().unwrap();
+        assert_eq!(bssid.0, [0xa4, 0x2b, 0xb0, 0x12, 0x3f, 0x0e]);
+        assert_eq!(bssid.to_string(), "a4:2b:b0:12:3f:0e");
+        assert_eq!("a4-2b-b0-12-3f-0e".parse::<Bssid>(), Ok(bssid));
+
+        let mut list = list(&["mesh"]);
+        assert!(list.set_bssid("mesh", Some(bssid)));
+        assert_eq!(list.connection_order()[0].bssid, Some(bssid));
+        assert!(!list.set_bssid("cafe", Some(bssid)));
+        for bad in ["a4:2b:b0:12:3f", "a4:2b:b0:12:3f:0e:00", "a4:2b:b0:12:3f:zz", "a42bb0123f0e"] {
+            assert!(bad.parse::<Bssid>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn scan_results_deduped_and_sorted() {
+        let seen = |ssid: &str, rssi| ScannedNetwork {
+            ssid: ssid.to_string(),
+            rssi,
+            auth: WifiAuth::Wpa2Personal,
+        };
+        let sorted = sort_scan_results(vec![
+            seen("hangar", -80),
+            seen("", -40),
+            seen("home", -70),
+            seen("hangar", -50),
+            seen("cafe", -70),
+        ]);
+        let ssids: Vec<&str> = sorted.iter().map(|n| n.ssid.as_str()).collect();
+        assert_eq!(ssids, vec!["hangar", "cafe", "home"]);
+        assert_eq!(sorted[0].bars(), 4);
+        assert_eq!(sorted[1].bars(), 2);
+        assert_eq!(seen("far", -90).bars(), 0);
+
+        let many = (0..30).map(|i| seen(&format!("net{i}"), -60)).collect();
+        assert_eq!(sort_scan_results(many).len(), MAX_SCAN_RESULTS);
+    }
+
+    #[test]
+    fn credentials_from_raw_buffers() {
+        let mut ssid = [0u8; 32];
+        ssid[..4].copy_from_slice(b"Home");
+        let mut password = [0u8; 64];
+        password[..8].copy_from_slice(b"hunter22");
+        let creds = Credentials::from_raw(&ssid, &password).unwrap();
+        assert_eq!(creds, Credentials::new("Home", "hunter22"));
+
+        // A full-length SSID has no terminator
+        assert_eq!(Credentials::from_raw(&[b'x'; 32], &[]).unwrap().ssid.len(), 32);
+        assert!(Credentials::from_raw(&[0; 32], &password).is_none());
+        assert!(Credentials::from_raw(&[0xff, 0xfe, 0], &password).is_none());
+    }
+
+    #[test]
+    fn signal_monitor_smooths_and_has_hysteresis() {
+        let mut signal = SignalMonitor::new(-75);
+        assert_eq!(signal.rssi(), None);
+        assert_eq!(signal.sample(-60), None);
+
+        // One bad reading only moves the average partway
+        assert_eq!(signal.sample(-90), None);
+        assert_eq!(signal.rssi(), Some(-67));
+        let mut changed = None;
+        for _ in 0..10 {
+            changed = changed.or(signal.sample(-90));
+        }
+        assert_eq!(changed, Some(true));
+        assert!(signal.is_weak());
+
+        // Back at the threshold isn't enough to recover
+        for _ in 0..20 {
+            assert_eq!(signal.sample(-74), None);
+        }
+        let mut changed = None;
+        for _ in 0..10 {
+            changed = changed.or(signal.sample(-60));
+        }
+        assert_eq!(changed, Some(false));
+
+        signal.reset();
+        assert_eq!(signal.rssi(), None);
+    }
+
+    #[test]
+    fn setup_ap_name_and_password() {
+        let mac = [0x24, 0x0a, 0xc4, 0x12, 0x3f, 0x2a];
+        assert_eq!(setup_ap_ssid(&mac), "LED-Sectional-3F2A");
+        assert!(is_valid_ap_password("hangar42"));
+        assert!(!is_valid_ap_password("short"));
+        assert!(!is_valid_ap_password(&"x".repeat(64)));
+        assert!(!is_valid_ap_password("tab\tin here"));
+    }
+
+    #[test]
+    fn hostname_validation() {
+        assert!(is_valid_hostname(DEFAULT_HOSTNAME));
+        assert!(is_valid_hostname("hangar2"));
+        assert!(!is_valid_hostname(""));
+        assert!(!is_valid_hostname("-map"));
+        assert!(!is_valid_hostname("my map"));
+        assert!(!is_valid_hostname("map.local"));
+        assert!(!is_valid_hostname(&"a".repeat(64)));
+    }
+
+    #[test]
+    fn netmask_prefix_and_subnet() {
+        assert_eq!(prefix_len(Ipv4Addr::new(255, 255, 255, 0)), Some(24));
+        assert_eq!(prefix_len(Ipv4Addr::new(255, 255, 240, 0)), Some(20));
+        assert_eq!(prefix_len(Ipv4Addr::new(255, 255, 255, 255)), Some(32));
+        assert_eq!(prefix_len(Ipv4Addr::UNSPECIFIED), Some(0));
+        assert_eq!(prefix_len(Ipv4Addr::new(255, 0, 255, 0)), None);
+
+        let fixed = StaticIp {
+            ip: Ipv4Addr::new(192, 168, 1, 50),
+            prefix_len: 24,
+            gateway: Ipv4Addr::new(192, 168, 1, 1),
+            dns: Ipv4Addr::new(192, 168, 1, 1),
+        };
+        assert!(fixed.contains(fixed.gateway));
+        assert!(!fixed.contains(Ipv4Addr::new(192, 168, 2, 1)));
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let mut list = list(&["home", "shop"]);
+        list.mark_connected("shop");
+        list.add(Credentials {
+            auth: WifiAuth::Wpa2Enterprise,
+            username: Some("pilot".to_string()),
+            ..Credentials::new("hangar", "pw")
+        });
+        list.add(Credentials {
+            bssid: Some(Bssid([0xa4, 0x2b, 0xb0, 0x12, 0x3f, 0x0e])),
+            hidden: true,
+            ..Credentials::new("mesh", "pw")
+        });
+        let toml = list.to_toml().unwrap();
+        assert!(toml.contains("bssid = \"a4:2b:b0:12:3f:0e\""), "{toml}");
+        assert_eq!(toml.matches("hidden = true").count(), 1, "{toml}");
+        let parsed = NetworkList::from_toml(&toml).unwrap();
+        assert_eq!(parsed, list);
+        assert!(NetworkList::from_toml("").unwrap().is_empty());
+    }
+}
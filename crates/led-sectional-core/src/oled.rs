@@ -0,0 +1,248 @@
+//! Text frames for 128x64 monochrome I2C OLED panels built on the SSD1306 or
+//! SH1106 controller. Both take the picture as eight pages of 128 columns,
+//! each column byte holding eight pixels with bit 0 at the top; they differ
+//! in power-up commands and the SH1106 centering 128 columns in its 132.
+
+use serde::{Deserialize, Serialize};
+
+pub const WIDTH: usize = 128;
+pub const PAGES: usize = 8;
+/// Characters per line in the 6-pixel-wide font.
+pub const COLUMNS: usize = WIDTH / GLYPH_WIDTH;
+/// Lines of text, one per page.
+pub const ROWS: usize = PAGES;
+/// The address most panels answer on; some can be strapped to 0x3D.
+pub const DEFAULT_ADDRESS: u8 = 0x3C;
+
+/// Control byte starting a run of commands.
+pub const COMMANDS: u8 = 0x00;
+/// Control byte starting a run of pixel data.
+pub const DATA: u8 = 0x40;
+
+/// Five columns of glyph and one of spacing.
+const GLYPH_WIDTH: usize = 6;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Controller {
+    #[default]
+    Ssd1306,
+    Sh1106,
+}
+
+impl Controller {
+    /// Commands that wake the panel from reset and turn it on, set up for
+    /// page addressing with column 0 at the left and page 0 at the top.
+    pub fn init_commands(self) -> &'static [u8] {
+        const SSD1306: &[u8] = &[
+            0xAE, // Display off
+            0xD5, 0x80, // Clock divider
+            0xA8, 0x3F, // 64 rows
+            0xD3, 0x00, // No vertical offset
+            0x40, // Start at line 0
+            0x8D, 0x14, // Internal charge pump on
+            0x20, 0x02, // Page addressing
+            0xA1, 0xC8, // Mirror both ways, as panels are mounted
+            0xDA, 0x12, // Alternative COM pins
+            0x81, 0xCF, // Contrast
+            0xD9, 0xF1, // Pre-charge
+            0xDB, 0x40, // VCOMH deselect level
+            0xA4, 0xA6, // Show RAM, not inverted
+            0xAF, // Display on
+        ];
+        const SH1106: &[u8] = &[
+            0xAE, // Display off
+            0xD5, 0x80, // Clock divider
+            0xA8, 0x3F, // 64 rows
+            0xD3, 0x00, // No vertical offset
+            0x40, // Start at line 0
+            0xAD, 0x8B, // DC-DC converter on
+            0xA1, 0xC8, // Mirror both ways, as panels are mounted
+            0xDA, 0x12, // Alternative COM pins
+            0x81, 0xCF, // Contrast
+            0xD9, 0x22, // Pre-charge
+            0xDB, 0x35, // VCOM deselect level
+            0xA4, 0xA6, // Show RAM, not inverted
+            0xAF, // Display on
+        ];
+        match self {
+            Self::Ssd1306 => SSD1306,
+            Self::Sh1106 => SH1106,
+        }
+    }
+
+    /// Commands pointing the write position at the left of `page`.
+    pub fn page_commands(self, page: u8) -> [u8; 3] {
+        let column = match self {
+            Self::Ssd1306 => 0,
+            Self::Sh1106 => 2,
+        };
+        [0xB0 | (page & 0x07), column & 0x0F, 0x10 | (column >> 4)]
+    }
+}
+
+/// One screenful of pixels, page by page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub pages: [[u8; WIDTH]; PAGES],
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self {
+            pages: [[0; WIDTH]; PAGES],
+        }
+    }
+}
+
+impl Frame {
+    /// Draw `lines` from the top, one per page. Lines past [`ROWS`] and
+    /// characters past [`COLUMNS`] are cut off; characters the font lacks
+    /// show as `?`.
+    pub fn text<S: AsRef<str>>(lines: &[S]) -> Self {
+        let mut frame = Self::default();
+        for (page, line) in frame.pages.iter_mut().zip(lines) {
+            for (cell, c) in page.chunks_exact_mut(GLYPH_WIDTH).zip(line.as_ref().chars()) {
+                cell[..5].copy_from_slice(glyph(c));
+            }
+        }
+        frame
+    }
+}
+
+fn glyph(c: char) -> &'static [u8; 5] {
+    let index = match c {
+        ' '..='~' => c as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+    &FONT[index]
+}
+
+/// The classic 5x7 font for printable ASCII, a column per byte.
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_lands_in_six_pixel_cells() {
+        let frame = Frame::text(&["A1", "", "é"]);
+        assert_eq!(frame.pages[0][..6], [0x7E, 0x11, 0x11, 0x11, 0x7E, 0]);
+        assert_eq!(frame.pages[0][6..11], [0x00, 0x42, 0x7F, 0x40, 0x00]);
+        assert!(frame.pages[1].iter().all(|&b| b == 0));
+        assert_eq!(frame.pages[2][..5], FONT['?' as usize - 32]);
+
+        let long = "X".repeat(COLUMNS + 5);
+        let frame = Frame::text(&[long]);
+        // 21 characters fill 126 columns; the last two stay dark
+        assert_eq!(frame.pages[0][COLUMNS * 6 - 2], 0x63);
+        assert_eq!(frame.pages[0][126..], [0, 0]);
+    }
+
+    #[test]
+    fn sh1106_pages_start_two_columns_in() {
+        assert_eq!(Controller::Ssd1306.page_commands(3), [0xB3, 0x00, 0x10]);
+        assert_eq!(Controller::Sh1106.page_commands(7), [0xB7, 0x02, 0x10]);
+        for controller in [Controller::Ssd1306, Controller::Sh1106] {
+            let commands = controller.init_commands();
+            assert_eq!((commands[0], commands[commands.len() - 1]), (0xAE, 0xAF));
+        }
+    }
+}
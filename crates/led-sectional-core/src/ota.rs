@@ -0,0 +1,407 @@
+//! Over-the-air updates: image checks, so an upload of the wrong file is
+//! refused before the OTA partition is erased, and the manifest a device
+//! polls to find newer firmware. The bootloader still verifies the whole
+//! image (chip, segments, appended SHA-256) when the update is finished.
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+/// Bytes from the start of an image needed by [`AppInfo::from_image`]: the
+/// image header, the first segment header, and the app description.
+pub const IMAGE_HEADER_LEN: usize = IMAGE_DESC_OFFSET + APP_DESC_LEN;
+
+/// First byte of every ESP-IDF app image.
+const IMAGE_MAGIC: u8 = 0xE9;
+/// 24-byte image header plus the first 8-byte segment header.
+const IMAGE_DESC_OFFSET: usize = 32;
+/// `esp_app_desc_t`, which the build puts at the start of the first segment.
+const APP_DESC_MAGIC: u32 = 0xABCD_5432;
+const APP_DESC_LEN: usize = 256;
+const VERSION_FIELD: std::ops::Range<usize> = 16..48;
+const PROJECT_FIELD: std::ops::Range<usize> = 48..80;
+
+/// What an image says about itself in its app description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppInfo {
+    pub project: String,
+    pub version: String,
+}
+
+impl AppInfo {
+    /// Read the app description from the first [`IMAGE_HEADER_LEN`] bytes of
+    /// an image.
+    pub fn from_image(image: &[u8]) -> Result<Self> {
+        if image.len() < IMAGE_HEADER_LEN {
+            return Err(Error::FirmwareImage("is too short".to_string()));
+        }
+        if image[0] != IMAGE_MAGIC {
+            return Err(Error::FirmwareImage("is not an ESP32 app image".to_string()));
+        }
+        Self::from_app_desc(&image[IMAGE_DESC_OFFSET..])
+    }
+
+    /// Read a raw `esp_app_desc_t`, like the running app's own.
+    pub fn from_app_desc(desc: &[u8]) -> Result<Self> {
+        let magic = desc
+            .get(..4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+        if desc.len() < APP_DESC_LEN || magic != Some(APP_DESC_MAGIC) {
+            return Err(Error::FirmwareImage("has no app description".to_string()));
+        }
+        Ok(Self {
+            project: c_string(&desc[PROJECT_FIELD]),
+            version: c_string(&desc[VERSION_FIELD]),
+        })
+    }
+
+    /// Check that `image` is an update for this app: an app image built from
+    /// the same project. Returns what the image describes.
+    pub fn check_update(&self, image: &[u8]) -> Result<AppInfo> {
+        let update = Self::from_image(image)?;
+        if update.project != self.project {
+            return Err(Error::FirmwareImage(format!(
+                "is for \"{}\", not \"{}\"",
+                update.project, self.project
+            )));
+        }
+        Ok(update)
+    }
+}
+
+/// A fixed-size NUL-padded C string field.
+fn c_string(field: &[u8]) -> String {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..len]).into_owned()
+}
+
+/// What `settings.ota.manifest_url` serves: the latest release, as JSON like
+/// `{"version": "0.2.0", "url": "https://…/led-sectional-firmware.bin",
+/// "sha256": "…"}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub version: String,
+    /// Where to download the image; must be HTTPS.
+    pub url: String,
+    /// SHA-256 of the whole image file.
+    pub sha256: [u8; 32],
+}
+
+#[derive(Deserialize)]
+struct RawManifest {
+    version: String,
+    url: String,
+    sha256: String,
+}
+
+impl Manifest {
+    pub fn parse(json: &str) -> Result<Self> {
+        let raw: RawManifest = serde_json::from_str(json)?;
+        if !raw.url.starts_with("https://") {
+            return Err(Error::Manifest(format!("url \"{}\" is not HTTPS", raw.url)));
+        }
+        let sha256 = parse_digest(&raw.sha256)
+            .ok_or_else(|| Error::Manifest("sha256 must be 64 hex digits".to_string()))?;
+        Ok(Self {
+            version: raw.version,
+            url: raw.url,
+            sha256,
+        })
+    }
+
+    /// Whether this release is newer than `running`, comparing dotted
+    /// version numbers (a leading `v` is ignored). A pre-release such as
+    /// `1.2.0-rc1` comes before `1.2.0`. Versions that don't parse are never
+    /// newer, so a malformed manifest can't cause an update loop.
+    pub fn is_newer_than(&self, running: &str) -> bool {
+        match (version_key(&self.version), version_key(running)) {
+            (Some(latest), Some(running)) => latest > running,
+            _ => false,
+        }
+    }
+}
+
+/// Numeric parts, then whether it's a full release, so tuples compare in
+/// version order.
+fn version_key(version: &str) -> Option<(Vec<u64>, bool)> {
+    let version = version.trim().trim_start_matches('v');
+    let (numbers, pre) = match version.split_once('-') {
+        Some((numbers, pre)) => (numbers, Some(pre)),
+        None => (version, None),
+    };
+    let mut parts = numbers
+        .split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    // 1.2 and 1.2.0 are the same version
+    while parts.len() > 1 && parts.last() == Some(&0) {
+        parts.pop();
+    }
+    Some((parts, pre.is_none()))
+}
+
+fn parse_digest(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut digest = [0u8; 32];
+    for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(digest)
+}
+
+/// Streaming SHA-256 (FIPS 180-4), for checking a download against its
+/// manifest as it is written.
+#[derive(Debug, Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+const SHA256_INIT: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Self {
+            state: SHA256_INIT,
+            block: [0; 64],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        while !data.is_empty() {
+            let take = (64 - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + take].copy_from_slice(&data[..take]);
+            self.block_len += take;
+            data = &data[take..];
+            if self.block_len == 64 {
+                compress(&mut self.state, &self.block);
+                self.block_len = 0;
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> [u8; 32] {
+        let bit_len = self.total_len * 8;
+        self.update(&[0x80]);
+        while self.block_len != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bit_len.to_be_bytes());
+        let mut digest = [0u8; 32];
+        for (out, word) in digest.chunks_mut(4).zip(self.state) {
+            out.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+fn compress(state: &mut [u32; 8], block: &[u8; 64]) {
+    let mut w = [0u32; 64];
+    for (i, word) in block.chunks(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(SHA256_K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *s = s.wrapping_add(v);
+    }
+}
+
+/// The `/update` page. Uploading posts the chosen file as the request body
+/// and reloads the dashboard once the device is back.
+pub fn update_page(running: &AppInfo) -> String {
+    HTML_UPDATE.replace("{VERSION}", &running.version.replace('<', "&lt;"))
+}
+
+const HTML_UPDATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width,initial-scale=1">
+<title>LED Sectional - Update</title>
+<style>
+body{font-family:system-ui,sans-serif;background:#1a1a2e;color:#e0e0e0;margin:1rem;max-width:480px}
+h1{font-size:1.4rem;color:#a8d8ea}
+a{color:#a8d8ea}
+button{background:#e94560;color:#e0e0e0;border:none;border-radius:4px;padding:.5rem 1rem;margin-top:1rem}
+progress{width:100%;margin-top:1rem}
+.error{color:#e94560}
+</style>
+</head>
+<body>
+<h1>Firmware update</h1>
+<p>Running version {VERSION}. Choose a <code>led-sectional-firmware.bin</code> from a release.</p>
+<input id="file" type="file" accept=".bin">
+<button id="go" onclick="upload()">Install</button>
+<progress id="bar" value="0" max="1" hidden></progress>
+<p id="msg"></p>
+<p><a href="/">Back to the map</a></p>
+<script>
+function say(t,bad){var m=document.getElementById('msg');m.textContent=t;m.className=bad?'error':''}
+function back(){fetch('/status').then(function(){location.href='/'}).catch(function(){setTimeout(back,2000)})}
+function upload(){var f=document.getElementById('file').files[0];if(!f){say('Choose a file first',true);return}
+var bar=document.getElementById('bar');var go=document.getElementById('go');bar.hidden=false;go.disabled=true;say('Uploading…');
+var x=new XMLHttpRequest();x.open('POST','/update');
+x.upload.onprogress=function(e){if(e.lengthComputable){bar.value=e.loaded/e.total;if(e.loaded==e.total)say('Verifying…')}};
+x.onload=function(){if(x.status==200){say(x.responseText);setTimeout(back,5000)}else{say(x.responseText,true);go.disabled=false}};
+x.onerror=function(){say('Upload failed; check the connection and try again',true);go.disabled=false};
+x.send(f)}
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(project: &str, version: &str) -> Vec<u8> {
+        let mut image = vec![0u8; IMAGE_HEADER_LEN + 64];
+        image[0] = IMAGE_MAGIC;
+        let desc = &mut image[IMAGE_DESC_OFFSET..];
+        desc[..4].copy_from_slice(&APP_DESC_MAGIC.to_le_bytes());
+        desc[VERSION_FIELD][..version.len()].copy_from_slice(version.as_bytes());
+        desc[PROJECT_FIELD][..project.len()].copy_from_slice(project.as_bytes());
+        image
+    }
+
+    #[test]
+    fn reads_the_app_description() {
+        let info = AppInfo::from_image(&image("led-sectional", "0.2.0")).unwrap();
+        assert_eq!(
+            info,
+            AppInfo {
+                project: "led-sectional".to_string(),
+                version: "0.2.0".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let running = AppInfo::from_image(&image("led-sectional", "0.1.0")).unwrap();
+        let update = running.check_update(&image("led-sectional", "0.2.0")).unwrap();
+        assert_eq!(update.version, "0.2.0");
+
+        let err = running.check_update(&image("blinky", "1.0.0")).unwrap_err();
+        assert!(err.to_string().contains("\"blinky\""), "{err}");
+        let mut text = image("led-sectional", "0.2.0");
+        text[0] = b'<';
+        assert!(running.check_update(&text).is_err());
+        text = image("led-sectional", "0.2.0");
+        text[IMAGE_DESC_OFFSET] = 0;
+        assert!(running.check_update(&text).is_err());
+        assert!(running.check_update(&[IMAGE_MAGIC; 40]).is_err());
+    }
+
+    fn hex(digest: [u8; 32]) -> String {
+        digest.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn sha256_known_answers() {
+        let digest = |data: &[u8]| {
+            let mut sha = Sha256::new();
+            sha.update(data);
+            hex(sha.finish())
+        };
+        assert_eq!(
+            digest(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            digest(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // Two blocks once padded
+        assert_eq!(
+            digest(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+
+        // Fed in pieces that straddle block boundaries
+        let data: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        let mut sha = Sha256::new();
+        for chunk in data.chunks(37) {
+            sha.update(chunk);
+        }
+        assert_eq!(hex(sha.finish()), digest(&data));
+    }
+
+    #[test]
+    fn manifest_parses_and_compares_versions() {
+        let sha = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let json = format!(
+            r#"{{"version":"0.2.0","url":"https://example.com/fw.bin","sha256":"{sha}"}}"#
+        );
+        let manifest = Manifest::parse(&json).unwrap();
+        assert_eq!(hex(manifest.sha256), sha);
+        assert!(manifest.is_newer_than("0.1.9"));
+        assert!(manifest.is_newer_than("0.2.0-rc1"));
+        assert!(!manifest.is_newer_than("0.2"));
+        assert!(!manifest.is_newer_than("v0.10.0"));
+        assert!(!manifest.is_newer_than("dev"));
+
+        let plain = json.replace("https://", "http://");
+        assert!(matches!(Manifest::parse(&plain), Err(Error::Manifest(_))));
+        let short = json.replace(sha, "abc123");
+        assert!(matches!(Manifest::parse(&short), Err(Error::Manifest(_))));
+        assert!(Manifest::parse("{}").is_err());
+    }
+}
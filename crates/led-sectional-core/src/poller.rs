@@ -0,0 +1,697 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::config::{Config, DisplayMode};
+use crate::led::{update_leds_from_metars, LedState, UpdateSummary, COLOR_FETCH_ERROR};
+use crate::metar::{self, FlightCategory, MetarReport};
+use crate::source::{FetchResult, MetarSource};
+use crate::winds_aloft::{self, StationWinds};
+
+/// Delay before retrying after a failed fetch.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+/// Minimum time between fetches, even when a refresh is requested.
+pub const MIN_FETCH_SPACING: Duration = Duration::from_secs(30);
+/// Upper bound on the randomized delay before the first fetch after boot.
+const MAX_STARTUP_DELAY: Duration = Duration::from_secs(10);
+
+/// What happened during a call to [`MetarPoller::poll`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PollOutcome {
+    /// No fetch was due.
+    Idle,
+    /// New data was applied to the LED state.
+    Updated(UpdateSummary),
+    /// The source had nothing new; the LED state is unchanged.
+    NotModified,
+    /// The fetch failed and the LEDs show the fetch-error color.
+    Failed(String),
+}
+
+impl PollOutcome {
+    /// Whether the LED state changed and must be written to the strip.
+    pub fn needs_render(&self) -> bool {
+        matches!(self, Self::Updated(_) | Self::Failed(_))
+    }
+}
+
+/// A fetch the poller wants made. Running it only needs the source, so it
+/// can happen on another thread while the LEDs keep animating; the result
+/// goes back through [`MetarPoller::finish_fetch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchJob {
+    kind: FetchKind,
+    /// Forget cached validators first, as after a reconfigure.
+    fresh: bool,
+    generation: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FetchKind {
+    Metars(Vec<String>),
+    WindsAloft(u8),
+}
+
+/// What a [`FetchJob`] brought back.
+#[derive(Debug, Clone)]
+pub struct FetchResponse {
+    generation: u32,
+    data: FetchData,
+}
+
+#[derive(Debug, Clone)]
+enum FetchData {
+    Metars(Result<FetchResult, String>),
+    WindsAloft(Result<HashMap<String, StationWinds>, String>),
+}
+
+impl FetchJob {
+    /// Make the request. Blocks for as long as the source takes.
+    pub fn run<S: MetarSource>(self, source: &mut S) -> FetchResponse {
+        if self.fresh {
+            source.invalidate_cache();
+        }
+        let data = match self.kind {
+            FetchKind::Metars(codes) => {
+                let codes: Vec<&str> = codes.iter().map(String::as_str).collect();
+                FetchData::Metars(source.fetch(&codes).map_err(|e| e.to_string()))
+            }
+            FetchKind::WindsAloft(hours) => {
+                FetchData::WindsAloft(source.fetch_winds_aloft(hours).map_err(|e| e.to_string()))
+            }
+        };
+        FetchResponse {
+            generation: self.generation,
+            data,
+        }
+    }
+}
+
+/// Fetch scheduling and LED updates for the main loop, independent of the
+/// transport so it can run against a fake source on the host.
+///
+/// [`poll`](Self::poll) fetches in place. To keep the caller free while
+/// the request is out, [`start_fetch`](Self::start_fetch) hands back the
+/// job and [`finish_fetch`](Self::finish_fetch) applies its response.
+///
+/// Each interval is extended by a random jitter so that devices which booted
+/// together (e.g. after a regional power blip) don't hit the API in lockstep.
+///
+/// With `active_interval_secs` set, a fetch that finds thunderstorms or
+/// IFR/LIFR drops the interval to that floor; once conditions improve it
+/// doubles on each fetch back up to `request_interval_secs`.
+pub struct MetarPoller {
+    fetch_interval: Duration,
+    active_interval: Option<Duration>,
+    current_interval: Duration,
+    jitter: Duration,
+    rng: XorShift,
+    next_fetch: Option<Instant>,
+    last_fetch: Option<Instant>,
+    started: bool,
+    /// Reports from the last METAR fetch that returned any, by station.
+    reports: HashMap<String, MetarReport>,
+    in_flight: bool,
+    /// Bumped by `reconfigure`, so a response to a job started before it
+    /// is dropped.
+    generation: u32,
+    fresh: bool,
+}
+
+impl MetarPoller {
+    pub fn new(config: &Config) -> Self {
+        Self::with_seed(config, 0)
+    }
+
+    /// Create a poller whose jitter is derived from `seed`. Devices should pass
+    /// a hardware random number so their schedules diverge.
+    pub fn with_seed(config: &Config, seed: u64) -> Self {
+        let fetch_interval = Duration::from_secs(config.settings.request_interval_secs);
+        Self {
+            fetch_interval,
+            active_interval: config.settings.active_interval_secs.map(Duration::from_secs),
+            current_interval: fetch_interval,
+            jitter: Duration::from_secs(config.settings.request_jitter_secs),
+            rng: XorShift::new(seed),
+            next_fetch: None,
+            last_fetch: None,
+            started: false,
+            reports: HashMap::new(),
+            in_flight: false,
+            generation: 0,
+            fresh: false,
+        }
+    }
+
+    /// Fetch on the next `poll`, subject to [`MIN_FETCH_SPACING`].
+    pub fn request_refresh(&mut self) {
+        self.started = true;
+        self.next_fetch = self.last_fetch.map(|t| t + MIN_FETCH_SPACING);
+    }
+
+    /// Apply a reloaded config without rebooting: pick up the new schedule,
+    /// resize or re-dim the LED state, find the indicator LEDs again, and
+    /// repaint from a fresh fetch. A fetch already out is ignored when it
+    /// comes back.
+    pub fn reconfigure(&mut self, config: &Config, led_state: &mut LedState) {
+        self.fetch_interval = Duration::from_secs(config.settings.request_interval_secs);
+        self.active_interval = config.settings.active_interval_secs.map(Duration::from_secs);
+        self.current_interval = self.fetch_interval;
+        self.jitter = Duration::from_secs(config.settings.request_jitter_secs);
+
+        let settings = &config.settings;
+        if led_state.num_leds() != config.num_leds() {
+            let (display_on, scheduled_off) = (led_state.display_on(), led_state.scheduled_off());
+            *led_state = LedState::new(config.num_leds(), settings.brightness);
+            led_state.set_display_on(display_on);
+            led_state.set_scheduled_off(scheduled_off);
+        } else {
+            // Indices may now point at different airports
+            led_state.set_lightning_indices(Vec::new());
+            led_state.set_distant_lightning_indices(Vec::new());
+            led_state.set_blink_indices(Vec::new());
+        }
+        led_state.set_brightness_limits(settings.min_brightness, settings.max_brightness);
+        led_state.set_current_limit(settings.max_current_ma);
+        led_state.set_brightness(settings.brightness);
+        led_state.set_indicator_indices(config.indicator_led_indices());
+
+        self.generation = self.generation.wrapping_add(1);
+        self.in_flight = false;
+        self.fresh = true;
+        self.request_refresh();
+    }
+
+    /// The interval the next fetch is scheduled on, before jitter; shorter
+    /// than `request_interval_secs` during active weather.
+    pub fn current_interval(&self) -> Duration {
+        self.current_interval
+    }
+
+    /// When the next fetch is due; None until the first is scheduled.
+    pub fn next_fetch(&self) -> Option<Instant> {
+        self.next_fetch
+    }
+
+    /// The reports the LEDs were last painted from, by station. Kept through
+    /// failed and not-modified fetches; empty in winds-aloft mode.
+    pub fn reports(&self) -> &HashMap<String, MetarReport> {
+        &self.reports
+    }
+
+    /// Shorten or relax `current_interval` after a successful update.
+    fn adapt_interval(&mut self, summary: &UpdateSummary) {
+        let Some(floor) = self.active_interval else {
+            return;
+        };
+        let active = summary.has_lightning()
+            || summary.worst_category() >= Some(FlightCategory::Ifr);
+        self.current_interval = if active {
+            floor.min(self.fetch_interval)
+        } else {
+            (self.current_interval * 2).min(self.fetch_interval)
+        };
+    }
+
+    fn random_delay(&mut self, max: Duration) -> Duration {
+        let max_ms = max.as_millis() as u64;
+        if max_ms == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(self.rng.next() % (max_ms + 1))
+    }
+
+    /// Fetch and apply new data if the schedule says it's due.
+    pub fn poll<S: MetarSource>(
+        &mut self,
+        now: Instant,
+        source: &mut S,
+        config: &Config,
+        led_state: &mut LedState,
+    ) -> PollOutcome {
+        match self.start_fetch(now, config) {
+            Some(job) => self.finish_fetch(job.run(source), config, led_state),
+            None => PollOutcome::Idle,
+        }
+    }
+
+    /// The fetch to make if the schedule says one is due and none is out.
+    pub fn start_fetch(&mut self, now: Instant, config: &Config) -> Option<FetchJob> {
+        if self.in_flight {
+            return None;
+        }
+        if !self.started {
+            self.started = true;
+            let delay = self.random_delay(self.jitter.min(MAX_STARTUP_DELAY));
+            if !delay.is_zero() {
+                self.next_fetch = Some(now + delay);
+            }
+        }
+        if self.next_fetch.is_some_and(|t| now < t) {
+            return None;
+        }
+        self.last_fetch = Some(now);
+        self.in_flight = true;
+
+        let settings = &config.settings;
+        let kind = match settings.display_mode {
+            DisplayMode::Metar => FetchKind::Metars(
+                config.metar_airport_codes().into_iter().map(str::to_string).collect(),
+            ),
+            DisplayMode::WindsAloft => FetchKind::WindsAloft(settings.winds_aloft.forecast_hours),
+        };
+        Some(FetchJob {
+            kind,
+            fresh: std::mem::take(&mut self.fresh),
+            generation: self.generation,
+        })
+    }
+
+    /// Apply the response to the job from [`start_fetch`](Self::start_fetch)
+    /// and schedule the next. Idle, changing nothing, if the config was
+    /// reloaded while it was out.
+    pub fn finish_fetch(
+        &mut self,
+        response: FetchResponse,
+        config: &Config,
+        led_state: &mut LedState,
+    ) -> PollOutcome {
+        if response.generation != self.generation || !self.in_flight {
+            return PollOutcome::Idle;
+        }
+        self.in_flight = false;
+
+        let outcome = match response.data {
+            FetchData::Metars(result) => self.apply_metars(result, config, led_state),
+            FetchData::WindsAloft(result) => {
+                self.reports.clear();
+                Self::apply_winds_aloft(result, config, led_state)
+            }
+        };
+
+        if let PollOutcome::Updated(summary) = &outcome {
+            self.adapt_interval(summary);
+        }
+        let delay = match outcome {
+            PollOutcome::Failed(_) => RETRY_INTERVAL.min(self.current_interval),
+            _ => self.current_interval,
+        };
+        let jitter = self.random_delay(self.jitter);
+        let started = self.last_fetch.unwrap_or_else(Instant::now);
+        self.next_fetch = Some(started + delay.max(MIN_FETCH_SPACING) + jitter);
+        outcome
+    }
+
+    fn apply_metars(
+        &mut self,
+        result: Result<FetchResult, String>,
+        config: &Config,
+        led_state: &mut LedState,
+    ) -> PollOutcome {
+        match result {
+            Ok(FetchResult::NotModified) => PollOutcome::NotModified,
+            Ok(FetchResult::Updated(reports)) => {
+                let metar_map = metar::metars_by_icao(reports);
+                let summary = update_leds_from_metars(
+                    led_state,
+                    &config.airports,
+                    &metar_map,
+                    &config.settings,
+                );
+                led_state.set_lightning_indices(summary.lightning_indices.clone());
+                led_state.set_distant_lightning_indices(summary.distant_lightning_indices.clone());
+                let blink = if config.settings.gust_blink {
+                    summary.gust_indices.clone()
+                } else {
+                    Vec::new()
+                };
+                led_state.set_blink_indices(blink);
+                self.reports = metar_map;
+                PollOutcome::Updated(summary)
+            }
+            Err(e) => Self::fail(led_state, e),
+        }
+    }
+
+    fn apply_winds_aloft(
+        result: Result<HashMap<String, StationWinds>, String>,
+        config: &Config,
+        led_state: &mut LedState,
+    ) -> PollOutcome {
+        match result {
+            Ok(winds) => {
+                winds_aloft::update_leds_from_winds_aloft(
+                    led_state,
+                    &config.airports,
+                    &winds,
+                    &config.settings,
+                );
+                led_state.set_lightning_indices(Vec::new());
+                led_state.set_distant_lightning_indices(Vec::new());
+                led_state.set_blink_indices(Vec::new());
+                PollOutcome::Updated(UpdateSummary::default())
+            }
+            Err(e) => Self::fail(led_state, e),
+        }
+    }
+
+    fn fail(led_state: &mut LedState, error: String) -> PollOutcome {
+        led_state.set_all(COLOR_FETCH_ERROR);
+        led_state.set_blink_indices(Vec::new());
+        PollOutcome::Failed(error)
+    }
+}
+
+/// Minimal xorshift64* generator; jitter only needs to decorrelate devices.
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    pub(crate) fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift
+        Self(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::{COLOR_IFR, COLOR_VFR};
+    use crate::metar::MetarReport;
+    use crate::winds_aloft::StationWinds;
+    use std::collections::{HashMap, VecDeque};
+
+    /// A source that replays scripted results and records requests.
+    #[derive(Default)]
+    struct ScriptedSource {
+        results: VecDeque<Result<FetchResult, String>>,
+        requests: Vec<Vec<String>>,
+        invalidations: usize,
+    }
+
+    impl MetarSource for ScriptedSource {
+        type Error = String;
+
+        fn fetch(&mut self, codes: &[&str]) -> Result<FetchResult, String> {
+            self.requests.push(codes.iter().map(|c| c.to_string()).collect());
+            self.results
+                .pop_front()
+                .unwrap_or(Ok(FetchResult::NotModified))
+        }
+
+        fn fetch_winds_aloft(&mut self, _: u8) -> Result<HashMap<String, StationWinds>, String> {
+            Err("winds unavailable".to_string())
+        }
+
+        fn invalidate_cache(&mut self) {
+            self.invalidations += 1;
+        }
+    }
+
+    fn report(icao: &str, cat: &str) -> MetarReport {
+        MetarReport {
+            icao_id: icao.to_string(),
+            flt_cat: Some(cat.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn config() -> Config {
+        Config::from_toml(
+            r#"
+[settings]
+request_interval_secs = 300
+request_jitter_secs = 0
+
+[[airports]]
+code = "KSFO"
+
+[[airports]]
+code = "VFR"
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn first_poll_fetches_and_updates() {
+        let config = config();
+        let mut state = LedState::new(config.num_leds(), 255);
+        let mut source = ScriptedSource::default();
+        source
+            .results
+            .push_back(Ok(FetchResult::Updated(vec![report("KSFO", "IFR")])));
+        let mut poller = MetarPoller::new(&config);
+
+        let outcome = poller.poll(Instant::now(), &mut source, &config, &mut state);
+
+        assert!(matches!(outcome, PollOutcome::Updated(_)));
+        assert!(outcome.needs_render());
+        assert_eq!(state.get(0).unwrap(), COLOR_IFR);
+        assert_eq!(state.get(1).unwrap(), COLOR_VFR);
+        assert_eq!(source.requests, vec![vec!["KSFO".to_string()]]);
+        assert_eq!(poller.reports()["KSFO"].flt_cat.as_deref(), Some("IFR"));
+    }
+
+    #[test]
+    fn polls_on_interval() {
+        let config = config();
+        let mut state = LedState::new(config.num_leds(), 255);
+        let mut source = ScriptedSource::default();
+        let mut poller = MetarPoller::new(&config);
+        let start = Instant::now();
+
+        assert_eq!(poller.poll(start, &mut source, &config, &mut state), PollOutcome::NotModified);
+        let early = start + Duration::from_secs(299);
+        assert_eq!(poller.poll(early, &mut source, &config, &mut state), PollOutcome::Idle);
+        let due = start + Duration::from_secs(300);
+        assert_eq!(poller.poll(due, &mut source, &config, &mut state), PollOutcome::NotModified);
+        assert_eq!(source.requests.len(), 2);
+    }
+
+    #[test]
+    fn failure_shows_error_and_retries_sooner() {
+        let config = config();
+        let mut state = LedState::new(config.num_leds(), 255);
+        let mut source = ScriptedSource::default();
+        source.results.push_back(Err("timeout".to_string()));
+        let mut poller = MetarPoller::new(&config);
+        let start = Instant::now();
+
+        let outcome = poller.poll(start, &mut source, &config, &mut state);
+        assert_eq!(outcome, PollOutcome::Failed("timeout".to_string()));
+        assert_eq!(state.get(0).unwrap(), COLOR_FETCH_ERROR);
+
+        let retry = start + RETRY_INTERVAL;
+        assert_ne!(poller.poll(retry, &mut source, &config, &mut state), PollOutcome::Idle);
+    }
+
+    #[test]
+    fn active_weather_shortens_interval_then_relaxes() {
+        let mut config = config();
+        config.settings.request_interval_secs = 900;
+        config.settings.active_interval_secs = Some(120);
+        let mut state = LedState::new(config.num_leds(), 255);
+        let mut source = ScriptedSource::default();
+        for cat in ["IFR", "VFR", "VFR", "VFR"] {
+            source
+                .results
+                .push_back(Ok(FetchResult::Updated(vec![report("KSFO", cat)])));
+        }
+        let mut poller = MetarPoller::new(&config);
+        let mut now = Instant::now();
+
+        let mut intervals = Vec::new();
+        for _ in 0..4 {
+            assert!(matches!(
+                poller.poll(now, &mut source, &config, &mut state),
+                PollOutcome::Updated(_)
+            ));
+            intervals.push(poller.current_interval().as_secs());
+            now += poller.current_interval();
+        }
+        assert_eq!(intervals, vec![120, 240, 480, 900]);
+    }
+
+    #[test]
+    fn no_active_interval_keeps_schedule() {
+        let config = config();
+        let mut state = LedState::new(config.num_leds(), 255);
+        let mut source = ScriptedSource::default();
+        source
+            .results
+            .push_back(Ok(FetchResult::Updated(vec![report("KSFO", "LIFR")])));
+        let mut poller = MetarPoller::new(&config);
+
+        poller.poll(Instant::now(), &mut source, &config, &mut state);
+        assert_eq!(poller.current_interval(), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn request_refresh_forces_fetch() {
+        let config = config();
+        let mut state = LedState::new(config.num_leds(), 255);
+        let mut source = ScriptedSource::default();
+        let mut poller = MetarPoller::new(&config);
+        let now = Instant::now();
+
+        poller.poll(now, &mut source, &config, &mut state);
+        let later = now + MIN_FETCH_SPACING;
+        assert_eq!(poller.poll(later, &mut source, &config, &mut state), PollOutcome::Idle);
+        poller.request_refresh();
+        assert_eq!(poller.poll(later, &mut source, &config, &mut state), PollOutcome::NotModified);
+    }
+
+    #[test]
+    fn request_refresh_respects_min_spacing() {
+        let config = config();
+        let mut state = LedState::new(config.num_leds(), 255);
+        let mut source = ScriptedSource::default();
+        let mut poller = MetarPoller::new(&config);
+        let now = Instant::now();
+
+        poller.poll(now, &mut source, &config, &mut state);
+        poller.request_refresh();
+        let soon = now + Duration::from_secs(5);
+        assert_eq!(poller.poll(soon, &mut source, &config, &mut state), PollOutcome::Idle);
+        assert_eq!(source.requests.len(), 1);
+    }
+
+    #[test]
+    fn reconfigure_resizes_and_refetches() {
+        let config = config();
+        let mut state = LedState::new(config.num_leds(), 255);
+        let mut source = ScriptedSource::default();
+        let mut poller = MetarPoller::new(&config);
+        let now = Instant::now();
+        poller.poll(now, &mut source, &config, &mut state);
+
+        let mut reloaded = Config::from_toml(
+            r#"
+[settings]
+brightness = 40
+request_interval_secs = 600
+request_jitter_secs = 0
+
+[[airports]]
+code = "KSFO"
+
+[[airports]]
+code = "KOAK"
+
+[[airports]]
+code = "NULL"
+"#,
+        )
+        .unwrap();
+        state.set_display_on(false);
+        poller.reconfigure(&reloaded, &mut state);
+        assert_eq!(state.num_leds(), 3);
+        assert_eq!(state.brightness(), 40);
+        // A display switched off stays off
+        assert!(!state.display_on());
+
+        let later = now + MIN_FETCH_SPACING;
+        poller.poll(later, &mut source, &reloaded, &mut state);
+        assert_eq!(source.requests[1], vec!["KSFO".to_string(), "KOAK".to_string()]);
+        // The cache is dropped once, with the fetch after the reload
+        assert_eq!(source.invalidations, 1);
+
+        // Same LED count keeps the buffer and only changes brightness
+        reloaded.settings.brightness = 10;
+        state.set(0, COLOR_IFR).unwrap();
+        poller.reconfigure(&reloaded, &mut state);
+        assert_eq!(state.get(0).unwrap(), COLOR_IFR);
+        assert_eq!(state.brightness(), 10);
+
+        let next = later + Duration::from_secs(599);
+        poller.poll(later + MIN_FETCH_SPACING, &mut source, &reloaded, &mut state);
+        assert_eq!(poller.poll(next, &mut source, &reloaded, &mut state), PollOutcome::Idle);
+    }
+
+    #[test]
+    fn fetches_can_finish_later() {
+        let config = config();
+        let mut state = LedState::new(config.num_leds(), 255);
+        let mut source = ScriptedSource::default();
+        source
+            .results
+            .push_back(Ok(FetchResult::Updated(vec![report("KSFO", "IFR")])));
+        let mut poller = MetarPoller::new(&config);
+        let now = Instant::now();
+
+        let job = poller.start_fetch(now, &config).unwrap();
+        // Only one job at a time
+        assert_eq!(poller.start_fetch(now + Duration::from_secs(900), &config), None);
+        let response = job.run(&mut source);
+        let outcome = poller.finish_fetch(response, &config, &mut state);
+        assert!(matches!(outcome, PollOutcome::Updated(_)));
+        assert_eq!(state.get(0).unwrap(), COLOR_IFR);
+        // Scheduled from when the fetch started
+        assert_eq!(poller.next_fetch(), Some(now + Duration::from_secs(300)));
+
+        // A reload while a job is out drops its response
+        let later = now + Duration::from_secs(300);
+        let stale = poller.start_fetch(later, &config).unwrap().run(&mut source);
+        poller.reconfigure(&config, &mut state);
+        state.set(0, COLOR_VFR).unwrap();
+        assert_eq!(poller.finish_fetch(stale, &config, &mut state), PollOutcome::Idle);
+        assert_eq!(state.get(0).unwrap(), COLOR_VFR);
+        let fresh = poller.start_fetch(later + MIN_FETCH_SPACING, &config).unwrap();
+        assert!(fresh.fresh);
+    }
+
+    #[test]
+    fn jitter_delays_startup_and_intervals() {
+        let mut config = config();
+        config.settings.request_jitter_secs = 60;
+        let mut state = LedState::new(config.num_leds(), 255);
+        let mut source = ScriptedSource::default();
+        let start = Instant::now();
+
+        // Different seeds should produce different startup delays
+        let first_fetch = |seed: u64, source: &mut ScriptedSource, state: &mut LedState| {
+            let mut poller = MetarPoller::with_seed(&config, seed);
+            (0..=MAX_STARTUP_DELAY.as_secs())
+                .find(|&s| {
+                    let t = start + Duration::from_secs(s);
+                    poller.poll(t, source, &config, state) != PollOutcome::Idle
+                })
+                .expect("first fetch within the startup window")
+        };
+        let delays: Vec<u64> = (1..=8)
+            .map(|seed| first_fetch(seed, &mut source, &mut state))
+            .collect();
+        assert!(delays.iter().any(|&d| d != delays[0]), "delays: {delays:?}");
+
+        // Intervals stay within [interval, interval + jitter]
+        let mut poller = MetarPoller::with_seed(&config, 42);
+        poller.request_refresh();
+        poller.poll(start, &mut source, &config, &mut state);
+        let before = start + Duration::from_secs(299);
+        assert_eq!(poller.poll(before, &mut source, &config, &mut state), PollOutcome::Idle);
+        let after = start + Duration::from_secs(361);
+        assert_ne!(poller.poll(after, &mut source, &config, &mut state), PollOutcome::Idle);
+    }
+
+    #[test]
+    fn winds_aloft_mode_uses_winds_source() {
+        let mut config = config();
+        config.settings.display_mode = DisplayMode::WindsAloft;
+        let mut state = LedState::new(config.num_leds(), 255);
+        let mut source = ScriptedSource::default();
+        let mut poller = MetarPoller::new(&config);
+
+        let outcome = poller.poll(Instant::now(), &mut source, &config, &mut state);
+
+        assert_eq!(outcome, PollOutcome::Failed("winds unavailable".to_string()));
+        assert!(source.requests.is_empty());
+    }
+}
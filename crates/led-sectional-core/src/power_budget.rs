@@ -0,0 +1,88 @@
+//! Estimated strip current, and dimming frames to fit `max_current_ma`, so
+//! a full-white lightning flash can't pull the supply down far enough to
+//! brown out the board mid-update. The estimate is the usual WS2812B rule of
+//! thumb: about 20 mA per color channel at full, plus about 1 mA per LED
+//! for its controller even when dark.
+
+use crate::led::Color;
+
+/// Per color channel at 255.
+pub const CHANNEL_MA: u32 = 20;
+/// Each LED's controller, lit or not.
+pub const IDLE_MA: u32 = 1;
+/// Kept back from `max_current_ma` for the board itself, WiFi transmit
+/// peaks included.
+pub const BOARD_MA: u32 = 300;
+
+/// Roughly what the strip draws showing `frame`, in mA.
+pub fn estimate_ma(frame: &[Color]) -> u32 {
+    let idle = frame.len() as u32 * IDLE_MA;
+    idle + lit_ma(frame)
+}
+
+/// The part of [`estimate_ma`] that scales with brightness.
+fn lit_ma(frame: &[Color]) -> u32 {
+    levels(frame) * CHANNEL_MA / 255
+}
+
+/// Every channel level in `frame`, added up.
+fn levels(frame: &[Color]) -> u32 {
+    frame.iter().map(|c| c.r as u32 + c.g as u32 + c.b as u32).sum()
+}
+
+/// The strip's share of a supply rated `max_current_ma`.
+pub fn strip_budget_ma(max_current_ma: u32) -> u32 {
+    max_current_ma.saturating_sub(BOARD_MA)
+}
+
+/// Dim `frame` evenly until it fits in a supply rated `max_current_ma`.
+/// Returns what it would have drawn if it had to be dimmed.
+pub fn fit(frame: &mut [Color], max_current_ma: u32) -> Option<u32> {
+    let estimate = estimate_ma(frame);
+    let budget = strip_budget_ma(max_current_ma);
+    if estimate <= budget {
+        return None;
+    }
+    let total = levels(frame) as u64;
+    if total == 0 {
+        // Dark already; nothing left to dim
+        return Some(estimate);
+    }
+    let allowed_ma = budget.saturating_sub(frame.len() as u32 * IDLE_MA) as u64;
+    let allowed = allowed_ma * 255 / CHANNEL_MA as u64;
+    // Rounding down keeps the dimmed frame within the budget
+    let scale = |level: u8| (level as u64 * allowed / total) as u8;
+    for c in frame.iter_mut() {
+        *c = Color::new(scale(c.r), scale(c.g), scale(c.b));
+    }
+    Some(estimate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white_strip_is_dimmed_to_the_budget() {
+        // 100 LEDs at full white: 100 x 61 mA
+        let mut frame = vec![Color::new(255, 255, 255); 100];
+        assert_eq!(estimate_ma(&frame), 6_100);
+        assert_eq!(fit(&mut frame, 2_000), Some(6_100));
+        assert!(estimate_ma(&frame) <= strip_budget_ma(2_000));
+        assert!(estimate_ma(&frame) > strip_budget_ma(2_000) - 100);
+        // Colors keep their balance
+        assert!(frame.iter().all(|c| c.r == c.g && c.g == c.b));
+    }
+
+    #[test]
+    fn frames_within_budget_are_untouched() {
+        let mut frame = vec![Color::new(0, 64, 0); 100];
+        let before = frame.clone();
+        assert_eq!(fit(&mut frame, 2_000), None);
+        assert_eq!(frame, before);
+        // Even dark LEDs draw something; going dark is all a tiny budget gets
+        let mut frame = vec![Color::new(255, 0, 0); 10];
+        assert_eq!(fit(&mut frame, 305), Some(210));
+        assert_eq!(frame[0], Color::new(0, 0, 0));
+    }
+}
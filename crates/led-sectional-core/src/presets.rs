@@ -0,0 +1,89 @@
+//! Regional airport lists built into the firmware, so a new map works before
+//! it has been customized. Select one with `preset = "<id>"` in `[settings]`.
+
+/// A named airport list, one LED per entry in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preset {
+    /// Short identifier used in config, e.g. `pnw`.
+    pub id: &'static str,
+    /// Human-readable name for the provisioning UI.
+    pub name: &'static str,
+    pub airports: &'static [&'static str],
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        id: "pnw",
+        name: "Pacific Northwest",
+        airports: &[
+            "KBLI", "KPAE", "KAWO", "KBFI", "KSEA", "KRNT", "KTIW", "KPWT", "KOLM", "KHQM",
+            "KAST", "KKLS", "KPDX", "KHIO", "KTTD", "KSLE", "KCVO", "KEUG", "KELN", "KYKM",
+            "KEAT", "KPSC", "KALW", "KGEG",
+        ],
+    },
+    Preset {
+        id: "new_england",
+        name: "New England",
+        airports: &[
+            "KBTV", "KMPV", "KRUT", "KLEB", "KCON", "KMHT", "KPSM", "KPWM", "KAUG", "KBGR",
+            "KRKD", "KEEN", "KORH", "KBED", "KBOS", "KOWD", "KPVC", "KHYA", "KACK", "KMVY",
+            "KEWB", "KPVD", "KGON", "KHFD", "KBDL", "KBAF",
+        ],
+    },
+    Preset {
+        id: "bay_area",
+        name: "San Francisco Bay Area",
+        airports: &[
+            "KSTS", "KDVO", "KAPC", "KCCR", "KOAK", "KSFO", "KHAF", "KSQL", "KPAO", "KNUQ",
+            "KSJC", "KRHV", "KHWD", "KLVK", "KTCY", "KSCK", "KMRY", "KSNS", "KWVI",
+        ],
+    },
+    Preset {
+        id: "socal",
+        name: "Southern California",
+        airports: &[
+            "KSBA", "KOXR", "KCMA", "KVNY", "KWHP", "KBUR", "KSMO", "KLAX", "KHHR", "KTOA",
+            "KLGB", "KSNA", "KFUL", "KPOC", "KEMT", "KONT", "KRAL", "KPSP", "KCRQ", "KMYF",
+            "KSAN", "KSEE",
+        ],
+    },
+    Preset {
+        id: "front_range",
+        name: "Colorado Front Range",
+        airports: &[
+            "KFNL", "KGXY", "KLMO", "KBJC", "KDEN", "KAPA", "KBKF", "KEIK", "KFTG", "KCOS",
+            "KFLY", "KPUB",
+        ],
+    },
+];
+
+/// Look up a preset by its id.
+pub fn find_preset(id: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|p| p.id == id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::is_special_code;
+
+    #[test]
+    fn find_known_preset() {
+        let pnw = find_preset("pnw").unwrap();
+        assert_eq!(pnw.name, "Pacific Northwest");
+        assert!(pnw.airports.contains(&"KSEA"));
+        assert!(find_preset("atlantis").is_none());
+    }
+
+    #[test]
+    fn presets_are_well_formed() {
+        for (i, preset) in PRESETS.iter().enumerate() {
+            assert!(PRESETS[..i].iter().all(|p| p.id != preset.id), "{}", preset.id);
+            for (j, code) in preset.airports.iter().enumerate() {
+                assert_eq!(code.len(), 4, "{code}");
+                assert!(!is_special_code(code), "{code}");
+                assert!(!preset.airports[..j].contains(code), "{} repeats {code}", preset.id);
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Boot-loop detection. Crashed boots are counted in NVS; after
+//! [`CRASH_LIMIT`] in a row, none of them proving [`Stability`], the
+//! firmware starts in safe mode: the embedded default config, no
+//! animations, and the setup AP up, so a stored config or airport list that
+//! crashes it can be fixed or factory reset from the web UI.
+//!
+//! Safe mode lasts until a reset that isn't a crash, such as a power cycle
+//! or the restart after a factory reset or update. That boot tries the
+//! stored config again, and one more crash goes straight back to safe mode.
+
+use std::time::Duration;
+
+use crate::crash::is_crash;
+
+/// Crashed boots in a row before safe mode.
+pub const CRASH_LIMIT: u8 = 3;
+/// How long a boot must run to count as stable, clearing the count.
+pub const STABLE_AFTER: Duration = Duration::from_secs(600);
+
+/// Whether this boot has proven the config: it has run for
+/// [`STABLE_AFTER`], or fetched weather. A map that deep-sleeps between
+/// fetches never stays up that long, but fetches on every wake.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stability {
+    fetched: bool,
+    reported: bool,
+}
+
+impl Stability {
+    /// A fetch got an answer from the weather service.
+    pub fn fetched(&mut self) {
+        self.fetched = true;
+    }
+
+    /// True once, when the boot first counts as stable `uptime` after it
+    /// started.
+    pub fn take_stable(&mut self, uptime: Duration) -> bool {
+        if self.reported || !(self.fetched || uptime >= STABLE_AFTER) {
+            return false;
+        }
+        self.reported = true;
+        true
+    }
+}
+
+/// Boots in a row that ended in a crash, as kept across resets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CrashCount(pub u8);
+
+impl CrashCount {
+    /// The count for this boot, given why the chip reset (as in
+    /// [`crate::api::DeviceHealth`]).
+    pub fn after_reset(self, reset_reason: &str) -> Self {
+        if is_crash(reset_reason) {
+            Self(self.0.saturating_add(1))
+        } else if self.is_safe_mode() {
+            // Deliberate restart out of safe mode: one more try
+            Self(CRASH_LIMIT - 1)
+        } else {
+            self
+        }
+    }
+
+    /// Whether to start in safe mode.
+    pub fn is_safe_mode(self) -> bool {
+        self.0 >= CRASH_LIMIT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_mode_after_repeated_crashes() {
+        let mut count = CrashCount::default();
+        for _ in 0..CRASH_LIMIT - 1 {
+            count = count.after_reset("panic");
+            assert!(!count.is_safe_mode());
+        }
+        // Power cycles in between don't clear the count
+        count = count.after_reset("power_on");
+        count = count.after_reset("task_watchdog");
+        assert!(count.is_safe_mode());
+        assert!(count.after_reset("panic").is_safe_mode());
+    }
+
+    #[test]
+    fn restarting_out_of_safe_mode_tries_once_more() {
+        let count = CrashCount(CRASH_LIMIT).after_reset("software");
+        assert!(!count.is_safe_mode());
+        assert!(count.after_reset("panic").is_safe_mode());
+        assert_eq!(CrashCount(255).after_reset("panic"), CrashCount(255));
+    }
+
+    #[test]
+    fn long_uptime_is_stable_once() {
+        let mut stability = Stability::default();
+        assert!(!stability.take_stable(Duration::from_secs(300)));
+        assert!(stability.take_stable(STABLE_AFTER));
+        assert!(!stability.take_stable(STABLE_AFTER * 2));
+    }
+
+    #[test]
+    fn sleep_wakes_are_stable_after_a_fetch() {
+        // A timer wake is up for seconds, never STABLE_AFTER
+        let mut wake = Stability::default();
+        assert!(!wake.take_stable(Duration::from_secs(8)));
+        wake.fetched();
+        assert!(wake.take_stable(Duration::from_secs(9)));
+        assert!(!wake.take_stable(Duration::from_secs(10)));
+
+        // Waking from sleep isn't a crash, so it doesn't add to the count
+        let count = CrashCount(CRASH_LIMIT - 1).after_reset("deep_sleep");
+        assert_eq!(count, CrashCount(CRASH_LIMIT - 1));
+    }
+}
@@ -0,0 +1,159 @@
+//! Obfuscation for secrets at rest, such as the saved WiFi passwords in NVS.
+//!
+//! Text is encrypted with ChaCha20 under a key derived from a device
+//! identifier (the eFuse MAC on the ESP32), so the passwords don't show up
+//! in a flash dump and a copied NVS partition is useless on another board.
+//! Anyone who knows the MAC can derive the key, so this is not a substitute
+//! for flash encryption.
+
+use crate::config_layer::checksum;
+use crate::error::{Error, Result};
+
+/// Marks sealed data, and the format version.
+const MAGIC: &[u8; 4] = b"LSX1";
+pub const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + NONCE_LEN + 4;
+
+/// Fixed key for deriving device keys; only the device id varies.
+const DERIVATION_KEY: &[u8; 32] = b"led-sectional credential seal v1";
+
+/// The sealing key for the device identified by `device_id`.
+pub fn device_key(device_id: &[u8]) -> [u8; 32] {
+    let mut nonce = [0u8; NONCE_LEN];
+    for (n, b) in nonce.iter_mut().zip(device_id) {
+        *n = *b;
+    }
+    let block = chacha20_block(DERIVATION_KEY, 0, &nonce);
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&block[..32]);
+    key
+}
+
+/// Whether `bytes` look like the output of [`seal`], as opposed to older
+/// plaintext.
+pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Encrypt `text`. `nonce` must not repeat for the same key; use random
+/// bytes.
+pub fn seal(key: &[u8; 32], nonce: [u8; NONCE_LEN], text: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + text.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&checksum(text).to_le_bytes());
+    let start = out.len();
+    out.extend_from_slice(text.as_bytes());
+    chacha20_xor(key, &nonce, &mut out[start..]);
+    out
+}
+
+/// Decrypt the output of [`seal`]. Fails if it's truncated, or if it was
+/// sealed with a different key, which shows up as a checksum mismatch.
+pub fn open(key: &[u8; 32], sealed: &[u8]) -> Result<String> {
+    if !is_sealed(sealed) || sealed.len() < HEADER_LEN {
+        return Err(Error::Unseal("is not in the sealed format"));
+    }
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce.copy_from_slice(&sealed[MAGIC.len()..MAGIC.len() + NONCE_LEN]);
+    let mut crc = [0u8; 4];
+    crc.copy_from_slice(&sealed[MAGIC.len() + NONCE_LEN..HEADER_LEN]);
+
+    let mut text = sealed[HEADER_LEN..].to_vec();
+    chacha20_xor(key, &nonce, &mut text);
+    let text = String::from_utf8(text).map_err(|_| Error::Unseal("was sealed with another key"))?;
+    if checksum(&text) != u32::from_le_bytes(crc) {
+        return Err(Error::Unseal("was sealed with another key"));
+    }
+    Ok(text)
+}
+
+/// XOR `data` with the ChaCha20 (RFC 8439) keystream, starting at block 1.
+fn chacha20_xor(key: &[u8; 32], nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        let block = chacha20_block(key, i as u32 + 1, nonce);
+        for (b, k) in chunk.iter_mut().zip(block) {
+            *b ^= k;
+        }
+    }
+}
+
+fn chacha20_block(key: &[u8; 32], counter: u32, nonce: &[u8; NONCE_LEN]) -> [u8; 64] {
+    let word = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&[0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574]);
+    for i in 0..8 {
+        state[4 + i] = word(&key[i * 4..]);
+    }
+    state[12] = counter;
+    for i in 0..3 {
+        state[13 + i] = word(&nonce[i * 4..]);
+    }
+
+    let mut x = state;
+    let quarter = |x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize| {
+        x[a] = x[a].wrapping_add(x[b]);
+        x[d] = (x[d] ^ x[a]).rotate_left(16);
+        x[c] = x[c].wrapping_add(x[d]);
+        x[b] = (x[b] ^ x[c]).rotate_left(12);
+        x[a] = x[a].wrapping_add(x[b]);
+        x[d] = (x[d] ^ x[a]).rotate_left(8);
+        x[c] = x[c].wrapping_add(x[d]);
+        x[b] = (x[b] ^ x[c]).rotate_left(7);
+    };
+    for _ in 0..10 {
+        quarter(&mut x, 0, 4, 8, 12);
+        quarter(&mut x, 1, 5, 9, 13);
+        quarter(&mut x, 2, 6, 10, 14);
+        quarter(&mut x, 3, 7, 11, 15);
+        quarter(&mut x, 0, 5, 10, 15);
+        quarter(&mut x, 1, 6, 11, 12);
+        quarter(&mut x, 2, 7, 8, 13);
+        quarter(&mut x, 3, 4, 9, 14);
+    }
+
+    let mut out = [0u8; 64];
+    for (i, chunk) in out.chunks_mut(4).enumerate() {
+        chunk.copy_from_slice(&x[i].wrapping_add(state[i]).to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chacha20_matches_rfc_8439() {
+        // Section 2.3.2 test vector
+        let key: Vec<u8> = (0..32).collect();
+        let nonce = [0, 0, 0, 9, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+        let block = chacha20_block(key.as_slice().try_into().unwrap(), 1, &nonce);
+        assert_eq!(&block[..8], &[0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15]);
+        assert_eq!(&block[56..], &[0xcb, 0xd0, 0x83, 0xe8, 0xa2, 0x50, 0x3c, 0x4e]);
+    }
+
+    #[test]
+    fn seal_round_trips_and_hides_text() {
+        let key = device_key(&[0x24, 0x0a, 0xc4, 0x12, 0x3f, 0x2a]);
+        let text = "[[networks]]\nssid = \"Home\"\npassword = \"hunter22\"\n".repeat(3);
+        let sealed = seal(&key, [7; NONCE_LEN], &text);
+
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.windows(8).any(|w| w == b"hunter22"));
+        assert_eq!(open(&key, &sealed).unwrap(), text);
+    }
+
+    #[test]
+    fn open_rejects_other_keys_and_plaintext() {
+        let key = device_key(&[1, 2, 3, 4, 5, 6]);
+        let other = device_key(&[1, 2, 3, 4, 5, 7]);
+        assert_ne!(key, other);
+
+        let sealed = seal(&key, [0; NONCE_LEN], "password = \"hunter22\"");
+        assert!(matches!(open(&other, &sealed), Err(Error::Unseal(_))));
+        assert!(open(&key, &sealed[..10]).is_err());
+        assert!(!is_sealed(b"[[networks]]"));
+        assert!(open(&key, b"[[networks]]").is_err());
+    }
+}
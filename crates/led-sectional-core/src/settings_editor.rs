@@ -0,0 +1,200 @@
+//! The display settings that can be changed live from the web UI and API:
+//! lightning flashes, wind coloring, the wind threshold, and attract mode.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::Result;
+
+/// Highest `wind_threshold_kt` accepted, as config validation clamps it.
+pub const MAX_WIND_THRESHOLD_KT: u32 = 100;
+
+/// The live-editable settings as the running config has them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LiveSettings {
+    pub do_lightning: bool,
+    pub do_winds: bool,
+    pub wind_threshold_kt: u32,
+    pub attract_mode: bool,
+}
+
+impl LiveSettings {
+    pub fn from_config(config: &Config) -> Self {
+        let settings = &config.settings;
+        Self {
+            do_lightning: settings.do_lightning,
+            do_winds: settings.do_winds,
+            wind_threshold_kt: settings.wind_threshold_kt,
+            attract_mode: settings.attract_mode,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// A change to some of the [`LiveSettings`]; absent fields stay as they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsPatch {
+    pub do_lightning: Option<bool>,
+    pub do_winds: Option<bool>,
+    pub wind_threshold_kt: Option<u32>,
+    pub attract_mode: Option<bool>,
+}
+
+impl SettingsPatch {
+    /// Parse a JSON object such as `{"do_winds":false}`. Unknown keys and an
+    /// out-of-range threshold are refused rather than ignored or clamped.
+    pub fn parse(json: &str) -> std::result::Result<Self, String> {
+        let patch: Self = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if patch == Self::default() {
+            return Err("no settings given".to_string());
+        }
+        if patch.wind_threshold_kt.is_some_and(|kt| kt > MAX_WIND_THRESHOLD_KT) {
+            return Err(format!("wind_threshold_kt must be 0-{MAX_WIND_THRESHOLD_KT}"));
+        }
+        Ok(patch)
+    }
+}
+
+/// `config` with `patch` applied to its `[settings]`, revalidated. A
+/// profile that sets the same keys still overrides them while active.
+pub fn with_settings(config: &Config, patch: &SettingsPatch) -> Result<Config> {
+    // Round-trip so an active profile's overrides aren't written as base
+    // settings
+    let mut edited: Config = toml::from_str(&config.to_toml()?)?;
+    let settings = &mut edited.settings;
+    if let Some(v) = patch.do_lightning {
+        settings.do_lightning = v;
+    }
+    if let Some(v) = patch.do_winds {
+        settings.do_winds = v;
+    }
+    if let Some(v) = patch.wind_threshold_kt {
+        settings.wind_threshold_kt = v;
+    }
+    if let Some(v) = patch.attract_mode {
+        settings.attract_mode = v;
+    }
+    Config::from_toml(&toml::to_string(&edited)?)
+}
+
+/// The settings page, showing `config`'s current values. Saving posts the
+/// form as JSON to `/api/settings`.
+pub fn settings_page(config: &Config) -> String {
+    let live = LiveSettings::from_config(config);
+    let checked = |on: bool| if on { " checked" } else { "" };
+    HTML_SETTINGS
+        .replace("{LIGHTNING}", checked(live.do_lightning))
+        .replace("{WINDS}", checked(live.do_winds))
+        .replace("{THRESHOLD}", &live.wind_threshold_kt.to_string())
+        .replace("{MAX_THRESHOLD}", &MAX_WIND_THRESHOLD_KT.to_string())
+        .replace("{ATTRACT}", checked(live.attract_mode))
+}
+
+const HTML_SETTINGS: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width,initial-scale=1">
+<title>LED Sectional - Settings</title>
+<style>
+body{font-family:system-ui,sans-serif;background:#1a1a2e;color:#e0e0e0;margin:1rem;max-width:480px}
+h1{font-size:1.4rem;color:#a8d8ea}
+a{color:#a8d8ea}
+label{display:block;padding:.4rem 0}
+small{color:#a0a0a0}
+input[type=number]{background:#16213e;color:#e0e0e0;border:1px solid #555;border-radius:4px;padding:.3rem;width:4rem}
+button{background:#e94560;color:#e0e0e0;border:none;border-radius:4px;padding:.5rem 1rem;margin-top:1rem}
+.error{color:#e94560}
+</style>
+</head>
+<body>
+<h1>Settings</h1>
+<form onsubmit="return save()">
+<label><input type="checkbox" id="do_lightning"{LIGHTNING}> Flash airports reporting lightning</label>
+<label><input type="checkbox" id="do_winds"{WINDS}> Show windy airports in the wind color</label>
+<label>Windy above <input type="number" id="wind_threshold_kt" min="0" max="{MAX_THRESHOLD}" value="{THRESHOLD}"> kt <small>(wind or gust)</small></label>
+<label><input type="checkbox" id="attract_mode"{ATTRACT}> Attract mode <small>(demo weather instead of live)</small></label>
+<button>Save</button>
+</form>
+<p id="msg"></p>
+<p><a href="/">Back to the map</a></p>
+<script>
+function save(){
+  var m=document.getElementById('msg');m.className='';m.textContent='Saving…';
+  var body={do_lightning:document.getElementById('do_lightning').checked,
+    do_winds:document.getElementById('do_winds').checked,
+    wind_threshold_kt:parseInt(document.getElementById('wind_threshold_kt').value,10),
+    attract_mode:document.getElementById('attract_mode').checked};
+  fetch('/api/settings',{method:'POST',headers:{'Content-Type':'application/json'},body:JSON.stringify(body)})
+    .then(function(r){return r.text().then(function(t){m.textContent=t;m.className=r.ok?'':'error'})})
+    .catch(function(){m.textContent='Could not reach the map';m.className='error'});
+  return false;
+}
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patch_parses_partial_json() {
+        let patch = SettingsPatch::parse(r#"{"do_winds":false}"#).unwrap();
+        assert_eq!(patch.do_winds, Some(false));
+        assert_eq!(patch.do_lightning, None);
+        assert!(SettingsPatch::parse("{}").is_err());
+        assert!(SettingsPatch::parse(r#"{"brightness":10}"#).is_err());
+        assert!(SettingsPatch::parse(r#"{"wind_threshold_kt":101}"#).is_err());
+        assert!(SettingsPatch::parse("do_winds=off").is_err());
+    }
+
+    #[test]
+    fn with_settings_changes_only_given_fields() {
+        let config = Config::builder().airport("KSFO").build().unwrap();
+        let before = LiveSettings::from_config(&config);
+        let patch = SettingsPatch {
+            do_lightning: Some(!before.do_lightning),
+            wind_threshold_kt: Some(12),
+            attract_mode: Some(true),
+            ..Default::default()
+        };
+        let edited = with_settings(&config, &patch).unwrap();
+        let after = LiveSettings::from_config(&edited);
+        assert_eq!(after.do_lightning, !before.do_lightning);
+        assert_eq!(after.do_winds, before.do_winds);
+        assert_eq!(after.wind_threshold_kt, 12);
+        assert!(after.attract_mode);
+        assert_eq!(edited.airports[0].code, "KSFO");
+        assert_eq!(
+            after.to_json(),
+            format!(
+                r#"{{"do_lightning":{},"do_winds":{},"wind_threshold_kt":12,"attract_mode":true}}"#,
+                after.do_lightning, after.do_winds
+            )
+        );
+    }
+
+    #[test]
+    fn page_shows_current_values() {
+        let config = Config::builder().airport("KSFO").wind_threshold_kt(30).build().unwrap();
+        let page = settings_page(&config);
+        assert!(page.contains(r#"value="30""#));
+        assert!(page.contains(r#"max="100""#));
+        let placeholders = [
+            "{LIGHTNING}",
+            "{WINDS}",
+            "{THRESHOLD}",
+            "{MAX_THRESHOLD}",
+            "{ATTRACT}",
+        ];
+        for placeholder in placeholders {
+            assert!(!page.contains(placeholder), "{placeholder}");
+        }
+    }
+}
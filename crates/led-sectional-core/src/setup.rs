@@ -0,0 +1,299 @@
+//! Map settings collected by the setup portal, so a new map can be set up
+//! from a phone without editing `config.toml` by hand.
+
+use std::net::Ipv4Addr;
+
+use serde::Serialize;
+
+use crate::config::{is_special_code, looks_like_station_id, Airport, Config};
+use crate::error::Result;
+use crate::presets;
+
+/// Where the map's airport list comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AirportChoice {
+    /// Leave the configured airports alone.
+    Keep,
+    /// A built-in list from [`crate::presets`], by id.
+    Preset(String),
+    /// These codes, one LED each in order.
+    Custom(Vec<String>),
+}
+
+/// The map fields of the setup form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetupChoices {
+    /// 1-100, as shown on the form's slider.
+    pub brightness_percent: u8,
+    pub airports: AirportChoice,
+    pub do_lightning: bool,
+    pub do_winds: bool,
+    pub do_fog_risk: bool,
+}
+
+impl SetupChoices {
+    /// The form's starting values: what `config` does now.
+    pub fn from_config(config: &Config) -> Self {
+        let settings = &config.settings;
+        Self {
+            brightness_percent: level_to_percent(settings.brightness),
+            airports: match &settings.preset {
+                Some(id) => AirportChoice::Preset(id.clone()),
+                None => AirportChoice::Keep,
+            },
+            do_lightning: settings.do_lightning,
+            do_winds: settings.do_winds,
+            do_fog_risk: settings.do_fog_risk,
+        }
+    }
+
+    /// Read the map fields from decoded form `fields`. Unticked checkboxes
+    /// are absent from a submitted form, so a missing toggle means off.
+    pub fn from_form<'a>(
+        fields: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> std::result::Result<Self, String> {
+        let mut choices = Self {
+            brightness_percent: 100,
+            airports: AirportChoice::Keep,
+            do_lightning: false,
+            do_winds: false,
+            do_fog_risk: false,
+        };
+        let mut source = "";
+        let mut list = "";
+        for (key, value) in fields {
+            match key {
+                "brightness" => {
+                    choices.brightness_percent = value
+                        .trim()
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|p| (1..=100).contains(p))
+                        .ok_or_else(|| format!("brightness \"{value}\" is not 1-100%"))?;
+                }
+                "preset" => source = value,
+                "airport_list" => list = value,
+                "do_lightning" => choices.do_lightning = true,
+                "do_winds" => choices.do_winds = true,
+                "do_fog_risk" => choices.do_fog_risk = true,
+                _ => {}
+            }
+        }
+        choices.airports = match source {
+            "" => AirportChoice::Keep,
+            "custom" => AirportChoice::Custom(parse_airport_list(list)?),
+            id if presets::find_preset(id).is_some() => AirportChoice::Preset(id.to_string()),
+            id => return Err(format!("unknown airport map \"{id}\"")),
+        };
+        Ok(choices)
+    }
+
+    /// `base` with these choices applied, as the TOML to write to
+    /// `/config.toml`. WiFi credentials are left out; they're stored
+    /// separately.
+    pub fn apply(&self, base: &Config) -> Result<String> {
+        // Round-trip so an active profile's overrides aren't written as
+        // base settings
+        let mut config: Config = toml::from_str(&base.to_toml()?)?;
+        let settings = &mut config.settings;
+        settings.brightness = percent_to_level(self.brightness_percent);
+        settings.do_lightning = self.do_lightning;
+        settings.do_winds = self.do_winds;
+        settings.do_fog_risk = self.do_fog_risk;
+        match &self.airports {
+            AirportChoice::Keep => {}
+            AirportChoice::Preset(id) => settings.preset = Some(id.clone()),
+            AirportChoice::Custom(codes) => {
+                settings.preset = None;
+                config.airports = codes
+                    .iter()
+                    .map(|code| Airport {
+                        code: code.clone(),
+                        ..Default::default()
+                    })
+                    .collect();
+            }
+        }
+
+        let wifi = &mut config.wifi;
+        wifi.ssid = None;
+        wifi.password = None;
+        wifi.auth = None;
+        wifi.username = None;
+        wifi.identity = None;
+        wifi.bssid = None;
+        wifi.hidden = false;
+
+        let toml = toml::to_string(&config)?;
+        Config::from_toml(&toml)?;
+        Ok(toml)
+    }
+}
+
+/// Split a typed airport list on commas and whitespace. Codes are
+/// uppercased; anything that isn't a station id or special code is rejected.
+pub fn parse_airport_list(text: &str) -> std::result::Result<Vec<String>, String> {
+    let codes: Vec<String> = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|code| !code.is_empty())
+        .map(str::to_ascii_uppercase)
+        .collect();
+    if let Some(bad) = codes
+        .iter()
+        .find(|code| !looks_like_station_id(code) && !is_special_code(code))
+    {
+        return Err(format!("\"{bad}\" is not an airport code"));
+    }
+    if codes.is_empty() {
+        return Err("enter at least one airport code".to_string());
+    }
+    Ok(codes)
+}
+
+/// How far the network submitted from the setup form has got, for the
+/// progress page to poll while the device tries it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum AttemptProgress {
+    /// Nothing submitted yet.
+    #[default]
+    Idle,
+    Connecting {
+        ssid: String,
+    },
+    Connected {
+        ssid: String,
+        ip: Ipv4Addr,
+    },
+    Failed {
+        ssid: String,
+        error: String,
+    },
+}
+
+impl AttemptProgress {
+    /// The progress as a JSON object with a `state` field.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+fn percent_to_level(percent: u8) -> u8 {
+    (percent.min(100) as u32 * 255 / 100) as u8
+}
+
+fn level_to_percent(level: u8) -> u8 {
+    ((level as u32 * 100 + 127) / 255).max(1) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"
+[settings]
+brightness = 40
+do_lightning = true
+
+[wifi]
+ssid = "Home"
+password = "hunter22"
+hostname = "den-map"
+
+[[airports]]
+code = "KSFO"
+"#;
+
+    fn form(fields: &[(&'static str, &'static str)]) -> SetupChoices {
+        SetupChoices::from_form(fields.iter().copied()).unwrap()
+    }
+
+    #[test]
+    fn form_fields_become_choices() {
+        let choices = form(&[
+            ("ssid", "Home"),
+            ("brightness", "50"),
+            ("preset", "custom"),
+            ("airport_list", "ksea, KBFI\nVFR"),
+            ("do_winds", "on"),
+        ]);
+        assert_eq!(choices.brightness_percent, 50);
+        assert_eq!(
+            choices.airports,
+            AirportChoice::Custom(vec!["KSEA".into(), "KBFI".into(), "VFR".into()])
+        );
+        assert!(choices.do_winds);
+        assert!(!choices.do_lightning);
+
+        assert!(SetupChoices::from_form([("brightness", "0")]).is_err());
+        assert!(SetupChoices::from_form([("preset", "atlantis")]).is_err());
+        assert!(SetupChoices::from_form([("preset", "custom"), ("airport_list", " ")]).is_err());
+    }
+
+    #[test]
+    fn airport_list_rejects_bad_codes() {
+        assert_eq!(parse_airport_list("kpdx khio").unwrap(), vec!["KPDX", "KHIO"]);
+        let err = parse_airport_list("KPDX, Portland").unwrap_err();
+        assert!(err.contains("PORTLAND"), "{err}");
+    }
+
+    #[test]
+    fn apply_writes_complete_config_without_credentials() {
+        let base = Config::from_toml(BASE).unwrap();
+        let choices = form(&[
+            ("brightness", "100"),
+            ("preset", "custom"),
+            ("airport_list", "KOAK KSJC"),
+            ("do_fog_risk", "on"),
+        ]);
+        let toml = choices.apply(&base).unwrap();
+        let written = Config::from_toml(&toml).unwrap();
+
+        assert_eq!(written.settings.brightness, 255);
+        assert!(!written.settings.do_lightning);
+        assert!(written.settings.do_fog_risk);
+        let codes: Vec<&str> = written.airports.iter().map(|a| a.code.as_str()).collect();
+        assert_eq!(codes, ["KOAK", "KSJC"]);
+        assert_eq!(written.wifi.hostname(), "den-map");
+        assert!(written.wifi.ssid.is_none());
+        assert!(!toml.contains("hunter22"));
+    }
+
+    #[test]
+    fn from_config_round_trips_through_apply() {
+        let base = Config::from_toml(BASE).unwrap();
+        let choices = SetupChoices::from_config(&base);
+        assert_eq!(choices.brightness_percent, 16);
+        assert_eq!(choices.airports, AirportChoice::Keep);
+
+        let preset = SetupChoices {
+            airports: AirportChoice::Preset("pnw".into()),
+            ..choices
+        };
+        let written = Config::from_toml(&preset.apply(&base).unwrap()).unwrap();
+        assert_eq!(written.settings.preset.as_deref(), Some("pnw"));
+        assert_eq!(written.settings.brightness, 40);
+        assert_eq!(written.num_leds(), presets::find_preset("pnw").unwrap().airports.len());
+    }
+
+    #[test]
+    fn attempt_progress_json() {
+        assert_eq!(AttemptProgress::Idle.to_json(), r#"{"state":"idle"}"#);
+        let connected = AttemptProgress::Connected {
+            ssid: "Home \"5G\"".into(),
+            ip: Ipv4Addr::new(192, 168, 1, 40),
+        };
+        assert_eq!(
+            connected.to_json(),
+            r#"{"state":"connected","ssid":"Home \"5G\"","ip":"192.168.1.40"}"#
+        );
+        let failed = AttemptProgress::Failed {
+            ssid: "Home".into(),
+            error: "wrong password".into(),
+        };
+        assert_eq!(
+            failed.to_json(),
+            r#"{"state":"failed","ssid":"Home","error":"wrong password"}"#
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use crate::metar::MetarReport;
+use crate::winds_aloft::StationWinds;
+
+/// Outcome of a successful METAR fetch.
+#[derive(Debug, Clone)]
+pub enum FetchResult {
+    Updated(Vec<MetarReport>),
+    /// The source reports that the previously fetched data is still current.
+    NotModified,
+}
+
+/// A source of weather data: the HTTPS client on the device, or a fake on the host.
+pub trait MetarSource {
+    type Error: std::fmt::Display;
+
+    /// Fetch METAR reports for the given station codes.
+    fn fetch(&mut self, codes: &[&str]) -> Result<FetchResult, Self::Error>;
+
+    /// Fetch the low-level winds-aloft forecast for the given period in hours.
+    fn fetch_winds_aloft(
+        &mut self,
+        forecast_hours: u8,
+    ) -> Result<HashMap<String, StationWinds>, Self::Error>;
+
+    /// Forget any cached validators so the next fetch returns full data, e.g.
+    /// after a config reload that needs every LED repainted.
+    fn invalidate_cache(&mut self) {}
+}
+
+/// A source that always returns the same canned data. Useful for host tools,
+/// simulators, and offline demos.
+#[derive(Debug, Clone, Default)]
+pub struct StaticSource {
+    pub reports: Vec<MetarReport>,
+    pub winds: HashMap<String, StationWinds>,
+}
+
+impl StaticSource {
+    pub fn new(reports: Vec<MetarReport>) -> Self {
+        Self {
+            reports,
+            winds: HashMap::new(),
+        }
+    }
+}
+
+impl MetarSource for StaticSource {
+    type Error = Infallible;
+
+    /// Returns the canned reports for the requested stations only.
+    fn fetch(&mut self, codes: &[&str]) -> Result<FetchResult, Self::Error> {
+        Ok(FetchResult::Updated(
+            self.reports
+                .iter()
+                .filter(|r| codes.contains(&r.icao_id.as_str()))
+                .cloned()
+                .collect(),
+        ))
+    }
+
+    fn fetch_winds_aloft(
+        &mut self,
+        _forecast_hours: u8,
+    ) -> Result<HashMap<String, StationWinds>, Self::Error> {
+        Ok(self.winds.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(icao: &str) -> MetarReport {
+        MetarReport {
+            icao_id: icao.to_string(),
+            flt_cat: Some("VFR".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn static_source_filters_requested_codes() {
+        let mut source = StaticSource::new(vec![report("KSFO"), report("KLAX")]);
+        let Ok(FetchResult::Updated(reports)) = source.fetch(&["KLAX", "KJFK"]) else {
+            panic!("static source should always return reports");
+        };
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].icao_id, "KLAX");
+    }
+
+    #[test]
+    fn static_source_winds() {
+        let mut source = StaticSource::default();
+        let Ok(winds) = source.fetch_winds_aloft(6);
+        assert!(winds.is_empty());
+    }
+}
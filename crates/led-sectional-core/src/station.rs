@@ -0,0 +1,113 @@
+use serde::Deserialize;
+
+use crate::error::Result;
+
+const STATION_INFO_BASE_URL: &str = "https://aviationweather.gov/api/data/stationinfo?format=json";
+
+/// Mean Earth radius in nautical miles.
+const EARTH_RADIUS_NM: f64 = 3440.065;
+
+/// Station metadata from the aviationweather.gov `stationinfo` endpoint.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StationInfo {
+    pub icao_id: String,
+    pub site: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl StationInfo {
+    /// Great-circle distance to another station in nautical miles.
+    pub fn distance_nm(&self, other: &StationInfo) -> f64 {
+        distance_nm(self.lat, self.lon, other.lat, other.lon)
+    }
+}
+
+/// Parse a JSON string containing an array of station info records.
+pub fn parse_station_info(json: &str) -> Result<Vec<StationInfo>> {
+    let stations: Vec<StationInfo> = serde_json::from_str(json)?;
+    Ok(stations)
+}
+
+/// Build the station info URL for the given airport codes.
+pub fn build_station_info_url(codes: &[&str]) -> String {
+    format!("{STATION_INFO_BASE_URL}&ids={}", codes.join(","))
+}
+
+/// Build a station info URL covering a bounding box around a center point.
+pub fn build_station_bbox_url(lat: f64, lon: f64, radius_nm: f64) -> String {
+    let dlat = radius_nm / 60.0;
+    let dlon = radius_nm / (60.0 * lat.to_radians().cos().max(0.01));
+    format!(
+        "{STATION_INFO_BASE_URL}&bbox={:.2},{:.2},{:.2},{:.2}",
+        lat - dlat,
+        lon - dlon,
+        lat + dlat,
+        lon + dlon
+    )
+}
+
+/// Haversine distance between two coordinates in nautical miles.
+pub fn distance_nm(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let dlat = (lat2 - lat1).to_radians();
+    let dlon = (lon2 - lon1).to_radians();
+    let a = (dlat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_NM * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_JSON: &str = r#"[
+        {"icaoId": "KSFO", "site": "San Francisco Intl", "lat": 37.619, "lon": -122.375},
+        {"icaoId": "KOAK", "site": null, "lat": 37.721, "lon": -122.221}
+    ]"#;
+
+    #[test]
+    fn parse_valid_json() {
+        let stations = parse_station_info(SAMPLE_JSON).unwrap();
+        assert_eq!(stations.len(), 2);
+        assert_eq!(stations[0].icao_id, "KSFO");
+        assert_eq!(stations[0].site.as_deref(), Some("San Francisco Intl"));
+        assert!(stations[1].site.is_none());
+    }
+
+    #[test]
+    fn parse_invalid_json_errors() {
+        assert!(parse_station_info("not json").is_err());
+    }
+
+    #[test]
+    fn build_station_info_url_multiple() {
+        assert_eq!(
+            build_station_info_url(&["KSFO", "KOAK"]),
+            "https://aviationweather.gov/api/data/stationinfo?format=json&ids=KSFO,KOAK"
+        );
+    }
+
+    #[test]
+    fn build_station_bbox_url_equator() {
+        assert_eq!(
+            build_station_bbox_url(0.0, 0.0, 60.0),
+            "https://aviationweather.gov/api/data/stationinfo?format=json&bbox=-1.00,-1.00,1.00,1.00"
+        );
+    }
+
+    #[test]
+    fn distance_between_stations() {
+        let stations = parse_station_info(SAMPLE_JSON).unwrap();
+        let d = stations[0].distance_nm(&stations[1]);
+        // KSFO to KOAK is roughly 9 nm
+        assert!((d - 9.4).abs() < 0.5, "distance was {d}");
+        assert_eq!(distance_nm(10.0, 20.0, 10.0, 20.0), 0.0);
+    }
+
+    #[test]
+    fn one_degree_latitude_is_sixty_nm() {
+        let d = distance_nm(0.0, 0.0, 1.0, 0.0);
+        assert!((d - 60.0).abs() < 0.1, "distance was {d}");
+    }
+}
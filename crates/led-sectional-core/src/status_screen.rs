@@ -0,0 +1,273 @@
+//! What the optional OLED panel shows: the device's IP address, when
+//! weather was last fetched, and the home airport's raw METAR, a few lines
+//! at a time when it doesn't fit. It follows the same events as the LEDs.
+
+use std::time::Duration;
+
+use crate::clock::TimeZone;
+use crate::dashboard::{MapRow, MapSnapshot};
+use crate::events::{Event, Subscriber};
+use crate::oled::{Frame, COLUMNS, ROWS};
+use crate::wifi_link::LinkState;
+
+/// How long each part of a long METAR stays up.
+pub const ROTATE_EVERY: Duration = Duration::from_secs(4);
+/// Lines above the METAR: link, fetch, airport.
+const HEADER_LINES: usize = 3;
+/// Lines of METAR on screen at once.
+const METAR_LINES: usize = ROWS - HEADER_LINES;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+enum Fetch {
+    #[default]
+    None,
+    Done {
+        at: Option<i64>,
+        failed: bool,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct StatusScreen {
+    airport: Option<String>,
+    time_zone: TimeZone,
+    link: String,
+    fetch: Fetch,
+    /// The home airport's flight category, or why it has none.
+    category: String,
+    /// The raw METAR, wrapped to the panel width.
+    metar: Vec<String>,
+    /// The first METAR line on screen.
+    first: usize,
+    changed: bool,
+}
+
+impl StatusScreen {
+    pub fn new(airport: Option<&str>, time_zone: TimeZone) -> Self {
+        Self {
+            airport: airport.map(str::to_ascii_uppercase),
+            time_zone,
+            link: "WiFi starting".into(),
+            category: "waiting".into(),
+            changed: true,
+            ..Self::default()
+        }
+    }
+
+    /// Show another airport from the next fetch on.
+    pub fn set_airport(&mut self, airport: Option<&str>) {
+        let airport = airport.map(str::to_ascii_uppercase);
+        if airport != self.airport {
+            self.airport = airport;
+            self.category = "waiting".into();
+            self.metar.clear();
+            self.first = 0;
+            self.changed = true;
+        }
+    }
+
+    pub fn set_time_zone(&mut self, time_zone: TimeZone) {
+        if time_zone != self.time_zone {
+            self.time_zone = time_zone;
+            self.changed = true;
+        }
+    }
+
+    /// Move on to the next lines of a METAR too long to show at once, back
+    /// to its start after the end.
+    pub fn rotate(&mut self) {
+        if self.metar.len() > METAR_LINES {
+            self.first += METAR_LINES;
+            if self.first >= self.metar.len() {
+                self.first = 0;
+            }
+            self.changed = true;
+        }
+    }
+
+    /// The screen as text, a line per row.
+    pub fn lines(&self) -> Vec<String> {
+        let fetch = match &self.fetch {
+            Fetch::None => "No fetch yet".to_string(),
+            Fetch::Done { at, failed } => {
+                let when = match at {
+                    Some(secs) => {
+                        let t = self.time_zone.to_local(*secs);
+                        format!("{:02}:{:02}", t.hour, t.minute)
+                    }
+                    None => "--:--".to_string(),
+                };
+                let what = if *failed { "Fetch failed" } else { "Fetched" };
+                format!("{what} {when}")
+            }
+        };
+        let airport = match &self.airport {
+            Some(code) => format!("{code} {}", self.category),
+            None => "No home_airport".to_string(),
+        };
+        let mut lines = vec![self.link.clone(), fetch, airport];
+        lines.extend(self.metar.iter().skip(self.first).take(METAR_LINES).cloned());
+        lines
+    }
+
+    /// The picture to send to the panel, once after each change.
+    pub fn take_frame(&mut self) -> Option<Frame> {
+        std::mem::take(&mut self.changed).then(|| Frame::text(&self.lines()))
+    }
+
+    fn update(&mut self, snapshot: &MapSnapshot) {
+        self.fetch = Fetch::Done {
+            at: snapshot.fetched_at,
+            failed: snapshot.error.is_some(),
+        };
+        self.changed = true;
+        // A failed fetch leaves the last report up
+        if snapshot.error.is_some() {
+            return;
+        }
+        let Some(airport) = &self.airport else {
+            return;
+        };
+        let home = |row: &&MapRow| row.weather && row.code.eq_ignore_ascii_case(airport);
+        let row = snapshot.rows.iter().find(home);
+        let report = row.and_then(|row| row.report.as_ref());
+        self.category = match (row, report) {
+            (None, _) => "not on map".into(),
+            (Some(_), None) => "no report".into(),
+            (Some(row), Some(_)) => row.category.map_or("", |c| c.as_str()).into(),
+        };
+        let raw = report.and_then(|report| report.raw_ob.as_deref()).unwrap_or("");
+        let metar = wrap(raw, COLUMNS);
+        if metar != self.metar {
+            self.metar = metar;
+            self.first = 0;
+        }
+    }
+}
+
+impl Subscriber for StatusScreen {
+    fn on_event(&mut self, event: &Event) {
+        match event {
+            Event::MetarUpdated(snapshot) => self.update(snapshot),
+            Event::WifiStateChanged(state) => {
+                self.link = match state {
+                    LinkState::Online { ip, .. } => ip.to_string(),
+                    LinkState::Connecting { ssid, .. } => format!("WiFi: {ssid}"),
+                    LinkState::Waiting { .. } => "WiFi offline".into(),
+                };
+                self.changed = true;
+            }
+            Event::BrightnessChanged(_) => {}
+        }
+    }
+}
+
+/// Break `text` into lines of at most `width` characters, between words
+/// where it can.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        if !line.is_empty() && line.chars().count() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        while word.len() > width {
+            lines.push(word.drain(..width).collect());
+        }
+        line.extend(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::Color;
+    use crate::metar::{FlightCategory, MetarReport};
+    use std::net::Ipv4Addr;
+
+    const RAW: &str = "KSFO 141756Z 28015G22KT 10SM FEW008 BKN200 18/12 A3001 RMK AO2 SLP163 \
+                       T01780122 10183 20117 58012";
+
+    fn fetched(raw: &str) -> Event {
+        let report = MetarReport {
+            icao_id: "KSFO".into(),
+            raw_ob: Some(raw.into()),
+            ..Default::default()
+        };
+        let row = MapRow {
+            led: 0,
+            code: "KSFO".into(),
+            name: "KSFO".into(),
+            color: Color::new(0, 0, 255),
+            category: Some(FlightCategory::Mvfr),
+            weather: true,
+            fallback: None,
+            wind: None,
+            observed: None,
+            position: None,
+            report: Some(report),
+        };
+        Event::MetarUpdated(MapSnapshot {
+            rows: vec![row],
+            fetched_at: Some(1_760_464_560),
+            error: None,
+        })
+    }
+
+    #[test]
+    fn shows_address_fetch_time_and_metar() {
+        let mut screen = StatusScreen::new(Some("ksfo"), TimeZone::UTC);
+        assert_eq!(screen.lines(), ["WiFi starting", "No fetch yet", "KSFO waiting"]);
+        screen.on_event(&Event::WifiStateChanged(LinkState::Online {
+            ssid: "Hangar".into(),
+            ip: Ipv4Addr::new(192, 168, 1, 20),
+        }));
+        screen.on_event(&fetched(RAW));
+        let lines = screen.lines();
+        assert_eq!(lines[..4], ["192.168.1.20", "Fetched 17:56", "KSFO MVFR", "KSFO 141756Z"]);
+        assert_eq!(lines[4], "28015G22KT 10SM");
+        assert!(lines.iter().all(|line| line.len() <= COLUMNS));
+        assert!(screen.take_frame().is_some());
+        assert!(screen.take_frame().is_none());
+
+        screen.on_event(&Event::MetarUpdated(MapSnapshot {
+            error: Some("HTTP 503".into()),
+            ..Default::default()
+        }));
+        assert_eq!(screen.lines()[1], "Fetch failed --:--");
+        assert_eq!(screen.lines()[3], "KSFO 141756Z");
+    }
+
+    #[test]
+    fn long_metars_rotate() {
+        let mut screen = StatusScreen::new(Some("KSFO"), TimeZone::UTC);
+        screen.on_event(&fetched(RAW));
+        let first = screen.lines();
+        assert_eq!(first.len(), ROWS);
+        assert_eq!(first[7], "T01780122 10183 20117");
+        screen.rotate();
+        assert_eq!(screen.lines()[3..], ["58012"]);
+        screen.rotate();
+        assert_eq!(screen.lines(), first);
+
+        // A short one stays put
+        screen.on_event(&fetched("KSFO 141756Z 00000KT"));
+        screen.take_frame();
+        screen.rotate();
+        assert!(screen.take_frame().is_none());
+    }
+
+    #[test]
+    fn words_wider_than_the_panel_are_split() {
+        assert_eq!(wrap("AB CDEFGH I", 4), ["AB", "CDEF", "GH I"]);
+        assert!(wrap("  ", 4).is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+//! Log lines as RFC 5424 syslog messages, sent over UDP to the collector in
+//! `settings.syslog.server` so several maps can be watched from one place.
+
+use std::net::{IpAddr, SocketAddr};
+
+use log::Level;
+
+use crate::clock::TimeZone;
+
+/// Port used when `server` gives none.
+pub const DEFAULT_PORT: u16 = 514;
+/// APP-NAME in every message.
+pub const APP_NAME: &str = "led-sectional";
+/// Longest message text sent, in bytes, so a datagram fits in one frame.
+pub const MAX_TEXT_LEN: usize = 400;
+/// The "user-level messages" facility.
+const FACILITY_USER: u8 = 1;
+
+/// The collector in `server`: an IP address, optionally with a port, such as
+/// `192.168.1.10` or `192.168.1.10:5514`.
+pub fn parse_server(server: &str) -> Option<SocketAddr> {
+    let server = server.trim();
+    server
+        .parse()
+        .ok()
+        .or_else(|| server.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, DEFAULT_PORT)))
+}
+
+/// One message from `hostname`, with the logging module as MSGID. The
+/// timestamp is left for the collector to fill in until the clock is set.
+pub fn message(
+    level: Level,
+    hostname: &str,
+    module: &str,
+    unix_secs: Option<i64>,
+    text: &str,
+) -> String {
+    let severity = match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    };
+    let timestamp = match unix_secs {
+        Some(secs) => {
+            let t = TimeZone::UTC.to_local(secs);
+            format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+                t.year, t.month, t.day, t.hour, t.minute, t.second
+            )
+        }
+        None => "-".to_string(),
+    };
+    let mut end = text.len().min(MAX_TEXT_LEN);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "<{}>1 {} {} {} - {} - {}",
+        FACILITY_USER * 8 + severity,
+        timestamp,
+        header_field(hostname),
+        APP_NAME,
+        header_field(module),
+        &text[..end]
+    )
+}
+
+/// Header fields are printable ASCII with no spaces, and `-` when empty.
+fn header_field(value: &str) -> String {
+    let field: String = value.chars().filter(|c| c.is_ascii_graphic()).take(48).collect();
+    if field.is_empty() {
+        "-".to_string()
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn servers_take_the_default_port() {
+        assert_eq!(parse_server("192.168.1.10"), Some("192.168.1.10:514".parse().unwrap()));
+        assert_eq!(parse_server(" 10.0.0.2:5514 "), Some("10.0.0.2:5514".parse().unwrap()));
+        assert_eq!(parse_server("[fd00::2]:514"), Some("[fd00::2]:514".parse().unwrap()));
+        assert_eq!(parse_server("logs.local"), None);
+        assert_eq!(parse_server("10.0.0.2:syslog"), None);
+    }
+
+    #[test]
+    fn messages_follow_rfc_5424() {
+        let line = message(
+            Level::Warn,
+            "hangar-map",
+            "metar_client",
+            Some(1_760_400_000),
+            "Weather fetch failed",
+        );
+        assert_eq!(
+            line,
+            "<12>1 2025-10-14T00:00:00Z hangar-map led-sectional - metar_client - \
+             Weather fetch failed"
+        );
+        let line = message(Level::Debug, "", "", None, &"é".repeat(MAX_TEXT_LEN));
+        assert!(line.starts_with("<15>1 - - led-sectional - - - é"));
+        assert!(line.len() <= 40 + MAX_TEXT_LEN);
+    }
+}
@@ -0,0 +1,433 @@
+//! What the map publishes over MQTT. Each airport's flight category and
+//! lightning go to retained topics when they change, lightning starting at
+//! an airport is also sent as an event, and on every heartbeat all of it is
+//! sent again along with the device health:
+//!
+//! - `<prefix>/status`: `online`, or `offline` as the broker's last will
+//! - `<prefix>/airport/<CODE>/category`: `VFR`, `MVFR`, `IFR`, `LIFR`, or
+//!   `unknown`
+//! - `<prefix>/airport/<CODE>/lightning`: `none`, `distant`, or `at_station`
+//! - `<prefix>/event/lightning`: `{"code":"KDEN","lightning":"at_station"}`
+//! - `<prefix>/health`: the [`DeviceHealth`] JSON of `GET /api/health`
+//! - `<prefix>/brightness`, `<prefix>/display`, `<prefix>/mode`: what the
+//!   [command topics](crate::commands) control, as they are now
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use crate::api::{DeviceHealth, Payload};
+use crate::config::{Config, DisplayMode};
+use crate::dashboard::MapSnapshot;
+use crate::events::{Event, Subscriber};
+use crate::metar::{FlightCategory, Lightning};
+
+/// Payloads of the availability topic.
+pub const ONLINE: &str = "online";
+pub const OFFLINE: &str = "offline";
+
+/// One MQTT publish.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub topic: String,
+    pub payload: String,
+    pub retain: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AirportState {
+    category: Option<FlightCategory>,
+    lightning: Option<Lightning>,
+}
+
+/// The state behind the command topics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayState {
+    /// The level the LEDs run at, after limits and profiles.
+    pub brightness: u8,
+    pub on: bool,
+    pub mode: DisplayMode,
+}
+
+/// Decides what to publish and when.
+#[derive(Debug)]
+pub struct Telemetry {
+    prefix: String,
+    heartbeat: Duration,
+    /// Airports as of the last fetch, by code.
+    current: BTreeMap<String, AirportState>,
+    /// What the broker was last sent, by code.
+    published: HashMap<String, AirportState>,
+    events: Vec<Message>,
+    display: Option<DisplayState>,
+    published_display: Option<DisplayState>,
+    /// None until the first heartbeat, which is then due at once.
+    next_heartbeat: Option<Instant>,
+}
+
+impl Telemetry {
+    pub fn new(prefix: impl Into<String>, heartbeat: Duration) -> Self {
+        Self {
+            prefix: prefix.into(),
+            heartbeat,
+            current: BTreeMap::new(),
+            published: HashMap::new(),
+            events: Vec::new(),
+            display: None,
+            published_display: None,
+            next_heartbeat: None,
+        }
+    }
+
+    /// Set up from `settings.mqtt`; None when no broker is configured.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let mqtt = &config.settings.mqtt;
+        mqtt.broker.as_ref()?;
+        let prefix = match &mqtt.topic_prefix {
+            Some(prefix) => prefix.clone(),
+            None => format!("led-sectional/{}", config.wifi.hostname()),
+        };
+        Some(Self::new(prefix, Duration::from_secs(mqtt.heartbeat_secs)))
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Retained `online`; the client registers `offline` as its last will.
+    pub fn availability_topic(&self) -> String {
+        format!("{}/status", self.prefix)
+    }
+
+    /// Take in the map after a fetch. Lightning that starts, or moves from
+    /// distant to the field, queues an event; the first map after boot
+    /// only sets the baseline.
+    pub fn observe(&mut self, snapshot: &MapSnapshot) {
+        let mut airports = BTreeMap::new();
+        for row in snapshot.rows.iter().filter(|row| row.weather) {
+            let state = AirportState {
+                category: row.category,
+                lightning: row.report.as_ref().and_then(|r| r.lightning()),
+            };
+            airports.insert(row.code.clone(), state);
+        }
+        for (code, state) in &airports {
+            let before = self.current.get(code).and_then(|s| s.lightning);
+            let first_map = self.current.is_empty();
+            if let Some(lightning) = state.lightning.filter(|&l| !first_map && Some(l) > before) {
+                self.events.push(Message {
+                    topic: format!("{}/event/lightning", self.prefix),
+                    payload: serde_json::json!({
+                        "code": code,
+                        "lightning": lightning_text(Some(lightning)),
+                    })
+                    .to_string(),
+                    retain: false,
+                });
+            }
+        }
+        self.current = airports;
+    }
+
+    /// Take in the display state; published when it changes.
+    pub fn set_display(&mut self, state: DisplayState) {
+        self.display = Some(state);
+    }
+
+    /// Send everything on the next [`poll`](Self::poll), as after connecting
+    /// to the broker again.
+    pub fn resend_all(&mut self) {
+        self.published.clear();
+        self.published_display = None;
+        self.next_heartbeat = None;
+    }
+
+    /// What to publish now: airports and display state that changed since
+    /// they were last sent, queued events, and on the heartbeat all of it
+    /// plus the health from `health`. Airports no longer on the map have their
+    /// retained topics cleared.
+    pub fn poll(&mut self, now: Instant, health: impl FnOnce() -> DeviceHealth) -> Vec<Message> {
+        let heartbeat = self.next_heartbeat.is_none_or(|t| now >= t);
+        if heartbeat {
+            self.next_heartbeat = Some(now + self.heartbeat);
+        }
+        let mut messages = Vec::new();
+        for (code, state) in &self.current {
+            if !heartbeat && self.published.get(code) == Some(state) {
+                continue;
+            }
+            messages.push(self.airport_message(code, "category", category_text(state.category)));
+            messages.push(self.airport_message(code, "lightning", lightning_text(state.lightning)));
+            self.published.insert(code.clone(), *state);
+        }
+        let gone: Vec<String> = self
+            .published
+            .keys()
+            .filter(|code| !self.current.contains_key(*code))
+            .cloned()
+            .collect();
+        for code in gone {
+            messages.push(self.airport_message(&code, "category", ""));
+            messages.push(self.airport_message(&code, "lightning", ""));
+            self.published.remove(&code);
+        }
+        if let Some(display) = self.display {
+            if heartbeat || self.published_display != Some(display) {
+                let on = if display.on { "ON" } else { "OFF" };
+                messages.push(self.state_message("brightness", display.brightness.to_string()));
+                messages.push(self.state_message("display", on.to_string()));
+                messages.push(self.state_message("mode", display.mode.as_str().to_string()));
+                self.published_display = Some(display);
+            }
+        }
+        messages.append(&mut self.events);
+        if heartbeat {
+            messages.push(Message {
+                topic: format!("{}/health", self.prefix),
+                payload: health().to_json(),
+                retain: true,
+            });
+        }
+        messages
+    }
+
+    /// When [`poll`](Self::poll) next has a heartbeat to send.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.next_heartbeat
+    }
+
+    fn state_message(&self, topic: &str, payload: String) -> Message {
+        Message {
+            topic: format!("{}/{topic}", self.prefix),
+            payload,
+            retain: true,
+        }
+    }
+
+    fn airport_message(&self, code: &str, topic: &str, payload: &str) -> Message {
+        Message {
+            topic: format!("{}/airport/{code}/{topic}", self.prefix),
+            payload: payload.to_string(),
+            retain: true,
+        }
+    }
+}
+
+/// Maps after each fetch go to [`observe`](Telemetry::observe); a new
+/// brightness updates the display state already set.
+impl Subscriber for Telemetry {
+    fn on_event(&mut self, event: &Event) {
+        match event {
+            Event::MetarUpdated(snapshot) => self.observe(snapshot),
+            Event::BrightnessChanged(level) => {
+                if let Some(display) = self.display.as_mut() {
+                    display.brightness = *level;
+                }
+            }
+            Event::WifiStateChanged(_) => {}
+        }
+    }
+}
+
+fn category_text(category: Option<FlightCategory>) -> &'static str {
+    category.map_or("unknown", |c| c.as_str())
+}
+
+fn lightning_text(lightning: Option<Lightning>) -> &'static str {
+    match lightning {
+        None => "none",
+        Some(Lightning::Distant) => "distant",
+        Some(Lightning::AtStation) => "at_station",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dashboard::MapRow;
+    use crate::led::Color;
+    use crate::metar::MetarReport;
+
+    fn row(code: &str, category: &str, wx: Option<&str>) -> MapRow {
+        let report = MetarReport {
+            icao_id: code.to_string(),
+            flt_cat: Some(category.to_string()),
+            wx_string: wx.map(str::to_string),
+            ..Default::default()
+        };
+        MapRow {
+            led: 0,
+            code: code.to_string(),
+            name: code.to_string(),
+            color: Color::new(0, 0, 0),
+            category: report.flight_category(),
+            weather: true,
+            fallback: None,
+            wind: None,
+            observed: None,
+            position: None,
+            report: Some(report),
+        }
+    }
+
+    fn snapshot(rows: Vec<MapRow>) -> MapSnapshot {
+        MapSnapshot {
+            rows,
+            ..Default::default()
+        }
+    }
+
+    fn health() -> DeviceHealth {
+        DeviceHealth {
+            version: "0.1.0".into(),
+            git_hash: None,
+            built_at: None,
+            free_heap: 1,
+            min_free_heap: 1,
+            largest_free_block: 1,
+            low_memory: false,
+            rssi: None,
+            reset_reason: "power_on".into(),
+            secs_since_fetch: None,
+            safe_mode: false,
+            last_crash: None,
+        }
+    }
+
+    fn topics(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|m| m.topic.as_str()).collect()
+    }
+
+    #[test]
+    fn publishes_changes_and_heartbeats() {
+        let now = Instant::now();
+        let mut telemetry = Telemetry::new("home/map", Duration::from_secs(300));
+        telemetry.observe(&snapshot(vec![row("KSFO", "VFR", None), row("KOAK", "IFR", None)]));
+
+        let first = telemetry.poll(now, health);
+        assert_eq!(
+            topics(&first),
+            [
+                "home/map/airport/KOAK/category",
+                "home/map/airport/KOAK/lightning",
+                "home/map/airport/KSFO/category",
+                "home/map/airport/KSFO/lightning",
+                "home/map/health",
+            ]
+        );
+        assert_eq!(first[0].payload, "IFR");
+        assert!(first.iter().all(|m| m.retain));
+        assert!(telemetry.poll(now + Duration::from_secs(1), health).is_empty());
+
+        telemetry.observe(&snapshot(vec![row("KSFO", "MVFR", None), row("KOAK", "IFR", None)]));
+        let changed = telemetry.poll(now + Duration::from_secs(2), health);
+        assert_eq!(
+            topics(&changed),
+            ["home/map/airport/KSFO/category", "home/map/airport/KSFO/lightning"]
+        );
+        assert_eq!(changed[0].payload, "MVFR");
+
+        let beat = telemetry.poll(now + Duration::from_secs(300), health);
+        assert_eq!(beat.len(), 5);
+        assert_eq!(telemetry.next_deadline(), Some(now + Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn follows_events() {
+        let now = Instant::now();
+        let mut telemetry = Telemetry::new("m", Duration::from_secs(300));
+        telemetry.set_display(DisplayState {
+            brightness: 100,
+            on: true,
+            mode: DisplayMode::Metar,
+        });
+        telemetry.on_event(&Event::MetarUpdated(snapshot(vec![row("KSFO", "IFR", None)])));
+        telemetry.on_event(&Event::BrightnessChanged(30));
+        let messages = telemetry.poll(now, health);
+        let brightness = messages.iter().find(|m| m.topic == "m/brightness").unwrap();
+        assert_eq!(brightness.payload, "30");
+        assert!(topics(&messages).contains(&"m/airport/KSFO/category"));
+    }
+
+    #[test]
+    fn lightning_starting_is_an_event() {
+        let now = Instant::now();
+        let mut telemetry = Telemetry::new("m", Duration::from_secs(300));
+        // Already storming at boot: no event, just the state
+        telemetry.observe(&snapshot(vec![row("KDEN", "VFR", Some("VCTS"))]));
+        let first = telemetry.poll(now, health);
+        assert!(!topics(&first).contains(&"m/event/lightning"));
+        assert_eq!(first[1].payload, "distant");
+
+        telemetry.observe(&snapshot(vec![row("KDEN", "VFR", Some("TSRA"))]));
+        let messages = telemetry.poll(now + Duration::from_secs(1), health);
+        let event = messages.iter().find(|m| m.topic == "m/event/lightning").unwrap();
+        assert_eq!(event.payload, r#"{"code":"KDEN","lightning":"at_station"}"#);
+        assert!(!event.retain);
+
+        telemetry.observe(&snapshot(vec![row("KDEN", "VFR", None)]));
+        let messages = telemetry.poll(now + Duration::from_secs(2), health);
+        assert_eq!(topics(&messages), ["m/airport/KDEN/category", "m/airport/KDEN/lightning"]);
+        assert_eq!(messages[1].payload, "none");
+    }
+
+    #[test]
+    fn removed_airports_are_cleared_and_reconnects_resend() {
+        let now = Instant::now();
+        let mut telemetry = Telemetry::new("m", Duration::from_secs(300));
+        let mut legend = row("VFR", "VFR", None);
+        legend.weather = false;
+        telemetry.observe(&snapshot(vec![row("KSFO", "VFR", None), legend]));
+        assert_eq!(telemetry.poll(now, health).len(), 3);
+
+        telemetry.observe(&snapshot(vec![row("KOAK", "VFR", None)]));
+        let messages = telemetry.poll(now + Duration::from_secs(1), health);
+        let cleared: Vec<&str> = messages
+            .iter()
+            .filter(|m| m.payload.is_empty())
+            .map(|m| m.topic.as_str())
+            .collect();
+        assert_eq!(cleared, ["m/airport/KSFO/category", "m/airport/KSFO/lightning"]);
+
+        telemetry.resend_all();
+        assert_eq!(telemetry.poll(now + Duration::from_secs(2), health).len(), 3);
+    }
+
+    #[test]
+    fn display_state_is_published_on_change() {
+        let now = Instant::now();
+        let mut telemetry = Telemetry::new("m", Duration::from_secs(300));
+        // Nothing to say about the display until it's known
+        assert_eq!(topics(&telemetry.poll(now, health)), ["m/health"]);
+
+        let mut display = DisplayState {
+            brightness: 40,
+            on: true,
+            mode: DisplayMode::Metar,
+        };
+        telemetry.set_display(display);
+        let messages = telemetry.poll(now + Duration::from_secs(1), health);
+        assert_eq!(topics(&messages), ["m/brightness", "m/display", "m/mode"]);
+        assert_eq!(messages[1].payload, "ON");
+        assert!(messages.iter().all(|m| m.retain));
+
+        telemetry.set_display(display);
+        assert!(telemetry.poll(now + Duration::from_secs(2), health).is_empty());
+        display.on = false;
+        telemetry.set_display(display);
+        let messages = telemetry.poll(now + Duration::from_secs(3), health);
+        assert_eq!(messages[1].payload, "OFF");
+
+        telemetry.resend_all();
+        assert_eq!(telemetry.poll(now + Duration::from_secs(4), health).len(), 4);
+    }
+
+    #[test]
+    fn prefix_defaults_to_the_hostname() {
+        let config = Config::builder().airport("KSFO").build().unwrap();
+        assert!(Telemetry::from_config(&config).is_none());
+        let toml = "[settings.mqtt]\nbroker = \"mqtt://broker\"\n\n[wifi]\nhostname = \"den-map\"\n";
+        let telemetry = Telemetry::from_config(&Config::from_toml(toml).unwrap()).unwrap();
+        assert_eq!(telemetry.prefix(), "led-sectional/den-map");
+        assert_eq!(telemetry.availability_topic(), "led-sectional/den-map/status");
+    }
+}
@@ -0,0 +1,277 @@
+//! Test patterns for checking the wiring: the whole strip in one color, a
+//! dot chasing along it, a moving rainbow, or a single LED lit. A pattern
+//! takes over the LEDs for a while and then hands back the weather colors it
+//! replaced.
+
+use std::time::{Duration, Instant};
+
+use crate::led::{Color, LedState};
+
+/// How long a pattern runs when no duration is given.
+pub const DEFAULT_DURATION: Duration = Duration::from_secs(60);
+/// Longest a pattern may run, so a forgotten test doesn't hide the weather.
+pub const MAX_DURATION: Duration = Duration::from_secs(600);
+const CHASE_STEP: Duration = Duration::from_millis(100);
+const RAINBOW_STEP: Duration = Duration::from_millis(50);
+
+const OFF: Color = Color::new(0, 0, 0);
+const WHITE: Color = Color::new(255, 255, 255);
+const SOLIDS: [(&str, Color); 4] = [
+    ("all-red", Color::new(255, 0, 0)),
+    ("all-green", Color::new(0, 255, 0)),
+    ("all-blue", Color::new(0, 0, 255)),
+    ("all-white", WHITE),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestPattern {
+    /// Every LED in one color, to check each channel.
+    Solid(Color),
+    /// One white LED stepping along the strip, to check the order.
+    Chase,
+    /// A rainbow scrolling along the strip.
+    Rainbow,
+    /// Only this LED lit, to find where an index sits on the map.
+    Single(usize),
+}
+
+/// What `POST /api/test` asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestRequest {
+    Start(TestPattern, Duration),
+    /// End a running pattern now.
+    Stop,
+}
+
+impl TestRequest {
+    /// Parse a pattern name (`all-red`, `all-green`, `all-blue`,
+    /// `all-white`, `chase`, `rainbow`, or `single N`) optionally followed
+    /// by how many seconds to run, or `stop`. `N` must be below `num_leds`.
+    pub fn parse(text: &str, num_leds: usize) -> Result<Self, String> {
+        let mut words = text.split_whitespace();
+        let name = words.next().ok_or("no pattern given")?.to_ascii_lowercase();
+        let pattern = match name.as_str() {
+            "stop" => return Ok(Self::Stop),
+            "chase" => TestPattern::Chase,
+            "rainbow" => TestPattern::Rainbow,
+            "single" => {
+                let index = words.next().ok_or("single needs an LED index")?;
+                let index: usize = index
+                    .parse()
+                    .map_err(|_| format!("\"{index}\" isn't an LED index"))?;
+                if index >= num_leds {
+                    return Err(format!(
+                        "LED {index} is past the end of the strip ({num_leds} LEDs)"
+                    ));
+                }
+                TestPattern::Single(index)
+            }
+            _ => match SOLIDS.iter().find(|(solid, _)| *solid == name) {
+                Some(&(_, color)) => TestPattern::Solid(color),
+                None => {
+                    return Err(format!(
+                        "unknown pattern \"{name}\" (known: {}, chase, rainbow, single N, stop)",
+                        SOLIDS.map(|(solid, _)| solid).join(", ")
+                    ))
+                }
+            },
+        };
+        let duration = match words.next() {
+            Some(secs) => {
+                let secs: u64 = secs
+                    .parse()
+                    .map_err(|_| format!("\"{secs}\" isn't a number of seconds"))?;
+                if secs == 0 {
+                    return Err("duration must be at least 1 second".to_string());
+                }
+                Duration::from_secs(secs).min(MAX_DURATION)
+            }
+            None => DEFAULT_DURATION,
+        };
+        if let Some(extra) = words.next() {
+            return Err(format!("unexpected \"{extra}\""));
+        }
+        Ok(Self::Start(pattern, duration))
+    }
+}
+
+/// A pattern on the LEDs, with the colors it covered.
+#[derive(Debug, Clone)]
+pub struct TestRun {
+    pattern: TestPattern,
+    until: Instant,
+    frame: usize,
+    next_step: Option<Instant>,
+    saved: Vec<Color>,
+    saved_dim: bool,
+}
+
+impl TestRun {
+    /// Take over `led_state` from `now` for `duration`, remembering what it
+    /// showed for [`finish`](Self::finish).
+    pub fn start(
+        pattern: TestPattern,
+        now: Instant,
+        duration: Duration,
+        led_state: &mut LedState,
+    ) -> Self {
+        let saved = (0..led_state.num_leds())
+            .map(|i| led_state.get(i).unwrap_or(OFF))
+            .collect();
+        let saved_dim = led_state.indicator_dim();
+        led_state.set_indicator_dim(false);
+        Self {
+            pattern,
+            until: now + duration,
+            frame: 0,
+            next_step: Some(now),
+            saved,
+            saved_dim,
+        }
+    }
+
+    pub fn pattern(&self) -> TestPattern {
+        self.pattern
+    }
+
+    pub fn is_over(&self, now: Instant) -> bool {
+        now >= self.until
+    }
+
+    /// Draw the next frame if one is due. Returns true if the LEDs changed.
+    pub fn tick(&mut self, now: Instant, led_state: &mut LedState) -> bool {
+        if self.next_step.is_none_or(|t| now < t) {
+            return false;
+        }
+        let n = led_state.num_leds();
+        match self.pattern {
+            TestPattern::Solid(color) => {
+                led_state.set_all(color);
+                self.next_step = None;
+            }
+            TestPattern::Single(index) => {
+                led_state.set_all(OFF);
+                let _ = led_state.set(index, WHITE);
+                self.next_step = None;
+            }
+            TestPattern::Chase => {
+                led_state.set_all(OFF);
+                if n > 0 {
+                    let _ = led_state.set(self.frame % n, WHITE);
+                }
+                self.next_step = Some(now + CHASE_STEP);
+            }
+            TestPattern::Rainbow => {
+                for i in 0..n {
+                    let hue = (i * 256 / n.max(1) + self.frame * 4) % 256;
+                    let _ = led_state.set(i, wheel(hue as u8));
+                }
+                self.next_step = Some(now + RAINBOW_STEP);
+            }
+        }
+        self.frame = self.frame.wrapping_add(1);
+        true
+    }
+
+    /// When the next frame is due, or the pattern ends.
+    pub fn next_deadline(&self) -> Instant {
+        self.next_step.map_or(self.until, |t| t.min(self.until))
+    }
+
+    /// Put back the colors the pattern covered.
+    pub fn finish(self, led_state: &mut LedState) {
+        for (i, color) in self.saved.into_iter().enumerate() {
+            let _ = led_state.set(i, color);
+        }
+        led_state.set_indicator_dim(self.saved_dim);
+    }
+}
+
+/// A fully saturated color around the hue circle: red, green, blue, back.
+fn wheel(hue: u8) -> Color {
+    let h = hue as u16 * 3;
+    match h {
+        0..=255 => Color::new((255 - h) as u8, h as u8, 0),
+        256..=511 => Color::new(0, (511 - h) as u8, (h - 256) as u8),
+        _ => Color::new((h - 512) as u8, 0, (767 - h) as u8),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_patterns_and_durations() {
+        let start = |p, secs| Ok(TestRequest::Start(p, Duration::from_secs(secs)));
+        assert_eq!(TestRequest::parse("chase", 10), start(TestPattern::Chase, 60));
+        assert_eq!(TestRequest::parse(" Rainbow 15\n", 10), start(TestPattern::Rainbow, 15));
+        assert_eq!(
+            TestRequest::parse("all-red", 10),
+            start(TestPattern::Solid(Color::new(255, 0, 0)), 60)
+        );
+        assert_eq!(TestRequest::parse("single 9 5", 10), start(TestPattern::Single(9), 5));
+        assert_eq!(TestRequest::parse("chase 100000", 10), start(TestPattern::Chase, 600));
+        assert_eq!(TestRequest::parse("stop", 10), Ok(TestRequest::Stop));
+    }
+
+    #[test]
+    fn parse_rejects_bad_requests() {
+        for text in ["", "plaid", "single", "single 10", "single x", "chase 0", "chase 5 6"] {
+            assert!(TestRequest::parse(text, 10).is_err(), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn chase_steps_along_the_strip_then_restores() {
+        let now = Instant::now();
+        let mut leds = LedState::new(3, 255);
+        leds.set_all(Color::new(0, 0, 255));
+        leds.set_indicator_indices(vec![0]);
+        leds.set_indicator_dim(true);
+        let mut run = TestRun::start(TestPattern::Chase, now, Duration::from_secs(1), &mut leds);
+        assert!(!leds.indicator_dim());
+
+        assert!(run.tick(now, &mut leds));
+        assert_eq!(leds.get(0).unwrap(), WHITE);
+        assert_eq!(leds.get(1).unwrap(), OFF);
+        assert!(!run.tick(now + CHASE_STEP / 2, &mut leds));
+        assert_eq!(run.next_deadline(), now + CHASE_STEP);
+        assert!(run.tick(now + CHASE_STEP, &mut leds));
+        assert_eq!(leds.get(0).unwrap(), OFF);
+        assert_eq!(leds.get(1).unwrap(), WHITE);
+
+        assert!(!run.is_over(now + Duration::from_millis(999)));
+        assert!(run.is_over(now + Duration::from_secs(1)));
+        run.finish(&mut leds);
+        assert!((0..3).all(|i| leds.get(i).unwrap() == Color::new(0, 0, 255)));
+        assert!(leds.indicator_dim());
+    }
+
+    #[test]
+    fn static_patterns_draw_once() {
+        let now = Instant::now();
+        let mut leds = LedState::new(4, 255);
+        let mut run = TestRun::start(TestPattern::Single(2), now, DEFAULT_DURATION, &mut leds);
+        assert!(run.tick(now, &mut leds));
+        let lit: Vec<usize> = (0..4).filter(|&i| leds.get(i).unwrap() != OFF).collect();
+        assert_eq!(lit, [2]);
+        assert!(!run.tick(now + Duration::from_secs(1), &mut leds));
+        assert_eq!(run.next_deadline(), now + DEFAULT_DURATION);
+    }
+
+    #[test]
+    fn rainbow_covers_the_hue_circle() {
+        assert_eq!(wheel(0), Color::new(255, 0, 0));
+        assert_eq!(wheel(85), Color::new(0, 255, 0));
+        assert_eq!(wheel(170), Color::new(0, 1, 254));
+        let now = Instant::now();
+        let mut leds = LedState::new(3, 255);
+        let mut run = TestRun::start(TestPattern::Rainbow, now, DEFAULT_DURATION, &mut leds);
+        run.tick(now, &mut leds);
+        let first: Vec<Color> = (0..3).map(|i| leds.get(i).unwrap()).collect();
+        run.tick(now + RAINBOW_STEP, &mut leds);
+        assert_ne!(leds.get(0).unwrap(), first[0]);
+        assert_ne!(first[0], first[1]);
+    }
+}
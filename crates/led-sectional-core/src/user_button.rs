@@ -0,0 +1,220 @@
+//! An optional second button for everyday control, separate from the setup
+//! button in [`crate::button`]. Presses are told apart as short, double or
+//! long, and each can be given one of the [`ButtonAction`]s in
+//! `[settings.user_button]`.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::commands::Command;
+use crate::config::{DisplayMode, Settings};
+
+/// The input must hold a new level this long to count, riding out contact
+/// bounce.
+pub const DEBOUNCE: Duration = Duration::from_millis(30);
+/// Held this long, a press is long; it fires without waiting for release.
+pub const LONG_PRESS: Duration = Duration::from_millis(800);
+/// A second press starting within this of the first release makes a double
+/// press.
+pub const DOUBLE_PRESS_GAP: Duration = Duration::from_millis(350);
+/// Brightness levels [`ButtonAction::CycleBrightness`] steps through.
+pub const BRIGHTNESS_STEPS: [u8; 4] = [32, 80, 160, 255];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Press {
+    Short,
+    Double,
+    Long,
+}
+
+/// What a press does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonAction {
+    #[default]
+    None,
+    /// Step up through [`BRIGHTNESS_STEPS`], back to the lowest after the
+    /// highest.
+    CycleBrightness,
+    ToggleDisplay,
+    /// Switch between the METAR and winds aloft displays.
+    NextMode,
+    /// Fetch weather now.
+    Refresh,
+    /// Switch to the next `[[profiles]]` entry, back to plain `[settings]`
+    /// after the last.
+    NextProfile,
+}
+
+impl ButtonAction {
+    /// The command carrying out the action, given the current settings and
+    /// whether the display is on.
+    pub fn command(self, settings: &Settings, display_on: bool) -> Option<Command> {
+        match self {
+            Self::None => None,
+            Self::CycleBrightness => {
+                let next = BRIGHTNESS_STEPS
+                    .into_iter()
+                    .find(|&level| level > settings.brightness)
+                    .unwrap_or(BRIGHTNESS_STEPS[0]);
+                Some(Command::Brightness(next))
+            }
+            Self::ToggleDisplay => Some(Command::Display(!display_on)),
+            Self::NextMode => Some(Command::Mode(match settings.display_mode {
+                DisplayMode::Metar => DisplayMode::WindsAloft,
+                DisplayMode::WindsAloft => DisplayMode::Metar,
+            })),
+            Self::Refresh => Some(Command::Refresh),
+            Self::NextProfile => Some(Command::NextProfile),
+        }
+    }
+}
+
+/// Tells presses apart from periodic samples of the button.
+#[derive(Debug, Clone, Default)]
+pub struct PressDetector {
+    /// The debounced level.
+    pressed: bool,
+    /// How long the raw input has differed from `pressed`.
+    changing_for: Duration,
+    /// How long the current press has lasted.
+    held: Duration,
+    /// The current press already fired as long.
+    long_fired: bool,
+    /// Time since a short press was released, while waiting to see if a
+    /// second one follows.
+    since_release: Option<Duration>,
+    /// The current press follows a short one closely enough to be a double.
+    second: bool,
+}
+
+impl PressDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one sample taken `elapsed` after the previous one. With
+    /// `double_press` false, a short press is reported on release instead of
+    /// after [`DOUBLE_PRESS_GAP`], and no doubles are reported.
+    pub fn sample(&mut self, raw: bool, elapsed: Duration, double_press: bool) -> Option<Press> {
+        if raw == self.pressed {
+            self.changing_for = Duration::ZERO;
+        } else {
+            self.changing_for += elapsed;
+            if self.changing_for >= DEBOUNCE {
+                self.changing_for = Duration::ZERO;
+                self.pressed = raw;
+                return if raw { self.on_press() } else { self.on_release(double_press) };
+            }
+        }
+
+        if self.pressed {
+            self.held += elapsed;
+            if self.held >= LONG_PRESS && !self.long_fired {
+                self.long_fired = true;
+                self.second = false;
+                return Some(Press::Long);
+            }
+        } else if let Some(since) = self.since_release.as_mut() {
+            *since += elapsed;
+            if *since >= DOUBLE_PRESS_GAP {
+                self.since_release = None;
+                return Some(Press::Short);
+            }
+        }
+        None
+    }
+
+    fn on_press(&mut self) -> Option<Press> {
+        self.held = Duration::ZERO;
+        self.long_fired = false;
+        self.second = self.since_release.take().is_some();
+        None
+    }
+
+    fn on_release(&mut self, double_press: bool) -> Option<Press> {
+        if self.long_fired {
+            return None;
+        }
+        if std::mem::take(&mut self.second) {
+            return Some(Press::Double);
+        }
+        if double_press {
+            self.since_release = Some(Duration::ZERO);
+            None
+        } else {
+            Some(Press::Short)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    const TICK: Duration = Duration::from_millis(10);
+
+    /// Hold the raw input at `raw` for `duration`, collecting presses.
+    fn feed(detector: &mut PressDetector, raw: bool, duration: Duration) -> Vec<Press> {
+        let ticks = duration.as_millis() / TICK.as_millis();
+        (0..ticks).filter_map(|_| detector.sample(raw, TICK, true)).collect()
+    }
+
+    #[test]
+    fn short_double_and_long_presses() {
+        let mut detector = PressDetector::new();
+        assert!(feed(&mut detector, true, Duration::from_millis(150)).is_empty());
+        // Short fires once the gap passes with no second press
+        assert!(feed(&mut detector, false, Duration::from_millis(200)).is_empty());
+        assert_eq!(feed(&mut detector, false, Duration::from_millis(300)), [Press::Short]);
+
+        feed(&mut detector, true, Duration::from_millis(100));
+        feed(&mut detector, false, Duration::from_millis(100));
+        feed(&mut detector, true, Duration::from_millis(100));
+        assert_eq!(feed(&mut detector, false, Duration::from_millis(500)), [Press::Double]);
+
+        assert_eq!(feed(&mut detector, true, Duration::from_secs(2)), [Press::Long]);
+        assert!(feed(&mut detector, false, Duration::from_millis(500)).is_empty());
+    }
+
+    #[test]
+    fn bounces_are_ignored() {
+        let mut detector = PressDetector::new();
+        for _ in 0..5 {
+            assert_eq!(detector.sample(true, TICK, false), None);
+            assert_eq!(detector.sample(false, TICK, false), None);
+        }
+        feed(&mut detector, true, Duration::from_millis(100));
+        // Without doubles, short fires on release
+        let presses: Vec<_> =
+            (0..4).filter_map(|_| detector.sample(false, TICK, false)).collect();
+        assert_eq!(presses, [Press::Short]);
+    }
+
+    #[test]
+    fn actions_become_commands() {
+        let mut config = Config::builder().airport("KSFO").brightness(100).build().unwrap();
+        let settings = &config.settings;
+        assert_eq!(
+            ButtonAction::CycleBrightness.command(settings, true),
+            Some(Command::Brightness(160))
+        );
+        assert_eq!(
+            ButtonAction::ToggleDisplay.command(settings, true),
+            Some(Command::Display(false))
+        );
+        assert_eq!(
+            ButtonAction::NextMode.command(settings, true),
+            Some(Command::Mode(DisplayMode::WindsAloft))
+        );
+        assert_eq!(ButtonAction::NextProfile.command(settings, true), Some(Command::NextProfile));
+        assert_eq!(ButtonAction::None.command(settings, true), None);
+        config.settings.brightness = 255;
+        assert_eq!(
+            ButtonAction::CycleBrightness.command(&config.settings, true),
+            Some(Command::Brightness(32))
+        );
+    }
+}
@@ -0,0 +1,376 @@
+//! WiFi connection state machine. The firmware feeds it the driver's
+//! associated / disconnected / got-IP / lost-IP events and carries out the
+//! [`LinkAction`]s it returns, so joining, reconnecting, and trying a network
+//! from the setup form never block the main loop.
+
+use std::collections::VecDeque;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+use crate::backoff::Backoff;
+use crate::networks::{Credentials, NetworkList};
+
+/// How long an association plus DHCP may take before moving on to the next
+/// network.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Disconnect reason the driver reports when we dropped the association
+/// ourselves, e.g. to join another network (`WIFI_REASON_ASSOC_LEAVE`).
+pub const REASON_ASSOC_LEAVE: u16 = 8;
+
+/// A station event from the WiFi driver or the IP stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkEvent {
+    /// Joined the access point; DHCP comes next.
+    Associated,
+    /// The association ended or an attempt failed, with the driver's reason
+    /// code.
+    Disconnected { reason: u16 },
+    GotIp(Ipv4Addr),
+    /// The DHCP lease lapsed while still associated.
+    LostIp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkState {
+    /// Idle until `until`, then start trying the saved networks again.
+    Waiting { until: Instant },
+    /// Joining `ssid`; it counts as failed if there's no address by
+    /// `deadline`. Once `associated`, it's waiting for DHCP.
+    Connecting {
+        ssid: String,
+        deadline: Instant,
+        associated: bool,
+    },
+    Online { ssid: String, ip: Ipv4Addr },
+}
+
+impl LinkState {
+    /// Short name for status reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LinkState::Waiting { .. } => "waiting",
+            LinkState::Connecting {
+                associated: false, ..
+            } => "connecting",
+            LinkState::Connecting { .. } => "obtaining_ip",
+            LinkState::Online { .. } => "online",
+        }
+    }
+}
+
+/// What the firmware should do next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkAction {
+    /// Drop any current association and start joining this network.
+    Connect(Credentials),
+    /// Got an address on `ssid`. `trial` is the network passed to
+    /// [`WifiLink::try_network`] if that's the one that connected; it is now
+    /// in [`WifiLink::networks`] and should be saved.
+    Online {
+        ssid: String,
+        trial: Option<Credentials>,
+    },
+    /// The network passed to [`WifiLink::try_network`] didn't connect;
+    /// `reason` is None if it timed out. The saved networks are tried again
+    /// on the next [`WifiLink::poll`].
+    TrialFailed { ssid: String, reason: Option<u16> },
+}
+
+/// Cycles through the saved networks (last successful first) until one
+/// connects, then waits out a [`Backoff`] delay before each further round.
+#[derive(Debug, Clone)]
+pub struct WifiLink {
+    networks: NetworkList,
+    state: LinkState,
+    /// Networks left to try this round.
+    queue: VecDeque<Credentials>,
+    /// A network from the setup form being tried before it is saved.
+    trial: Option<Credentials>,
+    backoff: Backoff,
+}
+
+impl WifiLink {
+    /// Start trying `networks` on the first [`poll`](Self::poll).
+    pub fn new(networks: NetworkList, backoff: Backoff, now: Instant) -> Self {
+        Self {
+            networks,
+            state: LinkState::Waiting { until: now },
+            queue: VecDeque::new(),
+            trial: None,
+            backoff,
+        }
+    }
+
+    pub fn state(&self) -> &LinkState {
+        &self.state
+    }
+
+    pub fn is_online(&self) -> bool {
+        matches!(self.state, LinkState::Online { .. })
+    }
+
+    /// The saved networks, including any trial network once it connected.
+    pub fn networks(&self) -> &NetworkList {
+        &self.networks
+    }
+
+    /// Rounds of attempts that failed since the link was last online.
+    pub fn failed_rounds(&self) -> u32 {
+        self.backoff.failures()
+    }
+
+    /// Leave the saved networks and join `network` instead. The outcome
+    /// arrives as [`LinkAction::Online`] or [`LinkAction::TrialFailed`].
+    pub fn try_network(&mut self, network: Credentials, now: Instant) -> LinkAction {
+        self.queue.clear();
+        self.trial = Some(network.clone());
+        self.connect(network, now)
+    }
+
+    /// Feed in a driver event.
+    pub fn handle(&mut self, event: LinkEvent, now: Instant) -> Option<LinkAction> {
+        match event {
+            LinkEvent::Associated => {
+                if let LinkState::Connecting { associated, .. } = &mut self.state {
+                    *associated = true;
+                }
+                None
+            }
+            LinkEvent::Disconnected { reason } if reason == REASON_ASSOC_LEAVE => None,
+            LinkEvent::Disconnected { reason } => match &self.state {
+                LinkState::Connecting { .. } => self.fail_attempt(Some(reason), now),
+                LinkState::Online { .. } => {
+                    // Not a failed round: the network worked a moment ago
+                    self.start_round(now)
+                }
+                LinkState::Waiting { .. } => None,
+            },
+            LinkEvent::GotIp(ip) => match &mut self.state {
+                LinkState::Connecting { ssid, .. } => {
+                    let ssid = std::mem::take(ssid);
+                    self.state = LinkState::Online {
+                        ssid: ssid.clone(),
+                        ip,
+                    };
+                    self.queue.clear();
+                    self.backoff.reset();
+                    let trial = self.trial.take();
+                    if let Some(network) = &trial {
+                        self.networks.add(network.clone());
+                    }
+                    self.networks.mark_connected(&ssid);
+                    Some(LinkAction::Online { ssid, trial })
+                }
+                LinkState::Online { ip: current, .. } => {
+                    *current = ip;
+                    None
+                }
+                LinkState::Waiting { .. } => None,
+            },
+            LinkEvent::LostIp => {
+                if let LinkState::Online { ssid, .. } = &mut self.state {
+                    // Still associated; give DHCP the usual time to renew,
+                    // then carry on with the other networks
+                    let ssid = std::mem::take(ssid);
+                    self.queue = self.networks.connection_order().into_iter().cloned().collect();
+                    self.queue.retain(|network| network.ssid != ssid);
+                    self.state = LinkState::Connecting {
+                        ssid,
+                        deadline: now + CONNECT_TIMEOUT,
+                        associated: true,
+                    };
+                }
+                None
+            }
+        }
+    }
+
+    /// Check timers: an attempt that ran out of time, or the end of a
+    /// backoff wait.
+    pub fn poll(&mut self, now: Instant) -> Option<LinkAction> {
+        match &self.state {
+            LinkState::Connecting { deadline, .. } if now >= *deadline => {
+                self.fail_attempt(None, now)
+            }
+            LinkState::Waiting { until } if now >= *until => self.start_round(now),
+            _ => None,
+        }
+    }
+
+    fn connect(&mut self, network: Credentials, now: Instant) -> LinkAction {
+        self.state = LinkState::Connecting {
+            ssid: network.ssid.clone(),
+            deadline: now + CONNECT_TIMEOUT,
+            associated: false,
+        };
+        LinkAction::Connect(network)
+    }
+
+    fn fail_attempt(&mut self, reason: Option<u16>, now: Instant) -> Option<LinkAction> {
+        if let Some(trial) = self.trial.take() {
+            self.state = LinkState::Waiting { until: now };
+            return Some(LinkAction::TrialFailed {
+                ssid: trial.ssid,
+                reason,
+            });
+        }
+        self.next_attempt(now)
+    }
+
+    fn start_round(&mut self, now: Instant) -> Option<LinkAction> {
+        self.queue = self.networks.connection_order().into_iter().cloned().collect();
+        self.next_attempt(now)
+    }
+
+    fn next_attempt(&mut self, now: Instant) -> Option<LinkAction> {
+        match self.queue.pop_front() {
+            Some(network) => Some(self.connect(network, now)),
+            None => {
+                self.state = LinkState::Waiting {
+                    until: now + self.backoff.next_delay(),
+                };
+                None
+            }
+        }
+    }
+}
+
+/// Why an attempt failed, in words for the setup form.
+pub fn failure_message(reason: Option<u16>) -> &'static str {
+    match reason {
+        None => "timed out waiting for an address",
+        // NO_AP_FOUND
+        Some(201) => "network not found",
+        // 4WAY_HANDSHAKE_TIMEOUT, HANDSHAKE_TIMEOUT, AUTH_FAIL, MIC_FAILURE
+        Some(15 | 204 | 202 | 14) => "wrong password",
+        Some(_) => "could not connect",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn networks() -> NetworkList {
+        let mut networks = NetworkList::default();
+        networks.add(Credentials::new("Home", "hunter22"));
+        networks.add(Credentials::new("Hangar", "cessna172"));
+        networks
+    }
+
+    fn link(now: Instant) -> WifiLink {
+        let backoff = Backoff::new(Duration::from_secs(5), Duration::from_secs(300));
+        WifiLink::new(networks(), backoff, now)
+    }
+
+    fn connect_ssid(action: Option<LinkAction>) -> String {
+        match action {
+            Some(LinkAction::Connect(network)) => network.ssid,
+            other => panic!("expected a connect, got {other:?}"),
+        }
+    }
+
+    const IP: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 40);
+    const NO_AP_FOUND: LinkEvent = LinkEvent::Disconnected { reason: 201 };
+
+    #[test]
+    fn cycles_networks_then_backs_off() {
+        let start = Instant::now();
+        let mut link = link(start);
+        assert_eq!(connect_ssid(link.poll(start)), "Home");
+        assert_eq!(link.state().name(), "connecting");
+        link.handle(LinkEvent::Associated, start);
+        assert_eq!(link.state().name(), "obtaining_ip");
+
+        // Our own disconnect before switching networks is ignored
+        let leave = LinkEvent::Disconnected {
+            reason: REASON_ASSOC_LEAVE,
+        };
+        assert_eq!(link.handle(leave, start), None);
+
+        assert_eq!(connect_ssid(link.handle(NO_AP_FOUND, start)), "Hangar");
+        // Timing out counts the same as a failure event
+        let late = start + CONNECT_TIMEOUT;
+        assert_eq!(link.poll(late), None);
+        assert_eq!(link.failed_rounds(), 1);
+        assert_eq!(link.poll(late + Duration::from_secs(4)), None);
+        assert_eq!(connect_ssid(link.poll(late + Duration::from_secs(5))), "Home");
+    }
+
+    #[test]
+    fn connecting_resets_backoff_and_drops_restart_a_round() {
+        let start = Instant::now();
+        let mut link = link(start);
+        link.poll(start);
+        link.handle(NO_AP_FOUND, start);
+        link.handle(NO_AP_FOUND, start);
+        connect_ssid(link.poll(start + Duration::from_secs(5)));
+        link.handle(NO_AP_FOUND, start);
+
+        let online = link.handle(LinkEvent::GotIp(IP), start);
+        assert_eq!(
+            online,
+            Some(LinkAction::Online {
+                ssid: "Hangar".into(),
+                trial: None
+            })
+        );
+        assert_eq!(link.failed_rounds(), 0);
+
+        // Losing the connection retries straight away, last good network first
+        assert_eq!(connect_ssid(link.handle(NO_AP_FOUND, start)), "Hangar");
+    }
+
+    #[test]
+    fn lost_ip_waits_for_dhcp_before_giving_up() {
+        let start = Instant::now();
+        let mut link = link(start);
+        link.poll(start);
+        link.handle(LinkEvent::GotIp(IP), start);
+
+        assert_eq!(link.handle(LinkEvent::LostIp, start), None);
+        assert!(!link.is_online());
+        assert_eq!(link.state().name(), "obtaining_ip");
+        link.handle(LinkEvent::GotIp(IP), start);
+        assert!(link.is_online());
+
+        link.handle(LinkEvent::LostIp, start);
+        assert_eq!(connect_ssid(link.poll(start + CONNECT_TIMEOUT)), "Hangar");
+    }
+
+    #[test]
+    fn trial_network_is_saved_only_once_it_connects() {
+        let start = Instant::now();
+        let mut link = link(start);
+        link.poll(start);
+        link.handle(LinkEvent::GotIp(IP), start);
+
+        let cafe = Credentials::new("Cafe", "latte123");
+        link.try_network(cafe.clone(), start);
+        let failed = link.handle(LinkEvent::Disconnected { reason: 15 }, start);
+        assert_eq!(
+            failed,
+            Some(LinkAction::TrialFailed {
+                ssid: "Cafe".into(),
+                reason: Some(15)
+            })
+        );
+        assert!(!link.networks().ssids().any(|s| s == "Cafe"));
+        // Back to the saved networks, without counting a failed round
+        assert_eq!(connect_ssid(link.poll(start)), "Home");
+        assert_eq!(link.failed_rounds(), 0);
+
+        link.try_network(cafe, start);
+        let online = link.handle(LinkEvent::GotIp(IP), start);
+        assert!(matches!(online, Some(LinkAction::Online { trial: Some(_), .. })));
+        assert_eq!(link.networks().connection_order()[0].ssid, "Cafe");
+    }
+
+    #[test]
+    fn failure_messages() {
+        assert_eq!(failure_message(Some(15)), "wrong password");
+        assert_eq!(failure_message(Some(201)), "network not found");
+        assert_eq!(failure_message(None), "timed out waiting for an address");
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use crate::config::{Airport, Settings};
+use crate::led::{Color, LedState, COLOR_IFR, COLOR_UNKNOWN, COLOR_VFR, COLOR_WIND};
+
+const WINDS_ALOFT_BASE_URL: &str = "https://aviationweather.gov/api/data/windtemp?region=all&level=low";
+
+/// Wind and temperature forecast at one altitude.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindAloft {
+    /// True direction in degrees, or None for light and variable.
+    pub direction_deg: Option<u16>,
+    pub speed_kt: u16,
+    pub temp_c: Option<i8>,
+}
+
+/// Forecast winds for one FD station, keyed by altitude in feet MSL.
+pub type StationWinds = HashMap<u32, WindAloft>;
+
+/// Build the winds-aloft (FD) URL for the given forecast period (6, 12, or 24 hours).
+pub fn build_winds_aloft_url(forecast_hours: u8) -> String {
+    format!("{WINDS_ALOFT_BASE_URL}&fcst={forecast_hours:02}")
+}
+
+/// Parse an FD winds-aloft text product into per-station forecasts.
+///
+/// Data columns are right-aligned under the altitude labels of the `FT`
+/// header line, so groups are sliced by the header's column positions.
+/// Stations or groups that don't parse are skipped.
+pub fn parse_winds_aloft(text: &str) -> HashMap<String, StationWinds> {
+    let mut lines = text.lines();
+    let columns: Vec<(usize, usize, u32)> = match lines.find(|l| l.starts_with("FT ")) {
+        Some(header) => header_columns(header),
+        None => return HashMap::new(),
+    };
+
+    let mut stations = HashMap::new();
+    for line in lines {
+        let Some(station) = line.get(..3).map(str::trim) else {
+            continue;
+        };
+        if station.len() != 3 || !station.chars().all(|c| c.is_ascii_alphanumeric()) {
+            continue;
+        }
+
+        let winds: StationWinds = columns
+            .iter()
+            .filter_map(|&(start, end, altitude)| {
+                let group = line.get(start..end.min(line.len()))?.trim();
+                parse_group(group, altitude).map(|w| (altitude, w))
+            })
+            .collect();
+        if !winds.is_empty() {
+            stations.insert(station.to_string(), winds);
+        }
+    }
+    stations
+}
+
+/// Locate `(start, end, altitude)` for each altitude label in the header.
+fn header_columns(header: &str) -> Vec<(usize, usize, u32)> {
+    let mut columns = Vec::new();
+    let mut prev_end = 3;
+    let mut pos = 0;
+    for label in header.split_whitespace() {
+        let start = pos + header[pos..].find(label).unwrap_or(0);
+        let end = start + label.len();
+        pos = end;
+        if let Ok(altitude) = label.parse() {
+            columns.push((prev_end, end, altitude));
+            prev_end = end;
+        }
+    }
+    columns
+}
+
+/// Decode a `DDSS`, `DDSS±TT`, or `DDSSTT` group. Above 24,000 ft the
+/// temperature sign is omitted and always negative.
+fn parse_group(group: &str, altitude: u32) -> Option<WindAloft> {
+    if group.len() < 4 || !group.is_ascii() {
+        return None;
+    }
+    let dd: u16 = group[0..2].parse().ok()?;
+    let ss: u16 = group[2..4].parse().ok()?;
+    let temp = &group[4..];
+    let temp_c = match temp.len() {
+        0 => None,
+        3 => Some(temp.parse().ok()?),
+        2 if altitude > 24_000 => Some(-temp.parse::<i8>().ok()?),
+        _ => return None,
+    };
+
+    let (direction_deg, speed_kt) = match (dd, ss) {
+        (99, 0) => (None, 0),
+        (51..=86, _) => (Some((dd - 50) * 10), ss + 100),
+        (0..=36, _) => (Some(dd * 10), ss),
+        _ => return None,
+    };
+    Some(WindAloft {
+        direction_deg,
+        speed_kt,
+        temp_c,
+    })
+}
+
+/// FD station identifier for an airport: the explicit `winds_station`, or
+/// the ICAO code with the leading `K` dropped for contiguous-US airports.
+pub fn winds_station_for(airport: &Airport) -> Option<&str> {
+    if let Some(station) = airport.winds_station.as_deref() {
+        return Some(station);
+    }
+    let code = airport.code.as_str();
+    code.strip_prefix('K').filter(|rest| rest.len() == 3)
+}
+
+/// Color for a wind speed: green when calm, yellow at half of `max_kt`,
+/// red at or above `max_kt`.
+pub fn wind_speed_color(speed_kt: u16, max_kt: u16) -> Color {
+    let max = max_kt.max(1) as u32;
+    let speed = (speed_kt as u32).min(max);
+    let half = max / 2;
+    if speed <= half {
+        COLOR_VFR.blend(COLOR_WIND, (speed * 255 / half.max(1)) as u8)
+    } else {
+        COLOR_WIND.blend(COLOR_IFR, ((speed - half) * 255 / (max - half)) as u8)
+    }
+}
+
+/// Update LED state from winds-aloft forecasts at the configured altitude.
+/// Legend and special codes keep their fixed colors; airports without a forecast go dark.
+pub fn update_leds_from_winds_aloft(
+    led_state: &mut LedState,
+    airports: &[Airport],
+    winds: &HashMap<String, StationWinds>,
+    settings: &Settings,
+) {
+    let cfg = &settings.winds_aloft;
+    for (i, airport) in airports.iter().enumerate().take(led_state.num_leds()) {
+        let color = airport
+            .legend_color
+            .or_else(|| crate::led::special_code_color(&airport.code))
+            .unwrap_or_else(|| {
+                winds_station_for(airport)
+                    .and_then(|s| winds.get(s))
+                    .and_then(|w| w.get(&cfg.altitude_ft))
+                    .map(|w| wind_speed_color(w.speed_kt, cfg.max_kt))
+                    .unwrap_or(COLOR_UNKNOWN)
+            });
+        let _ = led_state.set(i, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_FD: &str = "\
+(Extracted from FBUS31 KWNO 141200)
+FD1US1
+DATA BASED ON 141200Z
+VALID 141800Z   FOR USE 1400-2100Z. TEMPS NEG ABV 24000
+
+FT  3000    6000    9000   12000   18000   24000  30000  34000  39000
+ABQ              2519+12 2631+05 2650-09 2662-21 268236 269245 770655
+SFO 9900 2011+22 2315+16 2519+09 2626-04 2638-16 254831 254339 254550
+";
+
+    fn airport(code: &str) -> Airport {
+        Airport {
+            code: code.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_sample_product() {
+        let winds = parse_winds_aloft(SAMPLE_FD);
+        assert_eq!(winds.len(), 2);
+
+        let sfo = &winds["SFO"];
+        assert_eq!(
+            sfo[&3000],
+            WindAloft {
+                direction_deg: None,
+                speed_kt: 0,
+                temp_c: None
+            }
+        );
+        assert_eq!(
+            sfo[&6000],
+            WindAloft {
+                direction_deg: Some(200),
+                speed_kt: 11,
+                temp_c: Some(22)
+            }
+        );
+        assert_eq!(sfo[&30000].temp_c, Some(-31));
+
+        // ABQ has no 3000/6000 groups (station elevation)
+        let abq = &winds["ABQ"];
+        assert!(!abq.contains_key(&3000));
+        assert_eq!(abq[&9000].speed_kt, 19);
+    }
+
+    #[test]
+    fn parse_high_speed_encoding() {
+        // 77 => direction 270, speed 106
+        let wind = parse_group("770655", 39000).unwrap();
+        assert_eq!(wind.direction_deg, Some(270));
+        assert_eq!(wind.speed_kt, 106);
+        assert_eq!(wind.temp_c, Some(-55));
+    }
+
+    #[test]
+    fn parse_without_header_is_empty() {
+        assert!(parse_winds_aloft("garbage\nmore garbage").is_empty());
+        assert!(parse_group("ZZZZ", 3000).is_none());
+        assert!(parse_group("4020", 3000).is_none());
+    }
+
+    #[test]
+    fn winds_station_mapping() {
+        assert_eq!(winds_station_for(&airport("KSFO")), Some("SFO"));
+        assert_eq!(winds_station_for(&airport("PANC")), None);
+        let explicit = Airport {
+            winds_station: Some("ANC".to_string()),
+            ..airport("PAMR")
+        };
+        assert_eq!(winds_station_for(&explicit), Some("ANC"));
+    }
+
+    #[test]
+    fn wind_speed_color_gradient() {
+        assert_eq!(wind_speed_color(0, 50), COLOR_VFR);
+        assert_eq!(wind_speed_color(25, 50), COLOR_WIND);
+        assert_eq!(wind_speed_color(50, 50), COLOR_IFR);
+        assert_eq!(wind_speed_color(120, 50), COLOR_IFR);
+    }
+
+    #[test]
+    fn update_leds_from_winds() {
+        let airports = vec![airport("VFR"), airport("KSFO"), airport("KXYZ")];
+        let mut state = LedState::new(3, 255);
+        let winds = parse_winds_aloft(SAMPLE_FD);
+        let mut settings = Settings::default();
+        settings.winds_aloft.altitude_ft = 3000;
+
+        update_leds_from_winds_aloft(&mut state, &airports, &winds, &settings);
+
+        assert_eq!(state.get(0).unwrap(), COLOR_VFR); // legend
+        assert_eq!(state.get(1).unwrap(), COLOR_VFR); // light and variable
+        assert_eq!(state.get(2).unwrap(), COLOR_UNKNOWN); // no FD station
+    }
+
+    #[test]
+    fn build_url() {
+        assert_eq!(
+            build_winds_aloft_url(6),
+            "https://aviationweather.gov/api/data/windtemp?region=all&level=low&fcst=06"
+        );
+    }
+}
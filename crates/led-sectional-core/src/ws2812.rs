@@ -0,0 +1,59 @@
+//! WS2812B frames as SPI data, for driving the strip from the SPI
+//! peripheral's MOSI pin when no RMT channel is free. At [`SPI_HZ`] three
+//! SPI bits span one 1.25 us WS2812B bit: `100` sends a 0 and `110` a 1.
+
+use crate::led::Color;
+
+/// SPI clock for the encoding: 417 ns per SPI bit.
+pub const SPI_HZ: u32 = 2_400_000;
+
+/// WS2812B wire order.
+pub fn grb(c: Color) -> [u8; 3] {
+    [c.g, c.r, c.b]
+}
+
+/// Encode `colors` into `out`, replacing its contents, followed by at least
+/// `reset_us` of low output to latch the frame.
+pub fn encode_spi(colors: &[Color], reset_us: u32, out: &mut Vec<u8>) {
+    out.clear();
+    out.reserve(colors.len() * 9);
+    for &color in colors {
+        // 24 WS2812B bits make 72 SPI bits, a whole 9 bytes
+        let mut bits: u128 = 0;
+        for byte in grb(color) {
+            for bit in (0..8).rev() {
+                let pattern = if byte & (1 << bit) != 0 { 0b110 } else { 0b100 };
+                bits = (bits << 3) | pattern;
+            }
+        }
+        out.extend_from_slice(&bits.to_be_bytes()[16 - 9..]);
+    }
+    let reset_bytes = (u64::from(reset_us) * u64::from(SPI_HZ)).div_ceil(8_000_000);
+    out.resize(out.len() + reset_bytes as usize, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_become_spi_patterns() {
+        let mut out = Vec::new();
+        encode_spi(&[Color::new(0, 0x80, 0)], 0, &mut out);
+        // Green first: 1 then seven 0s is 110 100 100 100 100 100 100 100
+        assert_eq!(out[..3], [0b1101_0010, 0b0100_1001, 0b0010_0100]);
+        // Red and blue: all 0s
+        assert_eq!(out[3..9], [0b1001_0010, 0b0100_1001, 0b0010_0100].repeat(2));
+        assert_eq!(out.len(), 9);
+    }
+
+    #[test]
+    fn frame_ends_in_the_reset() {
+        let mut out = vec![0xff; 4];
+        encode_spi(&[Color::new(255, 255, 255); 2], 300, &mut out);
+        assert_eq!(out[..9], [0b1101_1011, 0b0110_1101, 0b1011_0110].repeat(3));
+        // 300 us at 2.4 MHz is 720 bits
+        assert_eq!(out.len(), 18 + 90);
+        assert!(out[18..].iter().all(|&b| b == 0));
+    }
+}
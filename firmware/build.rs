@@ -1,3 +1,69 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use led_sectional_core::asset::{gzip_frame, ASSETS};
+
 fn main() {
     embuild::espidf::sysenv::output();
+    embed_assets();
+    embed_build_info();
+}
+
+/// Set `BUILD_GIT_HASH` and `BUILD_UNIX_TIME` for `system.rs`. Both are
+/// empty when unknown; `SOURCE_DATE_EPOCH` overrides the time, for
+/// reproducible builds.
+fn embed_build_info() {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|out| out.status.success())
+            .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+    };
+    let mut hash = git(&["rev-parse", "--short=12", "HEAD"]).unwrap_or_default();
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"]);
+    if !hash.is_empty() && dirty.is_some_and(|changes| !changes.is_empty()) {
+        hash.push_str("-dirty");
+    }
+    let built_at = env::var("SOURCE_DATE_EPOCH").unwrap_or_else(|_| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs().to_string())
+            .unwrap_or_default()
+    });
+    println!("cargo:rustc-env=BUILD_GIT_HASH={hash}");
+    println!("cargo:rustc-env=BUILD_UNIX_TIME={built_at}");
+
+    // Run again for a new commit or staged change as well as for edits here
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        println!("cargo:rerun-if-changed={git_dir}/HEAD");
+        println!("cargo:rerun-if-changed={git_dir}/index");
+    }
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
+
+/// Gzip each web UI asset into `OUT_DIR` and write `assets.rs`, which
+/// `http.rs` includes to serve them.
+fn embed_assets() {
+    let out = PathBuf::from(env::var_os("OUT_DIR").expect("cargo sets OUT_DIR"));
+    let mut list = String::from("pub static ASSETS: &[EmbeddedAsset] = &[\n");
+    for (i, asset) in ASSETS.iter().enumerate() {
+        let body = asset.body.as_bytes();
+        let gzip = gzip_frame(&miniz_oxide::deflate::compress_to_vec(body, 10), body);
+        let file = format!("asset{i}.gz");
+        fs::write(out.join(&file), &gzip).expect("failed to write a compressed asset");
+        list.push_str(&format!(
+            "    EmbeddedAsset {{ path: {:?}, content_type: {:?}, \
+             gzip: include_bytes!(concat!(env!(\"OUT_DIR\"), \"/{file}\")) }},\n",
+            asset.path, asset.content_type
+        ));
+    }
+    list.push_str("];\n");
+    fs::write(out.join("assets.rs"), list).expect("failed to write assets.rs");
 }
@@ -0,0 +1,56 @@
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sys::{self, EspError};
+use led_sectional_core::auth::Credential;
+use led_sectional_core::seal;
+use log::{error, info};
+
+use crate::storage::Storage;
+use crate::wifi;
+
+const NVS_NAMESPACE: &str = "auth";
+const NVS_KEY_CREDENTIAL: &str = "credential";
+
+/// The credential protecting the HTTP endpoints that change the device, or
+/// None if they are open. A stored credential that can't be opened or parsed
+/// is an error, so the caller can keep the endpoints locked rather than open.
+pub fn load(nvs_partition: EspDefaultNvsPartition) -> Result<Option<Credential>, EspError> {
+    let nvs = Storage::open(nvs_partition, NVS_NAMESPACE)?;
+    let Some(sealed) = nvs.get_blob(NVS_KEY_CREDENTIAL)? else {
+        return Ok(None);
+    };
+    let parsed = seal::open(&wifi::device_key(), &sealed)
+        .and_then(|text| Credential::parse(&text));
+    match parsed {
+        Ok(credential) => Ok(Some(credential)),
+        Err(e) => {
+            error!("Stored HTTP credential is unreadable: {}", e);
+            Err(EspError::from_infallible::<{ sys::ESP_ERR_INVALID_STATE }>())
+        }
+    }
+}
+
+/// Store `credential`, sealed, or remove it with None.
+pub fn save(
+    nvs_partition: EspDefaultNvsPartition,
+    credential: Option<&Credential>,
+) -> Result<(), EspError> {
+    let mut nvs = Storage::open(nvs_partition, NVS_NAMESPACE)?;
+    match credential {
+        Some(credential) => {
+            let mut nonce = [0u8; seal::NONCE_LEN];
+            // SAFETY: fills exactly `nonce.len()` bytes of the live buffer
+            // from the hardware RNG.
+            unsafe {
+                sys::esp_fill_random(nonce.as_mut_ptr() as *mut core::ffi::c_void, nonce.len())
+            };
+            let sealed = seal::seal(&wifi::device_key(), nonce, &credential.to_text());
+            nvs.set_blob(NVS_KEY_CREDENTIAL, &sealed)?;
+            info!("HTTP credential stored in NVS");
+        }
+        None => {
+            nvs.remove(NVS_KEY_CREDENTIAL)?;
+            info!("HTTP credential removed; all endpoints are open");
+        }
+    }
+    Ok(())
+}
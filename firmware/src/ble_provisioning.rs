@@ -0,0 +1,142 @@
+use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::hal::modem::Modem;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sys::{self, esp, EspError};
+use esp_idf_svc::wifi::{Configuration, EspWifi};
+use led_sectional_core::networks::Credentials;
+use log::{info, warn};
+use std::ffi::CString;
+use std::time::{Duration, Instant};
+
+use crate::{provisioning, wifi};
+
+/// Start of the advertised BLE name; the ESP BLE Provisioning apps list
+/// devices whose names start with `PROV_`. The rest matches the setup AP.
+const SERVICE_NAME_PREFIX: &str = "PROV_";
+const PROVISIONING_TIMEOUT_SECS: u64 = 600;
+const POLL: Duration = Duration::from_millis(500);
+
+/// Provision WiFi over Bluetooth LE with ESP-IDF's provisioning manager, as
+/// an alternative to the captive portal.
+///
+/// The phone app sends the credentials, the manager tries them and reports
+/// the result back to the app, and a failure lets the user try again. Once a
+/// network connects it is added to the saved list and the device reboots. If
+/// nothing connects before the timeout the manager is shut down and this
+/// returns.
+pub fn start(
+    modem: Modem,
+    sysloop: EspSystemEventLoop,
+    nvs: EspDefaultNvsPartition,
+    pop: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let name = format!("{SERVICE_NAME_PREFIX}{}", provisioning::setup_ap_ssid());
+    info!("Starting BLE provisioning as {}", name);
+
+    // Brings up the WiFi driver and default netifs that the manager expects
+    let wifi = EspWifi::new(modem, sysloop, Some(nvs.clone()))?;
+
+    let config = sys::wifi_prov_mgr_config_t {
+        // SAFETY: wifi_prov_scheme_ble is an immutable scheme table exported
+        // by the provisioning component; it is only read here.
+        scheme: unsafe { sys::wifi_prov_scheme_ble },
+        // Release classic-BT and BLE memory once provisioning is done
+        scheme_event_handler: sys::wifi_prov_event_handler_t {
+            event_cb: Some(sys::wifi_prov_scheme_ble_event_cb_free_btdm),
+            user_data: std::ptr::null_mut(),
+        },
+        app_event_handler: sys::wifi_prov_event_handler_t {
+            event_cb: None,
+            user_data: std::ptr::null_mut(),
+        },
+    };
+    // SAFETY: called once, before any other wifi_prov_mgr function, with a
+    // fully initialized config that the manager copies.
+    esp!(unsafe { sys::wifi_prov_mgr_init(config) })?;
+
+    let service_name = CString::new(name)?;
+    let pop = pop.map(CString::new).transpose()?;
+    let pop_ptr = pop
+        .as_ref()
+        .map_or(std::ptr::null(), |p| p.as_ptr() as *const core::ffi::c_void);
+    if pop.is_none() {
+        warn!("BLE provisioning has no pop code; anyone nearby can set the WiFi network");
+    }
+    // SAFETY: the strings are NUL-terminated and outlive provisioning, since
+    // this function only returns after deinit; a null service key is allowed
+    // for BLE.
+    esp!(unsafe {
+        sys::wifi_prov_mgr_start_provisioning(
+            sys::wifi_prov_security_WIFI_PROV_SECURITY_1,
+            pop_ptr,
+            service_name.as_ptr(),
+            std::ptr::null(),
+        )
+    })?;
+    info!("Waiting for credentials from the ESP BLE Provisioning app");
+
+    let deadline = Instant::now() + Duration::from_secs(PROVISIONING_TIMEOUT_SECS);
+    while Instant::now() < deadline {
+        std::thread::sleep(POLL);
+        match sta_state() {
+            Ok(sys::wifi_prov_sta_state_t_WIFI_PROV_STA_CONNECTED) => {
+                save_connected_network(&wifi, nvs);
+                // Let the manager tell the app it worked before stopping
+                std::thread::sleep(Duration::from_secs(2));
+                // SAFETY: provisioning has finished; deinit is safe to call
+                // from any task once it has.
+                unsafe { sys::wifi_prov_mgr_deinit() };
+                info!("BLE provisioning complete. Rebooting...");
+                // SAFETY: esp_restart() is always safe to call and triggers a clean reboot.
+                unsafe { sys::esp_restart() };
+            }
+            Ok(sys::wifi_prov_sta_state_t_WIFI_PROV_STA_DISCONNECTED) => {
+                warn!("Provisioned network failed to connect; waiting for new credentials");
+                // SAFETY: only valid after a failed attempt, which is the
+                // state just read; it re-arms the manager for another try.
+                if let Err(e) = esp!(unsafe { sys::wifi_prov_mgr_reset_sm_state_on_failure() }) {
+                    warn!("Failed to reset provisioning state: {:?}", e);
+                }
+            }
+            // Nothing received yet, or still connecting
+            _ => {}
+        }
+    }
+
+    warn!("BLE provisioning timed out after {}s", PROVISIONING_TIMEOUT_SECS);
+    // SAFETY: the manager was initialized above; deinit stops provisioning
+    // and frees it, after which nothing else here touches it.
+    unsafe { sys::wifi_prov_mgr_deinit() };
+    drop(wifi);
+    Ok(())
+}
+
+/// The manager's view of the station: connecting, connected, or failed.
+/// Errors until credentials have been received.
+fn sta_state() -> Result<sys::wifi_prov_sta_state_t, EspError> {
+    let mut state: sys::wifi_prov_sta_state_t = 0;
+    // SAFETY: state is a valid out-pointer for the duration of the call.
+    esp!(unsafe { sys::wifi_prov_mgr_get_wifi_state(&mut state) })?;
+    Ok(state)
+}
+
+/// Copy the network the manager connected to into the saved list, which is
+/// what the normal boot path reads.
+fn save_connected_network(wifi: &EspWifi<'static>, nvs: EspDefaultNvsPartition) {
+    let client = match wifi.get_configuration() {
+        Ok(Configuration::Client(client) | Configuration::Mixed(client, _)) => client,
+        Ok(_) => {
+            warn!("Provisioned WiFi configuration has no station settings");
+            return;
+        }
+        Err(e) => {
+            warn!("Failed to read provisioned WiFi configuration: {:?}", e);
+            return;
+        }
+    };
+    let credentials = Credentials::new(client.ssid.as_str(), client.password.as_str());
+    info!("Provisioned over BLE: {}", credentials.ssid);
+    if let Err(e) = wifi::store_credentials(nvs, credentials) {
+        warn!("Failed to store provisioned credentials: {:?}", e);
+    }
+}
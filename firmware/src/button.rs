@@ -0,0 +1,78 @@
+use esp_idf_svc::hal::gpio::{AnyIOPin, PinDriver, Pull};
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use led_sectional_core::button::{HoldAction, HoldTracker};
+use log::{error, info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::watchdog::{self, Watch};
+use crate::{factory_reset, wifi};
+
+const POLL: Duration = Duration::from_millis(100);
+
+/// Set on a quick press until `take_quick_press`.
+static QUICK_PRESSED: AtomicBool = AtomicBool::new(false);
+
+/// Watch the active-low setup button on GPIO `pin` from a background thread,
+/// from boot onwards. A quick press is recorded for `take_quick_press`;
+/// holding it 5 s and releasing forgets the WiFi networks and reboots into
+/// setup; holding it 10 s factory-resets.
+pub fn watch(pin: u8, nvs: EspDefaultNvsPartition) {
+    let spawned = std::thread::Builder::new()
+        .name("button".into())
+        .stack_size(4096)
+        .spawn(move || {
+            // SAFETY: config validation keeps the button off data_pin, and no
+            // other driver claims this GPIO, so this is its only handle.
+            let pin = unsafe { AnyIOPin::new(pin as i32) };
+            let mut button = match PinDriver::input(pin) {
+                Ok(button) => button,
+                Err(e) => {
+                    error!("Failed to configure setup button: {:?}", e);
+                    return;
+                }
+            };
+            if let Err(e) = button.set_pull(Pull::Up) {
+                warn!("Failed to enable setup button pull-up: {:?}", e);
+            }
+
+            let _watch = Watch::current_task_or_log("button");
+            let mut tracker = HoldTracker::new();
+            loop {
+                watchdog::feed();
+                match tracker.sample(button.is_low(), POLL) {
+                    Some(HoldAction::QuickPress) => QUICK_PRESSED.store(true, Ordering::Relaxed),
+                    Some(HoldAction::ForgetWifi) => forget_wifi(nvs.clone()),
+                    Some(HoldAction::FactoryReset) => {
+                        factory_reset::perform(nvs.clone(), "setup button held")
+                    }
+                    None => {}
+                }
+                std::thread::sleep(POLL);
+            }
+        });
+    if let Err(e) = spawned {
+        error!("Failed to start setup button thread: {}", e);
+    }
+}
+
+/// True once after each quick press of the setup button.
+pub fn take_quick_press() -> bool {
+    QUICK_PRESSED.swap(false, Ordering::Relaxed)
+}
+
+/// Erase the saved networks and reboot into the setup portal, even if
+/// `[wifi]` in the config names a network.
+fn forget_wifi(nvs: EspDefaultNvsPartition) -> ! {
+    warn!("Setup button held: forgetting WiFi networks");
+    if let Err(e) = wifi::clear_credentials(nvs.clone()) {
+        error!("Failed to erase WiFi credentials: {:?}", e);
+    }
+    if let Err(e) = wifi::request_setup_on_boot(nvs) {
+        error!("Failed to flag setup for next boot: {:?}", e);
+    }
+    info!("Rebooting into setup...");
+    std::thread::sleep(Duration::from_millis(500));
+    // SAFETY: esp_restart() is always safe to call and triggers a clean reboot.
+    unsafe { esp_idf_svc::sys::esp_restart() };
+}
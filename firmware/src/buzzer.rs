@@ -0,0 +1,70 @@
+use esp_idf_svc::hal::gpio::AnyOutputPin;
+use esp_idf_svc::hal::ledc::config::TimerConfig;
+use esp_idf_svc::hal::ledc::{LedcDriver, LedcTimerDriver, LEDC};
+use esp_idf_svc::hal::prelude::*;
+use led_sectional_core::home_alert::Alert;
+use log::{error, info, warn};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+
+/// Near the resonance of common passive piezo discs, so chirps are loud.
+const TONE_HZ: u32 = 2_700;
+
+/// Hands alerts to the buzzer thread; None until [`start`] succeeds.
+static CHIRPS: Mutex<Option<Sender<Alert>>> = Mutex::new(None);
+
+/// Drive a passive piezo buzzer on GPIO `pin` with a square wave from the
+/// LEDC peripheral, from a thread that plays each alert's pattern so the
+/// main loop never waits on it.
+pub fn start(ledc: LEDC, pin: u8) {
+    let (tx, rx) = mpsc::channel::<Alert>();
+    let spawned = std::thread::Builder::new()
+        .name("buzzer".into())
+        .stack_size(3072)
+        .spawn(move || {
+            let timer = match LedcTimerDriver::new(
+                ledc.timer0,
+                &TimerConfig::new().frequency(TONE_HZ.Hz().into()),
+            ) {
+                Ok(timer) => timer,
+                Err(e) => {
+                    error!("Failed to set up the buzzer timer: {:?}", e);
+                    return;
+                }
+            };
+            // SAFETY: config validation keeps the buzzer off the other pins
+            // the firmware drives, and nothing else claims it.
+            let pin = unsafe { AnyOutputPin::new(pin as i32) };
+            let mut buzzer = match LedcDriver::new(ledc.channel0, timer, pin) {
+                Ok(buzzer) => buzzer,
+                Err(e) => {
+                    error!("Failed to set up the buzzer: {:?}", e);
+                    return;
+                }
+            };
+            let half = buzzer.get_max_duty() / 2;
+            for alert in rx {
+                // Tone and silence alternate, starting with a tone
+                for (i, length) in alert.pattern().iter().enumerate() {
+                    let duty = if i % 2 == 0 { half } else { 0 };
+                    if let Err(e) = buzzer.set_duty(duty) {
+                        warn!("Buzzer failed: {:?}", e);
+                    }
+                    std::thread::sleep(*length);
+                }
+                let _ = buzzer.set_duty(0);
+            }
+        });
+    match spawned {
+        Ok(_) => *CHIRPS.lock().unwrap() = Some(tx),
+        Err(e) => error!("Failed to start buzzer thread: {}", e),
+    }
+}
+
+/// Chirp for `alert`, unless the buzzer isn't set up.
+pub fn chirp(alert: Alert) {
+    if let Some(tx) = CHIRPS.lock().unwrap().as_ref() {
+        info!("Chirping for {:?} at the home airport", alert);
+        let _ = tx.send(alert);
+    }
+}
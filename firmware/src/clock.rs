@@ -0,0 +1,59 @@
+use esp_idf_svc::sntp::EspSntp;
+use esp_idf_svc::sys::EspError;
+use led_sectional_core::clock::{LocalClock, LocalTime, TimeZone};
+use log::info;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Anything earlier means the RTC hasn't been set since boot.
+const MIN_VALID_UNIX_SECS: i64 = 1_704_067_200; // 2024-01-01
+
+/// Wall-clock time kept in sync by SNTP, converted with the configured zone.
+pub struct SntpClock {
+    _sntp: EspSntp<'static>,
+    tz: TimeZone,
+}
+
+impl SntpClock {
+    /// Start SNTP against the default pool. Call once the network is up.
+    pub fn start(timezone: &str) -> Result<Self, EspError> {
+        let sntp = EspSntp::new_default()?;
+        info!("SNTP started");
+        let mut clock = Self {
+            _sntp: sntp,
+            tz: TimeZone::UTC,
+        };
+        clock.set_time_zone(timezone);
+        Ok(clock)
+    }
+
+    /// Switch to a POSIX `TZ` string; invalid strings fall back to UTC, as
+    /// reported by config validation.
+    pub fn set_time_zone(&mut self, timezone: &str) {
+        let (tz, timezone) = match TimeZone::parse(timezone) {
+            Some(tz) => (tz, timezone),
+            None => (TimeZone::UTC, "UTC0"),
+        };
+        self.tz = tz;
+        set_libc_timezone(timezone);
+    }
+}
+
+impl LocalClock for SntpClock {
+    fn now(&self) -> Option<LocalTime> {
+        unix_now().map(|secs| self.tz.to_local(secs))
+    }
+}
+
+/// The time as Unix seconds, once SNTP (or anything else) has set the RTC.
+pub fn unix_now() -> Option<i64> {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    (secs >= MIN_VALID_UNIX_SECS).then_some(secs)
+}
+
+/// Also hand the zone to newlib so ESP-IDF log timestamps are local.
+fn set_libc_timezone(timezone: &str) {
+    std::env::set_var("TZ", timezone);
+    // SAFETY: tzset() only re-reads the TZ environment variable; nothing else
+    // touches the environment concurrently at this point.
+    unsafe { esp_idf_svc::sys::tzset() };
+}
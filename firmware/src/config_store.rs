@@ -0,0 +1,158 @@
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sys::EspError;
+use led_sectional_core::config::Config;
+use led_sectional_core::config_layer::{self, ConfigLayer};
+use log::{info, warn};
+
+use crate::storage::Storage;
+
+const NVS_NAMESPACE: &str = "config";
+const NVS_KEY_TOML: &str = "toml";
+const NVS_KEY_GENERATION: &str = "gen";
+const NVS_KEY_CHECKSUM: &str = "crc";
+
+/// Runtime config overrides, persisted in NVS so changes made on the device
+/// survive reboots.
+///
+/// This is the top config layer: only values that differ from the embedded
+/// default and `/config.toml` are stored. Callers that replace the file should
+/// `clear()` this store so the new file is picked up as written.
+pub struct ConfigStore {
+    nvs: Storage,
+    generation: u32,
+}
+
+impl ConfigStore {
+    pub const LAYER_NAME: &'static str = "nvs";
+
+    pub fn new(nvs_partition: EspDefaultNvsPartition) -> Result<Self, EspError> {
+        let nvs = Storage::open(nvs_partition, NVS_NAMESPACE)?;
+        let generation = nvs.get_u32(NVS_KEY_GENERATION)?.unwrap_or(0);
+        Ok(Self { nvs, generation })
+    }
+
+    /// Number of times the overrides have been saved; 0 if never.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Load the stored override layer.
+    ///
+    /// Returns [`ConfigStoreError::Corrupt`] if the blob can't be read or no
+    /// longer matches its checksum; callers should treat that as grounds for a
+    /// factory reset. A blob that is intact but doesn't parse (e.g. written by
+    /// older firmware) is erased so the lower layers apply on their own.
+    pub fn load(&mut self) -> Result<Option<ConfigLayer>, ConfigStoreError> {
+        let toml = match self.read_toml() {
+            Ok(Some(toml)) => toml,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(ConfigStoreError::Corrupt(e)),
+        };
+        // Blobs saved before checksums were added have none to check
+        let stored = self.nvs.get_u32(NVS_KEY_CHECKSUM).map_err(ConfigStoreError::Nvs)?;
+        if let Some(expected) = stored {
+            let actual = config_layer::checksum(&toml);
+            if actual != expected {
+                return Err(ConfigStoreError::Corrupt(format!(
+                    "checksum {actual:08x} does not match stored {expected:08x}"
+                )));
+            }
+        }
+
+        match ConfigLayer::parse(Self::LAYER_NAME, &toml) {
+            Ok(layer) => {
+                info!("Loaded config overrides generation {} from NVS", self.generation);
+                Ok(Some(layer))
+            }
+            Err(e) => {
+                warn!(
+                    "Stored config generation {} is unusable ({}); discarding",
+                    self.generation, e
+                );
+                self.discard();
+                Ok(None)
+            }
+        }
+    }
+
+    /// Persist the difference between `config` and the `lower` layers, bumping
+    /// the generation counter. Returns the new generation.
+    pub fn save(&mut self, config: &Config, lower: &[ConfigLayer]) -> Result<u32, ConfigStoreError> {
+        let overrides = ConfigLayer::overrides(Self::LAYER_NAME, config, lower)
+            .map_err(ConfigStoreError::Serialize)?;
+        self.save_layer(&overrides)
+    }
+
+    /// Replace the stored overrides with `layer` as given. Overrides that
+    /// match what is stored aren't written again and keep their generation.
+    pub fn save_layer(&mut self, layer: &ConfigLayer) -> Result<u32, ConfigStoreError> {
+        let toml = layer.to_toml();
+        let written = self
+            .nvs
+            .set_blob(NVS_KEY_TOML, toml.as_bytes())
+            .map_err(ConfigStoreError::Nvs)?;
+        if !written {
+            info!("Config overrides unchanged; keeping generation {}", self.generation);
+            return Ok(self.generation);
+        }
+        let generation = self.generation.wrapping_add(1);
+        self.nvs
+            .set_u32(NVS_KEY_CHECKSUM, config_layer::checksum(&toml))
+            .map_err(ConfigStoreError::Nvs)?;
+        self.nvs
+            .set_u32(NVS_KEY_GENERATION, generation)
+            .map_err(ConfigStoreError::Nvs)?;
+        self.generation = generation;
+        info!("Saved config overrides generation {} to NVS ({} bytes)", generation, toml.len());
+        Ok(generation)
+    }
+
+    /// Remove the stored overrides so the next boot uses the file/default config.
+    pub fn clear(&mut self) -> Result<(), EspError> {
+        self.nvs.remove(NVS_KEY_TOML)?;
+        self.nvs.remove(NVS_KEY_CHECKSUM)?;
+        info!("Cleared stored config overrides");
+        Ok(())
+    }
+
+    /// Remove the overrides and the generation counter, as on a new device.
+    pub fn erase(&mut self) -> Result<(), EspError> {
+        self.clear()?;
+        self.nvs.remove(NVS_KEY_GENERATION)?;
+        self.generation = 0;
+        Ok(())
+    }
+
+    fn read_toml(&self) -> Result<Option<String>, String> {
+        let Some(bytes) = self.nvs.get_blob(NVS_KEY_TOML).map_err(|e| format!("{e:?}"))? else {
+            return Ok(None);
+        };
+        String::from_utf8(bytes).map(Some).map_err(|e| e.to_string())
+    }
+
+    /// Erase overrides that can't be used, logging rather than failing.
+    pub fn discard(&mut self) {
+        if let Err(e) = self.clear() {
+            warn!("Failed to erase corrupt config: {:?}", e);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigStoreError {
+    Serialize(led_sectional_core::error::Error),
+    Nvs(EspError),
+    Corrupt(String),
+}
+
+impl std::fmt::Display for ConfigStoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Serialize(e) => write!(f, "{e}"),
+            Self::Nvs(e) => write!(f, "NVS error: {e:?}"),
+            Self::Corrupt(e) => write!(f, "stored config is corrupt: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigStoreError {}
@@ -0,0 +1,116 @@
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sys::EspError;
+use led_sectional_core::api::Payload;
+use led_sectional_core::crash::CrashReport;
+use log::{error, warn};
+use std::sync::Mutex;
+
+use crate::storage::{Storage, StorageError};
+use crate::{clock, system};
+
+const NVS_NAMESPACE: &str = "crash";
+/// Written by the panic hook, read and removed at the next boot.
+const NVS_KEY_PANIC: &str = "panic_report";
+/// The last crash, kept until the next one.
+const NVS_KEY_LAST: &str = "last_report";
+/// Where older firmware kept the same two reports, as JSON strings.
+const NVS_KEY_LEGACY_PANIC: &str = "panic";
+const NVS_KEY_LEGACY_LAST: &str = "last";
+
+/// The last crash as read at boot, for `GET /api/health`.
+static LAST_CRASH: Mutex<Option<CrashReport>> = Mutex::new(None);
+
+/// Record panics to NVS before the reset, then let the default hook print
+/// them and abort as usual. Call once, early in boot.
+pub fn install_panic_hook(nvs: EspDefaultNvsPartition) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("(no message)");
+        let location = info.location().map(|l| l.to_string());
+        let mut report = CrashReport::panic(message, location, system::FIRMWARE_VERSION);
+        report.uptime_secs = Some(system::uptime_secs());
+        report.at = clock::unix_now();
+        // Best effort: a panic inside NVS itself can't be recorded
+        if let Err(e) = store(nvs.clone(), NVS_KEY_PANIC, &report) {
+            error!("Failed to record the panic: {:?}", e);
+        }
+        default_hook(info);
+    }));
+}
+
+/// Work out whether the last reset was a crash, log it, and keep it as the
+/// last crash if so.
+pub fn check_last_boot(nvs: EspDefaultNvsPartition) {
+    match update_last(nvs) {
+        Ok(last) => *LAST_CRASH.lock().unwrap() = last,
+        Err(e) => warn!("Failed to read the crash report: {:?}", e),
+    }
+}
+
+/// The most recent crash, for `GET /api/health`.
+pub fn last() -> Option<CrashReport> {
+    LAST_CRASH.lock().unwrap().clone()
+}
+
+/// Forget recorded crashes, for a factory reset.
+pub fn clear(nvs: EspDefaultNvsPartition) -> Result<(), EspError> {
+    let mut storage = Storage::open(nvs, NVS_NAMESPACE)?;
+    for key in [NVS_KEY_PANIC, NVS_KEY_LAST, NVS_KEY_LEGACY_PANIC, NVS_KEY_LEGACY_LAST] {
+        storage.remove(key)?;
+    }
+    Ok(())
+}
+
+/// Fold the panic recorded before the reset, if any, into the last crash.
+fn update_last(nvs: EspDefaultNvsPartition) -> Result<Option<CrashReport>, StorageError> {
+    let mut storage = Storage::open(nvs, NVS_NAMESPACE)?;
+    let recorded = read(&mut storage, NVS_KEY_PANIC, NVS_KEY_LEGACY_PANIC)?;
+    storage.remove(NVS_KEY_PANIC)?;
+    let reason = system::reset_reason();
+    match CrashReport::after_reset(recorded, reason, system::FIRMWARE_VERSION) {
+        Some(report) => {
+            storage.set(NVS_KEY_LAST, &report)?;
+            storage.remove(NVS_KEY_LEGACY_LAST)?;
+            warn!("The last run crashed: {}", report.summary());
+            Ok(Some(report))
+        }
+        None => {
+            let last = read(&mut storage, NVS_KEY_LAST, NVS_KEY_LEGACY_LAST)?;
+            if let Some(report) = &last {
+                storage.set(NVS_KEY_LAST, report)?;
+            }
+            Ok(last)
+        }
+    }
+}
+
+fn store(nvs: EspDefaultNvsPartition, key: &str, report: &CrashReport) -> Result<(), StorageError> {
+    Storage::open(nvs, NVS_NAMESPACE)?.set(key, report)?;
+    Ok(())
+}
+
+/// The report under `key`, or failing that one older firmware left under
+/// `legacy_key`, which is removed.
+fn read(
+    storage: &mut Storage,
+    key: &str,
+    legacy_key: &str,
+) -> Result<Option<CrashReport>, StorageError> {
+    match storage.get(key) {
+        Ok(Some(report)) => return Ok(Some(report)),
+        Ok(None) => {}
+        // An unreadable report, say from other firmware, is dropped
+        Err(StorageError::Decode(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let Some(json) = storage.get_str(legacy_key)? else {
+        return Ok(None);
+    };
+    storage.remove(legacy_key)?;
+    Ok(CrashReport::from_json(&json).ok())
+}
@@ -0,0 +1,40 @@
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use log::{error, info, warn};
+use std::time::Duration;
+
+use crate::config_store::ConfigStore;
+use crate::{auth, crash, flash_fs, safe_mode, wifi};
+
+/// Erase WiFi credentials, the HTTP credential, stored config overrides, and
+/// the config files on flash, then reboot. With everything gone the device comes back up on the
+/// embedded default config and starts the captive portal.
+pub fn perform(nvs: EspDefaultNvsPartition, reason: &str) -> ! {
+    warn!("Factory reset: {}", reason);
+
+    if let Err(e) = wifi::clear_credentials(nvs.clone()) {
+        error!("Failed to erase WiFi credentials: {:?}", e);
+    }
+    if let Err(e) = auth::save(nvs.clone(), None) {
+        error!("Failed to erase HTTP credential: {:?}", e);
+    }
+    if let Err(e) = crash::clear(nvs.clone()) {
+        error!("Failed to erase crash reports: {:?}", e);
+    }
+    if let Err(e) = safe_mode::clear(nvs.clone()) {
+        error!("Failed to erase the boot count: {:?}", e);
+    }
+    match ConfigStore::new(nvs) {
+        Ok(mut store) => {
+            if let Err(e) = store.erase() {
+                error!("Failed to erase stored config: {:?}", e);
+            }
+        }
+        Err(e) => error!("Failed to open config store: {:?}", e),
+    }
+    flash_fs::remove_user_files();
+
+    info!("Factory reset complete. Rebooting...");
+    std::thread::sleep(Duration::from_millis(500));
+    // SAFETY: esp_restart() is always safe to call and triggers a clean reboot.
+    unsafe { esp_idf_svc::sys::esp_restart() };
+}
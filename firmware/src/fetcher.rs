@@ -0,0 +1,151 @@
+use led_sectional_core::poller::{FetchJob, FetchResponse};
+use log::{error, warn};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use crate::metar_client::MetarClient;
+use crate::ota;
+use crate::watchdog::{self, Watch};
+
+/// TLS handshakes and METAR parsing; what the main task needed when it
+/// fetched itself.
+const STACK_SIZE: usize = 8 * 1024;
+/// Longest the thread waits for work before feeding the watchdog.
+const IDLE_FEED: Duration = Duration::from_secs(10);
+
+/// Work for the fetch thread, in the order sent.
+enum Request {
+    Fetch(FetchJob),
+    LowMemory(bool),
+    CheckForUpdate(String),
+}
+
+/// Weather fetches and update checks on a thread of their own, so the main
+/// loop keeps animating while a slow TLS request is out. If the thread
+/// can't start, requests run on the caller instead.
+pub struct Fetcher {
+    worker: Worker,
+    /// A response the main loop hasn't taken yet.
+    response: Option<FetchResponse>,
+}
+
+enum Worker {
+    Thread {
+        requests: Sender<Request>,
+        responses: Receiver<FetchResponse>,
+    },
+    Inline(MetarClient),
+}
+
+impl Fetcher {
+    pub fn start() -> Self {
+        let (requests, inbox) = mpsc::channel();
+        let (outbox, responses) = mpsc::channel();
+        let spawned = std::thread::Builder::new()
+            .name("fetch".into())
+            .stack_size(STACK_SIZE)
+            .spawn(move || run(inbox, outbox));
+        let worker = match spawned {
+            Ok(_) => Worker::Thread {
+                requests,
+                responses,
+            },
+            Err(e) => {
+                error!("Failed to start the fetch thread: {}; fetching inline", e);
+                Worker::Inline(MetarClient::new())
+            }
+        };
+        Self {
+            worker,
+            response: None,
+        }
+    }
+
+    /// Make the request in `job`; its response comes from
+    /// [`take_response`](Self::take_response).
+    pub fn start_job(&mut self, job: FetchJob) {
+        match &mut self.worker {
+            Worker::Thread { requests, .. } => send(requests, Request::Fetch(job)),
+            Worker::Inline(client) => self.response = Some(job.run(client)),
+        }
+    }
+
+    pub fn take_response(&mut self) -> Option<FetchResponse> {
+        if let Worker::Thread { responses, .. } = &self.worker {
+            if self.response.is_none() {
+                self.response = responses.try_recv().ok();
+            }
+        }
+        self.response.take()
+    }
+
+    /// Sleep up to `timeout`, waking early when a response comes in.
+    pub fn wait(&mut self, timeout: Duration) {
+        match &self.worker {
+            Worker::Thread { responses, .. } if self.response.is_none() => {
+                match responses.recv_timeout(timeout) {
+                    Ok(response) => self.response = Some(response),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => std::thread::sleep(timeout),
+                }
+            }
+            Worker::Thread { .. } => {}
+            Worker::Inline(_) => std::thread::sleep(timeout),
+        }
+    }
+
+    /// See [`MetarClient::set_low_memory`].
+    pub fn set_low_memory(&mut self, low: bool) {
+        match &mut self.worker {
+            Worker::Thread { requests, .. } => send(requests, Request::LowMemory(low)),
+            Worker::Inline(client) => client.set_low_memory(low),
+        }
+    }
+
+    /// Check the manifest at `url` and install a newer firmware, rebooting
+    /// into it if there is one.
+    pub fn check_for_update(&mut self, url: &str) {
+        match &mut self.worker {
+            Worker::Thread { requests, .. } => {
+                send(requests, Request::CheckForUpdate(url.to_string()))
+            }
+            Worker::Inline(_) => check_for_update(url),
+        }
+    }
+}
+
+fn send(requests: &Sender<Request>, request: Request) {
+    if requests.send(request).is_err() {
+        warn!("The fetch thread is gone; dropping the request");
+    }
+}
+
+fn run(requests: Receiver<Request>, responses: Sender<FetchResponse>) {
+    let _watch = Watch::current_task_or_log("fetch");
+    let mut client = MetarClient::new();
+    loop {
+        watchdog::feed();
+        let request = match requests.recv_timeout(IDLE_FEED) {
+            Ok(request) => request,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        match request {
+            Request::Fetch(job) => {
+                if responses.send(job.run(&mut client)).is_err() {
+                    return;
+                }
+            }
+            Request::LowMemory(low) => client.set_low_memory(low),
+            Request::CheckForUpdate(url) => check_for_update(&url),
+        }
+    }
+}
+
+fn check_for_update(url: &str) {
+    match ota::check_for_update(url, &ota::running_app()) {
+        Ok(Some(_)) => ota::reboot(),
+        Ok(None) => {}
+        Err(e) => warn!("{}", e),
+    }
+}
@@ -0,0 +1,116 @@
+use esp_idf_svc::sys::{esp, esp_vfs_spiffs_conf_t, esp_vfs_spiffs_register, EspError};
+use led_sectional_core::config::Config;
+use log::{info, warn};
+use std::ffi::CStr;
+use std::time::SystemTime;
+
+const BASE_PATH: &CStr = c"/spiffs";
+const PARTITION_LABEL: &CStr = c"storage";
+const MAX_OPEN_FILES: usize = 4;
+
+pub const CONFIG_PATH: &str = "/spiffs/config.toml";
+/// WiFi credentials kept out of `config.toml` so it can be shared as-is.
+pub const SECRETS_PATH: &str = "/spiffs/secrets.toml";
+const CONFIG_TMP_PATH: &str = "/spiffs/config.tmp";
+
+/// Mount the SPIFFS `storage` partition at `/spiffs`, formatting it if it
+/// has never been initialized.
+pub fn mount() -> Result<(), EspError> {
+    let conf = esp_vfs_spiffs_conf_t {
+        base_path: BASE_PATH.as_ptr(),
+        partition_label: PARTITION_LABEL.as_ptr(),
+        max_files: MAX_OPEN_FILES,
+        format_if_mount_failed: true,
+    };
+    // SAFETY: `conf` and the static C strings it points to outlive the call;
+    // ESP-IDF copies what it needs during registration.
+    esp!(unsafe { esp_vfs_spiffs_register(&conf) })?;
+    info!("Mounted SPIFFS at {}", BASE_PATH.to_string_lossy());
+    Ok(())
+}
+
+/// Read the user config from flash. Returns None if no file has been written.
+pub fn read_config() -> Option<String> {
+    read_file(CONFIG_PATH)
+}
+
+/// Read `/secrets.toml`, if present.
+pub fn read_secrets() -> Option<String> {
+    read_file(SECRETS_PATH)
+}
+
+/// Delete `/config.toml` and `/secrets.toml` so the embedded default applies.
+pub fn remove_user_files() {
+    for path in [CONFIG_PATH, SECRETS_PATH] {
+        match std::fs::remove_file(path) {
+            Ok(()) => info!("Removed {}", path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove {}: {}", path, e),
+        }
+    }
+}
+
+fn read_file(path: &str) -> Option<String> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => Some(s),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            warn!("Failed to read {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Validate and store a new config. The file is written to a temporary path
+/// and renamed so a power loss mid-write can't leave a truncated config.
+pub fn write_config(toml: &str) -> Result<Config, ConfigWriteError> {
+    let config = Config::from_toml(toml).map_err(ConfigWriteError::Invalid)?;
+    std::fs::write(CONFIG_TMP_PATH, toml).map_err(ConfigWriteError::Io)?;
+    std::fs::rename(CONFIG_TMP_PATH, CONFIG_PATH).map_err(ConfigWriteError::Io)?;
+    info!("Wrote {} ({} bytes)", CONFIG_PATH, toml.len());
+    Ok(config)
+}
+
+/// Detects changes to `/config.toml` by polling its size and modification time.
+pub struct ConfigWatcher {
+    last: Option<(u64, Option<SystemTime>)>,
+}
+
+impl ConfigWatcher {
+    /// Start watching, treating the file's current state as already loaded.
+    pub fn new() -> Self {
+        Self { last: stat_config() }
+    }
+
+    /// Returns true once each time the file is written, replaced, or removed.
+    pub fn changed(&mut self) -> bool {
+        let current = stat_config();
+        if current == self.last {
+            return false;
+        }
+        self.last = current;
+        true
+    }
+}
+
+fn stat_config() -> Option<(u64, Option<SystemTime>)> {
+    let meta = std::fs::metadata(CONFIG_PATH).ok()?;
+    Some((meta.len(), meta.modified().ok()))
+}
+
+#[derive(Debug)]
+pub enum ConfigWriteError {
+    Invalid(led_sectional_core::error::Error),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for ConfigWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid(e) => write!(f, "invalid config: {e}"),
+            Self::Io(e) => write!(f, "flash write error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigWriteError {}
@@ -0,0 +1,226 @@
+use esp_idf_svc::http::server::{
+    Configuration as HttpConfig, EspHttpConnection, EspHttpServer, Request, Response,
+};
+use esp_idf_svc::http::{Headers, Method};
+use esp_idf_svc::io::{EspIOError, Read, Write};
+use esp_idf_svc::sys::EspError;
+use led_sectional_core::cors::CorsPolicy;
+use led_sectional_core::{asset, auth};
+use std::sync::{Mutex, PoisonError};
+
+/// Which other sites' pages may call the API; set from the config.
+static CORS: Mutex<CorsPolicy> = Mutex::new(CorsPolicy::disabled());
+
+/// Start a server whose routes may end in `/*`, so a [`fallback`] can catch
+/// what the others don't. `stack_size` overrides the default 6 KB for
+/// handlers that need more.
+pub fn server(stack_size: Option<usize>) -> Result<EspHttpServer<'static>, EspError> {
+    let defaults = HttpConfig::default();
+    EspHttpServer::new(&HttpConfig {
+        stack_size: stack_size.unwrap_or(defaults.stack_size),
+        uri_match_wildcard: true,
+        ..defaults
+    })
+}
+
+/// Allow the pages of other origins to call the API, as
+/// `settings.http.cors_origins` lists; replaces the previous policy.
+pub fn set_cors(policy: CorsPolicy) {
+    *CORS.lock().unwrap_or_else(PoisonError::into_inner) = policy;
+}
+
+/// Answer CORS preflights for every path, so browsers let allowed origins
+/// send the credential and JSON bodies.
+pub fn handle_preflight(server: &mut EspHttpServer<'static>) -> Result<(), EspError> {
+    server.fn_handler("/*", Method::Options, |req| -> Result<(), EspIOError> {
+        let headers = CORS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .preflight_headers(req.header("Origin"));
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        req.into_response(204, None, &headers)?;
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Start a response, adding the CORS headers the request's origin gets.
+/// Every response goes through here so none is missing them.
+pub fn response<'r, 'c>(
+    req: Request<&'r mut EspHttpConnection<'c>>,
+    status: u16,
+    headers: &[(&str, &str)],
+) -> Result<Response<&'r mut EspHttpConnection<'c>>, EspIOError> {
+    let cors = CORS.lock().unwrap_or_else(PoisonError::into_inner).headers(req.header("Origin"));
+    let mut all = headers.to_vec();
+    all.extend(cors.iter().map(|(k, v)| (*k, v.as_str())));
+    req.into_response(status, None, &all)
+}
+
+/// A web UI file, gzipped by `build.rs` from `led_sectional_core::asset`.
+pub struct EmbeddedAsset {
+    pub path: &'static str,
+    pub content_type: &'static str,
+    pub gzip: &'static [u8],
+}
+
+include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+
+/// Serve every embedded asset at its path, compressed. The rare client
+/// that doesn't accept gzip gets it inflated on the fly.
+pub fn serve_assets(server: &mut EspHttpServer<'static>) -> Result<(), EspError> {
+    for embedded in ASSETS {
+        server.fn_handler(embedded.path, Method::Get, move |req| -> Result<(), EspIOError> {
+            if asset::accepts_gzip(req.header("Accept-Encoding")) {
+                let mut resp = response(
+                    req,
+                    200,
+                    &[
+                        ("Content-Type", embedded.content_type),
+                        ("Content-Encoding", "gzip"),
+                        ("Vary", "Accept-Encoding"),
+                    ],
+                )?;
+                resp.write_all(embedded.gzip)?;
+                return Ok(());
+            }
+            match asset::gunzip(embedded.gzip, miniz_oxide::inflate::decompress_to_vec) {
+                Ok(body) => send(req, 200, embedded.content_type, &body),
+                Err(_) => respond(req, 500, "asset is corrupt"),
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// Answer every GET the other routes didn't match: a redirect to where
+/// `location` says, or 404 when it says None. Register it last, as routes
+/// match in the order they were added.
+pub fn fallback<F>(server: &mut EspHttpServer<'static>, location: F) -> Result<(), EspError>
+where
+    F: Fn() -> Option<String> + Send + 'static,
+{
+    server.fn_handler("/*", Method::Get, move |req| -> Result<(), EspIOError> {
+        match location() {
+            Some(location) => redirect(req, &location),
+            None => respond(req, 404, "not found"),
+        }
+    })?;
+    Ok(())
+}
+
+/// Read the whole request body, or None if it exceeds `limit` bytes.
+pub fn read_body(
+    req: &mut Request<&mut EspHttpConnection>,
+    limit: usize,
+) -> Result<Option<Vec<u8>>, EspIOError> {
+    let mut body = Vec::new();
+    let mut buf = [0u8; 1024];
+    loop {
+        let n = req.read(&mut buf)?;
+        if n == 0 {
+            return Ok(Some(body));
+        }
+        if body.len() + n > limit {
+            return Ok(None);
+        }
+        body.extend_from_slice(&buf[..n]);
+    }
+}
+
+pub fn send(
+    req: Request<&mut EspHttpConnection>,
+    status: u16,
+    content_type: &str,
+    body: &[u8],
+) -> Result<(), EspIOError> {
+    let mut resp = response(req, status, &[("Content-Type", content_type)])?;
+    resp.write_all(body)?;
+    Ok(())
+}
+
+/// A plain text answer, for API results and errors.
+pub fn respond(
+    req: Request<&mut EspHttpConnection>,
+    status: u16,
+    message: &str,
+) -> Result<(), EspIOError> {
+    send(req, status, "text/plain", message.as_bytes())
+}
+
+pub fn html(req: Request<&mut EspHttpConnection>, page: &str) -> Result<(), EspIOError> {
+    send(req, 200, "text/html; charset=utf-8", page.as_bytes())
+}
+
+pub fn json(req: Request<&mut EspHttpConnection>, json: &str) -> Result<(), EspIOError> {
+    send(req, 200, "application/json", json.as_bytes())
+}
+
+pub fn redirect(req: Request<&mut EspHttpConnection>, location: &str) -> Result<(), EspIOError> {
+    response(req, 302, &[("Location", location)])?;
+    Ok(())
+}
+
+/// Refuse a request that lacks the credential, prompting browsers to log in.
+pub fn unauthorized(req: Request<&mut EspHttpConnection>) -> Result<(), EspIOError> {
+    let mut resp = response(
+        req,
+        401,
+        &[("Content-Type", "text/plain"), ("WWW-Authenticate", auth::CHALLENGE)],
+    )?;
+    resp.write_all(b"this endpoint needs the device's credential")?;
+    Ok(())
+}
+
+/// The fields of a form-urlencoded body, in order, with values decoded.
+pub fn form_fields(body: &str) -> Vec<(&str, String)> {
+    body.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key, url_decode(value)))
+        .collect()
+}
+
+/// Decode %XX escapes and + for spaces. Escapes are collected as bytes, so
+/// multi-byte UTF-8 such as an SSID with an emoji comes out whole.
+pub fn url_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let byte = s
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match byte {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Escape text for inclusion in HTML; SSIDs can contain any characters.
+pub fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
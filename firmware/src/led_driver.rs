@@ -1,31 +1,261 @@
-use esp_idf_svc::hal::gpio::OutputPin;
+use esp_idf_svc::hal::gpio::{AnyIOPin, AnyOutputPin, OutputPin};
+use esp_idf_svc::hal::peripheral::Peripheral;
+use esp_idf_svc::hal::rmt::config::TransmitConfig;
+use esp_idf_svc::hal::rmt::{PinState, Pulse, RmtChannel, TxRmtDriver, VariableLengthSignal, RMT};
+use esp_idf_svc::hal::spi::config::{Config as SpiConfig, DriverConfig};
+use esp_idf_svc::hal::spi::{Dma, SpiDeviceDriver, SpiDriver, SPI2};
+use esp_idf_svc::hal::units::Hertz;
+use esp_idf_svc::sys::{self, EspError};
+use led_sectional_core::chip::Chip;
+use led_sectional_core::config::{LedSettings, Settings};
 use led_sectional_core::led::{Color, LedState};
-use smart_leds::SmartLedsWrite;
-use smart_leds::RGB8;
-use ws2812_esp32_rmt_driver::Ws2812Esp32Rmt;
+use led_sectional_core::ws2812;
+use log::{debug, error, info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
 
+use crate::power;
+
+// WS2812B bit timings
+const T0H: Duration = Duration::from_nanos(400);
+const T0L: Duration = Duration::from_nanos(850);
+const T1H: Duration = Duration::from_nanos(800);
+const T1L: Duration = Duration::from_nanos(450);
+
+/// The strip [`show`] writes to, once [`start`] has set it up.
+static STRIP: Mutex<Option<LedDriver>> = Mutex::new(None);
+/// Set after the first failed write, so a broken strip logs once.
+static WRITE_FAILED: AtomicBool = AtomicBool::new(false);
+/// Whether frames are being dimmed to fit `settings.max_current_ma`.
+static CURRENT_LIMITED: AtomicBool = AtomicBool::new(false);
+
+/// Set up the strip on GPIO `settings.data_pin` with `[settings.led]`.
+/// Without it the map keeps running, web UI and MQTT included, with the
+/// LEDs dark. A new `data_pin` or `[settings.led]` takes a restart.
+pub fn start(rmt: RMT, spi: SPI2, settings: &Settings) {
+    let gpio = settings.data_pin;
+    if u32::from(gpio) >= sys::gpio_num_t_GPIO_NUM_MAX as u32 {
+        error!("data_pin GPIO{} doesn't exist on this chip; LEDs disabled", gpio);
+        return;
+    }
+    // The pin number is in range, config validation keeps the button off
+    // data_pin, and no other driver claims it, so the driver's is its only
+    // handle.
+    match LedDriver::from_settings(rmt, spi, gpio, &settings.led) {
+        Ok(driver) => {
+            info!("LED strip on GPIO{} over {}", gpio, driver.backend_name());
+            *STRIP.lock().unwrap_or_else(PoisonError::into_inner) = Some(driver);
+        }
+        Err(e) => error!("Failed to start the LED strip on GPIO{}: {:?}", gpio, e),
+    }
+}
+
+/// Write `state` to the strip, if it started.
+pub fn show(state: &LedState) {
+    let mut strip = STRIP.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some(driver) = strip.as_mut() else {
+        return;
+    };
+    let over = state.over_current_limit();
+    if over.is_some() != CURRENT_LIMITED.swap(over.is_some(), Ordering::Relaxed) {
+        match over {
+            Some(ma) => debug!("LED frame would draw about {} mA; dimmed to max_current_ma", ma),
+            None => debug!("LED frames fit max_current_ma again"),
+        }
+    }
+    match power::while_writing_strip(|| driver.write(state)) {
+        Ok(()) => WRITE_FAILED.store(false, Ordering::Relaxed),
+        Err(e) if !WRITE_FAILED.swap(true, Ordering::Relaxed) => {
+            warn!("Failed to write the LED strip: {:?}", e);
+        }
+        Err(_) => {}
+    }
+}
+
+/// How frames reach the strip.
+trait LedBackend: Send {
+    fn write(&mut self, colors: &[Color]) -> Result<(), EspError>;
+    fn name(&self) -> &'static str;
+}
+
+/// WS2812B driver on an RMT channel, or over SPI on chips where none is
+/// free. Either way it encodes GRB frames itself so the reset pulse can be
+/// lengthened.
 pub struct LedDriver {
-    driver: Ws2812Esp32Rmt,
+    backend: Box<dyn LedBackend>,
 }
 
 impl LedDriver {
-    pub fn new(pin: impl OutputPin, channel: u8) -> Result<Self, ws2812_esp32_rmt_driver::LedPixelError> {
-        let driver = Ws2812Esp32Rmt::new(channel, pin.pin())?;
-        Ok(Self { driver })
+    /// Drive `pin` from `channel` with the options in `[settings.led]`; the
+    /// `rmt_channel` key is ignored here, see [`LedDriver::from_settings`].
+    pub fn rmt<C: RmtChannel>(
+        channel: impl Peripheral<P = C> + 'static,
+        pin: impl Peripheral<P = impl OutputPin> + 'static,
+        options: &LedSettings,
+    ) -> Result<Self, EspError> {
+        let backend = RmtBackend::new(channel, pin, options)?;
+        Ok(Self {
+            backend: Box::new(backend),
+        })
+    }
+
+    /// Drive `pin` from the MOSI line of `spi`. The line idles low, so
+    /// `options.invert` isn't supported.
+    pub fn spi(
+        spi: impl Peripheral<P = SPI2> + 'static,
+        pin: impl Peripheral<P = impl OutputPin> + 'static,
+        options: &LedSettings,
+    ) -> Result<Self, EspError> {
+        let backend = SpiBackend::new(spi, pin, options)?;
+        Ok(Self {
+            backend: Box::new(backend),
+        })
+    }
+
+    /// Drive GPIO `gpio` from the RMT channel numbered by
+    /// `options.rmt_channel`, or from SPI if that channel isn't available.
+    pub fn from_settings(
+        rmt: RMT,
+        spi: SPI2,
+        gpio: u8,
+        options: &LedSettings,
+    ) -> Result<Self, EspError> {
+        // SAFETY: the caller checked `gpio` and holds no other handle to it
+        let rmt_result = match options.rmt_channel {
+            0 => Self::rmt(rmt.channel0, unsafe { output_pin(gpio) }, options),
+            1 => Self::rmt(rmt.channel1, unsafe { output_pin(gpio) }, options),
+            // The S3 transmits on channels 0-3, the C3 only on 0 and 1
+            #[cfg(feature = "esp32s3")]
+            2 => Self::rmt(rmt.channel2, unsafe { output_pin(gpio) }, options),
+            #[cfg(feature = "esp32s3")]
+            3 => Self::rmt(rmt.channel3, unsafe { output_pin(gpio) }, options),
+            other => {
+                warn!("RMT channel {} can't transmit on the {}", other, Chip::TARGET.name());
+                Err(EspError::from_infallible::<{ sys::ESP_ERR_NOT_FOUND }>())
+            }
+        };
+        match rmt_result {
+            Ok(driver) => Ok(driver),
+            Err(e) if options.invert => Err(e),
+            Err(e) => {
+                warn!("RMT unavailable ({:?}); driving the strip over SPI", e);
+                // SAFETY: the failed RMT attempt dropped the first handle
+                Self::spi(spi, unsafe { output_pin(gpio) }, options)
+            }
+        }
     }
 
     /// Write the current LED state to the hardware strip.
-    pub fn write(&mut self, state: &LedState) -> Result<(), ws2812_esp32_rmt_driver::LedPixelError> {
-        let buf = state.brightness_scaled_buffer();
-        let pixels: Vec<RGB8> = buf.iter().map(|c| to_rgb8(*c)).collect();
-        self.driver.write(pixels.into_iter())
+    pub fn write(&mut self, state: &LedState) -> Result<(), EspError> {
+        self.backend.write(&state.brightness_scaled_buffer())
+    }
+
+    /// The peripheral driving the strip, for the log.
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
     }
 }
 
-fn to_rgb8(c: Color) -> RGB8 {
-    RGB8 {
-        r: c.r,
-        g: c.g,
-        b: c.b,
+/// # Safety
+///
+/// `gpio` must exist on this chip and have no other live handle.
+unsafe fn output_pin(gpio: u8) -> AnyOutputPin {
+    AnyOutputPin::new(gpio as i32)
+}
+
+struct RmtBackend {
+    tx: TxRmtDriver<'static>,
+    zero: [Pulse; 2],
+    one: [Pulse; 2],
+    reset: Pulse,
+}
+
+impl RmtBackend {
+    fn new<C: RmtChannel>(
+        channel: impl Peripheral<P = C> + 'static,
+        pin: impl Peripheral<P = impl OutputPin> + 'static,
+        options: &LedSettings,
+    ) -> Result<Self, EspError> {
+        let (high, low) = if options.invert {
+            (PinState::Low, PinState::High)
+        } else {
+            (PinState::High, PinState::Low)
+        };
+        // 80 MHz APB clock undivided: 12.5 ns per tick
+        let config = TransmitConfig::new().clock_divider(1).idle(Some(low));
+        let tx = TxRmtDriver::new(channel, pin, &config)?;
+
+        let ticks = tx.counter_clock()?;
+        let pulse = |state, duration| Pulse::new_with_duration(ticks, state, &duration);
+        Ok(Self {
+            zero: [pulse(high, T0H)?, pulse(low, T0L)?],
+            one: [pulse(high, T1H)?, pulse(low, T1L)?],
+            reset: pulse(low, Duration::from_micros(options.reset_us as u64))?,
+            tx,
+        })
+    }
+}
+
+impl LedBackend for RmtBackend {
+    fn write(&mut self, colors: &[Color]) -> Result<(), EspError> {
+        let mut signal = VariableLengthSignal::with_capacity(colors.len() * 24 + 1);
+        for &color in colors {
+            for byte in ws2812::grb(color) {
+                for bit in (0..8).rev() {
+                    let pulses = if byte & (1 << bit) != 0 { &self.one } else { &self.zero };
+                    signal.push(pulses)?;
+                }
+            }
+        }
+        signal.push([&self.reset])?;
+        self.tx.start_blocking(&signal)
+    }
+
+    fn name(&self) -> &'static str {
+        "RMT"
+    }
+}
+
+/// Frames encoded by [`ws2812::encode_spi`] and clocked out of MOSI; SCLK
+/// and CS aren't connected.
+struct SpiBackend {
+    device: SpiDeviceDriver<'static, SpiDriver<'static>>,
+    /// Reused between writes so a frame doesn't allocate.
+    frame: Vec<u8>,
+    reset_us: u32,
+}
+
+impl SpiBackend {
+    fn new(
+        spi: impl Peripheral<P = SPI2> + 'static,
+        pin: impl Peripheral<P = impl OutputPin> + 'static,
+        options: &LedSettings,
+    ) -> Result<Self, EspError> {
+        // DMA, since a frame is 9 bytes per LED
+        let driver = SpiDriver::new_without_sclk(
+            spi,
+            pin,
+            Option::<AnyIOPin>::None,
+            &DriverConfig::new().dma(Dma::Auto(4096)),
+        )?;
+        let config = SpiConfig::new().baudrate(Hertz(ws2812::SPI_HZ));
+        let device = SpiDeviceDriver::new(driver, Option::<AnyOutputPin>::None, &config)?;
+        Ok(Self {
+            device,
+            frame: Vec::new(),
+            reset_us: options.reset_us as u32,
+        })
+    }
+}
+
+impl LedBackend for SpiBackend {
+    fn write(&mut self, colors: &[Color]) -> Result<(), EspError> {
+        ws2812::encode_spi(colors, self.reset_us, &mut self.frame);
+        self.device.write(&self.frame)
+    }
+
+    fn name(&self) -> &'static str {
+        "SPI"
     }
 }
@@ -0,0 +1,210 @@
+use esp_idf_svc::sys;
+use led_sectional_core::events::{Event, Subscriber};
+use led_sectional_core::log_event::LogEvent;
+use led_sectional_core::log_filter::LogFilter;
+use led_sectional_core::log_ring::LogRing;
+use led_sectional_core::metar::FlightCategory;
+use led_sectional_core::syslog;
+use led_sectional_core::wifi_link::LinkState;
+use log::{info, warn, LevelFilter, Log, Metadata, Record};
+use std::ffi::{CStr, CString};
+use std::io::Write;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Mutex, PoisonError};
+
+use crate::clock;
+
+/// Lines kept for `GET /api/logs`; at most about 16 KB of heap.
+pub const LOG_LINES: usize = 80;
+
+static RING: Mutex<LogRing> = Mutex::new(LogRing::new(LOG_LINES));
+static FILTER: Mutex<LogFilter> = Mutex::new(LogFilter::new(LevelFilter::Info));
+static LOGGER: RingLogger = RingLogger;
+static SYSLOG: Mutex<Option<Syslog>> = Mutex::new(None);
+
+/// Where log lines are shipped, with `settings.syslog.server`.
+struct Syslog {
+    socket: UdpSocket,
+    server: SocketAddr,
+    hostname: String,
+}
+
+/// Logs to the console in ESP-IDF's format and keeps a copy of the latest
+/// lines, both through the [`set_filter`] filter.
+struct RingLogger;
+
+impl Log for RingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let filter = FILTER.lock().unwrap_or_else(PoisonError::into_inner);
+        filter.enabled(metadata.level(), metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let level = record.level().as_str().chars().next().unwrap_or('?');
+        // SAFETY: returns a NUL-terminated string in a buffer ESP-IDF keeps;
+        // it's copied out straight away.
+        let timestamp = unsafe { CStr::from_ptr(sys::esp_log_system_timestamp()) };
+        // Console output is best effort; there's nowhere to report it failing
+        let _ = writeln!(
+            std::io::stdout(),
+            "{} ({}) {}: {}",
+            level,
+            timestamp.to_string_lossy(),
+            record.target(),
+            record.args()
+        );
+        // SAFETY: reads the monotonic boot timer; no preconditions.
+        let micros = unsafe { sys::esp_timer_get_time() };
+        let module = record.target().rsplit("::").next().unwrap_or_default();
+        let line = format!(
+            "{}.{:03} {} {}: {}",
+            micros / 1_000_000,
+            micros / 1_000 % 1_000,
+            level,
+            module,
+            record.args()
+        );
+        if let Some(sink) = SYSLOG.lock().unwrap_or_else(PoisonError::into_inner).as_ref() {
+            let text = record.args().to_string();
+            let message =
+                syslog::message(record.level(), &sink.hostname, module, clock::unix_now(), &text);
+            // Dropped while offline or if the collector is gone, like any UDP
+            let _ = sink.socket.send_to(message.as_bytes(), sink.server);
+        }
+        RING.lock().unwrap_or_else(PoisonError::into_inner).push(line);
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Install the logger in place of `EspLogger::initialize_default`, logging
+/// at info until [`set_filter`]. Only messages from Rust code are kept;
+/// ESP-IDF's own components still log to the console alone.
+pub fn initialize() {
+    log::set_logger(&LOGGER).expect("logger is only installed once");
+    set_filter(LogFilter::default());
+}
+
+/// Log through `filter` from now on. Its levels apply to ESP-IDF's
+/// components too, by their log tags (such as `wifi`), though those never
+/// log more than info.
+pub fn set_filter(filter: LogFilter) {
+    let mut current = FILTER.lock().unwrap_or_else(PoisonError::into_inner);
+    // Tags the old filter set go back to the default
+    for (module, _) in current.modules() {
+        set_idf_level(module, filter.default_level());
+    }
+    set_idf_level("*", filter.default_level());
+    for (module, level) in filter.modules() {
+        set_idf_level(module, level);
+    }
+    log::set_max_level(filter.max_level());
+    *current = filter;
+}
+
+/// Send log lines to `server` as RFC 5424 syslog over UDP from now on, or
+/// stop if None. Needs the network stack, so call it once WiFi is set up.
+pub fn set_syslog(server: Option<SocketAddr>, hostname: &str) {
+    let sink = server.and_then(|server| {
+        // Never block a logging task on a full send buffer
+        let socket = UdpSocket::bind("0.0.0.0:0").and_then(|socket| {
+            socket.set_nonblocking(true)?;
+            Ok(socket)
+        });
+        match socket {
+            Ok(socket) => Some(Syslog {
+                socket,
+                server,
+                hostname: hostname.to_string(),
+            }),
+            Err(e) => {
+                warn!("Failed to open the syslog socket: {}", e);
+                None
+            }
+        }
+    });
+    let shipping = sink.as_ref().map(|sink| sink.server);
+    *SYSLOG.lock().unwrap_or_else(PoisonError::into_inner) = sink;
+    if let Some(server) = shipping {
+        info!("Sending logs to syslog at {}", server);
+    }
+}
+
+/// The filter in use, as `settings.log_level` would write it.
+pub fn filter() -> String {
+    FILTER.lock().unwrap_or_else(PoisonError::into_inner).to_string()
+}
+
+fn set_idf_level(tag: &str, level: LevelFilter) {
+    let level = match level {
+        LevelFilter::Off => sys::esp_log_level_t_ESP_LOG_NONE,
+        LevelFilter::Error => sys::esp_log_level_t_ESP_LOG_ERROR,
+        LevelFilter::Warn => sys::esp_log_level_t_ESP_LOG_WARN,
+        LevelFilter::Info => sys::esp_log_level_t_ESP_LOG_INFO,
+        LevelFilter::Debug => sys::esp_log_level_t_ESP_LOG_DEBUG,
+        LevelFilter::Trace => sys::esp_log_level_t_ESP_LOG_VERBOSE,
+    };
+    // Module names can't hold a NUL; LogFilter::parse reads them from text
+    let Ok(tag) = CString::new(tag) else {
+        return;
+    };
+    // SAFETY: `tag` is NUL-terminated; ESP-IDF copies it.
+    unsafe { sys::esp_log_level_set(tag.as_ptr(), level) };
+}
+
+/// Keep at most `lines` from now on, dropping the oldest beyond it.
+pub fn set_capacity(lines: usize) {
+    RING.lock().unwrap_or_else(PoisonError::into_inner).set_capacity(lines);
+}
+
+/// The kept lines, oldest first, each prefixed with seconds since boot.
+pub fn recent() -> String {
+    RING.lock().unwrap_or_else(PoisonError::into_inner).to_text()
+}
+
+/// Log `event` as a JSON line with the target `event`, when built with the
+/// `structured-logs` feature; the plain messages say the same for people.
+pub fn emit(event: LogEvent) {
+    if cfg!(feature = "structured-logs") {
+        log::log!(target: "event", event.code.level(), "{}", event.to_json(clock::unix_now()));
+    }
+}
+
+/// Logs what changed on the map and the WiFi link, as the main loop
+/// announces it.
+#[derive(Default)]
+pub struct EventLog {
+    worst: Option<FlightCategory>,
+    was_online: bool,
+}
+
+impl Subscriber for EventLog {
+    fn on_event(&mut self, event: &Event) {
+        if let Some(structured) = LogEvent::from_event(event) {
+            emit(structured);
+        }
+        match event {
+            Event::MetarUpdated(snapshot) if snapshot.error.is_none() => {
+                let worst = snapshot.rows.iter().filter_map(|row| row.category).max();
+                if worst != std::mem::replace(&mut self.worst, worst) {
+                    if let Some(worst) = worst {
+                        info!("Worst category on map: {}", worst.as_str());
+                    }
+                }
+            }
+            Event::MetarUpdated(_) => {}
+            Event::WifiStateChanged(state) => {
+                let online = matches!(state, LinkState::Online { .. });
+                if std::mem::replace(&mut self.was_online, online) && !online {
+                    warn!("WiFi connection lost");
+                }
+            }
+            Event::BrightnessChanged(level) => info!("Brightness now {}", level),
+        }
+    }
+}
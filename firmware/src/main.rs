@@ -1,34 +1,156 @@
+mod auth;
+mod ble_provisioning;
+mod button;
+mod buzzer;
+mod clock;
+mod config_store;
+mod crash;
+mod factory_reset;
+mod fetcher;
+mod flash_fs;
+mod http;
 mod led_driver;
+mod logging;
+mod mdns;
 mod metar_client;
+mod motion;
+mod mqtt;
+mod oled;
+mod ota;
+mod power;
 mod provisioning;
+mod safe_mode;
+mod sleep;
+mod smartconfig;
+mod storage;
+mod system;
+mod user_button;
+mod watchdog;
+mod web;
 mod wifi;
+mod wifi_events;
+mod wps;
+
+#[cfg(all(feature = "esp32c3", feature = "esp32s3"))]
+compile_error!("enable only one of the esp32c3 and esp32s3 features");
+#[cfg(not(any(feature = "esp32c3", feature = "esp32s3")))]
+compile_error!("enable the esp32c3 or esp32s3 feature for the target chip");
+#[cfg(all(feature = "esp32c3", not(target_arch = "riscv32")))]
+compile_error!("the esp32c3 feature needs --target riscv32imc-esp-espidf");
+#[cfg(all(feature = "esp32s3", not(target_arch = "xtensa")))]
+compile_error!("the esp32s3 feature needs --target xtensa-esp32s3-espidf");
 
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::hal::prelude::*;
+use esp_idf_svc::mdns::EspMdns;
 use esp_idf_svc::nvs::EspDefaultNvsPartition;
-use led_sectional_core::config::Config;
-use led_sectional_core::led::{
-    update_leds_from_metars, LedState, COLOR_CONNECTED, COLOR_CONNECTING, COLOR_FETCH_ERROR,
-};
-use led_sectional_core::metar;
+use led_sectional_core::airport_editor;
+use led_sectional_core::backoff::Backoff;
+use led_sectional_core::chip::Chip;
+use led_sectional_core::clock::LocalClock;
+use led_sectional_core::commands::{self, Command};
+use led_sectional_core::config::{Config, ProvisioningMethod, Severity};
+use led_sectional_core::config_layer::ConfigLayer;
+use led_sectional_core::dashboard::MapSnapshot;
+use led_sectional_core::deep_sleep::SleepSchedule;
+use led_sectional_core::demo::DemoAnimator;
+use led_sectional_core::display_schedule::DisplaySchedule;
+use led_sectional_core::events::{Event, EventBus, Repaint};
+use led_sectional_core::heap::{HeapMonitor, LOW_MEMORY_LOG_LINES};
+use led_sectional_core::home_alert::HomeAlerts;
+use led_sectional_core::led::{LedState, COLOR_CONNECTED, COLOR_CONNECTING};
+use led_sectional_core::lightning::LightningAnimator;
+use led_sectional_core::link_anim::{LinkAnimator, LinkPhase};
+use led_sectional_core::networks::{NetworkList, SignalMonitor};
+use led_sectional_core::poller::{MetarPoller, PollOutcome};
+use led_sectional_core::safe_mode::Stability;
+use led_sectional_core::settings_editor;
+use led_sectional_core::status_screen::{self, StatusScreen};
+use led_sectional_core::telemetry::{DisplayState, Telemetry};
+use led_sectional_core::test_pattern::{TestRequest, TestRun};
+use led_sectional_core::wifi_link::{failure_message, LinkAction, LinkEvent, LinkState, WifiLink};
 use log::{error, info, warn};
+use std::collections::VecDeque;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-/// Default config used when no config file is available on flash.
+/// Lowest config layer; `/config.toml` and NVS overrides are merged on top.
 const DEFAULT_CONFIG_TOML: &str = include_str!("../../cfg.toml.example");
 
+/// Longest the main loop sleeps between checks; also the gust blink period.
+const LOOP_PERIOD: Duration = Duration::from_secs(5);
+/// How often WiFi events are checked while offline.
+const LINK_POLL_PERIOD: Duration = Duration::from_millis(250);
+/// Wait between rounds of trying every saved network: the first delay and
+/// the cap it doubles up to.
+const RECONNECT_INITIAL: Duration = Duration::from_secs(5);
+const RECONNECT_MAX: Duration = Duration::from_secs(300);
+/// Failed rounds (about five minutes of retrying) before the setup AP comes
+/// up on its own, for when the router or its password changed.
+const SETUP_AFTER_FAILURES: u32 = 6;
+/// Failed rounds (about 35 seconds) on a map that hasn't been online since
+/// boot before the offline demo takes over the LEDs.
+const DEMO_AFTER_FAILURES: u32 = 3;
+/// How often the station RSSI is read while connected.
+const SIGNAL_CHECK_PERIOD: Duration = Duration::from_secs(30);
+
 fn main() {
     esp_idf_svc::sys::link_patches();
-    esp_idf_svc::log::EspLogger::initialize_default();
+    logging::initialize();
 
-    info!("LED Sectional booting...");
+    info!(
+        "LED Sectional {} booting on an {} (last reset: {})...",
+        system::build_info().summary(),
+        Chip::TARGET.name(),
+        system::reset_reason()
+    );
+
+    ota::log_boot_state();
+    watchdog::configure();
 
     let peripherals = Peripherals::take().expect("failed to take peripherals");
     let sysloop = EspSystemEventLoop::take().expect("failed to take event loop");
     let nvs = EspDefaultNvsPartition::take().expect("failed to take NVS partition");
+    crash::install_panic_hook(nvs.clone());
+    crash::check_last_boot(nvs.clone());
+    let safe_mode = safe_mode::check(nvs.clone());
 
-    // Load config (from flash filesystem in production, fallback to built-in default)
-    let config = Config::from_toml(DEFAULT_CONFIG_TOML).expect("failed to parse default config");
+    // Load config: built-in default, overlaid by the flash file, overlaid by NVS overrides
+    if let Err(e) = flash_fs::mount() {
+        error!("Failed to mount SPIFFS: {:?}", e);
+    }
+    // Safe mode leaves the stored config alone, on flash and in NVS
+    let mut config_store = if safe_mode {
+        None
+    } else {
+        config_store::ConfigStore::new(nvs.clone())
+            .inspect_err(|e| error!("Failed to open config store: {:?}", e))
+            .ok()
+    };
+    let config = if safe_mode {
+        Config::from_toml(DEFAULT_CONFIG_TOML).expect("embedded default config must be valid")
+    } else {
+        load_config(config_store.as_mut(), &nvs)
+    };
+    logging::set_filter(config.settings.log_filter());
+    if config.settings.button_pin != config.settings.data_pin {
+        button::watch(config.settings.button_pin, nvs.clone());
+    }
+    if let Some(pin) = config.settings.user_button.pin {
+        user_button::configure(&config.settings.user_button);
+        user_button::watch(pin);
+    }
+    if let Some(pin) = config.settings.buzzer.pin {
+        buzzer::start(peripherals.ledc, pin);
+    }
+    oled::start(peripherals.i2c0, &config.settings.oled);
+    if let Some(pin) = config.settings.display_off.motion_pin {
+        motion::watch(pin);
+    }
+    if config.settings.factory_reset {
+        factory_reset::perform(nvs.clone(), "requested by settings.factory_reset");
+    }
+    log_diagnostics(&config);
     info!(
         "Config loaded: {} airports, {} LEDs",
         config.airports.len(),
@@ -37,121 +159,1015 @@ fn main() {
 
     // Initialize LED state
     let mut led_state = LedState::new(config.num_leds(), config.settings.brightness);
-    led_state.set_all(COLOR_CONNECTING);
-    // TODO: write to hardware via led_driver once GPIO pin is configured
+    led_state.set_brightness_limits(config.settings.min_brightness, config.settings.max_brightness);
+    led_state.set_current_limit(config.settings.max_current_ma);
+    led_state.set_indicator_indices(config.indicator_led_indices());
+    // After a deep sleep the strip still shows the last fetch; keep it
+    let restored = sleep::restore(&mut led_state, config.settings.data_pin);
+    power::configure(&config.settings.power);
+    led_driver::start(peripherals.rmt, peripherals.spi2, &config.settings);
+    if !restored {
+        led_state.set_all(COLOR_CONNECTING);
+        led_driver::show(&led_state);
+    }
 
-    // Resolve WiFi credentials: NVS first, then TOML config, else provisioning
-    let credentials = resolve_wifi_credentials(&nvs, &config);
+    // Resolve WiFi networks: saved in NVS, plus the TOML config; else provisioning
+    let force_setup = wifi::take_setup_request(nvs.clone())
+        .inspect_err(|e| warn!("Failed to read setup request: {:?}", e))
+        .unwrap_or(false);
+    let networks = if force_setup {
+        info!("Setup requested by the button; skipping saved networks");
+        NetworkList::default()
+    } else {
+        resolve_wifi_networks(&nvs, &config)
+    };
+    // A display with no WiFi to join needs no setup; the button still
+    // starts it
+    if networks.is_empty() && config.settings.attract_mode && !force_setup && !safe_mode {
+        info!("Attract mode on; showing demo weather without WiFi");
+        run_offline_demo(&config, &mut led_state);
+    }
 
-    match credentials {
-        Some((ssid, password)) => {
-            // Connect to WiFi
-            let mut wifi_mgr =
-                wifi::WifiManager::new(peripherals.modem, sysloop, nvs.clone())
-                    .expect("failed to create WiFi manager");
+    if !networks.is_empty() {
+        // WiFi connects in the background; the main loop drives it
+        let events = wifi_events::WifiEvents::subscribe(sysloop.clone())
+            .expect("failed to subscribe to WiFi events");
+        let mut wifi_mgr =
+            wifi::WifiManager::new(peripherals.modem, sysloop, nvs.clone())
+                .expect("failed to create WiFi manager");
+        wifi_mgr.set_static_ip(config.wifi.static_ip());
+        wifi_mgr.set_hostname(config.wifi.hostname());
+        let backoff = Backoff::new(RECONNECT_INITIAL, RECONNECT_MAX);
+        // A new hostname is picked up on the next boot
+        let mdns = mdns::start(config.wifi.hostname(), config.num_leds())
+            .inspect_err(|e| error!("Failed to start mDNS: {:?}", e))
+            .ok();
+        let mut station = Station {
+            mgr: wifi_mgr,
+            link: WifiLink::new(networks, backoff, Instant::now()),
+            events,
+            mdns,
+        };
 
-            match wifi_mgr.connect_sta(&ssid, &password) {
-                Ok(()) => {
-                    info!("WiFi connected");
-                    led_state.set_all(COLOR_CONNECTED);
-                    std::thread::sleep(Duration::from_millis(500));
+        let web_state = Arc::new(web::SharedState::default());
+        web_state.publish_config(&config);
+        match auth::load(nvs.clone()) {
+            Ok(credential) => {
+                if credential.is_none() {
+                    info!("No HTTP credential set; anyone on the network can change the map");
                 }
-                Err(e) => {
-                    error!("WiFi connection failed: {:?}", e);
-                    led_state.set_all(COLOR_FETCH_ERROR);
-                    // Connection failed — could enter provisioning here
-                    // For MVP, log and continue (will retry on next reboot)
+                web_state.set_credential(credential);
+            }
+            Err(e) => {
+                error!(
+                    "Failed to read HTTP credential ({:?}); endpoints are locked until it is \
+                     reset by holding the setup button 10 s for a factory reset",
+                    e
+                );
+                web_state.lock_credential();
+            }
+        }
+        let _server = web::start(web_state.clone())
+            .inspect_err(|e| error!("Failed to start HTTP server: {:?}", e))
+            .ok();
+
+        let clock = clock::SntpClock::start(&config.settings.timezone)
+            .inspect_err(|e| error!("Failed to start SNTP: {:?}", e))
+            .ok();
+
+        run_main_loop(
+            config,
+            config_store,
+            &nvs,
+            &mut station,
+            &web_state,
+            clock,
+            &mut led_state,
+        );
+    } else {
+        led_state.set_all(COLOR_CONNECTING);
+        led_driver::show(&led_state);
+
+        let setup = &config.settings.provisioning;
+        match setup.method {
+            ProvisioningMethod::Portal => {
+                warn!("No WiFi credentials found — starting captive portal");
+                if let Err(e) =
+                    provisioning::start_captive_portal(peripherals.modem, sysloop, nvs, &config)
+                {
+                    error!("Captive portal failed: {:?}", e);
+                }
+            }
+            ProvisioningMethod::Ble => {
+                warn!("No WiFi credentials found — starting BLE provisioning");
+                let pop = setup.pop.as_deref();
+                if let Err(e) = ble_provisioning::start(peripherals.modem, sysloop, nvs, pop) {
+                    error!("BLE provisioning failed: {:?}", e);
                 }
             }
+        }
+        // Both reboot once a network connects; they return on timeout
+        if config.settings.offline_demo && !safe_mode {
+            warn!("No WiFi set up; showing the offline demo");
+            run_offline_demo(&config, &mut led_state);
+        }
+        info!("Rebooting to start setup again");
+        // SAFETY: esp_restart() is always safe to call and triggers a clean reboot.
+        unsafe { esp_idf_svc::sys::esp_restart() };
+    }
+}
+
+/// Animate demo weather forever, with the radio off, after setup timed out
+/// or for attract mode. A power cycle or the setup button starts setup
+/// again.
+fn run_offline_demo(config: &Config, led_state: &mut LedState) -> ! {
+    let mut demo = DemoAnimator::new();
+    // SAFETY: esp_random() has no preconditions; it reads the hardware RNG.
+    let seed = unsafe { esp_idf_svc::sys::esp_random() } as u64;
+    let mut lightning = LightningAnimator::with_seed(seed);
+    loop {
+        let now = Instant::now();
+        let mut changed = demo.tick(now, config, led_state);
+        changed |= lightning.tick(now, &config.settings, led_state);
+        if changed {
+            led_driver::show(led_state);
+        }
+        let wake = [demo.next_deadline(), lightning.next_deadline()]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(now + LOOP_PERIOD);
+        std::thread::sleep(wake.saturating_duration_since(Instant::now()).min(LOOP_PERIOD));
+    }
+}
 
-            run_main_loop(&config, &mut led_state);
+/// The station connection: the driver, the state machine deciding what it
+/// does next, the events feeding that, and the mDNS responder advertising
+/// the device on it.
+struct Station {
+    mgr: wifi::WifiManager,
+    link: WifiLink,
+    events: wifi_events::WifiEvents,
+    mdns: Option<EspMdns>,
+}
+
+impl Station {
+    /// Feed pending events and timers into the link and carry out what it
+    /// asks for. Results of networks tried from `setup` are reported to it.
+    fn drive(&mut self, mut setup: Option<&mut provisioning::SetupSession>) {
+        let now = Instant::now();
+        let mut actions: VecDeque<LinkAction> = VecDeque::new();
+        if let Some(network) = setup.as_deref_mut().and_then(|s| s.next_attempt()) {
+            info!("Trying {} from the setup form", network.ssid);
+            actions.push_back(self.link.try_network(network, now));
+        }
+        for event in self.events.drain() {
+            actions.extend(self.link.handle(event, now));
         }
-        None => {
-            warn!("No WiFi credentials found — starting captive portal");
-            led_state.set_all(COLOR_CONNECTING);
+        actions.extend(self.link.poll(now));
 
-            if let Err(e) =
-                provisioning::start_captive_portal(peripherals.modem, sysloop, nvs)
-            {
-                error!("Captive portal failed: {:?}", e);
+        while let Some(action) = actions.pop_front() {
+            match action {
+                LinkAction::Connect(network) => {
+                    if let Err(e) = self.mgr.begin_connect(&network) {
+                        warn!("Could not start connecting to {}: {:?}", network.ssid, e);
+                        // Move on as if the attempt had failed
+                        let failed = LinkEvent::Disconnected { reason: 0 };
+                        actions.extend(self.link.handle(failed, now));
+                    }
+                }
+                LinkAction::Online { ssid, trial } => {
+                    if let LinkState::Online { ip, .. } = self.link.state() {
+                        info!("WiFi connected to {}. IP: {}", ssid, ip);
+                        if trial.is_some() {
+                            if let Some(session) = setup.as_deref_mut() {
+                                session.finish_attempt(Ok(*ip));
+                            }
+                        }
+                    }
+                    self.mgr.save_connected(&ssid, trial);
+                }
+                LinkAction::TrialFailed { ssid, reason } => {
+                    let message = failure_message(reason);
+                    warn!("Could not switch to {}: {}; back to saved networks", ssid, message);
+                    if let Some(session) = setup.as_deref_mut() {
+                        session.finish_attempt(Err(message.to_string()));
+                    }
+                }
             }
-            // start_captive_portal reboots on success or timeout, so we shouldn't reach here
         }
     }
 }
 
-/// Main application loop: fetch METARs, update LEDs, animate lightning.
-fn run_main_loop(config: &Config, led_state: &mut LedState) {
+/// Main application loop: keep WiFi up, fetch METARs, update LEDs, animate
+/// lightning, and hot-reload the config when `/config.toml` changes.
+fn run_main_loop(
+    mut config: Config,
+    mut config_store: Option<config_store::ConfigStore>,
+    nvs: &EspDefaultNvsPartition,
+    station: &mut Station,
+    web_state: &web::SharedState,
+    mut clock: Option<clock::SntpClock>,
+    led_state: &mut LedState,
+) {
     info!("Entering main loop");
+    // Fed at the top of every iteration; a deadlock resets
+    let _watch = watchdog::Watch::current_task_or_log("main");
 
-    let airport_codes = config.metar_airport_codes();
-    let fetch_interval = Duration::from_secs(config.settings.request_interval_secs);
-    let mut last_fetch = Instant::now() - fetch_interval; // Force immediate first fetch
-    let client = metar_client::MetarClient::new();
+    // Fetches run on their own thread so animations don't stall behind them
+    let mut fetcher = fetcher::Fetcher::start();
+    // SAFETY: esp_random() has no preconditions; it reads the hardware RNG.
+    let seed = unsafe { esp_idf_svc::sys::esp_random() } as u64;
+    let mut poller = MetarPoller::with_seed(&config, seed);
+    let mut interval = poller.current_interval();
+    let mut lightning = LightningAnimator::with_seed(seed.rotate_left(32));
+    let mut next_blink = Instant::now();
+    let mut watcher = flash_fs::ConfigWatcher::new();
+    let mut clock_synced = false;
+    let mut shown_state = "";
+    let mut was_online = false;
+    let mut demo: Option<DemoAnimator> = None;
+    let mut link_anim = LinkAnimator::new();
+    let mut test_run: Option<TestRun> = None;
+    let mut setup: Option<provisioning::SetupSession> = None;
+    let mut signal = SignalMonitor::new(config.settings.weak_signal_dbm);
+    let mut next_signal_check = Instant::now();
+    let mut heap = HeapMonitor::new(config.settings.low_heap_kb);
+    let mut stability = Stability::default();
+    // In safe mode the setup AP comes up at once and nothing animates
+    let mut setup_at_boot = safe_mode::is_active();
+    lightning.set_paused(safe_mode::is_active());
+    let mut update_check = false;
+    let (mut telemetry, mut mqtt) = start_mqtt(&config);
+    let mut sleep_schedule = SleepSchedule::new(Instant::now(), sleep::woke_from_sleep());
+    let mut mqtt_config = (config.settings.mqtt.clone(), config.wifi.hostname().to_string());
+    logging::set_syslog(config.settings.syslog.server_addr(), config.wifi.hostname());
+    let mut syslog_config = (config.settings.syslog.clone(), config.wifi.hostname().to_string());
+    let mut bus = EventBus::new();
+    let mut event_log = logging::EventLog::default();
+    let mut repaint = Repaint::default();
+    let mut home_alerts = HomeAlerts::new(config.settings.home_airport.as_deref());
+    let mut screen = config.settings.oled.pins().map(|_| {
+        StatusScreen::new(config.settings.home_airport.as_deref(), config.settings.time_zone())
+    });
+    let mut next_rotate = Instant::now() + status_screen::ROTATE_EVERY;
+    let mut display_schedule = DisplaySchedule::new(&config.settings.display_off);
+    let mut shown_brightness = led_state.brightness();
 
     loop {
-        if last_fetch.elapsed() >= fetch_interval {
-            info!("Fetching METAR data...");
-
-            let code_refs: Vec<&str> = airport_codes.iter().copied().collect();
-            match client.fetch(&code_refs) {
-                Ok(reports) => {
-                    info!("Received {} METAR reports", reports.len());
-                    let metar_map = metar::metars_by_icao(reports);
-                    let lightning = update_leds_from_metars(
+        watchdog::feed();
+        let (free, largest) = (system::free_heap(), system::largest_free_block());
+        match heap.sample(free, largest) {
+            Some(true) => {
+                warn!(
+                    "Memory low: {} KiB free, largest block {} KiB; pausing animations",
+                    free / 1024,
+                    largest / 1024
+                );
+                logging::set_capacity(LOW_MEMORY_LOG_LINES);
+                set_low_memory(true, &mut fetcher, &mut lightning, led_state, web_state);
+            }
+            Some(false) => {
+                info!("Memory recovered: {} KiB free", free / 1024);
+                logging::set_capacity(logging::LOG_LINES);
+                set_low_memory(false, &mut fetcher, &mut lightning, led_state, web_state);
+            }
+            None => {}
+        }
+
+        // Crashes before this count toward safe mode
+        if stability.take_stable(Duration::from_secs(system::uptime_secs())) {
+            safe_mode::mark_stable(nvs.clone());
+        }
+
+        let wants_setup = std::mem::take(&mut setup_at_boot)
+            || web_state.take_setup_request()
+            || station.link.failed_rounds() == SETUP_AFTER_FAILURES;
+        if wants_setup && setup.is_none() {
+            let ap_password = config.settings.provisioning.ap_password.as_deref();
+            let saved = station.link.networks();
+            match provisioning::SetupSession::start(&mut station.mgr, web_state, saved, ap_password)
+            {
+                Ok(session) => setup = Some(session),
+                Err(e) => error!("Failed to start setup AP: {:?}", e),
+            }
+        }
+
+        if web_state.take_update_installed() {
+            ota::reboot();
+        }
+
+        station.drive(setup.as_mut());
+        if setup.as_ref().is_some_and(|s| s.is_over(Instant::now())) {
+            if let Some(session) = setup.take() {
+                session.stop(&mut station.mgr, web_state);
+            }
+        }
+
+        let state = station.link.state();
+        if state.name() != shown_state {
+            shown_state = state.name();
+            bus.publish(Event::WifiStateChanged(state.clone()));
+            match state {
+                LinkState::Online { .. } => {
+                    // Attract mode keeps the demo up
+                    if !config.settings.attract_mode && demo.take().is_some() {
+                        info!("WiFi connected; leaving the offline demo");
+                    }
+                    // Shown until the next fetch repaints the map; after a
+                    // deep sleep the last fetch stays up instead
+                    if !sleep_schedule.is_timer_wake() && demo.is_none() {
+                        led_state.set_all(COLOR_CONNECTED);
+                        led_driver::show(led_state);
+                    }
+                    if !was_online {
+                        ota::confirm_running();
+                        // Look for an update once per boot, not on every wake
+                        update_check = config.settings.ota.manifest_url.is_some()
+                            && !sleep_schedule.is_timer_wake();
+                    }
+                    was_online = true;
+                    signal.reset();
+                    // Repaint from fresh data rather than waiting out the interval
+                    poller.request_refresh();
+                }
+                LinkState::Waiting { until } if station.link.failed_rounds() > 0 => warn!(
+                    "No saved WiFi network connected (round {}); retrying in {}s",
+                    station.link.failed_rounds(),
+                    until.saturating_duration_since(Instant::now()).as_secs()
+                ),
+                LinkState::Waiting { .. } | LinkState::Connecting { .. } => {}
+            }
+        }
+        bus.dispatch(&mut [
+            &mut &*web_state,
+            &mut telemetry,
+            &mut event_log,
+            &mut home_alerts,
+            &mut screen,
+            &mut repaint,
+        ]);
+        if !station.link.is_online() {
+            if let Some(run) = test_run.take() {
+                run.finish(led_state);
+                led_driver::show(led_state);
+            }
+            let sleeps = config.settings.power.deep_sleep && !config.settings.attract_mode;
+            if sleeps && setup.is_none() {
+                let interval = Duration::from_secs(config.settings.request_interval_secs);
+                if let Some(duration) = sleep_schedule.sleep_offline(Instant::now(), interval) {
+                    warn!("WiFi didn't connect after waking; sleeping until the next fetch");
+                    sleep::sleep(led_state, config.settings.data_pin, duration);
+                }
+            }
+            let never_connects =
+                !was_online && station.link.failed_rounds() >= DEMO_AFTER_FAILURES;
+            let demo_allowed = config.settings.offline_demo && !safe_mode::is_active();
+            if demo.is_none() && config.settings.attract_mode {
+                info!("Attract mode on; showing demo weather");
+                demo = Some(DemoAnimator::new());
+            } else if demo.is_none() && never_connects && demo_allowed {
+                warn!("WiFi isn't connecting; showing the offline demo meanwhile");
+                demo = Some(DemoAnimator::new());
+            }
+            let now = Instant::now();
+            let changed = match (demo.as_mut(), LinkPhase::of(state, was_online)) {
+                (Some(demo), _) => {
+                    demo.tick(now, &config, led_state)
+                        | lightning.tick(now, &config.settings, led_state)
+                }
+                // Leave the last fetch up while reconnecting after a deep sleep
+                (None, Some(phase)) if !sleep_schedule.is_timer_wake() => {
+                    link_anim.tick(now, phase, station.link.failed_rounds(), led_state)
+                }
+                (None, _) => false,
+            };
+            if changed {
+                led_driver::show(led_state);
+            }
+            let wake = match link_anim.next_deadline().filter(|_| demo.is_none()) {
+                Some(t) => t.saturating_duration_since(Instant::now()).min(LINK_POLL_PERIOD),
+                None => LINK_POLL_PERIOD,
+            };
+            std::thread::sleep(wake);
+            continue;
+        }
+
+        if Instant::now() >= next_signal_check {
+            next_signal_check = Instant::now() + SIGNAL_CHECK_PERIOD;
+            if let Some(rssi) = station.mgr.rssi() {
+                match signal.sample(rssi) {
+                    Some(true) => warn!(
+                        "WiFi signal weak: {} dBm, below {} dBm; fetches may time out",
+                        rssi, config.settings.weak_signal_dbm
+                    ),
+                    Some(false) => info!("WiFi signal recovered: {} dBm", rssi),
+                    None => {}
+                }
+                web_state.publish_signal(signal.rssi(), signal.is_weak());
+            }
+        }
+
+        if !clock_synced {
+            if let Some(t) = clock.as_ref().and_then(|c| c.now()) {
+                clock_synced = true;
+                info!(
+                    "Clock synced: {:04}-{:02}-{:02} {:02}:{:02} local",
+                    t.year, t.month, t.day, t.hour, t.minute
+                );
+            }
+        }
+
+        if std::mem::take(&mut update_check) | web_state.take_update_check() {
+            match config.settings.ota.manifest_url.as_deref() {
+                Some(url) => fetcher.check_for_update(url),
+                None => warn!("Update check requested, but settings.ota.manifest_url isn't set"),
+            }
+        }
+
+        if let Some(credential) = web_state.take_credential_change() {
+            if let Err(e) = auth::save(nvs.clone(), credential.as_ref()) {
+                warn!("Failed to store HTTP credential: {:?}", e);
+            }
+        }
+
+        // Motion keeps the map lit through display-off hours; while it's dark
+        // a press only wakes it, rather than acting unseen
+        let mut presses = user_button::take_presses();
+        let motion = motion::take_motion();
+        let was_dark = led_state.scheduled_off();
+        if motion || (was_dark && !presses.is_empty()) {
+            display_schedule.wake(Instant::now());
+        }
+        let local = clock.as_ref().and_then(|c| c.now());
+        let scheduled_dark = display_schedule.is_dark(local.as_ref(), Instant::now());
+        if led_state.set_scheduled_off(scheduled_dark) {
+            if scheduled_dark {
+                info!("Display off for the scheduled hours");
+            } else if was_dark && (motion || !presses.is_empty()) {
+                let cause = if motion { "motion" } else { "the button" };
+                let wake_secs = config.settings.display_off.wake_secs;
+                info!("Display woken by {} for {}s", cause, wake_secs);
+            } else {
+                info!("Display back on after the scheduled hours");
+            }
+            led_driver::show(led_state);
+        }
+        if was_dark {
+            presses.clear();
+        }
+
+        let mqtt_commands = mqtt.as_mut().map(|m| m.take_commands()).unwrap_or_default();
+        let button_commands = presses.into_iter().filter_map(|press| {
+            let action = config.settings.user_button.action(press);
+            action.command(&config.settings, led_state.display_on())
+        });
+        let commands: Vec<_> = mqtt_commands
+            .into_iter()
+            .map(|command| ("over MQTT", command))
+            .chain(button_commands.map(|command| ("with the button", command)))
+            .collect();
+        for (source, command) in commands {
+            match command {
+                Command::Display(on) => {
+                    if led_state.set_display_on(on) {
+                        info!("Display switched {} {}", if on { "on" } else { "off" }, source);
+                        led_driver::show(led_state);
+                    }
+                }
+                Command::Refresh => match web_state.request_refresh(Instant::now()) {
+                    Ok(()) => info!("Weather refresh requested {}", source),
+                    Err(wait) => warn!(
+                        "Ignoring refresh {}; weather was just fetched, try again in {}s",
+                        source,
+                        wait.as_secs().max(1)
+                    ),
+                },
+                Command::Brightness(_) | Command::Mode(_) | Command::NextProfile => {
+                    match commands::with_command(&config, command) {
+                        Ok(Some(edited)) if !edited.has_errors() => {
+                            config = apply_edited_config(
+                                edited,
+                                config_store.as_mut(),
+                                &mut test_run,
+                                &mut poller,
+                                station.mdns.as_mut(),
+                                web_state,
+                                led_state,
+                            );
+                            info!("Applied {:?} {}", command, source);
+                        }
+                        Ok(_) => warn!("{:?} {} doesn't validate; ignoring it", command, source),
+                        Err(e) => warn!("Ignoring {:?} {}: {}", command, source, e),
+                    }
+                }
+            }
+        }
+
+        if web_state.take_refresh_request() {
+            poller.request_refresh();
+        }
+
+        if web_state.take_factory_reset_request() {
+            factory_reset::perform(nvs.clone(), "requested over HTTP");
+        }
+
+        let config_changed = watcher.changed();
+        if config_changed && safe_mode::is_active() {
+            info!("Config changed on flash; restart to leave safe mode and load it");
+        } else if config_changed {
+            if let Some(reloaded) = reload_config(config_store.as_mut()) {
+                if let Some(run) = test_run.take() {
+                    run.finish(led_state);
+                }
+                let (old, new) = (&config.settings, &reloaded.settings);
+                if new.data_pin != old.data_pin || new.led != old.led {
+                    warn!("The new data_pin and [settings.led] take effect after a restart");
+                }
+                if new.power != old.power {
+                    power::configure(&new.power);
+                }
+                if new.log_level != old.log_level {
+                    logging::set_filter(new.log_filter());
+                }
+                if new.user_button.pin != old.user_button.pin {
+                    warn!("The new settings.user_button.pin takes effect after a restart");
+                }
+                user_button::configure(&new.user_button);
+                if new.buzzer.pin != old.buzzer.pin {
+                    warn!("The new settings.buzzer.pin takes effect after a restart");
+                }
+                home_alerts.set_airport(new.home_airport.as_deref());
+                if new.oled != old.oled {
+                    warn!("The new [settings.oled] takes effect after a restart");
+                }
+                if new.display_off.motion_pin != old.display_off.motion_pin {
+                    warn!("The new settings.display_off.motion_pin takes effect after a restart");
+                }
+                display_schedule.configure(&new.display_off);
+                if let Some(screen) = screen.as_mut() {
+                    screen.set_airport(new.home_airport.as_deref());
+                    screen.set_time_zone(new.time_zone());
+                }
+                log_diagnostics(&reloaded);
+                config = reloaded;
+                web_state.publish_config(&config);
+                if let Some(clock) = clock.as_mut() {
+                    clock.set_time_zone(&config.settings.timezone);
+                }
+                poller.reconfigure(&config, led_state);
+                signal.set_threshold(config.settings.weak_signal_dbm);
+                heap.set_threshold(config.settings.low_heap_kb);
+                // Takes effect on the next reconnect
+                station.mgr.set_static_ip(config.wifi.static_ip());
+                if let Some(mdns) = station.mdns.as_mut() {
+                    mdns::set_led_count(mdns, config.num_leds());
+                }
+                led_driver::show(led_state);
+                info!("Config reloaded: {} LEDs", config.num_leds());
+            }
+        }
+
+        if let Some(codes) = web_state.take_airport_edit() {
+            match airport_editor::with_airports(&config, &codes) {
+                Ok(edited) if !edited.has_errors() => {
+                    config = apply_edited_config(
+                        edited,
+                        config_store.as_mut(),
+                        &mut test_run,
+                        &mut poller,
+                        station.mdns.as_mut(),
+                        web_state,
                         led_state,
-                        &config.airports,
-                        &metar_map,
-                        config.settings.wind_threshold_kt,
-                        config.settings.do_winds,
                     );
-                    led_state.set_lightning_indices(lightning);
-                    last_fetch = Instant::now();
-                    // TODO: write to hardware
+                    info!("Airport list applied: {} LEDs", config.num_leds());
                 }
-                Err(e) => {
-                    error!("METAR fetch failed: {}", e);
-                    led_state.set_all(COLOR_FETCH_ERROR);
-                    // TODO: write to hardware
-                    // Retry sooner (60 seconds)
-                    last_fetch = Instant::now() - fetch_interval + Duration::from_secs(60);
+                Ok(_) => warn!("Edited airport list no longer validates; ignoring it"),
+                Err(e) => warn!("Ignoring edited airport list: {}", e),
+            }
+        }
+
+        if let Some(patch) = web_state.take_settings_edit() {
+            match settings_editor::with_settings(&config, &patch) {
+                Ok(edited) if !edited.has_errors() => {
+                    config = apply_edited_config(
+                        edited,
+                        config_store.as_mut(),
+                        &mut test_run,
+                        &mut poller,
+                        station.mdns.as_mut(),
+                        web_state,
+                        led_state,
+                    );
+                    info!("Settings applied");
+                }
+                Ok(_) => warn!("Edited settings no longer validate; ignoring them"),
+                Err(e) => warn!("Ignoring edited settings: {}", e),
+            }
+        }
+
+        if let Some(profile) = web_state.take_profile_change() {
+            let name = profile.as_deref().unwrap_or("none");
+            match commands::with_profile(&config, profile.as_deref()) {
+                Ok(edited) if !edited.has_errors() => {
+                    config = apply_edited_config(
+                        edited,
+                        config_store.as_mut(),
+                        &mut test_run,
+                        &mut poller,
+                        station.mdns.as_mut(),
+                        web_state,
+                        led_state,
+                    );
+                    info!("Profile {} applied", name);
                 }
+                Ok(_) => warn!("Profile {} doesn't validate; ignoring it", name),
+                Err(e) => warn!("Ignoring profile {}: {}", name, e),
+            }
+        }
+
+        if let Some(request) = web_state.take_test_request() {
+            if let Some(run) = test_run.take() {
+                run.finish(led_state);
+            }
+            match request {
+                TestRequest::Start(pattern, duration) => {
+                    info!("Showing a test pattern for {}s", duration.as_secs());
+                    test_run = Some(TestRun::start(pattern, Instant::now(), duration, led_state));
+                }
+                TestRequest::Stop => info!("Test pattern stopped"),
+            }
+            led_driver::show(led_state);
+        }
+
+        // A test pattern holds the LEDs; fetches wait until it's over
+        if let Some(run) = test_run.as_mut() {
+            let now = Instant::now();
+            if !run.is_over(now) {
+                if run.tick(now, led_state) {
+                    led_driver::show(led_state);
+                }
+                let wake = run.next_deadline().saturating_duration_since(Instant::now());
+                std::thread::sleep(wake.min(LOOP_PERIOD));
+                continue;
+            }
+            info!("Test pattern over; back to weather");
+            if let Some(run) = test_run.take() {
+                run.finish(led_state);
+            }
+            led_driver::show(led_state);
+        }
+
+        // Reconnect with new broker settings from a reload or an edit
+        let wanted = (config.settings.mqtt.clone(), config.wifi.hostname().to_string());
+        if wanted != mqtt_config {
+            mqtt_config = wanted;
+            // Drop the old client first so it disconnects from the old broker
+            mqtt = None;
+            (telemetry, mqtt) = start_mqtt(&config);
+        }
+        let wanted = (config.settings.syslog.clone(), config.wifi.hostname().to_string());
+        if wanted != syslog_config {
+            syslog_config = wanted;
+            logging::set_syslog(config.settings.syslog.server_addr(), config.wifi.hostname());
+        }
+
+        // Attract mode shows the demo in place of fetched weather
+        let outcome = if config.settings.attract_mode {
+            let demo = demo.get_or_insert_with(|| {
+                info!("Attract mode on; showing demo weather");
+                DemoAnimator::new()
+            });
+            if demo.tick(Instant::now(), &config, led_state) {
+                led_driver::show(led_state);
+            }
+            PollOutcome::Idle
+        } else {
+            if demo.take().is_some() {
+                info!("Attract mode off; back to live weather");
+                poller.request_refresh();
+            }
+            if let Some(job) = poller.start_fetch(Instant::now(), &config) {
+                fetcher.start_job(job);
+            }
+            match fetcher.take_response() {
+                Some(response) => poller.finish_fetch(response, &config, led_state),
+                None => PollOutcome::Idle,
+            }
+        };
+        match &outcome {
+            PollOutcome::Updated(_) => {
+                if poller.current_interval() != interval {
+                    interval = poller.current_interval();
+                    info!("Fetch interval now {}s", interval.as_secs());
+                }
+            }
+            PollOutcome::Failed(e) => match signal.rssi() {
+                Some(rssi) => error!("Weather fetch failed: {} (WiFi {} dBm)", e, rssi),
+                None => error!("Weather fetch failed: {}", e),
+            },
+            PollOutcome::NotModified | PollOutcome::Idle => {}
+        }
+        if matches!(outcome, PollOutcome::Updated(_) | PollOutcome::NotModified) {
+            // Checked here too, as a deep-sleeping map may sleep before the
+            // next pass
+            stability.fetched();
+            if stability.take_stable(Duration::from_secs(system::uptime_secs())) {
+                safe_mode::mark_stable(nvs.clone());
+            }
+        }
+        if outcome != PollOutcome::Idle {
+            sleep_schedule.fetched(Instant::now());
+            let mut snapshot = MapSnapshot::capture(&config, poller.reports(), led_state);
+            snapshot.fetched_at = clock::unix_now();
+            if let PollOutcome::Failed(e) = &outcome {
+                snapshot.error = Some(e.clone());
+            }
+            bus.publish(Event::MetarUpdated(snapshot));
+        }
+        if led_state.brightness() != shown_brightness {
+            shown_brightness = led_state.brightness();
+            bus.publish(Event::BrightnessChanged(shown_brightness));
+        }
+        bus.dispatch(&mut [
+            &mut &*web_state,
+            &mut telemetry,
+            &mut event_log,
+            &mut home_alerts,
+            &mut screen,
+            &mut repaint,
+        ]);
+        if repaint.take() {
+            led_driver::show(led_state);
+        }
+        for alert in home_alerts.take_alerts() {
+            let quiet = config.settings.buzzer.quiet_window();
+            let now = clock.as_ref().and_then(|c| c.now());
+            match quiet.zip(now) {
+                Some((window, now)) if window.contains(&now) => {
+                    info!("Not chirping for {:?} during quiet hours {}", alert, window)
+                }
+                _ => buzzer::chirp(alert),
+            }
+        }
+
+        if let (Some(mqtt), Some(telemetry)) = (mqtt.as_mut(), telemetry.as_mut()) {
+            telemetry.set_display(DisplayState {
+                brightness: led_state.brightness(),
+                on: led_state.display_on(),
+                mode: config.settings.display_mode,
+            });
+            if mqtt.take_connected() {
+                telemetry.resend_all();
+            }
+            if mqtt.is_connected() {
+                for message in telemetry.poll(Instant::now(), || web_state.health()) {
+                    mqtt.publish(&message);
+                }
+            }
+        }
+
+        // With settings.power.deep_sleep, sleep until the next fetch once
+        // this one is shown and sent
+        let sleeps = config.settings.power.deep_sleep && !config.settings.attract_mode;
+        if sleeps && setup.is_none() {
+            if let Some(duration) = sleep_schedule.sleep_for(Instant::now(), poller.next_fetch()) {
+                sleep::sleep(led_state, config.settings.data_pin, duration);
+            }
+        }
+
+        let now = Instant::now();
+        if lightning.tick(now, &config.settings, led_state) {
+            led_driver::show(led_state);
+        }
+
+        // Gust blink; a weak WiFi signal blinks the legend LEDs dimly
+        if now >= next_blink {
+            next_blink = now + LOOP_PERIOD;
+            // Gusting airports hold their color while memory is low
+            let mut changed = if heap.is_low() || safe_mode::is_active() {
+                led_state.end_blink()
+            } else {
+                led_state.toggle_blink()
+            };
+            let dim = signal.is_weak() && !led_state.indicator_dim();
+            changed |= led_state.set_indicator_dim(dim);
+            if changed {
+                led_driver::show(led_state);
             }
         }
 
-        // Lightning animation
-        if config.settings.do_lightning && led_state.apply_lightning_flash() {
-            // TODO: write to hardware
-            std::thread::sleep(Duration::from_millis(25));
-            led_state.restore_lightning();
-            // TODO: write to hardware
+        if let Some(screen) = screen.as_mut() {
+            if now >= next_rotate {
+                next_rotate = now + status_screen::ROTATE_EVERY;
+                screen.rotate();
+            }
+            if let Some(frame) = screen.take_frame() {
+                oled::show(frame);
+            }
+        }
+
+        // Wake early for the lightning animation, the demo's next frame, or
+        // a finished fetch
+        let wake = [lightning.next_deadline(), demo.as_ref().and_then(|d| d.next_deadline())]
+            .into_iter()
+            .flatten()
+            .fold(next_blink, Instant::min);
+        fetcher.wait(wake.saturating_duration_since(Instant::now()).min(LOOP_PERIOD));
+    }
+}
+
+/// Apply a validated change from the airport editor, settings page, a
+/// command or a profile switch: end any test pattern, store the change with
+/// the NVS overrides, and put it in effect. Returns the config to run on.
+fn apply_edited_config(
+    edited: Config,
+    config_store: Option<&mut config_store::ConfigStore>,
+    test_run: &mut Option<TestRun>,
+    poller: &mut MetarPoller,
+    mdns: Option<&mut EspMdns>,
+    web_state: &web::SharedState,
+    led_state: &mut LedState,
+) -> Config {
+    if let Some(run) = test_run.take() {
+        run.finish(led_state);
+    }
+    if let Some(store) = config_store {
+        if let Err(e) = store.save(&edited, &base_layers()) {
+            warn!("Failed to store the config overrides: {}", e);
         }
+    }
+    web_state.publish_config(&edited);
+    poller.reconfigure(&edited, led_state);
+    if let Some(mdns) = mdns {
+        mdns::set_led_count(mdns, edited.num_leds());
+    }
+    led_driver::show(led_state);
+    edited
+}
 
-        std::thread::sleep(Duration::from_secs(5));
+/// Enter or leave low-memory mode: no lightning or gust blink, and no
+/// weather connection kept open between fetches.
+fn set_low_memory(
+    low: bool,
+    fetcher: &mut fetcher::Fetcher,
+    lightning: &mut LightningAnimator,
+    led_state: &mut LedState,
+    web_state: &web::SharedState,
+) {
+    fetcher.set_low_memory(low);
+    lightning.set_paused(low || safe_mode::is_active());
+    if low && led_state.end_blink() {
+        led_driver::show(led_state);
     }
+    web_state.publish_low_memory(low);
 }
 
-/// Resolve WiFi credentials: NVS first, then TOML config fallback.
-fn resolve_wifi_credentials(
+/// Start publishing telemetry if `settings.mqtt` names a broker. The
+/// client is None if it couldn't start; the config is checked again when it
+/// changes.
+fn start_mqtt(config: &Config) -> (Option<Telemetry>, Option<mqtt::Mqtt>) {
+    let Some(telemetry) = Telemetry::from_config(config) else {
+        return (None, None);
+    };
+    let client = mqtt::Mqtt::start(&config.settings.mqtt, config.wifi.hostname(), &telemetry)
+        .inspect_err(|e| error!("Failed to start MQTT: {:?}", e))
+        .ok();
+    (Some(telemetry), client)
+}
+
+/// Build the config from its layers, lowest precedence first: the embedded
+/// default, `/config.toml`, `/secrets.toml`, then runtime overrides from NVS.
+/// A layer that fails to parse, or breaks validation once stacked, is skipped;
+/// overrides that fail their checksum trigger a factory reset.
+fn load_config(
+    store: Option<&mut config_store::ConfigStore>,
     nvs: &EspDefaultNvsPartition,
-    config: &Config,
-) -> Option<(String, String)> {
-    // Try NVS first
-    match wifi::load_credentials(nvs.clone()) {
-        Ok(Some((ssid, password))) => return Some((ssid, password)),
-        Ok(None) => {}
-        Err(e) => warn!("Failed to load NVS credentials: {:?}", e),
-    }
-
-    // Fall back to TOML config
-    config
-        .wifi
-        .ssid
-        .as_ref()
-        .map(|ssid| {
-            (
-                ssid.clone(),
-                config.wifi.password.clone().unwrap_or_default(),
-            )
-        })
+) -> Config {
+    let mut layers = base_layers();
+    if let Some(store) = store {
+        match store.load() {
+            Ok(Some(overrides)) => {
+                if stack_layer(&mut layers, overrides).is_none() {
+                    store.discard();
+                }
+            }
+            Ok(None) => {}
+            Err(e @ config_store::ConfigStoreError::Corrupt(_)) => {
+                factory_reset::perform(nvs.clone(), &e.to_string());
+            }
+            Err(e) => warn!("Failed to load config overrides: {}", e),
+        }
+    }
+    info!(
+        "Config layers: {}",
+        layers.iter().map(|l| l.name.as_str()).collect::<Vec<_>>().join(" + ")
+    );
+    Config::from_layers(&layers).expect("embedded default config must be valid")
+}
+
+/// The embedded default plus `/config.toml` and `/secrets.toml` when usable.
+fn base_layers() -> Vec<ConfigLayer> {
+    let mut layers = vec![ConfigLayer::parse("embedded", DEFAULT_CONFIG_TOML)
+        .expect("failed to parse default config")];
+    if let Some(toml) = flash_fs::read_config() {
+        match ConfigLayer::parse(flash_fs::CONFIG_PATH, &toml) {
+            Ok(layer) => {
+                stack_layer(&mut layers, layer);
+            }
+            Err(e) => warn!("Invalid {}: {}; skipping", flash_fs::CONFIG_PATH, e),
+        }
+    } else {
+        info!("No config on flash; using built-in default");
+    }
+    if let Some(secrets) = secrets_layer() {
+        stack_layer(&mut layers, secrets);
+    }
+    layers
+}
+
+/// The `[wifi]` table from `/secrets.toml`, if present and parseable.
+fn secrets_layer() -> Option<ConfigLayer> {
+    let toml = flash_fs::read_secrets()?;
+    match ConfigLayer::parse_secrets(flash_fs::SECRETS_PATH, &toml) {
+        Ok((layer, ignored)) => {
+            for key in ignored {
+                warn!("Ignoring [{}] in {}; only [wifi] is read", key, flash_fs::SECRETS_PATH);
+            }
+            Some(layer)
+        }
+        Err(e) => {
+            warn!("Invalid {}: {}; skipping", flash_fs::SECRETS_PATH, e);
+            None
+        }
+    }
+}
+
+/// Push `layer` if the stack still validates with it on top.
+fn stack_layer(layers: &mut Vec<ConfigLayer>, layer: ConfigLayer) -> Option<()> {
+    layers.push(layer);
+    if let Err(e) = Config::from_layers(layers) {
+        let rejected = layers.pop().expect("just pushed");
+        warn!("Config layer {} rejected: {}", rejected.name, e);
+        return None;
+    }
+    Some(())
+}
+
+/// Log validation findings so adjusted or ignored values aren't a surprise.
+fn log_diagnostics(config: &Config) {
+    for diagnostic in &config.diagnostics {
+        match diagnostic.severity {
+            Severity::Warning => warn!("Config {}", diagnostic),
+            Severity::Error => error!("Config {}", diagnostic),
+        }
+    }
+}
+
+/// Re-read `/config.toml` after it changed on flash. The new file replaces any
+/// runtime overrides in NVS; a file that fails to parse or validate leaves the
+/// running config alone.
+fn reload_config(store: Option<&mut config_store::ConfigStore>) -> Option<Config> {
+    let mut layers = vec![ConfigLayer::parse("embedded", DEFAULT_CONFIG_TOML)
+        .expect("failed to parse default config")];
+    if let Some(toml) = flash_fs::read_config() {
+        match ConfigLayer::parse(flash_fs::CONFIG_PATH, &toml) {
+            Ok(layer) => layers.push(layer),
+            Err(e) => {
+                warn!("Ignoring invalid {}: {}", flash_fs::CONFIG_PATH, e);
+                return None;
+            }
+        }
+    }
+    layers.extend(secrets_layer());
+    match Config::from_layers(&layers) {
+        Ok(config) => {
+            if let Some(store) = store {
+                if let Err(e) = store.clear() {
+                    warn!("Failed to clear stored config: {:?}", e);
+                }
+            }
+            Some(config)
+        }
+        Err(e) => {
+            warn!("Ignoring invalid {}: {}", flash_fs::CONFIG_PATH, e);
+            None
+        }
+    }
+}
+
+/// The networks to try: those saved in NVS, then `[wifi]` from the config.
+/// A `bssid` in `[wifi]` also pins a saved network with the same SSID.
+fn resolve_wifi_networks(nvs: &EspDefaultNvsPartition, config: &Config) -> NetworkList {
+    let mut networks = wifi::load_networks(nvs.clone())
+        .inspect_err(|e| warn!("Failed to load NVS credentials: {:?}", e))
+        .unwrap_or_default();
+    if let Some(credentials) = config.wifi.credentials() {
+        if !networks.ssids().any(|s| s == credentials.ssid) {
+            networks.add(credentials);
+        } else if credentials.bssid.is_some() {
+            networks.set_bssid(&credentials.ssid, credentials.bssid);
+        }
+    }
+    networks
 }
@@ -0,0 +1,51 @@
+use esp_idf_svc::mdns::EspMdns;
+use esp_idf_svc::sys::EspError;
+use log::{info, warn};
+
+use crate::system;
+
+const INSTANCE_NAME: &str = "LED Sectional";
+/// The web UI, for browsers and generic service browsers.
+const HTTP_SERVICE: &str = "_http";
+/// This device specifically, for companion apps and the host CLI.
+const DEVICE_SERVICE: &str = "_ledsectional";
+const PROTO: &str = "_tcp";
+const HTTP_PORT: u16 = 80;
+const TXT_LEDS: &str = "leds";
+
+/// Answer mDNS queries for `<hostname>.local` on every interface that comes
+/// up, so the device can be reached without knowing its DHCP address, and
+/// advertise the web UI as `_http._tcp` and `_ledsectional._tcp`. The latter
+/// carries TXT records `version` (firmware), `build` and `built` (its commit
+/// and Unix build time, when known) and `leds` (LED count). The responder
+/// runs until the returned handle is dropped.
+pub fn start(hostname: &str, num_leds: usize) -> Result<EspMdns, EspError> {
+    let mut mdns = EspMdns::take()?;
+    mdns.set_hostname(hostname)?;
+    mdns.set_instance_name(INSTANCE_NAME)?;
+    mdns.add_service(None, HTTP_SERVICE, PROTO, HTTP_PORT, &[("path", "/")])?;
+    let leds = num_leds.to_string();
+    let txt: Vec<(&str, &str)> = [
+        ("version", system::FIRMWARE_VERSION),
+        ("build", system::GIT_HASH),
+        ("built", system::BUILD_TIME),
+        (TXT_LEDS, &leds),
+    ]
+    .into_iter()
+    .filter(|(_, value)| !value.is_empty())
+    .collect();
+    mdns.add_service(None, DEVICE_SERVICE, PROTO, HTTP_PORT, &txt)?;
+    info!(
+        "mDNS: answering as {}.local, advertising {}.{} and {}.{}",
+        hostname, HTTP_SERVICE, PROTO, DEVICE_SERVICE, PROTO
+    );
+    Ok(mdns)
+}
+
+/// Update the advertised LED count after the config changed.
+pub fn set_led_count(mdns: &mut EspMdns, num_leds: usize) {
+    let leds = num_leds.to_string();
+    if let Err(e) = mdns.set_service_txt_item(DEVICE_SERVICE, PROTO, TXT_LEDS, &leds) {
+        warn!("Failed to update mDNS LED count: {:?}", e);
+    }
+}
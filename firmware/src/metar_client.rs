@@ -1,78 +1,280 @@
 use esp_idf_svc::http::client::{Configuration as HttpConfig, EspHttpConnection};
 use esp_idf_svc::http::Method;
-use led_sectional_core::metar::{self, MetarReport};
+use led_sectional_core::asset;
+use led_sectional_core::metar;
+use led_sectional_core::source::{FetchResult, MetarSource};
+use led_sectional_core::winds_aloft::{self, StationWinds};
 use log::{debug, info};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::watchdog;
 
 const USER_AGENT: &str = "LED-Sectional-Rust/0.1";
 const READ_TIMEOUT_MS: u64 = 15_000;
 const RESPONSE_BUF_SIZE: usize = 4096;
+/// Upper bound on the decompressed body, well within the ESP32-C3 heap budget.
+const MAX_DECOMPRESSED_SIZE: usize = 128 * 1024;
+
+/// Cache validators from the last successful response.
+#[derive(Default)]
+struct Validators {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
 
-pub struct MetarClient;
+/// HTTPS client for aviationweather.gov.
+///
+/// The connection (and its TLS session) is kept alive across fetches and
+/// only re-established after an error, avoiding a full handshake per poll.
+/// While memory is low it is closed after each fetch instead, freeing the
+/// TLS buffers in between.
+pub struct MetarClient {
+    connection: Option<EspHttpConnection>,
+    validators: Validators,
+    low_memory: bool,
+}
 
 impl MetarClient {
     pub fn new() -> Self {
-        Self
+        Self {
+            connection: None,
+            validators: Validators::default(),
+            low_memory: false,
+        }
     }
 
-    /// Fetch METAR reports for the given airport codes via HTTPS.
-    pub fn fetch(&self, airport_codes: &[&str]) -> Result<Vec<MetarReport>, MetarFetchError> {
+    /// Stop keeping the connection between fetches while `low`, closing it
+    /// now if one is open.
+    pub fn set_low_memory(&mut self, low: bool) {
+        self.low_memory = low;
+        if low {
+            self.connection = None;
+        }
+    }
+
+    /// GET a URL over the cached connection. A reused connection the server
+    /// has since closed fails on first use, so that case retries once fresh.
+    fn get(&mut self, url: &str, conditional: bool) -> Result<Response, MetarFetchError> {
+        let reused = self.connection.is_some();
+        let result = self.try_get(url, conditional);
+        match result {
+            Err(e) if reused && e.is_transport() => {
+                debug!("Reused connection failed ({}); reconnecting", e);
+                // The retry gets a full watchdog period of its own
+                watchdog::feed();
+                self.try_get(url, conditional)
+            }
+            other => other,
+        }
+    }
+
+    fn try_get(&mut self, url: &str, conditional: bool) -> Result<Response, MetarFetchError> {
+        let connection = match self.connection.as_mut() {
+            Some(c) => c,
+            None => {
+                debug!("Opening new HTTPS connection");
+                self.connection.insert(new_connection()?)
+            }
+        };
+        let validators = conditional.then_some(&self.validators);
+        let result = request(connection, url, validators);
+        if result.is_err() || self.low_memory {
+            // Connection state is unknown after an error; start fresh next time
+            self.connection = None;
+        }
+        result
+    }
+
+    fn fetch_inner(&mut self, airport_codes: &[&str]) -> Result<FetchResult, MetarFetchError> {
         if airport_codes.is_empty() {
-            return Ok(Vec::new());
+            return Ok(FetchResult::Updated(Vec::new()));
         }
 
         let url = metar::build_metar_url(airport_codes);
         info!("Fetching METARs: {}", url);
 
-        let config = HttpConfig {
-            use_global_ca_store: true,
-            crt_bundle_attach: Some(esp_idf_svc::sys::esp_crt_bundle_attach),
-            timeout: Some(std::time::Duration::from_millis(READ_TIMEOUT_MS)),
-            ..Default::default()
+        // Validators only apply to the same station list
+        if self.validators.url != url {
+            self.validators = Validators {
+                url: url.clone(),
+                ..Default::default()
+            };
+        }
+
+        let (body, etag, last_modified) = match self.get(&url, true)? {
+            Response::NotModified => {
+                info!("METAR data not modified");
+                return Ok(FetchResult::NotModified);
+            }
+            Response::Body {
+                text,
+                etag,
+                last_modified,
+            } => (text, etag, last_modified),
         };
 
-        let mut connection = EspHttpConnection::new(&config)
-            .map_err(|e| MetarFetchError::Connection(format!("{e:?}")))?;
+        debug!("METAR response: {} bytes", body.len());
 
-        let headers = [("User-Agent", USER_AGENT)];
+        let reports = metar::parse_metars(&body)
+            .map_err(|e| MetarFetchError::Parse(e.to_string()))?;
 
-        connection
-            .initiate_request(Method::Get, &url, &headers)
-            .map_err(|e| MetarFetchError::Request(format!("{e:?}")))?;
+        info!("Parsed {} METAR reports", reports.len());
 
-        connection
-            .initiate_response()
-            .map_err(|e| MetarFetchError::Response(format!("{e:?}")))?;
+        // Only remember validators once the body has been fully accepted
+        self.validators.etag = etag;
+        self.validators.last_modified = last_modified;
 
-        let status = connection.status();
-        if status != 200 {
-            return Err(MetarFetchError::HttpStatus(status));
+        Ok(FetchResult::Updated(reports))
+    }
+}
+
+impl MetarSource for MetarClient {
+    type Error = MetarFetchError;
+
+    /// Fetch METAR reports for the given airport codes via HTTPS.
+    ///
+    /// Sends `If-None-Match`/`If-Modified-Since` from the previous response so
+    /// unchanged data costs a 304 instead of a full download and parse.
+    fn fetch(&mut self, airport_codes: &[&str]) -> Result<FetchResult, MetarFetchError> {
+        let result = self.fetch_inner(airport_codes);
+        if result.is_err() {
+            // The caller shows an error state, so the next response must be a full one
+            self.validators = Validators::default();
         }
+        result
+    }
 
-        // Read response body
-        let mut body = Vec::new();
-        let mut buf = [0u8; RESPONSE_BUF_SIZE];
-        loop {
-            use embedded_svc::io::Read;
-            let n = connection
-                .read(&mut buf)
-                .map_err(|e| MetarFetchError::Read(format!("{e:?}")))?;
-            if n == 0 {
-                break;
-            }
-            body.extend_from_slice(&buf[..n]);
+    /// Fetch the low-level winds-aloft (FD) product for the given forecast period.
+    fn fetch_winds_aloft(
+        &mut self,
+        forecast_hours: u8,
+    ) -> Result<HashMap<String, StationWinds>, MetarFetchError> {
+        let url = winds_aloft::build_winds_aloft_url(forecast_hours);
+        info!("Fetching winds aloft: {}", url);
+
+        let text = match self.get(&url, false)? {
+            Response::Body { text, .. } => text,
+            Response::NotModified => return Err(MetarFetchError::HttpStatus(304)),
+        };
+
+        let winds = winds_aloft::parse_winds_aloft(&text);
+        if winds.is_empty() {
+            return Err(MetarFetchError::Parse("no winds-aloft stations found".to_string()));
         }
+        info!("Parsed winds aloft for {} stations", winds.len());
+        Ok(winds)
+    }
 
-        let body_str = String::from_utf8(body)
-            .map_err(|e| MetarFetchError::Utf8(e.to_string()))?;
+    fn invalidate_cache(&mut self) {
+        self.validators = Validators::default();
+    }
+}
 
-        debug!("METAR response: {} bytes", body_str.len());
+enum Response {
+    Body {
+        text: String,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+    NotModified,
+}
 
-        let reports = metar::parse_metars(&body_str)
-            .map_err(|e| MetarFetchError::Parse(e.to_string()))?;
+fn new_connection() -> Result<EspHttpConnection, MetarFetchError> {
+    let config = HttpConfig {
+        use_global_ca_store: true,
+        crt_bundle_attach: Some(esp_idf_svc::sys::esp_crt_bundle_attach),
+        timeout: Some(Duration::from_millis(READ_TIMEOUT_MS)),
+        ..Default::default()
+    };
 
-        info!("Parsed {} METAR reports", reports.len());
-        Ok(reports)
+    EspHttpConnection::new(&config).map_err(|e| MetarFetchError::Connection(format!("{e:?}")))
+}
+
+/// Perform an HTTPS GET, sending conditional headers when validators are given.
+/// Gzip-encoded bodies are decompressed transparently. The body is always read
+/// to the end so the connection can be reused for the next request.
+fn request(
+    connection: &mut EspHttpConnection,
+    url: &str,
+    validators: Option<&Validators>,
+) -> Result<Response, MetarFetchError> {
+    let mut headers = vec![
+        ("User-Agent", USER_AGENT),
+        ("Accept-Encoding", "gzip"),
+        ("Connection", "keep-alive"),
+    ];
+    if let Some(v) = validators {
+        if let Some(etag) = &v.etag {
+            headers.push(("If-None-Match", etag));
+        }
+        if let Some(last_modified) = &v.last_modified {
+            headers.push(("If-Modified-Since", last_modified));
+        }
+    }
+
+    connection
+        .initiate_request(Method::Get, url, &headers)
+        .map_err(|e| MetarFetchError::Request(format!("{e:?}")))?;
+
+    connection
+        .initiate_response()
+        .map_err(|e| MetarFetchError::Response(format!("{e:?}")))?;
+
+    let status = connection.status();
+    if status == 304 {
+        return Ok(Response::NotModified);
     }
+    if status != 200 {
+        return Err(MetarFetchError::HttpStatus(status));
+    }
+
+    let etag = connection.header("ETag").map(str::to_string);
+    let last_modified = connection.header("Last-Modified").map(str::to_string);
+
+    let gzipped = connection
+        .header("Content-Encoding")
+        .is_some_and(|v| v.eq_ignore_ascii_case("gzip"));
+
+    // Read response body
+    let mut body = Vec::new();
+    let mut buf = [0u8; RESPONSE_BUF_SIZE];
+    loop {
+        use embedded_svc::io::Read;
+        let n = connection
+            .read(&mut buf)
+            .map_err(|e| MetarFetchError::Read(format!("{e:?}")))?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&buf[..n]);
+    }
+
+    if gzipped {
+        let compressed_len = body.len();
+        body = gunzip(&body)?;
+        debug!("Decompressed {} -> {} bytes", compressed_len, body.len());
+    }
+
+    let text = String::from_utf8(body)
+        .map_err(|e| MetarFetchError::Utf8(e.to_string()))?;
+
+    Ok(Response::Body {
+        text,
+        etag,
+        last_modified,
+    })
+}
+
+/// Decompress a gzip body with the pure-Rust miniz_oxide inflater, checking
+/// it against the CRC32 and size in the trailer.
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, MetarFetchError> {
+    asset::gunzip(data, |deflate| {
+        miniz_oxide::inflate::decompress_to_vec_with_limit(deflate, MAX_DECOMPRESSED_SIZE)
+            .map_err(|e| e.status)
+    })
+    .map_err(|e| MetarFetchError::Decompress(e.to_string()))
 }
 
 #[derive(Debug)]
@@ -82,10 +284,21 @@ pub enum MetarFetchError {
     Response(String),
     HttpStatus(u16),
     Read(String),
+    Decompress(String),
     Utf8(String),
     Parse(String),
 }
 
+impl MetarFetchError {
+    /// Errors from the socket/TLS layer, as opposed to a bad response.
+    fn is_transport(&self) -> bool {
+        matches!(
+            self,
+            Self::Connection(_) | Self::Request(_) | Self::Response(_) | Self::Read(_)
+        )
+    }
+}
+
 impl std::fmt::Display for MetarFetchError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -94,8 +307,9 @@ impl std::fmt::Display for MetarFetchError {
             Self::Response(e) => write!(f, "HTTP response error: {e}"),
             Self::HttpStatus(code) => write!(f, "HTTP status {code}"),
             Self::Read(e) => write!(f, "HTTP read error: {e}"),
+            Self::Decompress(e) => write!(f, "gzip decode error: {e}"),
             Self::Utf8(e) => write!(f, "UTF-8 decode error: {e}"),
-            Self::Parse(e) => write!(f, "JSON parse error: {e}"),
+            Self::Parse(e) => write!(f, "parse error: {e}"),
         }
     }
 }
@@ -0,0 +1,60 @@
+use esp_idf_svc::hal::gpio::{AnyIOPin, PinDriver, Pull};
+use log::{debug, error, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::watchdog::{self, Watch};
+
+/// PIR sensors hold their output high for seconds, so this is plenty.
+const POLL: Duration = Duration::from_millis(100);
+
+/// Whether motion was seen since [`take_motion`] last looked.
+static MOTION: AtomicBool = AtomicBool::new(false);
+
+/// Watch the active-high motion sensor on GPIO `pin` from a background
+/// thread, for [`take_motion`] to wake the map during display-off hours.
+pub fn watch(pin: u8) {
+    let spawned = std::thread::Builder::new()
+        .name("motion".into())
+        .stack_size(3072)
+        .spawn(move || {
+            // SAFETY: config validation keeps this pin off the other pins
+            // the firmware drives, and no other driver claims it.
+            let pin = unsafe { AnyIOPin::new(pin as i32) };
+            let mut sensor = match PinDriver::input(pin) {
+                Ok(sensor) => sensor,
+                Err(e) => {
+                    error!("Failed to configure motion sensor: {:?}", e);
+                    return;
+                }
+            };
+            // Keeps an unplugged sensor from reading as motion
+            if let Err(e) = sensor.set_pull(Pull::Down) {
+                warn!("Failed to enable motion sensor pull-down: {:?}", e);
+            }
+
+            let _watch = Watch::current_task_or_log("motion");
+            let mut was_high = false;
+            loop {
+                watchdog::feed();
+                let high = sensor.is_high();
+                if high && !was_high {
+                    debug!("Motion detected");
+                }
+                if high {
+                    MOTION.store(true, Ordering::Relaxed);
+                }
+                was_high = high;
+                std::thread::sleep(POLL);
+            }
+        });
+    if let Err(e) = spawned {
+        error!("Failed to start motion sensor thread: {}", e);
+    }
+}
+
+/// Whether there has been motion since the last call; keeps returning true
+/// while someone stays in view.
+pub fn take_motion() -> bool {
+    MOTION.swap(false, Ordering::Relaxed)
+}
@@ -0,0 +1,139 @@
+use esp_idf_svc::mqtt::client::{
+    Details, EspMqttClient, EventPayload, LwtConfiguration, MqttClientConfiguration, QoS,
+};
+use esp_idf_svc::sys::EspError;
+use led_sectional_core::commands::{self, Command};
+use led_sectional_core::config::MqttSettings;
+use led_sectional_core::telemetry::{Message, Telemetry, OFFLINE, ONLINE};
+use log::{info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Connection to the broker in `settings.mqtt`. ESP-IDF reconnects on its
+/// own; the main loop is told so it can publish everything again and
+/// subscribe to the command topics.
+pub struct Mqtt {
+    client: EspMqttClient<'static>,
+    connected: Arc<AtomicBool>,
+    /// Set on each (re)connect until [`take_connected`](Self::take_connected).
+    just_connected: Arc<AtomicBool>,
+    availability_topic: String,
+    command_topics: String,
+    /// Parsed commands, oldest first, until the main loop takes them.
+    commands: Arc<Mutex<Vec<Command>>>,
+}
+
+impl Mqtt {
+    /// Start connecting to `settings.broker` in the background, with
+    /// `telemetry`'s availability topic as the last will so the broker marks
+    /// the map offline if it drops off.
+    pub fn start(
+        settings: &MqttSettings,
+        client_id: &str,
+        telemetry: &Telemetry,
+    ) -> Result<Self, EspError> {
+        let availability_topic = telemetry.availability_topic();
+        let prefix = telemetry.prefix().to_string();
+        let broker = settings.broker.as_deref().unwrap_or_default();
+        let conf = MqttClientConfiguration {
+            client_id: Some(client_id),
+            username: settings.username.as_deref(),
+            password: settings.password.as_deref(),
+            lwt: Some(LwtConfiguration {
+                topic: &availability_topic,
+                payload: OFFLINE.as_bytes(),
+                qos: QoS::AtLeastOnce,
+                retain: true,
+            }),
+            // For mqtts:// brokers
+            crt_bundle_attach: Some(esp_idf_svc::sys::esp_crt_bundle_attach),
+            ..Default::default()
+        };
+
+        let connected = Arc::new(AtomicBool::new(false));
+        let just_connected = Arc::new(AtomicBool::new(false));
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let (on_connect, on_connect_flag) = (connected.clone(), just_connected.clone());
+        let on_command = commands.clone();
+        let client = EspMqttClient::new_cb(broker, &conf, move |event| match event.payload() {
+            EventPayload::Connected(_) => {
+                info!("MQTT connected");
+                on_connect.store(true, Ordering::Relaxed);
+                on_connect_flag.store(true, Ordering::Relaxed);
+            }
+            EventPayload::Disconnected => {
+                if on_connect.swap(false, Ordering::Relaxed) {
+                    warn!("MQTT disconnected; retrying");
+                }
+            }
+            EventPayload::Received {
+                topic: Some(topic),
+                data,
+                details: Details::Complete,
+                ..
+            } => {
+                let command = std::str::from_utf8(data)
+                    .map_err(|_| format!("{topic}: payload isn't UTF-8"))
+                    .and_then(|payload| Command::parse(&prefix, topic, payload));
+                match command {
+                    Ok(command) => on_command.lock().unwrap().push(command),
+                    Err(e) => warn!("Ignoring MQTT command: {}", e),
+                }
+            }
+            EventPayload::Error(e) => warn!("MQTT error: {:?}", e),
+            _ => {}
+        })?;
+        info!("MQTT connecting to {}", broker);
+
+        Ok(Self {
+            client,
+            connected,
+            just_connected,
+            command_topics: commands::subscription(telemetry.prefix()),
+            availability_topic,
+            commands,
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Whether the broker connected since the last call. Announces the map
+    /// online when it did, as the broker may hold the last will's offline,
+    /// and subscribes again since the broker forgets a clean session.
+    pub fn take_connected(&mut self) -> bool {
+        if !self.just_connected.swap(false, Ordering::Relaxed) {
+            return false;
+        }
+        let online = Message {
+            topic: self.availability_topic.clone(),
+            payload: ONLINE.to_string(),
+            retain: true,
+        };
+        self.publish(&online);
+        if let Err(e) = self.client.subscribe(&self.command_topics, QoS::AtLeastOnce) {
+            warn!("MQTT subscribe to {} failed: {:?}", self.command_topics, e);
+        }
+        true
+    }
+
+    /// Commands received since the last call, oldest first.
+    pub fn take_commands(&mut self) -> Vec<Command> {
+        std::mem::take(&mut *self.commands.lock().unwrap())
+    }
+
+    /// Send `message` at most once; telemetry is republished on the
+    /// heartbeat, so a lost one is soon replaced.
+    pub fn publish(&mut self, message: &Message) {
+        let result = self.client.enqueue(
+            &message.topic,
+            QoS::AtMostOnce,
+            message.retain,
+            message.payload.as_bytes(),
+        );
+        if let Err(e) = result {
+            warn!("MQTT publish to {} failed: {:?}", message.topic, e);
+        }
+    }
+}
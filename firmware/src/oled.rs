@@ -0,0 +1,98 @@
+use esp_idf_svc::hal::delay::BLOCK;
+use esp_idf_svc::hal::gpio::AnyIOPin;
+use esp_idf_svc::hal::i2c::{I2cConfig, I2cDriver, I2C0};
+use esp_idf_svc::hal::prelude::*;
+use esp_idf_svc::sys::EspError;
+use led_sectional_core::config::OledSettings;
+use led_sectional_core::oled::{self, Controller, Frame, WIDTH};
+use log::{error, info, warn};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, PoisonError};
+
+/// I2C fast mode: a whole frame takes about 25 ms.
+const I2C_HZ: u32 = 400_000;
+
+/// Hands frames to the panel thread; None until [`start`] succeeds.
+static FRAMES: Mutex<Option<Sender<Frame>>> = Mutex::new(None);
+
+struct Panel<'d> {
+    i2c: I2cDriver<'d>,
+    controller: Controller,
+    address: u8,
+}
+
+impl Panel<'_> {
+    fn commands(&mut self, commands: &[u8]) -> Result<(), EspError> {
+        let mut bytes = Vec::with_capacity(commands.len() + 1);
+        bytes.push(oled::COMMANDS);
+        bytes.extend_from_slice(commands);
+        self.i2c.write(self.address, &bytes, BLOCK)
+    }
+
+    fn draw(&mut self, frame: &Frame) -> Result<(), EspError> {
+        let mut data = [0; WIDTH + 1];
+        data[0] = oled::DATA;
+        for (page, pixels) in frame.pages.iter().enumerate() {
+            self.commands(&self.controller.page_commands(page as u8))?;
+            data[1..].copy_from_slice(pixels);
+            self.i2c.write(self.address, &data, BLOCK)?;
+        }
+        Ok(())
+    }
+}
+
+/// Drive the panel in `settings`, if it has pins, from a thread that draws
+/// each frame passed to [`show`] so the main loop never waits on the bus.
+pub fn start(i2c: I2C0, settings: &OledSettings) {
+    let Some((sda, scl)) = settings.pins() else {
+        return;
+    };
+    let (controller, address) = (settings.controller, settings.address);
+    let (tx, rx) = mpsc::channel::<Frame>();
+    let spawned = std::thread::Builder::new()
+        .name("oled".into())
+        .stack_size(4096)
+        .spawn(move || {
+            // SAFETY: config validation keeps sda and scl off the other pins
+            // the firmware drives, and nothing else claims them.
+            let (sda, scl) = unsafe { (AnyIOPin::new(sda as i32), AnyIOPin::new(scl as i32)) };
+            let config = I2cConfig::new().baudrate(I2C_HZ.Hz().into());
+            let i2c = match I2cDriver::new(i2c, sda, scl, &config) {
+                Ok(i2c) => i2c,
+                Err(e) => {
+                    error!("Failed to set up I2C for the OLED panel: {:?}", e);
+                    return;
+                }
+            };
+            let mut panel = Panel {
+                i2c,
+                controller,
+                address,
+            };
+            if let Err(e) = panel.commands(controller.init_commands()) {
+                error!("No {:?} OLED panel answered at {:#04x}: {:?}", controller, address, e);
+                return;
+            }
+            info!("{:?} OLED panel on at {:#04x}", controller, address);
+            while let Ok(mut frame) = rx.recv() {
+                // Only the newest picture is worth drawing
+                while let Ok(newer) = rx.try_recv() {
+                    frame = newer;
+                }
+                if let Err(e) = panel.draw(&frame) {
+                    warn!("OLED panel write failed: {:?}", e);
+                }
+            }
+        });
+    match spawned {
+        Ok(_) => *FRAMES.lock().unwrap_or_else(PoisonError::into_inner) = Some(tx),
+        Err(e) => error!("Failed to start OLED thread: {}", e),
+    }
+}
+
+/// Put `frame` on the panel, unless it isn't set up.
+pub fn show(frame: Frame) {
+    if let Some(tx) = FRAMES.lock().unwrap_or_else(PoisonError::into_inner).as_ref() {
+        let _ = tx.send(frame);
+    }
+}
@@ -0,0 +1,251 @@
+use esp_idf_svc::http::client::{Configuration as HttpConfig, EspHttpConnection};
+use esp_idf_svc::http::Method;
+use esp_idf_svc::io::{EspIOError, Read};
+use esp_idf_svc::ota::{EspOta, SlotState};
+use esp_idf_svc::sys::{self, EspError};
+use led_sectional_core::log_event::{EventCode, LogEvent};
+use led_sectional_core::ota::{AppInfo, Manifest, Sha256, IMAGE_HEADER_LEN};
+use log::{info, warn};
+use std::time::Duration;
+
+use crate::logging;
+use crate::system::{self, FIRMWARE_VERSION};
+use crate::watchdog;
+
+const READ_TIMEOUT: Duration = Duration::from_secs(15);
+/// Largest manifest read; a real one is a few hundred bytes.
+const MAX_MANIFEST_SIZE: usize = 4 * 1024;
+/// Bytes written between `ota_progress` events.
+const PROGRESS_EVERY: usize = 64 * 1024;
+
+/// Why an update wasn't installed.
+#[derive(Debug)]
+pub enum OtaError {
+    /// Not an image for this firmware; nothing was written.
+    Image(led_sectional_core::error::Error),
+    /// Reading the upload failed partway.
+    Read(EspIOError),
+    /// Writing or finishing the update failed, including the final image
+    /// verification.
+    Ota(EspError),
+    /// The update manifest couldn't be fetched or parsed.
+    Manifest(String),
+    /// The download didn't match the manifest's SHA-256; it was discarded.
+    Digest,
+}
+
+impl std::fmt::Display for OtaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Image(e) => write!(f, "{e}"),
+            Self::Read(e) => write!(f, "upload interrupted: {e:?}"),
+            Self::Ota(e) => write!(f, "update failed: {e:?}"),
+            Self::Manifest(e) => write!(f, "update check failed: {e}"),
+            Self::Digest => write!(f, "download doesn't match the manifest's SHA-256"),
+        }
+    }
+}
+
+impl std::error::Error for OtaError {}
+
+/// The running firmware's own app description.
+pub fn running_app() -> AppInfo {
+    // SAFETY: returns a pointer to the app description linked into this
+    // image, which is valid for the life of the program.
+    let desc = unsafe { &*sys::esp_app_get_description() };
+    // SAFETY: `esp_app_desc_t` is plain old data; viewing it as bytes is
+    // sound and the slice doesn't outlive `desc`.
+    let bytes = unsafe {
+        std::slice::from_raw_parts(
+            desc as *const sys::esp_app_desc_t as *const u8,
+            std::mem::size_of::<sys::esp_app_desc_t>(),
+        )
+    };
+    AppInfo::from_app_desc(bytes).expect("the running image has an app description")
+}
+
+/// Stream a firmware image from `body` into the inactive OTA slot and make it
+/// the boot image. The start of the image is checked before anything is
+/// erased, and the whole of it against `sha256` when given; the bootloader's
+/// own verification runs when the write finishes. The new firmware runs on
+/// the next reboot, and is rolled back unless it confirms itself with
+/// [`confirm_running`].
+pub fn install(
+    body: &mut impl Read<Error = EspIOError>,
+    running: &AppInfo,
+    sha256: Option<&[u8; 32]>,
+) -> Result<AppInfo, OtaError> {
+    let mut header = vec![0u8; IMAGE_HEADER_LEN];
+    let mut filled = 0;
+    while filled < header.len() {
+        match body.read(&mut header[filled..]).map_err(OtaError::Read)? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    let update = running.check_update(&header[..filled]).map_err(OtaError::Image)?;
+    info!("Installing firmware {} over {}", update.version, running.version);
+    logging::emit(
+        LogEvent::new(EventCode::OtaStarted)
+            .field("version", update.version.to_string())
+            .field("from", running.version.to_string()),
+    );
+
+    let mut ota = EspOta::new().map_err(OtaError::Ota)?;
+    let mut writer = ota.initiate_update().map_err(OtaError::Ota)?;
+    let mut buf = [0u8; 4096];
+    let mut total = header.len();
+    let mut digest = Sha256::new();
+    digest.update(&header);
+    let written = writer.write(&header).map_err(OtaError::Ota).and_then(|_| loop {
+        watchdog::feed();
+        let n = body.read(&mut buf).map_err(OtaError::Read)?;
+        if n == 0 {
+            break Ok(());
+        }
+        writer.write(&buf[..n]).map_err(OtaError::Ota)?;
+        digest.update(&buf[..n]);
+        if total / PROGRESS_EVERY != (total + n) / PROGRESS_EVERY {
+            logging::emit(
+                LogEvent::new(EventCode::OtaProgress)
+                    .field("version", update.version.to_string())
+                    .field("bytes", total + n),
+            );
+        }
+        total += n;
+    });
+    let written = written.and_then(|_| match sha256 {
+        Some(expected) if digest.finish() != *expected => Err(OtaError::Digest),
+        _ => Ok(()),
+    });
+    if let Err(e) = written {
+        if let Err(abort) = writer.abort() {
+            warn!("Failed to abort the update: {:?}", abort);
+        }
+        logging::emit(
+            LogEvent::new(EventCode::OtaFailed)
+                .field("version", update.version.to_string())
+                .field("error", e.to_string()),
+        );
+        return Err(e);
+    }
+    writer.complete().map_err(OtaError::Ota)?;
+    info!("Firmware {} written ({} bytes); it runs after a reboot", update.version, total);
+    logging::emit(
+        LogEvent::new(EventCode::OtaWritten)
+            .field("version", update.version.to_string())
+            .field("bytes", total),
+    );
+    Ok(update)
+}
+
+/// Fetch the manifest at `url` and, if it names a newer version than the
+/// running one, download and install it. Returns the installed update, or
+/// None when already up to date; either way nothing runs until a reboot.
+pub fn check_for_update(url: &str, running: &AppInfo) -> Result<Option<AppInfo>, OtaError> {
+    info!("Checking for a firmware update: {}", url);
+    logging::emit(LogEvent::new(EventCode::OtaChecking).field("url", url));
+    let mut connection = get(url).map_err(OtaError::Manifest)?;
+    let mut json = Vec::new();
+    let mut buf = [0u8; 512];
+    loop {
+        watchdog::feed();
+        let n = connection.read(&mut buf).map_err(OtaError::Read)?;
+        if n == 0 {
+            break;
+        }
+        if json.len() + n > MAX_MANIFEST_SIZE {
+            return Err(OtaError::Manifest("manifest too large".to_string()));
+        }
+        json.extend_from_slice(&buf[..n]);
+    }
+    let json = String::from_utf8(json).map_err(|e| OtaError::Manifest(e.to_string()))?;
+    let manifest = Manifest::parse(&json).map_err(|e| OtaError::Manifest(e.to_string()))?;
+    if !manifest.is_newer_than(&running.version) {
+        info!("Firmware {} is up to date (latest {})", running.version, manifest.version);
+        logging::emit(
+            LogEvent::new(EventCode::OtaUpToDate)
+                .field("version", running.version.to_string())
+                .field("latest", manifest.version.to_string()),
+        );
+        return Ok(None);
+    }
+
+    info!("Downloading firmware {}: {}", manifest.version, manifest.url);
+    let mut connection = get(&manifest.url).map_err(OtaError::Manifest)?;
+    install(&mut connection, running, Some(&manifest.sha256)).map(Some)
+}
+
+/// Start an HTTPS GET and return the connection positioned at the body.
+fn get(url: &str) -> Result<EspHttpConnection, String> {
+    let mut connection = EspHttpConnection::new(&HttpConfig {
+        use_global_ca_store: true,
+        crt_bundle_attach: Some(sys::esp_crt_bundle_attach),
+        timeout: Some(READ_TIMEOUT),
+        ..Default::default()
+    })
+    .map_err(|e| format!("{e:?}"))?;
+    // Names the exact build, so a manifest server can tell who is asking
+    let user_agent = system::build_info().user_agent();
+    connection
+        .initiate_request(Method::Get, url, &[("User-Agent", user_agent.as_str())])
+        .map_err(|e| format!("{e:?}"))?;
+    connection.initiate_response().map_err(|e| format!("{e:?}"))?;
+    match connection.status() {
+        200 => Ok(connection),
+        status => Err(format!("HTTP {status} from {url}")),
+    }
+}
+
+/// Reboot into a freshly installed update, after a moment for any HTTP
+/// response to reach the browser.
+pub fn reboot() -> ! {
+    info!("Rebooting into the new firmware...");
+    std::thread::sleep(Duration::from_millis(500));
+    // SAFETY: esp_restart() is always safe to call and triggers a clean reboot.
+    unsafe { sys::esp_restart() };
+}
+
+/// Log how this boot came about: a new image waiting to be confirmed, or an
+/// update that was rolled back.
+pub fn log_boot_state() {
+    let ota = match EspOta::new() {
+        Ok(ota) => ota,
+        Err(e) => {
+            warn!("Failed to read OTA state: {:?}", e);
+            return;
+        }
+    };
+    if let Ok(Some(slot)) = ota.get_last_invalid_slot() {
+        let version = slot.firmware.map(|f| f.version.to_string()).unwrap_or_default();
+        warn!("Firmware {} in {} didn't confirm itself and was rolled back", version, slot.label);
+        logging::emit(LogEvent::new(EventCode::OtaRolledBack).field("version", version));
+    }
+    if ota.get_running_slot().is_ok_and(|slot| slot.state == SlotState::Unverified) {
+        info!("Running newly installed firmware; it is kept once WiFi connects");
+    }
+}
+
+/// Keep the running firmware if it was just installed. Until this is called
+/// a reboot goes back to the previous image, so a bad update that can't get
+/// online undoes itself on the next power cycle.
+pub fn confirm_running() {
+    let mut ota = match EspOta::new() {
+        Ok(ota) => ota,
+        Err(e) => {
+            warn!("Failed to read OTA state: {:?}", e);
+            return;
+        }
+    };
+    if !ota.get_running_slot().is_ok_and(|slot| slot.state == SlotState::Unverified) {
+        return;
+    }
+    match ota.mark_running_slot_valid() {
+        Ok(()) => {
+            info!("New firmware confirmed");
+            let event = LogEvent::new(EventCode::OtaConfirmed).field("version", FIRMWARE_VERSION);
+            logging::emit(event);
+        }
+        Err(e) => warn!("Failed to confirm new firmware: {:?}", e),
+    }
+}
@@ -0,0 +1,90 @@
+use esp_idf_svc::sys::{self, esp, EspError};
+use led_sectional_core::config::PowerSettings;
+use log::{info, warn};
+use std::ffi::c_void;
+use std::sync::OnceLock;
+
+/// Locks held while the LED strip is written: the RMT and SPI bit timing
+/// depends on the APB clock, and both stop in light sleep.
+struct StripLocks {
+    apb_max: sys::esp_pm_lock_handle_t,
+    no_light_sleep: sys::esp_pm_lock_handle_t,
+}
+
+// SAFETY: power management locks may be taken and released from any task.
+unsafe impl Send for StripLocks {}
+unsafe impl Sync for StripLocks {}
+
+/// None if power management isn't built in, in which case the clock never
+/// changes and nothing needs holding.
+static STRIP_LOCKS: OnceLock<Option<StripLocks>> = OnceLock::new();
+
+/// Scale the CPU clock between `settings.min_cpu_mhz` and `max_cpu_mhz`,
+/// dropping it whenever every task is idle, and light-sleep then as well
+/// with `settings.light_sleep`. Called at boot and when they change.
+pub fn configure(settings: &PowerSettings) {
+    let config = sys::esp_pm_config_t {
+        max_freq_mhz: settings.max_cpu_mhz as i32,
+        min_freq_mhz: settings.min_cpu_mhz as i32,
+        light_sleep_enable: settings.light_sleep,
+    };
+    // SAFETY: `config` is a valid esp_pm_config_t; it's copied before the
+    // call returns.
+    match esp!(unsafe { sys::esp_pm_configure(&config as *const _ as *const c_void) }) {
+        Ok(()) => info!(
+            "CPU at {}-{} MHz, light sleep {}",
+            settings.min_cpu_mhz,
+            settings.max_cpu_mhz,
+            if settings.light_sleep { "on" } else { "off" }
+        ),
+        Err(e) => warn!("Failed to set up power management: {:?}", e),
+    }
+}
+
+/// Run `write` with the clock at full speed and light sleep held off, so
+/// an LED frame goes out with its timing intact.
+pub fn while_writing_strip<T>(write: impl FnOnce() -> T) -> T {
+    let Some(locks) = STRIP_LOCKS.get_or_init(create_strip_locks) else {
+        return write();
+    };
+    // SAFETY: both handles came from esp_pm_lock_create and are never
+    // deleted; each acquire is paired with the release below.
+    unsafe {
+        sys::esp_pm_lock_acquire(locks.apb_max);
+        sys::esp_pm_lock_acquire(locks.no_light_sleep);
+    }
+    let result = write();
+    // SAFETY: as above.
+    unsafe {
+        sys::esp_pm_lock_release(locks.no_light_sleep);
+        sys::esp_pm_lock_release(locks.apb_max);
+    }
+    result
+}
+
+fn create_strip_locks() -> Option<StripLocks> {
+    let create = |kind, name: &'static [u8]| -> Result<sys::esp_pm_lock_handle_t, EspError> {
+        let mut handle = std::ptr::null_mut();
+        // SAFETY: `name` is NUL-terminated and static, as the lock keeps it;
+        // `handle` is written before the call returns.
+        esp!(unsafe { sys::esp_pm_lock_create(kind, 0, name.as_ptr().cast(), &mut handle) })?;
+        Ok(handle)
+    };
+    let locks = create(sys::esp_pm_lock_type_t_ESP_PM_APB_FREQ_MAX, b"led_apb\0")
+        .and_then(|apb_max| {
+            let no_light_sleep =
+                create(sys::esp_pm_lock_type_t_ESP_PM_NO_LIGHT_SLEEP, b"led_awake\0")?;
+            Ok(StripLocks {
+                apb_max,
+                no_light_sleep,
+            })
+        });
+    match locks {
+        Ok(locks) => Some(locks),
+        Err(e) if e.code() == sys::ESP_ERR_NOT_SUPPORTED => None,
+        Err(e) => {
+            warn!("Failed to create power management locks for the LEDs: {:?}", e);
+            None
+        }
+    }
+}
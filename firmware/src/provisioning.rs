@@ -1,23 +1,52 @@
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::hal::modem::Modem;
-use esp_idf_svc::http::server::{Configuration as HttpConfig, EspHttpServer};
+use esp_idf_svc::http::server::{EspHttpConnection, Request};
 use esp_idf_svc::http::Method;
-use esp_idf_svc::io::Write;
+use esp_idf_svc::io::EspIOError;
 use esp_idf_svc::nvs::EspDefaultNvsPartition;
-use esp_idf_svc::wifi::{AccessPointConfiguration, BlockingWifi, Configuration, EspWifi};
-use log::{info, warn};
+use esp_idf_svc::sys::{esp, esp_mac_type_t_ESP_MAC_WIFI_STA, esp_read_mac, EspError};
+use esp_idf_svc::wifi::{
+    AccessPointConfiguration, AccessPointInfo, AuthMethod, BlockingWifi, ClientConfiguration,
+    Configuration, EspWifi,
+};
+use led_sectional_core::captive_dns;
+use led_sectional_core::config::Config;
+use led_sectional_core::networks::{self, Credentials, NetworkList, ScannedNetwork, WifiAuth};
+use led_sectional_core::presets;
+use led_sectional_core::setup::{AirportChoice, AttemptProgress, SetupChoices};
+use led_sectional_core::wifi_link::{failure_message, LinkEvent, REASON_ASSOC_LEAVE};
+use log::{error, info, warn};
+use std::net::{Ipv4Addr, UdpSocket};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use crate::wifi;
+use crate::button;
+use crate::config_store::ConfigStore;
+use crate::flash_fs;
+use crate::http::{self, html_escape};
+use crate::smartconfig::SmartConfig;
+use crate::web::{SetupPage, SharedState};
+use crate::wifi::{self, WifiManager};
+use crate::wifi_events::WifiEvents;
+use crate::wps::Wps;
 
-const AP_SSID: &str = "LED-Sectional-Setup";
 const AP_MAX_CONNECTIONS: u16 = 4;
 const PORTAL_TIMEOUT_SECS: u64 = 180;
+/// How long the setup AP stays up alongside the running map.
+const SETUP_SESSION_TIMEOUT: Duration = Duration::from_secs(600);
+/// Enough for the longest SSID, password, and enterprise login plus a long
+/// custom airport list, URL-encoded.
+const MAX_FORM_BODY: usize = 4096;
+/// How long the setup AP stays up after a network connects, so the progress
+/// page's next poll sees the result.
+const WIND_DOWN: Duration = Duration::from_secs(5);
 
 const HTML_FORM: &str = r#"<!DOCTYPE html>
 <html>
 <head>
+<meta charset="utf-8">
 <meta name="viewport" content="width=device-width,initial-scale=1">
 <title>LED Sectional Setup</title>
 <style>
@@ -26,182 +55,761 @@ body{font-family:system-ui,sans-serif;background:#1a1a2e;color:#e0e0e0;display:f
 .card{background:#16213e;border-radius:12px;padding:2rem;width:100%;max-width:400px;box-shadow:0 4px 24px rgba(0,0,0,.4)}
 h1{font-size:1.4rem;margin-bottom:1.5rem;text-align:center;color:#a8d8ea}
 label{display:block;margin-bottom:.3rem;font-size:.9rem;color:#a0a0a0}
-input[type=text],input[type=password]{width:100%;padding:.7rem;border:1px solid #333;border-radius:6px;background:#0f3460;color:#fff;font-size:1rem;margin-bottom:1rem}
+input[type=text],input[type=password],select,textarea{width:100%;padding:.7rem;border:1px solid #333;border-radius:6px;background:#0f3460;color:#fff;font-size:1rem;margin-bottom:1rem}
 input:focus{outline:none;border-color:#a8d8ea}
 button{width:100%;padding:.8rem;border:none;border-radius:6px;background:#e94560;color:#fff;font-size:1rem;cursor:pointer;font-weight:600}
 button:hover{background:#c73e54}
 p{text-align:center;margin-top:1rem;font-size:.85rem;color:#666}
+.saved{text-align:left;margin:0 0 1rem;color:#a0a0a0}
+.error{text-align:left;margin:0 0 1rem;color:#e94560}
+h2{font-size:1.1rem;margin:.5rem 0 1rem;color:#a8d8ea}
+input[type=range]{width:100%;margin-bottom:1rem}
+label.check{display:flex;gap:.5rem;align-items:center;margin-bottom:.8rem;color:#e0e0e0}
 </style>
 </head>
 <body>
 <div class="card">
 <h1>LED Sectional WiFi Setup</h1>
-<form method="POST" action="/connect">
-<label for="ssid">WiFi Network Name (SSID)</label>
+{ERROR}{SAVED_NETWORKS}<form method="POST" action="{ACTION}">
+{SCAN_RESULTS}<label for="ssid">WiFi Network Name (SSID)</label>
 <input type="text" id="ssid" name="ssid" required maxlength="32" autocomplete="off">
+<label class="check"><input type="checkbox" id="hidden" name="hidden"> Hidden network (not in the list above)</label>
 <label for="password">Password</label>
 <input type="password" id="password" name="password" maxlength="64" autocomplete="off">
-<button type="submit">Connect</button>
+<label for="auth">Security</label>
+<select id="auth" name="auth" onchange="document.getElementById('eap').hidden=this.value!='wpa2_enterprise'">
+<option value="auto">Automatic (Open or WPA2)</option>
+<option value="open">Open (no password)</option>
+<option value="wpa2_personal">WPA2-Personal</option>
+<option value="wpa3_personal">WPA3-Personal</option>
+<option value="wpa2_enterprise">WPA2-Enterprise</option>
+</select>
+<div id="eap" hidden>
+<label for="username">Username</label>
+<input type="text" id="username" name="username" maxlength="128" autocomplete="off">
+<label for="identity">Anonymous identity (optional)</label>
+<input type="text" id="identity" name="identity" maxlength="128" autocomplete="off">
+</div>
+{MAP_SETTINGS}<button type="submit">Connect</button>
 </form>
-<p>Device will reboot after saving credentials.</p>
+<script>
+function pick(s){var o=s.options[s.selectedIndex];if(!o.value)return;
+document.getElementById('ssid').value=o.value;document.getElementById('hidden').checked=false;
+var a=document.getElementById('auth');a.value=o.dataset.auth;a.onchange();
+document.getElementById('password').focus()}
+</script>
+<p>The connection is tested before it's saved. The device tries every saved network, starting with the last one that worked.</p>
 </div>
 </body>
 </html>"#;
 
-const HTML_SUCCESS: &str = r#"<!DOCTYPE html>
+const HTML_PROGRESS: &str = r#"<!DOCTYPE html>
 <html>
 <head>
+<meta charset="utf-8">
 <meta name="viewport" content="width=device-width,initial-scale=1">
-<title>LED Sectional - Saved</title>
+<title>LED Sectional - Connecting</title>
 <style>
 body{font-family:system-ui,sans-serif;background:#1a1a2e;color:#e0e0e0;display:flex;justify-content:center;align-items:center;min-height:100vh}
 .card{background:#16213e;border-radius:12px;padding:2rem;text-align:center;max-width:400px}
 h1{color:#a8d8ea;margin-bottom:1rem}
+a{color:#e94560}
 </style>
 </head>
 <body>
 <div class="card">
-<h1>Credentials Saved</h1>
-<p>Rebooting to connect to your WiFi network...</p>
+<h1 id="title">Connecting…</h1>
+<p id="message">Trying the network…</p>
+<p id="retry" hidden><a href="{RETRY_URL}">Try again</a></p>
 </div>
+<script>
+function show(t,m){document.getElementById('title').textContent=t;document.getElementById('message').textContent=m}
+function poll(){fetch('{STATUS_URL}').then(function(r){return r.json()}).then(function(s){
+if(s.state=='connected'){show('Connected','Got IP '+s.ip+' on '+s.ssid+'. {DONE}');return}
+if(s.state=='failed'){show('Not connected','Failed: '+s.error+'.');document.getElementById('retry').hidden=false;return}
+if(s.ssid)show('Connecting…','Connecting to '+s.ssid+'…');
+setTimeout(poll,1000)}).catch(function(){setTimeout(poll,1000)})}
+poll()
+</script>
 </body>
 </html>"#;
 
+/// The map part of the first-boot form; the running map's setup page leaves
+/// it out and only changes WiFi.
+const HTML_MAP_SETTINGS: &str = r#"<h2>Map</h2>
+<input type="hidden" name="map" value="1">
+<label for="brightness">Brightness: <output id="bv">{BRIGHTNESS}</output>%</label>
+<input type="range" id="brightness" name="brightness" min="1" max="100" value="{BRIGHTNESS}" oninput="document.getElementById('bv').value=this.value">
+<label for="preset">Airports</label>
+<select id="preset" name="preset" onchange="document.getElementById('custom').hidden=this.value!='custom'">
+<option value="">Keep configured airports</option>
+{PRESET_OPTIONS}<option value="custom">Custom list…</option>
+</select>
+<div id="custom" hidden>
+<label for="airport_list">Airport codes, one per LED in strip order</label>
+<textarea id="airport_list" name="airport_list" rows="4" placeholder="KSEA KBFI KPAE"></textarea>
+</div>
+<label class="check"><input type="checkbox" name="do_lightning"{LIGHTNING}> Flash for lightning</label>
+<label class="check"><input type="checkbox" name="do_winds"{WINDS}> Blink for high winds</label>
+<label class="check"><input type="checkbox" name="do_fog_risk"{FOG_RISK}> Show fog risk</label>
+"#;
+
+/// Credentials submitted from the form, waiting to be tried by the thread
+/// that owns the WiFi driver.
+pub struct Attempt {
+    pub credentials: Credentials,
+    /// Map settings, when the form included them.
+    pub map: Option<SetupChoices>,
+}
+
+/// The latest attempt's progress, updated by whoever tries it and served as
+/// JSON to the progress page.
+pub type Progress = Arc<Mutex<AttemptProgress>>;
+
+fn set_progress(progress: &Progress, update: AttemptProgress) {
+    *progress.lock().unwrap() = update;
+}
+
+/// This device's setup AP name, `LED-Sectional-XXXX` after the end of its
+/// station MAC address (the one on the module's label).
+pub fn setup_ap_ssid() -> String {
+    let mut mac = [0u8; 6];
+    // SAFETY: `mac` is the six-byte buffer esp_read_mac writes; the station
+    // MAC comes from eFuse and can be read before WiFi starts.
+    let read = esp!(unsafe { esp_read_mac(mac.as_mut_ptr(), esp_mac_type_t_ESP_MAC_WIFI_STA) });
+    if let Err(e) = read {
+        warn!("Failed to read MAC address: {:?}", e);
+    }
+    networks::setup_ap_ssid(&mac)
+}
+
+/// The setup access point: WPA2 with `password`, or open without one.
+pub fn setup_ap_configuration(password: Option<&str>) -> AccessPointConfiguration {
+    let mut ap = AccessPointConfiguration {
+        ssid: setup_ap_ssid().as_str().try_into().unwrap_or_default(),
+        max_connections: AP_MAX_CONNECTIONS,
+        ..Default::default()
+    };
+    if let Some(password) = password {
+        ap.auth_method = AuthMethod::WPA2Personal;
+        ap.password = password.try_into().unwrap_or_default();
+    }
+    ap
+}
+
 /// Start the captive portal for WiFi provisioning.
 ///
-/// This function blocks until credentials are received or timeout elapses.
-/// On successful credential submission, the device reboots.
+/// This function blocks until working credentials are received or timeout
+/// elapses. Submitted credentials are tried over the station interface while
+/// the AP stays up, and the form answers with a page that follows the attempt
+/// through `GET /status`. Only credentials that connect are saved, along with
+/// the map settings applied to `base` as a new `/config.toml`, then the
+/// device reboots. On timeout the portal is taken down and this returns.
 pub fn start_captive_portal(
     modem: Modem,
     sysloop: EspSystemEventLoop,
     nvs: EspDefaultNvsPartition,
+    base: &Config,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    info!("Starting captive portal AP: {}", AP_SSID);
+
+    // Disconnect reasons tell a wrong password from a network out of range
+    let events = WifiEvents::subscribe(sysloop.clone())?;
 
     // Start WiFi in AP mode
     let mut wifi = BlockingWifi::wrap(
         EspWifi::new(modem, sysloop.clone(), Some(nvs.clone()))?,
         sysloop,
     )?;
+    wifi::keep_driver_config_in_ram()?;
 
-    let ap_config = AccessPointConfiguration {
-        ssid: AP_SSID.try_into().unwrap_or_default(),
-        max_connections: AP_MAX_CONNECTIONS,
-        ..Default::default()
-    };
+    let ap_config = setup_ap_configuration(base.settings.provisioning.ap_password.as_deref());
+    info!("Starting captive portal AP: {}", ap_config.ssid);
 
-    wifi.set_configuration(&Configuration::AccessPoint(ap_config))?;
+    // AP+STA so the station interface can scan while the AP is up
+    let idle = Configuration::Mixed(ClientConfiguration::default(), ap_config.clone());
+    wifi.set_configuration(&idle)?;
     wifi.start()?;
     wifi.wait_netif_up()?;
+    let scanned = scan_networks(&mut wifi);
 
     let ip_info = wifi.wifi().ap_netif().get_ip_info()?;
-    info!("AP started. IP: {}, SSID: {}", ip_info.ip, AP_SSID);
+    info!("AP started. IP: {}, SSID: {}", ip_info.ip, ap_config.ssid);
+    let _dns = DnsResponder::start(ip_info.ip);
 
-    // Track whether credentials have been received
-    let credentials_received = Arc::new(AtomicBool::new(false));
-    let credentials_received_clone = credentials_received.clone();
-    let nvs_clone = nvs.clone();
+    // Submissions are tried on this thread, which owns the WiFi driver
+    let (attempt_tx, attempt_rx) = mpsc::sync_channel::<Attempt>(1);
+    let progress = Progress::default();
 
-    // Start HTTP server
-    let mut server = EspHttpServer::new(&HttpConfig::default())?;
+    // The ESP-Touch app can deliver credentials too; first to work wins
+    let smartconfig = if base.settings.provisioning.smartconfig {
+        SmartConfig::start(attempt_tx.clone())
+            .inspect_err(|e| warn!("Failed to start SmartConfig: {:?}", e))
+            .ok()
+    } else {
+        None
+    };
+    // So can WPS, once the setup button is quick-pressed
+    let wps = if base.settings.provisioning.wps {
+        button::take_quick_press();
+        Wps::new(attempt_tx.clone())
+            .inspect(|_| info!("Quick-press the setup button to pair by WPS"))
+            .inspect_err(|e| warn!("Failed to set up WPS: {:?}", e))
+            .ok()
+    } else {
+        None
+    };
+
+    // Start HTTP server; wildcards let unknown paths redirect to the form
+    let mut server = http::server(None)?;
 
     // GET / — serve the WiFi config form
-    server.fn_handler("/", Method::Get, |req| {
-        let mut resp = req.into_ok_response()?;
-        resp.write_all(HTML_FORM.as_bytes())?;
-        Ok(())
+    let saved = wifi::load_networks(nvs.clone())
+        .map(|n| n.ssids().map(str::to_string).collect())
+        .unwrap_or_default();
+    let map = SetupChoices::from_config(base);
+    let form = render_form(&saved, &scanned, Some(&map), "/connect");
+    let retry_form = form.clone();
+    server.fn_handler("/", Method::Get, move |req| {
+        http::html(req, &form.replace("{ERROR}", ""))
     })?;
 
-    // POST /connect — receive credentials, store in NVS, reboot
-    server.fn_handler("/connect", Method::Post, move |mut req| {
-        // Read the POST body
-        let mut body = vec![0u8; 256];
-        let len = req.read(&mut body).unwrap_or(0);
-        let body_str = String::from_utf8_lossy(&body[..len]);
+    // POST /connect — receive credentials and hand them over for testing,
+    // answering straight away with the progress page
+    let page = progress_page(
+        "/status",
+        "/",
+        "The device saved it and is rebooting; you can rejoin your usual WiFi.",
+    );
+    let connect_progress = progress.clone();
+    server.fn_handler("/connect", Method::Post, move |req| {
+        handle_connect(req, &attempt_tx, &connect_progress, &retry_form, &page)
+    })?;
+
+    // GET /status — how the latest attempt is going, for the progress page
+    let status_progress = progress.clone();
+    server.fn_handler("/status", Method::Get, move |req| {
+        let json = status_progress.lock().unwrap().to_json();
+        http::json(req, &json)
+    })?;
 
-        // Parse form-urlencoded data
-        let (ssid, password) = parse_form_data(&body_str);
+    // Anything else (OS connectivity probes like /generate_204 or
+    // /hotspot-detect.html) redirects to the form; registered last so the
+    // routes above match first
+    let portal_url = format!("http://{}/", ip_info.ip);
+    http::fallback(&mut server, move || Some(portal_url.clone()))?;
 
-        if ssid.is_empty() {
-            let mut resp = req.into_response(400, None, &[("Content-Type", "text/plain")])?;
-            resp.write_all(b"SSID is required")?;
-            return Ok(());
+    // Try submitted credentials until one works or the portal times out
+    let deadline = Instant::now() + Duration::from_secs(PORTAL_TIMEOUT_SECS);
+    while Instant::now() < deadline {
+        if let Some(wps) = wps.as_ref().filter(|_| button::take_quick_press()) {
+            if let Err(e) = wps.pair() {
+                warn!("Failed to start WPS: {:?}", e);
+            }
         }
+        let attempt = match attempt_rx.recv_timeout(Duration::from_millis(500)) {
+            Ok(attempt) => attempt,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let ssid = attempt.credentials.ssid.clone();
+        set_progress(&progress, AttemptProgress::Connecting { ssid: ssid.clone() });
+        // Build the new config first so bad map settings fail before the test
+        let config_toml = attempt
+            .map
+            .as_ref()
+            .map(|map| map.apply(base))
+            .transpose()
+            .map_err(|e| format!("the map settings are invalid ({e})"));
+        let result = config_toml.and_then(|config_toml| {
+            let ip = test_connection(
+                &mut wifi,
+                &events,
+                &attempt.credentials,
+                &ap_config,
+                smartconfig.as_ref(),
+            )?;
+            wifi::store_credentials(nvs.clone(), attempt.credentials)
+                .map_err(|e| format!("failed to save credentials ({e:?})"))?;
+            if let Some(config_toml) = config_toml {
+                write_setup_config(nvs.clone(), &config_toml)?;
+            }
+            Ok(ip)
+        });
+        match result {
+            Ok(ip) => set_progress(&progress, AttemptProgress::Connected { ssid, ip }),
+            Err(error) => {
+                if let Some(smartconfig) = &smartconfig {
+                    smartconfig.rearm();
+                }
+                set_progress(&progress, AttemptProgress::Failed { ssid, error });
+                continue;
+            }
+        }
+        info!("Credentials verified and saved. Rebooting in {}s...", WIND_DOWN.as_secs());
+        std::thread::sleep(WIND_DOWN);
+        // SAFETY: esp_restart() is always safe to call and triggers a clean reboot.
+        unsafe { esp_idf_svc::sys::esp_restart() };
+    }
 
-        info!("Received WiFi credentials for SSID: {}", ssid);
+    warn!("Captive portal timed out after {}s", PORTAL_TIMEOUT_SECS);
+    Ok(())
+}
 
-        // Store in NVS
-        if let Err(e) = wifi::store_credentials(nvs_clone.clone(), &ssid, &password) {
-            warn!("Failed to store credentials: {:?}", e);
-            let mut resp = req.into_response(500, None, &[("Content-Type", "text/plain")])?;
-            resp.write_all(b"Failed to save credentials")?;
-            return Ok(());
+/// Handle a form submission: validate it, pass it to the WiFi thread through
+/// `attempts`, and answer with `page`, which follows the attempt through
+/// `progress`. Bad input gets `form` back with the error; it still has its
+/// `{ERROR}` placeholder.
+pub fn handle_connect(
+    mut req: Request<&mut EspHttpConnection>,
+    attempts: &SyncSender<Attempt>,
+    progress: &Progress,
+    form: &str,
+    page: &str,
+) -> Result<(), EspIOError> {
+    let Some(body) = http::read_body(&mut req, MAX_FORM_BODY)? else {
+        return http::respond(req, 413, "form too large");
+    };
+    let body_str = String::from_utf8_lossy(&body);
+
+    // Parse form-urlencoded data
+    let (credentials, map) = match parse_form_data(&body_str) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            let error = format!("<p class=\"error\">{}.</p>\n", html_escape(&e));
+            return http::html(req, &form.replace("{ERROR}", &error));
         }
+    };
 
-        // Send success response
-        let mut resp = req.into_ok_response()?;
-        resp.write_all(HTML_SUCCESS.as_bytes())?;
+    if credentials.ssid.is_empty() {
+        return http::respond(req, 400, "SSID is required");
+    }
+    if let Some(problem) = credentials.problem() {
+        return http::respond(req, 400, problem);
+    }
 
-        credentials_received_clone.store(true, Ordering::Relaxed);
-        Ok(())
-    })?;
+    info!(
+        "Received WiFi credentials for {}SSID: {} ({:?})",
+        if credentials.hidden { "hidden " } else { "" },
+        credentials.ssid,
+        credentials.auth_method()
+    );
 
-    // Wait for credentials or timeout
-    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(PORTAL_TIMEOUT_SECS);
-    while std::time::Instant::now() < deadline {
-        if credentials_received.load(Ordering::Relaxed) {
-            info!("Credentials received. Rebooting in 2 seconds...");
-            std::thread::sleep(std::time::Duration::from_secs(2));
-            // SAFETY: esp_restart() is always safe to call and triggers a clean reboot.
-            unsafe { esp_idf_svc::sys::esp_restart() };
+    // Mark it connecting under the lock, so the page's first poll can't see
+    // an earlier attempt's outcome
+    let sent = {
+        let mut current = progress.lock().unwrap();
+        let ssid = credentials.ssid.clone();
+        let sent = attempts.try_send(Attempt { credentials, map }).is_ok();
+        if sent {
+            *current = AttemptProgress::Connecting { ssid };
         }
-        std::thread::sleep(std::time::Duration::from_millis(500));
+        sent
+    };
+    if sent {
+        http::html(req, page)
+    } else {
+        let error = "<p class=\"error\">Another network is being tried; wait a moment and \
+                     try again.</p>\n";
+        http::html(req, &form.replace("{ERROR}", error))
     }
+}
+
+/// The page a submitted form answers with. It polls `status_url` until the
+/// attempt connects, then shows the address and `done`, or fails, then shows
+/// why with a link back to `retry_url`.
+fn progress_page(status_url: &str, retry_url: &str, done: &str) -> String {
+    HTML_PROGRESS
+        .replace("{STATUS_URL}", status_url)
+        .replace("{RETRY_URL}", retry_url)
+        .replace("{DONE}", done)
+}
 
-    warn!("Captive portal timed out after {}s. Rebooting...", PORTAL_TIMEOUT_SECS);
-    // SAFETY: esp_restart() is always safe to call and triggers a clean reboot.
-    unsafe { esp_idf_svc::sys::esp_restart() };
+/// The setup AP and form, running alongside the map so the WiFi details can
+/// be changed without a reboot. Submitted networks are handed to the main
+/// loop's `WifiLink` to try; the session ends shortly after one connects, or
+/// after [`SETUP_SESSION_TIMEOUT`].
+pub struct SetupSession {
+    attempts: Receiver<Attempt>,
+    progress: Progress,
+    /// The SSID being tried, if any.
+    pending: Option<String>,
+    deadline: Instant,
+    _dns: DnsResponder,
 }
 
-/// Parse form-urlencoded POST body into (ssid, password).
-fn parse_form_data(body: &str) -> (String, String) {
-    let mut ssid = String::new();
-    let mut password = String::new();
+impl SetupSession {
+    /// Bring up the setup AP next to the station connection, secured with
+    /// `ap_password` if given, and publish the form at `/setup` on the main
+    /// web server. `saved` is listed on the form.
+    pub fn start(
+        wifi_mgr: &mut WifiManager,
+        web_state: &SharedState,
+        saved: &NetworkList,
+        ap_password: Option<&str>,
+    ) -> Result<Self, EspError> {
+        let scanned = wifi_mgr.scan();
+        let ap = setup_ap_configuration(ap_password);
+        let ssid = ap.ssid.clone();
+        let ip = wifi_mgr.start_setup_ap(ap)?;
+        info!("Setup AP {} up at {} alongside the map", ssid, ip);
+
+        let saved: Vec<String> = saved.ssids().map(str::to_string).collect();
+        let (attempt_tx, attempt_rx) = mpsc::sync_channel::<Attempt>(1);
+        let progress = Progress::default();
+        web_state.open_setup(SetupPage {
+            form: render_form(&saved, &scanned, None, "/setup/connect"),
+            progress_page: progress_page(
+                "/setup/status",
+                "/setup",
+                "The map switched to it and saved it; you can rejoin your usual WiFi.",
+            ),
+            attempts: attempt_tx,
+            progress: progress.clone(),
+        });
+        Ok(Self {
+            attempts: attempt_rx,
+            progress,
+            pending: None,
+            deadline: Instant::now() + SETUP_SESSION_TIMEOUT,
+            _dns: DnsResponder::start(ip),
+        })
+    }
+
+    /// A submitted network to try, unless one is already being tried. Report
+    /// how it went with [`finish_attempt`](Self::finish_attempt).
+    pub fn next_attempt(&mut self) -> Option<Credentials> {
+        if self.pending.is_some() {
+            return None;
+        }
+        let attempt = self.attempts.try_recv().ok()?;
+        self.pending = Some(attempt.credentials.ssid.clone());
+        Some(attempt.credentials)
+    }
+
+    /// Report the outcome of the network from `next_attempt`: the address it
+    /// got, or why it failed. Once one connects the session winds down,
+    /// leaving the progress page time to see it before the AP goes.
+    pub fn finish_attempt(&mut self, result: Result<Ipv4Addr, String>) {
+        let Some(ssid) = self.pending.take() else {
+            return;
+        };
+        match result {
+            Ok(ip) => {
+                set_progress(&self.progress, AttemptProgress::Connected { ssid, ip });
+                self.deadline = Instant::now() + WIND_DOWN;
+            }
+            Err(error) => set_progress(&self.progress, AttemptProgress::Failed { ssid, error }),
+        }
+    }
+
+    /// True once the session should be stopped.
+    pub fn is_over(&self, now: Instant) -> bool {
+        now >= self.deadline
+    }
 
-    for pair in body.split('&') {
-        if let Some((key, value)) = pair.split_once('=') {
-            let decoded = url_decode(value);
-            match key {
-                "ssid" => ssid = decoded,
-                "password" => password = decoded,
-                _ => {}
+    /// Take the setup AP and form down again.
+    pub fn stop(self, wifi_mgr: &mut WifiManager, web_state: &SharedState) {
+        web_state.close_setup();
+        if let Err(e) = wifi_mgr.stop_setup_ap() {
+            warn!("Failed to stop setup AP: {:?}", e);
+        }
+        info!("Setup AP stopped");
+    }
+}
+
+/// Join `network` on the station interface, keeping the AP up, and wait for
+/// an address. Always leaves the station idle again afterwards; if
+/// `smartconfig` delivered the network, not until the app has been told.
+/// `events` supplies the driver's reason when the attempt fails.
+fn test_connection(
+    wifi: &mut BlockingWifi<EspWifi<'static>>,
+    events: &WifiEvents,
+    network: &Credentials,
+    ap: &AccessPointConfiguration,
+    smartconfig: Option<&SmartConfig>,
+) -> Result<Ipv4Addr, String> {
+    info!("Testing connection to {}", network.ssid);
+    // Only this attempt's disconnects matter
+    events.drain().for_each(drop);
+    let result = wifi::client_configuration(network)
+        .and_then(|client| wifi.set_configuration(&Configuration::Mixed(client, ap.clone())))
+        .and_then(|()| wifi.connect())
+        .and_then(|()| wifi.wait_netif_up())
+        .and_then(|()| wifi.wifi().sta_netif().get_ip_info());
+    let outcome = match result {
+        Ok(ip_info) => {
+            info!("Test connection to {} succeeded, got {}", network.ssid, ip_info.ip);
+            if let Some(smartconfig) = smartconfig {
+                smartconfig.wait_for_ack();
             }
+            Ok(ip_info.ip)
+        }
+        Err(e) => {
+            warn!("Test connection to {} failed: {:?}", network.ssid, e);
+            let reason = events
+                .drain()
+                .filter_map(|event| match event {
+                    LinkEvent::Disconnected { reason } if reason != REASON_ASSOC_LEAVE => {
+                        Some(reason)
+                    }
+                    _ => None,
+                })
+                .last();
+            Err(reason.map_or_else(
+                || connect_error_message(&e),
+                |reason| failure_message(Some(reason)).to_string(),
+            ))
         }
+    };
+    let _ = wifi.disconnect();
+    let idle = Configuration::Mixed(ClientConfiguration::default(), ap.clone());
+    if let Err(e) = wifi.set_configuration(&idle) {
+        warn!("Failed to restore AP-only configuration: {:?}", e);
     }
+    outcome
+}
 
-    (ssid, password)
+/// What to tell the user about a failed test connection.
+fn connect_error_message(e: &EspError) -> String {
+    if e.code() == esp_idf_svc::sys::ESP_ERR_TIMEOUT as i32 {
+        "timed out (wrong password or out of range?)".to_string()
+    } else {
+        e.to_string()
+    }
 }
 
-/// Basic URL decoding (handles %XX and + for spaces).
-fn url_decode(s: &str) -> String {
-    let mut result = String::with_capacity(s.len());
-    let mut chars = s.chars();
+/// Answers every DNS lookup from connected clients with the AP's address, so
+/// phones detect the captive portal and open the setup page on their own.
+/// Stops when dropped.
+struct DnsResponder {
+    stop: Arc<AtomicBool>,
+}
 
-    while let Some(c) = chars.next() {
-        match c {
-            '+' => result.push(' '),
-            '%' => {
-                let hex: String = chars.by_ref().take(2).collect();
-                if let Ok(byte) = u8::from_str_radix(&hex, 16) {
-                    result.push(byte as char);
-                } else {
-                    result.push('%');
-                    result.push_str(&hex);
+impl DnsResponder {
+    /// Polling period for the stop flag.
+    const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+    fn start(ip: Ipv4Addr) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let spawned = std::thread::Builder::new()
+            .name("captive-dns".into())
+            .stack_size(4096)
+            .spawn(move || {
+                let socket = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 53)) {
+                    Ok(socket) => socket,
+                    Err(e) => {
+                        error!("Failed to bind DNS responder: {}", e);
+                        return;
+                    }
+                };
+                if let Err(e) = socket.set_read_timeout(Some(Self::READ_TIMEOUT)) {
+                    warn!("Failed to set DNS socket timeout: {}", e);
+                }
+                info!("Captive DNS responder answering with {}", ip);
+                let mut buf = [0u8; 512];
+                while !thread_stop.load(Ordering::Relaxed) {
+                    let (len, peer) = match socket.recv_from(&mut buf) {
+                        Ok(received) => received,
+                        Err(e) if matches!(
+                            e.kind(),
+                            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                        ) => continue,
+                        Err(e) => {
+                            warn!("DNS receive failed: {}", e);
+                            continue;
+                        }
+                    };
+                    if let Some(reply) = captive_dns::response(&buf[..len], ip) {
+                        if let Err(e) = socket.send_to(&reply, peer) {
+                            warn!("DNS reply to {} failed: {}", peer, e);
+                        }
+                    }
                 }
+            });
+        if let Err(e) = spawned {
+            error!("Failed to start DNS responder thread: {}", e);
+        }
+        Self { stop }
+    }
+}
+
+impl Drop for DnsResponder {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Scan for nearby networks to offer in the form. A failed scan leaves only
+/// manual entry.
+pub fn scan_networks(wifi: &mut BlockingWifi<EspWifi<'static>>) -> Vec<ScannedNetwork> {
+    match wifi.scan() {
+        Ok(found) => {
+            let scanned = networks::sort_scan_results(found.iter().map(scanned_network).collect());
+            info!("Scan found {} networks", scanned.len());
+            scanned
+        }
+        Err(e) => {
+            warn!("WiFi scan failed: {:?}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn scanned_network(ap: &AccessPointInfo) -> ScannedNetwork {
+    let auth = match ap.auth_method {
+        Some(AuthMethod::None) => WifiAuth::Open,
+        Some(AuthMethod::WPA3Personal) => WifiAuth::Wpa3Personal,
+        Some(AuthMethod::WPA2Enterprise | AuthMethod::WPA3Enterprise) => WifiAuth::Wpa2Enterprise,
+        // WPA2 and mixed WPA/WPA2/WPA3 networks all accept WPA2
+        _ => WifiAuth::Auto,
+    };
+    ScannedNetwork {
+        ssid: ap.ssid.to_string(),
+        rssi: ap.signal_strength,
+        auth,
+    }
+}
+
+/// Nearby networks as a drop-down that fills in the SSID and security fields.
+fn render_scan_results(scanned: &[ScannedNetwork]) -> String {
+    if scanned.is_empty() {
+        return String::new();
+    }
+    let mut html = String::from(
+        "<label for=\"scan\">Nearby networks</label>\n\
+         <select id=\"scan\" onchange=\"pick(this)\">\n\
+         <option value=\"\">Choose a network…</option>\n",
+    );
+    for network in scanned {
+        let ssid = html_escape(&network.ssid);
+        let bars: String = "▂▄▆█".chars().take(network.bars().max(1) as usize).collect();
+        let open = if network.auth == WifiAuth::Open { ", open" } else { "" };
+        html.push_str(&format!(
+            "<option value=\"{ssid}\" data-auth=\"{}\">{ssid} {bars} ({} dBm{open})</option>\n",
+            form_auth_value(network.auth),
+            network.rssi
+        ));
+    }
+    html.push_str("<option value=\"\">Other (type the name below)</option>\n</select>\n");
+    html
+}
+
+/// The Security option matching `auth`; see `parse_auth`.
+fn form_auth_value(auth: WifiAuth) -> &'static str {
+    match auth {
+        WifiAuth::Open => "open",
+        WifiAuth::Wpa3Personal => "wpa3_personal",
+        WifiAuth::Wpa2Enterprise => "wpa2_enterprise",
+        // Scans report WPA2 and mixed WPA2/WPA3 networks as automatic
+        WifiAuth::Auto | WifiAuth::Wpa2Personal => "auto",
+    }
+}
+
+/// Offer the scanned networks, list the networks already saved (submitting
+/// the form adds to them), and include the map settings starting from `map`
+/// if given.
+fn render_form(
+    saved: &[String],
+    scanned: &[ScannedNetwork],
+    map: Option<&SetupChoices>,
+    action: &str,
+) -> String {
+    let saved_html = if saved.is_empty() {
+        String::new()
+    } else {
+        let names: Vec<String> = saved.iter().map(|s| html_escape(s)).collect();
+        format!(
+            "<p class=\"saved\">Saved networks: {}. Adding one keeps the others.</p>\n",
+            names.join(", ")
+        )
+    };
+    HTML_FORM
+        .replace("{ACTION}", action)
+        .replace("{SAVED_NETWORKS}", &saved_html)
+        .replace("{SCAN_RESULTS}", &render_scan_results(scanned))
+        .replace("{MAP_SETTINGS}", &map.map(render_map_settings).unwrap_or_default())
+}
+
+/// The map fields, filling the preset drop-down from the built-in presets.
+fn render_map_settings(map: &SetupChoices) -> String {
+    let selected = match &map.airports {
+        AirportChoice::Preset(id) => id.as_str(),
+        AirportChoice::Keep | AirportChoice::Custom(_) => "",
+    };
+    let mut options = String::new();
+    for preset in presets::PRESETS {
+        let attr = if preset.id == selected { " selected" } else { "" };
+        options.push_str(&format!(
+            "<option value=\"{}\"{attr}>{}</option>\n",
+            preset.id, preset.name
+        ));
+    }
+    let checked = |on: bool| if on { " checked" } else { "" };
+    HTML_MAP_SETTINGS
+        .replace("{BRIGHTNESS}", &map.brightness_percent.to_string())
+        .replace("{PRESET_OPTIONS}", &options)
+        .replace("{LIGHTNING}", checked(map.do_lightning))
+        .replace("{WINDS}", checked(map.do_winds))
+        .replace("{FOG_RISK}", checked(map.do_fog_risk))
+}
+
+/// Replace `/config.toml` with the one built from the form. The NVS
+/// overrides are cleared so the new file applies as written.
+fn write_setup_config(nvs: EspDefaultNvsPartition, toml: &str) -> Result<(), String> {
+    flash_fs::write_config(toml).map_err(|e| format!("failed to save the map settings ({e})"))?;
+    match ConfigStore::new(nvs) {
+        Ok(mut store) => {
+            if let Err(e) = store.clear() {
+                warn!("Failed to clear stored config overrides: {:?}", e);
             }
-            _ => result.push(c),
         }
+        Err(e) => warn!("Failed to open config store: {:?}", e),
     }
+    info!("Saved map settings from the setup form");
+    Ok(())
+}
 
-    result
+/// Parse form-urlencoded POST body into credentials and, if the form had
+/// them, map settings. The login fields are only kept for WPA2-Enterprise;
+/// the hidden checkbox, like the map toggles, is only sent when ticked.
+fn parse_form_data(body: &str) -> Result<(Credentials, Option<SetupChoices>), String> {
+    let fields = http::form_fields(body);
+
+    let mut credentials = Credentials::default();
+    for (key, decoded) in &fields {
+        let decoded = decoded.clone();
+        match *key {
+            "ssid" => credentials.ssid = decoded,
+            "password" => credentials.password = decoded,
+            "auth" => credentials.auth = parse_auth(&decoded),
+            "username" if !decoded.is_empty() => credentials.username = Some(decoded),
+            "identity" if !decoded.is_empty() => credentials.identity = Some(decoded),
+            "hidden" => credentials.hidden = true,
+            _ => {}
+        }
+    }
+    if !credentials.auth.is_enterprise() {
+        credentials.username = None;
+        credentials.identity = None;
+    }
+
+    let map = if fields.iter().any(|(key, _)| *key == "map") {
+        Some(SetupChoices::from_form(
+            fields.iter().map(|(key, value)| (*key, value.as_str())),
+        )?)
+    } else {
+        None
+    };
+    Ok((credentials, map))
+}
+
+fn parse_auth(value: &str) -> WifiAuth {
+    match value {
+        "open" => WifiAuth::Open,
+        "wpa2_personal" => WifiAuth::Wpa2Personal,
+        "wpa3_personal" => WifiAuth::Wpa3Personal,
+        "wpa2_enterprise" => WifiAuth::Wpa2Enterprise,
+        _ => WifiAuth::Auto,
+    }
 }
@@ -0,0 +1,79 @@
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sys::EspError;
+use led_sectional_core::safe_mode::{CrashCount, CRASH_LIMIT};
+use log::{info, warn};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::storage::Storage;
+use crate::system;
+
+const NVS_NAMESPACE: &str = "boot";
+/// Crashed boots in a row, cleared once a boot proves stable.
+const NVS_KEY_CRASHES: &str = "crashes";
+
+/// The count as of this boot, for `GET /api/health` and
+/// [`mark_stable`].
+static CRASHES: AtomicU8 = AtomicU8::new(0);
+
+/// Count this boot if the last run crashed and decide whether to start in
+/// safe mode. Call once at boot, after NVS is taken.
+pub fn check(nvs: EspDefaultNvsPartition) -> bool {
+    let count = match update_count(nvs) {
+        Ok(count) => count,
+        Err(e) => {
+            warn!("Failed to read the boot count: {:?}", e);
+            return false;
+        }
+    };
+    CRASHES.store(count.0, Ordering::Relaxed);
+    if count.is_safe_mode() {
+        warn!(
+            "Crashed on {} boots in a row; starting in safe mode. Fix or factory reset the \
+             config, then restart",
+            count.0
+        );
+    } else if count.0 > 0 {
+        info!("Crashed on {} of the last boots; safe mode after {}", count.0, CRASH_LIMIT);
+    }
+    count.is_safe_mode()
+}
+
+/// Whether this boot started in safe mode.
+pub fn is_active() -> bool {
+    CrashCount(CRASHES.load(Ordering::Relaxed)).is_safe_mode()
+}
+
+/// This boot has run long enough or fetched weather, so the config can be
+/// trusted; forget earlier crashes. Does nothing in safe mode, which lasts until a restart.
+pub fn mark_stable(nvs: EspDefaultNvsPartition) {
+    let crashes = CRASHES.load(Ordering::Relaxed);
+    if crashes == 0 || CrashCount(crashes).is_safe_mode() {
+        return;
+    }
+    let result = Storage::open(nvs, NVS_NAMESPACE).and_then(|mut storage| {
+        storage.remove(NVS_KEY_CRASHES)?;
+        Ok(())
+    });
+    match result {
+        Ok(()) => {
+            CRASHES.store(0, Ordering::Relaxed);
+            info!("Up for {}s; forgetting the earlier crashes", system::uptime_secs());
+        }
+        Err(e) => warn!("Failed to clear the boot count: {:?}", e),
+    }
+}
+
+/// Forget the boot count, for a factory reset.
+pub fn clear(nvs: EspDefaultNvsPartition) -> Result<(), EspError> {
+    let mut storage = Storage::open(nvs, NVS_NAMESPACE)?;
+    storage.remove(NVS_KEY_CRASHES)?;
+    Ok(())
+}
+
+fn update_count(nvs: EspDefaultNvsPartition) -> Result<CrashCount, EspError> {
+    let mut storage = Storage::open(nvs, NVS_NAMESPACE)?;
+    let recorded = CrashCount(storage.get_u8(NVS_KEY_CRASHES)?.unwrap_or(0));
+    let count = recorded.after_reset(system::reset_reason());
+    storage.set_u8(NVS_KEY_CRASHES, count.0)?;
+    Ok(count)
+}
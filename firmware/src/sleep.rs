@@ -0,0 +1,50 @@
+use esp_idf_svc::sys;
+use led_sectional_core::deep_sleep::{self, IMAGE_LEN};
+use led_sectional_core::led::LedState;
+use log::{info, warn};
+use std::time::Duration;
+
+/// The LED state saved before sleeping. RTC slow memory keeps it through
+/// deep sleep; power-on zeroes it.
+#[link_section = ".rtc.data"]
+static mut WAKE_IMAGE: [u8; IMAGE_LEN] = [0; IMAGE_LEN];
+
+/// Whether this boot is the end of a deep sleep.
+pub fn woke_from_sleep() -> bool {
+    // SAFETY: reads what the ROM recorded about this boot; no preconditions.
+    let cause = unsafe { sys::esp_sleep_get_wakeup_cause() };
+    cause == sys::esp_sleep_source_t_ESP_SLEEP_WAKEUP_TIMER
+}
+
+/// After a deep sleep, release the LED data pin held through it and put
+/// back the LED state saved before. False after power-on or a reset, or if
+/// the saved state doesn't fit the config.
+pub fn restore(led_state: &mut LedState, data_pin: u8) -> bool {
+    if !woke_from_sleep() {
+        return false;
+    }
+    // SAFETY: the pin number comes from the validated config.
+    unsafe { sys::gpio_hold_dis(data_pin as sys::gpio_num_t) };
+    // SAFETY: only the main task touches WAKE_IMAGE, before sleeping and at
+    // boot.
+    let image = unsafe { &*std::ptr::addr_of!(WAKE_IMAGE) };
+    deep_sleep::restore(image, led_state)
+}
+
+/// Save `led_state` and deep-sleep for `duration`, holding the LED data pin
+/// low so the strip keeps its colors. The chip boots again afterwards.
+pub fn sleep(led_state: &LedState, data_pin: u8, duration: Duration) -> ! {
+    // SAFETY: as in `restore`.
+    let image = unsafe { &mut *std::ptr::addr_of_mut!(WAKE_IMAGE) };
+    if !deep_sleep::save(led_state, image) {
+        warn!("Too many LEDs to keep through sleep; they're repainted after waking");
+    }
+    info!("Deep sleeping for {}s", duration.as_secs());
+    // SAFETY: holding a configured output pin and sleeping have no other
+    // preconditions; esp_deep_sleep doesn't return.
+    unsafe {
+        sys::gpio_hold_en(data_pin as sys::gpio_num_t);
+        sys::gpio_deep_sleep_hold_en();
+        sys::esp_deep_sleep(duration.as_micros() as u64)
+    }
+}
@@ -0,0 +1,150 @@
+use esp_idf_svc::sys::{self, esp, EspError};
+use led_sectional_core::networks::Credentials;
+use log::{info, warn};
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::SyncSender;
+use std::time::{Duration, Instant};
+
+use crate::provisioning::Attempt;
+
+/// How long to stay connected so the phone app hears that it worked.
+const ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// State the SmartConfig event handler reaches through its `arg` pointer.
+struct Shared {
+    attempts: SyncSender<Attempt>,
+    /// Credentials came in and haven't been re-armed since.
+    received: AtomicBool,
+    ack_sent: AtomicBool,
+}
+
+/// Listens for ESP-Touch broadcasts from Espressif's phone app while the
+/// captive portal runs. Received credentials go down the same `attempts`
+/// channel as the form's, so they're tested and saved the same way. Stops
+/// when dropped.
+pub struct SmartConfig {
+    shared: Box<Shared>,
+    handler: sys::esp_event_handler_instance_t,
+}
+
+impl SmartConfig {
+    /// Start listening. WiFi must already be started in station or AP+STA
+    /// mode.
+    pub fn start(attempts: SyncSender<Attempt>) -> Result<Self, EspError> {
+        let shared = Box::new(Shared {
+            attempts,
+            received: AtomicBool::new(false),
+            ack_sent: AtomicBool::new(false),
+        });
+        let mut handler: sys::esp_event_handler_instance_t = std::ptr::null_mut();
+        // SAFETY: `shared` is boxed so its address is stable, and it outlives
+        // the registration, which Drop removes before freeing it.
+        esp!(unsafe {
+            sys::esp_event_handler_instance_register(
+                sys::SC_EVENT,
+                sys::ESP_EVENT_ANY_ID,
+                Some(on_event),
+                &*shared as *const Shared as *mut c_void,
+                &mut handler,
+            )
+        })?;
+        let smartconfig = Self { shared, handler };
+        smartconfig.listen()?;
+        info!("Listening for SmartConfig (ESP-Touch) credentials");
+        Ok(smartconfig)
+    }
+
+    /// Call after a connection test succeeds: if the credentials came from
+    /// the app, wait for it to be told, so it can report success.
+    pub fn wait_for_ack(&self) {
+        if !self.shared.received.load(Ordering::Relaxed) {
+            return;
+        }
+        let deadline = Instant::now() + ACK_TIMEOUT;
+        while !self.shared.ack_sent.load(Ordering::Relaxed) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Call after a connection test fails: if the credentials came from the
+    /// app, start listening again so it can retry.
+    pub fn rearm(&self) {
+        if !self.shared.received.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        // SAFETY: stopping is allowed at any point after a start.
+        unsafe { sys::esp_smartconfig_stop() };
+        if let Err(e) = self.listen() {
+            warn!("Failed to restart SmartConfig: {:?}", e);
+        }
+    }
+
+    fn listen(&self) -> Result<(), EspError> {
+        self.shared.ack_sent.store(false, Ordering::Relaxed);
+        // SAFETY: selecting the protocol has no preconditions while stopped.
+        esp!(unsafe { sys::esp_smartconfig_set_type(sys::smartconfig_type_t_SC_TYPE_ESPTOUCH) })?;
+        let config = sys::smartconfig_start_config_t {
+            enable_log: false,
+            esp_touch_v2_enable_crypt: false,
+            esp_touch_v2_key: std::ptr::null_mut(),
+        };
+        // SAFETY: `config` is fully initialized and copied by the driver;
+        // the null key is unused since ESP-Touch v2 encryption is off.
+        esp!(unsafe { sys::esp_smartconfig_start(&config) })
+    }
+}
+
+impl Drop for SmartConfig {
+    fn drop(&mut self) {
+        // SAFETY: unregistering the instance registered in `start` stops
+        // further calls into `on_event` before `shared` is freed.
+        unsafe {
+            sys::esp_smartconfig_stop();
+            sys::esp_event_handler_instance_unregister(
+                sys::SC_EVENT,
+                sys::ESP_EVENT_ANY_ID,
+                self.handler,
+            );
+        }
+    }
+}
+
+/// Runs on the event loop task.
+unsafe extern "C" fn on_event(
+    arg: *mut c_void,
+    _base: sys::esp_event_base_t,
+    id: i32,
+    data: *mut c_void,
+) {
+    // SAFETY: `arg` is the boxed `Shared` registered with this handler,
+    // which stays alive until the handler is unregistered.
+    let shared = unsafe { &*(arg as *const Shared) };
+    match id as u32 {
+        sys::smartconfig_event_t_SC_EVENT_GOT_SSID_PSWD => {
+            // SAFETY: this event's data is a smartconfig_event_got_ssid_pswd_t
+            // owned by the event loop for the duration of the call.
+            let got = unsafe { &*(data as *const sys::smartconfig_event_got_ssid_pswd_t) };
+            let Some(credentials) = Credentials::from_raw(&got.ssid, &got.password) else {
+                warn!("SmartConfig delivered an unusable SSID or password");
+                return;
+            };
+            info!("SmartConfig received credentials for SSID: {}", credentials.ssid);
+            shared.received.store(true, Ordering::Relaxed);
+            // The portal loop logs the outcome and shows it on the progress page
+            let attempt = Attempt {
+                credentials,
+                map: None,
+            };
+            // Left marked as received, so SmartConfig is re-armed if the
+            // network being tried doesn't work out
+            if shared.attempts.try_send(attempt).is_err() {
+                warn!("Ignoring SmartConfig credentials while another network is tried");
+            }
+        }
+        sys::smartconfig_event_t_SC_EVENT_SEND_ACK_DONE => {
+            shared.ack_sent.store(true, Ordering::Relaxed);
+        }
+        _ => {}
+    }
+}
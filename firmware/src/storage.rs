@@ -0,0 +1,124 @@
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_svc::sys::EspError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// One NVS namespace, with typed getters and setters.
+///
+/// Setters compare with what is stored first and skip the write when it is
+/// the same, returning whether they wrote, so code that saves on every
+/// change doesn't wear the flash for nothing.
+pub struct Storage {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl Storage {
+    /// Open `namespace` for reading and writing, creating it if needed.
+    pub fn open(partition: EspDefaultNvsPartition, namespace: &str) -> Result<Self, EspError> {
+        Ok(Self {
+            nvs: EspNvs::new(partition, namespace, true)?,
+        })
+    }
+
+    pub fn get_u8(&self, key: &str) -> Result<Option<u8>, EspError> {
+        self.nvs.get_u8(key)
+    }
+
+    pub fn set_u8(&mut self, key: &str, value: u8) -> Result<bool, EspError> {
+        if self.nvs.get_u8(key)? == Some(value) {
+            return Ok(false);
+        }
+        self.nvs.set_u8(key, value)?;
+        Ok(true)
+    }
+
+    pub fn get_u32(&self, key: &str) -> Result<Option<u32>, EspError> {
+        self.nvs.get_u32(key)
+    }
+
+    pub fn set_u32(&mut self, key: &str, value: u32) -> Result<bool, EspError> {
+        if self.nvs.get_u32(key)? == Some(value) {
+            return Ok(false);
+        }
+        self.nvs.set_u32(key, value)?;
+        Ok(true)
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<Option<String>, EspError> {
+        let Some(len) = self.nvs.str_len(key)? else {
+            return Ok(None);
+        };
+        // The length includes the terminating NUL
+        let mut buf = vec![0u8; len.max(1)];
+        Ok(self.nvs.get_str(key, &mut buf)?.map(str::to_string))
+    }
+
+    pub fn set_str(&mut self, key: &str, value: &str) -> Result<bool, EspError> {
+        if self.get_str(key)?.as_deref() == Some(value) {
+            return Ok(false);
+        }
+        self.nvs.set_str(key, value)?;
+        Ok(true)
+    }
+
+    pub fn get_blob(&self, key: &str) -> Result<Option<Vec<u8>>, EspError> {
+        let Some(len) = self.nvs.blob_len(key)? else {
+            return Ok(None);
+        };
+        let mut buf = vec![0u8; len];
+        Ok(self.nvs.get_blob(key, &mut buf)?.map(<[u8]>::to_vec))
+    }
+
+    pub fn set_blob(&mut self, key: &str, value: &[u8]) -> Result<bool, EspError> {
+        if self.get_blob(key)?.as_deref() == Some(value) {
+            return Ok(false);
+        }
+        self.nvs.set_blob(key, value)?;
+        Ok(true)
+    }
+
+    /// A value stored with [`set`](Self::set).
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, StorageError> {
+        let Some(bytes) = self.get_blob(key)? else {
+            return Ok(None);
+        };
+        postcard::from_bytes(&bytes).map(Some).map_err(StorageError::Decode)
+    }
+
+    /// Store `value` as a postcard-encoded blob.
+    pub fn set<T: Serialize>(&mut self, key: &str, value: &T) -> Result<bool, StorageError> {
+        let bytes = postcard::to_allocvec(value).map_err(StorageError::Encode)?;
+        Ok(self.set_blob(key, &bytes)?)
+    }
+
+    /// Remove `key`; false if it wasn't stored.
+    pub fn remove(&mut self, key: &str) -> Result<bool, EspError> {
+        self.nvs.remove(key)
+    }
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    Nvs(EspError),
+    Encode(postcard::Error),
+    /// Stored by other firmware, or damaged.
+    Decode(postcard::Error),
+}
+
+impl From<EspError> for StorageError {
+    fn from(e: EspError) -> Self {
+        Self::Nvs(e)
+    }
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nvs(e) => write!(f, "NVS error: {e:?}"),
+            Self::Encode(e) => write!(f, "can't encode value: {e}"),
+            Self::Decode(e) => write!(f, "stored value is unreadable: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
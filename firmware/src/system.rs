@@ -0,0 +1,60 @@
+use esp_idf_svc::sys;
+use led_sectional_core::build_info::BuildInfo;
+
+/// The firmware version from `Cargo.toml`.
+pub const FIRMWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Commit the firmware was built from, set by `build.rs`; empty outside a
+/// git checkout.
+pub const GIT_HASH: &str = env!("BUILD_GIT_HASH");
+/// Unix seconds of the build, set by `build.rs`.
+pub const BUILD_TIME: &str = env!("BUILD_UNIX_TIME");
+
+/// Version, commit and build time together.
+pub fn build_info() -> BuildInfo {
+    BuildInfo::new(FIRMWARE_VERSION, GIT_HASH, BUILD_TIME)
+}
+
+/// Heap bytes free right now.
+pub fn free_heap() -> u32 {
+    // SAFETY: reads an allocator counter; no preconditions.
+    unsafe { sys::esp_get_free_heap_size() }
+}
+
+/// The least the free heap has been since boot.
+pub fn min_free_heap() -> u32 {
+    // SAFETY: reads an allocator counter; no preconditions.
+    unsafe { sys::esp_get_minimum_free_heap_size() }
+}
+
+/// The biggest single allocation the heap could satisfy right now; much
+/// less than [`free_heap`] means it's fragmented.
+pub fn largest_free_block() -> u32 {
+    // SAFETY: reads allocator state; no preconditions.
+    unsafe { sys::heap_caps_get_largest_free_block(sys::MALLOC_CAP_8BIT) as u32 }
+}
+
+/// Seconds since boot.
+pub fn uptime_secs() -> u64 {
+    // SAFETY: reads the high-resolution timer; no preconditions.
+    (unsafe { sys::esp_timer_get_time() } / 1_000_000) as u64
+}
+
+/// Why the chip last reset, as a short snake_case name.
+pub fn reset_reason() -> &'static str {
+    // SAFETY: reads a value the startup code recorded; no preconditions.
+    match unsafe { sys::esp_reset_reason() } {
+        sys::esp_reset_reason_t_ESP_RST_POWERON => "power_on",
+        sys::esp_reset_reason_t_ESP_RST_EXT => "external_pin",
+        sys::esp_reset_reason_t_ESP_RST_SW => "software",
+        sys::esp_reset_reason_t_ESP_RST_PANIC => "panic",
+        sys::esp_reset_reason_t_ESP_RST_INT_WDT => "interrupt_watchdog",
+        sys::esp_reset_reason_t_ESP_RST_TASK_WDT => "task_watchdog",
+        sys::esp_reset_reason_t_ESP_RST_WDT => "watchdog",
+        sys::esp_reset_reason_t_ESP_RST_DEEPSLEEP => "deep_sleep",
+        sys::esp_reset_reason_t_ESP_RST_BROWNOUT => "brownout",
+        sys::esp_reset_reason_t_ESP_RST_SDIO => "sdio",
+        sys::esp_reset_reason_t_ESP_RST_USB => "usb",
+        sys::esp_reset_reason_t_ESP_RST_JTAG => "jtag",
+        _ => "unknown",
+    }
+}
@@ -0,0 +1,68 @@
+use esp_idf_svc::hal::gpio::{AnyIOPin, PinDriver, Pull};
+use led_sectional_core::config::UserButtonSettings;
+use led_sectional_core::user_button::{ButtonAction, Press, PressDetector};
+use log::{debug, error, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::watchdog::{self, Watch};
+
+/// Short enough to debounce and time presses with.
+const POLL: Duration = Duration::from_millis(10);
+
+/// Presses not yet handled by the main loop.
+static PRESSES: Mutex<Vec<Press>> = Mutex::new(Vec::new());
+/// Whether a double press does anything, so short presses know whether to
+/// wait for one; kept current by [`configure`].
+static DOUBLE_PRESS: AtomicBool = AtomicBool::new(true);
+
+/// Watch the active-low user button on GPIO `pin` from a background thread.
+/// Presses are queued for [`take_presses`]; the main loop decides what they
+/// do, so changes to the actions apply without a restart.
+pub fn watch(pin: u8) {
+    let spawned = std::thread::Builder::new()
+        .name("user_button".into())
+        .stack_size(3072)
+        .spawn(move || {
+            // SAFETY: config validation keeps this pin off data_pin and
+            // button_pin, and no other driver claims it.
+            let pin = unsafe { AnyIOPin::new(pin as i32) };
+            let mut button = match PinDriver::input(pin) {
+                Ok(button) => button,
+                Err(e) => {
+                    error!("Failed to configure user button: {:?}", e);
+                    return;
+                }
+            };
+            if let Err(e) = button.set_pull(Pull::Up) {
+                warn!("Failed to enable user button pull-up: {:?}", e);
+            }
+
+            let _watch = Watch::current_task_or_log("user_button");
+            let mut detector = PressDetector::new();
+            loop {
+                watchdog::feed();
+                let double_press = DOUBLE_PRESS.load(Ordering::Relaxed);
+                if let Some(press) = detector.sample(button.is_low(), POLL, double_press) {
+                    debug!("User button: {:?} press", press);
+                    PRESSES.lock().unwrap().push(press);
+                }
+                std::thread::sleep(POLL);
+            }
+        });
+    if let Err(e) = spawned {
+        error!("Failed to start user button thread: {}", e);
+    }
+}
+
+/// Follow `settings` for whether to wait for double presses. Call at boot
+/// and when the config changes.
+pub fn configure(settings: &UserButtonSettings) {
+    DOUBLE_PRESS.store(settings.double_press != ButtonAction::None, Ordering::Relaxed);
+}
+
+/// Presses since the last call, oldest first.
+pub fn take_presses() -> Vec<Press> {
+    std::mem::take(&mut *PRESSES.lock().unwrap())
+}
@@ -0,0 +1,68 @@
+use esp_idf_svc::sys::{self, esp, EspError};
+use log::{info, warn};
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// Longest a watched task may go without feeding the watchdog: well past a
+/// weather fetch and its one retry at 15 s read timeouts each, short enough
+/// that a hang doesn't leave the map frozen for long.
+pub const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Set the task watchdog to [`TIMEOUT`], panicking when it fires so the
+/// panic handler logs the stuck tasks and the chip resets with
+/// `task_watchdog` as the reason. ESP-IDF already runs it from boot,
+/// watching the idle task.
+pub fn configure() {
+    let config = sys::esp_task_wdt_config_t {
+        timeout_ms: TIMEOUT.as_millis() as u32,
+        // Keep watching the idle task, which starves if a task spins
+        idle_core_mask: 1,
+        trigger_panic: true,
+    };
+    // SAFETY: both calls copy the config; it only has to live through them.
+    let result = esp!(unsafe { sys::esp_task_wdt_reconfigure(&config) })
+        .or_else(|_| esp!(unsafe { sys::esp_task_wdt_init(&config) }));
+    match result {
+        Ok(()) => info!("Task watchdog: {}s", TIMEOUT.as_secs()),
+        Err(e) => warn!("Failed to configure the task watchdog: {:?}", e),
+    }
+}
+
+/// The calling task's subscription to the task watchdog, which resets the
+/// chip unless the task calls [`feed`] at least every [`TIMEOUT`].
+/// Dropping it unsubscribes; it can't leave the task that made it.
+pub struct Watch {
+    _task: PhantomData<*const ()>,
+}
+
+impl Watch {
+    pub fn current_task() -> Result<Self, EspError> {
+        // SAFETY: a null handle subscribes the calling task.
+        esp!(unsafe { sys::esp_task_wdt_add(std::ptr::null_mut()) })?;
+        Ok(Self { _task: PhantomData })
+    }
+
+    /// Like [`Watch::current_task`], logging instead of failing; the task
+    /// runs unwatched if it can't subscribe.
+    pub fn current_task_or_log(name: &str) -> Option<Self> {
+        Self::current_task()
+            .inspect_err(|e| warn!("Failed to watch the {} task: {:?}", name, e))
+            .ok()
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        // SAFETY: a null handle unsubscribes the calling task, which is
+        // the one that subscribed since Watch isn't Send.
+        unsafe { sys::esp_task_wdt_delete(std::ptr::null_mut()) };
+    }
+}
+
+/// Tell the watchdog the calling task is alive. Long operations call this
+/// between steps, such as each chunk of a firmware download; from a task
+/// that isn't watched it does nothing.
+pub fn feed() {
+    // SAFETY: no preconditions; an unsubscribed task just gets an error.
+    unsafe { sys::esp_task_wdt_reset() };
+}
@@ -0,0 +1,787 @@
+use esp_idf_svc::http::server::{EspHttpConnection, EspHttpServer, Request};
+use esp_idf_svc::http::{Headers, Method};
+use esp_idf_svc::io::{EspIOError, Write};
+use esp_idf_svc::sys::EspError;
+use led_sectional_core::airport_editor;
+use led_sectional_core::api::{DeviceHealth, Payload, StatusResponse};
+use led_sectional_core::auth::{self, Credential};
+use led_sectional_core::config::Config;
+use led_sectional_core::cors::CorsPolicy;
+use led_sectional_core::dashboard::MapSnapshot;
+use led_sectional_core::events::{Event, Subscriber};
+use led_sectional_core::log_filter::LogFilter;
+use led_sectional_core::ota::update_page;
+use led_sectional_core::poller::MIN_FETCH_SPACING;
+use led_sectional_core::settings_editor::{self, LiveSettings, SettingsPatch};
+use led_sectional_core::test_pattern::TestRequest;
+use led_sectional_core::wifi_link::LinkState;
+use log::{info, warn};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::SyncSender;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::clock;
+use crate::crash;
+use crate::flash_fs;
+use crate::http::{self, read_body, respond, unauthorized};
+use crate::logging;
+use crate::ota;
+use crate::provisioning::{self, Attempt, Progress};
+use crate::safe_mode;
+use crate::system;
+
+/// Largest config accepted by `POST /config`.
+const MAX_CONFIG_SIZE: usize = 16 * 1024;
+/// Largest airport list accepted by `POST /airports`.
+const MAX_AIRPORTS_SIZE: usize = 4 * 1024;
+/// Largest credential accepted by `POST /api/auth`.
+const MAX_CREDENTIAL_SIZE: usize = 256;
+/// Largest settings change accepted by `POST /api/settings`.
+const MAX_SETTINGS_SIZE: usize = 512;
+/// Largest log filter accepted by `POST /api/log-level`.
+const MAX_LOG_FILTER_SIZE: usize = 256;
+/// Largest profile name accepted by `POST /api/profile`.
+const MAX_PROFILE_SIZE: usize = 64;
+/// Largest test pattern request accepted by `POST /api/test`.
+const MAX_TEST_SIZE: usize = 64;
+/// Handlers parse TOML, which needs more than the default 6 KB stack.
+const SERVER_STACK_SIZE: usize = 10 * 1024;
+
+/// Data the main loop publishes for the HTTP handlers.
+#[derive(Default)]
+pub struct SharedState {
+    config_toml: Mutex<String>,
+    /// LEDs in the running config, for checking `POST /api/test`.
+    num_leds: AtomicUsize,
+    airport_editor: Mutex<String>,
+    airport_edit: Mutex<Option<Vec<String>>>,
+    settings_page: Mutex<String>,
+    live_settings: Mutex<Option<LiveSettings>>,
+    settings_edit: Mutex<Option<SettingsPatch>>,
+    /// Names of the `[[profiles]]` entries, to check `POST /api/profile`.
+    profiles: Mutex<Vec<String>>,
+    /// The profile picked over `POST /api/profile`; None inside for plain
+    /// `[settings]`.
+    profile_change: Mutex<Option<Option<String>>>,
+    factory_reset: AtomicBool,
+    /// An upload to `POST /update` is being written.
+    updating: AtomicBool,
+    update_installed: AtomicBool,
+    update_check: AtomicBool,
+    refresh: AtomicBool,
+    test_request: Mutex<Option<TestRequest>>,
+    /// When `POST /api/refresh` was last accepted, for rate limiting.
+    last_refresh: Mutex<Option<Instant>>,
+    /// Required by the endpoints that change the device; None leaves them
+    /// open.
+    credential: Mutex<Option<Credential>>,
+    /// The stored credential couldn't be read, so nothing is authorized.
+    locked: AtomicBool,
+    credential_change: Mutex<Option<Option<Credential>>>,
+    setup_requested: AtomicBool,
+    setup: Mutex<Option<SetupPage>>,
+    wifi: Mutex<WifiStatus>,
+    map: Mutex<MapSnapshot>,
+    /// When weather was last fetched successfully, for `GET /api/health`.
+    last_fetch: Mutex<Option<Instant>>,
+    low_memory: AtomicBool,
+}
+
+/// WiFi connection state and signal readings for `GET /status`.
+#[derive(Debug, Clone, Default)]
+struct WifiStatus {
+    state: &'static str,
+    ssid: Option<String>,
+    rssi: Option<i8>,
+    weak: bool,
+}
+
+/// The WiFi form served at `/setup` while the setup AP is up.
+#[derive(Clone)]
+pub struct SetupPage {
+    /// Rendered form, with its `{ERROR}` placeholder.
+    pub form: String,
+    /// Served after a submission; polls `/setup/status`.
+    pub progress_page: String,
+    pub attempts: SyncSender<Attempt>,
+    pub progress: Progress,
+}
+
+impl SharedState {
+    /// Snapshot the effective config for `GET /config`, the airport editor,
+    /// and the settings page, and apply its CORS origins. The WiFi password,
+    /// BLE provisioning code, setup AP password, and MQTT password are left
+    /// out so backups don't expose them.
+    pub fn publish_config(&self, config: &Config) {
+        *self.airport_editor.lock().unwrap() = airport_editor::editor_page(config);
+        self.num_leds.store(config.num_leds(), Ordering::Relaxed);
+        *self.settings_page.lock().unwrap() = settings_editor::settings_page(config);
+        *self.live_settings.lock().unwrap() = Some(LiveSettings::from_config(config));
+        *self.profiles.lock().unwrap() = config.profiles.iter().map(|p| p.name.clone()).collect();
+        http::set_cors(CorsPolicy::new(&config.settings.http.cors_origins));
+        let mut export = config.clone();
+        export.wifi.password = None;
+        export.settings.provisioning.pop = None;
+        export.settings.provisioning.ap_password = None;
+        export.settings.mqtt.password = None;
+        match export.to_toml() {
+            Ok(toml) => *self.config_toml.lock().unwrap() = toml,
+            Err(e) => warn!("Failed to serialize config for export: {}", e),
+        }
+    }
+
+    /// The airport list submitted to `POST /airports`, once; the main loop
+    /// applies and stores it.
+    pub fn take_airport_edit(&self) -> Option<Vec<String>> {
+        self.airport_edit.lock().unwrap().take()
+    }
+
+    /// The change submitted to `POST /api/settings`, once; the main loop
+    /// applies and stores it.
+    pub fn take_settings_edit(&self) -> Option<SettingsPatch> {
+        self.settings_edit.lock().unwrap().take()
+    }
+
+    /// The profile picked over `POST /api/profile`, once; the main loop
+    /// applies and stores it.
+    pub fn take_profile_change(&self) -> Option<Option<String>> {
+        self.profile_change.lock().unwrap().take()
+    }
+
+    /// True once after `POST /factory-reset`; the main loop performs the reset.
+    pub fn take_factory_reset_request(&self) -> bool {
+        self.factory_reset.swap(false, Ordering::Relaxed)
+    }
+
+    /// True once after `POST /update` installed new firmware; the main loop
+    /// reboots into it.
+    pub fn take_update_installed(&self) -> bool {
+        self.update_installed.swap(false, Ordering::Relaxed)
+    }
+
+    /// True once after `POST /api/update/check`; the main loop checks
+    /// `settings.ota.manifest_url`.
+    pub fn take_update_check(&self) -> bool {
+        self.update_check.swap(false, Ordering::Relaxed)
+    }
+
+    /// True once after `POST /api/refresh`; the main loop fetches weather now
+    /// instead of waiting out the interval.
+    pub fn take_refresh_request(&self) -> bool {
+        self.refresh.swap(false, Ordering::Relaxed)
+    }
+
+    /// The pattern asked for by `POST /api/test`, once; the main loop shows
+    /// it in place of the weather until it times out.
+    pub fn take_test_request(&self) -> Option<TestRequest> {
+        self.test_request.lock().unwrap().take()
+    }
+
+    /// Accept a refresh request unless weather was fetched, or a refresh
+    /// requested, within [`MIN_FETCH_SPACING`]; otherwise return how long
+    /// to wait.
+    pub fn request_refresh(&self, now: Instant) -> Result<(), Duration> {
+        let mut last_refresh = self.last_refresh.lock().unwrap();
+        let last = (*last_refresh).max(*self.last_fetch.lock().unwrap());
+        if let Some(wait) = last.map(|t| (t + MIN_FETCH_SPACING).saturating_duration_since(now)) {
+            if !wait.is_zero() {
+                return Err(wait);
+            }
+        }
+        *last_refresh = Some(now);
+        self.refresh.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Protect the mutating endpoints with `credential`, or open them with
+    /// None.
+    pub fn set_credential(&self, credential: Option<Credential>) {
+        *self.credential.lock().unwrap() = credential;
+    }
+
+    /// Refuse every mutating request until a factory reset clears the
+    /// unreadable stored credential.
+    pub fn lock_credential(&self) {
+        self.locked.store(true, Ordering::Relaxed);
+    }
+
+    /// The credential set or removed over `/api/auth`, once; the main loop
+    /// stores it.
+    pub fn take_credential_change(&self) -> Option<Option<Credential>> {
+        self.credential_change.lock().unwrap().take()
+    }
+
+    /// Whether `req` may change the device: no credential is set, or its
+    /// `Authorization` header matches. Never while locked.
+    fn authorized(&self, req: &Request<&mut EspHttpConnection>) -> bool {
+        if self.locked.load(Ordering::Relaxed) {
+            return false;
+        }
+        match self.credential.lock().unwrap().as_ref() {
+            Some(credential) => credential.authorizes(req.header("Authorization")),
+            None => true,
+        }
+    }
+
+    /// True once after `POST /setup`; the main loop brings up the setup AP.
+    pub fn take_setup_request(&self) -> bool {
+        self.setup_requested.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn open_setup(&self, page: SetupPage) {
+        *self.setup.lock().unwrap() = Some(page);
+    }
+
+    pub fn close_setup(&self) {
+        *self.setup.lock().unwrap() = None;
+    }
+
+    fn setup_page(&self) -> Option<SetupPage> {
+        self.setup.lock().unwrap().clone()
+    }
+
+    /// Record what the map shows after a fetch, for `GET /`, `GET /api/map`,
+    /// and `GET /api/metars`.
+    pub fn publish_map(&self, snapshot: MapSnapshot) {
+        *self.map.lock().unwrap() = snapshot;
+    }
+
+    /// Record a successful weather fetch (new data or not modified) for
+    /// `GET /api/health`.
+    pub fn publish_fetch(&self, at: Instant) {
+        *self.last_fetch.lock().unwrap() = Some(at);
+    }
+
+    /// Record whether the main loop is saving memory, for `GET /api/health`.
+    pub fn publish_low_memory(&self, low: bool) {
+        self.low_memory.store(low, Ordering::Relaxed);
+    }
+
+    /// Record the latest smoothed signal strength for `GET /status`.
+    pub fn publish_signal(&self, rssi: Option<i8>, weak: bool) {
+        let mut wifi = self.wifi.lock().unwrap();
+        wifi.rssi = rssi;
+        wifi.weak = weak;
+    }
+
+    /// Record the WiFi connection state for `GET /status`.
+    pub fn publish_link(&self, state: &LinkState) {
+        let mut wifi = self.wifi.lock().unwrap();
+        wifi.state = state.name();
+        wifi.ssid = match state {
+            LinkState::Connecting { ssid, .. } | LinkState::Online { ssid, .. } => {
+                Some(ssid.clone())
+            }
+            LinkState::Waiting { .. } => None,
+        };
+    }
+
+    /// What `GET /api/health` reports; also published over MQTT.
+    pub fn health(&self) -> DeviceHealth {
+        let since_fetch = self.last_fetch.lock().unwrap().map(|t| t.elapsed().as_secs());
+        let build = system::build_info();
+        DeviceHealth {
+            version: build.version,
+            git_hash: build.git_hash,
+            built_at: build.built_at,
+            free_heap: system::free_heap(),
+            min_free_heap: system::min_free_heap(),
+            largest_free_block: system::largest_free_block(),
+            low_memory: self.low_memory.load(Ordering::Relaxed),
+            rssi: self.wifi.lock().unwrap().rssi,
+            reset_reason: system::reset_reason().to_string(),
+            secs_since_fetch: since_fetch,
+            safe_mode: safe_mode::is_active(),
+            last_crash: crash::last(),
+        }
+    }
+
+    fn status_json(&self) -> String {
+        let wifi = self.wifi.lock().unwrap().clone();
+        StatusResponse {
+            wifi: wifi.state.to_string(),
+            ssid: wifi.ssid,
+            rssi: wifi.rssi,
+            weak_signal: wifi.weak,
+        }
+        .to_json()
+    }
+}
+
+/// Keeps the snapshots behind `/status`, `/api/map` and `/api/health`
+/// current.
+impl Subscriber for &SharedState {
+    fn on_event(&mut self, event: &Event) {
+        match event {
+            Event::MetarUpdated(snapshot) => {
+                if snapshot.error.is_none() {
+                    self.publish_fetch(Instant::now());
+                }
+                self.publish_map(snapshot.clone());
+            }
+            Event::WifiStateChanged(state) => self.publish_link(state),
+            Event::BrightnessChanged(_) => {}
+        }
+    }
+}
+
+/// Start the HTTP server on the station interface.
+///
+/// - `GET /` is a dashboard mirroring the map: each airport's color,
+///   category, wind, and observation time. It redirects to the form while
+///   setup mode is on.
+/// - `GET /map` draws the LEDs in their current colors, plotted by
+///   position when every airport has `lat` and `lon`; it polls
+///   `GET /api/map`, the same snapshot as JSON.
+/// - `GET /api/metars` has the report behind each LED as last fetched,
+///   raw observation text included.
+/// - `GET /status` reports the WiFi connection state and signal strength as
+///   JSON.
+/// - `GET /api/health` reports the firmware version, free and minimum free
+///   heap, WiFi RSSI, reset reason, and seconds since the last successful
+///   weather fetch as JSON, for scripts that power-cycle a stuck device.
+/// - `GET /api/logs` returns the latest log lines as plain text.
+/// - `GET /config` downloads the running config as TOML.
+/// - `POST /config` uploads a replacement. It is fully validated before being
+///   written to `/config.toml`, then picked up by the main loop's hot reload;
+///   a rejected upload leaves the running config untouched.
+/// - `GET /airports` is an editor for the airport list. `POST /airports`
+///   takes the new list as codes separated by commas or whitespace; once it
+///   validates, the main loop applies it live and stores it with the NVS
+///   config overrides.
+/// - `GET /settings` is a page for the live settings (lightning, wind
+///   coloring, wind threshold); `GET /api/settings` returns them as JSON and
+///   `POST /api/settings` takes a JSON object with any of them, which the
+///   main loop applies and stores with the NVS config overrides.
+/// - `POST /api/profile` switches to the `[[profiles]]` entry named in the
+///   body, or back to plain `[settings]` with `none`; the main loop applies
+///   it and stores the choice with the NVS config overrides.
+/// - `GET /update` is a page for uploading new firmware; `POST /update` takes
+///   the image as the request body, writes it to the inactive OTA slot, and
+///   reboots into it once it verifies.
+/// - `POST /api/update/check` looks for newer firmware at
+///   `settings.ota.manifest_url` and installs it.
+/// - `POST /api/test` shows a test pattern (`all-red`, `chase`, `rainbow`,
+///   `single N`, ...) in place of the weather for a while, to check the
+///   wiring; `stop` ends it early.
+/// - `POST /factory-reset` erases credentials and stored config, then reboots
+///   into the captive portal.
+/// - `POST /setup` brings up the setup AP next to the running map; while it is
+///   up, `GET /setup` serves the WiFi form, `GET /setup/status` reports how a
+///   submitted network is doing, and every other unknown path redirects to
+///   the form.
+///
+/// Pages from the origins in `settings.http.cors_origins` may call all of
+/// these from the browser; `OPTIONS` on any path answers their preflights.
+pub fn start(state: Arc<SharedState>) -> Result<EspHttpServer<'static>, EspError> {
+    let mut server = http::server(Some(SERVER_STACK_SIZE))?;
+
+    let map_state = state.clone();
+    server.fn_handler("/", Method::Get, move |req| -> Result<(), EspIOError> {
+        if map_state.setup_page().is_some() {
+            return http::redirect(req, "/setup");
+        }
+        let html = map_state.map.lock().unwrap().to_html(clock::unix_now());
+        http::html(req, &html)
+    })?;
+
+    // Static pages such as `/map`, gzipped into the image
+    http::serve_assets(&mut server)?;
+    http::handle_preflight(&mut server)?;
+
+    let preview_state = state.clone();
+    server.fn_handler("/api/map", Method::Get, move |req| -> Result<(), EspIOError> {
+        let json = preview_state.map.lock().unwrap().to_json();
+        http::json(req, &json)
+    })?;
+
+    let metars_state = state.clone();
+    server.fn_handler("/api/metars", Method::Get, move |req| -> Result<(), EspIOError> {
+        let json = metars_state.map.lock().unwrap().metars_json();
+        http::json(req, &json)
+    })?;
+
+    let status_state = state.clone();
+    server.fn_handler("/status", Method::Get, move |req| -> Result<(), EspIOError> {
+        let json = status_state.status_json();
+        http::json(req, &json)
+    })?;
+
+    server.fn_handler("/api/logs", Method::Get, |req| -> Result<(), EspIOError> {
+        respond(req, 200, &logging::recent())
+    })?;
+
+    server.fn_handler("/api/log-level", Method::Get, |req| -> Result<(), EspIOError> {
+        respond(req, 200, &logging::filter())
+    })?;
+
+    let log_level_state = state.clone();
+    server.fn_handler("/api/log-level", Method::Post, move |mut req| -> Result<(), EspIOError> {
+        if !log_level_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        let body = match read_body(&mut req, MAX_LOG_FILTER_SIZE)? {
+            Some(body) => body,
+            None => return respond(req, 413, "log filter too large"),
+        };
+        let filter = match std::str::from_utf8(&body).map(LogFilter::parse) {
+            Ok(Ok(filter)) => filter,
+            Ok(Err(e)) => return respond(req, 400, &e),
+            Err(_) => return respond(req, 400, "log filter must be UTF-8 text"),
+        };
+        // Until the next boot or a config reload that changes log_level
+        info!("Log level set over HTTP: {}", filter);
+        let text = format!("logging at {filter}");
+        logging::set_filter(filter);
+        respond(req, 200, &text)
+    })?;
+
+    let health_state = state.clone();
+    server.fn_handler("/api/health", Method::Get, move |req| -> Result<(), EspIOError> {
+        let json = health_state.health().to_json();
+        http::json(req, &json)
+    })?;
+
+    let export_state = state.clone();
+    server.fn_handler("/config", Method::Get, move |req| -> Result<(), EspIOError> {
+        let toml = export_state.config_toml.lock().unwrap().clone();
+        let mut resp = http::response(
+            req,
+            200,
+            &[
+                ("Content-Type", "application/toml"),
+                ("Content-Disposition", "attachment; filename=\"config.toml\""),
+            ],
+        )?;
+        resp.write_all(toml.as_bytes())?;
+        Ok(())
+    })?;
+
+    let upload_state = state.clone();
+    server.fn_handler("/config", Method::Post, move |mut req| -> Result<(), EspIOError> {
+        if !upload_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        let body = match read_body(&mut req, MAX_CONFIG_SIZE)? {
+            Some(body) => body,
+            None => return respond(req, 413, "config too large"),
+        };
+        let Ok(toml) = String::from_utf8(body) else {
+            return respond(req, 400, "config must be UTF-8 text");
+        };
+
+        let config = match Config::from_toml(&toml) {
+            Ok(config) => config,
+            Err(e) => return respond(req, 400, &e.to_string()),
+        };
+        if config.has_errors() {
+            let report: Vec<String> = config.diagnostics.iter().map(|d| d.to_string()).collect();
+            return respond(req, 422, &report.join("\n"));
+        }
+
+        match flash_fs::write_config(&toml) {
+            Ok(_) => {
+                info!("Config uploaded over HTTP ({} bytes)", toml.len());
+                respond(req, 200, "config saved; applying")
+            }
+            Err(e) => respond(req, 500, &e.to_string()),
+        }
+    })?;
+
+    let editor_state = state.clone();
+    server.fn_handler("/airports", Method::Get, move |req| -> Result<(), EspIOError> {
+        let html = editor_state.airport_editor.lock().unwrap().clone();
+        http::html(req, &html)
+    })?;
+
+    let edit_state = state.clone();
+    server.fn_handler("/airports", Method::Post, move |mut req| -> Result<(), EspIOError> {
+        if !edit_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        let body = match read_body(&mut req, MAX_AIRPORTS_SIZE)? {
+            Some(body) => body,
+            None => return respond(req, 413, "airport list too large"),
+        };
+        let Ok(text) = String::from_utf8(body) else {
+            return respond(req, 400, "airport list must be UTF-8 text");
+        };
+
+        // Check against the running config so a bad list is refused here
+        // rather than failing later in the main loop
+        let toml = edit_state.config_toml.lock().unwrap().clone();
+        let config = match Config::from_toml(&toml) {
+            Ok(config) => config,
+            Err(e) => return respond(req, 500, &e.to_string()),
+        };
+        let codes = match airport_editor::parse_codes(&text, &config) {
+            Ok(codes) => codes,
+            Err(e) => return respond(req, 400, &e),
+        };
+        match airport_editor::with_airports(&config, &codes) {
+            Ok(edited) if edited.has_errors() => {
+                let report: Vec<String> =
+                    edited.diagnostics.iter().map(|d| d.to_string()).collect();
+                return respond(req, 422, &report.join("\n"));
+            }
+            Ok(_) => {}
+            Err(e) => return respond(req, 422, &e.to_string()),
+        }
+
+        info!("Airport list edited over HTTP: {} LEDs", codes.len());
+        let leds = codes.len();
+        *edit_state.airport_edit.lock().unwrap() = Some(codes);
+        respond(req, 202, &format!("saved {leds} LEDs; applying"))
+    })?;
+
+    let page_state = state.clone();
+    server.fn_handler("/settings", Method::Get, move |req| -> Result<(), EspIOError> {
+        let html = page_state.settings_page.lock().unwrap().clone();
+        http::html(req, &html)
+    })?;
+
+    let settings_state = state.clone();
+    server.fn_handler("/api/settings", Method::Get, move |req| -> Result<(), EspIOError> {
+        let json = settings_state.live_settings.lock().unwrap().map(|s| s.to_json());
+        http::json(req, json.as_deref().unwrap_or("null"))
+    })?;
+
+    let change_state = state.clone();
+    server.fn_handler("/api/settings", Method::Post, move |mut req| -> Result<(), EspIOError> {
+        if !change_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        let body = match read_body(&mut req, MAX_SETTINGS_SIZE)? {
+            Some(body) => body,
+            None => return respond(req, 413, "settings too large"),
+        };
+        let patch = match std::str::from_utf8(&body).map(SettingsPatch::parse) {
+            Ok(Ok(patch)) => patch,
+            Ok(Err(e)) => return respond(req, 400, &e),
+            Err(_) => return respond(req, 400, "settings must be UTF-8 JSON"),
+        };
+        info!("Settings changed over HTTP: {:?}", patch);
+        *change_state.settings_edit.lock().unwrap() = Some(patch);
+        respond(req, 202, "settings saved; applying")
+    })?;
+
+    let profile_state = state.clone();
+    server.fn_handler("/api/profile", Method::Post, move |mut req| -> Result<(), EspIOError> {
+        if !profile_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        let body = match read_body(&mut req, MAX_PROFILE_SIZE)? {
+            Some(body) => body,
+            None => return respond(req, 413, "profile name too large"),
+        };
+        let name = match std::str::from_utf8(&body) {
+            Ok(name) => name.trim(),
+            Err(_) => return respond(req, 400, "profile name must be UTF-8 text"),
+        };
+        let profile = match name {
+            "" | "none" => None,
+            name if profile_state.profiles.lock().unwrap().iter().any(|p| p == name) => {
+                Some(name.to_string())
+            }
+            name => return respond(req, 404, &format!("no profile named {name}")),
+        };
+        info!("Profile switched over HTTP: {}", profile.as_deref().unwrap_or("none"));
+        let message = match &profile {
+            Some(name) => format!("switching to profile {name}"),
+            None => "switching to plain [settings]".to_string(),
+        };
+        *profile_state.profile_change.lock().unwrap() = Some(profile);
+        respond(req, 202, &message)
+    })?;
+
+    server.fn_handler("/update", Method::Get, |req| -> Result<(), EspIOError> {
+        http::html(req, &update_page(&ota::running_app()))
+    })?;
+
+    let update_state = state.clone();
+    server.fn_handler("/update", Method::Post, move |mut req| -> Result<(), EspIOError> {
+        if !update_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        if update_state.updating.swap(true, Ordering::Relaxed) {
+            return respond(req, 409, "another update is being installed");
+        }
+        let result = ota::install(&mut req, &ota::running_app(), None);
+        update_state.updating.store(false, Ordering::Relaxed);
+        match result {
+            Ok(update) => {
+                update_state.update_installed.store(true, Ordering::Relaxed);
+                respond(req, 200, &format!("firmware {} installed; rebooting", update.version))
+            }
+            Err(e @ ota::OtaError::Image(_)) => respond(req, 400, &e.to_string()),
+            Err(e) => {
+                warn!("Firmware update failed: {}", e);
+                respond(req, 500, &e.to_string())
+            }
+        }
+    })?;
+
+    let check_state = state.clone();
+    server.fn_handler("/api/update/check", Method::Post, move |req| -> Result<(), EspIOError> {
+        if !check_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        info!("Update check requested over HTTP");
+        check_state.update_check.store(true, Ordering::Relaxed);
+        respond(req, 202, "checking for an update; the device reboots if it installs one")
+    })?;
+
+    let refresh_state = state.clone();
+    server.fn_handler("/api/refresh", Method::Post, move |req| -> Result<(), EspIOError> {
+        if !refresh_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        match refresh_state.request_refresh(Instant::now()) {
+            Ok(()) => {
+                info!("Weather refresh requested over HTTP");
+                respond(req, 202, "fetching weather now")
+            }
+            Err(wait) => {
+                let secs = wait.as_secs().max(1).to_string();
+                let mut resp = http::response(
+                    req,
+                    429,
+                    &[("Content-Type", "text/plain"), ("Retry-After", &secs)],
+                )?;
+                let message = format!("weather was just fetched; try again in {secs}s");
+                resp.write_all(message.as_bytes())?;
+                Ok(())
+            }
+        }
+    })?;
+
+    let test_state = state.clone();
+    server.fn_handler("/api/test", Method::Post, move |mut req| -> Result<(), EspIOError> {
+        if !test_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        let body = match read_body(&mut req, MAX_TEST_SIZE)? {
+            Some(body) => body,
+            None => return respond(req, 413, "test pattern request too large"),
+        };
+        let num_leds = test_state.num_leds.load(Ordering::Relaxed);
+        let request = match std::str::from_utf8(&body) {
+            Ok(text) => match TestRequest::parse(text, num_leds) {
+                Ok(request) => request,
+                Err(e) => return respond(req, 400, &e),
+            },
+            Err(_) => return respond(req, 400, "test pattern must be UTF-8 text"),
+        };
+        let message = match request {
+            TestRequest::Start(_, duration) => {
+                format!("showing the test pattern for {}s", duration.as_secs())
+            }
+            TestRequest::Stop => "back to weather".to_string(),
+        };
+        info!("Test pattern requested over HTTP: {}", String::from_utf8_lossy(&body).trim());
+        *test_state.test_request.lock().unwrap() = Some(request);
+        respond(req, 202, &message)
+    })?;
+
+    let auth_state = state.clone();
+    server.fn_handler("/api/auth", Method::Post, move |mut req| -> Result<(), EspIOError> {
+        if !auth_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        let body = match read_body(&mut req, MAX_CREDENTIAL_SIZE)? {
+            Some(body) => body,
+            None => return respond(req, 413, "credential too large"),
+        };
+        let credential = match std::str::from_utf8(&body).map(Credential::parse) {
+            Ok(Ok(credential)) => credential,
+            Ok(Err(e)) => return respond(req, 400, &e.to_string()),
+            Err(_) => return respond(req, 400, "credential must be UTF-8 text"),
+        };
+        let message = match credential {
+            Credential::Token(_) => "token set; send it as a Bearer token",
+            Credential::Basic { .. } => "username and password set",
+        };
+        info!("HTTP credential changed over HTTP");
+        auth_state.set_credential(Some(credential.clone()));
+        *auth_state.credential_change.lock().unwrap() = Some(Some(credential));
+        respond(req, 200, message)
+    })?;
+
+    let unlock_state = state.clone();
+    server.fn_handler("/api/auth", Method::Delete, move |req| -> Result<(), EspIOError> {
+        if !unlock_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        info!("HTTP credential removed over HTTP");
+        unlock_state.set_credential(None);
+        *unlock_state.credential_change.lock().unwrap() = Some(None);
+        respond(req, 200, "credential removed; all endpoints are open")
+    })?;
+
+    let reset_state = state.clone();
+    server.fn_handler("/factory-reset", Method::Post, move |req| -> Result<(), EspIOError> {
+        if !reset_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        info!("Factory reset requested over HTTP");
+        reset_state.factory_reset.store(true, Ordering::Relaxed);
+        respond(req, 202, "factory reset scheduled; the device will reboot into setup mode")
+    })?;
+
+    let setup_state = state.clone();
+    server.fn_handler("/setup", Method::Post, move |req| -> Result<(), EspIOError> {
+        if !setup_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        info!("Setup mode requested over HTTP");
+        setup_state.setup_requested.store(true, Ordering::Relaxed);
+        let message = format!(
+            "setup mode starting; join the {} network",
+            provisioning::setup_ap_ssid()
+        );
+        respond(req, 202, &message)
+    })?;
+
+    let form_state = state.clone();
+    server.fn_handler("/setup", Method::Get, move |req| -> Result<(), EspIOError> {
+        match form_state.setup_page() {
+            Some(page) => http::html(req, &page.form.replace("{ERROR}", "")),
+            None => respond(req, 404, "setup mode is off; POST /setup to start it"),
+        }
+    })?;
+
+    let connect_state = state.clone();
+    server.fn_handler("/setup/connect", Method::Post, move |req| -> Result<(), EspIOError> {
+        if !connect_state.authorized(&req) {
+            return unauthorized(req);
+        }
+        match connect_state.setup_page() {
+            Some(page) => provisioning::handle_connect(
+                req,
+                &page.attempts,
+                &page.progress,
+                &page.form,
+                &page.progress_page,
+            ),
+            None => respond(req, 404, "setup mode is off"),
+        }
+    })?;
+
+    let progress_state = state.clone();
+    server.fn_handler("/setup/status", Method::Get, move |req| -> Result<(), EspIOError> {
+        match progress_state.setup_page() {
+            Some(page) => {
+                let json = page.progress.lock().unwrap().to_json();
+                http::json(req, &json)
+            }
+            None => respond(req, 404, "setup mode is off"),
+        }
+    })?;
+
+    // Registered last so the routes above match first
+    http::fallback(&mut server, move || state.setup_page().map(|_| "/setup".to_string()))?;
+
+    info!("HTTP server started");
+    Ok(server)
+}
@@ -1,18 +1,46 @@
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::hal::modem::Modem;
-use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_svc::ipv4::{
+    ClientConfiguration as IpClientConfiguration, ClientSettings, Configuration as IpConfiguration,
+    Mask, Subnet,
+};
+use esp_idf_svc::netif::{EspNetif, NetifConfiguration};
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sys::{self, esp, EspError, ESP_FAIL};
 use esp_idf_svc::wifi::{
-    AuthMethod, BlockingWifi, ClientConfiguration, Configuration, EspWifi,
+    AccessPointConfiguration, AuthMethod, BlockingWifi, ClientConfiguration, Configuration,
+    EspWifi, ScanMethod, ScanSortMethod,
+};
+use led_sectional_core::networks::{
+    Credentials, NetworkList, ScannedNetwork, StaticIp, WifiAuth, DEFAULT_HOSTNAME,
 };
+use led_sectional_core::seal;
 use log::{info, warn};
+use std::net::Ipv4Addr;
+
+use crate::provisioning;
+use crate::storage::Storage;
 
 const NVS_NAMESPACE: &str = "wifi";
+// Single network stored by older firmware; migrated into the list on load
 const NVS_KEY_SSID: &str = "ssid";
 const NVS_KEY_PASS: &str = "pass";
-const CONNECT_TIMEOUT_SECS: u64 = 60;
+// Plaintext list stored by older firmware; sealed on load
+const NVS_KEY_NETWORKS: &str = "nets";
+/// The saved network list, sealed with the device key (see `seal`).
+const NVS_KEY_SEALED_NETWORKS: &str = "nets_sealed";
+/// Set to start the setup portal on the next boot regardless of `[wifi]`.
+const NVS_KEY_FORCE_SETUP: &str = "setup";
 
 pub struct WifiManager {
     wifi: BlockingWifi<EspWifi<'static>>,
+    nvs: EspDefaultNvsPartition,
+    static_ip: Option<StaticIp>,
+    /// The addressing the STA netif was last set up with.
+    applied_ip: Option<StaticIp>,
+    hostname: String,
+    /// Setup AP kept up next to the station while reprovisioning.
+    setup_ap: Option<AccessPointConfiguration>,
 }
 
 impl WifiManager {
@@ -20,44 +48,152 @@ impl WifiManager {
         modem: Modem,
         sysloop: EspSystemEventLoop,
         nvs: EspDefaultNvsPartition,
-    ) -> Result<Self, esp_idf_svc::sys::EspError> {
-        let wifi = EspWifi::new(modem, sysloop.clone(), Some(nvs))?;
+    ) -> Result<Self, EspError> {
+        let wifi = EspWifi::new(modem, sysloop.clone(), Some(nvs.clone()))?;
+        keep_driver_config_in_ram()?;
         let wifi = BlockingWifi::wrap(wifi, sysloop)?;
-        Ok(Self { wifi })
+        Ok(Self {
+            wifi,
+            nvs,
+            static_ip: None,
+            applied_ip: None,
+            hostname: DEFAULT_HOSTNAME.to_string(),
+            setup_ap: None,
+        })
     }
 
-    pub fn connect_sta(&mut self, ssid: &str, password: &str) -> Result<(), esp_idf_svc::sys::EspError> {
-        info!("Connecting to WiFi SSID: {}", ssid);
+    /// Name sent with DHCP requests from the next connection on.
+    pub fn set_hostname(&mut self, hostname: &str) {
+        self.hostname = hostname.to_string();
+    }
 
-        let auth = if password.is_empty() {
-            AuthMethod::None
-        } else {
-            AuthMethod::WPA2Personal
+    /// Use a fixed address instead of DHCP from the next connection on, or
+    /// go back to DHCP with `None`.
+    pub fn set_static_ip(&mut self, static_ip: Option<StaticIp>) {
+        self.static_ip = static_ip;
+    }
+
+    /// Start joining `network` and return without waiting. Any current
+    /// association is dropped first; the outcome arrives as events (see
+    /// `wifi_events`).
+    pub fn begin_connect(&mut self, network: &Credentials) -> Result<(), EspError> {
+        info!("Connecting to WiFi SSID: {} ({:?})", network.ssid, network.auth_method());
+        if let Some(bssid) = network.bssid {
+            info!("Pinned to access point {}", bssid);
+        }
+
+        let client = client_configuration(network)?;
+        let config = match &self.setup_ap {
+            Some(ap) => Configuration::Mixed(client, ap.clone()),
+            None => Configuration::Client(client),
         };
 
-        let config = Configuration::Client(ClientConfiguration {
-            ssid: ssid.try_into().unwrap_or_default(),
-            password: password.try_into().unwrap_or_default(),
-            auth_method: auth,
-            ..Default::default()
-        });
+        let wifi = self.wifi.wifi_mut();
+        if wifi.is_started()? {
+            let _ = wifi.disconnect();
+        }
+        self.apply_ip_config()?;
+        let wifi = self.wifi.wifi_mut();
+        // A swapped-in netif starts with the default name, so set it every time
+        wifi.sta_netif_mut().set_hostname(&self.hostname)?;
+        wifi.set_configuration(&config)?;
+        if !wifi.is_started()? {
+            wifi.start()?;
+        }
+        wifi.connect()
+    }
+
+    /// Persist a connection to `ssid`: save `trial` when it was a network
+    /// from the setup form, and try `ssid` first on the next boot.
+    pub fn save_connected(&self, ssid: &str, trial: Option<Credentials>) {
+        if let Some(network) = trial {
+            if let Err(e) = store_credentials(self.nvs.clone(), network) {
+                warn!("Failed to save WiFi credentials for {}: {:?}", ssid, e);
+            }
+        }
+        if let Err(e) = remember_connected(self.nvs.clone(), ssid) {
+            warn!("Failed to save last connected network: {:?}", e);
+        }
+    }
+
+    /// Swap in a STA netif with the wanted addressing if it changed. DHCP is
+    /// the driver's default, so nothing is swapped until a static IP is set.
+    fn apply_ip_config(&mut self) -> Result<(), EspError> {
+        if self.static_ip == self.applied_ip {
+            return Ok(());
+        }
+        let ip_configuration = match self.static_ip {
+            Some(fixed) => {
+                info!(
+                    "Using static IP {}/{} via {}, DNS {}",
+                    fixed.ip, fixed.prefix_len, fixed.gateway, fixed.dns
+                );
+                IpConfiguration::Client(IpClientConfiguration::Fixed(ClientSettings {
+                    ip: fixed.ip,
+                    subnet: Subnet {
+                        gateway: fixed.gateway,
+                        mask: Mask(fixed.prefix_len),
+                    },
+                    dns: Some(fixed.dns),
+                    secondary_dns: None,
+                }))
+            }
+            None => {
+                info!("Using DHCP");
+                IpConfiguration::Client(IpClientConfiguration::default())
+            }
+        };
+        let netif = EspNetif::new_with_conf(&NetifConfiguration {
+            ip_configuration: Some(ip_configuration),
+            ..NetifConfiguration::wifi_default_client()
+        })?;
+        self.wifi.wifi_mut().swap_netif_sta(netif)?;
+        self.applied_ip = self.static_ip;
+        Ok(())
+    }
 
-        self.wifi.set_configuration(&config)?;
-        self.wifi.start()?;
-        self.wifi.connect()?;
-        self.wifi.wait_netif_up()?;
+    /// Nearby access points, strongest first.
+    pub fn scan(&mut self) -> Vec<ScannedNetwork> {
+        provisioning::scan_networks(&mut self.wifi)
+    }
 
-        let ip_info = self.wifi.wifi().sta_netif().get_ip_info()?;
-        info!("WiFi connected. IP: {}", ip_info.ip);
+    /// Bring up `ap` alongside the station connection. Returns the AP's
+    /// address. The station may briefly reconnect while the mode changes.
+    pub fn start_setup_ap(&mut self, ap: AccessPointConfiguration) -> Result<Ipv4Addr, EspError> {
+        let client = self.current_client()?;
+        self.wifi.set_configuration(&Configuration::Mixed(client, ap.clone()))?;
+        self.setup_ap = Some(ap);
+        Ok(self.wifi.wifi().ap_netif().get_ip_info()?.ip)
+    }
 
+    /// Go back to station only.
+    pub fn stop_setup_ap(&mut self) -> Result<(), EspError> {
+        if self.setup_ap.take().is_some() {
+            let client = self.current_client()?;
+            self.wifi.set_configuration(&Configuration::Client(client))?;
+        }
         Ok(())
     }
 
-    pub fn is_connected(&self) -> bool {
-        self.wifi.is_connected().unwrap_or(false)
+    fn current_client(&self) -> Result<ClientConfiguration, EspError> {
+        Ok(match self.wifi.get_configuration()? {
+            Configuration::Client(client) | Configuration::Mixed(client, _) => client,
+            _ => ClientConfiguration::default(),
+        })
     }
 
-    pub fn disconnect(&mut self) -> Result<(), esp_idf_svc::sys::EspError> {
+    /// Signal strength of the connected access point in dBm, or None when
+    /// not connected.
+    pub fn rssi(&self) -> Option<i8> {
+        let mut info = sys::wifi_ap_record_t::default();
+        // SAFETY: `info` is a valid, writable record for the duration of the
+        // call; the driver only fills it in.
+        esp!(unsafe { sys::esp_wifi_sta_get_ap_info(&mut info) })
+            .ok()
+            .map(|()| info.rssi)
+    }
+
+    pub fn disconnect(&mut self) -> Result<(), EspError> {
         self.wifi.disconnect()?;
         Ok(())
     }
@@ -68,41 +204,203 @@ impl WifiManager {
     }
 }
 
-/// Store WiFi credentials in NVS.
+/// Station settings for `network`. For WPA2-Enterprise this also hands the
+/// login to the EAP client, so call it right before connecting.
+pub fn client_configuration(network: &Credentials) -> Result<ClientConfiguration, EspError> {
+    let (auth_method, password) = match network.auth_method() {
+        WifiAuth::Open => (AuthMethod::None, ""),
+        WifiAuth::Wpa3Personal => (AuthMethod::WPA3Personal, network.password.as_str()),
+        // The enterprise password goes to the EAP client, not the 4-way handshake
+        WifiAuth::Wpa2Enterprise => (AuthMethod::WPA2Enterprise, ""),
+        WifiAuth::Auto | WifiAuth::Wpa2Personal => {
+            (AuthMethod::WPA2Personal, network.password.as_str())
+        }
+    };
+
+    if auth_method == AuthMethod::WPA2Enterprise {
+        set_enterprise_login(network)?;
+    } else {
+        // SAFETY: only clears a flag in the WiFi driver; harmless when
+        // enterprise mode was never enabled.
+        esp!(unsafe { sys::esp_wifi_sta_enterprise_disable() })?;
+    }
+
+    Ok(ClientConfiguration {
+        ssid: network.ssid.as_str().try_into().unwrap_or_default(),
+        bssid: network.bssid.map(|bssid| bssid.0),
+        password: password.try_into().unwrap_or_default(),
+        auth_method,
+        // The default fast scan joins the first node it hears, which on a
+        // mesh is often the farthest; scan every channel and take the
+        // strongest. The probes name the SSID, so hidden networks answer
+        // too, on whatever channel they're on.
+        scan_method: ScanMethod::CompleteScan(ScanSortMethod::Signal),
+        ..Default::default()
+    })
+}
+
+/// Hand the WPA2-Enterprise login to the EAP client and enable it for the next
+/// connection. The outer identity falls back to the username.
+fn set_enterprise_login(network: &Credentials) -> Result<(), EspError> {
+    let username = network.username.as_deref().unwrap_or_default();
+    let identity = network.identity.as_deref().unwrap_or(username);
+    let password = network.password.as_str();
+    // SAFETY: the EAP client copies each buffer before returning, and the
+    // lengths passed match the slices, so nothing is read out of bounds or
+    // after the borrowed strings go away.
+    unsafe {
+        esp!(sys::esp_eap_client_set_identity(identity.as_ptr(), identity.len() as i32))?;
+        esp!(sys::esp_eap_client_set_username(username.as_ptr(), username.len() as i32))?;
+        esp!(sys::esp_eap_client_set_password(password.as_ptr(), password.len() as i32))?;
+        esp!(sys::esp_wifi_sta_enterprise_enable())?;
+    }
+    Ok(())
+}
+
+/// Add a network to the saved list, keeping the others. Saving an SSID that
+/// is already in the list replaces its password and login.
 pub fn store_credentials(
     nvs_partition: EspDefaultNvsPartition,
-    ssid: &str,
-    password: &str,
-) -> Result<(), esp_idf_svc::sys::EspError> {
-    let mut nvs = EspNvs::new(nvs_partition, NVS_NAMESPACE, true)?;
-    nvs.set_str(NVS_KEY_SSID, ssid)?;
-    nvs.set_str(NVS_KEY_PASS, password)?;
-    info!("WiFi credentials stored in NVS");
+    credentials: Credentials,
+) -> Result<(), EspError> {
+    let ssid = credentials.ssid.clone();
+    let mut networks = load_networks(nvs_partition.clone())?;
+    if networks.add(credentials) {
+        save_networks(nvs_partition, &networks)?;
+    }
+    info!("WiFi credentials for {} stored in NVS ({} networks saved)", ssid, networks.len());
     Ok(())
 }
 
-/// Load WiFi credentials from NVS. Returns None if not found.
-pub fn load_credentials(
-    nvs_partition: EspDefaultNvsPartition,
-) -> Result<Option<(String, String)>, esp_idf_svc::sys::EspError> {
-    let nvs = EspNvs::new(nvs_partition, NVS_NAMESPACE, false)?;
+/// Record `ssid` as the network to try first next time.
+fn remember_connected(nvs_partition: EspDefaultNvsPartition, ssid: &str) -> Result<(), EspError> {
+    let mut networks = load_networks(nvs_partition.clone())?;
+    if networks.mark_connected(ssid) {
+        save_networks(nvs_partition, &networks)?;
+    }
+    Ok(())
+}
+
+/// Erase all stored WiFi credentials so the next boot starts the captive portal.
+pub fn clear_credentials(nvs_partition: EspDefaultNvsPartition) -> Result<(), EspError> {
+    let mut nvs = Storage::open(nvs_partition, NVS_NAMESPACE)?;
+    nvs.remove(NVS_KEY_SEALED_NETWORKS)?;
+    nvs.remove(NVS_KEY_NETWORKS)?;
+    nvs.remove(NVS_KEY_SSID)?;
+    nvs.remove(NVS_KEY_PASS)?;
+    info!("WiFi credentials erased from NVS");
+    Ok(())
+}
+
+/// Make the next boot start the setup portal even if a network is configured.
+pub fn request_setup_on_boot(nvs_partition: EspDefaultNvsPartition) -> Result<(), EspError> {
+    let mut nvs = Storage::open(nvs_partition, NVS_NAMESPACE)?;
+    nvs.set_u8(NVS_KEY_FORCE_SETUP, 1)?;
+    Ok(())
+}
 
-    let mut ssid_buf = [0u8; 64];
-    let mut pass_buf = [0u8; 128];
+/// True once after `request_setup_on_boot`.
+pub fn take_setup_request(nvs_partition: EspDefaultNvsPartition) -> Result<bool, EspError> {
+    let mut nvs = Storage::open(nvs_partition, NVS_NAMESPACE)?;
+    nvs.remove(NVS_KEY_FORCE_SETUP)
+}
 
-    let ssid = match nvs.get_str(NVS_KEY_SSID, &mut ssid_buf)? {
-        Some(s) => s.to_string(),
-        None => {
-            warn!("No WiFi SSID found in NVS");
-            return Ok(None);
+/// Load the saved networks from NVS. Lists stored in plaintext by older
+/// firmware, including a single network, are sealed and the plaintext
+/// erased. Returns an empty list if none are saved.
+pub fn load_networks(nvs_partition: EspDefaultNvsPartition) -> Result<NetworkList, EspError> {
+    let nvs = Storage::open(nvs_partition.clone(), NVS_NAMESPACE)?;
+
+    if let Some(bytes) = nvs.get_blob(NVS_KEY_SEALED_NETWORKS)? {
+        let parsed = seal::open(&device_key(), &bytes)
+            .and_then(|toml| NetworkList::from_toml(&toml))
+            .map_err(|e| e.to_string());
+        match parsed {
+            Ok(networks) => {
+                info!("Loaded {} saved WiFi networks from NVS", networks.len());
+                return Ok(networks);
+            }
+            Err(e) => warn!("Saved WiFi networks are unreadable ({}); ignoring", e),
         }
-    };
+    }
 
-    let password = match nvs.get_str(NVS_KEY_PASS, &mut pass_buf)? {
-        Some(s) => s.to_string(),
-        None => String::new(),
-    };
+    let networks = load_plaintext_networks(&nvs)?;
+    if !networks.is_empty() {
+        drop(nvs);
+        save_networks(nvs_partition, &networks)?;
+        info!("Sealed {} WiFi networks saved by older firmware", networks.len());
+    }
+    Ok(networks)
+}
 
-    info!("Loaded WiFi credentials from NVS for SSID: {}", ssid);
-    Ok(Some((ssid, password)))
+/// The list or single network older firmware stored in plaintext.
+fn load_plaintext_networks(nvs: &Storage) -> Result<NetworkList, EspError> {
+    if let Some(bytes) = nvs.get_blob(NVS_KEY_NETWORKS)? {
+        let parsed = std::str::from_utf8(&bytes)
+            .map_err(|e| e.to_string())
+            .and_then(|s| NetworkList::from_toml(s).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(networks) => return Ok(networks),
+            Err(e) => warn!("Plaintext WiFi networks are unreadable ({}); ignoring", e),
+        }
+    }
+
+    let mut networks = NetworkList::default();
+    match nvs.get_str(NVS_KEY_SSID)? {
+        Some(ssid) => {
+            let password = nvs.get_str(NVS_KEY_PASS)?.unwrap_or_default();
+            info!("Loaded WiFi credentials from NVS for SSID: {}", ssid);
+            networks.add(Credentials::new(ssid, password));
+        }
+        None => warn!("No WiFi networks found in NVS"),
+    }
+    Ok(networks)
+}
+
+/// Stop the WiFi driver persisting its configuration, which would keep a
+/// plaintext copy of the password in its own NVS namespace, and erase any
+/// copy an earlier run (or BLE provisioning) left there. Call right after
+/// creating the driver.
+pub fn keep_driver_config_in_ram() -> Result<(), EspError> {
+    // SAFETY: both only touch the driver's settings and need it initialized,
+    // which EspWifi::new has done; nothing is connected yet.
+    unsafe {
+        esp!(sys::esp_wifi_restore())?;
+        esp!(sys::esp_wifi_set_storage(sys::wifi_storage_t_WIFI_STORAGE_RAM))?;
+    }
+    Ok(())
+}
+
+/// The key saved networks are sealed with, derived from the eFuse MAC so it
+/// is the same on every boot and differs between devices.
+pub fn device_key() -> [u8; 32] {
+    let mut mac = [0u8; 6];
+    // SAFETY: `mac` is the six-byte buffer the call writes the base MAC to.
+    if let Err(e) = esp!(unsafe { sys::esp_efuse_mac_get_default(mac.as_mut_ptr()) }) {
+        warn!("Failed to read eFuse MAC: {:?}", e);
+    }
+    seal::device_key(&mac)
+}
+
+/// Replace the saved list, sealed, dropping the plaintext keys it supersedes.
+fn save_networks(
+    nvs_partition: EspDefaultNvsPartition,
+    networks: &NetworkList,
+) -> Result<(), EspError> {
+    let toml = networks.to_toml().map_err(|e| {
+        warn!("Failed to serialize WiFi networks: {}", e);
+        EspError::from_infallible::<ESP_FAIL>()
+    })?;
+    let mut nonce = [0u8; seal::NONCE_LEN];
+    // SAFETY: fills exactly `nonce.len()` bytes of the live buffer from the
+    // hardware RNG.
+    unsafe { sys::esp_fill_random(nonce.as_mut_ptr() as *mut core::ffi::c_void, nonce.len()) };
+    let sealed = seal::seal(&device_key(), nonce, &toml);
+
+    let mut nvs = Storage::open(nvs_partition, NVS_NAMESPACE)?;
+    nvs.set_blob(NVS_KEY_SEALED_NETWORKS, &sealed)?;
+    nvs.remove(NVS_KEY_NETWORKS)?;
+    nvs.remove(NVS_KEY_SSID)?;
+    nvs.remove(NVS_KEY_PASS)?;
+    Ok(())
 }
@@ -0,0 +1,104 @@
+use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::sys::{self, esp, EspError};
+use led_sectional_core::wifi_link::LinkEvent;
+use std::ffi::c_void;
+use std::net::Ipv4Addr;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Station events from the system event loop, queued for the main loop to
+/// feed into its `WifiLink`. The handlers only forward; nothing blocks on the
+/// event loop task. Unsubscribes when dropped.
+pub struct WifiEvents {
+    events: Receiver<LinkEvent>,
+    sender: Box<Sender<LinkEvent>>,
+    handlers: Vec<(sys::esp_event_base_t, i32, sys::esp_event_handler_instance_t)>,
+    _sysloop: EspSystemEventLoop,
+}
+
+impl WifiEvents {
+    /// Subscribe to station associations and disconnects, and to got-IP and
+    /// lost-IP from the IP stack.
+    pub fn subscribe(sysloop: EspSystemEventLoop) -> Result<Self, EspError> {
+        let (tx, rx) = mpsc::channel();
+        let mut events = Self {
+            events: rx,
+            sender: Box::new(tx),
+            handlers: Vec::new(),
+            _sysloop: sysloop,
+        };
+        // SAFETY: reading the event base statics the IDF defines.
+        let (wifi_base, ip_base) = unsafe { (sys::WIFI_EVENT, sys::IP_EVENT) };
+        events.register(wifi_base, sys::wifi_event_t_WIFI_EVENT_STA_CONNECTED as i32)?;
+        events.register(wifi_base, sys::wifi_event_t_WIFI_EVENT_STA_DISCONNECTED as i32)?;
+        events.register(ip_base, sys::ip_event_t_IP_EVENT_STA_GOT_IP as i32)?;
+        events.register(ip_base, sys::ip_event_t_IP_EVENT_STA_LOST_IP as i32)?;
+        Ok(events)
+    }
+
+    /// Events received since the last call, oldest first.
+    pub fn drain(&self) -> impl Iterator<Item = LinkEvent> + '_ {
+        self.events.try_iter()
+    }
+
+    fn register(&mut self, base: sys::esp_event_base_t, id: i32) -> Result<(), EspError> {
+        let mut handler: sys::esp_event_handler_instance_t = std::ptr::null_mut();
+        // SAFETY: `sender` is boxed so its address is stable, and it outlives
+        // the registration, which Drop removes before freeing it.
+        esp!(unsafe {
+            sys::esp_event_handler_instance_register(
+                base,
+                id,
+                Some(on_event),
+                &*self.sender as *const Sender<LinkEvent> as *mut c_void,
+                &mut handler,
+            )
+        })?;
+        self.handlers.push((base, id, handler));
+        Ok(())
+    }
+}
+
+impl Drop for WifiEvents {
+    fn drop(&mut self) {
+        for (base, id, handler) in self.handlers.drain(..) {
+            // SAFETY: unregistering an instance registered in `subscribe`
+            // stops further calls into `on_event` before `sender` is freed.
+            unsafe { sys::esp_event_handler_instance_unregister(base, id, handler) };
+        }
+    }
+}
+
+/// Runs on the event loop task.
+unsafe extern "C" fn on_event(
+    arg: *mut c_void,
+    base: sys::esp_event_base_t,
+    id: i32,
+    data: *mut c_void,
+) {
+    // SAFETY: `arg` is the boxed sender registered with this handler, which
+    // stays alive until the handler is unregistered.
+    let sender = unsafe { &*(arg as *const Sender<LinkEvent>) };
+    // SAFETY: reading the event base statics the IDF defines.
+    let (wifi_base, ip_base) = unsafe { (sys::WIFI_EVENT, sys::IP_EVENT) };
+    let event = if base == wifi_base && id as u32 == sys::wifi_event_t_WIFI_EVENT_STA_CONNECTED {
+        LinkEvent::Associated
+    } else if base == wifi_base {
+        // SAFETY: the other WiFi event subscribed to is STA_DISCONNECTED,
+        // whose data is a wifi_event_sta_disconnected_t owned by the event
+        // loop for the duration of the call.
+        let disconnected = unsafe { &*(data as *const sys::wifi_event_sta_disconnected_t) };
+        LinkEvent::Disconnected {
+            reason: disconnected.reason as u16,
+        }
+    } else if base == ip_base && id as u32 == sys::ip_event_t_IP_EVENT_STA_GOT_IP {
+        // SAFETY: STA_GOT_IP's data is an ip_event_got_ip_t owned by the
+        // event loop for the duration of the call.
+        let got_ip = unsafe { &*(data as *const sys::ip_event_got_ip_t) };
+        // lwIP keeps the address in network byte order
+        LinkEvent::GotIp(Ipv4Addr::from(u32::from_be(got_ip.ip_info.ip.addr)))
+    } else {
+        LinkEvent::LostIp
+    };
+    // Only fails once the main loop dropped the receiver
+    let _ = sender.send(event);
+}
@@ -0,0 +1,182 @@
+use esp_idf_svc::sys::{self, esp, EspError};
+use led_sectional_core::chip::Chip;
+use led_sectional_core::networks::Credentials;
+use log::{info, warn};
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::SyncSender;
+
+use crate::provisioning::Attempt;
+
+/// Shown to the router during pairing.
+const DEVICE_NAME: &str = "LED Sectional";
+
+/// State the WPS event handler reaches through its `arg` pointer.
+struct Shared {
+    attempts: SyncSender<Attempt>,
+    /// Pairing is running; the driver gives up on its own after two minutes.
+    active: AtomicBool,
+}
+
+/// WPS push-button pairing while the captive portal runs. [`Wps::pair`]
+/// starts a two-minute window in which pressing the router's WPS button hands
+/// over its credentials; they go down the same `attempts` channel as the
+/// form's, so they're tested and saved the same way. Stops when dropped.
+pub struct Wps {
+    shared: Box<Shared>,
+    handler: sys::esp_event_handler_instance_t,
+}
+
+impl Wps {
+    /// Listen for WPS results. Nothing is broadcast until [`pair`](Self::pair).
+    pub fn new(attempts: SyncSender<Attempt>) -> Result<Self, EspError> {
+        let shared = Box::new(Shared {
+            attempts,
+            active: AtomicBool::new(false),
+        });
+        let mut handler: sys::esp_event_handler_instance_t = std::ptr::null_mut();
+        // SAFETY: `shared` is boxed so its address is stable, and it outlives
+        // the registration, which Drop removes before freeing it.
+        esp!(unsafe {
+            sys::esp_event_handler_instance_register(
+                sys::WIFI_EVENT,
+                sys::ESP_EVENT_ANY_ID,
+                Some(on_event),
+                &*shared as *const Shared as *mut c_void,
+                &mut handler,
+            )
+        })?;
+        Ok(Self { shared, handler })
+    }
+
+    /// Start a pairing window, unless one is already open. WiFi must be
+    /// started in station or AP+STA mode.
+    pub fn pair(&self) -> Result<(), EspError> {
+        if self.shared.active.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let mut config = sys::esp_wps_config_t {
+            wps_type: sys::wps_type_WPS_TYPE_PBC,
+            ..Default::default()
+        };
+        let info = &mut config.factory_info;
+        copy_name(&mut info.manufacturer, "ESPRESSIF");
+        copy_name(&mut info.model_number, Chip::TARGET.name());
+        copy_name(&mut info.model_name, DEVICE_NAME);
+        copy_name(&mut info.device_name, DEVICE_NAME);
+        if let Err(e) = start(&config) {
+            self.shared.active.store(false, Ordering::Relaxed);
+            disable();
+            return Err(e);
+        }
+        info!("WPS pairing started; press the WPS button on the router within 2 minutes");
+        Ok(())
+    }
+}
+
+impl Drop for Wps {
+    fn drop(&mut self) {
+        if self.shared.active.load(Ordering::Relaxed) {
+            disable();
+        }
+        // SAFETY: unregistering the instance registered in `new` stops
+        // further calls into `on_event` before `shared` is freed.
+        unsafe {
+            sys::esp_event_handler_instance_unregister(
+                sys::WIFI_EVENT,
+                sys::ESP_EVENT_ANY_ID,
+                self.handler,
+            );
+        }
+    }
+}
+
+/// Copy `name` into a NUL-terminated C string field, truncating to fit.
+fn copy_name<const N: usize>(field: &mut [core::ffi::c_char; N], name: &str) {
+    for (dst, src) in field.iter_mut().take(N - 1).zip(name.bytes()) {
+        *dst = core::ffi::c_char::from_ne_bytes([src]);
+    }
+}
+
+fn start(config: &sys::esp_wps_config_t) -> Result<(), EspError> {
+    // SAFETY: `config` is fully initialized and copied by the driver, and
+    // starting only follows a successful enable, as the API requires.
+    unsafe {
+        esp!(sys::esp_wifi_wps_enable(config))?;
+        esp!(sys::esp_wifi_wps_start(0))
+    }
+}
+
+fn disable() {
+    // SAFETY: disabling is allowed whether or not WPS is running.
+    if let Err(e) = esp!(unsafe { sys::esp_wifi_wps_disable() }) {
+        warn!("Failed to stop WPS: {:?}", e);
+    }
+}
+
+/// The network WPS just configured, if it left one in the station config.
+fn configured_network() -> Option<Credentials> {
+    let mut config = sys::wifi_config_t::default();
+    // SAFETY: `config` is a valid, writable union for the duration of the
+    // call, which fills in its station variant.
+    esp!(unsafe { sys::esp_wifi_get_config(sys::wifi_interface_t_WIFI_IF_STA, &mut config) })
+        .ok()?;
+    // SAFETY: the STA interface's config is the `sta` variant.
+    let sta = unsafe { &config.sta };
+    Credentials::from_raw(&sta.ssid, &sta.password)
+}
+
+/// Runs on the event loop task.
+unsafe extern "C" fn on_event(
+    arg: *mut c_void,
+    _base: sys::esp_event_base_t,
+    id: i32,
+    data: *mut c_void,
+) {
+    // SAFETY: `arg` is the boxed `Shared` registered with this handler,
+    // which stays alive until the handler is unregistered.
+    let shared = unsafe { &*(arg as *const Shared) };
+    let credentials = match id as u32 {
+        sys::wifi_event_t_WIFI_EVENT_STA_WPS_ER_SUCCESS => {
+            // No list when the router sent a single network, which the
+            // driver has already put in the station config
+            let sent = if data.is_null() {
+                None
+            } else {
+                // SAFETY: non-null data for this event is a
+                // wifi_event_sta_wps_er_success_t owned by the event loop for
+                // the duration of the call.
+                let success = unsafe { &*(data as *const sys::wifi_event_sta_wps_er_success_t) };
+                let count = (success.ap_cred_cnt as usize).min(success.ap_cred.len());
+                success.ap_cred[..count]
+                    .first()
+                    .and_then(|cred| Credentials::from_raw(&cred.ssid, &cred.passphrase))
+            };
+            sent.or_else(configured_network)
+        }
+        sys::wifi_event_t_WIFI_EVENT_STA_WPS_ER_FAILED => {
+            warn!("WPS pairing failed; quick-press the setup button to try again");
+            None
+        }
+        sys::wifi_event_t_WIFI_EVENT_STA_WPS_ER_TIMEOUT => {
+            warn!("WPS pairing timed out; quick-press the setup button to try again");
+            None
+        }
+        _ => return,
+    };
+    disable();
+    shared.active.store(false, Ordering::Relaxed);
+
+    let Some(credentials) = credentials else {
+        return;
+    };
+    info!("WPS received credentials for SSID: {}", credentials.ssid);
+    // The portal loop logs the outcome and shows it on the progress page
+    let attempt = Attempt {
+        credentials,
+        map: None,
+    };
+    if shared.attempts.try_send(attempt).is_err() {
+        warn!("Ignoring WPS credentials while another network is tried");
+    }
+}